@@ -0,0 +1,110 @@
+//! Baselines for the clone queue, workspace status, and the worktree store.
+//!
+//! Run with `cargo bench --features bench`. Workspaces are generated under a
+//! temp dir (see `meta_git_lib::synthetic`), and `META_DATA_DIR` points there
+//! so the store and status index of the machine are left alone.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use meta_git_lib::clone_queue::{CloneQueue, SchedulingPolicy};
+use meta_git_lib::synthetic;
+use meta_git_lib::worktree::store::{store_add, store_entries_for, store_remove_batch};
+use std::path::Path;
+
+fn data_dir(tmp: &Path) {
+    std::env::set_var("META_DATA_DIR", tmp.join("meta-data"));
+}
+
+fn clone_queue_scheduling(c: &mut Criterion) {
+    let mut group = c.benchmark_group("clone_queue_scheduling");
+    let tasks = synthetic::clone_tasks(Path::new("/bench/ws"), 2000);
+    let policies = [
+        ("lifo", SchedulingPolicy::Lifo),
+        ("fifo", SchedulingPolicy::Fifo),
+        ("shallowest_first", SchedulingPolicy::ShallowestFirst),
+        (
+            "priority_tags",
+            SchedulingPolicy::PriorityTags(vec!["critical".to_string()]),
+        ),
+    ];
+    for (name, policy) in policies {
+        group.bench_function(BenchmarkId::new("push_and_drain", name), |b| {
+            b.iter_batched(
+                || CloneQueue::new(None, None).with_scheduling(policy.clone()),
+                |queue| {
+                    for task in &tasks {
+                        queue.push(task.clone());
+                    }
+                    while queue.take_one().is_some() {}
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn workspace_status(c: &mut Criterion) {
+    let mut group = c.benchmark_group("workspace_status");
+    group.sample_size(10);
+    let tmp = tempfile::tempdir().unwrap();
+    data_dir(tmp.path());
+    let ws = synthetic::generate(&tmp.path().join("ws"), 50, 20).unwrap();
+    for i in (0..ws.repos.len()).step_by(10) {
+        ws.make_dirty(i).unwrap();
+    }
+    for incremental in [false, true] {
+        let request = serde_json::json!({
+            "version": 1, "op": "status",
+            "params": {"meta_dir": ws.root, "incremental": incremental},
+        })
+        .to_string();
+        let name = if incremental { "incremental" } else { "full" };
+        group.bench_function(BenchmarkId::new("50_repos", name), |b| {
+            b.iter(|| meta_git_lib::api::execute(&request))
+        });
+    }
+    group.finish();
+}
+
+fn worktree_store(c: &mut Criterion) {
+    let mut group = c.benchmark_group("worktree_store");
+    let tmp = tempfile::tempdir().unwrap();
+    data_dir(tmp.path());
+    let project = tmp.path().join("ws");
+    std::fs::create_dir_all(&project).unwrap();
+    let project = project.canonicalize().unwrap();
+    let entries = synthetic::store_entries(
+        &tmp.path().join("worktrees"),
+        &project.to_string_lossy(),
+        500,
+        5,
+    );
+    let keys: Vec<String> = entries
+        .iter()
+        .map(|(path, _)| path.to_string_lossy().into_owned())
+        .collect();
+
+    group.bench_function("add_500", |b| {
+        b.iter_batched(
+            || store_remove_batch(&keys).unwrap(),
+            |()| {
+                for (path, entry) in &entries {
+                    store_add(path, entry.clone()).unwrap();
+                }
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    group.bench_function("entries_for_project_of_500", |b| {
+        b.iter(|| store_entries_for(&project).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    clone_queue_scheduling,
+    workspace_status,
+    worktree_store
+);
+criterion_main!(benches);
@@ -0,0 +1,34 @@
+/*
+ * C interface to meta_git_lib's JSON API.
+ *
+ * Build the shared library with:
+ *   cargo rustc --release --features ffi --crate-type cdylib
+ *
+ * See src/api.rs for the request/response schema.
+ */
+#ifndef META_GIT_H
+#define META_GIT_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Schema version spoken by meta_git_execute. */
+uint32_t meta_git_api_version(void);
+
+/*
+ * Execute a JSON request and return the JSON response (never NULL).
+ * The result must be released with meta_git_free_string.
+ */
+char *meta_git_execute(const char *request);
+
+/* Release a string returned by meta_git_execute. NULL is ignored. */
+void meta_git_free_string(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* META_GIT_H */
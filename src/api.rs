@@ -0,0 +1,1283 @@
+//! Structured JSON entry point for agents and automation.
+//!
+//! [`execute`] takes one JSON request and returns one JSON response, so
+//! callers can drive the main operations (clone, status, worktree CRUD,
+//! snapshots, diff, ignore checks) without shelling out to the CLI and parsing its output.
+//!
+//! Request:
+//! ```json
+//! {"version": 1, "id": 7, "op": "worktree.create",
+//!  "params": {"meta_dir": "/ws", "name": "fix-auth", "repos": ["api", "web"]}}
+//! ```
+//! Response:
+//! ```json
+//! {"version": 1, "id": 7, "ok": true, "result": { ... }}
+//! {"version": 1, "id": 7, "ok": false, "error": {"code": "operation_failed", "message": "..."}}
+//! ```
+//! `version` is bumped on incompatible schema changes; requests for an
+//! unknown version are rejected rather than guessed at.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::change_group::ChangeGroupId;
+use crate::operations::OperationHandle;
+use crate::project_options::{load_project_options, skipped_projects, ProjectOperation};
+use crate::read_only::ReadOnlyViolation;
+use crate::sandbox::{Sandbox, SandboxViolation};
+use crate::snapshot;
+use crate::status_index::StatusIndex;
+use crate::vcs::Vcs;
+use crate::worktree::git_ops::{
+    git_ahead_behind, git_check_ignore, git_diff_summary, git_fetch_if_stale, git_status_summary,
+    git_status_summary_in, git_worktree_add, remove_worktree_repos,
+};
+use crate::worktree::helpers::{
+    load_projects_with_root, lookup_nested_project, resolve_branch, resolve_start_ref,
+    resolve_worktree_root, validate_worktree_name,
+};
+use crate::worktree::hooks::{
+    fire_post_add_repo, fire_pre_create, fire_pre_destroy, fire_pre_remove_repo, HookAborted,
+};
+use crate::worktree::placement::{find_worktree, place_worktree};
+use crate::worktree::prune::prune;
+use crate::worktree::selection::expand_repo_specs;
+use crate::worktree::store::{
+    custom_matches, entry_ttl_remaining, set_protected, store_add, store_list, store_remove,
+};
+use crate::worktree::types::{
+    CreateOutput, CreateRepoEntry, DestroyOutput, DiffOutput, DiffRepoEntry, DiffTotals,
+    IgnoreCheck, ListEntry, ListOutput, ListRepoEntry, PlannedRepoEntry, RepoSpec, StatusOutput,
+    StatusRepoEntry, StoreRepoEntry, WorktreeStoreEntry,
+};
+use crate::worktree::unpushed::{unpushed, unpushed_at};
+
+/// Current request/response schema version.
+pub const API_VERSION: u32 = 1;
+
+/// A versioned API request.
+#[derive(Debug, Deserialize)]
+pub struct ApiRequest {
+    pub version: u32,
+    /// Echoed back in the response to correlate requests
+    #[serde(default)]
+    pub id: Option<Value>,
+    #[serde(flatten)]
+    pub operation: Operation,
+}
+
+/// Supported operations and their parameters.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", content = "params")]
+pub enum Operation {
+    #[serde(rename = "clone")]
+    Clone { url: String, target: PathBuf },
+    #[serde(rename = "status")]
+    Status {
+        meta_dir: PathBuf,
+        /// Reuse the status of repos that haven't changed since the last
+        /// incremental status (see [`crate::status_index`])
+        #[serde(default)]
+        incremental: bool,
+        /// Only report paths matching these git pathspecs (e.g. `src/**`)
+        #[serde(default)]
+        pathspecs: Vec<String>,
+        /// List untracked paths, not just count them
+        #[serde(default)]
+        list_untracked: bool,
+        /// Fetch repos whose remote data is older than this many seconds
+        /// before computing ahead/behind
+        #[serde(default)]
+        fetch_if_older_than: Option<u64>,
+    },
+    #[serde(rename = "worktree.create")]
+    WorktreeCreate {
+        meta_dir: PathBuf,
+        name: String,
+        /// `alias` or `alias:branch`
+        repos: Vec<String>,
+        #[serde(default)]
+        branch: Option<String>,
+        #[serde(default)]
+        from_ref: Option<String>,
+        /// Create even if the `pre-create` hook objects
+        #[serde(default)]
+        force: bool,
+    },
+    #[serde(rename = "worktree.list")]
+    WorktreeList {
+        meta_dir: PathBuf,
+        /// Only worktrees with these custom metadata values
+        #[serde(default)]
+        custom: HashMap<String, String>,
+    },
+    #[serde(rename = "worktree.protect")]
+    WorktreeProtect {
+        meta_dir: PathBuf,
+        name: String,
+        /// `false` clears the protection
+        protected: bool,
+    },
+    #[serde(rename = "worktree.unpushed")]
+    WorktreeUnpushed { meta_dir: PathBuf, name: String },
+    #[serde(rename = "worktree.remove")]
+    WorktreeRemove {
+        meta_dir: PathBuf,
+        name: String,
+        #[serde(default)]
+        force: bool,
+    },
+    #[serde(rename = "worktree.prune")]
+    WorktreePrune {
+        meta_dir: PathBuf,
+        /// Only report what would be removed
+        #[serde(default)]
+        dry_run: bool,
+        /// Also remove expired worktrees with unsaved work
+        #[serde(default)]
+        force: bool,
+    },
+    #[serde(rename = "snapshot.create")]
+    SnapshotCreate { meta_dir: PathBuf, name: String },
+    #[serde(rename = "snapshot.list")]
+    SnapshotList { meta_dir: PathBuf },
+    #[serde(rename = "snapshot.restore")]
+    SnapshotRestore {
+        meta_dir: PathBuf,
+        name: String,
+        #[serde(default)]
+        force: bool,
+    },
+    #[serde(rename = "diff")]
+    Diff {
+        meta_dir: PathBuf,
+        name: String,
+        base: String,
+        /// Only report paths matching these git pathspecs
+        #[serde(default)]
+        pathspecs: Vec<String>,
+    },
+    /// Which repo each path is in, and whether and why it's ignored there
+    #[serde(rename = "ignore.check")]
+    IgnoreCheck {
+        meta_dir: PathBuf,
+        /// Relative to `meta_dir`, or absolute
+        paths: Vec<PathBuf>,
+    },
+}
+
+/// A versioned API response.
+#[derive(Debug, Serialize)]
+pub struct ApiResponse {
+    pub version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ApiError>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiError {
+    /// `invalid_request`, `unsupported_version`, `read_only`,
+    /// `sandbox_violation`, or `operation_failed`
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ApiResponse {
+    fn success(id: Option<Value>, result: Value) -> Self {
+        ApiResponse {
+            version: API_VERSION,
+            id,
+            ok: true,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn failure(id: Option<Value>, code: &'static str, message: String) -> Self {
+        ApiResponse {
+            version: API_VERSION,
+            id,
+            ok: false,
+            result: None,
+            error: Some(ApiError { code, message }),
+        }
+    }
+}
+
+/// Execute a JSON request and return the JSON response. Never panics on bad input.
+pub fn execute(request_json: &str) -> String {
+    let response = match serde_json::from_str::<Value>(request_json) {
+        Ok(value) => execute_value(value),
+        Err(e) => ApiResponse::failure(None, "invalid_request", format!("Invalid JSON: {e}")),
+    };
+    serde_json::to_string(&response).unwrap_or_else(|e| {
+        format!(
+            r#"{{"version":{API_VERSION},"ok":false,"error":{{"code":"operation_failed","message":"Failed to serialize response: {e}"}}}}"#
+        )
+    })
+}
+
+/// Execute an already-parsed request.
+pub fn execute_value(request: Value) -> ApiResponse {
+    let id = request.get("id").cloned();
+    match request.get("version").and_then(Value::as_u64) {
+        Some(v) if v == u64::from(API_VERSION) => {}
+        Some(v) => {
+            return ApiResponse::failure(
+                id,
+                "unsupported_version",
+                format!("Unsupported API version {v}; this library speaks version {API_VERSION}"),
+            )
+        }
+        None => {
+            return ApiResponse::failure(id, "invalid_request", "Missing 'version'".to_string())
+        }
+    }
+
+    let request: ApiRequest = match serde_json::from_value(request) {
+        Ok(r) => r,
+        Err(e) => return ApiResponse::failure(id, "invalid_request", e.to_string()),
+    };
+    match dispatch(request.operation) {
+        Ok(result) => ApiResponse::success(request.id, result),
+        Err(e) => {
+            let code = if e.is::<ReadOnlyViolation>() {
+                "read_only"
+            } else if e.is::<SandboxViolation>() {
+                "sandbox_violation"
+            } else if e.is::<HookAborted>() {
+                "hook_aborted"
+            } else {
+                "operation_failed"
+            };
+            ApiResponse::failure(request.id, code, format!("{e:#}"))
+        }
+    }
+}
+
+fn dispatch(operation: Operation) -> Result<Value> {
+    let value = match operation {
+        Operation::Clone { url, target } => {
+            let pb = indicatif::ProgressBar::hidden();
+            crate::clone_repo_with_progress(&url, &target, Some(&pb))?;
+            serde_json::json!({ "url": url, "path": target })
+        }
+        Operation::Status {
+            meta_dir,
+            incremental,
+            pathspecs,
+            list_untracked,
+            fetch_if_older_than,
+        } => serde_json::to_value(workspace_status(
+            &meta_dir,
+            incremental,
+            &pathspecs,
+            list_untracked,
+            fetch_if_older_than,
+        )?)?,
+        Operation::WorktreeCreate {
+            meta_dir,
+            name,
+            repos,
+            branch,
+            from_ref,
+            force,
+        } => serde_json::to_value(worktree_create(
+            &meta_dir,
+            &name,
+            &repos,
+            branch.as_deref(),
+            from_ref.as_deref(),
+            force,
+        )?)?,
+        Operation::WorktreeList { meta_dir, custom } => {
+            serde_json::to_value(worktree_list(&meta_dir, &custom)?)?
+        }
+        Operation::WorktreeUnpushed { meta_dir, name } => {
+            validate_worktree_name(&name)?;
+            serde_json::to_value(unpushed(Some(&meta_dir), &name)?)?
+        }
+        Operation::WorktreeProtect {
+            meta_dir,
+            name,
+            protected,
+        } => {
+            let path = worktree_protect(&meta_dir, &name, protected)?;
+            serde_json::json!({ "name": name, "path": path, "protected": protected })
+        }
+        Operation::WorktreeRemove {
+            meta_dir,
+            name,
+            force,
+        } => serde_json::to_value(worktree_remove(&meta_dir, &name, force)?)?,
+        Operation::WorktreePrune {
+            meta_dir,
+            dry_run,
+            force,
+        } => serde_json::to_value(prune(&meta_dir, dry_run, force)?)?,
+        Operation::SnapshotCreate { meta_dir, name } => {
+            serde_json::to_value(snapshot_create(&meta_dir, &name)?)?
+        }
+        Operation::SnapshotList { meta_dir } => {
+            serde_json::to_value(snapshot::list_snapshots(&meta_dir)?)?
+        }
+        Operation::SnapshotRestore {
+            meta_dir,
+            name,
+            force,
+        } => {
+            let snap = snapshot::load_snapshot(&meta_dir, &name)?;
+            let mut journal = OperationHandle::start(
+                "snapshot restore",
+                &meta_dir,
+                serde_json::json!({"name": name, "force": force}),
+                snap.repos.keys().cloned().collect(),
+            )?;
+            let mut results = Vec::new();
+            for (path, state) in &snap.repos {
+                let mut result =
+                    match snapshot::restore_repo_state(&meta_dir.join(path), state, force) {
+                        Ok(result) => result,
+                        Err(e) => {
+                            journal.fail_item(path)?;
+                            return Err(e);
+                        }
+                    };
+                result.repo = path.clone();
+                results.push(result);
+                journal.complete_item(path)?;
+            }
+            journal.finish()?;
+            results.sort_by(|a, b| a.repo.cmp(&b.repo));
+            serde_json::to_value(results)?
+        }
+        Operation::Diff {
+            meta_dir,
+            name,
+            base,
+            pathspecs,
+        } => serde_json::to_value(worktree_diff(&meta_dir, &name, &base, &pathspecs)?)?,
+        Operation::IgnoreCheck { meta_dir, paths } => {
+            serde_json::to_value(check_ignore(&meta_dir, &paths)?)?
+        }
+    };
+    Ok(value)
+}
+
+fn repo_status(
+    alias: &str,
+    path: &Path,
+    pathspecs: &[String],
+    list_untracked: bool,
+) -> Result<StatusRepoEntry> {
+    let summary = git_status_summary_in(path, pathspecs)?;
+    let (ahead, behind) = git_ahead_behind(path)?;
+    Ok(StatusRepoEntry {
+        alias: alias.to_string(),
+        path: path.to_string_lossy().into_owned(),
+        branch: crate::vcs::GitVcs.current_branch(path).unwrap_or_default(),
+        dirty: summary.dirty,
+        modified_count: summary.modified_files.len(),
+        untracked_count: summary.untracked_count,
+        ahead,
+        behind,
+        modified_files: summary.modified_files,
+        renames: summary.renames,
+        untracked_files: if list_untracked {
+            summary.untracked_files
+        } else {
+            Vec::new()
+        },
+        last_fetched: crate::worktree::git_ops::git_last_fetched(path).map(|t| t.to_rfc3339()),
+    })
+}
+
+fn workspace_status(
+    meta_dir: &Path,
+    incremental: bool,
+    pathspecs: &[String],
+    list_untracked: bool,
+    fetch_if_older_than: Option<u64>,
+) -> Result<StatusOutput> {
+    let disabled = skipped_projects(meta_dir, ProjectOperation::Status);
+    // The index only holds unfiltered status, without untracked paths
+    let mut index =
+        (incremental && pathspecs.is_empty() && !list_untracked).then(StatusIndex::load);
+    let mut repos = Vec::new();
+    for project in load_projects_with_root(meta_dir, true)? {
+        if disabled.contains(&project.name) {
+            continue;
+        }
+        let path = meta_dir.join(&project.path);
+        if !snapshot::is_git_repo(&path) {
+            continue;
+        }
+        // A fetch updates FETCH_HEAD, so the index sees the new remote data
+        if let Some(max_age) = fetch_if_older_than {
+            if let Err(e) = git_fetch_if_stale(&path, max_age) {
+                log::warn!("Failed to refresh {}: {e:#}", path.display());
+            }
+        }
+        if let Some(cached) = index.as_ref().and_then(|index| index.lookup(&path)) {
+            repos.push(StatusRepoEntry {
+                alias: project.name.clone(),
+                ..cached.clone()
+            });
+            continue;
+        }
+        let status = repo_status(&project.name, &path, pathspecs, list_untracked)?;
+        if let Some(index) = &mut index {
+            index.record(&path, &status);
+        }
+        repos.push(status);
+    }
+    if let Some(index) = index {
+        if let Err(e) = index.save() {
+            log::warn!("Failed to save the status index: {e:#}");
+        }
+    }
+    Ok(StatusOutput {
+        name: meta_dir.to_string_lossy().into_owned(),
+        repos,
+        disabled: disabled.into_iter().collect(),
+    })
+}
+
+/// Check each of `paths` against the ignore rules of the repo it's in (the
+/// deepest project containing it).
+fn check_ignore(meta_dir: &Path, paths: &[PathBuf]) -> Result<Vec<IgnoreCheck>> {
+    let projects = load_projects_with_root(meta_dir, true)?;
+    let mut checks = Vec::new();
+    for path in paths {
+        let full = meta_dir.join(path);
+        let (project, relative) = projects
+            .iter()
+            .filter_map(|p| {
+                let relative = full.strip_prefix(meta_dir.join(&p.path)).ok()?;
+                Some((p, relative))
+            })
+            .min_by_key(|(_, relative)| relative.components().count())
+            .with_context(|| format!("{} is not in any repo of the workspace", path.display()))?;
+        let relative = relative.to_string_lossy().into_owned();
+        let repo = meta_dir.join(&project.path);
+        for (path, rule) in git_check_ignore(&repo, std::slice::from_ref(&relative))? {
+            checks.push(IgnoreCheck {
+                alias: project.name.clone(),
+                path,
+                ignored: rule.as_ref().is_some_and(|r| !r.is_negation()),
+                rule,
+            });
+        }
+    }
+    Ok(checks)
+}
+
+fn worktree_create(
+    meta_dir: &Path,
+    name: &str,
+    repos: &[String],
+    branch: Option<&str>,
+    from_ref: Option<&str>,
+    force: bool,
+) -> Result<CreateOutput> {
+    crate::read_only::check("create worktree")?;
+    validate_worktree_name(name)?;
+    if repos.is_empty() {
+        anyhow::bail!("No repos given");
+    }
+    if let Some(existing) = find_worktree(Some(meta_dir), name)? {
+        anyhow::bail!(
+            "Worktree '{}' already exists at {}",
+            name,
+            existing.display()
+        );
+    }
+
+    let skipped = skipped_projects(meta_dir, ProjectOperation::Worktree);
+    let project_options = load_project_options(meta_dir);
+    let requested = repos
+        .iter()
+        .map(|s| s.parse::<RepoSpec>())
+        .collect::<Result<Vec<_>, _>>()?;
+    let (requested, expansions) = expand_repo_specs(meta_dir, requested)?;
+    let (mut specs, disabled): (Vec<RepoSpec>, Vec<RepoSpec>) = requested
+        .into_iter()
+        .partition(|s| !skipped.contains(&s.alias));
+    if specs.is_empty() {
+        anyhow::bail!("All requested repos are disabled for worktrees");
+    }
+    specs.sort_by_key(|s| s.alias != ".");
+    let sources = specs
+        .iter()
+        .map(|spec| {
+            if spec.alias == "." {
+                Ok(meta_dir.to_path_buf())
+            } else {
+                Ok(lookup_nested_project(meta_dir, &spec.alias)?.0)
+            }
+        })
+        .collect::<Result<Vec<PathBuf>>>()?;
+    let wt_dir = place_worktree(meta_dir, name, &sources)?;
+    Sandbox::for_workspace(meta_dir).check(&wt_dir, "create worktree")?;
+
+    let planned: Vec<PlannedRepoEntry> = specs
+        .iter()
+        .map(|spec| PlannedRepoEntry {
+            alias: spec.alias.clone(),
+            path: if spec.alias == "." {
+                wt_dir.to_string_lossy().into_owned()
+            } else {
+                wt_dir.join(&spec.alias).to_string_lossy().into_owned()
+            },
+            branch: resolve_branch(name, branch, spec.branch.as_deref()),
+        })
+        .collect();
+    fire_pre_create(name, &wt_dir, &planned, force, Some(meta_dir))?;
+
+    let preexisting = wt_dir.exists();
+    let mut added = Vec::new();
+    let mut created = Vec::new();
+    for ((spec, source), planned) in specs.iter().zip(sources).zip(planned) {
+        let dest = PathBuf::from(planned.path);
+        let repo_branch = planned.branch;
+        let result = resolve_start_ref(&source, spec, from_ref).and_then(|start_ref| {
+            git_worktree_add(&source, &dest, &repo_branch, start_ref.as_deref())
+                .with_context(|| format!("Failed to create worktree for '{}'", spec.alias))
+        });
+        let created_branch = match result {
+            Ok(created_branch) => created_branch,
+            Err(e) => {
+                roll_back_create(&wt_dir, &added, preexisting);
+                return Err(e);
+            }
+        };
+        let setup = match project_options.get(&spec.alias) {
+            Some(options) if options.setup_in_worktrees => {
+                crate::setup::run_project_setup(&dest, options)
+            }
+            _ => Vec::new(),
+        };
+        let info = meta_cli::worktree::WorktreeRepoInfo {
+            alias: spec.alias.clone(),
+            branch: repo_branch.clone(),
+            path: dest.clone(),
+            source_path: source,
+            created_branch: Some(created_branch),
+        };
+        fire_post_add_repo(name, &wt_dir, &info, Some(meta_dir));
+        added.push(info);
+        created.push(CreateRepoEntry {
+            alias: spec.alias.clone(),
+            path: dest.to_string_lossy().into_owned(),
+            branch: repo_branch,
+            created_branch,
+            setup,
+        });
+    }
+
+    let change_group = ChangeGroupId::generate().to_string();
+    let stored = store_add(
+        &wt_dir,
+        WorktreeStoreEntry {
+            name: name.to_string(),
+            project: meta_dir.to_string_lossy().into_owned(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            ephemeral: false,
+            ttl_seconds: None,
+            repos: created.iter().map(StoreRepoEntry::from).collect(),
+            custom: HashMap::new(),
+            change_group: Some(change_group.clone()),
+            protected: false,
+            expansions: expansions.clone(),
+        },
+    );
+    if let Err(e) = stored {
+        roll_back_create(&wt_dir, &added, preexisting);
+        return Err(e);
+    }
+
+    Ok(CreateOutput {
+        name: name.to_string(),
+        root: wt_dir.to_string_lossy().into_owned(),
+        repos: created,
+        ephemeral: false,
+        ttl_seconds: None,
+        custom: HashMap::new(),
+        change_group: Some(change_group),
+        disabled: disabled.into_iter().map(|s| s.alias).collect(),
+        expansions,
+    })
+}
+
+/// Undo a partly created worktree: remove the repos `added` so far and the
+/// branches they created, then the worktree directory unless it was there
+/// before. Best-effort: failures are logged.
+fn roll_back_create(
+    wt_dir: &Path,
+    added: &[meta_cli::worktree::WorktreeRepoInfo],
+    preexisting: bool,
+) {
+    if let Err(e) = remove_worktree_repos(added, true, false) {
+        log::warn!("Failed to roll back worktree {}: {e:#}", wt_dir.display());
+    }
+    for info in added.iter().filter(|r| r.created_branch == Some(true)) {
+        let _ = crate::git_runner::status(
+            std::process::Command::new("git")
+                .args(["branch", "-D", &info.branch])
+                .current_dir(&info.source_path)
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null()),
+        );
+    }
+    if !preexisting && wt_dir.exists() {
+        if let Err(e) = std::fs::remove_dir_all(wt_dir) {
+            log::warn!("Failed to remove {}: {e}", wt_dir.display());
+        }
+    }
+}
+
+fn worktree_list(meta_dir: &Path, custom: &HashMap<String, String>) -> Result<ListOutput> {
+    let project = meta_dir
+        .canonicalize()
+        .unwrap_or_else(|_| meta_dir.to_path_buf());
+    let now = chrono::Utc::now().timestamp();
+    let store = store_list()?;
+    let mut worktrees: Vec<ListEntry> = store
+        .worktrees
+        .iter()
+        .filter(|(_, e)| {
+            let p = Path::new(&e.project);
+            (p == meta_dir || p.canonicalize().is_ok_and(|c| c == project))
+                && custom_matches(e, custom)
+        })
+        .map(|(root, e)| ListEntry {
+            name: e.name.clone(),
+            root: root.clone(),
+            has_meta_root: e.repos.iter().any(|r| r.alias == "."),
+            repos: e
+                .repos
+                .iter()
+                .map(|r| {
+                    let path = if r.alias == "." {
+                        PathBuf::from(root)
+                    } else {
+                        Path::new(root).join(&r.alias)
+                    };
+                    ListRepoEntry {
+                        alias: r.alias.clone(),
+                        branch: r.branch.clone(),
+                        dirty: git_status_summary(&path).is_ok_and(|s| s.dirty),
+                        base_moved_by: r.base_moved.as_ref().map(|b| b.commits),
+                    }
+                })
+                .collect(),
+            ephemeral: e.ephemeral.then_some(true),
+            ttl_remaining_seconds: entry_ttl_remaining(e, now),
+            protected: e.protected.then_some(true),
+            custom: (!e.custom.is_empty()).then(|| e.custom.clone()),
+            ..Default::default()
+        })
+        .collect();
+    worktrees.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(ListOutput { worktrees })
+}
+
+/// Set or clear prune protection on worktree `name`, returning its path.
+fn worktree_protect(meta_dir: &Path, name: &str, protected: bool) -> Result<PathBuf> {
+    validate_worktree_name(name)?;
+    let wt_dir = find_worktree(Some(meta_dir), name)?
+        .with_context(|| format!("Worktree '{name}' not found"))?;
+    set_protected(&wt_dir, protected)?;
+    Ok(wt_dir)
+}
+
+fn worktree_remove(meta_dir: &Path, name: &str, force: bool) -> Result<DestroyOutput> {
+    crate::read_only::check("remove worktree")?;
+    validate_worktree_name(name)?;
+    let wt_dir = match find_worktree(Some(meta_dir), name)? {
+        Some(existing) => existing,
+        None => resolve_worktree_root(Some(meta_dir))?.join(name),
+    };
+    Sandbox::for_workspace(meta_dir).check(&wt_dir, "remove worktree")?;
+    if !wt_dir.exists() {
+        anyhow::bail!("Worktree '{}' not found at {}", name, wt_dir.display());
+    }
+    if !force {
+        if let Some(repo) = unpushed_at(&wt_dir, name)?.repos.first() {
+            anyhow::bail!(
+                "Worktree '{name}' has {} unpushed commit(s) in {}; use force to remove it anyway",
+                repo.commit_count(),
+                repo.alias
+            );
+        }
+    }
+    fire_pre_destroy(name, &wt_dir, force, Some(meta_dir))?;
+    let repos = meta_cli::worktree::discover_worktree_repos(&wt_dir)?;
+    for repo in &repos {
+        fire_pre_remove_repo(name, &wt_dir, repo, force, Some(meta_dir))?;
+    }
+    let failures = remove_worktree_repos(&repos, force, false)?;
+    store_remove(&wt_dir)?;
+    if wt_dir.exists() {
+        std::fs::remove_dir_all(&wt_dir)?;
+    }
+    Ok(DestroyOutput {
+        name: name.to_string(),
+        path: wt_dir.to_string_lossy().into_owned(),
+        repos_removed: repos.len() - failures,
+    })
+}
+
+fn worktree_diff(
+    meta_dir: &Path,
+    name: &str,
+    base: &str,
+    pathspecs: &[String],
+) -> Result<DiffOutput> {
+    validate_worktree_name(name)?;
+    let wt_dir = resolve_worktree_root(Some(meta_dir))?.join(name);
+    let repos = meta_cli::worktree::discover_worktree_repos(&wt_dir)?;
+    let mut entries = Vec::new();
+    let mut totals = DiffTotals {
+        repos_changed: 0,
+        files_changed: 0,
+        insertions: 0,
+        deletions: 0,
+    };
+    for repo in repos {
+        let diff = git_diff_summary(&repo.path, base, pathspecs)?;
+        if diff.files_changed > 0 {
+            totals.repos_changed += 1;
+        }
+        totals.files_changed += diff.files_changed;
+        totals.insertions += diff.insertions;
+        totals.deletions += diff.deletions;
+        entries.push(DiffRepoEntry {
+            alias: repo.alias,
+            base_ref: base.to_string(),
+            files_changed: diff.files_changed,
+            insertions: diff.insertions,
+            deletions: diff.deletions,
+            files: diff.files,
+            renames: diff.renames,
+        });
+    }
+    Ok(DiffOutput {
+        name: name.to_string(),
+        base: base.to_string(),
+        repos: entries,
+        totals,
+    })
+}
+
+fn snapshot_create(meta_dir: &Path, name: &str) -> Result<snapshot::SnapshotInfo> {
+    let mut repos = HashMap::new();
+    for project in load_projects_with_root(meta_dir, true)? {
+        let path = meta_dir.join(&project.path);
+        if snapshot::is_git_repo(&path) {
+            repos.insert(project.path.clone(), snapshot::capture_repo_state(&path)?);
+        }
+    }
+    let snap = snapshot::Snapshot {
+        name: name.to_string(),
+        created: chrono::Utc::now(),
+        repos,
+    };
+    snapshot::save_snapshot(meta_dir, &snap)?;
+    Ok(snapshot::SnapshotInfo {
+        name: snap.name,
+        created: snap.created,
+        repo_count: snap.repos.len(),
+        dirty_count: snap.repos.values().filter(|r| r.dirty).count(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{git, repo};
+
+    fn workspace() -> tempfile::TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        let store_dir = tmp.path().join("meta-store");
+        std::fs::create_dir_all(&store_dir).unwrap();
+        std::env::set_var("META_DATA_DIR", &store_dir);
+        std::env::remove_var("META_WORKTREES");
+
+        let ws = tmp.path().join("ws");
+        repo(&ws.join("api"), &[]);
+        std::fs::write(
+            ws.join(".meta"),
+            r#"{"projects": {"api": "git@github.com:org/api.git"}}"#,
+        )
+        .unwrap();
+        tmp
+    }
+
+    fn call(request: Value) -> Value {
+        serde_json::from_str(&execute(&request.to_string())).unwrap()
+    }
+
+    // ── envelope ────────────────────────────────────────────
+
+    #[test]
+    fn invalid_json_is_reported() {
+        let resp: Value = serde_json::from_str(&execute("{not json")).unwrap();
+        assert_eq!(resp["ok"], false);
+        assert_eq!(resp["error"]["code"], "invalid_request");
+    }
+
+    #[test]
+    fn unsupported_version_is_rejected() {
+        let resp = call(
+            serde_json::json!({"version": 99, "id": "a", "op": "status", "params": {"meta_dir": "/x"}}),
+        );
+        assert_eq!(resp["error"]["code"], "unsupported_version");
+        assert_eq!(resp["id"], "a");
+    }
+
+    #[test]
+    fn unknown_operation_is_invalid() {
+        let resp = call(serde_json::json!({"version": 1, "op": "launch", "params": {}}));
+        assert_eq!(resp["error"]["code"], "invalid_request");
+    }
+
+    #[test]
+    fn operation_errors_are_wrapped() {
+        let resp = call(serde_json::json!({
+            "version": 1, "id": 3, "op": "snapshot.restore",
+            "params": {"meta_dir": "/nonexistent", "name": "nope"}
+        }));
+        assert_eq!(resp["ok"], false);
+        assert_eq!(resp["id"], 3);
+        assert_eq!(resp["error"]["code"], "operation_failed");
+    }
+
+    // ── operations ──────────────────────────────────────────
+
+    #[test]
+    #[serial_test::serial]
+    fn worktrees_dir_escaping_workspace_is_rejected() {
+        let tmp = workspace();
+        let ws = tmp.path().join("ws");
+        std::fs::write(
+            ws.join(".meta"),
+            r#"{"projects": {"api": "git@github.com:org/api.git"}, "worktrees_dir": "../escape"}"#,
+        )
+        .unwrap();
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "worktree.create",
+            "params": {"meta_dir": ws, "name": "feat", "repos": ["api"]}
+        }));
+        assert_eq!(resp["error"]["code"], "sandbox_violation", "{resp}");
+        assert!(!tmp.path().join("escape").exists());
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn status_reports_workspace_repos() {
+        let tmp = workspace();
+        let ws = tmp.path().join("ws");
+        let resp =
+            call(serde_json::json!({"version": 1, "op": "status", "params": {"meta_dir": ws}}));
+        assert_eq!(resp["ok"], true, "{resp}");
+        assert_eq!(resp["result"]["repos"][0]["alias"], "api");
+        assert_eq!(resp["result"]["repos"][0]["branch"], "main");
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn status_fetches_stale_repos_when_asked() {
+        let tmp = workspace();
+        let ws = tmp.path().join("ws");
+        let upstream = tmp.path().join("upstream");
+        repo(&upstream, &[]);
+        std::fs::remove_dir_all(ws.join("api")).unwrap();
+        git(
+            tmp.path(),
+            &["clone", "-q", upstream.to_str().unwrap(), "ws/api"],
+        );
+        git(&upstream, &["commit", "-q", "--allow-empty", "-m", "new"]);
+
+        let behind = |params: Value| {
+            call(serde_json::json!({"version": 1, "op": "status", "params": params}))["result"]
+                ["repos"][0]["behind"]
+                .clone()
+        };
+        assert_eq!(behind(serde_json::json!({"meta_dir": ws})), 0);
+        assert_eq!(
+            behind(serde_json::json!({"meta_dir": ws, "fetch_if_older_than": 3600})),
+            1
+        );
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn incremental_status_reuses_unchanged_repos() {
+        let tmp = workspace();
+        let ws = tmp.path().join("ws");
+        let request = serde_json::json!({
+            "version": 1, "op": "status", "params": {"meta_dir": ws, "incremental": true}
+        });
+        assert_eq!(
+            call(request.clone())["result"]["repos"][0]["branch"],
+            "main"
+        );
+
+        // Served from the index while the repo is unchanged
+        let index_path = tmp.path().join("meta-store/status-index.json");
+        let index = std::fs::read_to_string(&index_path).unwrap();
+        std::fs::write(&index_path, index.replace("\"main\"", "\"cached\"")).unwrap();
+        assert_eq!(
+            call(request.clone())["result"]["repos"][0]["branch"],
+            "cached"
+        );
+
+        // Re-inspected once HEAD moves
+        git(&ws.join("api"), &["checkout", "-q", "-b", "feature"]);
+        assert_eq!(call(request)["result"]["repos"][0]["branch"], "feature");
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn disabled_projects_are_reported_separately() {
+        let tmp = workspace();
+        let ws = tmp.path().join("ws");
+        repo(&ws.join("legacy"), &[]);
+        std::fs::write(
+            ws.join(".meta"),
+            r#"{"projects": {
+                "api": "git@github.com:org/api.git",
+                "legacy": {"repo": "git@github.com:org/legacy.git", "disabled": true}
+            }}"#,
+        )
+        .unwrap();
+
+        let resp =
+            call(serde_json::json!({"version": 1, "op": "status", "params": {"meta_dir": ws}}));
+        assert_eq!(
+            resp["result"]["repos"].as_array().unwrap().len(),
+            1,
+            "{resp}"
+        );
+        assert_eq!(resp["result"]["disabled"], serde_json::json!(["legacy"]));
+
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "worktree.create",
+            "params": {"meta_dir": ws, "name": "feat", "repos": ["api", "legacy"]}
+        }));
+        assert_eq!(resp["ok"], true, "{resp}");
+        assert_eq!(resp["result"]["repos"].as_array().unwrap().len(), 1);
+        assert_eq!(resp["result"]["disabled"], serde_json::json!(["legacy"]));
+        assert!(!ws.join(".worktrees/feat/legacy").exists());
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn worktree_create_list_diff_remove() {
+        let tmp = workspace();
+        let ws = tmp.path().join("ws");
+
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "worktree.create",
+            "params": {"meta_dir": ws, "name": "feat", "repos": ["api"]}
+        }));
+        assert_eq!(resp["ok"], true, "{resp}");
+        assert_eq!(resp["result"]["repos"][0]["branch"], "feat");
+        assert!(resp["result"]["change_group"]
+            .as_str()
+            .is_some_and(|id| id.starts_with("cg-")));
+
+        let resp = call(
+            serde_json::json!({"version": 1, "op": "worktree.list", "params": {"meta_dir": ws}}),
+        );
+        assert_eq!(resp["result"]["worktrees"][0]["name"], "feat");
+
+        let wt = ws.join(".worktrees").join("feat");
+        crate::worktree::store::set_custom(&wt, "ticket", "ENG-1").unwrap();
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "worktree.list",
+            "params": {"meta_dir": ws, "custom": {"ticket": "ENG-1"}}
+        }));
+        assert_eq!(resp["result"]["worktrees"][0]["custom"]["ticket"], "ENG-1");
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "worktree.list",
+            "params": {"meta_dir": ws, "custom": {"ticket": "ENG-2"}}
+        }));
+        assert_eq!(resp["result"]["worktrees"], serde_json::json!([]));
+
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "worktree.protect",
+            "params": {"meta_dir": ws, "name": "feat", "protected": true}
+        }));
+        assert_eq!(resp["ok"], true, "{resp}");
+        let resp = call(
+            serde_json::json!({"version": 1, "op": "worktree.list", "params": {"meta_dir": ws}}),
+        );
+        assert_eq!(resp["result"]["worktrees"][0]["protected"], true);
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "worktree.prune", "params": {"meta_dir": ws}
+        }));
+        assert_eq!(resp["result"]["removed"], serde_json::json!([]), "{resp}");
+
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "diff",
+            "params": {"meta_dir": ws, "name": "feat", "base": "main"}
+        }));
+        assert_eq!(resp["result"]["totals"]["files_changed"], 0, "{resp}");
+
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "worktree.remove",
+            "params": {"meta_dir": ws, "name": "feat", "force": true}
+        }));
+        assert_eq!(resp["ok"], true, "{resp}");
+        assert!(!ws.join(".worktrees").join("feat").exists());
+
+        let resp = call(
+            serde_json::json!({"version": 1, "op": "worktree.list", "params": {"meta_dir": ws}}),
+        );
+        assert_eq!(resp["result"]["worktrees"], serde_json::json!([]));
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn worktree_create_rolls_back_when_a_repo_fails() {
+        let tmp = workspace();
+        let ws = tmp.path().join("ws");
+        repo(&ws.join("web"), &[]);
+        std::fs::write(
+            ws.join(".meta"),
+            r#"{"projects": {"api": "git@github.com:org/api.git", "web": "git@github.com:org/web.git"}}"#,
+        )
+        .unwrap();
+        // Only api has the start ref, so web fails after api's worktree exists
+        git(&ws.join("api"), &["tag", "v1"]);
+
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "worktree.create",
+            "params": {"meta_dir": ws, "name": "feat", "repos": ["api", "web"], "from_ref": "v1"}
+        }));
+        assert_eq!(resp["ok"], false, "{resp}");
+        assert!(!ws.join(".worktrees").join("feat").exists());
+        assert_eq!(
+            git(&ws.join("api"), &["worktree", "list", "--porcelain"])
+                .lines()
+                .filter(|l| l.starts_with("worktree "))
+                .count(),
+            1
+        );
+        assert_eq!(git(&ws.join("api"), &["branch", "--list", "feat"]), "");
+        let resp = call(
+            serde_json::json!({"version": 1, "op": "worktree.list", "params": {"meta_dir": ws}}),
+        );
+        assert_eq!(resp["result"]["worktrees"], serde_json::json!([]));
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[serial_test::serial]
+    fn pre_hooks_abort_unless_forced() {
+        let tmp = workspace();
+        let ws = tmp.path().join("ws");
+        std::fs::write(
+            ws.join(".meta"),
+            serde_json::json!({
+                "projects": {"api": "git@github.com:org/api.git"},
+                "worktree": {"hooks": {
+                    "pre-create": "grep -q '\"name\":\"feat-' || { echo 'names must start with feat-' >&2; exit 1; }",
+                    "pre-destroy": ["sh", "-c", "exit 3"],
+                }}
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "worktree.create",
+            "params": {"meta_dir": ws, "name": "wip", "repos": ["api"]}
+        }));
+        assert_eq!(resp["error"]["code"], "hook_aborted", "{resp}");
+        let message = resp["error"]["message"].as_str().unwrap();
+        assert!(message.contains("names must start with feat-"), "{message}");
+        assert!(!ws.join(".worktrees").join("wip").exists());
+
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "worktree.create",
+            "params": {"meta_dir": ws, "name": "feat-x", "repos": ["api"]}
+        }));
+        assert_eq!(resp["ok"], true, "{resp}");
+
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "worktree.remove",
+            "params": {"meta_dir": ws, "name": "feat-x"}
+        }));
+        assert_eq!(resp["error"]["code"], "hook_aborted", "{resp}");
+        assert!(ws.join(".worktrees").join("feat-x").exists());
+
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "worktree.remove",
+            "params": {"meta_dir": ws, "name": "feat-x", "force": true}
+        }));
+        assert_eq!(resp["ok"], true, "{resp}");
+        assert!(!ws.join(".worktrees").join("feat-x").exists());
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn per_repo_hooks_fire_for_each_repo() {
+        let tmp = workspace();
+        let ws = tmp.path().join("ws");
+        let log = tmp.path().join("added");
+        std::fs::write(
+            ws.join(".meta"),
+            serde_json::json!({
+                "projects": {"api": "git@github.com:org/api.git"},
+                "worktree": {"hooks": {
+                    "post-add-repo": format!(
+                        "echo \"$META_REPO_ALIAS $META_REPO_BRANCH $(basename $META_REPO_SOURCE)\" >> '{}'",
+                        log.display()
+                    ),
+                    "pre-remove-repo": "test \"$META_REPO_ALIAS\" != api",
+                }}
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "worktree.create",
+            "params": {"meta_dir": ws, "name": "feat", "repos": ["api"]}
+        }));
+        assert_eq!(resp["ok"], true, "{resp}");
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "api feat api\n");
+
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "worktree.remove",
+            "params": {"meta_dir": ws, "name": "feat"}
+        }));
+        assert_eq!(resp["error"]["code"], "hook_aborted", "{resp}");
+        assert!(resp["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("pre-remove-repo"));
+        assert!(ws.join(".worktrees").join("feat").join("api").exists());
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn worktree_commits_are_found_by_change_group() {
+        let tmp = workspace();
+        let ws = tmp.path().join("ws");
+        let api = ws.join("api");
+        std::fs::write(api.join("lib.rs"), "fn old() {}\n").unwrap();
+        git(&api, &["add", "lib.rs"]);
+        git(&api, &["commit", "-q", "-m", "lib"]);
+
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "worktree.create",
+            "params": {"meta_dir": ws, "name": "feat", "repos": ["api"]}
+        }));
+        assert_eq!(resp["ok"], true, "{resp}");
+        let id = ChangeGroupId::parse(resp["result"]["change_group"].as_str().unwrap()).unwrap();
+
+        let plan = crate::bulk::sed_in_worktree(
+            &ws,
+            "feat",
+            "old",
+            "new",
+            &crate::bulk::FileFilter::default(),
+        )
+        .unwrap();
+        assert_eq!(plan.change_group.as_ref(), Some(&id));
+        plan.apply().unwrap();
+        let commits = plan.commit("Rename old", None).unwrap();
+        let sha = commits[0].commit.clone().unwrap();
+
+        let members = crate::change_group::find(&id).unwrap();
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].worktree.as_deref(), Some("feat"));
+        assert_eq!(members[0].commits, [sha]);
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn untracked_listing_and_ignore_check() {
+        let tmp = workspace();
+        let ws = tmp.path().join("ws");
+        let api = ws.join("api");
+        std::fs::create_dir(api.join("logs")).unwrap();
+        std::fs::write(api.join("logs/.gitignore"), "*.log\n!keep.log\n").unwrap();
+        std::fs::write(api.join("notes.txt"), "").unwrap();
+
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "status",
+            "params": {"meta_dir": ws, "list_untracked": true}
+        }));
+        let repo = &resp["result"]["repos"][0];
+        assert_eq!(repo["untracked_count"], 2, "{resp}");
+        assert_eq!(
+            repo["untracked_files"],
+            serde_json::json!(["logs/", "notes.txt"])
+        );
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "status", "params": {"meta_dir": ws}
+        }));
+        assert!(resp["result"]["repos"][0].get("untracked_files").is_none());
+
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "ignore.check",
+            "params": {"meta_dir": ws, "paths": ["api/logs/debug.log", api.join("logs/keep.log"), "api/notes.txt"]}
+        }));
+        let checks = resp["result"]
+            .as_array()
+            .unwrap_or_else(|| panic!("{resp}"));
+        assert_eq!(checks[0]["alias"], "api");
+        assert_eq!(checks[0]["path"], "logs/debug.log");
+        assert_eq!(checks[0]["ignored"], true);
+        assert_eq!(
+            checks[0]["rule"],
+            serde_json::json!({"source": "logs/.gitignore", "line": 1, "pattern": "*.log"})
+        );
+        assert_eq!(checks[1]["ignored"], false);
+        assert_eq!(checks[1]["rule"]["pattern"], "!keep.log");
+        assert_eq!(checks[2]["ignored"], false);
+        assert!(checks[2].get("rule").is_none());
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn snapshot_create_and_list() {
+        let tmp = workspace();
+        let ws = tmp.path().join("ws");
+        let resp = call(serde_json::json!({
+            "version": 1, "op": "snapshot.create",
+            "params": {"meta_dir": ws, "name": "before"}
+        }));
+        assert_eq!(resp["result"]["repo_count"], 1, "{resp}");
+
+        let resp = call(
+            serde_json::json!({"version": 1, "op": "snapshot.list", "params": {"meta_dir": ws}}),
+        );
+        assert_eq!(resp["result"][0]["name"], "before");
+        std::env::remove_var("META_DATA_DIR");
+    }
+}
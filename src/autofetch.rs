@@ -0,0 +1,445 @@
+//! Scheduled background fetching.
+//!
+//! Prefetches all remotes of every repo in a workspace and records per-repo
+//! freshness timestamps in `~/.meta/autofetch.json`, so interactive
+//! operations can show up-to-date ahead/behind counts without waiting on
+//! the network.
+//!
+//! Scheduling is available either as a long-running library task
+//! ([`spawn`]) or as an OS-level timer ([`install`]: a systemd user timer on
+//! Linux, a launchd agent on macOS) that invokes [`AUTOFETCH_COMMAND`]. The
+//! timers run it at low CPU and IO priority (`Nice=19`, and `LowPriorityIO`
+//! on macOS); [`spawn`] runs at the priority of the embedding process.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::credentials::HttpsTokens;
+use crate::hooks::{fire_post_update, millis, UpdateRepoResult};
+use crate::project_options::{skipped_projects, ProjectOperation};
+use crate::rerun::Fnv64;
+use crate::ssh_multiplexing::{
+    load_host_options, output_with_mux_recovery, ssh_command_for_url, HostOptions,
+    MultiplexingConfig,
+};
+use crate::worktree::helpers::load_projects_with_root;
+
+/// Command run by installed timers; the CLI implements it by calling [`run_once`].
+pub const AUTOFETCH_COMMAND: &[&str] = &["meta", "git", "autofetch", "--once"];
+
+/// Freshness log at `~/.meta/autofetch.json`, keyed by repo path.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AutofetchLog {
+    pub repos: HashMap<String, AutofetchRecord>,
+    /// Projects the run left out because they are disabled for updates (not persisted)
+    #[serde(skip)]
+    pub disabled: Vec<String>,
+    /// Projects the run left out because their remote is quarantined (not persisted)
+    #[serde(skip)]
+    pub quarantined: Vec<String>,
+}
+
+/// Outcome of the last background fetch of one repo.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutofetchRecord {
+    pub fetched_at: String,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+fn log_paths() -> (PathBuf, PathBuf) {
+    let data_path = meta_core::data_dir::data_file("autofetch");
+    let lock_path = data_path.with_extension("lock");
+    (data_path, lock_path)
+}
+
+/// Read the freshness log.
+pub fn read_log() -> Result<AutofetchLog> {
+    meta_core::store::read(&log_paths().0)
+}
+
+/// Build a non-interactive `git fetch --all` for `repo_path`, using the
+/// per-host identity or HTTPS token configured for its origin.
+fn fetch_command(
+    repo_path: &Path,
+    hosts: &HashMap<String, HostOptions>,
+    tokens: &HttpsTokens,
+) -> Command {
+    let mut cmd = Command::new("git");
+    crate::credentials::suppress_prompts(&mut cmd);
+    if let Some(url) = crate::get_remote_url(repo_path) {
+        if let Some(ssh_command) = ssh_command_for_url(&url, hosts) {
+            cmd.env("GIT_SSH_COMMAND", ssh_command);
+        }
+        tokens.apply(&mut cmd, &url);
+    }
+    cmd.args(["fetch", "--all", "--prune", "--quiet"])
+        .current_dir(repo_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    cmd
+}
+
+/// Fetch every repo in the workspace once and record the results, then
+/// fire the `post-update` hook with them.
+///
+/// Failures are recorded per repo rather than aborting the run.
+pub fn run_once(meta_dir: &Path) -> Result<AutofetchLog> {
+    crate::read_only::check("fetch")?;
+    let started = Instant::now();
+    let projects = load_projects_with_root(meta_dir, true)?;
+    let hosts = load_host_options(meta_dir);
+    let multiplexing = MultiplexingConfig::from_meta(meta_dir);
+    let tokens = HttpsTokens::from_env();
+    let disabled = skipped_projects(meta_dir, ProjectOperation::Update);
+    let mut quarantined = Vec::new();
+    let mut results = HashMap::new();
+    let mut updated = Vec::new();
+
+    for project in projects {
+        if disabled.contains(&project.name) {
+            continue;
+        }
+        let repo_path = meta_dir.join(&project.path);
+        if !repo_path.join(".git").exists() {
+            continue;
+        }
+        let url = crate::get_remote_url(&repo_path);
+        if let Some(url) = &url {
+            let entry = crate::quarantine::is_quarantined(url).unwrap_or_else(|e| {
+                log::warn!("Failed to read the quarantine list: {e:#}");
+                None
+            });
+            if entry.is_some() {
+                quarantined.push(project.name);
+                continue;
+            }
+        }
+        let repo_started = Instant::now();
+        let mut fetch = fetch_command(&repo_path, &hosts, &tokens);
+        let fetched = crate::lock::RepoLock::acquire(&repo_path, "autofetch").and_then(|_lock| {
+            output_with_mux_recovery(&mut fetch, &multiplexing).context("Failed to run git fetch")
+        });
+        let record = match fetched {
+            Ok(out) if out.status.success() => {
+                if let Some(url) = &url {
+                    if let Err(e) = crate::quarantine::record_success(url) {
+                        log::warn!("Failed to update the quarantine list for {url}: {e:#}");
+                    }
+                }
+                AutofetchRecord {
+                    fetched_at: chrono::Utc::now().to_rfc3339(),
+                    success: true,
+                    error: None,
+                }
+            }
+            Ok(out) => {
+                let stderr = String::from_utf8_lossy(&out.stderr).trim().to_string();
+                if let Some(url) = &url {
+                    if let Err(e) = crate::quarantine::record_failure(url, &stderr) {
+                        log::warn!("Failed to update the quarantine list for {url}: {e:#}");
+                    }
+                }
+                AutofetchRecord {
+                    fetched_at: chrono::Utc::now().to_rfc3339(),
+                    success: false,
+                    error: Some(stderr),
+                }
+            }
+            Err(e) => AutofetchRecord {
+                fetched_at: chrono::Utc::now().to_rfc3339(),
+                success: false,
+                error: Some(format!("{e:#}")),
+            },
+        };
+        updated.push(UpdateRepoResult {
+            name: project.name,
+            path: repo_path.clone(),
+            success: record.success,
+            error: record.error.clone(),
+            duration_ms: millis(repo_started.elapsed()),
+        });
+        let key = repo_path
+            .canonicalize()
+            .unwrap_or(repo_path)
+            .to_string_lossy()
+            .into_owned();
+        results.insert(key, record);
+    }
+
+    meta_core::data_dir::ensure_meta_dir()?;
+    let (data_path, lock_path) = log_paths();
+    let recorded = results.clone();
+    meta_core::store::update::<AutofetchLog, _>(&data_path, &lock_path, move |log| {
+        log.repos.extend(recorded);
+    })?;
+    fire_post_update(&updated, started.elapsed(), meta_dir);
+
+    Ok(AutofetchLog {
+        repos: results,
+        disabled: disabled.into_iter().collect(),
+        quarantined,
+    })
+}
+
+/// Handle to a background autofetch task started with [`spawn`].
+pub struct AutofetchHandle {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AutofetchHandle {
+    /// Signal the task to stop and wait for the current fetch to finish.
+    pub fn stop(mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for AutofetchHandle {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Run [`run_once`] every `interval` on a background thread.
+pub fn spawn(meta_dir: PathBuf, interval: Duration) -> AutofetchHandle {
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_flag = Arc::clone(&stop);
+
+    let thread = std::thread::spawn(move || {
+        let tick = Duration::from_millis(200);
+        while !stop_flag.load(Ordering::SeqCst) {
+            if let Err(e) = run_once(&meta_dir) {
+                log::warn!("Autofetch failed for {}: {e}", meta_dir.display());
+            }
+            let mut waited = Duration::ZERO;
+            while waited < interval && !stop_flag.load(Ordering::SeqCst) {
+                std::thread::sleep(tick);
+                waited += tick;
+            }
+        }
+    });
+
+    AutofetchHandle {
+        stop,
+        thread: Some(thread),
+    }
+}
+
+/// Unit name for a workspace: its directory name, plus a short hash of its
+/// canonical path so workspaces with the same directory name get their own
+/// units.
+fn unit_name(meta_dir: &Path) -> String {
+    let dir_name: String = meta_dir
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let canonical = meta_dir
+        .canonicalize()
+        .unwrap_or_else(|_| meta_dir.to_path_buf());
+    let mut hash = Fnv64::new();
+    hash.write_str(&canonical.to_string_lossy());
+    format!("meta-autofetch-{dir_name}-{:08x}", hash.finish() as u32)
+}
+
+/// `path` as the value of a systemd unit setting. systemd takes the rest of
+/// the line verbatim apart from `%` specifiers, which are doubled; a line
+/// break or trailing `\` (a line continuation) can't be written.
+fn systemd_path(path: &Path) -> Result<String> {
+    let path = path.to_string_lossy();
+    if path.contains(['\n', '\r']) || path.ends_with('\\') {
+        anyhow::bail!("Cannot schedule autofetch for {path:?}: not a valid systemd path");
+    }
+    Ok(path.replace('%', "%%"))
+}
+
+/// `text` escaped for XML character data and attribute values.
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Render the systemd user service and timer units.
+fn systemd_units(meta_dir: &Path, interval: Duration) -> Result<(String, String)> {
+    let dir = systemd_path(meta_dir)?;
+    let service = format!(
+        "[Unit]\nDescription=meta background fetch for {dir}\n\n\
+         [Service]\nType=oneshot\nWorkingDirectory={dir}\nExecStart={cmd}\nNice=19\n",
+        cmd = AUTOFETCH_COMMAND.join(" "),
+    );
+    let timer = format!(
+        "[Unit]\nDescription=meta background fetch timer for {dir}\n\n\
+         [Timer]\nOnBootSec=5min\nOnUnitActiveSec={secs}s\n\n\
+         [Install]\nWantedBy=timers.target\n",
+        secs = interval.as_secs().max(60),
+    );
+    Ok((service, timer))
+}
+
+/// Render the launchd agent plist.
+fn launchd_plist(label: &str, meta_dir: &Path, interval: Duration) -> String {
+    let args: String = AUTOFETCH_COMMAND
+        .iter()
+        .map(|a| format!("    <string>{}</string>\n", xml_escape(a)))
+        .collect();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n<dict>\n\
+         \x20 <key>Label</key>\n  <string>{label}</string>\n\
+         \x20 <key>ProgramArguments</key>\n  <array>\n{args}  </array>\n\
+         \x20 <key>WorkingDirectory</key>\n  <string>{dir}</string>\n\
+         \x20 <key>StartInterval</key>\n  <integer>{secs}</integer>\n\
+         \x20 <key>LowPriorityIO</key>\n  <true/>\n\
+         \x20 <key>Nice</key>\n  <integer>19</integer>\n\
+         </dict>\n</plist>\n",
+        label = xml_escape(label),
+        dir = xml_escape(&meta_dir.to_string_lossy()),
+        secs = interval.as_secs().max(60),
+    )
+}
+
+fn run_checked(program: &str, args: &[&str]) -> Result<()> {
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .with_context(|| format!("Failed to run {program}"))?;
+    if !status.success() {
+        anyhow::bail!("{} {} exited with {}", program, args.join(" "), status);
+    }
+    Ok(())
+}
+
+/// Register an OS-level timer that runs the autofetch command every `interval`.
+///
+/// Returns the paths of the files written.
+pub fn install(meta_dir: &Path, interval: Duration) -> Result<Vec<PathBuf>> {
+    crate::read_only::check("install autofetch schedule")?;
+    let home =
+        dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Cannot determine home directory"))?;
+    let name = unit_name(meta_dir);
+
+    if cfg!(target_os = "macos") {
+        let label = name.replace('-', ".");
+        let dir = home.join("Library").join("LaunchAgents");
+        std::fs::create_dir_all(&dir)?;
+        let plist_path = dir.join(format!("{label}.plist"));
+        std::fs::write(&plist_path, launchd_plist(&label, meta_dir, interval))?;
+        run_checked("launchctl", &["load", "-w", &plist_path.to_string_lossy()])?;
+        Ok(vec![plist_path])
+    } else if cfg!(target_os = "linux") {
+        let dir = home.join(".config").join("systemd").join("user");
+        std::fs::create_dir_all(&dir)?;
+        let (service, timer) = systemd_units(meta_dir, interval)?;
+        let service_path = dir.join(format!("{name}.service"));
+        let timer_path = dir.join(format!("{name}.timer"));
+        std::fs::write(&service_path, service)?;
+        std::fs::write(&timer_path, timer)?;
+        run_checked("systemctl", &["--user", "daemon-reload"])?;
+        run_checked(
+            "systemctl",
+            &["--user", "enable", "--now", &format!("{name}.timer")],
+        )?;
+        Ok(vec![service_path, timer_path])
+    } else {
+        anyhow::bail!("Scheduled autofetch is not supported on this platform; use autofetch::spawn")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_name_sanitizes_dir_name() {
+        let name = unit_name(Path::new("/home/me/my workspace"));
+        let (prefix, hash) = name.rsplit_once('-').unwrap();
+        assert_eq!(prefix, "meta-autofetch-my-workspace");
+        assert_eq!(hash.len(), 8);
+        assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(unit_name(Path::new("/home/me/my workspace")), name);
+        // Same directory name, different workspace
+        assert_ne!(
+            unit_name(Path::new("/a/platform")),
+            unit_name(Path::new("/b/platform"))
+        );
+    }
+
+    #[test]
+    fn systemd_units_use_interval_and_workdir() {
+        let (service, timer) = systemd_units(Path::new("/ws"), Duration::from_secs(900)).unwrap();
+        assert!(service.contains("WorkingDirectory=/ws"));
+        assert!(service.contains("ExecStart=meta git autofetch --once"));
+        assert!(timer.contains("OnUnitActiveSec=900s"));
+    }
+
+    #[test]
+    fn unusual_paths_are_escaped() {
+        let dir = Path::new("/home/me/R&D <100%> 'ws'");
+        let (service, timer) = systemd_units(dir, Duration::from_secs(900)).unwrap();
+        assert!(service.contains("WorkingDirectory=/home/me/R&D <100%%> 'ws'\n"));
+        assert!(timer.contains("for /home/me/R&D <100%%> 'ws'\n"));
+        assert!(systemd_units(Path::new("/ws\n[Service]"), Duration::from_secs(900)).is_err());
+
+        let plist = launchd_plist("meta.autofetch.ws", dir, Duration::from_secs(900));
+        assert!(plist.contains("<string>/home/me/R&amp;D &lt;100%&gt; &apos;ws&apos;</string>"));
+    }
+
+    #[test]
+    fn timers_never_fire_more_than_once_a_minute() {
+        let (_, timer) = systemd_units(Path::new("/ws"), Duration::from_secs(5)).unwrap();
+        assert!(timer.contains("OnUnitActiveSec=60s"));
+        let plist = launchd_plist(
+            "meta.autofetch.ws",
+            Path::new("/ws"),
+            Duration::from_secs(5),
+        );
+        assert!(plist.contains("<integer>60</integer>"));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn run_once_records_freshness() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store_dir = tmp.path().join("meta-store");
+        std::fs::create_dir_all(&store_dir).unwrap();
+        std::env::set_var("META_DATA_DIR", &store_dir);
+
+        let repo = tmp.path().join("app");
+        std::fs::create_dir(&repo).unwrap();
+        Command::new("git")
+            .args(["init", "-q"])
+            .current_dir(&repo)
+            .status()
+            .unwrap();
+        std::fs::write(
+            tmp.path().join(".meta"),
+            r#"{"projects": {"app": "git@github.com:org/app.git"}}"#,
+        )
+        .unwrap();
+
+        let result = run_once(tmp.path()).unwrap();
+        assert_eq!(result.repos.len(), 1);
+        assert_eq!(read_log().unwrap().repos.len(), 1);
+
+        std::env::remove_var("META_DATA_DIR");
+    }
+}
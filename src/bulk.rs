@@ -0,0 +1,830 @@
+//! Workspace-wide search and replace.
+//!
+//! [`sed`] finds every tracked file matching a [`FileFilter`] in each repo
+//! of a workspace (or [`sed_in_worktree`] for a worktree), and works out
+//! what a regex replacement would do to them without touching anything. The
+//! resulting [`SedPlan`] renders a consolidated [`preview`](SedPlan::preview)
+//! diff, is [`apply`](SedPlan::apply)-ed to the files, and then
+//! [`commit`](SedPlan::commit)-ed with one commit per repo that shares a
+//! message and a change-group trailer, so "rename this API everywhere" ends
+//! up as a linked set of commits.
+//!
+//! Like `sed`, the pattern is matched line by line (`$1`-style groups work
+//! in the replacement); binary and non-UTF-8 files are skipped.
+//!
+//! [`distribute`] does the same for shared boilerplate (CODEOWNERS, CI
+//! config, license files): it compares canonical copies against each repo,
+//! reporting [`FileState::Missing`] and [`FileState::Drifted`] copies, and
+//! its [`DistributionPlan`] writes and stages them and commits per repo.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Serialize;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+use crate::change_group::{with_trailer, ChangeGroupId};
+use crate::git_output::nul_fields;
+use crate::sandbox::Sandbox;
+use crate::worktree::helpers::{load_projects_with_root, validate_worktree_name};
+use crate::worktree::placement::find_worktree;
+
+/// Which tracked files of each repo to edit.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileFilter {
+    /// Git pathspecs to include (e.g. `src/**/*.rs`); all files when empty
+    pub include: Vec<String>,
+    /// Pathspecs to leave out, even if included
+    pub exclude: Vec<String>,
+    /// Aliases of the repos to edit; all when empty
+    pub repos: Vec<String>,
+}
+
+impl FileFilter {
+    fn pathspecs(&self) -> Vec<String> {
+        let mut specs = self.include.clone();
+        if specs.is_empty() && !self.exclude.is_empty() {
+            specs.push(".".to_string());
+        }
+        specs.extend(self.exclude.iter().map(|s| format!(":(exclude){s}")));
+        specs
+    }
+
+    fn includes_repo(&self, alias: &str) -> bool {
+        self.repos.is_empty() || self.repos.iter().any(|r| r == alias)
+    }
+}
+
+/// The edits to one file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileEdit {
+    /// Relative to the repo
+    pub path: String,
+    pub replacements: usize,
+    #[serde(skip)]
+    original: String,
+    #[serde(skip)]
+    replaced: String,
+}
+
+/// The edited files of one repo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RepoEdits {
+    pub alias: String,
+    pub path: PathBuf,
+    pub files: Vec<FileEdit>,
+}
+
+/// What a replacement would change, repo by repo; repos with no matches are
+/// left out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SedPlan {
+    pub pattern: String,
+    pub replacement: String,
+    pub repos: Vec<RepoEdits>,
+    /// Change group of the worktree the plan is for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change_group: Option<ChangeGroupId>,
+}
+
+/// The commit made in one repo, or why it couldn't be.
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkCommit {
+    pub alias: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Plan replacing `pattern` with `replacement` in the repos of the
+/// workspace at `meta_dir`.
+pub fn sed(
+    meta_dir: &Path,
+    pattern: &str,
+    replacement: &str,
+    filter: &FileFilter,
+) -> Result<SedPlan> {
+    let repos = load_projects_with_root(meta_dir, true)?
+        .into_iter()
+        .map(|p| (p.name, meta_dir.join(&p.path)))
+        .filter(|(_, path)| crate::snapshot::is_git_repo(path))
+        .collect();
+    plan(repos, pattern, replacement, filter)
+}
+
+/// Plan replacing `pattern` with `replacement` in the repos of worktree
+/// `name`.
+pub fn sed_in_worktree(
+    meta_dir: &Path,
+    name: &str,
+    pattern: &str,
+    replacement: &str,
+    filter: &FileFilter,
+) -> Result<SedPlan> {
+    validate_worktree_name(name)?;
+    let wt_dir = find_worktree(Some(meta_dir), name)?
+        .with_context(|| format!("Worktree '{name}' not found"))?;
+    let repos = meta_cli::worktree::discover_worktree_repos(&wt_dir)?
+        .into_iter()
+        .map(|r| (r.alias, r.path))
+        .collect();
+    let mut plan = plan(repos, pattern, replacement, filter)?;
+    plan.change_group = crate::change_group::of_worktree(&wt_dir)?;
+    Ok(plan)
+}
+
+fn plan(
+    repos: Vec<(String, PathBuf)>,
+    pattern: &str,
+    replacement: &str,
+    filter: &FileFilter,
+) -> Result<SedPlan> {
+    let regex = Regex::new(pattern).with_context(|| format!("Invalid pattern '{pattern}'"))?;
+    let mut planned = Vec::new();
+    for (alias, path) in repos {
+        if !filter.includes_repo(&alias) {
+            continue;
+        }
+        let mut files = Vec::new();
+        for file in tracked_files(&path, &filter.pathspecs())? {
+            // A tracked symlink would be edited at its target, maybe outside
+            // the repo
+            if path.join(&file).is_symlink() {
+                continue;
+            }
+            let Ok(original) = std::fs::read_to_string(path.join(&file)) else {
+                continue;
+            };
+            if original.contains('\0') {
+                continue;
+            }
+            let (replaced, replacements) = replace_lines(&regex, &original, replacement);
+            if replacements > 0 && replaced != original {
+                files.push(FileEdit {
+                    path: file,
+                    replacements,
+                    original,
+                    replaced,
+                });
+            }
+        }
+        if !files.is_empty() {
+            planned.push(RepoEdits { alias, path, files });
+        }
+    }
+    Ok(SedPlan {
+        pattern: pattern.to_string(),
+        replacement: replacement.to_string(),
+        repos: planned,
+        change_group: None,
+    })
+}
+
+/// Files tracked in `repo` matching `pathspecs`.
+fn tracked_files(repo: &Path, pathspecs: &[String]) -> Result<Vec<String>> {
+    let output = crate::git_runner::output(
+        Command::new("git")
+            .args(["ls-files", "-z", "--"])
+            .args(pathspecs)
+            .current_dir(repo),
+    )
+    .context("Failed to run git ls-files")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git ls-files failed in {}: {}",
+            repo.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(nul_fields(&output.stdout).collect())
+}
+
+/// `text` with `regex` replaced on every line, and the number of matches.
+fn replace_lines(regex: &Regex, text: &str, replacement: &str) -> (String, usize) {
+    let mut out = String::with_capacity(text.len());
+    let mut count = 0;
+    for line in text.split_inclusive('\n') {
+        let (body, ending) = split_ending(line);
+        count += regex.find_iter(body).count();
+        out.push_str(&regex.replace_all(body, replacement));
+        out.push_str(ending);
+    }
+    (out, count)
+}
+
+fn split_ending(line: &str) -> (&str, &str) {
+    let body = line.trim_end_matches(['\n', '\r']);
+    (body, &line[body.len()..])
+}
+
+impl SedPlan {
+    pub fn is_empty(&self) -> bool {
+        self.repos.is_empty()
+    }
+
+    /// Number of files that would change.
+    pub fn file_count(&self) -> usize {
+        self.repos.iter().map(|r| r.files.len()).sum()
+    }
+
+    /// The changes as one unified diff without context lines, paths prefixed
+    /// with each repo's alias (`git apply --unidiff-zero` can read it).
+    pub fn preview(&self) -> String {
+        let mut out = String::new();
+        for repo in &self.repos {
+            for file in &repo.files {
+                let path = if repo.alias == "." {
+                    file.path.clone()
+                } else {
+                    format!("{}/{}", repo.alias, file.path)
+                };
+                out.push_str(&format!("--- a/{path}\n+++ b/{path}\n"));
+                push_hunks(&mut out, &file.original, &file.replaced);
+            }
+        }
+        out
+    }
+
+    /// Write the replacements. Fails, before writing anything, if a file
+    /// changed since the plan was made or became a symlink.
+    ///
+    /// Every edited file is written to a temporary file next to it first and
+    /// only then renamed into place, so a failed write leaves no repo
+    /// half-edited.
+    pub fn apply(&self) -> Result<()> {
+        crate::read_only::check("bulk sed")?;
+        for repo in &self.repos {
+            for file in &repo.files {
+                let path = repo.path.join(&file.path);
+                if path.is_symlink() {
+                    anyhow::bail!(
+                        "{} in '{}' is a symlink; not writing through it",
+                        file.path,
+                        repo.alias
+                    );
+                }
+                let current = std::fs::read_to_string(path)?;
+                if current != file.original {
+                    anyhow::bail!(
+                        "{} in '{}' changed since the preview; plan again",
+                        file.path,
+                        repo.alias
+                    );
+                }
+            }
+        }
+        let mut staged = Vec::new();
+        for repo in &self.repos {
+            let sandbox = Sandbox::new([&repo.path]);
+            for file in &repo.files {
+                let dest = sandbox.check(&repo.path.join(&file.path), "bulk sed")?;
+                let temp = stage_write(&dest, &file.replaced)
+                    .with_context(|| format!("Failed to write {}", file.path))?;
+                staged.push((temp, dest));
+            }
+        }
+        for (temp, dest) in staged {
+            temp.persist(&dest)
+                .with_context(|| format!("Failed to write {}", dest.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Commit the applied changes in each repo with `message` and a
+    /// trailer for change group `id`, or else for the worktree's
+    /// [`change_group`](Self::change_group); no trailer if neither is set.
+    /// Only the edited files are committed; a repo that fails doesn't stop
+    /// the others.
+    pub fn commit(&self, message: &str, id: Option<&ChangeGroupId>) -> Result<Vec<BulkCommit>> {
+        crate::read_only::check("bulk sed commit")?;
+        let repos = self.repos.iter().map(|repo| {
+            let paths = repo.files.iter().map(|f| f.path.as_str()).collect();
+            (repo.alias.as_str(), repo.path.as_path(), paths)
+        });
+        Ok(commit_each(
+            repos,
+            message,
+            id.or(self.change_group.as_ref()),
+        ))
+    }
+}
+
+/// `contents` in a temporary file beside `dest` with `dest`'s permissions,
+/// ready to be renamed over it.
+fn stage_write(dest: &Path, contents: &str) -> Result<tempfile::NamedTempFile> {
+    use std::io::Write;
+    let dir = dest.parent().context("File has no parent directory")?;
+    let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+    temp.write_all(contents.as_bytes())?;
+    std::fs::set_permissions(temp.path(), std::fs::metadata(dest)?.permissions())?;
+    Ok(temp)
+}
+
+/// Commit just `paths` in each repo with `message` and a trailer for change
+/// group `id`, if any; a repo that fails doesn't stop the others.
+fn commit_each<'a>(
+    repos: impl Iterator<Item = (&'a str, &'a Path, Vec<&'a str>)>,
+    message: &str,
+    id: Option<&ChangeGroupId>,
+) -> Vec<BulkCommit> {
+    let message = match id {
+        Some(id) => with_trailer(message, id),
+        None => message.to_string(),
+    };
+    repos
+        .map(
+            |(alias, path, paths)| match commit_paths(path, &paths, &message) {
+                Ok(sha) => BulkCommit {
+                    alias: alias.to_string(),
+                    commit: Some(sha),
+                    error: None,
+                },
+                Err(e) => BulkCommit {
+                    alias: alias.to_string(),
+                    commit: None,
+                    error: Some(format!("{e:#}")),
+                },
+            },
+        )
+        .collect()
+}
+
+/// How a repo's copy of a distributed file compares to the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileState {
+    Missing,
+    /// Differs from the source, edited locally or left behind by an update
+    Drifted,
+    InSync,
+}
+
+/// One distributed file in one repo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DistributedFile {
+    /// Where the canonical copy is
+    pub source: PathBuf,
+    /// Relative to the repo
+    pub path: String,
+    pub state: FileState,
+    /// The repo's copy when the plan was made
+    #[serde(skip)]
+    existing: Option<Vec<u8>>,
+}
+
+/// The distributed files of one repo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RepoDistribution {
+    pub alias: String,
+    pub path: PathBuf,
+    pub files: Vec<DistributedFile>,
+}
+
+impl RepoDistribution {
+    fn out_of_sync(&self) -> impl Iterator<Item = &DistributedFile> {
+        self.files.iter().filter(|f| f.state != FileState::InSync)
+    }
+}
+
+/// Where each shared file stands in each repo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DistributionPlan {
+    pub repos: Vec<RepoDistribution>,
+}
+
+/// Compare `source_files` (relative to `meta_dir`, or absolute) with their
+/// copies at `target_path` (a directory relative to each repo, `.` for the
+/// top) in every project of the workspace at `meta_dir`. Nothing is written
+/// until the plan is [applied](DistributionPlan::apply).
+pub fn distribute(
+    meta_dir: &Path,
+    source_files: &[PathBuf],
+    target_path: &Path,
+) -> Result<DistributionPlan> {
+    if target_path
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        anyhow::bail!(
+            "Target path {} must be relative and stay inside each repo",
+            target_path.display()
+        );
+    }
+    let mut sources = Vec::new();
+    for file in source_files {
+        let source = meta_dir.join(file);
+        let name = source
+            .file_name()
+            .with_context(|| format!("{} is not a file", file.display()))?;
+        let content = std::fs::read(&source)
+            .with_context(|| format!("Failed to read {}", source.display()))?;
+        let path = target_path.join(name).to_string_lossy().into_owned();
+        let path = path.strip_prefix("./").unwrap_or(&path).to_string();
+        sources.push((source, path, content));
+    }
+
+    let mut repos = Vec::new();
+    for project in load_projects_with_root(meta_dir, false)? {
+        let repo = meta_dir.join(&project.path);
+        if !crate::snapshot::is_git_repo(&repo) {
+            continue;
+        }
+        let mut files = Vec::new();
+        for (source, path, content) in &sources {
+            let existing = read_if_exists(&repo.join(path))?;
+            files.push(DistributedFile {
+                source: source.clone(),
+                path: path.clone(),
+                state: match &existing {
+                    Some(existing) if existing == content => FileState::InSync,
+                    Some(_) => FileState::Drifted,
+                    None => FileState::Missing,
+                },
+                existing,
+            });
+        }
+        repos.push(RepoDistribution {
+            alias: project.name,
+            path: repo,
+            files,
+        });
+    }
+    Ok(DistributionPlan { repos })
+}
+
+impl DistributionPlan {
+    /// Copies that are missing or differ from the source, as (alias, file).
+    pub fn drift(&self) -> impl Iterator<Item = (&str, &DistributedFile)> {
+        self.repos
+            .iter()
+            .flat_map(|r| r.out_of_sync().map(move |f| (r.alias.as_str(), f)))
+    }
+
+    /// Copy the sources over every missing or drifted file and stage them.
+    /// Fails, before writing anything, if a copy changed since the plan was
+    /// made or is a symlink.
+    pub fn apply(&self) -> Result<()> {
+        crate::read_only::check("distribute files")?;
+        for repo in &self.repos {
+            for file in repo.out_of_sync() {
+                let dest = repo.path.join(&file.path);
+                if dest.is_symlink() {
+                    anyhow::bail!(
+                        "{} in '{}' is a symlink; not writing through it",
+                        file.path,
+                        repo.alias
+                    );
+                }
+                if read_if_exists(&dest)? != file.existing {
+                    anyhow::bail!(
+                        "{} in '{}' changed since the plan; plan again",
+                        file.path,
+                        repo.alias
+                    );
+                }
+            }
+        }
+        for repo in &self.repos {
+            let sandbox = Sandbox::new([&repo.path]);
+            let mut staged = Vec::new();
+            for file in repo.out_of_sync() {
+                let dest = sandbox.check(&repo.path.join(&file.path), "distribute files")?;
+                if let Some(parent) = dest.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::copy(&file.source, &dest)
+                    .with_context(|| format!("Failed to write {}", dest.display()))?;
+                staged.push(file.path.as_str());
+            }
+            if staged.is_empty() {
+                continue;
+            }
+            let add = crate::git_runner::output(
+                Command::new("git")
+                    .args(["add", "--"])
+                    .args(&staged)
+                    .current_dir(&repo.path),
+            )
+            .context("Failed to run git add")?;
+            if !add.status.success() {
+                anyhow::bail!(
+                    "git add failed in '{}': {}",
+                    repo.alias,
+                    String::from_utf8_lossy(&add.stderr).trim()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Commit the applied files in each repo that needed them, with
+    /// `message` and a trailer for change group `id`.
+    pub fn commit(&self, message: &str, id: &ChangeGroupId) -> Result<Vec<BulkCommit>> {
+        crate::read_only::check("distribute files commit")?;
+        let repos = self.repos.iter().filter_map(|repo| {
+            let paths: Vec<&str> = repo.out_of_sync().map(|f| f.path.as_str()).collect();
+            (!paths.is_empty()).then_some((repo.alias.as_str(), repo.path.as_path(), paths))
+        });
+        Ok(commit_each(repos, message, Some(id)))
+    }
+}
+
+/// The contents of `path`, or `None` if it doesn't exist.
+fn read_if_exists(path: &Path) -> Result<Option<Vec<u8>>> {
+    match std::fs::read(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// One zero-context hunk per run of changed lines. Replacement is line by
+/// line, so line `i` of one side pairs with line `i` of the other, unless
+/// the replacement added line breaks; then the whole file is one hunk.
+fn push_hunks(out: &mut String, original: &str, replaced: &str) {
+    let old: Vec<&str> = original.split_inclusive('\n').collect();
+    let new: Vec<&str> = replaced.split_inclusive('\n').collect();
+    if old.len() != new.len() {
+        out.push_str(&format!("@@ -1,{} +1,{} @@\n", old.len(), new.len()));
+        old.iter().for_each(|line| push_line(out, '-', line));
+        new.iter().for_each(|line| push_line(out, '+', line));
+        return;
+    }
+    let mut i = 0;
+    while i < old.len() {
+        if old[i] == new[i] {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < old.len() && old[i] != new[i] {
+            i += 1;
+        }
+        let len = i - start;
+        out.push_str(&format!(
+            "@@ -{},{len} +{},{len} @@\n",
+            start + 1,
+            start + 1
+        ));
+        for line in &old[start..i] {
+            push_line(out, '-', line);
+        }
+        for line in &new[start..i] {
+            push_line(out, '+', line);
+        }
+    }
+}
+
+fn push_line(out: &mut String, sign: char, line: &str) {
+    out.push(sign);
+    out.push_str(line);
+    if !line.ends_with('\n') {
+        out.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+fn commit_paths(repo: &Path, paths: &[&str], message: &str) -> Result<String> {
+    let _lock = crate::lock::RepoLock::acquire(repo, "commit")?;
+    let commit = crate::git_runner::output(
+        Command::new("git")
+            .args(["commit", "-q", "-m", message, "--"])
+            .args(paths)
+            .current_dir(repo),
+    )
+    .context("Failed to run git commit")?;
+    if !commit.status.success() {
+        anyhow::bail!(
+            "git commit failed: {}",
+            String::from_utf8_lossy(&commit.stderr).trim()
+        );
+    }
+    let head = crate::git_runner::output(
+        Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .current_dir(repo),
+    )?;
+    Ok(String::from_utf8_lossy(&head.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::repo;
+
+    #[test]
+    fn replaces_line_by_line_with_groups() {
+        let regex = Regex::new(r"old_(\w+)").unwrap();
+        let (text, count) = replace_lines(&regex, "old_api(old_x)\r\nkeep\nold_y", "new_$1");
+        assert_eq!(text, "new_api(new_x)\r\nkeep\nnew_y");
+        assert_eq!(count, 3);
+        let mut out = String::new();
+        push_hunks(&mut out, "a\nb\nc\n", "a\nB\nc\n");
+        assert_eq!(out, "@@ -2,1 +2,1 @@\n-b\n+B\n");
+    }
+
+    #[test]
+    fn plans_applies_and_commits_across_repos() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ws = tmp.path();
+        std::fs::write(
+            ws.join(".meta"),
+            r#"{"projects": {"api": "git@x:api.git", "web": "git@x:web.git", "docs": "git@x:docs.git"}}"#,
+        )
+        .unwrap();
+        repo(
+            &ws.join("api"),
+            &[
+                ("src/lib.rs", "fn get_user() {}\n"),
+                ("README", "get_user\n"),
+            ],
+        );
+        repo(
+            &ws.join("web"),
+            &[("app.ts", "api.get_user();\nother();\n")],
+        );
+        repo(&ws.join("docs"), &[("index.md", "nothing here\n")]);
+
+        let filter = FileFilter {
+            exclude: vec!["README".to_string()],
+            ..Default::default()
+        };
+        let plan = sed(ws, r"\bget_user\b", "fetch_user", &filter).unwrap();
+        let aliases: Vec<&str> = plan.repos.iter().map(|r| r.alias.as_str()).collect();
+        assert_eq!(aliases, ["api", "web"]);
+        assert_eq!(plan.file_count(), 2);
+        let preview = plan.preview();
+        assert!(preview.contains("--- a/api/src/lib.rs\n+++ b/api/src/lib.rs\n@@ -1,1 +1,1 @@\n-fn get_user() {}\n+fn fetch_user() {}\n"), "{preview}");
+        assert!(preview.contains("+api.fetch_user();\n"));
+        // Nothing is written by planning
+        assert_eq!(
+            std::fs::read_to_string(ws.join("web/app.ts")).unwrap(),
+            "api.get_user();\nother();\n"
+        );
+
+        plan.apply().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(ws.join("api/src/lib.rs")).unwrap(),
+            "fn fetch_user() {}\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(ws.join("api/README")).unwrap(),
+            "get_user\n"
+        );
+
+        let id = ChangeGroupId::parse("cg-rename").unwrap();
+        let commits = plan.commit("Rename get_user", Some(&id)).unwrap();
+        assert!(commits.iter().all(|c| c.commit.is_some()), "{commits:?}");
+        for alias in ["api", "web"] {
+            assert_eq!(
+                crate::change_group::find_commits(&ws.join(alias), &id).len(),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn apply_refuses_files_changed_since_the_plan() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ws = tmp.path();
+        std::fs::write(
+            ws.join(".meta"),
+            r#"{"projects": {"api": "git@x:api.git"}}"#,
+        )
+        .unwrap();
+        repo(&ws.join("api"), &[("a.txt", "foo\n")]);
+        let plan = sed(ws, "foo", "bar", &FileFilter::default()).unwrap();
+        std::fs::write(ws.join("api/a.txt"), "foo\nmore\n").unwrap();
+        let err = plan.apply().unwrap_err().to_string();
+        assert!(err.contains("changed since the preview"), "{err}");
+        assert!(sed(ws, "(", "x", &FileFilter::default()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sed_skips_symlinks_and_rejects_bad_worktree_names() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ws = tmp.path().join("ws");
+        std::fs::create_dir(&ws).unwrap();
+        std::fs::write(
+            ws.join(".meta"),
+            r#"{"projects": {"api": "git@x:api.git"}}"#,
+        )
+        .unwrap();
+        let outside = tmp.path().join("outside.txt");
+        std::fs::write(
+            &outside, "foo
+",
+        )
+        .unwrap();
+        std::fs::create_dir_all(ws.join("api")).unwrap();
+        std::os::unix::fs::symlink(&outside, ws.join("api/link.txt")).unwrap();
+        repo(
+            &ws.join("api"),
+            &[(
+                "a.txt", "foo
+",
+            )],
+        );
+
+        let plan = sed(&ws, "foo", "bar", &FileFilter::default()).unwrap();
+        assert_eq!(plan.file_count(), 1);
+        assert_eq!(plan.repos[0].files[0].path, "a.txt");
+        plan.apply().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(ws.join("api/a.txt")).unwrap(),
+            "bar\n"
+        );
+        assert_eq!(std::fs::read_to_string(&outside).unwrap(), "foo\n");
+        // Applied through a temp file and rename; none left behind
+        assert_eq!(std::fs::read_dir(ws.join("api")).unwrap().count(), 3);
+
+        let filter = FileFilter::default();
+        assert!(sed_in_worktree(&ws, "..", "foo", "bar", &filter).is_err());
+        assert!(sed_in_worktree(&ws, "../ws", "foo", "bar", &filter).is_err());
+        let err = sed_in_worktree(&ws, "missing", "foo", "bar", &filter).unwrap_err();
+        assert!(err.to_string().contains("not found"), "{err}");
+    }
+
+    #[test]
+    fn distributes_missing_and_drifted_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ws = tmp.path();
+        std::fs::write(
+            ws.join(".meta"),
+            r#"{"projects": {"api": "git@x:api.git", "web": "git@x:web.git"}}"#,
+        )
+        .unwrap();
+        std::fs::create_dir(ws.join("shared")).unwrap();
+        std::fs::write(ws.join("shared/CODEOWNERS"), "* @org/core\n").unwrap();
+        repo(&ws.join("api"), &[(".github/CODEOWNERS", "* @org/core\n")]);
+        repo(&ws.join("web"), &[(".github/CODEOWNERS", "* @someone\n")]);
+
+        let sources = [PathBuf::from("shared/CODEOWNERS")];
+        let plan = distribute(ws, &sources, Path::new(".github")).unwrap();
+        let states: Vec<(&str, FileState)> = plan.drift().map(|(a, f)| (a, f.state)).collect();
+        assert_eq!(states, [("web", FileState::Drifted)]);
+        assert_eq!(plan.repos[0].files[0].state, FileState::InSync);
+        assert_eq!(plan.repos[0].files[0].path, ".github/CODEOWNERS");
+
+        std::fs::write(ws.join("shared/LICENSE"), "MIT\n").unwrap();
+        let sources = [
+            PathBuf::from("shared/CODEOWNERS"),
+            PathBuf::from("shared/LICENSE"),
+        ];
+        let plan = distribute(ws, &sources, Path::new(".")).unwrap();
+        assert_eq!(plan.drift().count(), 4);
+        plan.apply().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(ws.join("web/LICENSE")).unwrap(),
+            "MIT\n"
+        );
+        let id = ChangeGroupId::parse("cg-boilerplate").unwrap();
+        let commits = plan.commit("Sync shared files", &id).unwrap();
+        assert_eq!(commits.len(), 2);
+        assert!(commits.iter().all(|c| c.commit.is_some()), "{commits:?}");
+        assert_eq!(
+            distribute(ws, &sources, Path::new("."))
+                .unwrap()
+                .drift()
+                .count(),
+            0
+        );
+
+        assert!(distribute(ws, &sources, Path::new("../x")).is_err());
+    }
+
+    #[test]
+    fn distribute_apply_refuses_changed_and_symlinked_copies() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ws = tmp.path();
+        std::fs::write(
+            ws.join(".meta"),
+            r#"{"projects": {"api": "git@x:api.git"}}"#,
+        )
+        .unwrap();
+        std::fs::write(ws.join("LICENSE"), "MIT\n").unwrap();
+        repo(&ws.join("api"), &[("README", "api\n")]);
+        let sources = [PathBuf::from("LICENSE")];
+
+        let plan = distribute(ws, &sources, Path::new(".")).unwrap();
+        std::fs::write(ws.join("api/LICENSE"), "Apache-2.0\n").unwrap();
+        let err = plan.apply().unwrap_err().to_string();
+        assert!(err.contains("changed since the plan"), "{err}");
+        assert_eq!(
+            std::fs::read_to_string(ws.join("api/LICENSE")).unwrap(),
+            "Apache-2.0\n"
+        );
+
+        #[cfg(unix)]
+        {
+            let outside = tmp.path().join("outside");
+            std::fs::write(&outside, "keep\n").unwrap();
+            std::fs::remove_file(ws.join("api/LICENSE")).unwrap();
+            std::os::unix::fs::symlink(&outside, ws.join("api/LICENSE")).unwrap();
+            let plan = distribute(ws, &sources, Path::new(".")).unwrap();
+            assert!(plan.apply().is_err());
+            assert_eq!(std::fs::read_to_string(&outside).unwrap(), "keep\n");
+        }
+    }
+}
@@ -0,0 +1,553 @@
+//! Cross-repo change groups.
+//!
+//! A change group ties together the commits, branches, and PRs that make up
+//! one logical change spanning several repos. A worktree's store entry
+//! records the [`ChangeGroupId`] it belongs to ([`ChangeGroupId::generate`]
+//! makes a new one); commits are linked either with a `Meta-Change-Group:`
+//! trailer in the message ([`with_trailer`]) or after the fact with a git
+//! note ([`tag_commit`]).
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::notes::{self, MetaNote};
+use crate::worktree::types::ListPrEntry;
+
+/// Commit trailer key linking a commit to its change group.
+pub const TRAILER_KEY: &str = "Meta-Change-Group";
+
+/// Identifier of a cross-repo change, e.g. `cg-18f3a2b4c5d6-1a2b`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(transparent)]
+pub struct ChangeGroupId(String);
+
+impl ChangeGroupId {
+    /// Generate a new id from the current time and process id.
+    pub fn generate() -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        ChangeGroupId(format!(
+            "cg-{:x}-{:04x}",
+            nanos,
+            std::process::id() & 0xffff
+        ))
+    }
+
+    /// Parse an id, rejecting characters that could break trailers or greps.
+    pub fn parse(s: &str) -> Result<Self> {
+        if s.is_empty()
+            || !s
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            anyhow::bail!("Invalid change group id '{s}'");
+        }
+        Ok(ChangeGroupId(s.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ChangeGroupId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The change group recorded for the worktree at `wt_dir`, if it is in the
+/// store.
+pub fn of_worktree(wt_dir: &Path) -> Result<Option<ChangeGroupId>> {
+    crate::worktree::store::store_get(wt_dir)?
+        .and_then(|entry| entry.change_group)
+        .map(|id| ChangeGroupId::parse(&id))
+        .transpose()
+}
+
+/// The trailer line for `id`.
+pub fn trailer(id: &ChangeGroupId) -> String {
+    format!("{TRAILER_KEY}: {id}")
+}
+
+/// Append the change-group trailer to a commit message, unless already present.
+pub fn with_trailer(message: &str, id: &ChangeGroupId) -> String {
+    let line = trailer(id);
+    let message = message.trim_end();
+    if message.lines().any(|l| l.trim() == line) {
+        return format!("{message}\n");
+    }
+    let last_paragraph = message.rsplit("\n\n").next().unwrap_or("");
+    let ends_with_trailers = message.contains("\n\n")
+        && last_paragraph
+            .lines()
+            .all(|l| l.split_once(": ").is_some_and(|(k, _)| !k.contains(' ')));
+    if ends_with_trailers {
+        format!("{message}\n{line}\n")
+    } else {
+        format!("{message}\n\n{line}\n")
+    }
+}
+
+/// Link an existing commit to `id` with a git note.
+pub fn tag_commit(repo_path: &Path, rev: &str, id: &ChangeGroupId) -> Result<String> {
+    crate::read_only::check("tag commit")?;
+    notes::attach_note(
+        repo_path,
+        rev,
+        &MetaNote {
+            change_group: Some(id.to_string()),
+            ..Default::default()
+        },
+    )
+}
+
+/// Everything belonging to a change group in one repo.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangeGroupMember {
+    pub alias: String,
+    pub path: String,
+    /// Worktree the repo belongs to, when found through the store
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub worktree: Option<String>,
+    /// Linked commits, oldest first
+    pub commits: Vec<String>,
+    pub branches: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pull_requests: Vec<ListPrEntry>,
+}
+
+fn git_lines(repo_path: &Path, args: &[&str]) -> Vec<String> {
+    crate::git_runner::output(
+        Command::new("git")
+            .args(args)
+            .current_dir(repo_path)
+            .stderr(Stdio::null()),
+    )
+    .ok()
+    .filter(|o| o.status.success())
+    .map(|o| {
+        String::from_utf8_lossy(&o.stdout)
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Commits in `repo_path` linked to `id` by trailer or note, oldest first.
+pub fn find_commits(repo_path: &Path, id: &ChangeGroupId) -> Vec<String> {
+    let grep = format!("^{TRAILER_KEY}: {id}$");
+    let mut commits = git_lines(
+        repo_path,
+        &[
+            "log",
+            "--all",
+            "--reverse",
+            "--format=%H",
+            "--extended-regexp",
+            &format!("--grep={grep}"),
+        ],
+    );
+
+    let noted: Vec<String> = notes::list_notes(repo_path)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(_, note)| note.change_group.as_deref() == Some(id.as_str()))
+        .map(|(commit, _)| commit)
+        .filter(|c| !commits.contains(c))
+        .collect();
+    if !noted.is_empty() {
+        commits.extend(noted);
+        // Re-establish topological order across both sources
+        let mut args = vec!["rev-list", "--topo-order", "--reverse", "--no-walk"];
+        args.extend(commits.iter().map(String::as_str));
+        let ordered = git_lines(repo_path, &args);
+        if ordered.len() == commits.len() {
+            commits = ordered;
+        }
+    }
+
+    // Drop replayed copies (`cherry-pick -x`) of commits already listed
+    let sources = cherry_pick_sources(repo_path, &commits);
+    let copies: Vec<String> = commits
+        .iter()
+        .filter(|c| sources.get(*c).is_some_and(|src| commits.contains(src)))
+        .cloned()
+        .collect();
+    commits.retain(|c| !copies.contains(c));
+    commits
+}
+
+/// Map each commit to the commit it was cherry-picked from, if recorded with `-x`.
+fn cherry_pick_sources(repo_path: &Path, commits: &[String]) -> HashMap<String, String> {
+    if commits.is_empty() {
+        return HashMap::new();
+    }
+    let mut args = vec!["log", "--no-walk", "--format=%H%n%B%x1e"];
+    args.extend(commits.iter().map(String::as_str));
+    let output = crate::git_runner::output(
+        Command::new("git")
+            .args(&args)
+            .current_dir(repo_path)
+            .stderr(Stdio::null()),
+    );
+    let Ok(output) = output else {
+        return HashMap::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .split('\x1e')
+        .filter_map(|record| {
+            let mut lines = record.trim().lines();
+            let commit = lines.next()?.trim().to_string();
+            let source = lines.find_map(|l| {
+                l.trim()
+                    .strip_prefix("(cherry picked from commit ")
+                    .and_then(|rest| rest.strip_suffix(')'))
+            })?;
+            Some((commit, source.to_string()))
+        })
+        .collect()
+}
+
+fn member_for(
+    alias: &str,
+    repo_path: &Path,
+    id: &ChangeGroupId,
+    worktree: Option<&str>,
+    known_branch: Option<&str>,
+) -> Option<ChangeGroupMember> {
+    let commits = find_commits(repo_path, id);
+    if commits.is_empty() && known_branch.is_none() {
+        return None;
+    }
+
+    let mut branches: Vec<String> = known_branch.map(str::to_string).into_iter().collect();
+    if let Some(last) = commits.last() {
+        for b in git_lines(
+            repo_path,
+            &["branch", "--contains", last, "--format=%(refname:short)"],
+        ) {
+            if !branches.contains(&b) {
+                branches.push(b);
+            }
+        }
+    }
+
+    let pull_requests = branches
+        .iter()
+        .filter_map(|b| {
+            crate::worktree::details::gh_pr_for_branch(repo_path, b).map(|mut pr| {
+                pr.alias = alias.to_string();
+                pr
+            })
+        })
+        .collect();
+
+    Some(ChangeGroupMember {
+        alias: alias.to_string(),
+        path: repo_path.to_string_lossy().into_owned(),
+        worktree: worktree.map(str::to_string),
+        commits,
+        branches,
+        pull_requests,
+    })
+}
+
+/// Locate a change group in the given `(alias, path)` repos.
+pub fn find_in(id: &ChangeGroupId, repos: &[(String, PathBuf)]) -> Vec<ChangeGroupMember> {
+    repos
+        .iter()
+        .filter_map(|(alias, path)| member_for(alias, path, id, None, None))
+        .collect()
+}
+
+/// Locate all commits, branches, and PRs belonging to `id`.
+///
+/// Searches every worktree recorded with this change group in the store.
+/// Use [`find_in`] to also search repos outside worktrees.
+pub fn find(id: &ChangeGroupId) -> Result<Vec<ChangeGroupMember>> {
+    let store = crate::worktree::store::store_list()?;
+    let mut members = Vec::new();
+    for (path, entry) in &store.worktrees {
+        if entry.change_group.as_deref() != Some(id.as_str()) {
+            continue;
+        }
+        let root = Path::new(path);
+        for repo in &entry.repos {
+            let repo_path = if repo.alias == "." {
+                root.to_path_buf()
+            } else {
+                root.join(&repo.alias)
+            };
+            if !repo_path.exists() {
+                continue;
+            }
+            members.extend(member_for(
+                &repo.alias,
+                &repo_path,
+                id,
+                Some(&entry.name),
+                Some(&repo.branch),
+            ));
+        }
+    }
+    members.sort_by(|a, b| a.alias.cmp(&b.alias));
+    Ok(members)
+}
+
+/// Outcome of replaying a change group into one repo.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayRepoResult {
+    pub alias: String,
+    /// Commits cherry-picked in this run
+    pub applied: Vec<String>,
+    /// Commits already contained in the target branch
+    pub skipped: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Cherry-pick every commit of change group `id` onto the repos of `new_worktree`.
+///
+/// The new worktree's repos are expected to be on fresh branches (as created
+/// by `git worktree add`); each member's commits are applied to the repo with
+/// the same alias. See [`replay_members`].
+pub fn replay(id: &ChangeGroupId, new_worktree: &Path) -> Result<Vec<ReplayRepoResult>> {
+    crate::read_only::check("replay change group")?;
+    let members = find(id)?;
+    if members.iter().all(|m| m.commits.is_empty()) {
+        anyhow::bail!("No commits found for change group '{id}'");
+    }
+    Ok(replay_members(&members, new_worktree))
+}
+
+/// Cherry-pick `members`' commits (oldest first) into `new_worktree/<alias>`.
+///
+/// Commits already reachable from the target HEAD are skipped, so an
+/// interrupted replay can be re-run. On conflict the cherry-pick is aborted,
+/// leaving that repo at its last cleanly applied commit.
+pub fn replay_members(members: &[ChangeGroupMember], new_worktree: &Path) -> Vec<ReplayRepoResult> {
+    members
+        .iter()
+        .filter(|m| !m.commits.is_empty())
+        .map(|member| {
+            let target = if member.alias == "." {
+                new_worktree.to_path_buf()
+            } else {
+                new_worktree.join(&member.alias)
+            };
+            let mut result = ReplayRepoResult {
+                alias: member.alias.clone(),
+                applied: Vec::new(),
+                skipped: Vec::new(),
+                error: None,
+            };
+            if let Err(e) = replay_into(&target, &member.commits, &mut result) {
+                result.error = Some(e.to_string());
+            }
+            result
+        })
+        .collect()
+}
+
+fn replay_into(target: &Path, commits: &[String], result: &mut ReplayRepoResult) -> Result<()> {
+    if !target.exists() {
+        anyhow::bail!("Repo not present in worktree: {}", target.display());
+    }
+    let _lock = crate::lock::RepoLock::acquire(target, "change group replay")?;
+
+    for commit in commits {
+        let contained = crate::git_runner::status(
+            Command::new("git")
+                .args(["merge-base", "--is-ancestor", commit, "HEAD"])
+                .current_dir(target)
+                .stderr(Stdio::null()),
+        )?
+        .success();
+        let already_picked = !git_lines(
+            target,
+            &[
+                "log",
+                "HEAD",
+                "--format=%H",
+                "--fixed-strings",
+                &format!("--grep=(cherry picked from commit {commit})"),
+            ],
+        )
+        .is_empty();
+        if contained || already_picked {
+            result.skipped.push(commit.clone());
+            continue;
+        }
+
+        let output = crate::git_runner::output(
+            Command::new("git")
+                .args(["cherry-pick", "-x", "--allow-empty", commit])
+                .current_dir(target),
+        )?;
+        if !output.status.success() {
+            let _ = crate::git_runner::output(
+                Command::new("git")
+                    .args(["cherry-pick", "--abort"])
+                    .current_dir(target),
+            );
+            anyhow::bail!(
+                "Cherry-pick of {} failed: {}",
+                &commit[..commit.len().min(12)],
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        result.applied.push(commit.clone());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{git, repo};
+
+    fn commit(dir: &Path, message: &str) -> String {
+        git(dir, &["commit", "-q", "--allow-empty", "-m", message]);
+        git(dir, &["rev-parse", "HEAD"])
+    }
+
+    // ── ids and trailers ────────────────────────────────────
+
+    #[test]
+    fn generated_ids_parse() {
+        let id = ChangeGroupId::generate();
+        assert!(id.as_str().starts_with("cg-"));
+        assert_eq!(ChangeGroupId::parse(id.as_str()).unwrap(), id);
+    }
+
+    #[test]
+    fn parse_rejects_unsafe_ids() {
+        assert!(ChangeGroupId::parse("").is_err());
+        assert!(ChangeGroupId::parse("cg 1").is_err());
+        assert!(ChangeGroupId::parse("cg$(x)").is_err());
+    }
+
+    #[test]
+    fn with_trailer_appends_paragraph() {
+        let id = ChangeGroupId::parse("cg-1").unwrap();
+        assert_eq!(
+            with_trailer("Fix bug", &id),
+            "Fix bug\n\nMeta-Change-Group: cg-1\n"
+        );
+    }
+
+    #[test]
+    fn with_trailer_joins_existing_trailers() {
+        let id = ChangeGroupId::parse("cg-1").unwrap();
+        let msg = "Fix bug\n\nSigned-off-by: A <a@b.c>";
+        assert_eq!(
+            with_trailer(msg, &id),
+            "Fix bug\n\nSigned-off-by: A <a@b.c>\nMeta-Change-Group: cg-1\n"
+        );
+        // Idempotent
+        assert_eq!(
+            with_trailer(&with_trailer(msg, &id), &id),
+            with_trailer(msg, &id)
+        );
+    }
+
+    // ── discovery ───────────────────────────────────────────
+
+    #[test]
+    fn find_in_uses_trailers_and_notes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let api = tmp.path().join("api");
+        let web = tmp.path().join("web");
+        repo(&api, &[]);
+        repo(&web, &[]);
+        let id = ChangeGroupId::parse("cg-42").unwrap();
+
+        let by_trailer = commit(&api, &with_trailer("api change", &id));
+        let by_note = commit(&web, "web change");
+        tag_commit(&web, "HEAD", &id).unwrap();
+        commit(&web, "unrelated");
+
+        let members = find_in(&id, &[("api".to_string(), api), ("web".to_string(), web)]);
+        assert_eq!(members.len(), 2);
+        assert_eq!(members[0].commits, vec![by_trailer]);
+        assert_eq!(members[1].commits, vec![by_note]);
+        assert_eq!(members[1].branches, vec!["main"]);
+    }
+
+    #[test]
+    fn find_in_skips_repos_without_commits() {
+        let tmp = tempfile::tempdir().unwrap();
+        repo(tmp.path(), &[]);
+        let id = ChangeGroupId::parse("cg-none").unwrap();
+        assert!(find_in(&id, &[(".".to_string(), tmp.path().to_path_buf())]).is_empty());
+    }
+
+    // ── replay ──────────────────────────────────────────────
+
+    #[test]
+    fn replay_applies_commits_to_new_worktree() {
+        let tmp = tempfile::tempdir().unwrap();
+        let api = tmp.path().join("api");
+        repo(&api, &[]);
+        let base = git_lines(&api, &["rev-parse", "HEAD"]).remove(0);
+        let id = ChangeGroupId::parse("cg-replay").unwrap();
+        std::fs::write(api.join("a.txt"), "a").unwrap();
+        git(&api, &["add", "a.txt"]);
+        commit(&api, &with_trailer("add a", &id));
+        commit(&api, &with_trailer("follow-up", &id));
+
+        let new_root = tmp.path().join("wt");
+        git(
+            &api,
+            &[
+                "worktree",
+                "add",
+                "-q",
+                "-b",
+                "replayed",
+                new_root.join("api").to_str().unwrap(),
+                &base,
+            ],
+        );
+
+        let members = find_in(&id, &[("api".to_string(), api.clone())]);
+        let results = replay_members(&members, &new_root);
+        assert_eq!(results.len(), 1);
+        assert!(results[0].error.is_none(), "{:?}", results[0].error);
+        assert_eq!(results[0].applied.len(), 2);
+        assert!(new_root.join("api").join("a.txt").exists());
+
+        // Copies on the new branch are not reported as separate members' commits
+        let members = find_in(&id, &[("api".to_string(), api)]);
+        assert_eq!(members[0].commits.len(), 2);
+
+        // Re-running is a no-op
+        let again = replay_members(&members, &new_root);
+        assert!(again[0].applied.is_empty());
+        assert_eq!(again[0].skipped.len(), 2);
+    }
+
+    #[test]
+    fn replay_reports_missing_target_repo() {
+        let tmp = tempfile::tempdir().unwrap();
+        let member = ChangeGroupMember {
+            alias: "gone".to_string(),
+            path: String::new(),
+            worktree: None,
+            commits: vec!["abc".to_string()],
+            branches: vec![],
+            pull_requests: vec![],
+        };
+        let results = replay_members(&[member], tmp.path());
+        assert!(results[0].error.as_deref().unwrap().contains("not present"));
+    }
+}
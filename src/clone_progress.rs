@@ -0,0 +1,356 @@
+//! Clone progress reporting from git's `--progress` output.
+//!
+//! `git clone --progress` writes lines such as
+//! `Receiving objects:  45% (450/1000), 1.20 MiB | 2.40 MiB/s` to stderr,
+//! separated by `\r` while a phase is in progress. [`output_with_events`]
+//! reads them as they arrive and reports each as a [`CloneProgressEvent`]
+//! with the [`ClonePhase`] and an overall percentage for the repo, so a
+//! multi-repo display can show real per-repo progress; [`output_with_progress`]
+//! drives a [`ProgressBar`] from those events.
+
+use indicatif::ProgressBar;
+use serde::Serialize;
+use std::io::{self, Read};
+use std::process::{Command, Output, Stdio};
+use std::time::Duration;
+
+use crate::process_timeout;
+
+/// Stage of a clone, in the order git runs them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClonePhase {
+    /// The server counting objects to send
+    Counting,
+    /// The server compressing objects
+    Compressing,
+    /// Downloading objects
+    Receiving,
+    /// Resolving deltas locally
+    Resolving,
+    /// Checking out files
+    CheckingOut,
+}
+
+impl ClonePhase {
+    /// Phase for a progress line's phase name. `None` for phases without a
+    /// share of the overall progress (e.g. "Enumerating objects").
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Counting objects" => Some(ClonePhase::Counting),
+            "Compressing objects" => Some(ClonePhase::Compressing),
+            "Receiving objects" => Some(ClonePhase::Receiving),
+            "Resolving deltas" => Some(ClonePhase::Resolving),
+            "Updating files" | "Checking out files" => Some(ClonePhase::CheckingOut),
+            _ => None,
+        }
+    }
+
+    /// Range of the overall percentage this phase covers; receiving dominates.
+    fn span(self) -> (u8, u8) {
+        match self {
+            ClonePhase::Counting => (0, 5),
+            ClonePhase::Compressing => (5, 10),
+            ClonePhase::Receiving => (10, 85),
+            ClonePhase::Resolving => (85, 95),
+            ClonePhase::CheckingOut => (95, 100),
+        }
+    }
+
+    /// Overall clone percentage when this phase is at `percent`.
+    pub fn overall_percent(self, percent: u8) -> u8 {
+        let (start, end) = self.span();
+        start + ((end - start) as u16 * percent.min(100) as u16 / 100) as u8
+    }
+}
+
+/// Structured progress of one repo's clone.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CloneProgressEvent {
+    pub repo: String,
+    pub phase: ClonePhase,
+    /// Progress of the current phase
+    pub phase_percent: u8,
+    /// Progress of the whole clone
+    pub overall_percent: u8,
+    pub current: u64,
+    pub total: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transfer: Option<String>,
+}
+
+impl CloneProgressEvent {
+    /// Event for `progress` of `repo`, or `None` if the line's phase isn't
+    /// part of the clone progress.
+    pub fn from_progress(repo: &str, progress: &GitProgress) -> Option<Self> {
+        let phase = ClonePhase::from_name(&progress.phase)?;
+        Some(CloneProgressEvent {
+            repo: repo.to_string(),
+            phase,
+            phase_percent: progress.percent,
+            overall_percent: phase.overall_percent(progress.percent),
+            current: progress.current,
+            total: progress.total,
+            transfer: progress.transfer.clone(),
+        })
+    }
+}
+
+/// One parsed progress line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitProgress {
+    /// Phase name, e.g. "Receiving objects" (a `remote: ` prefix is dropped)
+    pub phase: String,
+    pub percent: u8,
+    pub current: u64,
+    pub total: u64,
+    /// Transfer summary after the counts, e.g. "1.20 MiB | 2.40 MiB/s"
+    pub transfer: Option<String>,
+}
+
+impl std::fmt::Display for GitProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}% ({}/{})",
+            self.phase, self.percent, self.current, self.total
+        )?;
+        if let Some(transfer) = &self.transfer {
+            write!(f, ", {transfer}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a single progress line. Returns `None` for anything else.
+pub fn parse_progress_line(line: &str) -> Option<GitProgress> {
+    let line = line.trim();
+    let line = line.strip_prefix("remote:").unwrap_or(line).trim();
+    let (phase, rest) = line.split_once(':')?;
+    let rest = rest.trim();
+    let (percent, rest) = rest.split_once('%')?;
+    let percent: u8 = percent.trim().parse().ok()?;
+
+    let rest = rest.trim().strip_prefix('(')?;
+    let (counts, rest) = rest.split_once(')')?;
+    let (current, total) = counts.split_once('/')?;
+    let current = current.trim().parse().ok()?;
+    let total = total.trim().parse().ok()?;
+
+    let transfer = rest
+        .trim()
+        .trim_start_matches(',')
+        .trim()
+        .trim_end_matches("done.")
+        .trim()
+        .trim_end_matches(',')
+        .trim();
+    Some(GitProgress {
+        phase: phase.trim().to_string(),
+        percent: percent.min(100),
+        current,
+        total,
+        transfer: (!transfer.is_empty()).then(|| transfer.to_string()),
+    })
+}
+
+/// Show `progress` on `pb` as an overall percentage, prefixed with `label`.
+///
+/// Phases outside the clone progress only update the message.
+pub fn apply_progress(pb: &ProgressBar, label: &str, progress: &GitProgress) {
+    if let Some(event) = CloneProgressEvent::from_progress(label, progress) {
+        pb.set_length(100);
+        pb.set_position(event.overall_percent.into());
+    }
+    pb.set_message(format!("{label}: {progress}"));
+}
+
+/// Run `cmd` (which must write git progress to stderr), updating `pb` as
+/// progress lines arrive. Returns the collected output like [`Command::output`].
+///
+/// Stdout is discarded.
+pub fn output_with_progress(
+    cmd: &mut Command,
+    pb: &ProgressBar,
+    label: &str,
+) -> io::Result<Output> {
+    output_with_progress_timeout(cmd, pb, label, None)
+}
+
+/// Like [`output_with_progress`], killing the command after `timeout`
+/// (see [`process_timeout`]).
+pub fn output_with_progress_timeout(
+    cmd: &mut Command,
+    pb: &ProgressBar,
+    label: &str,
+    timeout: Option<Duration>,
+) -> io::Result<Output> {
+    output_with_lines(cmd, timeout, |progress| apply_progress(pb, label, progress))
+}
+
+/// Run `cmd` like [`output_with_progress`], passing a [`CloneProgressEvent`]
+/// for `repo` to `on_event` for each clone progress line.
+pub fn output_with_events(
+    cmd: &mut Command,
+    repo: &str,
+    mut on_event: impl FnMut(CloneProgressEvent),
+) -> io::Result<Output> {
+    output_with_lines(cmd, None, |progress| {
+        if let Some(event) = CloneProgressEvent::from_progress(repo, progress) {
+            on_event(event);
+        }
+    })
+}
+
+fn output_with_lines(
+    cmd: &mut Command,
+    timeout: Option<Duration>,
+    mut on_progress: impl FnMut(&GitProgress),
+) -> io::Result<Output> {
+    if crate::git_runner::routes(cmd) {
+        return crate::git_runner::output(cmd);
+    }
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    let (mut child, watchdog) = process_timeout::spawn(cmd, timeout)?;
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+
+    let mut collected = Vec::new();
+    let mut line = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = stderr.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        for &byte in &buf[..n] {
+            if byte == b'\r' || byte == b'\n' {
+                if let Some(progress) = parse_progress_line(&String::from_utf8_lossy(&line)) {
+                    on_progress(&progress);
+                } else if !line.is_empty() {
+                    // Keep non-progress lines for error reporting
+                    collected.extend_from_slice(&line);
+                    collected.push(b'\n');
+                }
+                line.clear();
+            } else {
+                line.push(byte);
+            }
+        }
+    }
+    if !line.is_empty() {
+        collected.extend_from_slice(&line);
+    }
+
+    let status = child.wait()?;
+    if let (Some(true), Some(timeout)) = (watchdog.map(process_timeout::Watchdog::stop), timeout) {
+        return Err(process_timeout::timed_out_error(timeout));
+    }
+    Ok(Output {
+        status,
+        stdout: Vec::new(),
+        stderr: collected,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_receiving_objects() {
+        let p = parse_progress_line("Receiving objects:  45% (450/1000), 1.20 MiB | 2.40 MiB/s")
+            .unwrap();
+        assert_eq!(p.phase, "Receiving objects");
+        assert_eq!((p.percent, p.current, p.total), (45, 450, 1000));
+        assert_eq!(p.transfer.as_deref(), Some("1.20 MiB | 2.40 MiB/s"));
+    }
+
+    #[test]
+    fn parses_remote_and_done_lines() {
+        let p = parse_progress_line("remote: Counting objects: 100% (10/10), done.").unwrap();
+        assert_eq!(p.phase, "Counting objects");
+        assert_eq!(p.transfer, None);
+
+        let p = parse_progress_line(
+            "Receiving objects: 100% (1000/1000), 5.00 MiB | 3.10 MiB/s, done.",
+        )
+        .unwrap();
+        assert_eq!(p.transfer.as_deref(), Some("5.00 MiB | 3.10 MiB/s"));
+        assert_eq!(
+            p.to_string(),
+            "Receiving objects 100% (1000/1000), 5.00 MiB | 3.10 MiB/s"
+        );
+    }
+
+    #[test]
+    fn ignores_other_lines() {
+        for line in [
+            "Cloning into 'repo'...",
+            "remote: Enumerating objects: 10, done.",
+            "fatal: repository not found",
+            "",
+        ] {
+            assert!(parse_progress_line(line).is_none(), "{line}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn streams_progress_and_keeps_other_output() {
+        let pb = ProgressBar::hidden();
+        let mut cmd = Command::new("sh");
+        cmd.args([
+            "-c",
+            "printf 'Receiving objects:  50%% (1/2)\\rReceiving objects: 100%% (2/2), done.\\nfatal: oops\\n' >&2",
+        ]);
+        let out = output_with_progress(&mut cmd, &pb, "repo").unwrap();
+        assert!(out.status.success());
+        assert_eq!(String::from_utf8_lossy(&out.stderr), "fatal: oops\n");
+        // Receiving done = 85% of the whole clone
+        assert_eq!(pb.position(), 85);
+        assert_eq!(pb.length(), Some(100));
+        assert_eq!(pb.message(), "repo: Receiving objects 100% (2/2)");
+    }
+
+    #[test]
+    fn phases_map_to_overall_percent() {
+        assert_eq!(ClonePhase::Counting.overall_percent(0), 0);
+        assert_eq!(ClonePhase::Receiving.overall_percent(50), 47);
+        assert_eq!(ClonePhase::Resolving.overall_percent(100), 95);
+        assert_eq!(ClonePhase::CheckingOut.overall_percent(100), 100);
+        assert_eq!(ClonePhase::from_name("Enumerating objects"), None);
+
+        let p = parse_progress_line("remote: Compressing objects:  40% (4/10)").unwrap();
+        let event = CloneProgressEvent::from_progress("api", &p).unwrap();
+        assert_eq!(event.phase, ClonePhase::Compressing);
+        assert_eq!((event.phase_percent, event.overall_percent), (40, 7));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn emits_events_per_phase() {
+        let mut cmd = Command::new("sh");
+        cmd.args([
+            "-c",
+            "printf 'remote: Counting objects: 100%% (3/3), done.\nReceiving objects:  50%% (1/2)\rResolving deltas: 100%% (1/1), done.\n' >&2",
+        ]);
+        let mut events = Vec::new();
+        let out = output_with_events(&mut cmd, "api", |e| events.push(e)).unwrap();
+        assert!(out.status.success());
+        let phases: Vec<(ClonePhase, u8)> = events
+            .iter()
+            .map(|e| (e.phase, e.overall_percent))
+            .collect();
+        assert_eq!(
+            phases,
+            [
+                (ClonePhase::Counting, 5),
+                (ClonePhase::Receiving, 47),
+                (ClonePhase::Resolving, 95)
+            ]
+        );
+        assert!(events.iter().all(|e| e.repo == "api"));
+    }
+}
@@ -1,12 +1,26 @@
+use crate::collision::{CloneAction, CloneResult, CollisionPolicy};
+use crate::credentials::{AuthFailureSummary, HttpsTokens};
+use crate::git_runner::GitRunner;
+use crate::outcome::{FailureCategory, OperationOutcome, RepoFailure};
+use crate::preflight::{PreflightOptions, PreflightProblem, PreflightReport};
+use crate::project_filter::ProjectFilter;
+use crate::project_options::{load_project_options, ProjectOperation, ProjectOptions};
+use crate::reference_store::ReferenceStore;
+use crate::sandbox::validate_project_path;
+use crate::ssh_multiplexing::{
+    load_host_options, ssh_command_for_url, MultiplexingConfig, NewHostKeys,
+};
 use log::{debug, warn};
 use meta_core::config;
-use std::collections::{BTreeSet, HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Mutex;
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 /// A clone task representing a single repository to clone
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CloneTask {
     /// Display name for progress output
     pub name: String,
@@ -18,6 +32,470 @@ pub struct CloneTask {
     pub depth_level: usize,
     /// Whether this project is itself a meta-repo (declared with `meta: true` in config)
     pub is_meta: bool,
+    /// Per-project git options from the `.meta` config
+    pub options: ProjectOptions,
+    /// `GIT_SSH_COMMAND` selecting the per-host identity from `.meta`, if any
+    pub ssh_command: Option<String>,
+    /// Failed attempts so far (see [`RetryPolicy`])
+    #[serde(default)]
+    pub attempts: u32,
+    /// Tags of the project in `.meta`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// Order in which queued tasks are taken by workers.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SchedulingPolicy {
+    /// Newest task first; nested repos are cloned as soon as they're found
+    #[default]
+    Lifo,
+    /// Oldest task first, in discovery order
+    Fifo,
+    /// Least nested task first, oldest first within a level
+    ShallowestFirst,
+    /// Tasks tagged with an earlier tag in the list first, untagged last,
+    /// oldest first otherwise
+    PriorityTags(Vec<String>),
+}
+
+impl SchedulingPolicy {
+    /// Index in `pending` (oldest first) of the task to take next.
+    fn next_index(&self, pending: &[CloneTask]) -> Option<usize> {
+        match self {
+            SchedulingPolicy::Lifo => pending.len().checked_sub(1),
+            SchedulingPolicy::Fifo => (!pending.is_empty()).then_some(0),
+            SchedulingPolicy::ShallowestFirst => pending
+                .iter()
+                .enumerate()
+                .min_by_key(|(i, t)| (t.depth_level, *i))
+                .map(|(i, _)| i),
+            SchedulingPolicy::PriorityTags(tags) => pending
+                .iter()
+                .enumerate()
+                .min_by_key(|(i, t)| {
+                    let rank = tags
+                        .iter()
+                        .position(|tag| t.tags.contains(tag))
+                        .unwrap_or(tags.len());
+                    (rank, *i)
+                })
+                .map(|(i, _)| i),
+        }
+    }
+}
+
+/// How transient clone failures are retried.
+///
+/// A task that fails with a network, DNS, or SSH rate-limit error is requeued
+/// after an exponentially growing delay until `max_attempts` is reached.
+/// Permanent failures (auth, not found) are never retried.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Total attempts per task, including the first (1 disables retries)
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubles for each further retry
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Random spread applied to each delay, as a fraction (0.2 = ±20%)
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(30),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy that never retries.
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Delay before retry number `retry` (1-based), without jitter.
+    pub fn base_delay_for(&self, retry: u32) -> Duration {
+        let factor = 1u32 << retry.saturating_sub(1).min(16);
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+
+    /// Delay before retry number `retry`, with jitter applied.
+    pub fn delay_for(&self, retry: u32) -> Duration {
+        let base = self.base_delay_for(retry);
+        if self.jitter <= 0.0 {
+            return base;
+        }
+        // Cheap pseudo-random value in [-1, 1]; no need for a real RNG here
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        let unit = (nanos % 2001) as f64 / 1000.0 - 1.0;
+        base.mul_f64((1.0 + self.jitter.min(1.0) * unit).max(0.0))
+    }
+
+    /// Whether a task that failed with `error` on its `attempts`-th try
+    /// should be retried: only [transient](FailureCategory::is_transient)
+    /// failures are.
+    pub fn should_retry(&self, attempts: u32, error: &str) -> bool {
+        attempts < self.max_attempts && FailureCategory::classify(error).is_transient()
+    }
+}
+
+/// What a dry run would do with each project of a workspace, as a tree
+/// (see [`CloneQueue::plan`]).
+#[derive(Debug, Clone, Serialize)]
+pub struct ClonePlan {
+    pub root: PathBuf,
+    pub projects: Vec<PlanNode>,
+}
+
+impl ClonePlan {
+    /// Repos the run would clone, at every level.
+    pub fn clone_count(&self) -> usize {
+        fn count(nodes: &[PlanNode]) -> usize {
+            nodes
+                .iter()
+                .map(|n| usize::from(n.action == PlanAction::Clone) + count(&n.children))
+                .sum()
+        }
+        count(&self.projects)
+    }
+}
+
+/// One project of a [`ClonePlan`].
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanNode {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    pub target_path: PathBuf,
+    pub depth_level: usize,
+    /// Declared `meta: true` or already has a nested `.meta`
+    pub is_meta: bool,
+    pub action: PlanAction,
+    /// Projects of the nested `.meta`, if it's already cloned; a meta repo
+    /// still to be cloned has none until it is
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<PlanNode>,
+}
+
+/// What a run would do with a project.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanAction {
+    Clone,
+    /// The target already exists
+    Present,
+    /// Disabled or skipped for clones in `.meta`
+    Disabled,
+    /// Not selected by the project filter
+    Filtered,
+    /// The URL is quarantined
+    Quarantined,
+    /// No repo URL to clone from
+    NoUrl,
+}
+
+/// Saved state of a persistent [`CloneQueue`], in `~/.meta/clone-queue.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CloneQueueState {
+    pub git_depth: Option<String>,
+    pub meta_depth: Option<usize>,
+    pub clone_filter: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shallow_since: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shallow_exclude: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub protocol_v2: bool,
+    /// Projects the run was limited to
+    #[serde(default, skip_serializing_if = "ProjectFilter::is_empty")]
+    pub project_filter: ProjectFilter,
+    #[serde(default)]
+    pub scheduling: SchedulingPolicy,
+    #[serde(default)]
+    pub retry_policy: RetryPolicy,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference_store: Option<ReferenceStore>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_timeout: Option<Duration>,
+    #[serde(default)]
+    pub collision_policy: CollisionPolicy,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub https_fallback: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror_root: Option<PathBuf>,
+    #[serde(default)]
+    pub new_host_keys: NewHostKeys,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub nested_setup: bool,
+    /// Checks run before the workers start; `None` if disabled
+    #[serde(default = "default_preflight")]
+    pub preflight: Option<PreflightOptions>,
+    /// Transfer limit set by the caller; `None` if it came from `.meta`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_transfers: Option<Option<usize>>,
+    /// Every task discovered during the run
+    pub tasks: Vec<CloneTask>,
+    /// Target paths cloned successfully
+    pub completed: BTreeSet<PathBuf>,
+    /// Target paths that failed for good
+    pub failed: BTreeSet<PathBuf>,
+    /// Why each failed target failed
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub failure_categories: BTreeMap<PathBuf, FailureCategory>,
+}
+
+fn default_preflight() -> Option<PreflightOptions> {
+    Some(PreflightOptions::default())
+}
+
+impl CloneQueueState {
+    /// Tasks that still need cloning (pending, in flight, or failed).
+    pub fn remaining(&self) -> impl Iterator<Item = &CloneTask> {
+        self.tasks
+            .iter()
+            .filter(|t| !self.completed.contains(&t.target_path))
+    }
+}
+
+fn state_paths() -> (PathBuf, PathBuf) {
+    let data_path = meta_core::data_dir::data_file("clone-queue");
+    let lock_path = data_path.with_extension("lock");
+    (data_path, lock_path)
+}
+
+/// The saved state of the last persistent clone run, if any.
+pub fn read_saved_state() -> anyhow::Result<Option<CloneQueueState>> {
+    let (data_path, _) = state_paths();
+    if !data_path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(meta_core::store::read(&data_path)?))
+}
+
+/// Delete the saved clone queue state (e.g. after a run finished cleanly).
+pub fn clear_saved_state() -> anyhow::Result<()> {
+    let (data_path, _) = state_paths();
+    match std::fs::remove_file(&data_path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Receives progress from [`CloneQueue::run`], e.g. for a GUI, TUI, or
+/// logger.
+///
+/// Methods are called from the worker threads; all default to doing nothing.
+/// `()` is a reporter that ignores everything.
+pub trait CloneReporter: Sync {
+    /// `task` is queued: reported when the run starts for the tasks already
+    /// queued, then as nested `.meta` files add more.
+    fn discovered(&self, _task: &CloneTask) {}
+
+    /// The clone of `task` contains the meta config `meta_path`, which added
+    /// `added` tasks (each reported with [`discovered`](Self::discovered)
+    /// first).
+    fn nested_meta_found(&self, _task: &CloneTask, _meta_path: &Path, _added: usize) {}
+
+    /// A worker started cloning `task`.
+    fn started(&self, _task: &CloneTask) {}
+
+    /// A clone attempt failed transiently and will be retried after `delay`.
+    fn retrying(&self, _task: &CloneTask, _error: &str, _delay: Duration) {}
+
+    /// The repo cloning into `target_path` failed for good, after any
+    /// retries; reported before [`finished`](Self::finished).
+    fn failed(&self, _target_path: &Path, _failure: &RepoFailure) {}
+
+    /// A repo is done; `completed` of `discovered` repos are finished so far.
+    fn finished(&self, _result: &RepoCloneResult, _completed: usize, _discovered: usize) {}
+}
+
+impl CloneReporter for () {}
+
+/// Alias of [`CloneReporter`], for callers that know it by that name.
+pub use self::CloneReporter as CloneObserver;
+
+/// How one repo of a [`CloneQueue::run`] ended.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum RepoCloneStatus {
+    /// Cloned, skipped, adopted, or replaced, per the collision policy
+    Done(CloneResult),
+    /// Failed for good, after any retries
+    Failed {
+        category: FailureCategory,
+        message: String,
+    },
+}
+
+/// Per-repo result of [`CloneQueue::run`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RepoCloneResult {
+    pub name: String,
+    pub url: String,
+    pub target_path: PathBuf,
+    pub depth_level: usize,
+    #[serde(flatten)]
+    pub status: RepoCloneStatus,
+    /// Time the last clone attempt took, without setup commands
+    pub duration_ms: u64,
+    /// Size of a fresh clone
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<CloneStats>,
+    /// The project's setup commands, run after a fresh clone
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub setup: Vec<crate::setup::SetupStepResult>,
+}
+
+/// Size of a cloned repo's object store, from `git count-objects -v`.
+///
+/// Approximates what the clone downloaded; objects borrowed from a reference
+/// repo or mirror are counted too.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CloneStats {
+    pub bytes: u64,
+    pub objects: u64,
+}
+
+impl CloneStats {
+    /// Measure the repo at `repo`. `None` if git can't tell.
+    pub fn measure(repo: &Path) -> Option<Self> {
+        let output = crate::git_runner::output(
+            std::process::Command::new("git")
+                .args(["count-objects", "-v"])
+                .current_dir(repo),
+        )
+        .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let mut stats = CloneStats::default();
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let Some((key, value)) = line.split_once(": ") else {
+                continue;
+            };
+            let Ok(value) = value.trim().parse::<u64>() else {
+                continue;
+            };
+            match key {
+                "count" | "in-pack" => stats.objects += value,
+                // Reported in KiB
+                "size" | "size-pack" => stats.bytes += value * 1024,
+                _ => {}
+            }
+        }
+        Some(stats)
+    }
+}
+
+/// Result of [`CloneQueue::run`], serializable for CI: counts by result,
+/// every repo in the order it finished, and the categorized outcome.
+#[derive(Debug, Clone, Serialize)]
+pub struct CloneReport {
+    /// Repos cloned (or replaced with a fresh clone)
+    pub succeeded: usize,
+    pub failed: usize,
+    /// Existing targets that were skipped or adopted instead of cloned
+    pub skipped_existing: usize,
+    pub duration_ms: u64,
+    /// Total size of the fresh clones (see [`CloneStats`])
+    pub bytes: u64,
+    pub objects: u64,
+    pub per_repo: Vec<RepoCloneResult>,
+    pub outcome: OperationOutcome,
+    /// Failed repos whose git output shows an authentication failure
+    #[serde(skip_serializing_if = "AuthFailureSummary::is_empty")]
+    pub auth_failures: AuthFailureSummary,
+}
+
+impl CloneReport {
+    pub fn new(
+        per_repo: Vec<RepoCloneResult>,
+        outcome: OperationOutcome,
+        duration: Duration,
+    ) -> Self {
+        let mut report = CloneReport {
+            succeeded: 0,
+            failed: 0,
+            skipped_existing: 0,
+            duration_ms: crate::hooks::millis(duration),
+            bytes: 0,
+            objects: 0,
+            per_repo,
+            outcome,
+            auth_failures: AuthFailureSummary::new(),
+        };
+        for repo in &report.per_repo {
+            if let Some(stats) = repo.stats {
+                report.bytes += stats.bytes;
+                report.objects += stats.objects;
+            }
+            match &repo.status {
+                RepoCloneStatus::Failed { message, .. } => {
+                    report.failed += 1;
+                    report.auth_failures.record(&repo.name, &repo.url, message);
+                }
+                RepoCloneStatus::Done(result) => match result.action {
+                    CloneAction::Cloned | CloneAction::Replaced { .. } => report.succeeded += 1,
+                    CloneAction::Skipped | CloneAction::Adopted => report.skipped_existing += 1,
+                },
+            }
+        }
+        report
+    }
+
+    /// Repos that failed for good.
+    pub fn failures(&self) -> impl Iterator<Item = &RepoCloneResult> {
+        self.per_repo
+            .iter()
+            .filter(|r| matches!(r.status, RepoCloneStatus::Failed { .. }))
+    }
+
+    /// Up to `n` repos that took longest, slowest first.
+    pub fn slowest(&self, n: usize) -> Vec<&RepoCloneResult> {
+        let mut repos: Vec<&RepoCloneResult> = self.per_repo.iter().collect();
+        repos.sort_by_key(|r| std::cmp::Reverse(r.duration_ms));
+        repos.truncate(n);
+        repos
+    }
+}
+
+/// Longest an idle worker sleeps before checking the queue again.
+const IDLE_POLL: Duration = Duration::from_millis(50);
+
+/// Caps how many clones download at once, across all workers.
+#[derive(Default)]
+struct TransferLimiter {
+    /// (max concurrent transfers, None = unlimited; transfers in progress)
+    state: Mutex<(Option<usize>, usize)>,
+    freed: Condvar,
+}
+
+/// A running clone's share of the transfer limit; released on drop.
+///
+/// See [`CloneQueue::acquire_transfer`].
+pub struct TransferPermit<'a> {
+    limiter: &'a TransferLimiter,
+}
+
+impl Drop for TransferPermit<'_> {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.1 -= 1;
+        self.limiter.freed.notify_one();
+    }
 }
 
 /// Thread-safe queue for managing clone tasks with dynamic discovery
@@ -26,16 +504,73 @@ pub struct CloneQueue {
     pending: Mutex<Vec<CloneTask>>,
     /// Completed task paths (to avoid duplicates)
     completed: Mutex<HashSet<PathBuf>>,
-    /// Failed task paths
-    failed: Mutex<HashSet<PathBuf>>,
+    /// Target paths that failed for good and why, for the clone report
+    failures: Mutex<Vec<(PathBuf, RepoFailure)>>,
+    /// SSH hosts that rate-limited or dropped a clone
+    rate_limited_hosts: Mutex<BTreeSet<String>>,
+    /// Projects excluded from cloning via `disabled` or `skip`
+    skipped: Mutex<BTreeSet<String>>,
+    /// Projects skipped because their remote is quarantined
+    quarantined: Mutex<BTreeSet<String>>,
+    /// Failed tasks waiting to be retried, with the time they become due
+    retrying: Mutex<Vec<(Instant, CloneTask)>>,
+    /// Retry policy for transient failures
+    retry_policy: RetryPolicy,
     /// Total tasks discovered (for progress display)
     total_discovered: AtomicUsize,
     /// Total tasks completed
     total_completed: AtomicUsize,
     /// Git depth argument (if any)
     git_depth: Option<String>,
+    /// `--shallow-since` date for projects that don't limit their own history
+    shallow_since: Option<String>,
+    /// `--shallow-exclude` refs for projects that don't limit their own history
+    shallow_exclude: Vec<String>,
+    /// Whether every clone forces wire protocol v2
+    protocol_v2: bool,
+    /// Partial clone filter for projects that don't set their own (if any)
+    clone_filter: Option<String>,
+    /// Projects to queue; the default queues every project
+    project_filter: ProjectFilter,
+    /// Order tasks are taken in
+    scheduling: SchedulingPolicy,
+    /// Local repos to borrow objects from when cloning
+    reference_store: Option<ReferenceStore>,
+    /// Time limit for each clone
+    task_timeout: Option<Duration>,
+    /// Handling of existing target directories
+    collision_policy: CollisionPolicy,
+    /// Whether SSH clones that are dropped or refused are retried over HTTPS
+    https_fallback: bool,
+    /// Tokens for HTTPS clones
+    https_tokens: HttpsTokens,
+    /// Local mirrors to clone from
+    mirror_root: Option<PathBuf>,
+    /// Handling of SSH hosts without a known_hosts entry
+    new_host_keys: NewHostKeys,
+    /// Whether setup commands from nested `.meta` files run too
+    nested_setup: bool,
+    /// Checks [`run`](Self::run) makes before starting the workers
+    preflight: Option<PreflightOptions>,
+    /// Host key scans for [`NewHostKeys::Scan`], one per host, done or in
+    /// flight
+    scanned_hosts: Mutex<HashMap<String, Arc<OnceLock<()>>>>,
     /// Max meta depth for recursion (None = unlimited)
     meta_depth: Option<usize>,
+    /// Whether progress is saved to `~/.meta/clone-queue.json`
+    persistent: bool,
+    /// Tasks pushed since the state was last saved
+    unsaved: Mutex<Vec<CloneTask>>,
+    /// Limit on concurrent clones
+    transfers: TransferLimiter,
+    /// Whether the transfer limit was set by the caller (and `.meta` must
+    /// not override it)
+    transfer_limit_set: bool,
+    /// Runs git for the queue; the caller's (see [`crate::git_runner`]) if `None`
+    git_runner: Option<Arc<dyn GitRunner>>,
+    /// Workspace passed to the top-level [`push_from_meta`](Self::push_from_meta),
+    /// whose `.meta` configures the clone hooks
+    root_meta_dir: Mutex<Option<PathBuf>>,
 }
 
 impl CloneQueue {
@@ -43,12 +578,359 @@ impl CloneQueue {
         Self {
             pending: Mutex::new(Vec::new()),
             completed: Mutex::new(HashSet::new()),
-            failed: Mutex::new(HashSet::new()),
+            failures: Mutex::new(Vec::new()),
+            rate_limited_hosts: Mutex::new(BTreeSet::new()),
+            skipped: Mutex::new(BTreeSet::new()),
+            quarantined: Mutex::new(BTreeSet::new()),
+            retrying: Mutex::new(Vec::new()),
+            retry_policy: RetryPolicy::default(),
             total_discovered: AtomicUsize::new(0),
             total_completed: AtomicUsize::new(0),
             git_depth,
+            shallow_since: None,
+            shallow_exclude: Vec::new(),
+            protocol_v2: false,
+            clone_filter: None,
+            project_filter: ProjectFilter::default(),
+            scheduling: SchedulingPolicy::default(),
+            reference_store: None,
+            task_timeout: None,
+            collision_policy: CollisionPolicy::default(),
+            https_fallback: false,
+            https_tokens: HttpsTokens::from_env(),
+            mirror_root: None,
+            new_host_keys: NewHostKeys::default(),
+            nested_setup: false,
+            preflight: default_preflight(),
+            scanned_hosts: Mutex::new(HashMap::new()),
             meta_depth,
+            persistent: false,
+            unsaved: Mutex::new(Vec::new()),
+            transfers: TransferLimiter::default(),
+            transfer_limit_set: false,
+            git_runner: None,
+            root_meta_dir: Mutex::new(None),
+        }
+    }
+
+    /// Save the queue's progress to `~/.meta/clone-queue.json` as tasks are
+    /// discovered, completed, and failed, so an interrupted run can be
+    /// continued with [`resume`](Self::resume).
+    ///
+    /// Replaces any previously saved state; call after the other `with_*`
+    /// settings and before pushing tasks. Every setting is saved except the
+    /// HTTPS tokens, which are never written to disk.
+    pub fn with_persistence(mut self) -> anyhow::Result<Self> {
+        crate::read_only::check("save the clone queue")?;
+        meta_core::data_dir::ensure_meta_dir()?;
+        let (data_path, lock_path) = state_paths();
+        meta_core::store::update::<CloneQueueState, _>(&data_path, &lock_path, |state| {
+            *state = CloneQueueState {
+                git_depth: self.git_depth.clone(),
+                meta_depth: self.meta_depth,
+                clone_filter: self.clone_filter.clone(),
+                shallow_since: self.shallow_since.clone(),
+                shallow_exclude: self.shallow_exclude.clone(),
+                protocol_v2: self.protocol_v2,
+                project_filter: self.project_filter.clone(),
+                scheduling: self.scheduling.clone(),
+                retry_policy: self.retry_policy.clone(),
+                reference_store: self.reference_store.clone(),
+                task_timeout: self.task_timeout,
+                collision_policy: self.collision_policy,
+                https_fallback: self.https_fallback,
+                mirror_root: self.mirror_root.clone(),
+                new_host_keys: self.new_host_keys,
+                nested_setup: self.nested_setup,
+                preflight: self.preflight.clone(),
+                max_transfers: self.transfer_limit_set.then(|| self.max_transfers()),
+                ..Default::default()
+            };
+        })?;
+        self.persistent = true;
+        Ok(self)
+    }
+
+    /// Continue the last persistent run from its saved state.
+    ///
+    /// Completed paths are skipped; tasks that were pending, in flight, or
+    /// failed are queued again with a fresh retry budget. The resumed queue
+    /// has the saved run's settings and keeps saving its progress; HTTPS
+    /// tokens come from the environment unless set again with
+    /// [`with_https_tokens`](Self::with_https_tokens). Returns `None` if there
+    /// is nothing to resume.
+    pub fn resume() -> anyhow::Result<Option<Self>> {
+        let Some(mut state) = read_saved_state()? else {
+            return Ok(None);
+        };
+        if state.remaining().next().is_none() {
+            return Ok(None);
+        }
+        crate::read_only::check("save the clone queue")?;
+
+        let mut queue = CloneQueue::new(state.git_depth.clone(), state.meta_depth)
+            .with_clone_filter(state.clone_filter.clone())
+            .with_shallow_since(state.shallow_since.clone())
+            .with_shallow_exclude(state.shallow_exclude.clone())
+            .with_protocol_v2(state.protocol_v2)
+            .with_project_filter(state.project_filter.clone())
+            .with_scheduling(state.scheduling.clone())
+            .with_retry_policy(state.retry_policy.clone())
+            .with_task_timeout(state.task_timeout)
+            .with_collision_policy(state.collision_policy)
+            .with_https_fallback(state.https_fallback)
+            .with_mirror_root(state.mirror_root.clone())
+            .with_new_host_keys(state.new_host_keys)
+            .with_nested_setup(state.nested_setup)
+            .with_preflight(state.preflight.clone());
+        if let Some(store) = state.reference_store.clone() {
+            queue = queue.with_reference_store(store);
+        }
+        if let Some(limit) = state.max_transfers {
+            queue = queue.with_max_transfers(limit);
+        }
+        {
+            let mut completed = queue.completed.lock().unwrap_or_else(|e| e.into_inner());
+            completed.extend(state.completed.iter().cloned());
+        }
+        queue
+            .total_completed
+            .store(state.completed.len(), Ordering::SeqCst);
+        queue
+            .total_discovered
+            .store(state.completed.len(), Ordering::SeqCst);
+        for mut task in state.remaining().cloned().collect::<Vec<_>>() {
+            // Queued again, so an earlier failure no longer applies
+            state.failure_categories.remove(&task.target_path);
+            task.attempts = 0;
+            queue.push(task);
+        }
+
+        state.failed.clear();
+        let (data_path, lock_path) = state_paths();
+        meta_core::store::update::<CloneQueueState, _>(&data_path, &lock_path, |saved| {
+            *saved = state;
+        })?;
+        queue.persistent = true;
+        Ok(Some(queue))
+    }
+
+    /// Whether progress is being saved (see [`with_persistence`](Self::with_persistence)).
+    pub fn is_persistent(&self) -> bool {
+        self.persistent
+    }
+
+    /// Apply `f` to the saved state, together with the tasks pushed since
+    /// the last save.
+    fn save_state(&self, f: impl FnOnce(&mut CloneQueueState)) {
+        if !self.persistent {
+            return;
         }
+        let unsaved = std::mem::take(&mut *self.unsaved.lock().unwrap_or_else(|e| e.into_inner()));
+        let (data_path, lock_path) = state_paths();
+        let saved =
+            meta_core::store::update::<CloneQueueState, _>(&data_path, &lock_path, |state| {
+                let known: HashSet<PathBuf> =
+                    state.tasks.iter().map(|t| t.target_path.clone()).collect();
+                state.tasks.extend(
+                    unsaved
+                        .into_iter()
+                        .filter(|t| !known.contains(&t.target_path)),
+                );
+                f(state);
+            });
+        if let Err(e) = saved {
+            warn!("Failed to save clone queue state: {e}");
+        }
+    }
+
+    /// Save the tasks pushed since the last save, if any.
+    fn flush_state(&self) {
+        if self.persistent
+            && !self
+                .unsaved
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .is_empty()
+        {
+            self.save_state(|_| {});
+        }
+    }
+
+    /// Clone with `--filter=<filter>` (e.g. `blob:none`) unless a project
+    /// sets its own `clone_filter`.
+    pub fn with_clone_filter(mut self, filter: Option<String>) -> Self {
+        self.clone_filter = filter.filter(|f| !f.trim().is_empty());
+        self
+    }
+
+    /// Clone only history after `date` (`--shallow-since`) unless a project
+    /// limits its own history.
+    pub fn with_shallow_since(mut self, date: Option<String>) -> Self {
+        self.shallow_since = date.filter(|d| !d.trim().is_empty());
+        self
+    }
+
+    /// Leave out history reachable from `refs` (`--shallow-exclude`) unless
+    /// a project limits its own history.
+    pub fn with_shallow_exclude(mut self, refs: Vec<String>) -> Self {
+        self.shallow_exclude = refs;
+        self
+    }
+
+    /// Force git's wire protocol v2 for every clone, not only projects that
+    /// set `protocol_v2`.
+    pub fn with_protocol_v2(mut self, enabled: bool) -> Self {
+        self.protocol_v2 = enabled;
+        self
+    }
+
+    /// Only queue projects selected by `filter`, at every level of nesting.
+    ///
+    /// Nested meta repos that are already present are still searched; one
+    /// that isn't selected isn't cloned, so its projects aren't discovered.
+    pub fn with_project_filter(mut self, filter: ProjectFilter) -> Self {
+        self.project_filter = filter;
+        self
+    }
+
+    /// Take tasks in the order of `policy` (newest first by default).
+    pub fn with_scheduling(mut self, policy: SchedulingPolicy) -> Self {
+        self.scheduling = policy;
+        self
+    }
+
+    /// Clone with `--reference <repo> --dissociate` when `store` has a local
+    /// repo with the same remote.
+    pub fn with_reference_store(mut self, store: ReferenceStore) -> Self {
+        self.reference_store = Some(store).filter(|s| !s.is_empty());
+        self
+    }
+
+    /// Kill any clone that runs longer than `timeout`; the task fails with
+    /// a timeout reason and the worker moves on.
+    pub fn with_task_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.task_timeout = timeout;
+        self
+    }
+
+    /// Handle existing target directories with `policy`.
+    ///
+    /// With any policy other than [`CollisionPolicy::Skip`], targets that
+    /// exist but aren't the expected repo are queued, and the clone applies
+    /// the policy to them.
+    pub fn with_collision_policy(mut self, policy: CollisionPolicy) -> Self {
+        self.collision_policy = policy;
+        self
+    }
+
+    /// Retry SSH clones that the server drops or refuses over HTTPS right
+    /// away, instead of only reporting the host as rate-limited. The clone
+    /// result records which transport succeeded.
+    pub fn with_https_fallback(mut self, enabled: bool) -> Self {
+        self.https_fallback = enabled;
+        self
+    }
+
+    /// Authenticate HTTPS clones with `tokens`, instead of those from the
+    /// environment (see [`HttpsTokens::from_env`]).
+    pub fn with_https_tokens(mut self, tokens: HttpsTokens) -> Self {
+        self.https_tokens = tokens;
+        self
+    }
+
+    /// Clone from mirrors under `root` where they exist (see
+    /// [`crate::mirrors`]), e.g. [`crate::mirrors::default_root`].
+    pub fn with_mirror_root(mut self, root: Option<PathBuf>) -> Self {
+        self.mirror_root = root;
+        self
+    }
+
+    /// Trust SSH hosts without a known_hosts entry per `policy` instead of
+    /// prompting (see [`NewHostKeys`]); e.g. `NewHostKeys::from_meta`.
+    pub fn with_new_host_keys(mut self, policy: NewHostKeys) -> Self {
+        self.new_host_keys = policy;
+        self
+    }
+
+    /// Also run the setup commands of projects found in nested `.meta`
+    /// files. Off by default: those files come from the cloned repos, not
+    /// from the workspace, so their commands aren't trusted.
+    pub fn with_nested_setup(mut self, enabled: bool) -> Self {
+        self.nested_setup = enabled;
+        self
+    }
+
+    /// Check the targets with `options` before [`run`](Self::run) starts
+    /// its workers (see [`preflight`](Self::preflight)); `None` skips the
+    /// checks. On by default, with [`PreflightOptions::default`].
+    pub fn with_preflight(mut self, options: Option<PreflightOptions>) -> Self {
+        self.preflight = options;
+        self
+    }
+
+    /// Run git through `runner` (e.g. `test_util::FakeGit`) instead of the
+    /// one installed on the calling thread, for the workers and checks.
+    pub fn with_git_runner(mut self, runner: Arc<dyn GitRunner>) -> Self {
+        self.git_runner = Some(runner);
+        self
+    }
+
+    fn git_runner(&self) -> Option<Arc<dyn GitRunner>> {
+        self.git_runner
+            .clone()
+            .or_else(crate::git_runner::installed)
+    }
+
+    /// Use `policy` for retrying transient failures.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Let at most `limit` clones download at once, however many workers
+    /// are running (`None` or 0 for no limit), so a large workspace doesn't
+    /// saturate the network link.
+    ///
+    /// Without this, the limit comes from `clone_max_transfers` in the
+    /// top-level `.meta` file read by [`push_from_meta`](Self::push_from_meta).
+    pub fn with_max_transfers(mut self, limit: Option<usize>) -> Self {
+        self.set_max_transfers(limit);
+        self.transfer_limit_set = true;
+        self
+    }
+
+    /// Current limit on concurrent clones, if any.
+    pub fn max_transfers(&self) -> Option<usize> {
+        self.transfers
+            .state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .0
+    }
+
+    fn set_max_transfers(&self, limit: Option<usize>) {
+        let mut state = self
+            .transfers
+            .state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        state.0 = limit.filter(|&n| n > 0);
+        self.transfers.freed.notify_all();
+    }
+
+    /// Wait for a free slot under the transfer limit and hold it until the
+    /// permit is dropped. Workers that don't use [`run`](Self::run) should
+    /// hold a permit for the duration of each clone.
+    pub fn acquire_transfer(&self) -> TransferPermit<'_> {
+        let limiter = &self.transfers;
+        let state = limiter.state.lock().unwrap_or_else(|e| e.into_inner());
+        let mut state = limiter
+            .freed
+            .wait_while(state, |(limit, active)| limit.is_some_and(|l| *active >= l))
+            .unwrap_or_else(|e| e.into_inner());
+        state.1 += 1;
+        TransferPermit { limiter }
     }
 
     /// Add a task to the queue if not already completed or pending
@@ -62,9 +944,20 @@ impl CloneQueue {
         }
 
         let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
-        if pending.iter().any(|t| t.target_path == path) {
+        let retrying = self.retrying.lock().unwrap_or_else(|e| e.into_inner());
+        if pending.iter().any(|t| t.target_path == path)
+            || retrying.iter().any(|(_, t)| t.target_path == path)
+        {
             return false;
         }
+        drop(retrying);
+        if self.persistent {
+            // Saved in batches by `save_state`, not once per push
+            self.unsaved
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(task.clone());
+        }
         pending.push(task);
         drop(pending);
         drop(completed);
@@ -75,6 +968,19 @@ impl CloneQueue {
 
     /// Add multiple tasks from a .meta file
     pub fn push_from_meta(&self, base_dir: &Path, depth_level: usize) -> anyhow::Result<usize> {
+        let mut added = Vec::new();
+        self.discover(base_dir, depth_level, &mut added)?;
+        Ok(added.len())
+    }
+
+    /// [`push_from_meta`](Self::push_from_meta), collecting the tasks queued
+    /// in `added`.
+    fn discover(
+        &self,
+        base_dir: &Path,
+        depth_level: usize,
+        added: &mut Vec<CloneTask>,
+    ) -> anyhow::Result<()> {
         // Check meta depth limit
         if let Some(max_depth) = self.meta_depth {
             if depth_level > max_depth {
@@ -82,16 +988,41 @@ impl CloneQueue {
                     "Skipping nested discovery at depth {} (max: {})",
                     depth_level, max_depth
                 );
-                return Ok(0);
+                return Ok(());
             }
         }
 
         let Some((meta_path, _format)) = config::find_meta_config_in(base_dir) else {
             debug!("No .meta config found in {}", base_dir.display());
-            return Ok(0);
+            return Ok(());
         };
 
-        let (projects, _) = config::parse_meta_config(&meta_path)?;
+        let projects = crate::workspace_model::parse_config(&meta_path)?;
+        if depth_level == 0 {
+            *self.root_meta_dir.lock().unwrap_or_else(|e| e.into_inner()) =
+                Some(base_dir.to_path_buf());
+        }
+        if depth_level == 0 && !self.transfer_limit_set {
+            if let Some(limit) = crate::worktree::helpers::read_meta_config_value(base_dir)
+                .and_then(|v| v.get("clone_max_transfers").cloned())
+            {
+                match serde_json::from_value::<usize>(limit) {
+                    Ok(limit) => self.set_max_transfers(Some(limit)),
+                    Err(e) => warn!("Invalid clone_max_transfers in .meta: {e}"),
+                }
+            }
+        }
+        let mut options = load_project_options(base_dir);
+        let hosts = load_host_options(base_dir);
+        // Key prefix of this meta repo's projects, for the project filter
+        let key_prefix = self
+            .root_meta_dir
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_deref()
+            .and_then(|root| base_dir.strip_prefix(root).ok())
+            .map(|rel| rel.to_string_lossy().replace('\\', "/"))
+            .unwrap_or_default();
         debug!(
             "Discovered {} projects in {} at depth {}",
             projects.len(),
@@ -99,63 +1030,219 @@ impl CloneQueue {
             depth_level
         );
 
-        let mut added = 0;
-        for project in projects {
-            let target_path = base_dir.join(&project.path);
+        let target_paths = projects
+            .iter()
+            .map(|project| validate_project_path(base_dir, &project.name, &project.path))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let parents: BTreeSet<&Path> = target_paths.iter().filter_map(|p| p.parent()).collect();
+        for parent in parents {
+            crate::collision::remove_stale_partials_in(parent);
+        }
+
+        for (project, target_path) in projects.into_iter().zip(target_paths) {
+            if options
+                .get(&project.name)
+                .is_some_and(|o| o.skips(ProjectOperation::Clone))
+            {
+                debug!("Skipping disabled project: {}", project.name);
+                self.skipped
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(project.name);
+                continue;
+            }
+
+            let collides = || {
+                self.collision_policy != CollisionPolicy::Skip
+                    && project.repo.as_deref().is_some_and(|url| {
+                        crate::collision::inspect_target(&target_path, url).is_collision()
+                    })
+            };
 
-            // Skip if already exists
-            if target_path.exists() {
+            // Skip if already exists (unless the collision policy handles it)
+            if target_path.exists() && !collides() {
                 // But still check if it has a config file for nested discovery
                 if config::find_meta_config_in(&target_path).is_some() {
                     // Queue it for discovery even though it's already cloned
-                    added += self.push_from_meta(&target_path, depth_level + 1)?;
+                    self.discover(&target_path, depth_level + 1, added)?;
                 }
                 continue;
             }
 
+            let key = if key_prefix.is_empty() {
+                project.name.clone()
+            } else {
+                format!("{key_prefix}/{}", project.name)
+            };
+            if !self.project_filter.matches(&key, &project.tags) {
+                debug!("Skipping project not selected by the filter: {key}");
+                continue;
+            }
+
             // Skip projects without a repo URL (cannot clone)
             let Some(url) = project.repo else {
                 continue;
             };
 
+            if crate::quarantine::is_quarantined(&url)
+                .unwrap_or_default()
+                .is_some()
+            {
+                debug!("Skipping quarantined project: {}", project.name);
+                self.quarantined
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(project.name);
+                continue;
+            }
+
             let task = CloneTask {
                 name: project.name.clone(),
-                url,
                 target_path,
                 depth_level,
                 is_meta: project.meta,
+                options: options.remove(&project.name).unwrap_or_default(),
+                ssh_command: ssh_command_for_url(&url, &hosts),
+                attempts: 0,
+                tags: project.tags.clone(),
+                url,
             };
 
-            let task_name = task.name.clone();
-            let task_is_meta = task.is_meta;
-            if self.push(task) {
+            if self.push(task.clone()) {
                 debug!(
                     "Queued clone task: {} (depth: {}, is_meta: {})",
-                    task_name, depth_level, task_is_meta
+                    task.name, depth_level, task.is_meta
                 );
-                added += 1;
+                added.push(task);
             }
         }
 
-        Ok(added)
+        self.flush_state();
+        Ok(())
     }
 
     /// Take a single task from the queue (for worker threads)
+    ///
+    /// Retries whose backoff has elapsed are returned before new tasks.
     pub fn take_one(&self) -> Option<CloneTask> {
-        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
-        pending.pop()
+        let task = self.take_due_retry().or_else(|| {
+            let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            let i = self.scheduling.next_index(&pending)?;
+            Some(pending.remove(i))
+        })?;
+        Some(task)
+    }
+
+    fn take_due_retry(&self) -> Option<CloneTask> {
+        let mut retrying = self.retrying.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let i = retrying.iter().position(|(due, _)| *due <= now)?;
+        Some(retrying.remove(i).1)
     }
 
-    /// Check if queue is finished (no pending and no active workers)
+    /// Check if queue is finished (no pending or retrying tasks and no active workers)
     pub fn is_finished(&self, active_workers: &AtomicUsize) -> bool {
         let pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
-        pending.is_empty() && active_workers.load(Ordering::SeqCst) == 0
+        let retrying = self.retrying.lock().unwrap_or_else(|e| e.into_inner());
+        pending.is_empty() && retrying.is_empty() && active_workers.load(Ordering::SeqCst) == 0
     }
 
-    /// Drain all pending tasks (for dry-run display)
+    /// Time until the next retry is due, if any are waiting.
+    ///
+    /// Idle workers can sleep for this long when [`take_one`](Self::take_one)
+    /// returns `None` but the queue isn't finished.
+    pub fn next_retry_in(&self) -> Option<Duration> {
+        let retrying = self.retrying.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        retrying
+            .iter()
+            .map(|(due, _)| due.saturating_duration_since(now))
+            .min()
+    }
+
+    /// Plan a run from the `.meta` in `base_dir` without cloning or fetching
+    /// anything, following nested `.meta` files of repos already cloned.
+    ///
+    /// Honors the depth limit, project filter, and per-project options like
+    /// [`push_from_meta`](Self::push_from_meta); the queue itself is untouched.
+    pub fn plan(&self, base_dir: &Path) -> anyhow::Result<ClonePlan> {
+        Ok(ClonePlan {
+            root: base_dir.to_path_buf(),
+            projects: self.plan_level(base_dir, "", 0)?,
+        })
+    }
+
+    fn plan_level(
+        &self,
+        dir: &Path,
+        key_prefix: &str,
+        depth_level: usize,
+    ) -> anyhow::Result<Vec<PlanNode>> {
+        if self.meta_depth.is_some_and(|max| depth_level > max) {
+            return Ok(Vec::new());
+        }
+        let Some((meta_path, _format)) = config::find_meta_config_in(dir) else {
+            return Ok(Vec::new());
+        };
+        let projects = crate::workspace_model::parse_config(&meta_path)?;
+        let options = load_project_options(dir);
+
+        let mut nodes = Vec::new();
+        for project in projects.iter() {
+            let target_path = validate_project_path(dir, &project.name, &project.path)?;
+            let key = if key_prefix.is_empty() {
+                project.name.clone()
+            } else {
+                format!("{key_prefix}/{}", project.name)
+            };
+            let present = target_path.exists();
+            let action = if options
+                .get(&project.name)
+                .is_some_and(|o| o.skips(ProjectOperation::Clone))
+            {
+                PlanAction::Disabled
+            } else if present {
+                PlanAction::Present
+            } else if !self.project_filter.matches(&key, &project.tags) {
+                PlanAction::Filtered
+            } else if let Some(url) = &project.repo {
+                if crate::quarantine::is_quarantined(url)
+                    .unwrap_or_default()
+                    .is_some()
+                {
+                    PlanAction::Quarantined
+                } else {
+                    PlanAction::Clone
+                }
+            } else {
+                PlanAction::NoUrl
+            };
+            let children = if present && action != PlanAction::Disabled {
+                self.plan_level(&target_path, &key, depth_level + 1)?
+            } else {
+                Vec::new()
+            };
+            nodes.push(PlanNode {
+                name: project.name.clone(),
+                url: project.repo.clone(),
+                is_meta: project.meta || config::find_meta_config_in(&target_path).is_some(),
+                target_path,
+                depth_level,
+                action,
+                children,
+            });
+        }
+        Ok(nodes)
+    }
+
+    /// Drain all pending tasks, including ones waiting to be retried (for dry-run display)
     pub fn drain_all(&self) -> Vec<CloneTask> {
         let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
-        pending.drain(..).collect()
+        let mut retrying = self.retrying.lock().unwrap_or_else(|e| e.into_inner());
+        pending
+            .drain(..)
+            .chain(retrying.drain(..).map(|(_, task)| task))
+            .collect()
     }
 
     /// Get current counts for display
@@ -171,24 +1258,149 @@ impl CloneQueue {
         self.git_depth.as_deref()
     }
 
+    /// Get the queue-wide partial clone filter (if any)
+    pub fn clone_filter(&self) -> Option<&str> {
+        self.clone_filter.as_deref()
+    }
+
+    /// Settings for cloning `task`: the project's `shallow_since` and
+    /// `shallow_exclude` or else the queue's depth and shallow settings,
+    /// protocol v2 if the queue or project asks for it, the project's
+    /// `clone_filter` or else the queue's, the project's `sparse_paths`,
+    /// `branch`, `single_branch`, `submodules`, and `ref`, a
+    /// local reference repo from the reference store, the task timeout, the
+    /// HTTPS fallback setting, and the task's ssh command.
+    pub fn clone_options(&self, task: &CloneTask) -> crate::CloneOptions {
+        let project = &task.options;
+        // A project limiting its own history replaces the queue-wide limits
+        let (depth, shallow_since, shallow_exclude) =
+            if project.shallow_since.is_some() || !project.shallow_exclude.is_empty() {
+                (
+                    None,
+                    project.shallow_since.clone(),
+                    project.shallow_exclude.clone(),
+                )
+            } else {
+                (
+                    self.git_depth.clone(),
+                    self.shallow_since.clone(),
+                    self.shallow_exclude.clone(),
+                )
+            };
+        crate::CloneOptions {
+            ssh_command: task.ssh_command.clone(),
+            depth,
+            shallow_since,
+            shallow_exclude,
+            protocol_v2: self.protocol_v2 || project.protocol_v2,
+            filter: task
+                .options
+                .clone_filter
+                .clone()
+                .filter(|f| !f.trim().is_empty())
+                .or_else(|| self.clone_filter.clone()),
+            sparse_paths: task.options.sparse_paths.clone(),
+            reference: self
+                .reference_store
+                .as_ref()
+                .and_then(|store| store.find(&task.url))
+                .map(Path::to_path_buf),
+            timeout: self.task_timeout,
+            collision: self.collision_policy,
+            branch: task.options.branch.clone(),
+            single_branch: task.options.single_branch,
+            submodules: task.options.submodules,
+            git_ref: task.options.git_ref.clone(),
+            https_fallback: self.https_fallback,
+            https_tokens: self.https_tokens.clone(),
+            mirror_root: self.mirror_root.clone(),
+            accept_new_host_keys: self.new_host_keys == NewHostKeys::AcceptNew,
+            multiplexing: self
+                .root_meta_dir
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .as_deref()
+                .map(MultiplexingConfig::from_meta)
+                .unwrap_or_default(),
+            // Done per parent directory while discovering
+            stale_partials_removed: true,
+        }
+    }
+
+    /// For [`NewHostKeys::Scan`], add the host keys of `url`'s host before
+    /// its first clone. Workers cloning from the same host wait for the scan.
+    fn scan_new_host(&self, url: &str) {
+        if self.new_host_keys != NewHostKeys::Scan {
+            return;
+        }
+        let Some(host) = crate::extract_ssh_host(url) else {
+            return;
+        };
+        // Only the map lookup is locked, so other hosts' scans don't wait
+        let scan = self
+            .scanned_hosts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(host.to_ascii_lowercase())
+            .or_default()
+            .clone();
+        scan.get_or_init(|| {
+            let meta_dir = self
+                .root_meta_dir
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone();
+            match crate::ssh_multiplexing::scan_new_host(&host, meta_dir.as_deref()) {
+                Ok(crate::ssh_multiplexing::KnownHostStatus::Added(fingerprints)) => {
+                    debug!("Added host keys of {host}: {}", fingerprints.join(", "))
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Could not add host keys of {host}: {e:#}"),
+            }
+        });
+    }
+
     /// Mark a task as completed and check for nested .meta files
     pub fn mark_completed(&self, task: &CloneTask) -> anyhow::Result<usize> {
+        Ok(self.complete(task, &())?.len())
+    }
+
+    /// [`mark_completed`](Self::mark_completed), telling `reporter` about
+    /// the tasks a nested .meta file added. Returns those tasks.
+    fn complete(
+        &self,
+        task: &CloneTask,
+        reporter: &dyn CloneReporter,
+    ) -> anyhow::Result<Vec<CloneTask>> {
         self.total_completed.fetch_add(1, Ordering::SeqCst);
 
         {
             let mut completed = self.completed.lock().unwrap_or_else(|e| e.into_inner());
             completed.insert(task.target_path.clone());
         }
+        self.save_state(|state| {
+            state.failed.remove(&task.target_path);
+            state.failure_categories.remove(&task.target_path);
+            state.completed.insert(task.target_path.clone());
+        });
 
         // Check for nested .meta file and add children to queue
-        let added = self.push_from_meta(&task.target_path, task.depth_level + 1)?;
+        let mut added = Vec::new();
+        self.discover(&task.target_path, task.depth_level + 1, &mut added)?;
+        if let Some((meta_path, _)) = config::find_meta_config_in(&task.target_path) {
+            for nested in &added {
+                reporter.discovered(nested);
+            }
+            reporter.nested_meta_found(task, &meta_path, added.len());
+        }
         debug!(
             "mark_completed: {} -> {} nested tasks discovered",
-            task.name, added
+            task.name,
+            added.len()
         );
 
         // Warn if the config declared meta: true but no nested .meta was found
-        if task.is_meta && added == 0 {
+        if task.is_meta && added.is_empty() {
             warn!(
                 "'{}' is declared with `meta: true` but no .meta config was found inside it",
                 task.name
@@ -216,18 +1428,436 @@ impl CloneQueue {
         pending.iter().map(|t| t.url.clone()).collect()
     }
 
-    /// Mark a task as failed
+    /// Check every pending remote with `git ls-remote` before cloning, using
+    /// each task's ssh command like the clone would.
+    ///
+    /// Does not modify the queue; see [`crate::remote_check::precheck`].
+    pub fn precheck_remotes(&self, concurrency: usize) -> Vec<crate::remote_check::RemoteCheck> {
+        let remotes: Vec<(String, Option<String>)> = self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|t| (t.url.clone(), t.ssh_command.clone()))
+            .collect();
+        crate::git_runner::with_installed(self.git_runner(), || {
+            crate::remote_check::precheck(&remotes, concurrency, &self.https_tokens)
+        })
+    }
+
+    /// Check the targets of the pending tasks for writability, case
+    /// collisions, and free disk space before [`run`](Self::run).
+    ///
+    /// Does not modify the queue; see [`crate::preflight::check`]. Repos of
+    /// nested meta repos aren't known yet and aren't checked.
+    pub fn preflight(&self, options: &PreflightOptions) -> PreflightReport {
+        let pending = self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        crate::preflight::check(&pending, options)
+    }
+
+    /// Names of projects skipped because they are disabled for cloning.
+    pub fn skipped_projects(&self) -> Vec<String> {
+        let skipped = self.skipped.lock().unwrap_or_else(|e| e.into_inner());
+        skipped.iter().cloned().collect()
+    }
+
+    /// Names of projects skipped because their remote is quarantined
+    /// (see [`crate::quarantine`]).
+    pub fn quarantined_projects(&self) -> Vec<String> {
+        let quarantined = self.quarantined.lock().unwrap_or_else(|e| e.into_inner());
+        quarantined.iter().cloned().collect()
+    }
+
+    /// Handle a failed attempt at `task` that reported `error`.
+    ///
+    /// Transient failures are requeued per the [`RetryPolicy`] and the retry
+    /// delay is returned; otherwise the task is marked failed and `None` is
+    /// returned.
+    ///
+    /// The error is categorized for [`outcome`](Self::outcome), and hosts that
+    /// rate-limited or dropped the clone are collected in
+    /// [`rate_limited_hosts`](Self::rate_limited_hosts).
+    pub fn retry_or_fail(&self, task: CloneTask, error: &str) -> Option<Duration> {
+        self.retry_or_fail_reporting(task, error, &())
+    }
+
+    /// [`retry_or_fail`](Self::retry_or_fail), telling `reporter` if the task
+    /// failed for good.
+    fn retry_or_fail_reporting(
+        &self,
+        mut task: CloneTask,
+        error: &str,
+        reporter: &dyn CloneReporter,
+    ) -> Option<Duration> {
+        task.attempts += 1;
+        let category = FailureCategory::classify(error);
+        if category == FailureCategory::RateLimit
+            || crate::ssh_multiplexing::is_ssh_rate_limit_error(error)
+        {
+            if let Some(host) = crate::extract_ssh_host(&task.url) {
+                self.rate_limited_hosts
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(host.to_ascii_lowercase());
+            }
+        }
+        if !self.retry_policy.should_retry(task.attempts, error) {
+            self.fail_for_good(&task, category, error, reporter);
+            return None;
+        }
+        let delay = self.retry_policy.delay_for(task.attempts);
+        debug!(
+            "Retrying clone of {} in {:?} (attempt {} of {})",
+            task.name,
+            delay,
+            task.attempts + 1,
+            self.retry_policy.max_attempts
+        );
+        let mut retrying = self.retrying.lock().unwrap_or_else(|e| e.into_inner());
+        retrying.push((Instant::now() + delay, task));
+        Some(delay)
+    }
+
+    /// SSH hosts that rate-limited or dropped a clone during this run, e.g.
+    /// to suggest [`crate::ssh_multiplexing::setup`] for them.
+    pub fn rate_limited_hosts(&self) -> Vec<String> {
+        let hosts = self
+            .rate_limited_hosts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        hosts.iter().cloned().collect()
+    }
+
+    /// Report for the run so far: clones completed, projects skipped
+    /// (disabled or quarantined), and categorized failures.
+    ///
+    /// Only failures reported through [`retry_or_fail`](Self::retry_or_fail)
+    /// carry a message.
+    pub fn outcome(&self) -> OperationOutcome {
+        let failures = self
+            .failures
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(_, failure)| failure.clone())
+            .collect();
+        let completed = self
+            .completed
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len();
+        let skipped = self.skipped.lock().unwrap_or_else(|e| e.into_inner()).len()
+            + self
+                .quarantined
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .len();
+        OperationOutcome {
+            operation: "clone".to_string(),
+            succeeded: completed,
+            skipped,
+            failures,
+        }
+    }
+
+    /// Clone everything in the queue with `parallelism` worker threads.
+    ///
+    /// First the pending targets are checked as set with
+    /// [`with_preflight`](Self::with_preflight); if that finds problems,
+    /// every pending task fails with them and nothing is cloned.
+    ///
+    /// Each worker takes tasks with [`take_one`](Self::take_one), clones them
+    /// with [`clone_options`](Self::clone_options), and reports the result
+    /// with [`mark_completed`](Self::mark_completed) — queueing projects of
+    /// nested `.meta` files — or [`retry_or_fail`](Self::retry_or_fail).
+    /// Returns once no tasks are pending, retrying, or in progress.
+    ///
+    /// Fresh clones of projects in the top-level `.meta` run their setup
+    /// commands (see [`crate::setup`]) before they count as finished; a
+    /// failed command fails the repo. Nested projects' setup commands only
+    /// run with [`with_nested_setup`](Self::with_nested_setup).
+    ///
+    /// Clones print nothing; progress goes to `reporter` only. At most
+    /// [`max_transfers`](Self::max_transfers) clones run at once.
+    ///
+    /// The `post-clone` hook of the workspace's `.meta` fires for each
+    /// finished repo, and `post-clone-all` once the queue has drained (see
+    /// [`crate::hooks`]). Persistent queues also record each clone's size for
+    /// later [`preflight`](Self::preflight) estimates.
+    pub fn run(&self, parallelism: usize, reporter: &dyn CloneReporter) -> CloneReport {
+        let started = Instant::now();
+        self.flush_state();
+        let queued = self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        for task in &queued {
+            reporter.discovered(task);
+        }
+        if let Some(options) = &self.preflight {
+            let preflight = self.preflight(options);
+            if !preflight.is_ok() {
+                return self.fail_preflight(&preflight, reporter, started);
+            }
+        }
+        let hooks_dir = self
+            .root_meta_dir
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let active = AtomicUsize::new(0);
+        let results = Mutex::new(Vec::new());
+        let runner = self.git_runner();
+        std::thread::scope(|scope| {
+            for _ in 0..parallelism.max(1) {
+                scope.spawn(|| {
+                    crate::git_runner::with_installed(runner.clone(), || loop {
+                        // Count as active before taking, so other workers don't
+                        // see an empty queue while this one is about to add to it
+                        active.fetch_add(1, Ordering::SeqCst);
+                        let Some(task) = self.take_one() else {
+                            active.fetch_sub(1, Ordering::SeqCst);
+                            if self.is_finished(&active) {
+                                break;
+                            }
+                            let wait = self.next_retry_in().unwrap_or(IDLE_POLL);
+                            std::thread::sleep(wait.min(IDLE_POLL));
+                            continue;
+                        };
+                        let result = self.run_task(task, reporter);
+                        if let Some(result) = result {
+                            let (completed, discovered) = self.get_counts();
+                            reporter.finished(&result, completed, discovered);
+                            if let Some(dir) = &hooks_dir {
+                                crate::hooks::fire_post_clone_repo(&result, dir);
+                            }
+                            results
+                                .lock()
+                                .unwrap_or_else(|e| e.into_inner())
+                                .push(result);
+                        }
+                        active.fetch_sub(1, Ordering::SeqCst);
+                    })
+                });
+            }
+        });
+        let report = CloneReport::new(
+            results.into_inner().unwrap_or_else(|e| e.into_inner()),
+            self.outcome(),
+            started.elapsed(),
+        );
+        if let Some(dir) = &hooks_dir {
+            crate::hooks::fire_post_clone_all(&report, dir);
+        }
+        if self.persistent {
+            if let Err(e) = crate::preflight::record_clone_sizes(&report) {
+                warn!("Failed to record clone sizes: {e:#}");
+            }
+        }
+        report
+    }
+
+    /// Fail every pending task because `preflight` found problems, without
+    /// cloning anything.
+    fn fail_preflight(
+        &self,
+        preflight: &PreflightReport,
+        reporter: &dyn CloneReporter,
+        started: Instant,
+    ) -> CloneReport {
+        let category = if preflight
+            .problems
+            .iter()
+            .any(|p| matches!(p, PreflightProblem::InsufficientSpace { .. }))
+        {
+            FailureCategory::DiskFull
+        } else {
+            FailureCategory::Other
+        };
+        let problems: Vec<String> = preflight.problems.iter().map(|p| p.to_string()).collect();
+        let message = format!("Preflight check failed: {}", problems.join("; "));
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap_or_else(|e| e.into_inner()));
+        let mut results = Vec::new();
+        for task in pending {
+            self.fail_for_good(&task, category, &message, reporter);
+            let result = RepoCloneResult {
+                name: task.name,
+                url: task.url,
+                target_path: task.target_path,
+                depth_level: task.depth_level,
+                status: RepoCloneStatus::Failed {
+                    category,
+                    message: message.clone(),
+                },
+                duration_ms: 0,
+                stats: None,
+                setup: Vec::new(),
+            };
+            let (completed, discovered) = self.get_counts();
+            reporter.finished(&result, completed, discovered);
+            results.push(result);
+        }
+        CloneReport::new(results, self.outcome(), started.elapsed())
+    }
+
+    /// Clone `task` for [`run`](Self::run). Returns `None` if it was requeued
+    /// for a retry.
+    fn run_task(&self, task: CloneTask, reporter: &dyn CloneReporter) -> Option<RepoCloneResult> {
+        reporter.started(&task);
+        let started = Instant::now();
+        self.scan_new_host(&task.url);
+        let options = self.clone_options(&task);
+        let hidden = indicatif::ProgressBar::hidden();
+        let permit = self.acquire_transfer();
+        let cloned =
+            crate::clone_repo_with_options(&task.url, &task.target_path, Some(&hidden), &options);
+        drop(permit);
+        // Setup commands and measuring the clone don't count
+        let duration_ms = crate::hooks::millis(started.elapsed());
+        let mut setup = Vec::new();
+        let mut stats = None;
+        let status = match cloned {
+            Ok(result) => {
+                if matches!(
+                    result.action,
+                    CloneAction::Cloned | CloneAction::Replaced { .. }
+                ) {
+                    if let Err(e) = crate::quarantine::record_success(&task.url) {
+                        warn!(
+                            "Failed to update the quarantine list for {}: {e:#}",
+                            task.url
+                        );
+                    }
+                    stats = CloneStats::measure(&task.target_path);
+                    if task.depth_level == 0 || self.nested_setup {
+                        setup = crate::setup::run_project_setup(&task.target_path, &task.options);
+                    } else if !task.options.setup.is_empty() {
+                        warn!(
+                            "Not running setup commands of nested project '{}'",
+                            task.name
+                        );
+                    }
+                }
+                match setup.iter().find(|step| !step.success) {
+                    Some(step) => {
+                        let message = format!("Setup command '{}' failed", step.command);
+                        self.fail_for_good(&task, FailureCategory::Other, &message, reporter);
+                        RepoCloneStatus::Failed {
+                            category: FailureCategory::Other,
+                            message,
+                        }
+                    }
+                    None => {
+                        if let Err(e) = self.complete(&task, reporter) {
+                            warn!("Failed to read nested .meta in {}: {e:#}", task.name);
+                        }
+                        RepoCloneStatus::Done(result)
+                    }
+                }
+            }
+            Err(e) => {
+                let message = e.to_string();
+                if let Some(delay) = self.retry_or_fail_reporting(task.clone(), &message, reporter)
+                {
+                    reporter.retrying(&task, &message, delay);
+                    return None;
+                }
+                let stderr = e
+                    .downcast_ref::<crate::CloneError>()
+                    .map_or(message.as_str(), |c| c.stderr.as_str());
+                if let Err(e) = crate::quarantine::record_failure(&task.url, stderr) {
+                    warn!(
+                        "Failed to update the quarantine list for {}: {e:#}",
+                        task.url
+                    );
+                }
+                RepoCloneStatus::Failed {
+                    category: self
+                        .failure_category(&task.target_path)
+                        .unwrap_or_else(|| FailureCategory::classify(&message)),
+                    message: message.trim().to_string(),
+                }
+            }
+        };
+        Some(RepoCloneResult {
+            name: task.name,
+            url: task.url,
+            target_path: task.target_path,
+            depth_level: task.depth_level,
+            status,
+            duration_ms,
+            stats,
+            setup,
+        })
+    }
+
+    /// Mark a task as failed, for a reason the queue wasn't told about
+    /// ([`FailureCategory::Other`]); see also [`retry_or_fail`](Self::retry_or_fail).
     pub fn mark_failed(&self, task: &CloneTask) {
+        self.fail(
+            task,
+            RepoFailure {
+                repo: task.name.clone(),
+                category: FailureCategory::Other,
+                message: String::new(),
+            },
+        );
+    }
+
+    /// Why the task cloning into `target_path` failed, if it did.
+    pub fn failure_category(&self, target_path: &Path) -> Option<FailureCategory> {
+        let failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        failures
+            .iter()
+            .rev()
+            .find(|(path, _)| path == target_path)
+            .map(|(_, failure)| failure.category)
+    }
+
+    /// Fail `task` without retrying, recording why for the clone report and
+    /// telling `reporter`.
+    fn fail_for_good(
+        &self,
+        task: &CloneTask,
+        category: FailureCategory,
+        error: &str,
+        reporter: &dyn CloneReporter,
+    ) {
+        let failure = RepoFailure {
+            repo: task.name.clone(),
+            category,
+            message: error.trim().to_string(),
+        };
+        reporter.failed(&task.target_path, &failure);
+        self.fail(task, failure);
+    }
+
+    fn fail(&self, task: &CloneTask, failure: RepoFailure) {
         self.total_completed.fetch_add(1, Ordering::SeqCst);
 
-        let mut failed = self.failed.lock().unwrap_or_else(|e| e.into_inner());
-        failed.insert(task.target_path.clone());
+        let category = failure.category;
+        self.failures
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((task.target_path.clone(), failure));
+        self.save_state(|state| {
+            state.failed.insert(task.target_path.clone());
+            state
+                .failure_categories
+                .insert(task.target_path.clone(), category);
+        });
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_fixtures::{git, repo};
 
     fn make_task(name: &str, path: &Path) -> CloneTask {
         CloneTask {
@@ -236,6 +1866,10 @@ mod tests {
             target_path: path.to_path_buf(),
             depth_level: 0,
             is_meta: false,
+            options: ProjectOptions::default(),
+            ssh_command: None,
+            attempts: 0,
+            tags: Vec::new(),
         }
     }
 
@@ -302,6 +1936,46 @@ mod tests {
         assert!(queue.take_one().is_none()); // now empty
     }
 
+    #[test]
+    fn take_one_follows_scheduling_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let tasks = [
+            ("deep", 2, "infra"),
+            ("a", 0, ""),
+            ("b", 1, "backend"),
+            ("c", 0, ""),
+        ];
+        let order = |policy: SchedulingPolicy| {
+            let queue = CloneQueue::new(None, None).with_scheduling(policy);
+            for (name, depth, tag) in tasks {
+                let mut task = make_task(name, &dir.path().join(name));
+                task.depth_level = depth;
+                task.tags = (!tag.is_empty())
+                    .then(|| tag.to_string())
+                    .into_iter()
+                    .collect();
+                queue.push(task);
+            }
+            std::iter::from_fn(|| queue.take_one())
+                .map(|t| t.name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(order(SchedulingPolicy::Lifo), ["c", "b", "a", "deep"]);
+        assert_eq!(order(SchedulingPolicy::Fifo), ["deep", "a", "b", "c"]);
+        assert_eq!(
+            order(SchedulingPolicy::ShallowestFirst),
+            ["a", "c", "b", "deep"]
+        );
+        assert_eq!(
+            order(SchedulingPolicy::PriorityTags(vec![
+                "backend".to_string(),
+                "infra".to_string()
+            ])),
+            ["b", "deep", "a", "c"]
+        );
+    }
+
     // ── is_finished ───────────────────────────────────────────
 
     #[test]
@@ -370,62 +2044,419 @@ mod tests {
         let added = queue.push_from_meta(dir.path(), 0).unwrap();
         assert_eq!(added, 1); // depth 0 is allowed
 
-        // Trying at depth 1 should be blocked
-        let added = queue.push_from_meta(dir.path(), 1).unwrap();
-        assert_eq!(added, 0);
+        // Trying at depth 1 should be blocked
+        let added = queue.push_from_meta(dir.path(), 1).unwrap();
+        assert_eq!(added, 0);
+    }
+
+    #[test]
+    fn push_from_meta_queues_only_filtered_projects() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("platform")).unwrap();
+        std::fs::write(
+            dir.path().join(".meta"),
+            r#"{"projects": {
+                "api": {"repo": "git@github.com:org/api.git", "tags": ["backend"]},
+                "web": {"repo": "git@github.com:org/web.git", "tags": ["frontend"]},
+                "platform": "git@github.com:org/platform.git"
+            }}"#,
+        )
+        .unwrap();
+        // Already cloned, so still searched although not selected itself
+        std::fs::write(
+            dir.path().join("platform/.meta"),
+            r#"{"projects": {
+                "infra-dns": "git@github.com:org/infra-dns.git",
+                "tools": "git@github.com:org/tools.git"
+            }}"#,
+        )
+        .unwrap();
+
+        let queue =
+            CloneQueue::new(None, None).with_project_filter(ProjectFilter::only("backend,infra-*"));
+        assert_eq!(queue.push_from_meta(dir.path(), 0).unwrap(), 2);
+        let mut names: Vec<String> = queue
+            .pending
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|t| t.name.clone())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["api", "infra-dns"]);
+    }
+
+    #[test]
+    fn push_from_meta_skips_existing_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        // Create the project directory so it appears "already cloned"
+        std::fs::create_dir(dir.path().join("existing")).unwrap();
+        std::fs::write(
+            dir.path().join(".meta"),
+            r#"{"projects": {"existing": "git@github.com:org/existing.git"}}"#,
+        )
+        .unwrap();
+
+        let queue = CloneQueue::new(None, None);
+        let added = queue.push_from_meta(dir.path(), 0).unwrap();
+        assert_eq!(added, 0); // skipped because dir exists
+    }
+
+    #[test]
+    fn push_from_meta_sets_per_host_ssh_command() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(".meta"),
+            r#"{
+                "projects": {
+                    "work": "git@gitlab.corp.com:team/work.git",
+                    "personal": "git@github.com:me/personal.git"
+                },
+                "hosts": {"gitlab.corp.com": {"identity_file": "/keys/corp"}}
+            }"#,
+        )
+        .unwrap();
+
+        let queue = CloneQueue::new(None, None);
+        queue.push_from_meta(dir.path(), 0).unwrap();
+        let tasks = queue.drain_all();
+        let work = tasks.iter().find(|t| t.name == "work").unwrap();
+        assert!(work
+            .ssh_command
+            .as_deref()
+            .unwrap()
+            .contains("-i '/keys/corp'"));
+        let personal = tasks.iter().find(|t| t.name == "personal").unwrap();
+        assert!(personal.ssh_command.is_none());
+    }
+
+    #[test]
+    fn push_from_meta_rejects_escaping_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(".meta"),
+            r#"{"projects": {"evil": {"repo": "git@github.com:org/evil.git", "path": "../../evil"}}}"#,
+        )
+        .unwrap();
+
+        let queue = CloneQueue::new(None, None);
+        let err = queue.push_from_meta(dir.path(), 0).unwrap_err();
+        assert!(err.to_string().contains("Invalid path"), "{err}");
+        assert_eq!(queue.total_discovered.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn push_from_meta_skips_projects_without_repo() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(".meta"),
+            r#"{"projects": {"no-repo": {"path": "no-repo"}}}"#,
+        )
+        .unwrap();
+
+        let queue = CloneQueue::new(None, None);
+        let added = queue.push_from_meta(dir.path(), 0).unwrap();
+        assert_eq!(added, 0);
+    }
+
+    #[test]
+    fn push_from_meta_preserves_is_meta_flag() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(".meta"),
+            r#"{"projects": {
+                "nested": {"repo": "git@github.com:org/nested.git", "meta": true},
+                "plain": "git@github.com:org/plain.git"
+            }}"#,
+        )
+        .unwrap();
+
+        let queue = CloneQueue::new(None, None);
+        queue.push_from_meta(dir.path(), 0).unwrap();
+
+        let tasks = queue.drain_all();
+        let nested = tasks.iter().find(|t| t.name == "nested").unwrap();
+        let plain = tasks.iter().find(|t| t.name == "plain").unwrap();
+
+        assert!(nested.is_meta);
+        assert!(!plain.is_meta);
+    }
+
+    #[test]
+    fn push_from_meta_attaches_project_options() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(".meta"),
+            r#"{"projects": {
+                "legacy": {"repo": "https://hg.example.com/legacy", "vcs": "hg"},
+                "plain": "git@github.com:org/plain.git"
+            }}"#,
+        )
+        .unwrap();
+
+        let queue = CloneQueue::new(None, None);
+        queue.push_from_meta(dir.path(), 0).unwrap();
+
+        let tasks = queue.drain_all();
+        let legacy = tasks.iter().find(|t| t.name == "legacy").unwrap();
+        let plain = tasks.iter().find(|t| t.name == "plain").unwrap();
+
+        assert_eq!(legacy.options.vcs, crate::vcs::VcsKind::Hg);
+        assert_eq!(plain.options.vcs, crate::vcs::VcsKind::Git);
     }
 
     #[test]
-    fn push_from_meta_skips_existing_dirs() {
+    fn push_from_meta_skips_disabled_projects() {
         let dir = tempfile::tempdir().unwrap();
-        // Create the project directory so it appears "already cloned"
-        std::fs::create_dir(dir.path().join("existing")).unwrap();
         std::fs::write(
             dir.path().join(".meta"),
-            r#"{"projects": {"existing": "git@github.com:org/existing.git"}}"#,
+            r#"{"projects": {
+                "broken": {"repo": "git@github.com:org/broken.git", "disabled": true},
+                "huge": {"repo": "git@github.com:org/huge.git", "skip": ["clone"]},
+                "other": {"repo": "git@github.com:org/other.git", "skip": ["update"]}
+            }}"#,
         )
         .unwrap();
 
         let queue = CloneQueue::new(None, None);
-        let added = queue.push_from_meta(dir.path(), 0).unwrap();
-        assert_eq!(added, 0); // skipped because dir exists
+        assert_eq!(queue.push_from_meta(dir.path(), 0).unwrap(), 1);
+        assert_eq!(queue.drain_all()[0].name, "other");
+        assert_eq!(queue.skipped_projects(), ["broken", "huge"]);
     }
 
     #[test]
-    fn push_from_meta_skips_projects_without_repo() {
+    #[serial_test::serial]
+    fn push_from_meta_skips_quarantined_remotes() {
         let dir = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", dir.path().join("store"));
+        for _ in 0..crate::quarantine::QUARANTINE_THRESHOLD {
+            crate::quarantine::record_failure(
+                "git@github.com:org/gone.git",
+                "ERROR: Repository not found.",
+            )
+            .unwrap();
+        }
         std::fs::write(
             dir.path().join(".meta"),
-            r#"{"projects": {"no-repo": {"path": "no-repo"}}}"#,
+            r#"{"projects": {
+                "gone": "git@github.com:org/gone.git",
+                "ok": "git@github.com:org/ok.git"
+            }}"#,
         )
         .unwrap();
 
         let queue = CloneQueue::new(None, None);
-        let added = queue.push_from_meta(dir.path(), 0).unwrap();
-        assert_eq!(added, 0);
+        assert_eq!(queue.push_from_meta(dir.path(), 0).unwrap(), 1);
+        assert_eq!(queue.quarantined_projects(), ["gone"]);
+        std::env::remove_var("META_DATA_DIR");
     }
 
     #[test]
-    fn push_from_meta_preserves_is_meta_flag() {
+    #[serial_test::serial]
+    fn failed_clones_are_recorded_for_quarantine() {
+        let dir = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", dir.path().join("store"));
+        let missing = dir.path().join("missing").to_string_lossy().into_owned();
+        let queue = CloneQueue::new(None, None);
+        queue.push(make_task_with_url(
+            "gone",
+            &missing,
+            &dir.path().join("gone"),
+        ));
+
+        assert_eq!(queue.run(1, &()).failed, 1);
+        let entries = crate::quarantine::list().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            (entries[0].url.as_str(), entries[0].failures),
+            (missing.as_str(), 1)
+        );
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn run_refuses_to_start_when_preflight_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", dir.path().join("store"));
+        let origin = dir.path().join("origin");
+        repo(&origin, &[]);
+        let url = origin.to_string_lossy().into_owned();
+        let queue = CloneQueue::new(None, None).with_preflight(Some(PreflightOptions {
+            margin_bytes: 0,
+            default_repo_bytes: 0,
+            case_insensitive: true,
+        }));
+        queue.push(make_task_with_url("api", &url, &dir.path().join("api")));
+        queue.push(make_task_with_url("API", &url, &dir.path().join("API")));
+
+        let report = queue.run(2, &());
+        assert_eq!((report.succeeded, report.failed), (0, 2));
+        assert!(matches!(
+            &report.per_repo[0].status,
+            RepoCloneStatus::Failed { message, .. } if message.contains("differ only in case")
+        ));
+        assert!(!dir.path().join("api").exists());
+        assert!(!dir.path().join("API").exists());
+
+        let queue = CloneQueue::new(None, None).with_preflight(None);
+        queue.push(make_task_with_url("api", &url, &dir.path().join("api")));
+        assert_eq!(queue.run(1, &()).succeeded, 1);
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    fn clone_filter_per_project_overrides_queue() {
         let dir = tempfile::tempdir().unwrap();
         std::fs::write(
             dir.path().join(".meta"),
             r#"{"projects": {
-                "nested": {"repo": "git@github.com:org/nested.git", "meta": true},
+                "huge": {"repo": "git@github.com:org/huge.git", "clone_filter": "tree:0"},
                 "plain": "git@github.com:org/plain.git"
             }}"#,
         )
         .unwrap();
 
-        let queue = CloneQueue::new(None, None);
+        let queue =
+            CloneQueue::new(Some("1".into()), None).with_clone_filter(Some("blob:none".into()));
         queue.push_from_meta(dir.path(), 0).unwrap();
-
         let tasks = queue.drain_all();
-        let nested = tasks.iter().find(|t| t.name == "nested").unwrap();
+        let huge = tasks.iter().find(|t| t.name == "huge").unwrap();
         let plain = tasks.iter().find(|t| t.name == "plain").unwrap();
 
-        assert!(nested.is_meta);
-        assert!(!plain.is_meta);
+        assert_eq!(queue.clone_options(huge).filter.as_deref(), Some("tree:0"));
+        let options = queue.clone_options(plain);
+        assert_eq!(options.filter.as_deref(), Some("blob:none"));
+        assert_eq!(options.depth.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn project_shallow_settings_replace_queue_depth() {
+        let tmp = tempfile::tempdir().unwrap();
+        let origin = tmp.path().join("origin");
+        std::fs::create_dir_all(&origin).unwrap();
+        git(&origin, &["init", "-q", "-b", "main"]);
+        for message in ["one", "two", "three"] {
+            git(&origin, &["commit", "-q", "--allow-empty", "-m", message]);
+            if message == "one" {
+                git(&origin, &["tag", "v1"]);
+            }
+        }
+        let ws = tmp.path().join("ws");
+        std::fs::create_dir_all(&ws).unwrap();
+        std::fs::write(
+            ws.join(".meta"),
+            serde_json::json!({"projects": {
+                "recent": {
+                    "repo": format!("file://{}", origin.display()),
+                    "shallow_exclude": ["v1"],
+                    "protocol_v2": true,
+                },
+                "plain": format!("file://{}", origin.display()),
+            }})
+            .to_string(),
+        )
+        .unwrap();
+
+        let queue = CloneQueue::new(Some("1".into()), None);
+        queue.push_from_meta(&ws, 0).unwrap();
+        let tasks = queue.drain_all();
+        let recent = tasks.iter().find(|t| t.name == "recent").unwrap();
+        let options = queue.clone_options(recent);
+        assert_eq!(options.depth, None);
+        assert_eq!(options.shallow_exclude, ["v1"]);
+        assert!(options.protocol_v2 && options.is_shallow());
+        let plain = queue.clone_options(tasks.iter().find(|t| t.name == "plain").unwrap());
+        assert_eq!(plain.depth.as_deref(), Some("1"));
+        assert!(!plain.protocol_v2);
+
+        crate::clone_repo_with_options(&recent.url, &recent.target_path, None, &options).unwrap();
+        assert_eq!(
+            git(&recent.target_path, &["rev-list", "--count", "HEAD"]),
+            "2"
+        );
+    }
+
+    // ── retries ──────────────────────────────────────────────
+
+    #[test]
+    fn retry_delays_grow_and_cap() {
+        let policy = RetryPolicy {
+            jitter: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(policy.delay_for(1), Duration::from_secs(2));
+        assert_eq!(policy.delay_for(2), Duration::from_secs(4));
+        assert_eq!(policy.delay_for(10), Duration::from_secs(30));
+
+        let jittered = RetryPolicy::default().delay_for(1);
+        assert!(jittered >= Duration::from_millis(1600) && jittered <= Duration::from_millis(2400));
+    }
+
+    #[test]
+    fn transient_failures_are_retried_then_failed() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = CloneQueue::new(None, None).with_retry_policy(RetryPolicy {
+            max_attempts: 2,
+            base_delay: Duration::ZERO,
+            jitter: 0.0,
+            ..Default::default()
+        });
+        queue.push(make_task("a", &dir.path().join("a")));
+        let active = AtomicUsize::new(0);
+
+        let task = queue.take_one().unwrap();
+        let timeout = "ssh: connect to host github.com port 22: Connection timed out";
+        assert_eq!(queue.retry_or_fail(task, timeout), Some(Duration::ZERO));
+        assert!(!queue.is_finished(&active));
+        assert_eq!(queue.next_retry_in(), Some(Duration::ZERO));
+
+        let task = queue.take_one().unwrap();
+        assert_eq!(task.attempts, 1);
+        assert_eq!(queue.retry_or_fail(task, timeout), None);
+        assert!(queue.is_finished(&active));
+        assert_eq!(queue.get_counts(), (1, 1));
+    }
+
+    #[test]
+    fn permanent_failures_are_not_retried() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = CloneQueue::new(None, None);
+        queue.push(make_task("a", &dir.path().join("a")));
+        queue.push(make_task("b", &dir.path().join("b")));
+        let task = queue.take_one().unwrap();
+        assert_eq!(
+            queue.retry_or_fail(task, "Permission denied (publickey)."),
+            None
+        );
+        let task = queue.take_one().unwrap();
+        assert_eq!(
+            queue.retry_or_fail(task, "ERROR: Repository not found."),
+            None
+        );
+        assert!(queue.next_retry_in().is_none());
+        assert_eq!(
+            queue.failure_category(&dir.path().join("b")),
+            Some(FailureCategory::Auth)
+        );
+        assert_eq!(
+            queue.failure_category(&dir.path().join("a")),
+            Some(FailureCategory::NotFound)
+        );
+    }
+
+    #[test]
+    fn delayed_retry_is_not_taken_early() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = CloneQueue::new(None, None);
+        queue.push(make_task("a", &dir.path().join("a")));
+        let task = queue.take_one().unwrap();
+        assert!(queue
+            .retry_or_fail(task, "Could not resolve host: github.com")
+            .is_some());
+        assert!(queue.take_one().is_none());
+        assert!(!queue.push(make_task("a", &dir.path().join("a"))));
+        assert_eq!(queue.drain_all().len(), 1);
     }
 
     // ── mark_completed / nested discovery ─────────────────────
@@ -450,6 +2481,10 @@ mod tests {
             target_path: child_dir,
             depth_level: 0,
             is_meta: true,
+            options: ProjectOptions::default(),
+            ssh_command: None,
+            attempts: 0,
+            tags: Vec::new(),
         };
 
         let added = queue.mark_completed(&task).unwrap();
@@ -471,6 +2506,10 @@ mod tests {
             target_path: child_dir,
             depth_level: 0,
             is_meta: false,
+            options: ProjectOptions::default(),
+            ssh_command: None,
+            attempts: 0,
+            tags: Vec::new(),
         };
 
         let added = queue.mark_completed(&task).unwrap();
@@ -506,6 +2545,62 @@ mod tests {
         assert!(queue.take_one().is_none());
     }
 
+    #[test]
+    fn plan_follows_cloned_nested_meta_without_queueing() {
+        let dir = tempfile::tempdir().unwrap();
+        let ws = dir.path();
+        std::fs::create_dir_all(ws.join("platform")).unwrap();
+        std::fs::write(
+            ws.join(".meta"),
+            r#"{"projects": {
+                "platform": "git@github.com:org/platform.git",
+                "web": "git@github.com:org/web.git",
+                "old": {"repo": "git@github.com:org/old.git", "disabled": true},
+                "tools": {"repo": "git@github.com:org/tools.git", "meta": true}
+            }}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            ws.join("platform/.meta"),
+            r#"{"projects": {"lib": "git@github.com:org/lib.git"}}"#,
+        )
+        .unwrap();
+
+        let queue = CloneQueue::new(None, None);
+        let plan = queue.plan(ws).unwrap();
+        let mut summary: Vec<_> = plan
+            .projects
+            .iter()
+            .map(|n| (n.name.as_str(), n.action, n.is_meta, n.children.len()))
+            .collect();
+        summary.sort_by_key(|(name, ..)| *name);
+        assert_eq!(
+            summary,
+            [
+                ("old", PlanAction::Disabled, false, 0),
+                ("platform", PlanAction::Present, true, 1),
+                ("tools", PlanAction::Clone, true, 0),
+                ("web", PlanAction::Clone, false, 0),
+            ]
+        );
+        let platform = plan.projects.iter().find(|n| n.name == "platform").unwrap();
+        assert_eq!(platform.children[0].depth_level, 1);
+        assert_eq!(platform.children[0].target_path, ws.join("platform/lib"));
+        assert_eq!(plan.clone_count(), 3);
+        assert!(queue.take_one().is_none());
+
+        let json = serde_json::to_value(&plan).unwrap();
+        assert!(json["projects"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|p| p["children"][0]["action"] == "clone"));
+
+        // The filter applies at every level
+        let queue = CloneQueue::new(None, None).with_project_filter(ProjectFilter::only("lib"));
+        assert_eq!(queue.plan(ws).unwrap().clone_count(), 1);
+    }
+
     // ── peek_ssh_hosts ──────────────────────────────────────────
 
     fn make_task_with_url(name: &str, url: &str, path: &Path) -> CloneTask {
@@ -515,6 +2610,10 @@ mod tests {
             target_path: path.to_path_buf(),
             depth_level: 0,
             is_meta: false,
+            options: ProjectOptions::default(),
+            ssh_command: None,
+            attempts: 0,
+            tags: Vec::new(),
         }
     }
 
@@ -623,4 +2722,711 @@ mod tests {
         let hosts = queue.peek_ssh_hosts();
         assert_eq!(hosts, vec!["github.com"]); // only SSH host
     }
+
+    #[test]
+    fn sparse_paths_from_meta_reach_the_clone() {
+        let tmp = tempfile::tempdir().unwrap();
+        let origin = tmp.path().join("origin");
+        repo(
+            &origin,
+            &[("services/api/main.rs", ""), ("services/web/index.js", "")],
+        );
+        let ws = tmp.path().join("ws");
+        std::fs::create_dir_all(&ws).unwrap();
+        std::fs::write(
+            ws.join(".meta"),
+            format!(
+                r#"{{"projects": {{"mono": {{"repo": "{}", "sparse_paths": ["services/api"]}}}}}}"#,
+                origin.display()
+            ),
+        )
+        .unwrap();
+
+        let queue = CloneQueue::new(None, None);
+        queue.push_from_meta(&ws, 0).unwrap();
+        let task = queue.drain_all().pop().unwrap();
+        let options = queue.clone_options(&task);
+        assert_eq!(options.sparse_paths, ["services/api"]);
+
+        crate::clone_repo_with_options(&task.url, &task.target_path, None, &options).unwrap();
+        assert!(task.target_path.join("services/api/main.rs").exists());
+        assert!(!task.target_path.join("services/web").exists());
+    }
+
+    #[test]
+    fn reference_store_supplies_local_objects() {
+        let tmp = tempfile::tempdir().unwrap();
+        let origin = tmp.path().join("origin");
+        repo(&origin, &[]);
+        let origin_url = origin.to_string_lossy().into_owned();
+        let mirror = tmp.path().join("cache").join("origin.git");
+        git(
+            tmp.path(),
+            &[
+                "clone",
+                "-q",
+                "--mirror",
+                &origin_url,
+                mirror.to_str().unwrap(),
+            ],
+        );
+
+        let store = ReferenceStore::scan(&[tmp.path().join("cache")]);
+        let queue = CloneQueue::new(None, None).with_reference_store(store);
+        let target = tmp.path().join("ws").join("origin");
+        queue.push(make_task_with_url("origin", &origin_url, &target));
+        let task = queue.drain_all().pop().unwrap();
+        let options = queue.clone_options(&task);
+        assert_eq!(options.reference.as_deref(), Some(mirror.as_path()));
+
+        crate::clone_repo_with_options(&task.url, &target, None, &options).unwrap();
+        // Dissociated: no alternates left pointing at the mirror
+        assert!(!target.join(".git/objects/info/alternates").exists());
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn persistent_queue_resumes_after_interruption() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp.path().join("data"));
+        let ws = tmp.path().join("ws");
+
+        let queue = CloneQueue::new(Some("1".into()), None)
+            .with_retry_policy(RetryPolicy::none())
+            .with_task_timeout(Some(Duration::from_secs(60)))
+            .with_new_host_keys(NewHostKeys::Scan)
+            .with_nested_setup(true)
+            .with_max_transfers(Some(2))
+            .with_persistence()
+            .unwrap();
+        for name in ["a", "b", "c", "d"] {
+            queue.push(make_task(name, &ws.join(name)));
+        }
+        // Pushed tasks are saved with the next state change, not one by one
+        assert!(read_saved_state().unwrap().unwrap().tasks.is_empty());
+        let done = queue.take_one().unwrap();
+        queue.mark_completed(&done).unwrap();
+        let failed = queue.take_one().unwrap();
+        queue.retry_or_fail(failed.clone(), "fatal: boom");
+        // One task is in flight when the run is interrupted
+        let in_flight = queue.take_one().unwrap();
+        drop(queue);
+
+        let state = read_saved_state().unwrap().unwrap();
+        assert_eq!(state.tasks.len(), 4);
+        assert!(state.failed.contains(&failed.target_path));
+        assert_eq!(
+            state.failure_categories.get(&failed.target_path),
+            Some(&FailureCategory::classify("fatal: boom"))
+        );
+
+        let resumed = CloneQueue::resume().unwrap().unwrap();
+        assert!(resumed.is_persistent());
+        assert_eq!(resumed.git_depth(), Some("1"));
+        assert_eq!(resumed.retry_policy, RetryPolicy::none());
+        assert_eq!(resumed.task_timeout, Some(Duration::from_secs(60)));
+        assert_eq!(resumed.new_host_keys, NewHostKeys::Scan);
+        assert!(resumed.nested_setup);
+        assert_eq!(resumed.max_transfers(), Some(2));
+        assert_eq!(resumed.get_counts(), (1, 4));
+        let mut names: Vec<String> = resumed.drain_all().into_iter().map(|t| t.name).collect();
+        names.sort();
+        // `d` completed; `c` failed and `b` was in flight, so both come back
+        assert_eq!((done.name.as_str(), failed.name.as_str()), ("d", "c"));
+        assert_eq!(in_flight.name, "b");
+        assert_eq!(names, ["a", "b", "c"]);
+        let state = read_saved_state().unwrap().unwrap();
+        assert!(state.failed.is_empty());
+        assert!(state.failure_categories.is_empty());
+
+        clear_saved_state().unwrap();
+        assert!(CloneQueue::resume().unwrap().is_none());
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn non_persistent_queue_saves_nothing() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp.path());
+        let queue = CloneQueue::new(None, None);
+        queue.push(make_task("a", &tmp.path().join("a")));
+        assert!(read_saved_state().unwrap().is_none());
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    fn failures_are_categorized_in_the_outcome() {
+        let tmp = tempfile::tempdir().unwrap();
+        let queue = CloneQueue::new(None, None).with_retry_policy(RetryPolicy::none());
+        for name in ["ok", "denied", "dropped"] {
+            queue.push(make_task(name, &tmp.path().join(name)));
+        }
+        while let Some(task) = queue.take_one() {
+            match task.name.as_str() {
+                "ok" => {
+                    queue.mark_completed(&task).unwrap();
+                }
+                "denied" => {
+                    queue.retry_or_fail(task, "git@github.com: Permission denied (publickey).");
+                }
+                _ => {
+                    queue.retry_or_fail(task, "Connection closed by 140.82.112.3 port 22");
+                }
+            }
+        }
+
+        let outcome = queue.outcome();
+        assert_eq!(outcome.succeeded, 1);
+        assert_eq!(
+            outcome.summary(),
+            "clone: 1 ok, 2 failed (auth: 1, network: 1)"
+        );
+        assert_eq!(queue.rate_limited_hosts(), ["github.com"]);
+    }
+
+    #[test]
+    fn clone_error_carries_git_stderr() {
+        let tmp = tempfile::tempdir().unwrap();
+        let missing = tmp.path().join("missing").to_string_lossy().into_owned();
+        let err = crate::clone_repo_with_options(
+            &missing,
+            &tmp.path().join("dest"),
+            None,
+            &crate::CloneOptions::default(),
+        )
+        .unwrap_err();
+        let clone_err = err.downcast_ref::<crate::CloneError>().unwrap();
+        assert_eq!(clone_err.category, FailureCategory::NotFound);
+        assert!(clone_err.stderr.contains("does not exist"));
+        assert!(err.to_string().contains("(not_found)"));
+    }
+
+    #[test]
+    fn clone_lands_only_on_success_and_clears_stale_partials() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("source");
+        repo(&source, &[]);
+        let target = tmp.path().join("dest");
+        // Left behind by a process that was killed mid-clone
+        let stale = tmp.path().join(format!("dest.partial-{}", u32::MAX - 1));
+        std::fs::create_dir_all(stale.join(".git")).unwrap();
+
+        crate::clone_repo_with_options(
+            &source.to_string_lossy(),
+            &target,
+            None,
+            &crate::CloneOptions::default(),
+        )
+        .unwrap();
+        assert!(target.join(".git").exists());
+        #[cfg(unix)]
+        assert!(!stale.exists());
+        assert!(!crate::collision::partial_path(&target).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn timed_out_clone_fails_with_timeout_reason() {
+        let tmp = tempfile::tempdir().unwrap();
+        let queue = CloneQueue::new(None, None)
+            .with_task_timeout(Some(Duration::from_millis(300)))
+            .with_retry_policy(RetryPolicy::none());
+        let target = tmp.path().join("dest");
+        queue.push(make_task_with_url(
+            "slow",
+            "ssh://example.invalid/org/slow.git",
+            &target,
+        ));
+        let task = queue.take_one().unwrap();
+        let mut options = queue.clone_options(&task);
+        assert_eq!(options.timeout, Some(Duration::from_millis(300)));
+        // An ssh that never answers
+        options.ssh_command = Some("sh -c 'sleep 30' --".to_string());
+
+        let err = crate::clone_repo_with_options(&task.url, &target, None, &options).unwrap_err();
+        let clone_err = err.downcast_ref::<crate::CloneError>().unwrap();
+        assert_eq!(clone_err.category, FailureCategory::Timeout);
+        assert!(!target.exists());
+        assert!(!crate::collision::partial_path(&target).exists());
+
+        assert_eq!(queue.retry_or_fail(task, &err.to_string()), None);
+        assert_eq!(
+            queue.outcome().failures_by_category()[&FailureCategory::Timeout],
+            1
+        );
+    }
+
+    #[test]
+    fn collision_policy_queues_and_replaces_foreign_targets() {
+        let tmp = tempfile::tempdir().unwrap();
+        let origin = tmp.path().join("origin");
+        repo(&origin, &[]);
+        let ws = tmp.path().join("ws");
+        let target = ws.join("api");
+        std::fs::create_dir_all(&target).unwrap();
+        std::fs::write(target.join("notes.txt"), "not a repo").unwrap();
+        std::fs::write(
+            ws.join(".meta"),
+            format!(r#"{{"projects": {{"api": "{}"}}}}"#, origin.display()),
+        )
+        .unwrap();
+
+        // The default policy leaves the directory alone
+        let queue = CloneQueue::new(None, None);
+        assert_eq!(queue.push_from_meta(&ws, 0).unwrap(), 0);
+
+        let queue = CloneQueue::new(None, None).with_collision_policy(CollisionPolicy::Fail);
+        assert_eq!(queue.push_from_meta(&ws, 0).unwrap(), 1);
+        let task = queue.drain_all().pop().unwrap();
+        let err =
+            crate::clone_repo_with_options(&task.url, &target, None, &queue.clone_options(&task))
+                .unwrap_err();
+        let collision = err
+            .downcast_ref::<crate::collision::CollisionError>()
+            .unwrap();
+        assert_eq!(collision.found, crate::collision::TargetState::NotARepo);
+
+        let queue = CloneQueue::new(None, None).with_collision_policy(CollisionPolicy::Replace);
+        let result =
+            crate::clone_repo_with_options(&task.url, &target, None, &queue.clone_options(&task))
+                .unwrap();
+        let crate::collision::CloneAction::Replaced { backup } = result.action else {
+            panic!("expected a replacement, got {result:?}");
+        };
+        assert!(backup.join("notes.txt").exists());
+        assert_eq!(
+            crate::collision::inspect_target(&target, &task.url),
+            crate::collision::TargetState::Matching
+        );
+
+        // Now the expected repo: adopted, and no longer queued
+        let queue = CloneQueue::new(None, None).with_collision_policy(CollisionPolicy::Adopt);
+        let result =
+            crate::clone_repo_with_options(&task.url, &target, None, &queue.clone_options(&task))
+                .unwrap();
+        assert_eq!(result.action, crate::collision::CloneAction::Adopted);
+        assert_eq!(queue.push_from_meta(&ws, 0).unwrap(), 0);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn run_clones_nested_projects_and_reports_each_repo() {
+        struct Counter(AtomicUsize);
+        impl CloneReporter for Counter {
+            fn started(&self, _task: &CloneTask) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp.path().join("store"));
+        let lib = tmp.path().join("lib-origin");
+        repo(&lib, &[]);
+        let platform = tmp.path().join("platform-origin");
+        std::fs::create_dir_all(&platform).unwrap();
+        std::fs::write(
+            platform.join(".meta"),
+            format!(
+                r#"{{"projects": {{"lib": {{"repo": "{}", "setup": ["touch bootstrapped"]}}}}}}"#,
+                lib.display()
+            ),
+        )
+        .unwrap();
+        repo(&platform, &[]);
+
+        let ws = tmp.path().join("ws");
+        std::fs::create_dir_all(&ws).unwrap();
+        let hook_log = tmp.path().join("hooks.log");
+        std::fs::write(
+            ws.join(".meta"),
+            serde_json::json!({
+                "projects": {
+                    "platform": platform.display().to_string(),
+                    "broken": tmp.path().join("missing").display().to_string(),
+                },
+                // One append per hook, as repo hooks run concurrently
+                "hooks": {
+                    "post-clone": format!(
+                        "touch post-clone-ran; printf '%s\\n' \"$META_HOOK_PAYLOAD\" >> '{}'",
+                        hook_log.display()
+                    ),
+                    "post-clone-all": format!(
+                        "printf '%s\\n' \"$META_HOOK_PAYLOAD\" >> '{}'",
+                        hook_log.display()
+                    ),
+                },
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let queue = CloneQueue::new(None, None).with_retry_policy(RetryPolicy::none());
+        queue.push_from_meta(&ws, 0).unwrap();
+        let reporter = Counter(AtomicUsize::new(0));
+        let report = queue.run(4, &reporter);
+
+        assert_eq!(reporter.0.load(Ordering::SeqCst), 3);
+        assert_eq!(report.per_repo.len(), 3);
+        assert_eq!(
+            (report.succeeded, report.failed, report.skipped_existing),
+            (2, 1, 0)
+        );
+        assert!(ws.join("platform/lib/.git").exists());
+        let nested = report.per_repo.iter().find(|r| r.name == "lib").unwrap();
+        assert_eq!(nested.depth_level, 1);
+        assert!(matches!(nested.status, RepoCloneStatus::Done(_)));
+        // Nested .meta files come from the cloned repos; their setup doesn't run
+        assert!(nested.setup.is_empty());
+        assert!(!ws.join("platform/lib/bootstrapped").exists());
+        assert!(nested.stats.is_some_and(|s| s.objects >= 2 && s.bytes > 0));
+        assert!(report.objects >= 4);
+        assert_eq!(report.slowest(1).len(), 1);
+        let failed: Vec<_> = report.failures().map(|r| r.name.as_str()).collect();
+        assert_eq!(failed, ["broken"]);
+        assert_eq!(
+            report.outcome.summary(),
+            "clone: 2 ok, 1 failed (not_found: 1)"
+        );
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["per_repo"].as_array().unwrap().len(), 3);
+        assert_eq!(json["failed"], 1);
+        assert_eq!(queue.get_counts(), (3, 3));
+
+        // post-clone fired for each repo, then post-clone-all for the run
+        let payloads: Vec<serde_json::Value> = std::fs::read_to_string(&hook_log)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(payloads.len(), 4);
+        assert!(payloads[..3].iter().all(|p| p["scope"] == "repo"));
+        let lib_payload = payloads.iter().find(|p| p["name"] == "lib").unwrap();
+        assert_eq!(lib_payload["depth"], 1);
+        assert_eq!(lib_payload["url"], lib.display().to_string());
+        assert!(lib_payload["path"].as_str().unwrap().ends_with("lib"));
+        // Each repo's hook ran in the repo
+        assert!(ws.join("platform/post-clone-ran").exists());
+        assert!(ws.join("platform/lib/post-clone-ran").exists());
+        assert_eq!(payloads[3]["scope"], "run");
+        assert_eq!(payloads[3]["repos"].as_array().unwrap().len(), 3);
+        assert_eq!(payloads[3]["bytes"], report.bytes);
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn nested_setup_is_opt_in_and_failed_setup_fails_the_repo() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp.path().join("store"));
+        let lib = tmp.path().join("lib-origin");
+        repo(&lib, &[]);
+        let platform = tmp.path().join("platform-origin");
+        std::fs::create_dir_all(&platform).unwrap();
+        std::fs::write(
+            platform.join(".meta"),
+            format!(
+                r#"{{"projects": {{"lib": {{"repo": "{}", "setup": ["touch bootstrapped"]}}}}}}"#,
+                lib.display()
+            ),
+        )
+        .unwrap();
+        repo(&platform, &[]);
+
+        let ws = tmp.path().join("ws");
+        std::fs::create_dir_all(&ws).unwrap();
+        std::fs::write(
+            ws.join(".meta"),
+            serde_json::json!({
+                "projects": {
+                    "platform": platform.display().to_string(),
+                    "tool": {"repo": lib.display().to_string(), "setup": ["exit 4"]},
+                },
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let queue = CloneQueue::new(None, None)
+            .with_retry_policy(RetryPolicy::none())
+            .with_nested_setup(true);
+        queue.push_from_meta(&ws, 0).unwrap();
+        let report = queue.run(2, &());
+
+        assert!(ws.join("platform/lib/bootstrapped").exists());
+        let tool = report.per_repo.iter().find(|r| r.name == "tool").unwrap();
+        assert_eq!(tool.setup.len(), 1);
+        assert!(matches!(
+            tool.status,
+            RepoCloneStatus::Failed {
+                category: FailureCategory::Other,
+                ..
+            }
+        ));
+        let failed: Vec<_> = report.failures().map(|r| r.name.as_str()).collect();
+        assert_eq!(failed, ["tool"]);
+        assert_eq!(
+            queue.failure_category(&ws.join("tool")),
+            Some(FailureCategory::Other)
+        );
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    fn transfer_limit_from_meta_unless_set_by_caller() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tmp.path().join(".meta"),
+            r#"{"projects": {}, "clone_max_transfers": 2}"#,
+        )
+        .unwrap();
+
+        let queue = CloneQueue::new(None, None);
+        assert_eq!(queue.max_transfers(), None);
+        queue.push_from_meta(tmp.path(), 0).unwrap();
+        assert_eq!(queue.max_transfers(), Some(2));
+
+        let queue = CloneQueue::new(None, None).with_max_transfers(Some(5));
+        queue.push_from_meta(tmp.path(), 0).unwrap();
+        assert_eq!(queue.max_transfers(), Some(5));
+        assert_eq!(
+            CloneQueue::new(None, None)
+                .with_max_transfers(Some(0))
+                .max_transfers(),
+            None
+        );
+    }
+
+    #[test]
+    fn transfer_permits_cap_concurrent_clones() {
+        let queue = CloneQueue::new(None, None).with_max_transfers(Some(2));
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..6 {
+                scope.spawn(|| {
+                    let _permit = queue.acquire_transfer();
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(20));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn refused_ssh_clone_falls_back_to_https() {
+        let tmp = tempfile::tempdir().unwrap();
+        let origin = tmp.path().join("origin");
+        repo(&origin, &[]);
+        // Serve the HTTPS URL from the local repo
+        std::env::set_var("GIT_CONFIG_COUNT", "1");
+        std::env::set_var(
+            "GIT_CONFIG_KEY_0",
+            format!("url.{}.insteadOf", origin.display()),
+        );
+        std::env::set_var("GIT_CONFIG_VALUE_0", "https://fallback.invalid/org/app.git");
+
+        let url = "git@fallback.invalid:org/app.git";
+        let queue = CloneQueue::new(None, None).with_https_fallback(true);
+        let target = tmp.path().join("app");
+        let mut options = queue.clone_options(&make_task_with_url("app", url, &target));
+        // An SSH server that drops every connection
+        options.ssh_command =
+            Some("sh -c 'echo Connection reset by peer >&2; exit 255' --".to_string());
+
+        let result = crate::clone_repo_with_options(url, &target, None, &options);
+        for key in ["GIT_CONFIG_COUNT", "GIT_CONFIG_KEY_0", "GIT_CONFIG_VALUE_0"] {
+            std::env::remove_var(key);
+        }
+        let result = result.unwrap();
+        assert_eq!(
+            result.transport,
+            Some(crate::collision::CloneTransport::Https)
+        );
+        assert_eq!(crate::get_remote_url(&target).as_deref(), Some(url));
+
+        options.https_fallback = false;
+        let err = crate::clone_repo_with_options(url, &tmp.path().join("other"), None, &options)
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<crate::CloneError>().unwrap().category,
+            FailureCategory::Network
+        );
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn clones_from_mirror_then_fetches_origin() {
+        let tmp = tempfile::tempdir().unwrap();
+        let origin = tmp.path().join("origin");
+        repo(&origin, &[]);
+        let mirrors = tmp.path().join("mirrors");
+        let mirror = mirrors.join("mirror.invalid/org/app.git");
+        std::fs::create_dir_all(mirror.parent().unwrap()).unwrap();
+        git(
+            tmp.path(),
+            &[
+                "clone",
+                "-q",
+                "--mirror",
+                origin.to_str().unwrap(),
+                mirror.to_str().unwrap(),
+            ],
+        );
+        // The remote moves on after the mirror was made
+        git(&origin, &["commit", "-q", "--allow-empty", "-m", "two"]);
+        std::env::set_var("GIT_CONFIG_COUNT", "1");
+        std::env::set_var(
+            "GIT_CONFIG_KEY_0",
+            format!("url.{}.insteadOf", origin.display()),
+        );
+        std::env::set_var("GIT_CONFIG_VALUE_0", "https://mirror.invalid/org/app.git");
+
+        let url = "https://mirror.invalid/org/app.git";
+        let queue = CloneQueue::new(None, None).with_mirror_root(Some(mirrors));
+        let target = tmp.path().join("app");
+        let options = queue.clone_options(&make_task_with_url("app", url, &target));
+        let result = crate::clone_repo_with_options(url, &target, None, &options);
+        for key in ["GIT_CONFIG_COUNT", "GIT_CONFIG_KEY_0", "GIT_CONFIG_VALUE_0"] {
+            std::env::remove_var(key);
+        }
+        result.unwrap();
+
+        assert_eq!(crate::get_remote_url(&target).as_deref(), Some(url));
+        assert_eq!(
+            git(&target, &["rev-parse", "HEAD"]),
+            git(&origin, &["rev-parse", "HEAD"])
+        );
+        // Objects came from the mirror, not a reference to it
+        assert!(!target.join(".git/objects/info/alternates").exists());
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn submodules_option_clones_submodules() {
+        let tmp = tempfile::tempdir().unwrap();
+        let lib = tmp.path().join("lib");
+        let app = tmp.path().join("app");
+        repo(&lib, &[]);
+        std::fs::create_dir_all(&app).unwrap();
+        git(&app, &["init", "-q"]);
+        git(
+            &app,
+            &[
+                "-c",
+                "protocol.file.allow=always",
+                "submodule",
+                "add",
+                "-q",
+                &lib.to_string_lossy(),
+                "vendor/lib",
+            ],
+        );
+        git(&app, &["commit", "-q", "-m", "add submodule"]);
+        let ws = tmp.path().join("ws");
+        std::fs::create_dir_all(&ws).unwrap();
+        std::fs::write(
+            ws.join(".meta"),
+            format!(
+                r#"{{"projects": {{"app": {{"repo": "{}", "submodules": true}}}}}}"#,
+                app.display()
+            ),
+        )
+        .unwrap();
+
+        let queue = CloneQueue::new(None, None);
+        queue.push_from_meta(&ws, 0).unwrap();
+        let task = queue.drain_all().pop().unwrap();
+        let options = queue.clone_options(&task);
+        assert!(options.submodules);
+        // Local submodule URLs need the file protocol
+        std::env::set_var("GIT_CONFIG_COUNT", "1");
+        std::env::set_var("GIT_CONFIG_KEY_0", "protocol.file.allow");
+        std::env::set_var("GIT_CONFIG_VALUE_0", "always");
+        let result = crate::clone_repo_with_options(&task.url, &task.target_path, None, &options);
+        for key in ["GIT_CONFIG_COUNT", "GIT_CONFIG_KEY_0", "GIT_CONFIG_VALUE_0"] {
+            std::env::remove_var(key);
+        }
+        result.unwrap();
+        assert!(task.target_path.join("vendor/lib/.git").exists());
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn reporter_sees_queue_events_in_order() {
+        #[derive(Default)]
+        struct Log(Mutex<Vec<String>>);
+        impl Log {
+            fn push(&self, event: String) {
+                self.0.lock().unwrap().push(event);
+            }
+        }
+        impl CloneReporter for Log {
+            fn discovered(&self, task: &CloneTask) {
+                self.push(format!("discovered {}", task.name));
+            }
+            fn started(&self, task: &CloneTask) {
+                self.push(format!("started {}", task.name));
+            }
+            fn nested_meta_found(&self, task: &CloneTask, _meta_path: &Path, added: usize) {
+                self.push(format!("nested {} +{added}", task.name));
+            }
+            fn finished(&self, result: &RepoCloneResult, _completed: usize, _discovered: usize) {
+                let status = match result.status {
+                    RepoCloneStatus::Done(_) => "done",
+                    RepoCloneStatus::Failed { .. } => "failed",
+                };
+                self.push(format!("{status} {}", result.name));
+            }
+            fn failed(&self, target_path: &Path, failure: &RepoFailure) {
+                assert!(target_path.ends_with("lib"));
+                self.push(format!("gave up on {}", failure.repo));
+            }
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp.path().join("store"));
+        let platform = tmp.path().join("platform-origin");
+        let missing = tmp.path().join("missing");
+        repo(
+            &platform,
+            &[(
+                ".meta",
+                &serde_json::json!({"projects": {"lib": missing.display().to_string()}})
+                    .to_string(),
+            )],
+        );
+        let ws = tmp.path().join("ws");
+        std::fs::create_dir_all(&ws).unwrap();
+        std::fs::write(
+            ws.join(".meta"),
+            serde_json::json!({"projects": {"platform": platform.display().to_string()}})
+                .to_string(),
+        )
+        .unwrap();
+
+        let queue = CloneQueue::new(None, None).with_retry_policy(RetryPolicy::none());
+        queue.push_from_meta(&ws, 0).unwrap();
+        let log = Log::default();
+        queue.run(1, &log);
+
+        assert_eq!(
+            *log.0.lock().unwrap(),
+            [
+                "discovered platform",
+                "started platform",
+                "discovered lib",
+                "nested platform +1",
+                "done platform",
+                "started lib",
+                "gave up on lib",
+                "failed lib",
+            ]
+        );
+        std::env::remove_var("META_DATA_DIR");
+    }
 }
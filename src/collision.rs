@@ -0,0 +1,304 @@
+//! What to do when a clone target directory already exists.
+//!
+//! An existing target may be the expected repo, a checkout of a different
+//! remote, a plain directory, or the remains of an interrupted clone.
+//! [`inspect_target`] tells these apart and the [`CollisionPolicy`] decides
+//! whether the clone pipeline skips it, adopts it, replaces it with a fresh
+//! clone, or fails. Each clone reports a [`CloneResult`] with what it found
+//! and what it did.
+//!
+//! Clones are written to a [`partial_path`] first and renamed into place on
+//! success; [`remove_stale_partials`] cleans up after processes that died
+//! mid-clone.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::ssh_multiplexing::{get_remote_url, urls_match};
+
+/// Handling of an existing target directory that isn't the expected repo.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionPolicy {
+    /// Leave it alone and move on
+    #[default]
+    Skip,
+    /// Use it only if it is a complete clone of the expected remote; error otherwise
+    Adopt,
+    /// Clone into a temporary sibling and swap it in, keeping the old
+    /// directory as a backup
+    Replace,
+    /// Error on any existing target other than the expected repo
+    Fail,
+}
+
+/// What was found at a clone target.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "state")]
+pub enum TargetState {
+    /// Nothing there yet
+    Missing,
+    /// A complete clone of the expected remote
+    Matching,
+    /// A repo whose `origin` is another remote (or missing)
+    WrongRemote { found: Option<String> },
+    /// A directory (or file) that isn't a repo
+    NotARepo,
+    /// A repo without a checked-out commit, e.g. an interrupted clone
+    Incomplete,
+}
+
+impl TargetState {
+    /// Whether the target is in the way of a clone of the expected remote.
+    pub fn is_collision(&self) -> bool {
+        !matches!(self, TargetState::Missing | TargetState::Matching)
+    }
+}
+
+impl std::fmt::Display for TargetState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TargetState::Missing => f.write_str("missing"),
+            TargetState::Matching => f.write_str("expected repo"),
+            TargetState::WrongRemote { found: Some(url) } => write!(f, "repo of {url}"),
+            TargetState::WrongRemote { found: None } => f.write_str("repo without origin"),
+            TargetState::NotARepo => f.write_str("not a git repo"),
+            TargetState::Incomplete => f.write_str("incomplete clone"),
+        }
+    }
+}
+
+/// What the clone pipeline did with a target.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "action")]
+pub enum CloneAction {
+    Cloned,
+    Skipped,
+    /// The existing repo was verified and kept
+    Adopted,
+    /// A fresh clone replaced the target; the old one was moved to `backup`
+    Replaced {
+        backup: PathBuf,
+    },
+}
+
+/// How a clone reached its remote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CloneTransport {
+    Ssh,
+    Https,
+    /// Local paths, `file://`, `git://`, and anything else
+    Other,
+}
+
+impl CloneTransport {
+    pub fn of(url: &str) -> Self {
+        let url = url.trim();
+        if url.starts_with("https://") || url.starts_with("http://") {
+            CloneTransport::Https
+        } else if crate::ssh_multiplexing::extract_ssh_host(url).is_some() {
+            CloneTransport::Ssh
+        } else {
+            CloneTransport::Other
+        }
+    }
+}
+
+/// Per-repo result of a clone.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CloneResult {
+    pub found: TargetState,
+    pub action: CloneAction,
+    /// Transport of the clone that succeeded; `None` if nothing was cloned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transport: Option<CloneTransport>,
+}
+
+/// An existing target the [`CollisionPolicy`] did not allow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollisionError {
+    pub target_dir: PathBuf,
+    pub url: String,
+    pub found: TargetState,
+    pub policy: CollisionPolicy,
+}
+
+impl std::fmt::Display for CollisionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Cannot clone {} into {}: found {} ({:?} policy)",
+            self.url,
+            self.target_dir.display(),
+            self.found,
+            self.policy
+        )
+    }
+}
+
+impl std::error::Error for CollisionError {}
+
+/// Inspect `target_dir` for a clone of `url`.
+pub fn inspect_target(target_dir: &Path, url: &str) -> TargetState {
+    if !target_dir.exists() {
+        return TargetState::Missing;
+    }
+    if !target_dir.join(".git").exists() {
+        return TargetState::NotARepo;
+    }
+    let has_head = crate::git_runner::output(
+        Command::new("git")
+            .args(["rev-parse", "--verify", "--quiet", "HEAD"])
+            .current_dir(target_dir),
+    )
+    .is_ok_and(|o| o.status.success());
+    if !has_head {
+        return TargetState::Incomplete;
+    }
+    match get_remote_url(target_dir) {
+        Some(found) if urls_match(&found, url) => TargetState::Matching,
+        found => TargetState::WrongRemote { found },
+    }
+}
+
+/// Sibling of `target_dir` named `.<name>.<suffix>`, for temporary clones
+/// and backups.
+pub fn sibling_path(target_dir: &Path, suffix: &str) -> PathBuf {
+    let name = target_dir
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    target_dir.with_file_name(format!(".{name}.{suffix}"))
+}
+
+/// Where a clone of `target_dir` is written before being renamed into
+/// place: `<target>.partial-<pid>`.
+pub fn partial_path(target_dir: &Path) -> PathBuf {
+    let mut name = target_dir.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".partial-{}", std::process::id()));
+    target_dir.with_file_name(name)
+}
+
+/// Remove partial clones of `target_dir` left by processes that are no
+/// longer running. Returns the removed paths.
+pub fn remove_stale_partials(target_dir: &Path) -> Vec<PathBuf> {
+    let (Some(parent), Some(name)) = (target_dir.parent(), target_dir.file_name()) else {
+        return Vec::new();
+    };
+    let prefix = format!("{}.partial-", name.to_string_lossy());
+    remove_stale(parent, |file_name| {
+        file_name.strip_prefix(&prefix)?.parse().ok()
+    })
+}
+
+/// Remove the partial clones of every target in `dir` left by processes
+/// that are no longer running, reading `dir` once. Returns the removed
+/// paths.
+pub fn remove_stale_partials_in(dir: &Path) -> Vec<PathBuf> {
+    remove_stale(dir, |file_name| {
+        file_name.rsplit_once(".partial-")?.1.parse().ok()
+    })
+}
+
+/// Remove the entries of `dir` for which `pid_of` gives the pid of a
+/// process that is no longer running.
+fn remove_stale(dir: &Path, pid_of: impl Fn(&str) -> Option<u32>) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut removed = Vec::new();
+    for entry in entries.flatten() {
+        let Some(pid) = pid_of(&entry.file_name().to_string_lossy()) else {
+            continue;
+        };
+        if crate::lock::pid_alive(pid) {
+            continue;
+        }
+        let path = entry.path();
+        match std::fs::remove_dir_all(&path) {
+            Ok(()) => removed.push(path),
+            Err(e) => log::warn!("Failed to remove partial clone {}: {e}", path.display()),
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::git;
+
+    #[test]
+    fn inspects_existing_targets() {
+        let tmp = tempfile::tempdir().unwrap();
+        let url = "git@github.com:org/api.git";
+        let target = tmp.path().join("api");
+        assert_eq!(inspect_target(&target, url), TargetState::Missing);
+
+        std::fs::create_dir_all(&target).unwrap();
+        assert_eq!(inspect_target(&target, url), TargetState::NotARepo);
+
+        git(&target, &["init", "-q"]);
+        assert_eq!(inspect_target(&target, url), TargetState::Incomplete);
+
+        git(&target, &["commit", "-q", "--allow-empty", "-m", "init"]);
+        assert_eq!(
+            inspect_target(&target, url),
+            TargetState::WrongRemote { found: None }
+        );
+        git(
+            &target,
+            &["remote", "add", "origin", "git@github.com:org/web.git"],
+        );
+        assert!(inspect_target(&target, url).is_collision());
+
+        git(
+            &target,
+            &[
+                "remote",
+                "set-url",
+                "origin",
+                "ssh://git@github.com/org/api",
+            ],
+        );
+        assert_eq!(inspect_target(&target, url), TargetState::Matching);
+        assert!(!TargetState::Matching.is_collision());
+    }
+
+    #[test]
+    fn sibling_paths_are_hidden() {
+        assert_eq!(
+            sibling_path(Path::new("/ws/libs/api"), "meta-clone-tmp"),
+            PathBuf::from("/ws/libs/.api.meta-clone-tmp")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn removes_only_partials_of_dead_processes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let target = tmp.path().join("api");
+        let own = partial_path(&target);
+        assert_eq!(
+            own.file_name().unwrap().to_string_lossy(),
+            format!("api.partial-{}", std::process::id())
+        );
+        // No process has pid u32::MAX - 1
+        let stale = tmp.path().join(format!("api.partial-{}", u32::MAX - 1));
+        let other_repo = tmp.path().join(format!("web.partial-{}", u32::MAX - 1));
+        for dir in [&own, &stale, &other_repo] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+
+        assert_eq!(remove_stale_partials(&target), vec![stale.clone()]);
+        assert!(own.exists() && other_repo.exists() && !stale.exists());
+
+        assert_eq!(
+            remove_stale_partials_in(tmp.path()),
+            vec![other_repo.clone()]
+        );
+        assert!(own.exists() && !other_repo.exists());
+    }
+}
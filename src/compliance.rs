@@ -0,0 +1,535 @@
+//! License inventory across the workspace.
+//!
+//! [`inventory`] finds each repo's license file (`LICENSE`, `LICENCE`,
+//! `COPYING`, with any extension) and identifies it as an SPDX id from its
+//! text, counts `SPDX-License-Identifier:` headers in tracked source files,
+//! and flags repos that don't match what the org requires:
+//!
+//! ```json
+//! { "compliance": { "license": "Apache-2.0", "headers": true } }
+//! ```
+//!
+//! The [`ComplianceReport`] serializes for legal reviews, and its
+//! [`findings`](ComplianceReport::findings) feed [`crate::sarif`].
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::git_output::nul_fields;
+use crate::sarif::{Finding, Level};
+use crate::worktree::helpers::{load_projects_with_root, read_meta_config_value};
+
+/// Header marker scanned for in source files.
+pub const SPDX_HEADER: &str = "SPDX-License-Identifier:";
+
+/// How far into a source file a header may appear.
+const HEADER_SCAN_LINES: usize = 20;
+
+/// Extensions of files expected to carry a license header.
+const SOURCE_EXTENSIONS: &[&str] = &[
+    "c", "cc", "cpp", "cs", "go", "h", "hpp", "java", "js", "jsx", "kt", "mjs", "py", "rb", "rs",
+    "scala", "sh", "swift", "ts", "tsx",
+];
+
+/// What the org requires, from `compliance` in `.meta`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompliancePolicy {
+    /// SPDX id every repo must be licensed under
+    #[serde(default)]
+    pub license: Option<String>,
+    /// Whether every source file must carry an SPDX header
+    #[serde(default)]
+    pub headers: bool,
+}
+
+impl CompliancePolicy {
+    /// The policy in the `.meta` file in `meta_dir`; none if unset or
+    /// invalid.
+    pub fn from_meta(meta_dir: &Path) -> Self {
+        read_meta_config_value(meta_dir)
+            .and_then(|v| v.get("compliance").cloned())
+            .and_then(|v| {
+                serde_json::from_value(v)
+                    .map_err(|e| log::warn!("Ignoring invalid compliance policy: {e}"))
+                    .ok()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// A license file at the top of a repo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LicenseFile {
+    pub path: String,
+    /// SPDX id identified from the text, if recognized
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+}
+
+/// SPDX headers in a repo's tracked source files.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct HeaderScan {
+    pub files_scanned: usize,
+    pub with_header: usize,
+    /// Files per header license
+    pub licenses: BTreeMap<String, usize>,
+    /// Source files without a header, when headers are required
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<String>,
+}
+
+/// Something a compliance review should look at.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ComplianceIssue {
+    MissingLicense,
+    /// A license file whose text wasn't recognized
+    UnknownLicense {
+        path: String,
+    },
+    LicenseMismatch {
+        found: String,
+        required: String,
+    },
+    /// Source files under another license than the required one
+    HeaderMismatch {
+        license: String,
+        files: usize,
+    },
+    MissingHeaders {
+        files: usize,
+    },
+}
+
+impl ComplianceIssue {
+    fn rule_id(&self) -> &'static str {
+        match self {
+            ComplianceIssue::MissingLicense => "compliance/missing-license",
+            ComplianceIssue::UnknownLicense { .. } => "compliance/unknown-license",
+            ComplianceIssue::LicenseMismatch { .. } => "compliance/license-mismatch",
+            ComplianceIssue::HeaderMismatch { .. } => "compliance/header-mismatch",
+            ComplianceIssue::MissingHeaders { .. } => "compliance/missing-headers",
+        }
+    }
+}
+
+impl std::fmt::Display for ComplianceIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ComplianceIssue::MissingLicense => write!(f, "No license file"),
+            ComplianceIssue::UnknownLicense { path } => {
+                write!(f, "Unrecognized license in {path}")
+            }
+            ComplianceIssue::LicenseMismatch { found, required } => {
+                write!(f, "Licensed under {found}, but {required} is required")
+            }
+            ComplianceIssue::HeaderMismatch { license, files } => {
+                write!(f, "{files} source file(s) have {license} headers")
+            }
+            ComplianceIssue::MissingHeaders { files } => {
+                write!(f, "{files} source file(s) have no license header")
+            }
+        }
+    }
+}
+
+/// The license state of one repo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RepoCompliance {
+    pub alias: String,
+    pub path: PathBuf,
+    pub license_files: Vec<LicenseFile>,
+    pub headers: HeaderScan,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub issues: Vec<ComplianceIssue>,
+}
+
+impl RepoCompliance {
+    /// The repo's license: that of its first recognized license file.
+    pub fn license(&self) -> Option<&str> {
+        self.license_files.iter().find_map(|f| f.license.as_deref())
+    }
+
+    /// Every license recognized in the repo's license files; more than one
+    /// for dual-licensed repos (`LICENSE-APACHE` and `LICENSE-MIT`).
+    pub fn licenses(&self) -> BTreeSet<&str> {
+        self.license_files
+            .iter()
+            .filter_map(|f| f.license.as_deref())
+            .collect()
+    }
+}
+
+/// Whether the SPDX expression `expression` lets the code be used under
+/// `license`: it names it, alone or as one alternative of an `OR`.
+pub fn spdx_allows(expression: &str, license: &str) -> bool {
+    expression
+        .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .split(|word| word.eq_ignore_ascii_case("OR"))
+        .any(|alternative| alternative.len() == 1 && alternative[0] == license)
+}
+
+/// License inventory of a workspace.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ComplianceReport {
+    pub policy: CompliancePolicy,
+    pub repos: Vec<RepoCompliance>,
+}
+
+impl ComplianceReport {
+    pub fn is_compliant(&self) -> bool {
+        self.repos.iter().all(|r| r.issues.is_empty())
+    }
+
+    /// Every issue as a finding, for [`crate::sarif::to_sarif`].
+    pub fn findings(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for repo in &self.repos {
+            for issue in &repo.issues {
+                let file = match issue {
+                    ComplianceIssue::UnknownLicense { path } => Some(path.clone()),
+                    _ => None,
+                };
+                findings.push(Finding {
+                    rule_id: issue.rule_id().to_string(),
+                    level: match issue {
+                        ComplianceIssue::LicenseMismatch { .. }
+                        | ComplianceIssue::MissingLicense => Level::Error,
+                        _ => Level::Warning,
+                    },
+                    message: issue.to_string(),
+                    repo: repo.alias.clone(),
+                    file,
+                    line: None,
+                });
+            }
+        }
+        findings
+    }
+}
+
+/// Inventory the licenses of every repo in the workspace at `meta_dir`
+/// (including the meta repo itself), checked against its
+/// [`CompliancePolicy`].
+pub fn inventory(meta_dir: &Path) -> Result<ComplianceReport> {
+    let policy = CompliancePolicy::from_meta(meta_dir);
+    let mut repos = Vec::new();
+    for project in load_projects_with_root(meta_dir, true)? {
+        let path = meta_dir.join(&project.path);
+        if !crate::snapshot::is_git_repo(&path) {
+            continue;
+        }
+        repos.push(repo_compliance(project.name, path, &policy)?);
+    }
+    Ok(ComplianceReport { policy, repos })
+}
+
+fn repo_compliance(
+    alias: String,
+    path: PathBuf,
+    policy: &CompliancePolicy,
+) -> Result<RepoCompliance> {
+    let license_files = license_files(&path);
+    let headers = scan_headers(&path, policy.headers)?;
+    let mut repo = RepoCompliance {
+        alias,
+        path,
+        license_files,
+        headers,
+        issues: Vec::new(),
+    };
+
+    if repo.license_files.is_empty() {
+        repo.issues.push(ComplianceIssue::MissingLicense);
+    }
+    for file in repo.license_files.iter().filter(|f| f.license.is_none()) {
+        repo.issues.push(ComplianceIssue::UnknownLicense {
+            path: file.path.clone(),
+        });
+    }
+    if let Some(required) = &policy.license {
+        let found = repo.licenses();
+        if !found.is_empty() && !found.iter().any(|f| spdx_allows(f, required)) {
+            repo.issues.push(ComplianceIssue::LicenseMismatch {
+                found: found.into_iter().collect::<Vec<_>>().join(", "),
+                required: required.clone(),
+            });
+        }
+        for (license, files) in &repo.headers.licenses {
+            if !spdx_allows(license, required) {
+                repo.issues.push(ComplianceIssue::HeaderMismatch {
+                    license: license.clone(),
+                    files: *files,
+                });
+            }
+        }
+    }
+    if !repo.headers.missing.is_empty() {
+        repo.issues.push(ComplianceIssue::MissingHeaders {
+            files: repo.headers.missing.len(),
+        });
+    }
+    Ok(repo)
+}
+
+/// License files at the top of `repo`, sorted by name.
+fn license_files(repo: &Path) -> Vec<LicenseFile> {
+    let Ok(entries) = std::fs::read_dir(repo) else {
+        return Vec::new();
+    };
+    let mut files: Vec<LicenseFile> = entries
+        .flatten()
+        .filter(|e| e.path().is_file())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter(|name| {
+            let stem = name
+                .split('.')
+                .next()
+                .unwrap_or_default()
+                .to_ascii_uppercase();
+            let stem = stem.split(['-', '_']).next().unwrap_or_default();
+            matches!(stem, "LICENSE" | "LICENCE" | "COPYING" | "UNLICENSE")
+        })
+        .map(|name| LicenseFile {
+            license: std::fs::read_to_string(repo.join(&name))
+                .ok()
+                .and_then(|text| identify_license(&text)),
+            path: name,
+        })
+        .collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files
+}
+
+/// The SPDX id of a license text, from an explicit identifier or the
+/// wording of common licenses.
+pub fn identify_license(text: &str) -> Option<String> {
+    if let Some(id) = spdx_header(text) {
+        return Some(id);
+    }
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let has = |needle: &str| text.contains(needle);
+    let id = if has("Apache License") && has("Version 2.0") {
+        "Apache-2.0"
+    } else if has("GNU LESSER GENERAL PUBLIC LICENSE") {
+        if has("Version 3") {
+            "LGPL-3.0"
+        } else {
+            "LGPL-2.1"
+        }
+    } else if has("GNU AFFERO GENERAL PUBLIC LICENSE") {
+        "AGPL-3.0"
+    } else if has("GNU GENERAL PUBLIC LICENSE") {
+        if has("Version 3") {
+            "GPL-3.0"
+        } else {
+            "GPL-2.0"
+        }
+    } else if has("Mozilla Public License Version 2.0") || has("Mozilla Public License, v. 2.0") {
+        "MPL-2.0"
+    } else if has("Permission is hereby granted, free of charge") {
+        "MIT"
+    } else if has("Redistribution and use in source and binary forms") {
+        if has("Neither the name") || has("may be used to endorse or promote") {
+            "BSD-3-Clause"
+        } else {
+            "BSD-2-Clause"
+        }
+    } else if has("Permission to use, copy, modify, and/or distribute this software") {
+        "ISC"
+    } else if has("This is free and unencumbered software released into the public domain") {
+        "Unlicense"
+    } else {
+        return None;
+    };
+    Some(id.to_string())
+}
+
+/// The id of an `SPDX-License-Identifier:` line near the top of `text`.
+fn spdx_header(text: &str) -> Option<String> {
+    text.lines().take(HEADER_SCAN_LINES).find_map(|line| {
+        let (_, id) = line.split_once(SPDX_HEADER)?;
+        // Strip comment closers such as `*/` or `-->`
+        let id = id.trim().trim_end_matches(['*', '/', '-', '>']).trim();
+        (!id.is_empty()).then(|| id.to_string())
+    })
+}
+
+/// Count SPDX headers in the tracked source files of `repo`, listing those
+/// without one if `list_missing`.
+fn scan_headers(repo: &Path, list_missing: bool) -> Result<HeaderScan> {
+    let output = crate::git_runner::output(
+        Command::new("git")
+            .args(["ls-files", "-z"])
+            .current_dir(repo),
+    )?;
+    let mut scan = HeaderScan::default();
+    for file in nul_fields(&output.stdout) {
+        let is_source = Path::new(&file)
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| SOURCE_EXTENSIONS.contains(&e));
+        if !is_source {
+            continue;
+        }
+        let Some(text) = read_head(&repo.join(&file)) else {
+            continue;
+        };
+        scan.files_scanned += 1;
+        match spdx_header(&text) {
+            Some(id) => {
+                scan.with_header += 1;
+                *scan.licenses.entry(id).or_default() += 1;
+            }
+            None if list_missing => scan.missing.push(file),
+            None => {}
+        }
+    }
+    Ok(scan)
+}
+
+/// The first [`HEADER_SCAN_LINES`] lines of a text file; `None` if it can't
+/// be read or isn't UTF-8.
+fn read_head(path: &Path) -> Option<String> {
+    let file = std::fs::File::open(path).ok()?;
+    let lines: std::io::Result<Vec<String>> = std::io::BufReader::new(file)
+        .lines()
+        .take(HEADER_SCAN_LINES)
+        .collect();
+    Some(lines.ok()?.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::repo;
+
+    const MIT: &str = "MIT License\n\nCopyright (c) 2024 Org\n\nPermission is hereby granted, free of charge, to any person obtaining a copy\n";
+
+    #[test]
+    fn identifies_common_licenses() {
+        assert_eq!(identify_license(MIT).as_deref(), Some("MIT"));
+        assert_eq!(
+            identify_license(
+                "                  Apache License\n            Version 2.0, January 2004\n"
+            )
+            .as_deref(),
+            Some("Apache-2.0")
+        );
+        assert_eq!(
+            identify_license("GNU GENERAL PUBLIC LICENSE\nVersion 3, 29 June 2007").as_deref(),
+            Some("GPL-3.0")
+        );
+        assert_eq!(
+            identify_license("/* SPDX-License-Identifier: MPL-2.0 */\n").as_deref(),
+            Some("MPL-2.0")
+        );
+        assert_eq!(identify_license("All rights reserved."), None);
+    }
+
+    #[test]
+    fn spdx_or_expressions_allow_each_alternative() {
+        assert!(spdx_allows("MIT", "MIT"));
+        assert!(spdx_allows("MIT OR Apache-2.0", "Apache-2.0"));
+        assert!(spdx_allows("(Apache-2.0 or MIT)", "MIT"));
+        assert!(!spdx_allows("MIT AND Apache-2.0", "MIT"));
+        assert!(!spdx_allows(
+            "GPL-2.0 WITH Classpath-exception-2.0",
+            "GPL-2.0"
+        ));
+        assert!(!spdx_allows("MIT-0", "MIT"));
+    }
+
+    #[test]
+    fn inventory_flags_missing_and_mismatched_licenses() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ws = tmp.path();
+        std::fs::write(
+            ws.join(".meta"),
+            serde_json::json!({
+                "projects": {"api": "git@x:api.git", "web": "git@x:web.git", "docs": "git@x:docs.git", "dual": "git@x:dual.git"},
+                "compliance": {"license": "MIT", "headers": true},
+            })
+            .to_string(),
+        )
+        .unwrap();
+        repo(
+            &ws.join("api"),
+            &[
+                ("LICENSE", MIT),
+                ("src/lib.rs", "// SPDX-License-Identifier: MIT\nfn a() {}\n"),
+                ("src/b.rs", "fn b() {}\n"),
+                ("README.md", "no header needed\n"),
+            ],
+        );
+        repo(
+            &ws.join("web"),
+            &[
+                ("LICENSE.txt", "Apache License\nVersion 2.0, January 2004\n"),
+                ("app.ts", "// SPDX-License-Identifier: Apache-2.0\n"),
+            ],
+        );
+        repo(&ws.join("docs"), &[("index.md", "# Docs\n")]);
+        repo(
+            &ws.join("dual"),
+            &[
+                (
+                    "LICENSE-APACHE",
+                    "Apache License\nVersion 2.0, January 2004\n",
+                ),
+                ("LICENSE-MIT", MIT),
+                (
+                    "src/lib.rs",
+                    "// SPDX-License-Identifier: MIT OR Apache-2.0\n",
+                ),
+            ],
+        );
+
+        let report = inventory(ws).unwrap();
+        assert!(!report.is_compliant());
+        let by_alias: BTreeMap<&str, &RepoCompliance> =
+            report.repos.iter().map(|r| (r.alias.as_str(), r)).collect();
+
+        let api = by_alias["api"];
+        assert_eq!(api.license(), Some("MIT"));
+        assert_eq!(api.headers.files_scanned, 2);
+        assert_eq!(api.headers.missing, ["src/b.rs"]);
+        assert_eq!(api.issues, [ComplianceIssue::MissingHeaders { files: 1 }]);
+
+        let web = by_alias["web"];
+        assert_eq!(
+            web.issues,
+            [
+                ComplianceIssue::LicenseMismatch {
+                    found: "Apache-2.0".into(),
+                    required: "MIT".into()
+                },
+                ComplianceIssue::HeaderMismatch {
+                    license: "Apache-2.0".into(),
+                    files: 1
+                },
+            ]
+        );
+        assert_eq!(by_alias["docs"].issues, [ComplianceIssue::MissingLicense]);
+        // Dual-licensed: satisfied through either file or header alternative
+        assert_eq!(
+            by_alias["dual"].licenses(),
+            BTreeSet::from(["Apache-2.0", "MIT"])
+        );
+        assert!(by_alias["dual"].issues.is_empty(), "{:?}", by_alias["dual"]);
+
+        let findings = report.findings();
+        assert_eq!(findings.len(), 4);
+        assert!(findings
+            .iter()
+            .any(|f| f.rule_id == "compliance/missing-license" && f.repo == "docs"));
+        let json = serde_json::to_value(web).unwrap();
+        assert_eq!(json["issues"][0]["kind"], "license_mismatch");
+    }
+}
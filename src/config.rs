@@ -0,0 +1,136 @@
+//! Consistency checks for the `.meta` project list.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+use crate::ssh_multiplexing::urls_match;
+
+/// What two or more project entries have in common.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// The entries point at the same repository
+    Remote,
+    /// The entries claim the same checkout path
+    Path,
+}
+
+/// A group of project entries that conflict with each other.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigConflict {
+    pub kind: ConflictKind,
+    /// The shared URL or path, as written by the first entry
+    pub value: String,
+    /// Project names, in the order the config lists them
+    pub projects: Vec<String>,
+}
+
+impl fmt::Display for ConfigConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self.kind {
+            ConflictKind::Remote => "repository",
+            ConflictKind::Path => "path",
+        };
+        write!(
+            f,
+            "Projects {} share the same {} ({})",
+            self.projects.join(", "),
+            what,
+            self.value
+        )
+    }
+}
+
+/// Find project entries in the `.meta` config in `meta_dir` that point at
+/// the same repository (per [`urls_match`]) or claim the same path.
+///
+/// Returns an empty list when there are no conflicts.
+pub fn duplicates(meta_dir: &Path) -> Result<Vec<ConfigConflict>> {
+    let (config_path, _) = meta_core::config::find_meta_config(meta_dir, None)
+        .ok_or_else(|| anyhow::anyhow!("No .meta config found in {}", meta_dir.display()))?;
+    let (projects, _) = meta_core::config::parse_meta_config(&config_path)?;
+
+    let mut conflicts = group(
+        projects
+            .iter()
+            .filter_map(|p| p.repo.as_deref().map(|repo| (p.name.as_str(), repo))),
+        ConflictKind::Remote,
+        urls_match,
+    );
+    conflicts.extend(group(
+        projects.iter().map(|p| (p.name.as_str(), p.path.as_str())),
+        ConflictKind::Path,
+        |a, b| normalize_path(a) == normalize_path(b),
+    ));
+    Ok(conflicts)
+}
+
+/// Group `(name, value)` pairs whose values are `same`, keeping groups of two or more.
+fn group<'a>(
+    entries: impl Iterator<Item = (&'a str, &'a str)>,
+    kind: ConflictKind,
+    same: impl Fn(&str, &str) -> bool,
+) -> Vec<ConfigConflict> {
+    let mut groups: Vec<ConfigConflict> = Vec::new();
+    for (name, value) in entries {
+        match groups.iter_mut().find(|g| same(&g.value, value)) {
+            Some(g) => g.projects.push(name.to_string()),
+            None => groups.push(ConfigConflict {
+                kind,
+                value: value.to_string(),
+                projects: vec![name.to_string()],
+            }),
+        }
+    }
+    groups.retain(|g| g.projects.len() > 1);
+    groups
+}
+
+fn normalize_path(path: &str) -> PathBuf {
+    Path::new(path)
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_duplicate_remotes_and_paths() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tmp.path().join(".meta"),
+            r#"{"projects": {
+                "api": "git@github.com:org/api.git",
+                "api-copy": {"repo": "git@github.com:org/api", "path": "api2"},
+                "web": {"repo": "git@github.com:org/web.git", "path": "apps/web"},
+                "admin": {"repo": "git@github.com:org/admin.git", "path": "./apps/web/"},
+                "docs": "git@github.com:org/docs.git"
+            }}"#,
+        )
+        .unwrap();
+
+        let conflicts = duplicates(tmp.path()).unwrap();
+        assert_eq!(conflicts.len(), 2, "{conflicts:?}");
+        assert_eq!(conflicts[0].kind, ConflictKind::Remote);
+        assert_eq!(conflicts[0].projects, ["api", "api-copy"]);
+        assert_eq!(conflicts[1].kind, ConflictKind::Path);
+        assert_eq!(conflicts[1].projects, ["admin", "web"]);
+        assert!(conflicts[1].to_string().contains("admin, web"));
+    }
+
+    #[test]
+    fn clean_config_has_no_conflicts() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tmp.path().join(".meta"),
+            r#"{"projects": {"a": "git@github.com:org/a.git", "b": "git@github.com:org/b.git"}}"#,
+        )
+        .unwrap();
+        assert!(duplicates(tmp.path()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,215 @@
+//! CSV and Markdown table export of reports.
+//!
+//! Reports implement [`Tabular`] once and can then be written with
+//! [`to_csv`] (spreadsheets) or [`to_markdown`] (tickets, wikis).
+
+use crate::worktree::types::{DiffOutput, ListOutput, StatusOutput};
+
+/// A report that can be flattened into a header row and data rows.
+pub trait Tabular {
+    fn headers(&self) -> Vec<&'static str>;
+    fn rows(&self) -> Vec<Vec<String>>;
+}
+
+/// Render as RFC 4180 CSV (CRLF line endings, quoted where needed).
+pub fn to_csv(report: &impl Tabular) -> String {
+    let mut out = String::new();
+    let headers: Vec<String> = report.headers().iter().map(|h| h.to_string()).collect();
+    for row in std::iter::once(headers).chain(report.rows()) {
+        let line: Vec<String> = row.iter().map(|c| csv_field(c)).collect();
+        out.push_str(&line.join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Render as a GitHub-flavored Markdown table.
+pub fn to_markdown(report: &impl Tabular) -> String {
+    let headers = report.headers();
+    let mut out = format!("| {} |\n", headers.join(" | "));
+    out.push_str(&format!("|{}\n", "---|".repeat(headers.len())));
+    for row in report.rows() {
+        let cells: Vec<String> = row.iter().map(|c| markdown_cell(c)).collect();
+        out.push_str(&format!("| {} |\n", cells.join(" | ")));
+    }
+    out
+}
+
+fn markdown_cell(value: &str) -> String {
+    value.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+impl Tabular for StatusOutput {
+    fn headers(&self) -> Vec<&'static str> {
+        vec![
+            "repo",
+            "branch",
+            "dirty",
+            "modified",
+            "untracked",
+            "ahead",
+            "behind",
+        ]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.repos
+            .iter()
+            .map(|r| {
+                vec![
+                    r.alias.clone(),
+                    r.branch.clone(),
+                    r.dirty.to_string(),
+                    r.modified_count.to_string(),
+                    r.untracked_count.to_string(),
+                    r.ahead.to_string(),
+                    r.behind.to_string(),
+                ]
+            })
+            .collect()
+    }
+}
+
+/// Per-repo rows followed by a `TOTAL` row.
+impl Tabular for DiffOutput {
+    fn headers(&self) -> Vec<&'static str> {
+        vec!["repo", "files_changed", "insertions", "deletions"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        let mut rows: Vec<Vec<String>> = self
+            .repos
+            .iter()
+            .map(|r| {
+                vec![
+                    r.alias.clone(),
+                    r.files_changed.to_string(),
+                    r.insertions.to_string(),
+                    r.deletions.to_string(),
+                ]
+            })
+            .collect();
+        rows.push(vec![
+            "TOTAL".to_string(),
+            self.totals.files_changed.to_string(),
+            self.totals.insertions.to_string(),
+            self.totals.deletions.to_string(),
+        ]);
+        rows
+    }
+}
+
+/// Disk-usage report: one row per worktree.
+impl Tabular for ListOutput {
+    fn headers(&self) -> Vec<&'static str> {
+        vec![
+            "name",
+            "root",
+            "repos",
+            "disk_usage_bytes",
+            "age_seconds",
+            "last_activity",
+        ]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        let opt = |v: Option<String>| v.unwrap_or_default();
+        self.worktrees
+            .iter()
+            .map(|wt| {
+                vec![
+                    wt.name.clone(),
+                    wt.root.clone(),
+                    wt.repos.len().to_string(),
+                    opt(wt.disk_usage_bytes.map(|b| b.to_string())),
+                    opt(wt.age_seconds.map(|s| s.to_string())),
+                    opt(wt.last_activity.clone()),
+                ]
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worktree::types::{DiffRepoEntry, DiffTotals, ListEntry};
+
+    fn diff() -> DiffOutput {
+        DiffOutput {
+            name: "wt".into(),
+            base: "main".into(),
+            repos: vec![DiffRepoEntry {
+                alias: "api".into(),
+                base_ref: "main".into(),
+                files_changed: 2,
+                insertions: 5,
+                deletions: 1,
+                files: vec![],
+                renames: vec![],
+            }],
+            totals: DiffTotals {
+                repos_changed: 1,
+                files_changed: 2,
+                insertions: 5,
+                deletions: 1,
+            },
+        }
+    }
+
+    #[test]
+    fn csv_includes_header_and_totals() {
+        assert_eq!(
+            to_csv(&diff()),
+            "repo,files_changed,insertions,deletions\r\napi,2,5,1\r\nTOTAL,2,5,1\r\n"
+        );
+    }
+
+    #[test]
+    fn csv_quotes_special_characters() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn markdown_table_layout() {
+        let md = to_markdown(&diff());
+        let lines: Vec<&str> = md.lines().collect();
+        assert_eq!(
+            lines[0],
+            "| repo | files_changed | insertions | deletions |"
+        );
+        assert_eq!(lines[1], "|---|---|---|---|");
+        assert_eq!(lines[2], "| api | 2 | 5 | 1 |");
+    }
+
+    #[test]
+    fn markdown_escapes_pipes() {
+        assert_eq!(markdown_cell("a|b\nc"), "a\\|b c");
+    }
+
+    #[test]
+    fn disk_usage_report_leaves_skipped_fields_empty() {
+        let list = ListOutput {
+            worktrees: vec![ListEntry {
+                name: "wt".into(),
+                root: "/ws/.worktrees/wt".into(),
+                disk_usage_bytes: Some(4096),
+                ..Default::default()
+            }],
+        };
+        assert_eq!(
+            list.rows(),
+            vec![vec!["wt", "/ws/.worktrees/wt", "0", "4096", "", ""]]
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! C ABI over the JSON API (`ffi` feature).
+//!
+//! Exposes [`crate::api::execute`] to other languages so Python/Node bindings
+//! can drive workspaces in-process. Build a shared library with:
+//!
+//! ```text
+//! cargo rustc --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! The matching C declarations are in `include/meta_git.h`. Strings returned
+//! by this module are owned by the library and must be released with
+//! [`meta_git_free_string`].
+
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+fn into_c_string(s: String) -> *mut c_char {
+    // JSON never contains raw NUL bytes, but don't trust that blindly
+    CString::new(s)
+        .unwrap_or_else(|e| {
+            let mut bytes = e.into_vec();
+            bytes.retain(|&b| b != 0);
+            CString::new(bytes).unwrap_or_default()
+        })
+        .into_raw()
+}
+
+fn error_response(message: &str) -> String {
+    serde_json::json!({
+        "version": crate::api::API_VERSION,
+        "ok": false,
+        "error": {"code": "invalid_request", "message": message},
+    })
+    .to_string()
+}
+
+/// Schema version spoken by [`meta_git_execute`].
+#[no_mangle]
+pub extern "C" fn meta_git_api_version() -> u32 {
+    crate::api::API_VERSION
+}
+
+/// Execute a JSON API request and return the JSON response.
+///
+/// Always returns a response (never NULL); errors are reported in-band.
+///
+/// # Safety
+///
+/// `request` must be NULL or a valid NUL-terminated string that stays alive
+/// for the duration of the call. The returned pointer must be released with
+/// [`meta_git_free_string`].
+#[no_mangle]
+pub unsafe extern "C" fn meta_git_execute(request: *const c_char) -> *mut c_char {
+    if request.is_null() {
+        return into_c_string(error_response("Request is NULL"));
+    }
+    let request = match CStr::from_ptr(request).to_str() {
+        Ok(s) => s,
+        Err(_) => return into_c_string(error_response("Request is not valid UTF-8")),
+    };
+    // Never unwind across the C boundary
+    let response = catch_unwind(AssertUnwindSafe(|| crate::api::execute(request)))
+        .unwrap_or_else(|_| error_response("Internal panic while executing request"));
+    into_c_string(response)
+}
+
+/// Release a string returned by this library.
+///
+/// # Safety
+///
+/// `s` must be NULL or a pointer previously returned by [`meta_git_execute`]
+/// that has not already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn meta_git_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(request: &str) -> serde_json::Value {
+        let c_request = CString::new(request).unwrap();
+        unsafe {
+            let response = meta_git_execute(c_request.as_ptr());
+            let text = CStr::from_ptr(response).to_str().unwrap().to_string();
+            meta_git_free_string(response);
+            serde_json::from_str(&text).unwrap()
+        }
+    }
+
+    #[test]
+    fn executes_requests_through_c_abi() {
+        let resp = call(
+            r#"{"version": 1, "op": "snapshot.list", "params": {"meta_dir": "/nonexistent"}}"#,
+        );
+        assert_eq!(resp["ok"], true);
+        assert_eq!(resp["result"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn null_request_is_an_error_response() {
+        let resp = unsafe {
+            let response = meta_git_execute(std::ptr::null());
+            let text = CStr::from_ptr(response).to_str().unwrap().to_string();
+            meta_git_free_string(response);
+            text
+        };
+        assert!(resp.contains("Request is NULL"));
+    }
+
+    #[test]
+    fn free_accepts_null() {
+        unsafe { meta_git_free_string(std::ptr::null_mut()) };
+        assert_eq!(meta_git_api_version(), crate::api::API_VERSION);
+    }
+}
@@ -0,0 +1,381 @@
+//! Parsers for git's machine-readable output.
+//!
+//! Human-oriented output changes with config and locale (`status.short`,
+//! `core.quotePath`, translated headers), so everything that reads git's
+//! stdout goes through formats git documents as stable: `status
+//! --porcelain=v2`, `diff --numstat`, `ls-tree -l`, and `check-ignore -v`,
+//! all with `-z`. Paths
+//! are NUL-terminated and never quoted, so names with spaces, newlines, or
+//! non-ASCII characters come through as they are (lossily, if not UTF-8).
+
+use serde::{Deserialize, Serialize};
+
+/// The NUL-terminated fields of `-z` output, without the trailing empty one.
+pub fn nul_fields(bytes: &[u8]) -> impl Iterator<Item = String> + '_ {
+    let bytes = bytes.strip_suffix(b"\0").unwrap_or(bytes);
+    bytes
+        .split(|b| *b == 0)
+        .filter(move |_| !bytes.is_empty())
+        .map(|field| String::from_utf8_lossy(field).into_owned())
+}
+
+/// The source of a rename or copy git detected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenameSource {
+    pub from: String,
+    /// How similar the two files are, 0 to 100
+    pub similarity: u8,
+    /// A copy rather than a rename
+    pub copy: bool,
+}
+
+impl RenameSource {
+    /// From a score such as `R100` or `C75` and the source path.
+    fn parse(score: &str, from: String) -> Option<Self> {
+        let copy = match score.chars().next()? {
+            'R' => false,
+            'C' => true,
+            _ => return None,
+        };
+        Some(RenameSource {
+            from,
+            similarity: score[1..].parse().ok()?,
+            copy,
+        })
+    }
+}
+
+/// One path from `git status --porcelain=v2 -z`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatusEntry {
+    /// A tracked path with staged and/or unstaged changes. `index` and
+    /// `worktree` are the `XY` codes, `.` for unchanged.
+    Changed {
+        index: char,
+        worktree: char,
+        path: String,
+        /// Where a renamed or copied path came from
+        renamed_from: Option<RenameSource>,
+    },
+    /// A path with merge conflicts
+    Unmerged {
+        path: String,
+    },
+    Untracked {
+        path: String,
+    },
+    Ignored {
+        path: String,
+    },
+}
+
+impl StatusEntry {
+    pub fn path(&self) -> &str {
+        match self {
+            StatusEntry::Changed { path, .. }
+            | StatusEntry::Unmerged { path }
+            | StatusEntry::Untracked { path }
+            | StatusEntry::Ignored { path } => path,
+        }
+    }
+}
+
+/// Parse `git status --porcelain=v2 -z` output. Header lines (`# branch.*`)
+/// and unknown records are skipped.
+pub fn parse_status_v2(bytes: &[u8]) -> Vec<StatusEntry> {
+    let mut fields = nul_fields(bytes);
+    let mut entries = Vec::new();
+    while let Some(record) = fields.next() {
+        let Some((kind, rest)) = record.split_once(' ') else {
+            continue;
+        };
+        let entry = match kind {
+            // 1 XY sub mH mI mW hH hI path
+            "1" => changed(rest, 6).map(|(index, worktree, path)| StatusEntry::Changed {
+                index,
+                worktree,
+                path,
+                renamed_from: None,
+            }),
+            // 2 XY sub mH mI mW hH hI Xscore path, then the original path
+            "2" => {
+                let from = fields.next().unwrap_or_default();
+                changed(rest, 7).map(|(index, worktree, path)| {
+                    let score = rest.split(' ').nth(7).unwrap_or_default();
+                    StatusEntry::Changed {
+                        index,
+                        worktree,
+                        path,
+                        renamed_from: RenameSource::parse(score, from),
+                    }
+                })
+            }
+            // u XY sub m1 m2 m3 mW h1 h2 h3 path
+            "u" => rest
+                .splitn(10, ' ')
+                .nth(9)
+                .map(|path| StatusEntry::Unmerged {
+                    path: path.to_string(),
+                }),
+            "?" => Some(StatusEntry::Untracked {
+                path: rest.to_string(),
+            }),
+            "!" => Some(StatusEntry::Ignored {
+                path: rest.to_string(),
+            }),
+            _ => None,
+        };
+        entries.extend(entry);
+    }
+    entries
+}
+
+/// The `XY` codes and path of a `1` or `2` record: `XY`, `fields` more
+/// space-separated fields, then the path (which may itself contain spaces).
+fn changed(rest: &str, fields: usize) -> Option<(char, char, String)> {
+    let mut parts = rest.splitn(fields + 2, ' ');
+    let mut xy = parts.next()?.chars();
+    let (index, worktree) = (xy.next()?, xy.next()?);
+    let path = parts.nth(fields)?;
+    Some((index, worktree, path.to_string()))
+}
+
+/// One file from `git diff --numstat -z`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumstatEntry {
+    /// `None` for binary files
+    pub insertions: Option<usize>,
+    pub deletions: Option<usize>,
+    pub path: String,
+    /// The path before a rename or copy
+    pub renamed_from: Option<String>,
+}
+
+/// Parse `git diff --numstat -z` output.
+pub fn parse_numstat(bytes: &[u8]) -> Vec<NumstatEntry> {
+    let mut fields = nul_fields(bytes);
+    let mut entries = Vec::new();
+    // `added\tdeleted\tpath`, or `added\tdeleted\t` followed by the old
+    // and new paths as fields of their own for a rename
+    while let Some(record) = fields.next() {
+        let mut parts = record.splitn(3, '\t');
+        let (Some(added), Some(deleted), Some(path)) = (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        let (path, renamed_from) = if path.is_empty() {
+            let (Some(from), Some(to)) = (fields.next(), fields.next()) else {
+                break;
+            };
+            (to, Some(from))
+        } else {
+            (path.to_string(), None)
+        };
+        entries.push(NumstatEntry {
+            insertions: added.parse().ok(),
+            deletions: deleted.parse().ok(),
+            path,
+            renamed_from,
+        });
+    }
+    entries
+}
+
+/// Renames and copies in `git diff --name-status -z` output, as (new path,
+/// source).
+pub fn parse_name_status_renames(bytes: &[u8]) -> Vec<(String, RenameSource)> {
+    let mut fields = nul_fields(bytes);
+    let mut renames = Vec::new();
+    // `X\0path`, or `Xscore\0from\0to` for renames and copies
+    while let Some(status) = fields.next() {
+        if !status.starts_with(['R', 'C']) {
+            fields.next();
+            continue;
+        }
+        let (Some(from), Some(to)) = (fields.next(), fields.next()) else {
+            break;
+        };
+        renames.extend(RenameSource::parse(&status, from).map(|source| (to, source)));
+    }
+    renames
+}
+
+/// The ignore rule that matched a path, from `git check-ignore -v`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IgnoreRule {
+    /// The file the pattern is in: a `.gitignore` relative to the repo,
+    /// `.git/info/exclude`, or the global excludes file
+    pub source: String,
+    pub line: usize,
+    pub pattern: String,
+}
+
+impl IgnoreRule {
+    /// A `!pattern`, which un-ignores what it matches.
+    pub fn is_negation(&self) -> bool {
+        self.pattern.starts_with('!')
+    }
+}
+
+/// Parse `git check-ignore -v -n -z` output into each path and the rule
+/// that matched it, if any.
+pub fn parse_check_ignore(bytes: &[u8]) -> Vec<(String, Option<IgnoreRule>)> {
+    let mut fields = nul_fields(bytes);
+    let mut entries = Vec::new();
+    // `source\0line\0pattern\0path`, with the first three empty for a
+    // path no rule matched
+    while let (Some(source), Some(line), Some(pattern), Some(path)) =
+        (fields.next(), fields.next(), fields.next(), fields.next())
+    {
+        let rule = (!source.is_empty()).then(|| IgnoreRule {
+            source,
+            line: line.parse().unwrap_or(0),
+            pattern,
+        });
+        entries.push((path, rule));
+    }
+    entries
+}
+
+/// Sizes of the blobs in `git ls-tree -r -l -z` output; entries without a
+/// size (submodules) are skipped.
+pub fn ls_tree_sizes(bytes: &[u8]) -> impl Iterator<Item = u64> + '_ {
+    // `mode type object size\tpath`
+    nul_fields(bytes).filter_map(|entry| {
+        let (meta, _path) = entry.split_once('\t')?;
+        meta.split_whitespace().nth(3)?.parse().ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_v2_keeps_paths_verbatim() {
+        let out = [
+            "# branch.oid 1234",
+            "# branch.head main",
+            "1 .M N... 100644 100644 100644 aaaa aaaa with space.txt",
+            "1 A. N... 000000 100644 100644 0000 bbbb line\nbreak",
+            "2 R. N... 100644 100644 100644 cccc cccc R100 new ünï.txt",
+            "old name.txt",
+            "u UU N... 100644 100644 100644 100644 dddd eeee ffff conflict.rs",
+            "? untracked dir/file",
+            "! target",
+            "",
+        ]
+        .join("\0");
+        let entries = parse_status_v2(out.as_bytes());
+        assert_eq!(
+            entries[0],
+            StatusEntry::Changed {
+                index: '.',
+                worktree: 'M',
+                path: "with space.txt".to_string(),
+                renamed_from: None,
+            }
+        );
+        assert_eq!(entries[1].path(), "line\nbreak");
+        assert_eq!(
+            entries[2],
+            StatusEntry::Changed {
+                index: 'R',
+                worktree: '.',
+                path: "new ünï.txt".to_string(),
+                renamed_from: Some(RenameSource {
+                    from: "old name.txt".to_string(),
+                    similarity: 100,
+                    copy: false,
+                }),
+            }
+        );
+        assert_eq!(
+            entries[3..],
+            [
+                StatusEntry::Unmerged {
+                    path: "conflict.rs".to_string()
+                },
+                StatusEntry::Untracked {
+                    path: "untracked dir/file".to_string()
+                },
+                StatusEntry::Ignored {
+                    path: "target".to_string()
+                },
+            ]
+        );
+        assert!(parse_status_v2(b"").is_empty());
+    }
+
+    #[test]
+    fn numstat_handles_renames_and_binary_files() {
+        let out = [
+            "3\t1\tsrc/a b.rs",
+            "-\t-\tlogo.png",
+            "0\t0\t",
+            "old\nname",
+            "new name",
+            "",
+        ]
+        .join("\0");
+        let entries = parse_numstat(out.as_bytes());
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            (entries[0].insertions, entries[0].deletions),
+            (Some(3), Some(1))
+        );
+        assert_eq!(entries[0].path, "src/a b.rs");
+        assert_eq!(entries[1].insertions, None);
+        assert_eq!(entries[2].path, "new name");
+        assert_eq!(entries[2].renamed_from.as_deref(), Some("old\nname"));
+    }
+
+    #[test]
+    fn name_status_lists_renames_and_copies() {
+        let out = [
+            "M",
+            "a.rs",
+            "R087",
+            "old.rs",
+            "new.rs",
+            "C100",
+            "b.rs",
+            "b copy.rs",
+            "",
+        ]
+        .join("\0");
+        let renames = parse_name_status_renames(out.as_bytes());
+        assert_eq!(renames.len(), 2);
+        assert_eq!(renames[0].0, "new.rs");
+        assert_eq!((renames[0].1.similarity, renames[0].1.copy), (87, false));
+        assert_eq!(renames[1].1.from, "b.rs");
+        assert!(renames[1].1.copy);
+    }
+
+    #[test]
+    fn check_ignore_reports_matching_rules() {
+        let out = [
+            "sub/.gitignore",
+            "3",
+            "*.log",
+            "sub/debug.log",
+            "",
+            "",
+            "",
+            "src/main.rs",
+            ".gitignore",
+            "7",
+            "!keep.env",
+            "keep.env",
+            "",
+        ]
+        .join("\0");
+        let entries = parse_check_ignore(out.as_bytes());
+        assert_eq!(entries.len(), 3);
+        let rule = entries[0].1.as_ref().unwrap();
+        assert_eq!((rule.source.as_str(), rule.line), ("sub/.gitignore", 3));
+        assert!(!rule.is_negation());
+        assert_eq!(entries[1], ("src/main.rs".to_string(), None));
+        assert!(entries[2].1.as_ref().unwrap().is_negation());
+    }
+}
@@ -0,0 +1,199 @@
+//! The seam every git invocation goes through.
+//!
+//! Call sites build a [`Command`] as usual and run it with [`output`] or
+//! [`status`] instead of calling it directly. With no runner installed that
+//! spawns git; with one installed through [`with_runner`] (or
+//! [`CloneQueue::with_git_runner`](crate::clone_queue::CloneQueue::with_git_runner)
+//! for the clone workers) the [`GitRunner`] answers instead, so higher-level
+//! subsystems can be tested against a fake such as `test_util::FakeGit`
+//! without spawning git.
+//!
+//! The runner is per thread. Code that fans work out to threads passes the
+//! caller's runner on with [`installed`] and [`with_installed`].
+
+use std::cell::RefCell;
+use std::ffi::OsString;
+use std::io;
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus, Output};
+use std::sync::Arc;
+
+/// One git invocation: what a [`GitRunner`] gets to run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitInvocation {
+    /// Arguments after `git`
+    pub args: Vec<OsString>,
+    /// Working directory; the process's own if `None`
+    pub dir: Option<PathBuf>,
+    /// Environment changes; `None` values are removed
+    pub envs: Vec<(OsString, Option<OsString>)>,
+}
+
+impl GitInvocation {
+    /// The invocation `cmd` describes.
+    pub fn of(cmd: &Command) -> Self {
+        GitInvocation {
+            args: cmd.get_args().map(|a| a.to_os_string()).collect(),
+            dir: cmd.get_current_dir().map(|d| d.to_path_buf()),
+            envs: cmd
+                .get_envs()
+                .map(|(k, v)| (k.to_os_string(), v.map(|v| v.to_os_string())))
+                .collect(),
+        }
+    }
+
+    /// The arguments as strings, lossily.
+    pub fn args_lossy(&self) -> Vec<String> {
+        self.args
+            .iter()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    /// A [`Command`] running this invocation.
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new("git");
+        cmd.args(&self.args);
+        if let Some(dir) = &self.dir {
+            cmd.current_dir(dir);
+        }
+        for (key, value) in &self.envs {
+            match value {
+                Some(value) => cmd.env(key, value),
+                None => cmd.env_remove(key),
+            };
+        }
+        cmd
+    }
+}
+
+/// Runs git invocations.
+pub trait GitRunner: Send + Sync {
+    /// Run `invocation`, capturing its output. A git that ran and failed is
+    /// an `Ok` output with an unsuccessful status; `Err` means git couldn't
+    /// be run at all.
+    fn run(&self, invocation: &GitInvocation) -> io::Result<Output>;
+}
+
+/// The real git on `PATH`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemGit;
+
+impl GitRunner for SystemGit {
+    fn run(&self, invocation: &GitInvocation) -> io::Result<Output> {
+        invocation.command().output()
+    }
+}
+
+thread_local! {
+    static RUNNER: RefCell<Option<Arc<dyn GitRunner>>> = const { RefCell::new(None) };
+}
+
+/// The runner installed on this thread, if any.
+pub fn installed() -> Option<Arc<dyn GitRunner>> {
+    RUNNER.with(|r| r.borrow().clone())
+}
+
+/// Run `f` with `runner` answering every git invocation on this thread.
+/// The previous runner is restored afterwards, even if `f` panics.
+pub fn with_runner<R>(runner: Arc<dyn GitRunner>, f: impl FnOnce() -> R) -> R {
+    with_installed(Some(runner), f)
+}
+
+/// Run `f` with `runner`, as returned by [`installed`], installed on this
+/// thread; git itself if `None`.
+pub fn with_installed<R>(runner: Option<Arc<dyn GitRunner>>, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<Arc<dyn GitRunner>>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            RUNNER.with(|r| *r.borrow_mut() = self.0.take());
+        }
+    }
+    let _restore = Restore(RUNNER.with(|r| r.replace(runner)));
+    f()
+}
+
+/// Run `cmd` and capture its output, through the installed runner if `cmd`
+/// is a git command.
+pub fn output(cmd: &mut Command) -> io::Result<Output> {
+    match runner_for(cmd) {
+        Some(runner) => runner.run(&GitInvocation::of(cmd)),
+        None => cmd.output(),
+    }
+}
+
+/// Run `cmd` for its exit status, through the installed runner if `cmd` is
+/// a git command (whose output is then discarded).
+pub fn status(cmd: &mut Command) -> io::Result<ExitStatus> {
+    match runner_for(cmd) {
+        Some(runner) => runner.run(&GitInvocation::of(cmd)).map(|o| o.status),
+        None => cmd.status(),
+    }
+}
+
+/// Whether [`output`] would hand `cmd` to an installed runner. Helpers that
+/// spawn git themselves (for timeouts or progress) defer to [`output`] then.
+pub fn routes(cmd: &Command) -> bool {
+    runner_for(cmd).is_some()
+}
+
+fn runner_for(cmd: &Command) -> Option<Arc<dyn GitRunner>> {
+    (cmd.get_program() == "git").then(installed).flatten()
+}
+
+/// An exit status with `code`, for runners that don't spawn processes.
+pub fn exit_status(code: i32) -> ExitStatus {
+    #[cfg(unix)]
+    {
+        std::os::unix::process::ExitStatusExt::from_raw(code << 8)
+    }
+    #[cfg(windows)]
+    {
+        std::os::windows::process::ExitStatusExt::from_raw(code as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<Vec<String>>>);
+
+    impl GitRunner for Recorder {
+        fn run(&self, invocation: &GitInvocation) -> io::Result<Output> {
+            self.0.lock().unwrap().push(invocation.args_lossy());
+            Ok(Output {
+                status: exit_status(3),
+                stdout: b"faked".to_vec(),
+                stderr: Vec::new(),
+            })
+        }
+    }
+
+    #[test]
+    fn installed_runner_answers_git_commands_only_in_scope() {
+        let recorder = Arc::new(Recorder::default());
+        let out = with_runner(recorder.clone(), || {
+            assert!(installed().is_some());
+            let nested = with_installed(None, installed);
+            assert!(nested.is_none());
+            output(Command::new("git").args(["status", "--porcelain"])).unwrap()
+        });
+        assert_eq!(out.stdout, b"faked");
+        assert_eq!(out.status.code(), Some(3));
+        assert_eq!(*recorder.0.lock().unwrap(), [["status", "--porcelain"]]);
+        assert!(installed().is_none());
+
+        // Threads it wasn't passed on to run git itself
+        with_runner(recorder.clone(), || {
+            let version = std::thread::spawn(|| output(Command::new("git").arg("--version")))
+                .join()
+                .unwrap()
+                .unwrap();
+            assert!(version.status.success());
+        });
+        assert_eq!(recorder.0.lock().unwrap().len(), 1);
+    }
+}
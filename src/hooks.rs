@@ -0,0 +1,385 @@
+//! Clone and update lifecycle hooks.
+//!
+//! Like the worktree hooks, these run a command from the `.meta` config with a
+//! JSON payload (see [`run_hook`]), so teams can chain bootstrap steps such as
+//! `npm install` onto a clone or update. They are configured under `hooks`,
+//! or under `worktree.hooks` next to the worktree hooks:
+//!
+//! ```json
+//! { "hooks": { "post-clone": "./scripts/bootstrap.sh", "post-update": ["make", "deps"] } }
+//! ```
+//!
+//! Executable scripts in `.meta/hooks/` are picked up too, as for the
+//! worktree hooks (see [`hook_script`]), and the top-level `hook_shell` and
+//! `hook_timeout_seconds` settings apply to both kinds.
+//!
+//! `post-clone` fires once per repo, with its name, path, URL, and depth,
+//! and runs in the cloned repo; `post-clone-all` fires once the clone queue
+//! has drained, and `post-update` once per update run ([`update_workspace`]
+//! or a background [`autofetch`](crate::autofetch) run), both in the
+//! workspace root.
+//!
+//! [`update_workspace`]: crate::vcs::update_workspace
+//!
+//! [`list`] reports every hook configured in a workspace, including those
+//! of nested meta repos, e.g. for a `hooks list` command or a doctor check
+//! for missing executables.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::clone_queue::{CloneReport, RepoCloneResult};
+use crate::worktree::helpers::read_meta_config_value;
+use crate::worktree::hooks::{
+    hook_script, hook_script_names, hook_shell, hook_timeout, run_hook, HookCommand, HOOKS_DIR,
+};
+
+/// Fire `hook_name` if configured in the `.meta` file in `meta_dir`,
+/// running it in `meta_dir`.
+pub fn fire_hook(hook_name: &str, payload: &serde_json::Value, meta_dir: &Path) {
+    fire_hook_in(hook_name, payload, meta_dir, meta_dir);
+}
+
+/// [`fire_hook`], running the hook in `dir`.
+fn fire_hook_in(hook_name: &str, payload: &serde_json::Value, meta_dir: &Path, dir: &Path) {
+    let hook = read_meta_config_value(meta_dir)
+        .and_then(|config| {
+            [
+                config.get("hooks"),
+                config.get("worktree").and_then(|wt| wt.get("hooks")),
+            ]
+            .into_iter()
+            .flatten()
+            .find_map(|hooks| hooks.get(hook_name).and_then(HookCommand::from_value))
+        })
+        .or_else(|| hook_script(meta_dir, hook_name));
+    if let Some(hook) = hook {
+        run_hook(
+            hook_name,
+            &hook,
+            payload,
+            hook_timeout(Some(meta_dir)),
+            hook_shell(Some(meta_dir)),
+            Some(dir),
+        );
+    }
+}
+
+/// Fire `post-clone` for one finished repo of a clone run, in the repo.
+pub fn fire_post_clone_repo(result: &RepoCloneResult, meta_dir: &Path) {
+    let payload = serde_json::json!({
+        "action": "clone",
+        "scope": "repo",
+        "name": result.name,
+        "path": result.target_path.display().to_string(),
+        "url": result.url,
+        "depth": result.depth_level,
+        "repo": result,
+    });
+    // A failed clone leaves no repo to run in
+    let dir = if result.target_path.is_dir() {
+        &result.target_path
+    } else {
+        meta_dir
+    };
+    fire_hook_in("post-clone", &payload, meta_dir, dir);
+}
+
+/// Fire `post-clone-all` for a finished clone run.
+pub fn fire_post_clone_all(report: &CloneReport, meta_dir: &Path) {
+    let payload = serde_json::json!({
+        "action": "clone",
+        "scope": "run",
+        "repos": report.per_repo,
+        "succeeded": report.succeeded,
+        "failed": report.failed,
+        "skipped_existing": report.skipped_existing,
+        "bytes": report.bytes,
+        "objects": report.objects,
+        "outcome": report.outcome,
+        "duration_ms": report.duration_ms,
+    });
+    fire_hook("post-clone-all", &payload, meta_dir);
+}
+
+/// Result of updating one repo, for the `post-update` payload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UpdateRepoResult {
+    pub name: String,
+    pub path: PathBuf,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+impl UpdateRepoResult {
+    /// Record `result` of an update of `name` at `path` that began at `started`.
+    pub fn new(name: &str, path: &Path, started: Instant, result: &anyhow::Result<()>) -> Self {
+        UpdateRepoResult {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+            success: result.is_ok(),
+            error: result.as_ref().err().map(|e| format!("{e:#}")),
+            duration_ms: millis(started.elapsed()),
+        }
+    }
+}
+
+/// Fire `post-update` for a finished update run.
+pub fn fire_post_update(repos: &[UpdateRepoResult], duration: Duration, meta_dir: &Path) {
+    let payload = serde_json::json!({
+        "action": "update",
+        "scope": "run",
+        "repos": repos,
+        "duration_ms": millis(duration),
+    });
+    fire_hook("post-update", &payload, meta_dir);
+}
+
+/// Config sections hooks are read from, as (display name, path of keys).
+const HOOK_SECTIONS: &[(&str, &[&str])] = &[
+    ("hooks", &["hooks"]),
+    ("worktree.hooks", &["worktree", "hooks"]),
+];
+
+/// Shell builtins that have no executable to look for.
+const SHELL_BUILTINS: &[&str] = &[
+    ".", ":", "[", "cd", "echo", "exit", "export", "printf", "set", "source", "test", "true",
+    "false",
+];
+
+/// What a hook is fired for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookCategory {
+    Worktree,
+    Clone,
+    Update,
+    /// A name no operation fires
+    Other,
+}
+
+impl HookCategory {
+    pub fn of(hook_name: &str) -> Self {
+        match hook_name {
+            "pre-create" | "post-create" | "pre-destroy" | "post-destroy" | "post-add-repo"
+            | "pre-remove-repo" | "post-rename" | "post-prune" | "metadata-changed"
+            | "ttl-changed" => HookCategory::Worktree,
+            "post-clone" | "post-clone-all" => HookCategory::Clone,
+            "post-update" => HookCategory::Update,
+            _ => HookCategory::Other,
+        }
+    }
+}
+
+/// Where a configured hook was found.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HookSource {
+    /// Directory of the `.meta` file
+    pub meta_dir: PathBuf,
+    /// Path of the nested meta repo from the workspace root; `None` for the
+    /// root `.meta`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// Config section, `hooks` or `worktree.hooks`, or `.meta/hooks` for
+    /// a script
+    pub section: &'static str,
+}
+
+/// A hook found by [`list`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfiguredHook {
+    pub name: String,
+    pub category: HookCategory,
+    pub command: HookCommand,
+    pub source: HookSource,
+}
+
+impl ConfiguredHook {
+    /// The program the hook runs: the first argv element, or the first word
+    /// of a shell command (after any `VAR=value` assignments). `None` for
+    /// shell builtins.
+    pub fn program(&self) -> Option<&str> {
+        let program = match &self.command {
+            HookCommand::Argv(argv) => argv.first().map(String::as_str),
+            HookCommand::Shell(cmd) => cmd.split_whitespace().find(|w| !w.contains('=')),
+        }?;
+        (!SHELL_BUILTINS.contains(&program)).then_some(program)
+    }
+
+    /// Whether the hook's [`program`](Self::program) can't be found, either
+    /// relative to its `.meta` file (for paths) or on `PATH`.
+    pub fn is_missing_executable(&self) -> bool {
+        self.program()
+            .is_some_and(|p| !executable_exists(p, &self.source.meta_dir))
+    }
+}
+
+/// Every hook configured in the workspace at `meta_dir` and its nested meta
+/// repos, root first.
+///
+/// Entries that aren't a command string or argv array are skipped with a
+/// warning. Only the root's hooks fire for operations on the workspace;
+/// nested ones fire when that repo is operated on as a workspace itself.
+pub fn list(meta_dir: &Path) -> Result<Vec<ConfiguredHook>> {
+    let mut hooks = hooks_in(meta_dir, None);
+    let model = crate::workspace_model::WorkspaceModel::load(meta_dir)?;
+    for (project, path) in model.nested_meta_dirs() {
+        hooks.extend(hooks_in(path, Some(project.to_string())));
+    }
+    Ok(hooks)
+}
+
+fn hooks_in(meta_dir: &Path, project: Option<String>) -> Vec<ConfiguredHook> {
+    let config = read_meta_config_value(meta_dir).unwrap_or_default();
+    let mut hooks = Vec::new();
+    for (section, keys) in HOOK_SECTIONS {
+        let entries = keys
+            .iter()
+            .try_fold(&config, |v, key| v.get(key))
+            .and_then(|v| v.as_object());
+        for (name, value) in entries.into_iter().flatten() {
+            let Some(command) = HookCommand::from_value(value) else {
+                log::warn!(
+                    "Invalid hook '{name}' in {} ({section})",
+                    meta_dir.display()
+                );
+                continue;
+            };
+            hooks.push(ConfiguredHook {
+                name: name.clone(),
+                category: HookCategory::of(name),
+                command,
+                source: HookSource {
+                    meta_dir: meta_dir.to_path_buf(),
+                    project: project.clone(),
+                    section,
+                },
+            });
+        }
+    }
+    // Scripts shadowed by an inline hook of the same name never run
+    for name in hook_script_names(meta_dir) {
+        if hooks.iter().any(|h| h.name == name) {
+            continue;
+        }
+        if let Some(command) = hook_script(meta_dir, &name) {
+            hooks.push(ConfiguredHook {
+                category: HookCategory::of(&name),
+                name,
+                command,
+                source: HookSource {
+                    meta_dir: meta_dir.to_path_buf(),
+                    project: project.clone(),
+                    section: HOOKS_DIR,
+                },
+            });
+        }
+    }
+    hooks
+}
+
+fn executable_exists(program: &str, base_dir: &Path) -> bool {
+    if program.contains('/') || program.contains(std::path::MAIN_SEPARATOR) {
+        return base_dir.join(program).is_file();
+    }
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+    std::env::split_paths(&path).any(|dir| {
+        dir.join(program).is_file()
+            || (cfg!(windows) && dir.join(format!("{program}.exe")).is_file())
+    })
+}
+
+/// `duration` in whole milliseconds, for payloads.
+pub(crate) fn millis(duration: Duration) -> u64 {
+    duration.as_millis().try_into().unwrap_or(u64::MAX)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn post_update_reads_top_level_hooks() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out.json");
+        std::fs::write(
+            tmp.path().join(".meta"),
+            json!({
+                "projects": {},
+                "hooks": {"post-update": ["cp", "/dev/stdin", out.to_str().unwrap()]}
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let started = Instant::now();
+        let repos = [
+            UpdateRepoResult::new("api", Path::new("/ws/api"), started, &Ok(())),
+            UpdateRepoResult::new(
+                "web",
+                Path::new("/ws/web"),
+                started,
+                &Err(anyhow::anyhow!("not a fast-forward")),
+            ),
+        ];
+        fire_post_update(&repos, Duration::from_millis(1500), tmp.path());
+
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&out).unwrap()).unwrap();
+        assert_eq!(written["action"], "update");
+        assert_eq!(written["duration_ms"], 1500);
+        assert_eq!(written["repos"][1]["error"], "not a fast-forward");
+        assert_eq!(written["repos"][0].get("error"), None);
+    }
+
+    #[test]
+    fn lists_root_and_nested_hooks_with_sources() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ws = tmp.path();
+        std::fs::create_dir_all(ws.join("platform/scripts")).unwrap();
+        std::fs::write(ws.join("platform/scripts/bootstrap"), "").unwrap();
+        std::fs::write(
+            ws.join(".meta"),
+            json!({
+                "projects": {"platform": "git@github.com:org/platform.git"},
+                "hooks": {"post-clone": "FOO=1 npm-that-does-not-exist ci", "bogus": 3},
+                "hook_shell": "sh",
+                "worktree": {"hooks": {"post-create": ["sh", "-c", "true"]}},
+            })
+            .to_string(),
+        )
+        .unwrap();
+        std::fs::write(
+            ws.join("platform/.meta"),
+            json!({"projects": {}, "hooks": {"post-update": "./scripts/bootstrap --quick"}})
+                .to_string(),
+        )
+        .unwrap();
+
+        let hooks = list(ws).unwrap();
+        let summary: Vec<_> = hooks
+            .iter()
+            .map(|h| (h.name.as_str(), h.category, h.source.project.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("post-clone", HookCategory::Clone, None),
+                ("post-create", HookCategory::Worktree, None),
+                ("post-update", HookCategory::Update, Some("platform")),
+            ]
+        );
+        assert_eq!(hooks[0].program(), Some("npm-that-does-not-exist"));
+        assert!(hooks[0].is_missing_executable());
+        assert!(!hooks[1].is_missing_executable());
+        assert_eq!(hooks[1].source.section, "worktree.hooks");
+        assert!(!hooks[2].is_missing_executable());
+    }
+}
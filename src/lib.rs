@@ -1,17 +1,66 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use indicatif::ProgressBar;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::time::Duration;
+pub mod api;
+pub mod autofetch;
+pub mod bulk;
+pub mod change_group;
+pub mod clone_progress;
 pub mod clone_queue;
+pub mod collision;
+pub mod compliance;
+pub mod config;
+pub mod credentials;
+pub mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod git_output;
+pub mod git_runner;
+pub mod git_url;
+pub mod hooks;
+pub mod lock;
+#[cfg(feature = "mcp")]
+pub mod mcp;
+pub mod migrate;
+pub mod mirrors;
 pub mod missing;
+pub mod notes;
+pub mod operations;
+pub mod outcome;
+pub mod preflight;
+pub mod process_timeout;
+pub mod project_filter;
+pub mod project_options;
+pub mod quarantine;
+pub mod read_only;
+pub mod reference_store;
+pub mod remote_check;
+pub mod render;
+pub mod rerun;
+pub mod sandbox;
+pub mod sarif;
+pub mod schema;
+pub mod setup;
 pub mod snapshot;
 pub mod ssh_multiplexing;
+pub mod status_index;
+#[cfg(any(feature = "bench", feature = "test-util"))]
+pub mod synthetic;
+#[cfg(test)]
+mod test_fixtures;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+pub mod vcs;
+pub mod verify;
+pub mod workspace_model;
 pub mod worktree;
 use console::style;
 pub use missing::print_missing_repo;
 pub use ssh_multiplexing::{
-    ensure_ssh_sockets_dir, extract_ssh_host, get_remote_url, is_ssh_rate_limit_error,
-    normalize_git_url, ssh_sockets_dir, urls_match,
+    ensure_known_host, ensure_ssh_sockets_dir, extract_ssh_host, get_remote_url,
+    is_ssh_rate_limit_error, normalize_git_url, ssh_sockets_dir, ssh_to_https_url, urls_match,
 };
 
 /// Clone a git repository into the target directory, with progress bar.
@@ -20,48 +69,461 @@ pub fn clone_repo_with_progress(
     target_dir: &Path,
     pb: Option<&ProgressBar>,
 ) -> Result<()> {
-    if target_dir.exists() {
-        if let Some(pb) = pb {
-            pb.finish_with_message(format!(
+    clone_repo_with_ssh_command(url, target_dir, pb, None)
+}
+
+/// Like [`clone_repo_with_progress`], running ssh as `ssh_command` when given
+/// (see [`clone_queue::CloneTask::ssh_command`]).
+pub fn clone_repo_with_ssh_command(
+    url: &str,
+    target_dir: &Path,
+    pb: Option<&ProgressBar>,
+    ssh_command: Option<&str>,
+) -> Result<()> {
+    let options = CloneOptions {
+        ssh_command: ssh_command.map(str::to_string),
+        ..Default::default()
+    };
+    clone_repo_with_options(url, target_dir, pb, &options).map(|_| ())
+}
+
+/// Extra settings for [`clone_repo_with_options`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CloneOptions {
+    /// `GIT_SSH_COMMAND` to run ssh with
+    pub ssh_command: Option<String>,
+    /// Value for `git clone --depth`
+    pub depth: Option<String>,
+    /// Date for `git clone --shallow-since`, e.g. `2024-01-01`
+    pub shallow_since: Option<String>,
+    /// Refs for `git clone --shallow-exclude`
+    pub shallow_exclude: Vec<String>,
+    /// Force git's wire protocol v2 (`-c protocol.version=2`)
+    pub protocol_v2: bool,
+    /// Let ssh trust hosts it has no key for yet (see
+    /// [`ssh_multiplexing::NewHostKeys::AcceptNew`])
+    pub accept_new_host_keys: bool,
+    /// Partial clone filter for `git clone --filter`, e.g. `blob:none`
+    pub filter: Option<String>,
+    /// Directories for `git sparse-checkout set`; empty checks out everything
+    pub sparse_paths: Vec<String>,
+    /// Local repo to borrow objects from (`--reference <path> --dissociate`)
+    pub reference: Option<PathBuf>,
+    /// Kill the clone if it runs longer than this
+    pub timeout: Option<Duration>,
+    /// What to do when the target directory already exists
+    pub collision: collision::CollisionPolicy,
+    /// Branch to check out instead of the remote's default (`--branch`)
+    pub branch: Option<String>,
+    /// Clone only that branch's history (`--single-branch`)
+    pub single_branch: bool,
+    /// Clone submodules too (`--recurse-submodules`, plus
+    /// `--shallow-submodules` with `depth`)
+    pub submodules: bool,
+    /// Commit, tag, or other revision to check out (detached) after cloning;
+    /// takes precedence over `branch`
+    pub git_ref: Option<String>,
+    /// Retry over HTTPS when an SSH clone is dropped or refused (see
+    /// [`is_ssh_rate_limit_error`])
+    pub https_fallback: bool,
+    /// Tokens for HTTPS clones
+    pub https_tokens: credentials::HttpsTokens,
+    /// Directory of local mirrors to clone from when they have the repo
+    /// (see [`mirrors`])
+    pub mirror_root: Option<PathBuf>,
+    /// SSH multiplexing settings, for cleaning up broken control sockets
+    pub multiplexing: ssh_multiplexing::MultiplexingConfig,
+    /// The caller already removed stale partial clones next to the target
+    /// (see [`collision::remove_stale_partials_in`])
+    pub stale_partials_removed: bool,
+}
+
+impl CloneOptions {
+    /// Whether the clone's history is cut off by depth, date, or excluded refs.
+    pub fn is_shallow(&self) -> bool {
+        self.depth.is_some() || self.shallow_since.is_some() || !self.shallow_exclude.is_empty()
+    }
+}
+
+/// A failed `git clone`, with git's error output and its category.
+///
+/// Returned (inside `anyhow::Error`) by the clone functions; downcast to
+/// inspect the category.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloneError {
+    pub url: String,
+    pub target_dir: PathBuf,
+    pub category: outcome::FailureCategory,
+    /// git's error output (progress lines removed)
+    pub stderr: String,
+}
+
+impl CloneError {
+    pub fn new(url: &str, target_dir: &Path, stderr: &str) -> Self {
+        CloneError {
+            url: url.to_string(),
+            target_dir: target_dir.to_path_buf(),
+            category: outcome::FailureCategory::classify(stderr),
+            stderr: stderr.trim().to_string(),
+        }
+    }
+
+    /// The clone was killed after `timeout`.
+    pub fn timed_out(url: &str, target_dir: &Path, timeout: Duration) -> Self {
+        CloneError {
+            url: url.to_string(),
+            target_dir: target_dir.to_path_buf(),
+            category: outcome::FailureCategory::Timeout,
+            stderr: process_timeout::timed_out_error(timeout).to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for CloneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Failed to clone {} into {} ({}): {}",
+            self.url,
+            self.target_dir.display(),
+            self.category.as_str(),
+            self.stderr
+        )
+    }
+}
+
+impl std::error::Error for CloneError {}
+
+/// Like [`clone_repo_with_progress`], with shallow history, partial clone filter,
+/// sparse checkout, object reference, timeout, and ssh command from
+/// `options` (see [`clone_queue::CloneQueue::clone_options`]).
+///
+/// A clone that times out is killed and its partial checkout removed. An
+/// existing target is handled per `options.collision`; the result tells what
+/// was found there and what was done.
+pub fn clone_repo_with_options(
+    url: &str,
+    target_dir: &Path,
+    pb: Option<&ProgressBar>,
+    options: &CloneOptions,
+) -> Result<collision::CloneResult> {
+    use collision::{CloneAction, CloneResult, CollisionError, CollisionPolicy, TargetState};
+
+    crate::read_only::check("clone repository")?;
+    vcs::check_configured_refs(options.branch.as_deref(), options.git_ref.as_deref())?;
+    if !options.stale_partials_removed {
+        collision::remove_stale_partials(target_dir);
+    }
+    let found = collision::inspect_target(target_dir, url);
+    let finish = |message: String| match pb {
+        Some(pb) => pb.finish_with_message(message),
+        None => println!("{message}"),
+    };
+    let mut transport = None;
+    let action = match (&found, options.collision) {
+        (TargetState::Missing, _) => {
+            transport = Some(run_clone_with_fallback(url, target_dir, pb, options)?);
+            CloneAction::Cloned
+        }
+        (TargetState::Matching, CollisionPolicy::Skip) => {
+            finish(format!(
                 "{}: already exists, skipping",
                 target_dir.display()
             ));
-        } else {
-            println!("{}: already exists, skipping", target_dir.display());
+            CloneAction::Skipped
         }
-        return Ok(());
+        (TargetState::Matching, _) => {
+            if options.branch.is_some() || options.git_ref.is_some() {
+                vcs::checkout_configured(
+                    target_dir,
+                    options.branch.as_deref(),
+                    options.git_ref.as_deref(),
+                )?;
+            }
+            finish(format!("{}: already cloned ✓", target_dir.display()));
+            CloneAction::Adopted
+        }
+        (found, CollisionPolicy::Skip) => {
+            finish(format!(
+                "{}: already exists ({found}), skipping",
+                target_dir.display()
+            ));
+            CloneAction::Skipped
+        }
+        (found, CollisionPolicy::Adopt | CollisionPolicy::Fail) => {
+            let error = CollisionError {
+                target_dir: target_dir.to_path_buf(),
+                url: url.to_string(),
+                found: found.clone(),
+                policy: options.collision,
+            };
+            finish(error.to_string());
+            return Err(error.into());
+        }
+        (_, CollisionPolicy::Replace) => {
+            let temp = collision::sibling_path(target_dir, "meta-clone-tmp");
+            if temp.exists() {
+                std::fs::remove_dir_all(&temp)?;
+            }
+            transport = Some(run_clone_with_fallback(url, &temp, pb, options)?);
+            let backup = collision::sibling_path(
+                target_dir,
+                &format!(
+                    "meta-replaced-{}",
+                    chrono::Utc::now().format("%Y%m%d%H%M%S")
+                ),
+            );
+            if let Err(e) = std::fs::rename(target_dir, &backup) {
+                // The original is untouched; don't leave the new clone beside it
+                let _ = std::fs::remove_dir_all(&temp);
+                return Err(anyhow::anyhow!(e).context(format!(
+                    "Failed to move {} aside to {}",
+                    target_dir.display(),
+                    backup.display()
+                )));
+            }
+            if let Err(e) = std::fs::rename(&temp, target_dir) {
+                // Put the original back rather than leave it only as the backup
+                if let Err(restore) = std::fs::rename(&backup, target_dir) {
+                    return Err(anyhow::anyhow!(e).context(format!(
+                        "Failed to move the new clone into {}, and to restore the original from {}: {restore}",
+                        target_dir.display(),
+                        backup.display()
+                    )));
+                }
+                let _ = std::fs::remove_dir_all(&temp);
+                return Err(anyhow::anyhow!(e).context(format!(
+                    "Failed to move the new clone into {}; the original was restored",
+                    target_dir.display()
+                )));
+            }
+            CloneAction::Replaced { backup }
+        }
+    };
+    Ok(CloneResult {
+        found,
+        action,
+        transport,
+    })
+}
+
+/// [`run_clone`], retrying over HTTPS if enabled and the SSH connection was
+/// dropped or refused. Returns the transport that succeeded.
+///
+/// After an HTTPS fallback `origin` is set back to `url`, so the checkout
+/// matches the config and later fetches use SSH again.
+fn run_clone_with_fallback(
+    url: &str,
+    target_dir: &Path,
+    pb: Option<&ProgressBar>,
+    options: &CloneOptions,
+) -> Result<collision::CloneTransport> {
+    let error = match run_clone(url, target_dir, pb, options) {
+        Ok(()) => return Ok(collision::CloneTransport::of(url)),
+        Err(error) => error,
+    };
+    let https_url = options
+        .https_fallback
+        .then(|| error.downcast_ref::<CloneError>())
+        .flatten()
+        .filter(|e| is_ssh_rate_limit_error(&e.stderr))
+        .and_then(|_| ssh_to_https_url(url));
+    let Some(https_url) = https_url else {
+        return Err(error);
+    };
+    log::warn!("SSH clone of {url} failed ({error}); retrying over HTTPS");
+    let https_options = CloneOptions {
+        ssh_command: None,
+        ..options.clone()
+    };
+    run_clone(&https_url, target_dir, pb, &https_options)?;
+    let restored = crate::git_runner::output(
+        Command::new("git")
+            .args(["remote", "set-url", "origin", url])
+            .current_dir(target_dir),
+    );
+    if !restored.is_ok_and(|o| o.status.success()) {
+        log::warn!(
+            "Failed to set origin of {} back to {url}",
+            target_dir.display()
+        );
     }
+    Ok(collision::CloneTransport::Https)
+}
+
+/// Clone `url` into the (missing) `target_dir`.
+///
+/// git writes into a `<target>.partial-<pid>` sibling that is renamed into
+/// place only once the clone (and sparse checkout) succeeded, so an
+/// interrupted clone never looks like a complete repo. The partial directory
+/// is removed on failure; ones left by killed processes are cleaned up by
+/// [`collision::remove_stale_partials`].
+fn run_clone(
+    url: &str,
+    target_dir: &Path,
+    pb: Option<&ProgressBar>,
+    options: &CloneOptions,
+) -> Result<()> {
     if let Some(pb) = pb {
         pb.set_message(format!("Cloning {url}"));
     } else {
         println!("Cloning {} into {}", url, target_dir.display());
     }
-    let mut child = Command::new("git")
-        .arg("clone")
-        .arg(url)
-        .arg(target_dir)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()?;
-    let status = child.wait()?;
-    if let Some(pb) = pb {
-        if status.success() {
-            pb.finish_with_message(format!("{} ✓", style(target_dir.display()).green()));
-        } else {
-            pb.finish_with_message(format!(
-                "Failed to clone {} into {}",
-                url,
-                target_dir.display()
-            ));
+    let partial = collision::partial_path(target_dir);
+    if partial.exists() {
+        std::fs::remove_dir_all(&partial)?;
+    }
+    let mirror = options
+        .mirror_root
+        .as_deref()
+        .and_then(|root| mirrors::find(root, url));
+    let source = match &mirror {
+        // Plain paths make git ignore --depth, --shallow-*, and --filter
+        Some(path) if options.is_shallow() || options.filter.is_some() => {
+            format!("file://{}", path.display())
         }
-    } else if status.success() {
-        println!("{} ✓", style(target_dir.display()).green());
-    } else {
-        println!("Failed to clone {} into {}", url, target_dir.display());
+        Some(path) => path.display().to_string(),
+        None => url.to_string(),
+    };
+    if let Some(path) = &mirror {
+        log::debug!("Cloning {url} from mirror {}", path.display());
     }
-    if status.success() {
-        Ok(())
-    } else {
-        anyhow::bail!("Failed to clone {} into {}", url, target_dir.display())
+    let mut cmd = Command::new("git");
+    credentials::suppress_prompts(&mut cmd);
+    if let Some(ssh_command) = &options.ssh_command {
+        cmd.env("GIT_SSH_COMMAND", ssh_command);
+    }
+    options.https_tokens.apply(&mut cmd, url);
+    if options.accept_new_host_keys {
+        ssh_multiplexing::accept_new_host_keys(&mut cmd);
+    }
+    if options.protocol_v2 {
+        cmd.args(["-c", "protocol.version=2"]);
+    }
+    cmd.arg("clone");
+    if let Some(depth) = &options.depth {
+        cmd.arg("--depth").arg(depth);
+    }
+    if let Some(since) = &options.shallow_since {
+        cmd.arg(format!("--shallow-since={since}"));
+    }
+    for excluded in &options.shallow_exclude {
+        cmd.arg(format!("--shallow-exclude={excluded}"));
+    }
+    if let Some(filter) = &options.filter {
+        cmd.arg(format!("--filter={filter}"));
+    }
+    if let (Some(reference), None) = (&options.reference, &mirror) {
+        cmd.arg("--reference").arg(reference).arg("--dissociate");
+    }
+    if let (Some(branch), None) = (&options.branch, &options.git_ref) {
+        cmd.arg("--branch").arg(branch);
+    }
+    if options.single_branch {
+        cmd.arg("--single-branch");
+    }
+    if options.submodules {
+        cmd.arg("--recurse-submodules");
+        if options.is_shallow() {
+            cmd.arg("--shallow-submodules");
+        }
+    }
+    if !options.sparse_paths.is_empty() {
+        // Only top-level files are checked out until the sparse paths are set
+        cmd.arg("--sparse");
+    }
+    let result = match pb {
+        Some(pb) => {
+            cmd.arg("--progress").arg(&source).arg(&partial);
+            ssh_multiplexing::run_with_mux_recovery(&mut cmd, &options.multiplexing, |cmd| {
+                clone_progress::output_with_progress_timeout(cmd, pb, url, options.timeout)
+            })
+        }
+        None => {
+            cmd.arg(&source)
+                .arg(&partial)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+            ssh_multiplexing::run_with_mux_recovery(&mut cmd, &options.multiplexing, |cmd| {
+                process_timeout::output_with_timeout(cmd, options.timeout)
+            })
+        }
+    };
+    let remove_partial = || {
+        if partial.exists() {
+            let _ = std::fs::remove_dir_all(&partial);
+        }
+    };
+    let output = match (result, options.timeout) {
+        (Err(e), Some(timeout)) if e.kind() == std::io::ErrorKind::TimedOut => {
+            remove_partial();
+            let error = CloneError::timed_out(url, target_dir, timeout);
+            let message = format!("Timed out cloning {} into {}", url, target_dir.display());
+            match pb {
+                Some(pb) => pb.finish_with_message(message),
+                None => println!("{message}"),
+            }
+            return Err(error.into());
+        }
+        (result, _) => result.inspect_err(|_| remove_partial())?,
+    };
+    let mut success = output.status.success();
+    let mut stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if success && mirror.is_some() {
+        if let Err(e) = mirrors::repoint_to_origin(&partial, url, options) {
+            success = false;
+            stderr = format!("{e:#}");
+        }
+    }
+    if success && !options.sparse_paths.is_empty() {
+        let sparse = crate::git_runner::output(
+            Command::new("git")
+                .arg("-C")
+                .arg(&partial)
+                .args(["sparse-checkout", "set"])
+                .args(&options.sparse_paths),
+        )?;
+        success = sparse.status.success();
+        stderr = format!(
+            "git sparse-checkout set failed: {}",
+            String::from_utf8_lossy(&sparse.stderr).trim()
+        );
+    }
+    if success && options.git_ref.is_some() {
+        if let Err(e) = vcs::checkout_configured(&partial, None, options.git_ref.as_deref()) {
+            success = false;
+            stderr = format!("{e:#}");
+        }
+    }
+    if success {
+        if let Err(e) = std::fs::rename(&partial, target_dir) {
+            remove_partial();
+            return Err(e).with_context(|| {
+                format!(
+                    "Failed to move finished clone into {}",
+                    target_dir.display()
+                )
+            });
+        }
+        match pb {
+            Some(pb) => {
+                pb.finish_with_message(format!("{} ✓", style(target_dir.display()).green()))
+            }
+            None => println!("{} ✓", style(target_dir.display()).green()),
+        }
+        return Ok(());
+    }
+    remove_partial();
+    let error = CloneError::new(url, target_dir, &stderr);
+    let message = format!(
+        "Failed to clone {} into {} ({})",
+        url,
+        target_dir.display(),
+        error.category.as_str()
+    );
+    match pb {
+        Some(pb) => pb.finish_with_message(message),
+        None => println!("{message}"),
     }
+    Err(error.into())
 }
@@ -0,0 +1,464 @@
+//! Advisory locks serializing conflicting operations.
+//!
+//! Mutating operations (update, fetch, restore, branch and worktree ops) take
+//! a per-repo lock at `meta.lock` in the repo's common git dir, so two
+//! concurrent meta invocations don't interleave fetch/checkout in the same
+//! repo, nor in two worktrees of it (which share refs and objects). Locks
+//! record the owning PID and start time; a lock whose process is gone or
+//! which is older than [`STALE_LOCK_AGE_SECS`] is treated as stale and
+//! reclaimed.
+//!
+//! Lock files are written in full before they are linked into place, so a
+//! reader never sees a half-written lock, and a stale lock is moved aside
+//! and checked again before it is deleted, so two processes reclaiming the
+//! same stale lock can't both end up holding it.
+//!
+//! Batch operations can additionally take the optional [`WorkspaceLock`] in
+//! the meta data dir, which serializes whole runs (e.g. two bulk updates
+//! started from cron and a terminal) with either fail-fast or wait semantics.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::worktree::git_ops::git_common_dir;
+
+const REPO_LOCK_FILE: &str = "meta.lock";
+
+/// Locks older than this are considered abandoned even if the PID is alive
+/// (PIDs get reused after reboots).
+pub const STALE_LOCK_AGE_SECS: i64 = 6 * 3600;
+
+/// Contents of a lock file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockInfo {
+    pub pid: u32,
+    pub operation: String,
+    pub started_at: String,
+}
+
+impl LockInfo {
+    fn current(operation: &str) -> Self {
+        LockInfo {
+            pid: std::process::id(),
+            operation: operation.to_string(),
+            started_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Whether the owning process is gone or the lock is too old to trust.
+    pub fn is_stale(&self, now_epoch: i64) -> bool {
+        let too_old = match chrono::DateTime::parse_from_rfc3339(&self.started_at) {
+            Ok(dt) => now_epoch - dt.timestamp() > STALE_LOCK_AGE_SECS,
+            // Unparseable timestamps can't be trusted
+            Err(_) => true,
+        };
+        too_old || !pid_alive(self.pid)
+    }
+}
+
+impl std::fmt::Display for LockInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "operation '{}' started by PID {} at {}",
+            self.operation, self.pid, self.started_at
+        )
+    }
+}
+
+/// Check whether a process with `pid` is still running.
+#[cfg(unix)]
+pub(crate) fn pid_alive(pid: u32) -> bool {
+    if pid == std::process::id() {
+        return true;
+    }
+    std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(true)
+}
+
+/// Without a portable liveness check, rely on lock age alone.
+#[cfg(not(unix))]
+pub(crate) fn pid_alive(_pid: u32) -> bool {
+    true
+}
+
+/// Read the lock info at `path`, if the file exists and parses.
+pub fn read_lock_info(path: &Path) -> Option<LockInfo> {
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Atomically create the lock file at `path`.
+///
+/// Returns `Ok(Err(holder))` if a live lock is already held, reclaiming
+/// stale locks transparently. A lock that doesn't parse can't have been
+/// written by a live holder, since locks are linked into place whole, so it
+/// counts as stale.
+fn try_create_lock(path: &Path, operation: &str) -> Result<std::result::Result<(), LockInfo>> {
+    let info = LockInfo::current(operation);
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut staged = tempfile::Builder::new()
+        .prefix(".meta-lock-")
+        .tempfile_in(dir)
+        .with_context(|| format!("Failed to create lock {}", path.display()))?;
+    serde_json::to_writer(&mut staged, &info)?;
+    staged
+        .flush()
+        .with_context(|| format!("Failed to write lock {}", path.display()))?;
+
+    // A few attempts: later ones follow a released or reclaimed lock
+    for _ in 0..3 {
+        let err = match staged.persist_noclobber(path) {
+            Ok(_) => return Ok(Ok(())),
+            Err(e) => e,
+        };
+        staged = err.file;
+        if err.error.kind() != io::ErrorKind::AlreadyExists {
+            return Err(err.error)
+                .with_context(|| format!("Failed to create lock {}", path.display()));
+        }
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            // Released since
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read lock {}", path.display()))
+            }
+        };
+        let holder = serde_json::from_str::<LockInfo>(&content).ok();
+        if let Some(holder) = &holder {
+            if !holder.is_stale(chrono::Utc::now().timestamp()) {
+                return Ok(Err(holder.clone()));
+            }
+        }
+        if reclaim_stale(path, &content)? {
+            log::warn!(
+                "Removed stale lock {}{}",
+                path.display(),
+                holder.map(|h| format!(" ({h})")).unwrap_or_default()
+            );
+        }
+    }
+    anyhow::bail!("Failed to acquire lock {}", path.display())
+}
+
+/// Move the lock at `path`, last read as the stale `content`, aside and
+/// delete it. Returns `false` if it turned out to be a different lock,
+/// taken since it was read; that one is put back.
+fn reclaim_stale(path: &Path, content: &str) -> Result<bool> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    // Deleted on drop, whatever ends up there
+    let aside = tempfile::Builder::new()
+        .prefix(".meta-lock-stale-")
+        .tempfile_in(dir)
+        .with_context(|| format!("Failed to reclaim lock {}", path.display()))?
+        .into_temp_path();
+    match fs::rename(path, &aside) {
+        Ok(()) => {}
+        // Someone else reclaimed or released it first
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to reclaim lock {}", path.display()))
+        }
+    }
+    if fs::read_to_string(&aside).is_ok_and(|moved| moved == content) {
+        return Ok(true);
+    }
+    if let Err(e) = fs::hard_link(&aside, path) {
+        log::warn!(
+            "Failed to restore lock {} taken while reclaiming it: {e}",
+            path.display()
+        );
+    }
+    Ok(false)
+}
+
+/// Advisory lock on a single repo, released on drop.
+#[derive(Debug)]
+pub struct RepoLock {
+    path: PathBuf,
+}
+
+impl RepoLock {
+    /// Acquire the lock for `repo_path`, failing if another live process
+    /// holds it for the same repo, through any of its worktrees.
+    pub fn acquire(repo_path: &Path, operation: &str) -> Result<RepoLock> {
+        let dir = git_common_dir(repo_path)
+            .ok_or_else(|| anyhow::anyhow!("Not a git repository: {}", repo_path.display()))?;
+        let path = dir.join(REPO_LOCK_FILE);
+
+        match try_create_lock(&path, operation)? {
+            Ok(()) => Ok(RepoLock { path }),
+            Err(holder) => anyhow::bail!(
+                "Repo {} is locked by {} (remove {} if no meta process is running)",
+                repo_path.display(),
+                holder,
+                path.display()
+            ),
+        }
+    }
+
+    /// Path of the lock file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for RepoLock {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            log::debug!("Failed to release lock {}: {e}", self.path.display());
+        }
+    }
+}
+
+/// Behavior when the workspace lock is already held by another process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitMode {
+    /// Return an error describing the running operation.
+    Fail,
+    /// Poll until the lock is released, optionally giving up after a timeout.
+    Wait { timeout: Option<Duration> },
+}
+
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Path of the process-wide operation lock in the meta data dir.
+pub fn workspace_lock_path() -> PathBuf {
+    meta_core::data_dir::data_file("operation").with_extension("lock")
+}
+
+/// Process-wide lock serializing whole batch operations, released on drop.
+#[derive(Debug)]
+pub struct WorkspaceLock {
+    path: PathBuf,
+}
+
+impl WorkspaceLock {
+    /// Acquire the workspace lock in the meta data dir.
+    pub fn acquire(operation: &str, mode: WaitMode) -> Result<WorkspaceLock> {
+        meta_core::data_dir::ensure_meta_dir()?;
+        Self::acquire_at(&workspace_lock_path(), operation, mode)
+    }
+
+    /// Acquire a workspace lock at an explicit path.
+    pub fn acquire_at(path: &Path, operation: &str, mode: WaitMode) -> Result<WorkspaceLock> {
+        let started = Instant::now();
+        let mut announced = false;
+
+        loop {
+            let holder = match try_create_lock(path, operation)? {
+                Ok(()) => {
+                    return Ok(WorkspaceLock {
+                        path: path.to_path_buf(),
+                    })
+                }
+                Err(holder) => holder,
+            };
+
+            let timeout = match mode {
+                WaitMode::Fail => anyhow::bail!(
+                    "Another meta operation is running: {holder} (remove {} if no meta process is running)",
+                    path.display()
+                ),
+                WaitMode::Wait { timeout } => timeout,
+            };
+            if timeout.is_some_and(|t| started.elapsed() >= t) {
+                anyhow::bail!("Timed out waiting for {holder} to finish");
+            }
+            if !announced {
+                log::info!("Waiting for {holder} to finish...");
+                announced = true;
+            }
+            std::thread::sleep(WAIT_POLL_INTERVAL);
+        }
+    }
+
+    /// Path of the lock file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for WorkspaceLock {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            log::debug!("Failed to release lock {}: {e}", self.path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_repo() -> tempfile::TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        crate::test_fixtures::repo(tmp.path(), &[]);
+        tmp
+    }
+
+    #[test]
+    fn acquire_creates_and_drop_releases() {
+        let repo = temp_repo();
+        let lock_path = repo.path().join(".git").join(REPO_LOCK_FILE);
+        {
+            let lock = RepoLock::acquire(repo.path(), "update").unwrap();
+            assert_eq!(lock.path(), lock_path);
+            let info = read_lock_info(&lock_path).unwrap();
+            assert_eq!(info.pid, std::process::id());
+            assert_eq!(info.operation, "update");
+        }
+        assert!(!lock_path.exists());
+    }
+
+    #[test]
+    fn second_acquire_fails_while_held() {
+        let repo = temp_repo();
+        let _lock = RepoLock::acquire(repo.path(), "update").unwrap();
+        let err = RepoLock::acquire(repo.path(), "restore").unwrap_err();
+        assert!(err.to_string().contains("operation 'update'"));
+    }
+
+    #[test]
+    fn old_lock_is_reclaimed() {
+        let repo = temp_repo();
+        let lock_path = repo.path().join(".git").join(REPO_LOCK_FILE);
+        let info = LockInfo {
+            pid: std::process::id(),
+            operation: "update".to_string(),
+            started_at: "2020-01-01T00:00:00Z".to_string(),
+        };
+        fs::write(&lock_path, serde_json::to_string(&info).unwrap()).unwrap();
+
+        let lock = RepoLock::acquire(repo.path(), "restore").unwrap();
+        assert_eq!(read_lock_info(lock.path()).unwrap().operation, "restore");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dead_pid_lock_is_reclaimed() {
+        let repo = temp_repo();
+        let lock_path = repo.path().join(".git").join(REPO_LOCK_FILE);
+        let info = LockInfo {
+            pid: 999_999_999,
+            operation: "update".to_string(),
+            started_at: chrono::Utc::now().to_rfc3339(),
+        };
+        fs::write(&lock_path, serde_json::to_string(&info).unwrap()).unwrap();
+
+        assert!(RepoLock::acquire(repo.path(), "restore").is_ok());
+    }
+
+    #[test]
+    fn worktrees_of_one_repo_share_its_lock() {
+        let tmp = tempfile::tempdir().unwrap();
+        let main = tmp.path().join("main");
+        crate::test_fixtures::repo(&main, &[]);
+        let wt = tmp.path().join("wt");
+        crate::test_fixtures::worktree(&main, &wt, "feat");
+
+        let lock = RepoLock::acquire(&wt, "update").unwrap();
+        assert_eq!(
+            lock.path().canonicalize().unwrap(),
+            main.join(".git")
+                .join(REPO_LOCK_FILE)
+                .canonicalize()
+                .unwrap()
+        );
+        assert!(RepoLock::acquire(&main, "restore").is_err());
+        drop(lock);
+        assert!(RepoLock::acquire(&main, "restore").is_ok());
+    }
+
+    #[test]
+    fn lock_taken_while_reclaiming_is_put_back() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(REPO_LOCK_FILE);
+        let stale = r#"{"pid":1,"operation":"update","started_at":"2020-01-01T00:00:00Z"}"#;
+        let fresh = serde_json::to_string(&LockInfo::current("restore")).unwrap();
+
+        fs::write(&path, &fresh).unwrap();
+        assert!(!reclaim_stale(&path, stale).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), fresh);
+
+        fs::write(&path, stale).unwrap();
+        assert!(reclaim_stale(&path, stale).unwrap());
+        assert!(!path.exists());
+        assert!(!reclaim_stale(&path, stale).unwrap());
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn unreadable_lock_is_reclaimed_and_temp_files_cleaned_up() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("operation.lock");
+        fs::write(&path, "").unwrap();
+
+        let lock = WorkspaceLock::acquire_at(&path, "clone", WaitMode::Fail).unwrap();
+        assert_eq!(read_lock_info(lock.path()).unwrap().operation, "clone");
+        assert!(WorkspaceLock::acquire_at(&path, "update", WaitMode::Fail).is_err());
+        drop(lock);
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn non_repo_is_an_error() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert!(RepoLock::acquire(tmp.path(), "update").is_err());
+    }
+
+    // ── WorkspaceLock ───────────────────────────────────────
+
+    #[test]
+    fn workspace_lock_fail_mode_reports_holder() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("operation.lock");
+        let _held = WorkspaceLock::acquire_at(&path, "update", WaitMode::Fail).unwrap();
+
+        let err = WorkspaceLock::acquire_at(&path, "clone", WaitMode::Fail).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("operation 'update'"));
+        assert!(msg.contains(&std::process::id().to_string()));
+    }
+
+    #[test]
+    fn workspace_lock_wait_mode_times_out() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("operation.lock");
+        let _held = WorkspaceLock::acquire_at(&path, "update", WaitMode::Fail).unwrap();
+
+        let mode = WaitMode::Wait {
+            timeout: Some(Duration::from_millis(300)),
+        };
+        let err = WorkspaceLock::acquire_at(&path, "clone", mode).unwrap_err();
+        assert!(err.to_string().contains("Timed out"));
+    }
+
+    #[test]
+    fn workspace_lock_wait_mode_acquires_after_release() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("operation.lock");
+        let held = WorkspaceLock::acquire_at(&path, "update", WaitMode::Fail).unwrap();
+
+        let releaser = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            drop(held);
+        });
+        let lock = WorkspaceLock::acquire_at(&path, "clone", WaitMode::Wait { timeout: None });
+        releaser.join().unwrap();
+        assert_eq!(
+            read_lock_info(lock.unwrap().path()).unwrap().operation,
+            "clone"
+        );
+    }
+}
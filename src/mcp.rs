@@ -0,0 +1,265 @@
+//! Model Context Protocol server (`mcp` feature).
+//!
+//! Serves MCP over stdio (newline-delimited JSON-RPC 2.0) so AI coding
+//! assistants can inspect and manipulate one workspace. Tools are thin
+//! wrappers over [`crate::api`] and are pinned to the workspace the server
+//! was started for: callers cannot point them at other directories.
+
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+/// MCP protocol revision implemented by this server.
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// An MCP server bound to a single workspace.
+pub struct McpServer {
+    meta_dir: PathBuf,
+}
+
+fn tool_definitions() -> Value {
+    json!([
+        {
+            "name": "list_worktrees",
+            "description": "List the workspace's worktrees with their repos, branches, and dirty state.",
+            "inputSchema": {"type": "object", "properties": {}}
+        },
+        {
+            "name": "workspace_status",
+            "description": "Show branch, dirty files, and ahead/behind counts for every repo in the workspace.",
+            "inputSchema": {"type": "object", "properties": {}}
+        },
+        {
+            "name": "create_worktree",
+            "description": "Create a multi-repo worktree with a new branch in each listed repo.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "name": {"type": "string", "description": "Worktree name (letters, digits, '-', '_')"},
+                    "repos": {"type": "array", "items": {"type": "string"}, "description": "Repo aliases, optionally alias:branch"},
+                    "branch": {"type": "string", "description": "Branch name (defaults to the worktree name)"},
+                    "from_ref": {"type": "string", "description": "Ref to branch from"}
+                },
+                "required": ["name", "repos"]
+            }
+        },
+        {
+            "name": "diff_worktree",
+            "description": "Summarize changes in a worktree relative to a base ref.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "name": {"type": "string"},
+                    "base": {"type": "string", "description": "Base ref, e.g. main"}
+                },
+                "required": ["name", "base"]
+            }
+        }
+    ])
+}
+
+impl McpServer {
+    pub fn new(meta_dir: impl Into<PathBuf>) -> Self {
+        McpServer {
+            meta_dir: meta_dir.into(),
+        }
+    }
+
+    /// Map a tool call onto a JSON API request.
+    fn api_request(&self, tool: &str, args: &Value) -> Result<Value, String> {
+        let arg = |key: &str| args.get(key).cloned().unwrap_or(Value::Null);
+        let (op, mut params) = match tool {
+            "list_worktrees" => ("worktree.list", json!({})),
+            "workspace_status" => ("status", json!({})),
+            "create_worktree" => (
+                "worktree.create",
+                json!({
+                    "name": arg("name"),
+                    "repos": arg("repos"),
+                    "branch": arg("branch"),
+                    "from_ref": arg("from_ref"),
+                }),
+            ),
+            "diff_worktree" => ("diff", json!({"name": arg("name"), "base": arg("base")})),
+            other => return Err(format!("Unknown tool: {other}")),
+        };
+        params["meta_dir"] = json!(self.meta_dir);
+        Ok(json!({"version": crate::api::API_VERSION, "op": op, "params": params}))
+    }
+
+    fn call_tool(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let name = params
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or((-32602, "Missing tool name".to_string()))?;
+        let args = params
+            .get("arguments")
+            .cloned()
+            .unwrap_or_else(|| json!({}));
+        let request = self.api_request(name, &args).map_err(|e| (-32602, e))?;
+
+        let response = crate::api::execute_value(request);
+        let (text, is_error) = if response.ok {
+            let result = response.result.unwrap_or(Value::Null);
+            (
+                serde_json::to_string_pretty(&result).unwrap_or_default(),
+                false,
+            )
+        } else {
+            (response.error.map(|e| e.message).unwrap_or_default(), true)
+        };
+        Ok(json!({
+            "content": [{"type": "text", "text": text}],
+            "isError": is_error,
+        }))
+    }
+
+    /// Handle one JSON-RPC message. Returns the response line, or `None`
+    /// for notifications.
+    pub fn handle_message(&self, line: &str) -> Option<String> {
+        let message: Value = match serde_json::from_str(line) {
+            Ok(v) => v,
+            Err(e) => {
+                return Some(error_response(
+                    Value::Null,
+                    -32700,
+                    &format!("Parse error: {e}"),
+                ))
+            }
+        };
+        // Notifications carry no id and get no response
+        let id = message.get("id").cloned()?;
+        let method = message.get("method").and_then(Value::as_str).unwrap_or("");
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        let result = match method {
+            "initialize" => Ok(json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": {"tools": {}},
+                "serverInfo": {"name": "meta-git", "version": env!("CARGO_PKG_VERSION")},
+            })),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({"tools": tool_definitions()})),
+            "tools/call" => self.call_tool(&params),
+            other => Err((-32601, format!("Method not found: {other}"))),
+        };
+        Some(match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}).to_string(),
+            Err((code, message)) => error_response(id, code, &message),
+        })
+    }
+
+    /// Serve requests from `reader`, writing responses to `writer`, until EOF.
+    pub fn serve(&self, reader: impl BufRead, mut writer: impl Write) -> std::io::Result<()> {
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle_message(&line) {
+                writeln!(writer, "{response}")?;
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Serve over stdin/stdout.
+    pub fn serve_stdio(&self) -> std::io::Result<()> {
+        let stdin = std::io::stdin();
+        self.serve(stdin.lock(), std::io::stdout().lock())
+    }
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> String {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}}).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server() -> McpServer {
+        McpServer::new("/nonexistent/workspace")
+    }
+
+    fn request(server: &McpServer, msg: Value) -> Value {
+        serde_json::from_str(&server.handle_message(&msg.to_string()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn initialize_advertises_tools() {
+        let resp = request(
+            &server(),
+            json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}),
+        );
+        assert_eq!(resp["result"]["protocolVersion"], PROTOCOL_VERSION);
+        assert!(resp["result"]["capabilities"]["tools"].is_object());
+    }
+
+    #[test]
+    fn tools_list_names() {
+        let resp = request(
+            &server(),
+            json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}),
+        );
+        let names: Vec<&str> = resp["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "list_worktrees",
+                "workspace_status",
+                "create_worktree",
+                "diff_worktree"
+            ]
+        );
+    }
+
+    #[test]
+    fn notifications_get_no_response() {
+        let msg = json!({"jsonrpc": "2.0", "method": "notifications/initialized"});
+        assert!(server().handle_message(&msg.to_string()).is_none());
+    }
+
+    #[test]
+    fn unknown_method_and_tool_are_errors() {
+        let s = server();
+        let resp = request(&s, json!({"jsonrpc": "2.0", "id": 3, "method": "nope"}));
+        assert_eq!(resp["error"]["code"], -32601);
+        let resp = request(
+            &s,
+            json!({"jsonrpc": "2.0", "id": 4, "method": "tools/call", "params": {"name": "rm_rf"}}),
+        );
+        assert_eq!(resp["error"]["code"], -32602);
+    }
+
+    #[test]
+    fn tool_failures_are_reported_in_band() {
+        let resp = request(
+            &server(),
+            json!({"jsonrpc": "2.0", "id": 5, "method": "tools/call",
+                   "params": {"name": "workspace_status", "arguments": {"meta_dir": "/etc"}}}),
+        );
+        // The workspace doesn't exist; the caller-supplied meta_dir is ignored
+        assert_eq!(resp["result"]["isError"], true);
+        assert!(resp["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("nonexistent"));
+    }
+
+    #[test]
+    fn serve_processes_lines() {
+        let input = b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\"}\n\n";
+        let mut out = Vec::new();
+        server().serve(&input[..], &mut out).unwrap();
+        let resp: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(resp["id"], 1);
+        assert_eq!(resp["result"], json!({}));
+    }
+}
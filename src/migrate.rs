@@ -0,0 +1,756 @@
+//! Moving a whole workspace to another machine.
+//!
+//! [`export`] bundles everything needed to pick up where you left off into
+//! one `.tar.gz` archive:
+//!
+//! - the root `.meta` config
+//! - every repo's URL and checked-out revision (branch and SHA)
+//! - a git bundle of each repo's branches no remote has
+//! - a binary patch of each checkout's uncommitted and untracked changes
+//! - the worktree store entries of the workspace, and its snapshots
+//!
+//! [`import`] re-materializes the workspace elsewhere. It re-clones the
+//! repos, restores the unpushed branches and checked-out revisions, and
+//! reapplies the patches. Then it recreates the worktrees at their recorded
+//! branches. Pushed history is fetched from the remotes again rather than
+//! archived. Every path in the manifest is checked to stay inside the new
+//! workspace (or the archive) before anything is written.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+use crate::operations::OperationHandle;
+use crate::sandbox::Sandbox;
+use crate::snapshot::{self, RepoState};
+use crate::verify::{git_lines, unpushed_branches};
+use crate::workspace_model::WorkspaceModel;
+use crate::worktree::git_ops::git_worktree_add;
+use crate::worktree::helpers::validate_worktree_name;
+use crate::worktree::hooks::fire_pre_create;
+use crate::worktree::placement::place_worktree;
+use crate::worktree::store::{store_add, store_entries_for};
+use crate::worktree::types::{PlannedRepoEntry, WorktreeStoreEntry};
+
+/// Name of the manifest inside the archive.
+pub const MANIFEST_FILE: &str = "manifest.json";
+const MANIFEST_VERSION: u32 = 1;
+
+/// Contents of a migration archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub created: DateTime<Utc>,
+    /// Workspace directory on the exporting machine
+    pub workspace: PathBuf,
+    /// File name of the root config (`.meta`, `.meta.yaml`, ...), under `config/`
+    pub config_file: String,
+    /// The meta repo itself (`.`, if it's a repo) first, then projects by key
+    pub repos: Vec<MigratedRepo>,
+    pub worktrees: Vec<MigratedWorktree>,
+    /// Snapshot names, each under `snapshots/<name>.json`
+    #[serde(default)]
+    pub snapshots: Vec<String>,
+}
+
+/// One repo of the workspace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigratedRepo {
+    /// Project key from the workspace root, `.` for the meta repo
+    pub key: String,
+    /// Path relative to the workspace
+    pub path: PathBuf,
+    /// `origin`, or the URL from `.meta`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    pub state: RepoState,
+    /// Branches no remote has, in the repo's bundle
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unpushed_branches: Vec<String>,
+    /// Git bundle of `unpushed_branches`, relative to the archive root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bundle: Option<String>,
+    /// Uncommitted changes as a binary diff, relative to the archive root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub patch: Option<String>,
+}
+
+/// One worktree of the workspace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigratedWorktree {
+    pub entry: WorktreeStoreEntry,
+    /// Uncommitted changes per repo alias, relative to the archive root
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub patches: BTreeMap<String, String>,
+}
+
+/// Result of [`export`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportSummary {
+    pub archive: PathBuf,
+    pub repos: usize,
+    pub bundles: usize,
+    pub patches: usize,
+    pub worktrees: usize,
+    pub snapshots: usize,
+    /// Repos and worktrees that couldn't be exported, with why
+    pub failed: Vec<(String, String)>,
+}
+
+/// Result of [`import`].
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportSummary {
+    pub workspace: PathBuf,
+    /// Repos cloned from their remotes
+    pub cloned: Vec<String>,
+    /// Repos whose unpushed branches or changes were restored
+    pub restored: Vec<String>,
+    pub worktrees: Vec<String>,
+    pub snapshots: usize,
+    /// Repos and worktrees that couldn't be brought back, with why
+    pub failed: Vec<(String, String)>,
+}
+
+/// Export the workspace at `meta_dir` to the archive `dest` (`.tar.gz`).
+///
+/// Repos and worktrees that can't be read are reported in
+/// [`ExportSummary::failed`] and left out of the archive rather than
+/// aborting the export.
+pub fn export(meta_dir: &Path, dest: &Path) -> Result<ExportSummary> {
+    crate::read_only::check("export the workspace")?;
+    let (config_path, _) = meta_core::config::find_meta_config_in(meta_dir)
+        .with_context(|| format!("No .meta config in {}", meta_dir.display()))?;
+    let staging = staging_dir("export")?;
+    let out = staging.path();
+    for dir in ["config", "bundles", "patches", "snapshots"] {
+        fs::create_dir_all(out.join(dir))?;
+    }
+    let config_file = file_name(&config_path);
+    fs::copy(&config_path, out.join("config").join(&config_file))?;
+
+    let mut manifest = Manifest {
+        version: MANIFEST_VERSION,
+        created: Utc::now(),
+        workspace: meta_dir.to_path_buf(),
+        config_file,
+        repos: Vec::new(),
+        worktrees: Vec::new(),
+        snapshots: Vec::new(),
+    };
+
+    let mut failed = Vec::new();
+    let mut repos = Vec::new();
+    if snapshot::is_git_repo(meta_dir) {
+        repos.push((".".to_string(), PathBuf::new(), None));
+    }
+    let model = WorkspaceModel::load(meta_dir)?;
+    for key in model.keys() {
+        let Some((path, info)) = model.lookup(key) else {
+            continue;
+        };
+        if snapshot::is_git_repo(path) {
+            let relative = path.strip_prefix(meta_dir).unwrap_or(path).to_path_buf();
+            repos.push((key.to_string(), relative, info.repo.clone()));
+        }
+    }
+    for (i, (key, relative, config_url)) in repos.into_iter().enumerate() {
+        match export_repo(meta_dir, out, i, key.clone(), relative, config_url) {
+            Ok(repo) => manifest.repos.push(repo),
+            Err(e) => failed.push((key, format!("{e:#}"))),
+        }
+    }
+
+    for (i, (root, entry)) in store_entries_for(meta_dir)?.into_iter().enumerate() {
+        let name = entry.name.clone();
+        match export_worktree_patches(Path::new(&root), &entry, out, i) {
+            Ok(patches) => manifest.worktrees.push(MigratedWorktree { entry, patches }),
+            Err(e) => failed.push((format!("worktree {name}"), format!("{e:#}"))),
+        }
+    }
+
+    for info in snapshot::list_snapshots(meta_dir)? {
+        let snap = snapshot::load_snapshot(meta_dir, &info.name)?;
+        fs::write(
+            out.join("snapshots").join(format!("{}.json", info.name)),
+            serde_json::to_string_pretty(&snap)?,
+        )?;
+        manifest.snapshots.push(info.name);
+    }
+
+    fs::write(
+        out.join(MANIFEST_FILE),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+    run_tar(&[
+        "-czf",
+        &dest.to_string_lossy(),
+        "-C",
+        &out.to_string_lossy(),
+        ".",
+    ])?;
+
+    let patches = manifest.repos.iter().filter(|r| r.patch.is_some()).count()
+        + manifest
+            .worktrees
+            .iter()
+            .map(|w| w.patches.len())
+            .sum::<usize>();
+    Ok(ExportSummary {
+        archive: dest.to_path_buf(),
+        repos: manifest.repos.len(),
+        bundles: manifest.repos.iter().filter(|r| r.bundle.is_some()).count(),
+        patches,
+        worktrees: manifest.worktrees.len(),
+        snapshots: manifest.snapshots.len(),
+        failed,
+    })
+}
+
+/// Capture one repo's state, unpushed branches and changes into `out`.
+fn export_repo(
+    meta_dir: &Path,
+    out: &Path,
+    index: usize,
+    key: String,
+    relative: PathBuf,
+    config_url: Option<String>,
+) -> Result<MigratedRepo> {
+    let path = meta_dir.join(&relative);
+    let mut repo = MigratedRepo {
+        url: origin_url(&path).or(config_url),
+        state: snapshot::capture_repo_state(&path)
+            .with_context(|| format!("Failed to read the state of {key}"))?,
+        key,
+        path: relative,
+        unpushed_branches: Vec::new(),
+        bundle: None,
+        patch: None,
+    };
+    repo.unpushed_branches = unpushed_branches(&path)?
+        .into_iter()
+        .map(|(branch, _)| branch)
+        .filter(|branch| branch != "HEAD")
+        .collect();
+    if !repo.unpushed_branches.is_empty() {
+        let bundle = format!("bundles/{index}.bundle");
+        write_bundle(&path, &repo.unpushed_branches, &out.join(&bundle))?;
+        repo.bundle = Some(bundle);
+    }
+    repo.patch = write_patch(&path, out, &format!("patches/{index}.patch"))?;
+    Ok(repo)
+}
+
+/// Write the uncommitted changes of each checkout of the worktree at `root`
+/// into `out`, keyed by repo alias.
+fn export_worktree_patches(
+    root: &Path,
+    entry: &WorktreeStoreEntry,
+    out: &Path,
+    index: usize,
+) -> Result<BTreeMap<String, String>> {
+    let mut patches = BTreeMap::new();
+    for (j, repo) in entry.repos.iter().enumerate() {
+        let path = if repo.alias == "." {
+            root.to_path_buf()
+        } else {
+            root.join(&repo.alias)
+        };
+        if !path.exists() {
+            continue;
+        }
+        if let Some(patch) = write_patch(&path, out, &format!("patches/wt-{index}-{j}.patch"))? {
+            patches.insert(repo.alias.clone(), patch);
+        }
+    }
+    Ok(patches)
+}
+
+/// Re-materialize the workspace exported to `archive` at `dest`, which must
+/// not exist yet or be empty.
+///
+/// Repos that fail to clone or restore are reported in
+/// [`ImportSummary::failed`] rather than aborting the import.
+pub fn import(archive: &Path, dest: &Path) -> Result<ImportSummary> {
+    crate::read_only::check("import a workspace")?;
+    if dest.exists() && fs::read_dir(dest)?.next().is_some() {
+        anyhow::bail!("{} is not empty", dest.display());
+    }
+    let staging = staging_dir("import")?;
+    let src = staging.path();
+    run_tar(&[
+        "-xzf",
+        &archive.to_string_lossy(),
+        "-C",
+        &src.to_string_lossy(),
+    ])?;
+    let manifest: Manifest = serde_json::from_str(
+        &fs::read_to_string(src.join(MANIFEST_FILE)).context("Archive has no manifest")?,
+    )
+    .context("Failed to parse the migration manifest")?;
+    if manifest.version > MANIFEST_VERSION {
+        anyhow::bail!(
+            "Archive format version {} is newer than this version supports ({MANIFEST_VERSION})",
+            manifest.version
+        );
+    }
+    validate_manifest(&manifest)?;
+
+    let mut summary = ImportSummary {
+        workspace: dest.to_path_buf(),
+        ..Default::default()
+    };
+    let options = crate::CloneOptions::default();
+    let hidden = indicatif::ProgressBar::hidden();
+    let sandbox = Sandbox::new([dest]);
+    let mut journal = OperationHandle::start(
+        "workspace import",
+        dest,
+        serde_json::json!({ "archive": archive }),
+        manifest.repos.iter().map(|r| r.key.clone()).collect(),
+    )?;
+    for repo in &manifest.repos {
+        let target = dest.join(&repo.path);
+        if let Err(e) = sandbox.check(&target, "import repo") {
+            summary.failed.push((repo.key.clone(), e.to_string()));
+            continue;
+        }
+        if repo.key == "." && dest.exists() {
+            // Cloning into the (empty) workspace directory itself
+            fs::remove_dir(dest)?;
+        }
+        let Some(url) = &repo.url else {
+            summary
+                .failed
+                .push((repo.key.clone(), "no URL to clone from".to_string()));
+            continue;
+        };
+        match crate::clone_repo_with_options(url, &target, Some(&hidden), &options) {
+            Ok(_) => summary.cloned.push(repo.key.clone()),
+            Err(e) => summary.failed.push((repo.key.clone(), format!("{e:#}"))),
+        }
+    }
+    fs::create_dir_all(dest)?;
+    if meta_core::config::find_meta_config_in(dest).is_none() {
+        let config = sandbox.check(&dest.join(&manifest.config_file), "import config")?;
+        fs::copy(src.join("config").join(&manifest.config_file), config)?;
+    }
+
+    for repo in &manifest.repos {
+        let target = dest.join(&repo.path);
+        if !summary.cloned.contains(&repo.key) {
+            journal.fail_item(&repo.key)?;
+            continue;
+        }
+        match restore_repo(&target, repo, src) {
+            Ok(true) => summary.restored.push(repo.key.clone()),
+            Ok(false) => {}
+            Err(e) => {
+                summary.failed.push((repo.key.clone(), format!("{e:#}")));
+                journal.fail_item(&repo.key)?;
+                continue;
+            }
+        }
+        journal.complete_item(&repo.key)?;
+    }
+
+    for name in &manifest.snapshots {
+        let snap: snapshot::Snapshot = serde_json::from_str(&fs::read_to_string(
+            src.join("snapshots").join(format!("{name}.json")),
+        )?)?;
+        if &snap.name != name {
+            anyhow::bail!("Archive snapshot '{name}' is named '{}'", snap.name);
+        }
+        snapshot::save_snapshot(dest, &snap)?;
+        summary.snapshots += 1;
+    }
+
+    for worktree in &manifest.worktrees {
+        let name = worktree.entry.name.clone();
+        match restore_worktree(dest, worktree, src) {
+            Ok(()) => summary.worktrees.push(name),
+            Err(e) => summary
+                .failed
+                .push((format!("worktree {name}"), format!("{e:#}"))),
+        }
+    }
+    journal.finish()?;
+    Ok(summary)
+}
+
+/// Check that every path in `manifest` stays inside the directory it is
+/// joined onto: the new workspace, a worktree, or the unpacked archive.
+fn validate_manifest(manifest: &Manifest) -> Result<()> {
+    check_file_name(&manifest.config_file, "config file")?;
+    for repo in &manifest.repos {
+        if (repo.key == ".") != repo.path.as_os_str().is_empty() {
+            anyhow::bail!(
+                "Archive repo '{}' has path '{}'; only the meta repo ('.') may be the workspace itself",
+                repo.key,
+                repo.path.display()
+            );
+        }
+        check_relative(&repo.path, "repo path")?;
+        for file in repo.bundle.iter().chain(&repo.patch) {
+            check_relative(Path::new(file), "file")?;
+        }
+    }
+    for name in &manifest.snapshots {
+        check_file_name(name, "snapshot name")?;
+    }
+    for worktree in &manifest.worktrees {
+        validate_worktree_name(&worktree.entry.name)?;
+        for repo in &worktree.entry.repos {
+            check_relative(Path::new(&repo.alias), "worktree repo alias")?;
+        }
+        for file in worktree.patches.values() {
+            check_relative(Path::new(file), "file")?;
+        }
+    }
+    Ok(())
+}
+
+/// Reject absolute paths and `..` components.
+fn check_relative(path: &Path, what: &str) -> Result<()> {
+    if path
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        anyhow::bail!(
+            "Archive {what} '{}' must be relative and without '..'",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Reject anything but a single plain path component.
+fn check_file_name(name: &str, what: &str) -> Result<()> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(()),
+        _ => anyhow::bail!("Archive {what} '{name}' must be a plain name"),
+    }
+}
+
+/// Bring a fresh clone to the exported state. Returns whether unpushed
+/// branches or changes were restored.
+fn restore_repo(path: &Path, repo: &MigratedRepo, archive_root: &Path) -> Result<bool> {
+    let mut restored = false;
+    if let Some(bundle) = &repo.bundle {
+        let bundle = archive_root.join(bundle);
+        git(
+            path,
+            &[
+                "fetch",
+                "--quiet",
+                &bundle.to_string_lossy(),
+                "+refs/heads/*:refs/migrated/*",
+            ],
+        )?;
+        let current = git_lines(path, &["symbolic-ref", "--quiet", "--short", "HEAD"])
+            .ok()
+            .and_then(|lines| lines.into_iter().next());
+        for branch in &repo.unpushed_branches {
+            let migrated = format!("refs/migrated/{branch}");
+            if current.as_deref() == Some(branch.as_str()) {
+                git(path, &["reset", "--quiet", "--hard", &migrated])?;
+            } else {
+                git(path, &["branch", "--force", branch, &migrated])?;
+            }
+            git(path, &["update-ref", "-d", &migrated])?;
+        }
+        restored = true;
+    }
+
+    match &repo.state.branch {
+        Some(branch) => git(path, &["checkout", "--quiet", branch])?,
+        None => git(path, &["checkout", "--quiet", "--detach", &repo.state.sha])?,
+    }
+    if let Some(patch) = &repo.patch {
+        apply_patch(path, &archive_root.join(patch))?;
+        restored = true;
+    }
+    Ok(restored)
+}
+
+fn restore_worktree(
+    meta_dir: &Path,
+    worktree: &MigratedWorktree,
+    archive_root: &Path,
+) -> Result<()> {
+    let entry = &worktree.entry;
+    let sources: Vec<PathBuf> = entry
+        .repos
+        .iter()
+        .map(|r| {
+            if r.alias == "." {
+                meta_dir.to_path_buf()
+            } else {
+                meta_dir.join(&r.alias)
+            }
+        })
+        .collect();
+    let wt_dir = place_worktree(meta_dir, &entry.name, &sources)?;
+    Sandbox::for_workspace(meta_dir).check(&wt_dir, "restore worktree")?;
+    let planned: Vec<PlannedRepoEntry> = entry
+        .repos
+        .iter()
+        .map(|r| PlannedRepoEntry {
+            alias: r.alias.clone(),
+            path: if r.alias == "." {
+                wt_dir.to_string_lossy().into_owned()
+            } else {
+                wt_dir.join(&r.alias).to_string_lossy().into_owned()
+            },
+            branch: r.branch.clone(),
+        })
+        .collect();
+    fire_pre_create(&entry.name, &wt_dir, &planned, false, Some(meta_dir))?;
+    for (repo, source) in entry.repos.iter().zip(&sources) {
+        let dest = if repo.alias == "." {
+            wt_dir.clone()
+        } else {
+            wt_dir.join(&repo.alias)
+        };
+        git_worktree_add(source, &dest, &repo.branch, None)
+            .with_context(|| format!("Failed to create worktree for '{}'", repo.alias))?;
+        if let Some(patch) = worktree.patches.get(&repo.alias) {
+            apply_patch(&dest, &archive_root.join(patch))?;
+        }
+    }
+    let project = meta_dir
+        .canonicalize()
+        .unwrap_or_else(|_| meta_dir.to_path_buf());
+    store_add(
+        &wt_dir,
+        WorktreeStoreEntry {
+            project: project.to_string_lossy().into_owned(),
+            ..entry.clone()
+        },
+    )
+}
+
+fn origin_url(path: &Path) -> Option<String> {
+    git_lines(path, &["remote", "get-url", "origin"])
+        .ok()?
+        .into_iter()
+        .next()
+}
+
+fn write_bundle(path: &Path, branches: &[String], bundle: &Path) -> Result<()> {
+    let mut args = vec!["bundle", "create", "--quiet"];
+    let bundle = bundle.to_string_lossy();
+    args.push(&bundle);
+    args.extend(branches.iter().map(String::as_str));
+    // Only what no remote has; a repo without remotes is bundled whole
+    args.extend(["--not", "--remotes"]);
+    git(path, &args)
+}
+
+/// Write the uncommitted changes of the checkout at `path`, untracked files
+/// included, to `relative` under `archive_root`. `None` if it's clean.
+///
+/// Uses a throwaway index, so the checkout's own index is untouched.
+fn write_patch(path: &Path, archive_root: &Path, relative: &str) -> Result<Option<String>> {
+    let index = archive_root.join(format!("{relative}.index"));
+    let diff = diff_with_index(path, &index);
+    let _ = fs::remove_file(&index);
+    let diff = diff?;
+    if diff.is_empty() {
+        return Ok(None);
+    }
+    fs::write(archive_root.join(relative), diff)?;
+    Ok(Some(relative.to_string()))
+}
+
+fn diff_with_index(path: &Path, index: &Path) -> Result<Vec<u8>> {
+    let with_index = |args: &[&str]| -> Result<Vec<u8>> {
+        let output = crate::git_runner::output(
+            Command::new("git")
+                .args(args)
+                .env("GIT_INDEX_FILE", index)
+                .current_dir(path),
+        )
+        .with_context(|| format!("Failed to run git {}", args[0]))?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "git {} failed in {}: {}",
+                args[0],
+                path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(output.stdout)
+    };
+    with_index(&["read-tree", "HEAD"])?;
+    with_index(&["add", "--all"])?;
+    with_index(&["diff", "--cached", "--binary", "HEAD"])
+}
+
+fn apply_patch(path: &Path, patch: &Path) -> Result<()> {
+    git(
+        path,
+        &["apply", "--whitespace=nowarn", &patch.to_string_lossy()],
+    )
+}
+
+fn git(path: &Path, args: &[&str]) -> Result<()> {
+    git_lines(path, args).map(drop)
+}
+
+fn run_tar(args: &[&str]) -> Result<()> {
+    let output = Command::new("tar")
+        .args(args)
+        .output()
+        .context("Failed to run tar")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "tar failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// A private directory under the system temp dir, removed when dropped.
+fn staging_dir(purpose: &str) -> Result<tempfile::TempDir> {
+    tempfile::Builder::new()
+        .prefix(&format!("meta-migrate-{purpose}-"))
+        .tempdir()
+        .context("Failed to create a staging directory")
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{git as run_git, repo};
+
+    #[test]
+    #[serial_test::serial]
+    fn export_and_import_restore_unpushed_work() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp.path().join("meta-store"));
+        std::env::set_var("META_WORKTREES", tmp.path().join("worktrees"));
+        let origin = tmp.path().join("origin");
+        repo(&origin, &[]);
+
+        let ws = tmp.path().join("old");
+        fs::create_dir(&ws).unwrap();
+        fs::write(
+            ws.join(".meta"),
+            serde_json::json!({"projects": {"api": origin.display().to_string()}}).to_string(),
+        )
+        .unwrap();
+        run_git(&ws, &["clone", "-q", origin.to_str().unwrap(), "api"]);
+        let api = ws.join("api");
+        run_git(&api, &["checkout", "-q", "-b", "feature"]);
+        fs::write(api.join("lib.rs"), "fn local() {}\n").unwrap();
+        run_git(&api, &["add", "lib.rs"]);
+        run_git(&api, &["commit", "-q", "-m", "local only"]);
+        fs::write(api.join("notes.txt"), "wip\n").unwrap();
+        let feature_sha = run_git(&api, &["rev-parse", "HEAD"]);
+
+        let snap = snapshot::Snapshot {
+            name: "before".to_string(),
+            created: Utc::now(),
+            repos: Default::default(),
+        };
+        snapshot::save_snapshot(&ws, &snap).unwrap();
+
+        let archive = tmp.path().join("ws.tar.gz");
+        let exported = export(&ws, &archive).unwrap();
+        assert_eq!(
+            (
+                exported.repos,
+                exported.bundles,
+                exported.patches,
+                exported.snapshots
+            ),
+            (1, 1, 1, 1)
+        );
+
+        let new_ws = tmp.path().join("new");
+        let imported = import(&archive, &new_ws).unwrap();
+        assert!(imported.failed.is_empty(), "{:?}", imported.failed);
+        assert_eq!(imported.cloned, ["api"]);
+        assert_eq!(imported.restored, ["api"]);
+        assert!(new_ws.join(".meta").exists());
+        let new_api = new_ws.join("api");
+        assert_eq!(
+            run_git(&new_api, &["rev-parse", "--abbrev-ref", "HEAD"]),
+            "feature"
+        );
+        assert_eq!(run_git(&new_api, &["rev-parse", "HEAD"]), feature_sha);
+        assert_eq!(
+            fs::read_to_string(new_api.join("notes.txt")).unwrap(),
+            "wip\n"
+        );
+        assert!(snapshot::load_snapshot(&new_ws, "before").is_ok());
+        assert!(import(&archive, &new_ws).is_err());
+
+        std::env::remove_var("META_WORKTREES");
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    fn import_rejects_paths_outside_the_workspace() {
+        let tmp = tempfile::tempdir().unwrap();
+        let escape = |path: &str, key: &str| {
+            let content = tmp.path().join("content");
+            fs::create_dir_all(&content).unwrap();
+            let manifest = Manifest {
+                version: MANIFEST_VERSION,
+                created: Utc::now(),
+                workspace: PathBuf::from("/old"),
+                config_file: ".meta".to_string(),
+                repos: vec![MigratedRepo {
+                    key: key.to_string(),
+                    path: PathBuf::from(path),
+                    url: Some("https://example.com/r.git".to_string()),
+                    state: RepoState {
+                        sha: "0".repeat(40),
+                        branch: None,
+                        dirty: false,
+                        stash_created: false,
+                    },
+                    unpushed_branches: Vec::new(),
+                    bundle: None,
+                    patch: None,
+                }],
+                worktrees: Vec::new(),
+                snapshots: Vec::new(),
+            };
+            fs::write(
+                content.join(MANIFEST_FILE),
+                serde_json::to_string(&manifest).unwrap(),
+            )
+            .unwrap();
+            let archive = tmp.path().join("evil.tar.gz");
+            run_tar(&[
+                "-czf",
+                &archive.to_string_lossy(),
+                "-C",
+                &content.to_string_lossy(),
+                ".",
+            ])
+            .unwrap();
+            import(&archive, &tmp.path().join("new"))
+        };
+
+        for (path, key) in [("../outside", "api"), ("/tmp/outside", "api"), ("sub", ".")] {
+            let err = escape(path, key).unwrap_err();
+            assert!(err.to_string().contains("Archive repo"), "{err:#}");
+        }
+        assert!(!tmp.path().join("outside").exists());
+    }
+}
@@ -0,0 +1,147 @@
+//! Cloning from a local mirror directory.
+//!
+//! Bootstrapping the same workspace again (CI runners, fresh machines with a
+//! pre-seeded cache) needn't download every repo from scratch. With a mirror
+//! root configured, a clone of `git@github.com:org/api.git` first looks for
+//! `<root>/github.com/org/api.git` (see [`mirror_path`]); if it exists the
+//! repo is cloned from there, `origin` is pointed at the real URL, and a
+//! fetch brings it up to date. Unlike `--reference`, nothing is downloaded
+//! that the mirror already has.
+//!
+//! The default root is `~/.meta/mirrors` ([`default_root`]). Mirrors are
+//! plain bare repos, e.g. made with `git clone --mirror`.
+
+use anyhow::{Context, Result};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::git_url::GitUrl;
+use crate::CloneOptions;
+
+/// `~/.meta/mirrors`
+pub fn default_root() -> PathBuf {
+    meta_core::data_dir::meta_dir().join("mirrors")
+}
+
+/// Where a mirror of `url` lives under a mirror root:
+/// `<host>/<path>.git`. `None` for local paths and URLs without a host.
+pub fn mirror_path(url: &str) -> Option<PathBuf> {
+    let url = GitUrl::parse(url).ok().filter(|u| !u.is_local())?;
+    let host = url.host.as_deref()?.to_ascii_lowercase();
+    let path = url.repo_path();
+    if path.is_empty() {
+        return None;
+    }
+    let relative = Path::new(&host).join(format!("{path}.git"));
+    relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+        .then_some(relative)
+}
+
+/// The mirror of `url` under `root`, if there is one.
+pub fn find(root: &Path, url: &str) -> Option<PathBuf> {
+    let path = root.join(mirror_path(url)?);
+    let is_repo = path.join("HEAD").is_file() || path.join(".git").exists();
+    is_repo.then_some(path)
+}
+
+/// Point `origin` of `repo` (just cloned from a mirror) at `url`, fetch, and
+/// fast-forward the checked-out branch.
+///
+/// A failed fetch is only logged: the clone is complete as of the mirror,
+/// and a later update catches it up.
+pub fn repoint_to_origin(repo: &Path, url: &str, options: &CloneOptions) -> Result<()> {
+    let set_url = crate::git_runner::output(
+        Command::new("git")
+            .args(["remote", "set-url", "origin", url])
+            .current_dir(repo),
+    )
+    .context("Failed to run git remote set-url")?;
+    if !set_url.status.success() {
+        anyhow::bail!(
+            "Failed to set origin to {url}: {}",
+            String::from_utf8_lossy(&set_url.stderr).trim()
+        );
+    }
+
+    let mut fetch = Command::new("git");
+    crate::credentials::suppress_prompts(&mut fetch);
+    if let Some(ssh_command) = &options.ssh_command {
+        fetch.env("GIT_SSH_COMMAND", ssh_command);
+    }
+    if options.accept_new_host_keys {
+        crate::ssh_multiplexing::accept_new_host_keys(&mut fetch);
+    }
+    options.https_tokens.apply(&mut fetch, url);
+    fetch
+        .args(["fetch", "--quiet", "origin"])
+        .current_dir(repo)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    match crate::process_timeout::output_with_timeout(&mut fetch, options.timeout) {
+        Ok(out) if out.status.success() => {}
+        Ok(out) => {
+            log::warn!(
+                "Fetch from {url} after mirror clone failed; {} is as of the mirror: {}",
+                repo.display(),
+                String::from_utf8_lossy(&out.stderr).trim()
+            );
+            return Ok(());
+        }
+        Err(e) => {
+            log::warn!(
+                "Fetch from {url} after mirror clone failed; {} is as of the mirror: {e}",
+                repo.display()
+            );
+            return Ok(());
+        }
+    }
+
+    // Detached checkouts and branches without upstream stay where they are
+    let has_upstream = crate::git_runner::output(
+        Command::new("git")
+            .args(["rev-parse", "--verify", "--quiet", "@{upstream}"])
+            .current_dir(repo),
+    )
+    .is_ok_and(|o| o.status.success());
+    if has_upstream {
+        let merged = crate::git_runner::output(
+            Command::new("git")
+                .args(["merge", "--ff-only", "--quiet", "@{upstream}"])
+                .current_dir(repo),
+        )?;
+        if !merged.status.success() {
+            log::warn!(
+                "Could not fast-forward {} after mirror clone: {}",
+                repo.display(),
+                String::from_utf8_lossy(&merged.stderr).trim()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mirror_paths_by_host_and_repo() {
+        let expected = PathBuf::from("github.com/org/api.git");
+        for url in [
+            "git@github.com:org/api.git",
+            "ssh://git@github.com:22/org/api",
+            "https://user@GitHub.com/org/api.git/",
+        ] {
+            assert_eq!(mirror_path(url).as_ref(), Some(&expected), "{url}");
+        }
+        assert_eq!(
+            mirror_path("https://gitlab.com/group/sub/lib.git"),
+            Some(PathBuf::from("gitlab.com/group/sub/lib.git"))
+        );
+        assert_eq!(mirror_path("/srv/git/api.git"), None);
+        assert_eq!(mirror_path("file:///srv/git/api.git"), None);
+        assert_eq!(mirror_path("git@host:../escape.git"), None);
+    }
+}
@@ -0,0 +1,314 @@
+//! Workspace metadata stored as git notes.
+//!
+//! Attaches a JSON [`MetaNote`] (worktree name, change-group id, review links)
+//! to commits under [`NOTES_REF`], so cross-repo change linkage travels with
+//! the repos themselves instead of living only in `~/.meta`. Push the notes
+//! ref alongside branches to share it.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Notes ref used for meta metadata.
+pub const NOTES_REF: &str = "refs/notes/meta";
+
+/// Metadata attached to a commit.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetaNote {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub worktree: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub change_group: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub review_links: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub custom: HashMap<String, String>,
+}
+
+impl MetaNote {
+    /// Merge `other` into `self`: set fields win, review links are unioned.
+    fn merge(&mut self, other: &MetaNote) {
+        if other.worktree.is_some() {
+            self.worktree = other.worktree.clone();
+        }
+        if other.change_group.is_some() {
+            self.change_group = other.change_group.clone();
+        }
+        for link in &other.review_links {
+            if !self.review_links.contains(link) {
+                self.review_links.push(link.clone());
+            }
+        }
+        self.custom
+            .extend(other.custom.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+}
+
+/// Result of attaching or reading a note in one repo.
+#[derive(Debug, Clone, Serialize)]
+pub struct NoteResult {
+    pub alias: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<MetaNote>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+fn repo_path(meta_dir: &Path, alias: &str) -> PathBuf {
+    if alias == "." {
+        meta_dir.to_path_buf()
+    } else {
+        meta_dir.join(alias)
+    }
+}
+
+fn git(repo_path: &Path, args: &[&str]) -> Result<String> {
+    let output = crate::git_runner::output(
+        Command::new("git")
+            .args(args)
+            .current_dir(repo_path)
+            .stdin(Stdio::null()),
+    )
+    .with_context(|| format!("Failed to run git in {}", repo_path.display()))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed in {}: {}",
+            args.join(" "),
+            repo_path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Read the note on `rev` in a single repo. `None` if there is none.
+pub fn read_note(repo_path: &Path, rev: &str) -> Result<Option<MetaNote>> {
+    let commit = git(
+        repo_path,
+        &["rev-parse", "--verify", &format!("{rev}^{{commit}}")],
+    )?;
+    let ref_arg = format!("--ref={NOTES_REF}");
+    let Ok(text) = git(repo_path, &["notes", &ref_arg, "show", &commit]) else {
+        return Ok(None);
+    };
+    let note = serde_json::from_str(&text)
+        .with_context(|| format!("Malformed meta note on {commit} in {}", repo_path.display()))?;
+    Ok(Some(note))
+}
+
+/// Attach `payload` to `rev` in a single repo, merging with any existing note.
+///
+/// Returns the full commit id the note was attached to.
+pub fn attach_note(repo_path: &Path, rev: &str, payload: &MetaNote) -> Result<String> {
+    crate::read_only::check("attach note")?;
+    // The note is read, merged, and written back
+    let _lock = crate::lock::RepoLock::acquire(repo_path, "attach note")?;
+    let commit = git(
+        repo_path,
+        &["rev-parse", "--verify", &format!("{rev}^{{commit}}")],
+    )?;
+    // A note we can't parse isn't ours to replace
+    let mut note = read_note(repo_path, &commit)?.unwrap_or_default();
+    note.merge(payload);
+    let json = serde_json::to_string(&note)?;
+    let ref_arg = format!("--ref={NOTES_REF}");
+    git(
+        repo_path,
+        &["notes", &ref_arg, "add", "-f", "-m", &json, &commit],
+    )?;
+    Ok(commit)
+}
+
+/// Attach `payload` to `rev` in each repo (aliases relative to `meta_dir`).
+///
+/// Failures are reported per repo rather than aborting.
+pub fn attach(meta_dir: &Path, repos: &[String], rev: &str, payload: &MetaNote) -> Vec<NoteResult> {
+    repos
+        .iter()
+        .map(
+            |alias| match attach_note(&repo_path(meta_dir, alias), rev, payload) {
+                Ok(commit) => NoteResult {
+                    alias: alias.clone(),
+                    commit: Some(commit),
+                    note: None,
+                    error: None,
+                },
+                Err(e) => NoteResult {
+                    alias: alias.clone(),
+                    commit: None,
+                    note: None,
+                    error: Some(e.to_string()),
+                },
+            },
+        )
+        .collect()
+}
+
+/// Read the note on `rev` in each repo.
+pub fn read(meta_dir: &Path, repos: &[String], rev: &str) -> Vec<NoteResult> {
+    repos
+        .iter()
+        .map(|alias| match read_note(&repo_path(meta_dir, alias), rev) {
+            Ok(note) => NoteResult {
+                alias: alias.clone(),
+                commit: None,
+                note,
+                error: None,
+            },
+            Err(e) => NoteResult {
+                alias: alias.clone(),
+                commit: None,
+                note: None,
+                error: Some(e.to_string()),
+            },
+        })
+        .collect()
+}
+
+/// All notes in a repo as `(commit, note)` pairs. Malformed notes are skipped.
+pub fn list_notes(repo_path: &Path) -> Result<Vec<(String, MetaNote)>> {
+    let ref_arg = format!("--ref={NOTES_REF}");
+    let listing = match git(repo_path, &["notes", &ref_arg, "list"]) {
+        Ok(out) => out,
+        // No notes ref yet
+        Err(_) => return Ok(Vec::new()),
+    };
+    let mut notes = Vec::new();
+    for line in listing.lines() {
+        let Some((_, commit)) = line.split_once(' ') else {
+            continue;
+        };
+        match read_note(repo_path, commit) {
+            Ok(Some(note)) => notes.push((commit.to_string(), note)),
+            Ok(None) => {}
+            Err(e) => log::warn!("{e}"),
+        }
+    }
+    Ok(notes)
+}
+
+/// Commits in each repo whose note carries `change_group`.
+pub fn find_change_group(
+    meta_dir: &Path,
+    repos: &[String],
+    change_group: &str,
+) -> Result<Vec<(String, String)>> {
+    let mut found = Vec::new();
+    for alias in repos {
+        for (commit, note) in list_notes(&repo_path(meta_dir, alias))? {
+            if note.change_group.as_deref() == Some(change_group) {
+                found.push((alias.clone(), commit));
+            }
+        }
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::repo;
+
+    fn note(group: &str, link: &str) -> MetaNote {
+        MetaNote {
+            worktree: Some("feature-x".to_string()),
+            change_group: Some(group.to_string()),
+            review_links: vec![link.to_string()],
+            custom: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn attach_and_read_across_repos() {
+        let tmp = tempfile::tempdir().unwrap();
+        repo(&tmp.path().join("api"), &[]);
+        repo(&tmp.path().join("web"), &[]);
+        let repos = vec!["api".to_string(), "web".to_string()];
+
+        let results = attach(tmp.path(), &repos, "HEAD", &note("cg-1", "https://pr/1"));
+        assert!(results
+            .iter()
+            .all(|r| r.error.is_none() && r.commit.is_some()));
+
+        let read_back = read(tmp.path(), &repos, "HEAD");
+        for r in read_back {
+            assert_eq!(r.note.unwrap().change_group.as_deref(), Some("cg-1"));
+        }
+    }
+
+    #[test]
+    fn attach_merges_with_existing_note() {
+        let tmp = tempfile::tempdir().unwrap();
+        repo(tmp.path(), &[]);
+        attach_note(tmp.path(), "HEAD", &note("cg-1", "https://pr/1")).unwrap();
+        attach_note(
+            tmp.path(),
+            "HEAD",
+            &MetaNote {
+                review_links: vec!["https://pr/2".to_string()],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let merged = read_note(tmp.path(), "HEAD").unwrap().unwrap();
+        assert_eq!(merged.change_group.as_deref(), Some("cg-1"));
+        assert_eq!(merged.review_links, vec!["https://pr/1", "https://pr/2"]);
+    }
+
+    #[test]
+    fn attach_refuses_to_replace_a_malformed_note() {
+        let tmp = tempfile::tempdir().unwrap();
+        repo(tmp.path(), &[]);
+        let ref_arg = format!("--ref={NOTES_REF}");
+        git(
+            tmp.path(),
+            &["notes", &ref_arg, "add", "-m", "hand-written", "HEAD"],
+        )
+        .unwrap();
+
+        let err = attach_note(tmp.path(), "HEAD", &note("cg-1", "l")).unwrap_err();
+        assert!(err.to_string().contains("Malformed meta note"), "{err:#}");
+        assert_eq!(
+            git(tmp.path(), &["notes", &ref_arg, "show", "HEAD"]).unwrap(),
+            "hand-written"
+        );
+    }
+
+    #[test]
+    fn read_without_note_is_none() {
+        let tmp = tempfile::tempdir().unwrap();
+        repo(tmp.path(), &[]);
+        assert!(read_note(tmp.path(), "HEAD").unwrap().is_none());
+        assert!(list_notes(tmp.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn attach_reports_missing_repo() {
+        let tmp = tempfile::tempdir().unwrap();
+        let results = attach(
+            tmp.path(),
+            &["missing".to_string()],
+            "HEAD",
+            &note("cg", "l"),
+        );
+        assert!(results[0].error.is_some());
+    }
+
+    #[test]
+    fn find_change_group_locates_commits() {
+        let tmp = tempfile::tempdir().unwrap();
+        repo(&tmp.path().join("api"), &[]);
+        repo(&tmp.path().join("web"), &[]);
+        let repos = vec!["api".to_string(), "web".to_string()];
+        attach(tmp.path(), &repos[..1], "HEAD", &note("cg-7", "l"));
+
+        let found = find_change_group(tmp.path(), &repos, "cg-7").unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, "api");
+    }
+}
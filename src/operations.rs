@@ -0,0 +1,350 @@
+//! Journal of long-running operations.
+//!
+//! Long operations (update, snapshot restore, release trains) record their
+//! work items in `~/.meta/operations.json` as they go. After a crash or
+//! reboot, [`in_progress`] lets the CLI warn about abandoned runs and
+//! [`resume_last`] hands back the most recent one with its remaining items so
+//! it can pick up where it left off. An operation whose handle is dropped
+//! without [`OperationHandle::finish`], e.g. because it returned an error, is
+//! marked [`OperationState::Failed`].
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::lock::pid_alive;
+
+/// Entries per journal; the oldest finished entries are dropped beyond this.
+const MAX_ENTRIES: usize = 50;
+
+/// All journaled operations, oldest first.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct OperationJournal {
+    pub operations: Vec<JournalEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationState {
+    Running,
+    Completed,
+    /// Stopped with an error; not resumed
+    Failed,
+}
+
+/// Progress of one long operation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: String,
+    /// Operation name, e.g. "update" or "snapshot restore"
+    pub kind: String,
+    pub meta_dir: PathBuf,
+    /// Operation parameters needed to resume (snapshot name, flags, ...)
+    #[serde(default)]
+    pub params: serde_json::Value,
+    pub pid: u32,
+    pub started_at: String,
+    pub updated_at: String,
+    pub state: OperationState,
+    /// Work items not yet done, in order
+    pub pending: Vec<String>,
+    #[serde(default)]
+    pub completed: Vec<String>,
+    #[serde(default)]
+    pub failed: Vec<String>,
+}
+
+impl JournalEntry {
+    /// Whether the entry is still running but its process is gone.
+    pub fn is_abandoned(&self) -> bool {
+        self.state == OperationState::Running && !pid_alive(self.pid)
+    }
+}
+
+fn journal_paths() -> (PathBuf, PathBuf) {
+    let data_path = meta_core::data_dir::data_file("operations");
+    let lock_path = data_path.with_extension("lock");
+    (data_path, lock_path)
+}
+
+fn update_entry(id: &str, f: impl FnOnce(&mut JournalEntry)) -> Result<()> {
+    let (data_path, lock_path) = journal_paths();
+    meta_core::store::update::<OperationJournal, _>(&data_path, &lock_path, |journal| {
+        if let Some(entry) = journal.operations.iter_mut().find(|e| e.id == id) {
+            f(entry);
+            entry.updated_at = chrono::Utc::now().to_rfc3339();
+        }
+    })
+}
+
+/// Handle used by a running operation to record progress.
+#[derive(Debug)]
+pub struct OperationHandle {
+    id: String,
+    entry: JournalEntry,
+    /// Set once the final state is recorded
+    done: bool,
+}
+
+impl OperationHandle {
+    /// Journal a new operation over `items`.
+    pub fn start(
+        kind: &str,
+        meta_dir: &Path,
+        params: serde_json::Value,
+        items: Vec<String>,
+    ) -> Result<OperationHandle> {
+        crate::read_only::check(kind)?;
+        meta_core::data_dir::ensure_meta_dir()?;
+        let now = chrono::Utc::now();
+        let entry = JournalEntry {
+            id: format!("{}-{}", now.format("%Y%m%dT%H%M%S%.3f"), std::process::id()),
+            kind: kind.to_string(),
+            meta_dir: meta_dir.to_path_buf(),
+            params,
+            pid: std::process::id(),
+            started_at: now.to_rfc3339(),
+            updated_at: now.to_rfc3339(),
+            state: OperationState::Running,
+            pending: items,
+            completed: Vec::new(),
+            failed: Vec::new(),
+        };
+        let (data_path, lock_path) = journal_paths();
+        let recorded = entry.clone();
+        meta_core::store::update::<OperationJournal, _>(&data_path, &lock_path, |journal| {
+            journal.operations.push(recorded);
+            while journal.operations.len() > MAX_ENTRIES {
+                match journal
+                    .operations
+                    .iter()
+                    .position(|e| e.state != OperationState::Running)
+                {
+                    Some(i) => journal.operations.remove(i),
+                    None => break,
+                };
+            }
+        })?;
+        Ok(OperationHandle {
+            id: entry.id.clone(),
+            entry,
+            done: false,
+        })
+    }
+
+    /// The journal entry as of the last update made through this handle.
+    pub fn entry(&self) -> &JournalEntry {
+        &self.entry
+    }
+
+    /// Items still to do.
+    pub fn pending(&self) -> &[String] {
+        &self.entry.pending
+    }
+
+    fn finish_item(&mut self, item: &str, failed: bool) -> Result<()> {
+        let apply = |entry: &mut JournalEntry| {
+            entry.pending.retain(|p| p != item);
+            if failed {
+                entry.failed.push(item.to_string());
+            } else {
+                entry.completed.push(item.to_string());
+            }
+        };
+        apply(&mut self.entry);
+        update_entry(&self.id, apply)
+    }
+
+    /// Record that `item` is done.
+    pub fn complete_item(&mut self, item: &str) -> Result<()> {
+        self.finish_item(item, false)
+    }
+
+    /// Record that `item` failed; it will not be retried on resume.
+    pub fn fail_item(&mut self, item: &str) -> Result<()> {
+        self.finish_item(item, true)
+    }
+
+    /// Mark the whole operation as completed.
+    pub fn finish(mut self) -> Result<JournalEntry> {
+        self.set_state(OperationState::Completed)?;
+        Ok(self.entry.clone())
+    }
+
+    /// Mark the whole operation as failed. Dropping the handle without
+    /// finishing it does the same.
+    pub fn fail(mut self) -> Result<JournalEntry> {
+        self.set_state(OperationState::Failed)?;
+        Ok(self.entry.clone())
+    }
+
+    fn set_state(&mut self, state: OperationState) -> Result<()> {
+        self.done = true;
+        self.entry.state = state;
+        update_entry(&self.id, |entry| entry.state = state)
+    }
+}
+
+impl Drop for OperationHandle {
+    fn drop(&mut self) {
+        if !self.done {
+            if let Err(e) = self.set_state(OperationState::Failed) {
+                log::warn!("Could not mark operation {} as failed: {e:#}", self.id);
+            }
+        }
+    }
+}
+
+/// Read the journal.
+pub fn read_journal() -> Result<OperationJournal> {
+    meta_core::store::read(&journal_paths().0)
+}
+
+/// Operations that were started but never finished, newest first.
+///
+/// Includes operations still running in another process; check
+/// [`JournalEntry::is_abandoned`] to tell them apart.
+pub fn in_progress() -> Result<Vec<JournalEntry>> {
+    let mut entries: Vec<_> = read_journal()?
+        .operations
+        .into_iter()
+        .filter(|e| e.state == OperationState::Running)
+        .collect();
+    entries.reverse();
+    Ok(entries)
+}
+
+/// Take over the most recent abandoned operation.
+///
+/// Returns a handle whose [`OperationHandle::pending`] items are the work
+/// left to do, or `None` if nothing was abandoned. The entry is claimed for
+/// this process so a concurrent resume doesn't pick it up too.
+pub fn resume_last() -> Result<Option<OperationHandle>> {
+    let Some(mut entry) = in_progress()?.into_iter().find(|e| e.is_abandoned()) else {
+        return Ok(None);
+    };
+    crate::read_only::check(&entry.kind)?;
+    let pid = std::process::id();
+    update_entry(&entry.id, |e| e.pid = pid)?;
+    entry.pid = pid;
+    Ok(Some(OperationHandle {
+        id: entry.id.clone(),
+        entry,
+        done: false,
+    }))
+}
+
+/// Drop finished, failed and abandoned operations from the journal.
+///
+/// Returns the number of entries removed.
+pub fn prune_journal() -> Result<usize> {
+    let (data_path, lock_path) = journal_paths();
+    if !data_path.exists() {
+        return Ok(0);
+    }
+    let mut removed = 0;
+    meta_core::store::update::<OperationJournal, _>(&data_path, &lock_path, |journal| {
+        let before = journal.operations.len();
+        journal
+            .operations
+            .retain(|e| e.state == OperationState::Running && !e.is_abandoned());
+        removed = before - journal.operations.len();
+    })?;
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn progress_is_journaled_and_finished() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp.path());
+
+        let mut op = OperationHandle::start(
+            "update",
+            tmp.path(),
+            serde_json::json!({"rebase": true}),
+            items(&["api", "web"]),
+        )
+        .unwrap();
+        op.complete_item("api").unwrap();
+
+        let running = in_progress().unwrap();
+        assert_eq!(running.len(), 1);
+        assert_eq!(running[0].pending, ["web"]);
+        assert_eq!(running[0].completed, ["api"]);
+        // Our own process is alive, so nothing is abandoned
+        assert!(!running[0].is_abandoned());
+        assert!(resume_last().unwrap().is_none());
+
+        op.fail_item("web").unwrap();
+        let entry = op.finish().unwrap();
+        assert_eq!(entry.failed, ["web"]);
+        assert!(in_progress().unwrap().is_empty());
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[serial_test::serial]
+    fn abandoned_operation_is_resumed() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp.path());
+
+        let mut op = OperationHandle::start(
+            "snapshot restore",
+            tmp.path(),
+            serde_json::json!({"name": "before"}),
+            items(&["a", "b", "c"]),
+        )
+        .unwrap();
+        op.complete_item("a").unwrap();
+        // Simulate a crash: the journal now points at a dead process, and
+        // the handle is never dropped
+        let dead_pid = u32::MAX - 1;
+        update_entry(&op.entry().id, |e| e.pid = dead_pid).unwrap();
+        std::mem::forget(op);
+
+        assert!(in_progress().unwrap()[0].is_abandoned());
+        let mut resumed = resume_last().unwrap().unwrap();
+        assert_eq!(resumed.entry().kind, "snapshot restore");
+        assert_eq!(resumed.entry().params["name"], "before");
+        assert_eq!(resumed.pending(), ["b", "c"]);
+        assert!(!in_progress().unwrap()[0].is_abandoned());
+
+        resumed.complete_item("b").unwrap();
+        resumed.complete_item("c").unwrap();
+        resumed.finish().unwrap();
+        assert_eq!(prune_journal().unwrap(), 1);
+        assert!(read_journal().unwrap().operations.is_empty());
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn dropped_operation_is_marked_failed() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp.path());
+
+        let failing = || -> Result<()> {
+            let mut op =
+                OperationHandle::start("update", tmp.path(), serde_json::json!({}), items(&["a"]))?;
+            op.complete_item("a")?;
+            anyhow::bail!("network down")
+        };
+        assert!(failing().is_err());
+
+        assert!(in_progress().unwrap().is_empty());
+        let journal = read_journal().unwrap();
+        assert_eq!(journal.operations[0].state, OperationState::Failed);
+        assert!(resume_last().unwrap().is_none());
+        std::env::remove_var("META_DATA_DIR");
+    }
+}
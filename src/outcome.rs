@@ -0,0 +1,365 @@
+//! Summary of a batch operation with partial-failure thresholds.
+//!
+//! An [`OperationOutcome`] counts successes, skips, and failures per
+//! [`FailureCategory`] across the repos of one run. Checked against
+//! [`FailureThresholds`], it yields an [`OutcomeStatus`] and exit code, so CI
+//! can tell "one flaky clone" (warning) from "auth is broken everywhere"
+//! (failure) without parsing log output.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::credentials::{classify_failure, FailureKind};
+use crate::remote_check::{classify_remote_error, RemoteProblem};
+
+const DISK_FULL_PATTERNS: &[&str] = &["No space left on device", "Disk quota exceeded"];
+
+/// Broad cause of a per-repo failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureCategory {
+    Auth,
+    NotFound,
+    Network,
+    RateLimit,
+    /// The local disk or quota is full
+    DiskFull,
+    /// The operation was killed after its time limit
+    Timeout,
+    Other,
+}
+
+impl FailureCategory {
+    /// Categorize git's error output.
+    pub fn classify(stderr: &str) -> Self {
+        if DISK_FULL_PATTERNS.iter().any(|p| stderr.contains(p)) {
+            return FailureCategory::DiskFull;
+        }
+        if stderr.contains(crate::process_timeout::TIMEOUT_MESSAGE) {
+            return FailureCategory::Timeout;
+        }
+        match classify_failure(stderr) {
+            FailureKind::Auth => return FailureCategory::Auth,
+            FailureKind::RateLimit => return FailureCategory::RateLimit,
+            FailureKind::Network => return FailureCategory::Network,
+            FailureKind::BrokenMux | FailureKind::Other => {}
+        }
+        match classify_remote_error(stderr) {
+            RemoteProblem::NotFound => FailureCategory::NotFound,
+            RemoteProblem::Dns | RemoteProblem::Unreachable => FailureCategory::Network,
+            RemoteProblem::Auth => FailureCategory::Auth,
+            RemoteProblem::Other => FailureCategory::Other,
+        }
+    }
+
+    /// Whether trying again may help: network trouble and rate limits pass,
+    /// while bad credentials, missing repos, and a full disk don't. Timeouts
+    /// count as permanent, as a retry would likely hit the same limit.
+    pub fn is_transient(self) -> bool {
+        matches!(self, FailureCategory::Network | FailureCategory::RateLimit)
+    }
+
+    /// What the user can do about a failure in this category, if anything
+    /// more specific than reading the error.
+    pub fn hint(self) -> Option<String> {
+        let hint = match self {
+            FailureCategory::Auth => format!(
+                "check your SSH keys or credentials (`{}`)",
+                crate::credentials::CREDENTIALS_DOCTOR_COMMAND
+            ),
+            FailureCategory::NotFound => {
+                "check the repo URL in .meta and that you have access to it".to_string()
+            }
+            FailureCategory::Network => "check your network connection and retry".to_string(),
+            FailureCategory::RateLimit => {
+                "lower the clone parallelism or set up SSH multiplexing".to_string()
+            }
+            FailureCategory::DiskFull => "free up disk space and retry".to_string(),
+            FailureCategory::Timeout => "raise the clone timeout for large repos".to_string(),
+            FailureCategory::Other => return None,
+        };
+        Some(hint)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FailureCategory::Auth => "auth",
+            FailureCategory::NotFound => "not_found",
+            FailureCategory::Network => "network",
+            FailureCategory::RateLimit => "rate_limit",
+            FailureCategory::DiskFull => "disk_full",
+            FailureCategory::Timeout => "timeout",
+            FailureCategory::Other => "other",
+        }
+    }
+}
+
+/// When a run with failures counts as a warning or a failure.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct FailureThresholds {
+    /// Fail the run when more than this fraction of attempted repos failed
+    pub fail_ratio: f64,
+    /// Fail the run when more than this many repos failed, regardless of ratio
+    pub max_failures: Option<usize>,
+    /// Fail the run on any failure in these categories
+    pub fatal_categories: Vec<FailureCategory>,
+}
+
+impl FailureThresholds {
+    /// Thresholds from `failure_thresholds` in the `.meta` file in `meta_dir`,
+    /// falling back to the defaults.
+    pub fn from_meta(meta_dir: &Path) -> Self {
+        crate::worktree::helpers::read_meta_config_value(meta_dir)
+            .and_then(|v| v.get("failure_thresholds").cloned())
+            .and_then(|v| {
+                serde_json::from_value(v)
+                    .map_err(|e| log::warn!("Invalid failure_thresholds in .meta: {e}"))
+                    .ok()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl Default for FailureThresholds {
+    fn default() -> Self {
+        FailureThresholds {
+            fail_ratio: 0.10,
+            max_failures: None,
+            fatal_categories: Vec::new(),
+        }
+    }
+}
+
+/// Overall verdict for a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutcomeStatus {
+    Success,
+    /// Some repos failed, but within the thresholds
+    Warning,
+    Failure,
+}
+
+impl OutcomeStatus {
+    /// Process exit code: 0 for success and warnings, 1 for failures.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            OutcomeStatus::Success | OutcomeStatus::Warning => 0,
+            OutcomeStatus::Failure => 1,
+        }
+    }
+}
+
+/// One failed repo.
+#[derive(Debug, Clone, Serialize)]
+pub struct RepoFailure {
+    pub repo: String,
+    pub category: FailureCategory,
+    pub message: String,
+}
+
+/// Counts for one batch run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OperationOutcome {
+    pub operation: String,
+    pub succeeded: usize,
+    pub skipped: usize,
+    pub failures: Vec<RepoFailure>,
+}
+
+impl OperationOutcome {
+    pub fn new(operation: &str) -> Self {
+        OperationOutcome {
+            operation: operation.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn record_success(&mut self) {
+        self.succeeded += 1;
+    }
+
+    /// Record a repo that was not attempted (disabled, quarantined, ...).
+    pub fn record_skipped(&mut self) {
+        self.skipped += 1;
+    }
+
+    /// Record a failed repo, categorizing `message` (usually git's stderr).
+    pub fn record_failure(&mut self, repo: &str, message: &str) -> FailureCategory {
+        let category = FailureCategory::classify(message);
+        self.failures.push(RepoFailure {
+            repo: repo.to_string(),
+            category,
+            message: message.trim().to_string(),
+        });
+        category
+    }
+
+    /// Repos that were attempted (succeeded or failed).
+    pub fn attempted(&self) -> usize {
+        self.succeeded + self.failures.len()
+    }
+
+    /// Failure counts per category.
+    pub fn failures_by_category(&self) -> BTreeMap<FailureCategory, usize> {
+        let mut counts = BTreeMap::new();
+        for failure in &self.failures {
+            *counts.entry(failure.category).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Fraction of attempted repos that failed (0 when nothing was attempted).
+    pub fn failure_ratio(&self) -> f64 {
+        match self.attempted() {
+            0 => 0.0,
+            n => self.failures.len() as f64 / n as f64,
+        }
+    }
+
+    /// Verdict for this run under `thresholds`.
+    pub fn status(&self, thresholds: &FailureThresholds) -> OutcomeStatus {
+        let failed = self.failures.len();
+        if failed == 0 {
+            return OutcomeStatus::Success;
+        }
+        let fatal_category = self
+            .failures
+            .iter()
+            .any(|f| thresholds.fatal_categories.contains(&f.category));
+        if fatal_category
+            || self.failure_ratio() > thresholds.fail_ratio
+            || thresholds.max_failures.is_some_and(|max| failed > max)
+        {
+            OutcomeStatus::Failure
+        } else {
+            OutcomeStatus::Warning
+        }
+    }
+
+    /// One-line summary, e.g. `clone: 18 ok, 2 failed (auth: 2), 1 skipped`.
+    pub fn summary(&self) -> String {
+        let mut out = format!("{}: {} ok", self.operation, self.succeeded);
+        if !self.failures.is_empty() {
+            let categories: Vec<String> = self
+                .failures_by_category()
+                .iter()
+                .map(|(category, count)| format!("{}: {count}", category.as_str()))
+                .collect();
+            out.push_str(&format!(
+                ", {} failed ({})",
+                self.failures.len(),
+                categories.join(", ")
+            ));
+        }
+        if self.skipped > 0 {
+            out.push_str(&format!(", {} skipped", self.skipped));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(ok: usize, failures: &[&str]) -> OperationOutcome {
+        let mut outcome = OperationOutcome::new("clone");
+        for _ in 0..ok {
+            outcome.record_success();
+        }
+        for (i, message) in failures.iter().enumerate() {
+            outcome.record_failure(&format!("repo{i}"), message);
+        }
+        outcome
+    }
+
+    #[test]
+    fn categorizes_failures() {
+        assert_eq!(
+            FailureCategory::classify("Permission denied (publickey)."),
+            FailureCategory::Auth
+        );
+        assert_eq!(
+            FailureCategory::classify("ERROR: Repository not found."),
+            FailureCategory::NotFound
+        );
+        assert_eq!(
+            FailureCategory::classify("Could not resolve hostname x"),
+            FailureCategory::Network
+        );
+        assert_eq!(
+            FailureCategory::classify("Connection reset by peer"),
+            FailureCategory::Network
+        );
+        assert_eq!(
+            FailureCategory::classify("remote: Too Many Requests"),
+            FailureCategory::RateLimit
+        );
+        assert_eq!(
+            FailureCategory::classify(
+                "fatal: write error: No space left on device\nfatal: index-pack failed"
+            ),
+            FailureCategory::DiskFull
+        );
+        assert_eq!(FailureCategory::classify("boom"), FailureCategory::Other);
+        assert!(FailureCategory::Network.is_transient());
+        assert!(!FailureCategory::NotFound.is_transient());
+        assert_eq!(FailureCategory::Other.hint(), None);
+    }
+
+    #[test]
+    fn one_flaky_repo_is_a_warning() {
+        let outcome = outcome(19, &["Connection timed out"]);
+        let thresholds = FailureThresholds::default();
+        assert_eq!(outcome.status(&thresholds), OutcomeStatus::Warning);
+        assert_eq!(outcome.status(&thresholds).exit_code(), 0);
+        assert_eq!(outcome.summary(), "clone: 19 ok, 1 failed (network: 1)");
+    }
+
+    #[test]
+    fn widespread_auth_failure_fails_the_run() {
+        let outcome = outcome(2, &["Permission denied (publickey)."; 3]);
+        assert_eq!(
+            outcome.status(&FailureThresholds::default()),
+            OutcomeStatus::Failure
+        );
+        assert_eq!(outcome.failures_by_category()[&FailureCategory::Auth], 3);
+    }
+
+    #[test]
+    fn fatal_categories_and_max_failures() {
+        let outcome = outcome(99, &["Permission denied (publickey)."]);
+        let thresholds = FailureThresholds {
+            fatal_categories: vec![FailureCategory::Auth],
+            ..Default::default()
+        };
+        assert_eq!(outcome.status(&thresholds), OutcomeStatus::Failure);
+
+        let thresholds = FailureThresholds {
+            max_failures: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(outcome.status(&thresholds), OutcomeStatus::Failure);
+        assert_eq!(
+            OperationOutcome::new("x").status(&thresholds),
+            OutcomeStatus::Success
+        );
+    }
+
+    #[test]
+    fn thresholds_from_meta() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tmp.path().join(".meta"),
+            r#"{"projects": {}, "failure_thresholds": {"fail_ratio": 0.5, "fatal_categories": ["auth"]}}"#,
+        )
+        .unwrap();
+        let thresholds = FailureThresholds::from_meta(tmp.path());
+        assert_eq!(thresholds.fail_ratio, 0.5);
+        assert_eq!(thresholds.fatal_categories, [FailureCategory::Auth]);
+        assert_eq!(thresholds.max_failures, None);
+    }
+}
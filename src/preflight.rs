@@ -0,0 +1,358 @@
+//! Checks before a clone run starts its workers.
+//!
+//! A run that fails halfway through because the disk filled up, or because
+//! `API` and `api` land in the same directory on a case-insensitive
+//! filesystem, leaves a half-cloned workspace behind. [`check`] looks at the
+//! target paths of the queued tasks up front:
+//!
+//! - the nearest existing directory of each target must be writable
+//! - no two targets may differ only in case (on macOS and Windows), nor
+//!   collide that way with a directory already on disk
+//! - each filesystem must have room for the repos cloned onto it, plus a
+//!   margin
+//!
+//! Repo sizes are estimated from earlier persistent runs (recorded in
+//! `~/.meta/clone-sizes.json` by [`record_clone_sizes`]), falling back to
+//! [`PreflightOptions::default_repo_bytes`] for repos never cloned before.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::clone_queue::{CloneReport, CloneTask};
+use crate::render::human_bytes;
+use crate::ssh_multiplexing::normalize_git_url;
+use crate::worktree::placement::filesystem_space;
+
+/// Default free space required beyond the estimated clone sizes.
+pub const DEFAULT_MARGIN_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Default size assumed for a repo with no recorded clone.
+pub const DEFAULT_REPO_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Settings for [`check`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreflightOptions {
+    /// Free space to leave on each filesystem after the run
+    pub margin_bytes: u64,
+    /// Size assumed for repos with no recorded clone
+    pub default_repo_bytes: u64,
+    /// Whether targets differing only in case collide; defaults to true on
+    /// macOS and Windows
+    pub case_insensitive: bool,
+}
+
+impl Default for PreflightOptions {
+    fn default() -> Self {
+        PreflightOptions {
+            margin_bytes: DEFAULT_MARGIN_BYTES,
+            default_repo_bytes: DEFAULT_REPO_BYTES,
+            case_insensitive: cfg!(any(target_os = "macos", windows)),
+        }
+    }
+}
+
+/// Something that would make the run fail.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PreflightProblem {
+    /// `dir`, where `target` would be created, can't be written to
+    NotWritable { target: PathBuf, dir: PathBuf },
+    /// `target` and `other` differ only in case
+    CaseCollision { target: PathBuf, other: PathBuf },
+    /// The filesystem mounted at `mount` has too little free space
+    InsufficientSpace {
+        mount: PathBuf,
+        needed: u64,
+        available: u64,
+    },
+}
+
+impl std::fmt::Display for PreflightProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PreflightProblem::NotWritable { target, dir } => write!(
+                f,
+                "{} can't be created: {} is not writable",
+                target.display(),
+                dir.display()
+            ),
+            PreflightProblem::CaseCollision { target, other } => write!(
+                f,
+                "{} and {} differ only in case",
+                target.display(),
+                other.display()
+            ),
+            PreflightProblem::InsufficientSpace {
+                mount,
+                needed,
+                available,
+            } => write!(
+                f,
+                "{} needs {} free but has {}",
+                mount.display(),
+                human_bytes(*needed),
+                human_bytes(*available)
+            ),
+        }
+    }
+}
+
+/// Estimated disk use of a run on one filesystem.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SpaceEstimate {
+    pub mount: PathBuf,
+    pub repos: usize,
+    /// Estimated clone sizes plus the margin
+    pub needed: u64,
+    pub available: u64,
+}
+
+/// Result of [`check`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PreflightReport {
+    pub problems: Vec<PreflightProblem>,
+    /// Per filesystem; empty where `df` isn't available
+    pub space: Vec<SpaceEstimate>,
+}
+
+impl PreflightReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Check the target paths of `tasks` before cloning them.
+pub fn check(tasks: &[CloneTask], options: &PreflightOptions) -> PreflightReport {
+    let sizes = read_sizes().unwrap_or_default();
+    let mut report = PreflightReport::default();
+
+    // Writability and free space by nearest existing directory, so targets
+    // sharing a parent are probed (and `df` run) once
+    let mut writable: HashMap<PathBuf, bool> = HashMap::new();
+    let mut space: HashMap<PathBuf, Option<(PathBuf, u64)>> = HashMap::new();
+    let nearest = |task: &CloneTask| {
+        task.target_path
+            .ancestors()
+            .skip(1)
+            .find(|p| p.exists())
+            .map(Path::to_path_buf)
+    };
+    for task in tasks {
+        let Some(dir) = nearest(task) else {
+            continue;
+        };
+        let ok = *writable
+            .entry(dir.clone())
+            .or_insert_with(|| is_writable(&dir));
+        if !ok {
+            report.problems.push(PreflightProblem::NotWritable {
+                target: task.target_path.clone(),
+                dir,
+            });
+        }
+    }
+
+    if options.case_insensitive {
+        report.problems.extend(case_collisions(tasks));
+    }
+
+    // Targets grouped by filesystem: (available, repos, estimated bytes)
+    let mut filesystems: BTreeMap<PathBuf, (u64, usize, u64)> = BTreeMap::new();
+    for task in tasks {
+        let Some(dir) = nearest(task) else {
+            continue;
+        };
+        let Some((mount, available)) = space
+            .entry(dir)
+            .or_insert_with_key(|dir| filesystem_space(dir))
+            .clone()
+        else {
+            continue;
+        };
+        let estimate = sizes
+            .repos
+            .get(&normalize_git_url(&task.url))
+            .copied()
+            .unwrap_or(options.default_repo_bytes);
+        let fs = filesystems.entry(mount).or_insert((available, 0, 0));
+        fs.1 += 1;
+        fs.2 = fs.2.saturating_add(estimate);
+    }
+    for (mount, (available, repos, bytes)) in filesystems {
+        let needed = bytes.saturating_add(options.margin_bytes);
+        if needed > available {
+            report.problems.push(PreflightProblem::InsufficientSpace {
+                mount: mount.clone(),
+                needed,
+                available,
+            });
+        }
+        report.space.push(SpaceEstimate {
+            mount,
+            repos,
+            needed,
+            available,
+        });
+    }
+    report
+}
+
+/// Targets that differ only in case from another target, or from an
+/// existing entry of their parent directory.
+fn case_collisions(tasks: &[CloneTask]) -> Vec<PreflightProblem> {
+    let mut problems = Vec::new();
+    let mut seen: HashMap<String, &Path> = HashMap::new();
+    for task in tasks {
+        let target = &task.target_path;
+        let folded = target.to_string_lossy().to_lowercase();
+        match seen.get(&folded) {
+            Some(other) if *other != target.as_path() => {
+                problems.push(PreflightProblem::CaseCollision {
+                    target: target.clone(),
+                    other: other.to_path_buf(),
+                });
+                continue;
+            }
+            Some(_) => continue,
+            None => {
+                seen.insert(folded, target);
+            }
+        }
+        let (Some(parent), Some(name)) = (target.parent(), target.file_name()) else {
+            continue;
+        };
+        let Ok(entries) = std::fs::read_dir(parent) else {
+            continue;
+        };
+        let name = name.to_string_lossy();
+        let existing = entries.flatten().map(|e| e.file_name()).find(|entry| {
+            let entry = entry.to_string_lossy();
+            entry != name && entry.to_lowercase() == name.to_lowercase()
+        });
+        if let Some(entry) = existing {
+            problems.push(PreflightProblem::CaseCollision {
+                target: target.clone(),
+                other: parent.join(entry),
+            });
+        }
+    }
+    problems
+}
+
+fn is_writable(dir: &Path) -> bool {
+    let probe = dir.join(format!(".meta-preflight-{}", std::process::id()));
+    match std::fs::File::create(&probe) {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Recorded clone sizes, keyed by normalized remote URL.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CloneSizes {
+    pub repos: HashMap<String, u64>,
+}
+
+fn store_paths() -> (PathBuf, PathBuf) {
+    let data_path = meta_core::data_dir::data_file("clone-sizes");
+    let lock_path = data_path.with_extension("lock");
+    (data_path, lock_path)
+}
+
+fn read_sizes() -> Result<CloneSizes> {
+    meta_core::store::read(&store_paths().0)
+}
+
+/// Record the size of every repo `report` measured, for later estimates.
+pub fn record_clone_sizes(report: &CloneReport) -> Result<()> {
+    let measured: Vec<(String, u64)> = report
+        .per_repo
+        .iter()
+        .filter_map(|r| Some((normalize_git_url(&r.url), r.stats?.bytes)))
+        .collect();
+    if measured.is_empty() {
+        return Ok(());
+    }
+    let (data_path, lock_path) = store_paths();
+    meta_core::store::update(&data_path, &lock_path, |sizes: &mut CloneSizes| {
+        sizes.repos.extend(measured);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(url: &str, target: PathBuf) -> CloneTask {
+        CloneTask {
+            name: target.file_name().unwrap().to_string_lossy().into_owned(),
+            url: url.to_string(),
+            target_path: target,
+            depth_level: 0,
+            is_meta: false,
+            options: Default::default(),
+            ssh_command: None,
+            attempts: 0,
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn reports_case_collisions_and_missing_space() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp.path().join("data"));
+        let ws = tmp.path().join("ws");
+        std::fs::create_dir_all(ws.join("Docs")).unwrap();
+        let tasks = [
+            task("git@github.com:org/api.git", ws.join("api")),
+            task("git@github.com:org/API.git", ws.join("API")),
+            task("git@github.com:org/docs.git", ws.join("docs")),
+        ];
+
+        let report = check(
+            &tasks,
+            &PreflightOptions {
+                margin_bytes: 0,
+                default_repo_bytes: 1,
+                case_insensitive: true,
+            },
+        );
+        assert_eq!(
+            report.problems,
+            [
+                PreflightProblem::CaseCollision {
+                    target: ws.join("API"),
+                    other: ws.join("api"),
+                },
+                PreflightProblem::CaseCollision {
+                    target: ws.join("docs"),
+                    other: ws.join("Docs"),
+                },
+            ]
+        );
+        if let [space] = report.space.as_slice() {
+            assert_eq!((space.repos, space.needed), (3, 3));
+        }
+
+        let tasks = &tasks[..1];
+        let sensitive = PreflightOptions {
+            case_insensitive: false,
+            margin_bytes: u64::MAX / 2,
+            ..Default::default()
+        };
+        let report = check(tasks, &sensitive);
+        if !report.space.is_empty() {
+            assert!(matches!(
+                report.problems.as_slice(),
+                [PreflightProblem::InsufficientSpace { .. }]
+            ));
+        }
+        std::env::remove_var("META_DATA_DIR");
+    }
+}
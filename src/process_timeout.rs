@@ -0,0 +1,152 @@
+//! Time limits for child processes.
+//!
+//! A hung `git clone` (a DNS lookup that never returns, an SSH session stuck
+//! on a dead connection) would otherwise hold its worker forever. A
+//! [`Watchdog`] kills the child — with its whole process group on Unix, so
+//! ssh and git-remote helpers holding the output pipes go too — once the
+//! limit passes, and the run functions report it as an
+//! [`io::ErrorKind::TimedOut`] error.
+
+use std::io;
+use std::process::{Child, Command, Output};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Start of the error message for a killed process; failure classification
+/// recognizes it.
+pub const TIMEOUT_MESSAGE: &str = "killed after time limit";
+
+/// Kills a child process if it is still running after a time limit.
+pub struct Watchdog {
+    done: Arc<(Mutex<bool>, Condvar)>,
+    fired: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl Watchdog {
+    /// Start watching the process `pid`.
+    pub fn start(pid: u32, timeout: Duration) -> Self {
+        let done = Arc::new((Mutex::new(false), Condvar::new()));
+        let fired = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let (done, fired) = (done.clone(), fired.clone());
+            std::thread::spawn(move || {
+                let (lock, cvar) = &*done;
+                let guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+                let (guard, _) = cvar
+                    .wait_timeout_while(guard, timeout, |done| !*done)
+                    .unwrap_or_else(|e| e.into_inner());
+                if !*guard {
+                    fired.store(true, Ordering::SeqCst);
+                    kill_tree(pid);
+                }
+            })
+        };
+        Watchdog {
+            done,
+            fired,
+            thread,
+        }
+    }
+
+    /// Stop watching. Returns whether the process was killed.
+    pub fn stop(self) -> bool {
+        let (lock, cvar) = &*self.done;
+        *lock.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        cvar.notify_all();
+        let _ = self.thread.join();
+        self.fired.load(Ordering::SeqCst)
+    }
+}
+
+/// Prepare `cmd` so a [`Watchdog`] can kill everything it starts.
+pub fn prepare(cmd: &mut Command) -> &mut Command {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+    cmd
+}
+
+/// Spawn `cmd`, with a watchdog when `timeout` is given.
+pub fn spawn(
+    cmd: &mut Command,
+    timeout: Option<Duration>,
+) -> io::Result<(Child, Option<Watchdog>)> {
+    if timeout.is_some() {
+        prepare(cmd);
+    }
+    let child = cmd.spawn()?;
+    let watchdog = timeout.map(|t| Watchdog::start(child.id(), t));
+    Ok((child, watchdog))
+}
+
+/// The error reported for a process killed after `timeout`.
+pub fn timed_out_error(timeout: Duration) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("{TIMEOUT_MESSAGE} of {}s", timeout.as_secs_f64()),
+    )
+}
+
+/// Like [`Command::output`], killing the process after `timeout`.
+///
+/// Stdout and stderr must be set up by the caller (e.g. piped).
+pub fn output_with_timeout(cmd: &mut Command, timeout: Option<Duration>) -> io::Result<Output> {
+    if crate::git_runner::routes(cmd) {
+        return crate::git_runner::output(cmd);
+    }
+    let (child, watchdog) = spawn(cmd, timeout)?;
+    let output = child.wait_with_output();
+    match (watchdog.map(Watchdog::stop), timeout) {
+        (Some(true), Some(timeout)) => Err(timed_out_error(timeout)),
+        _ => output,
+    }
+}
+
+fn kill_tree(pid: u32) {
+    #[cfg(unix)]
+    let status = Command::new("kill")
+        .args(["-s", "KILL", "--", &format!("-{pid}")])
+        .status();
+    #[cfg(not(unix))]
+    let status = Command::new("taskkill")
+        .args(["/F", "/T", "/PID", &pid.to_string()])
+        .status();
+    if !status.is_ok_and(|s| s.success()) {
+        log::warn!("Failed to kill timed-out process {pid}");
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::process::Stdio;
+    use std::time::Instant;
+
+    #[test]
+    fn kills_hung_process_and_its_children() {
+        let mut cmd = Command::new("sh");
+        // The background sleep keeps the stderr pipe open after sh dies
+        cmd.args(["-c", "sleep 30 & sleep 30"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let start = Instant::now();
+        let err = output_with_timeout(&mut cmd, Some(Duration::from_millis(200))).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn fast_process_is_left_alone() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo hi"]).stdout(Stdio::piped());
+        let out = output_with_timeout(&mut cmd, Some(Duration::from_secs(30))).unwrap();
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "hi\n");
+        let out = output_with_timeout(&mut cmd, None).unwrap();
+        assert!(out.status.success());
+    }
+}
@@ -0,0 +1,98 @@
+//! Choosing which projects of a workspace an operation covers.
+//!
+//! A [`ProjectFilter`] selects projects by tag and by alias glob, e.g. for
+//! `meta git clone --only backend,infra-*`. Globs use
+//! [`glob_match`](crate::worktree::selection::glob_match) against the
+//! project's key from the workspace root (`vendor/lib`) and its own name, so
+//! `infra-*` also matches `infra-dns` declared in a nested meta repo.
+
+use serde::{Deserialize, Serialize};
+
+use crate::worktree::selection::glob_match;
+
+/// Tags and alias globs selecting projects. The default selects everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectFilter {
+    /// Select projects with any of these tags
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub include_tags: Vec<String>,
+    /// Never select projects with any of these tags
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub exclude_tags: Vec<String>,
+    /// Select projects whose key or name matches any of these globs
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+impl ProjectFilter {
+    /// Filter for an `--only` list: each comma-separated item selects the
+    /// projects tagged with it or whose alias matches it as a glob.
+    pub fn only(list: &str) -> Self {
+        let items: Vec<String> = list
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect();
+        ProjectFilter {
+            include_tags: items.clone(),
+            exclude_tags: Vec::new(),
+            aliases: items,
+        }
+    }
+
+    /// Also reject projects tagged with any of `tags`.
+    pub fn excluding_tags(mut self, tags: impl IntoIterator<Item = String>) -> Self {
+        self.exclude_tags.extend(tags);
+        self
+    }
+
+    /// Whether the filter selects everything.
+    pub fn is_empty(&self) -> bool {
+        self.include_tags.is_empty() && self.exclude_tags.is_empty() && self.aliases.is_empty()
+    }
+
+    /// Whether the project at `key` (path from the workspace root, ending in
+    /// its `name`) with `tags` is selected.
+    ///
+    /// Excluded tags win; with no include tags or globs every other project
+    /// is selected.
+    pub fn matches(&self, key: &str, tags: &[String]) -> bool {
+        if tags.iter().any(|t| self.exclude_tags.contains(t)) {
+            return false;
+        }
+        if self.include_tags.is_empty() && self.aliases.is_empty() {
+            return true;
+        }
+        let name = key.rsplit('/').next().unwrap_or(key);
+        tags.iter().any(|t| self.include_tags.contains(t))
+            || self
+                .aliases
+                .iter()
+                .any(|glob| glob_match(glob, key) || glob_match(glob, name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn only_selects_by_tag_or_alias_glob() {
+        let filter = ProjectFilter::only("backend, infra-*");
+        assert!(filter.matches("api", &tags(&["backend"])));
+        assert!(filter.matches("infra-dns", &[]));
+        assert!(filter.matches("platform/infra-dns", &[]));
+        assert!(!filter.matches("web", &tags(&["frontend"])));
+
+        let filter = filter.excluding_tags(tags(&["deprecated"]));
+        assert!(!filter.matches("infra-old", &tags(&["deprecated"])));
+        assert!(ProjectFilter::default().matches("anything", &[]));
+        assert!(ProjectFilter::only(" , ").is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+//! Per-project options read from the raw `.meta` config.
+//!
+//! `meta_core::config::ProjectInfo` only models the fields every plugin needs.
+//! Options that are specific to git operations live here and are read
+//! directly from the `projects` map of the `.meta` file.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use crate::vcs::VcsKind;
+use crate::worktree::helpers::read_meta_config_value;
+use crate::worktree::hooks::HookCommand;
+
+/// Git-specific options for a single project entry.
+///
+/// Projects declared with the short string form (`"name": "url"`) get the defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectOptions {
+    /// Version control system used by the project (defaults to git)
+    pub vcs: VcsKind,
+    /// Exclude the project from every batch operation
+    pub disabled: bool,
+    /// Batch operations to exclude the project from, e.g. `["clone", "update"]`
+    pub skip: Vec<String>,
+    /// Partial clone filter, e.g. `blob:none` (overrides the queue-wide filter)
+    pub clone_filter: Option<String>,
+    /// Clone only history after this date (`--shallow-since`); with
+    /// `shallow_exclude`, replaces the queue-wide depth
+    pub shallow_since: Option<String>,
+    /// Clone only history not reachable from these refs (`--shallow-exclude`)
+    pub shallow_exclude: Vec<String>,
+    /// Force git's wire protocol v2 for the clone
+    pub protocol_v2: bool,
+    /// Directories to check out after cloning (`git sparse-checkout set`);
+    /// empty checks out everything
+    pub sparse_paths: Vec<String>,
+    /// Branch to clone and keep checked out instead of the remote's default
+    pub branch: Option<String>,
+    /// Fetch only `branch` (or the default branch), e.g. for pinned vendor repos
+    pub single_branch: bool,
+    /// Clone the project's submodules too
+    pub submodules: bool,
+    /// Commit, tag, or other revision to pin the checkout to (detached);
+    /// takes precedence over `branch`
+    #[serde(rename = "ref")]
+    pub git_ref: Option<String>,
+    /// Commands run in the repo after a fresh clone (see [`crate::setup`])
+    pub setup: Vec<HookCommand>,
+    /// Time limit per setup command, in seconds
+    pub setup_timeout: Option<u64>,
+    /// Also run `setup` in new worktrees of the project
+    pub setup_in_worktrees: bool,
+}
+
+/// Batch operations a project can be excluded from with `disabled` or `skip`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectOperation {
+    Clone,
+    Update,
+    Status,
+    Worktree,
+    Exec,
+}
+
+impl ProjectOperation {
+    /// Name used in the `skip` list.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProjectOperation::Clone => "clone",
+            ProjectOperation::Update => "update",
+            ProjectOperation::Status => "status",
+            ProjectOperation::Worktree => "worktree",
+            ProjectOperation::Exec => "exec",
+        }
+    }
+}
+
+impl ProjectOptions {
+    /// Whether the project is excluded from `operation`.
+    pub fn skips(&self, operation: ProjectOperation) -> bool {
+        self.disabled
+            || self
+                .skip
+                .iter()
+                .any(|s| s.trim().eq_ignore_ascii_case(operation.as_str()))
+    }
+}
+
+/// Load options for every project declared in the `.meta` config in `meta_dir`.
+///
+/// Returns an empty map if no config is found. Entries whose options fail to
+/// parse are logged and fall back to the defaults.
+pub fn load_project_options(meta_dir: &Path) -> HashMap<String, ProjectOptions> {
+    let Some(config) = read_meta_config_value(meta_dir) else {
+        return HashMap::new();
+    };
+    let Some(projects) = config.get("projects").and_then(|p| p.as_object()) else {
+        return HashMap::new();
+    };
+
+    projects
+        .iter()
+        .map(|(name, value)| {
+            let options = if value.is_object() {
+                serde_json::from_value(value.clone()).unwrap_or_else(|e| {
+                    log::warn!("Invalid options for project '{name}': {e}");
+                    ProjectOptions::default()
+                })
+            } else {
+                ProjectOptions::default()
+            };
+            (name.clone(), options)
+        })
+        .collect()
+}
+
+/// Names of projects in the `.meta` config in `meta_dir` that are excluded
+/// from `operation`.
+///
+/// Batch operations (including `exec` in the CLI) filter their project list
+/// with this and report the names separately.
+pub fn skipped_projects(meta_dir: &Path, operation: ProjectOperation) -> BTreeSet<String> {
+    load_project_options(meta_dir)
+        .into_iter()
+        .filter(|(_, options)| options.skips(operation))
+        .map(|(name, _)| name)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_form_entries_get_defaults() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tmp.path().join(".meta"),
+            r#"{"projects": {"app": "git@github.com:org/app.git"}}"#,
+        )
+        .unwrap();
+
+        let options = load_project_options(tmp.path());
+        assert_eq!(options["app"], ProjectOptions::default());
+    }
+
+    #[test]
+    fn object_entries_parse_vcs() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tmp.path().join(".meta"),
+            r#"{"projects": {"legacy": {"repo": "https://hg.example.com/legacy", "vcs": "hg"}}}"#,
+        )
+        .unwrap();
+
+        let options = load_project_options(tmp.path());
+        assert_eq!(options["legacy"].vcs, VcsKind::Hg);
+    }
+
+    #[test]
+    fn missing_config_returns_empty_map() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert!(load_project_options(tmp.path()).is_empty());
+    }
+
+    #[test]
+    fn disabled_and_skip_lists() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tmp.path().join(".meta"),
+            r#"{"projects": {
+                "broken": {"repo": "git@github.com:org/broken.git", "disabled": true},
+                "huge": {"repo": "git@github.com:org/huge.git", "skip": ["clone", "Update"]},
+                "app": "git@github.com:org/app.git"
+            }}"#,
+        )
+        .unwrap();
+
+        let options = load_project_options(tmp.path());
+        assert!(options["broken"].skips(ProjectOperation::Status));
+        assert!(options["huge"].skips(ProjectOperation::Update));
+        assert!(!options["huge"].skips(ProjectOperation::Exec));
+
+        let skipped = skipped_projects(tmp.path(), ProjectOperation::Clone);
+        assert_eq!(skipped.into_iter().collect::<Vec<_>>(), ["broken", "huge"]);
+        assert_eq!(
+            skipped_projects(tmp.path(), ProjectOperation::Worktree).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn branch_pinned_single_branch_entries() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tmp.path().join(".meta"),
+            r#"{"projects": {"vendor": {"repo": "git@github.com:org/vendor.git", "branch": "v2", "single_branch": true}}}"#,
+        )
+        .unwrap();
+
+        let options = load_project_options(tmp.path());
+        assert_eq!(options["vendor"].branch.as_deref(), Some("v2"));
+        assert!(options["vendor"].single_branch);
+    }
+}
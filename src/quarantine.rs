@@ -0,0 +1,224 @@
+//! Quarantine for repeatedly failing repos.
+//!
+//! A repo whose remote keeps failing with a permanent-looking error (auth
+//! rejected, repository not found) is recorded in `~/.meta/quarantine.json`
+//! with a backoff window. Batch operations skip quarantined repos and report
+//! them separately until the window passes or the user clears the entry, so
+//! one bad repo doesn't spam every run. Transient failures (network, rate
+//! limiting) never quarantine a repo.
+//!
+//! Entries are keyed by the remote URL in [`normalize_git_url`] form.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::remote_check::{classify_remote_error, RemoteProblem};
+use crate::ssh_multiplexing::normalize_git_url;
+
+/// Consecutive permanent failures before a repo is quarantined.
+pub const QUARANTINE_THRESHOLD: u32 = 2;
+
+/// Window after reaching the threshold; doubles with each further failure.
+pub const BASE_WINDOW_SECS: i64 = 60 * 60;
+
+/// Longest quarantine window.
+pub const MAX_WINDOW_SECS: i64 = 24 * 60 * 60;
+
+/// Quarantine entries keyed by normalized remote URL.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct QuarantineStoreData {
+    pub repos: HashMap<String, QuarantineEntry>,
+}
+
+/// Failure history of one remote.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantineEntry {
+    /// The URL as last reported
+    pub url: String,
+    /// Consecutive permanent failures
+    pub failures: u32,
+    pub problem: RemoteProblem,
+    pub last_error: String,
+    pub last_failed_at: DateTime<Utc>,
+    /// End of the quarantine window, once the threshold is reached
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl QuarantineEntry {
+    /// Whether batch operations should skip this repo at `now`.
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.until.is_some_and(|until| now < until)
+    }
+}
+
+/// Quarantine window after `failures` consecutive failures, or `None` below
+/// [`QUARANTINE_THRESHOLD`].
+pub fn backoff_window(failures: u32) -> Option<Duration> {
+    let extra = failures.checked_sub(QUARANTINE_THRESHOLD)?;
+    let secs = BASE_WINDOW_SECS
+        .saturating_mul(1i64 << extra.min(16))
+        .min(MAX_WINDOW_SECS);
+    Some(Duration::seconds(secs))
+}
+
+fn store_paths() -> (PathBuf, PathBuf) {
+    let data_path = meta_core::data_dir::data_file("quarantine");
+    let lock_path = data_path.with_extension("lock");
+    (data_path, lock_path)
+}
+
+fn read_store() -> Result<QuarantineStoreData> {
+    meta_core::store::read(&store_paths().0)
+}
+
+/// The active quarantine entry for `url`, if any.
+pub fn is_quarantined(url: &str) -> Result<Option<QuarantineEntry>> {
+    let mut data = read_store()?;
+    Ok(data
+        .repos
+        .remove(&normalize_git_url(url))
+        .filter(|entry| entry.is_active(Utc::now())))
+}
+
+/// All recorded entries, including ones whose window has passed, sorted by URL.
+pub fn list() -> Result<Vec<QuarantineEntry>> {
+    let mut entries: Vec<_> = read_store()?.repos.into_values().collect();
+    entries.sort_by(|a, b| a.url.cmp(&b.url));
+    Ok(entries)
+}
+
+/// Record a failed operation against `url` with git's `stderr`.
+///
+/// Only auth and not-found failures count. Returns the updated entry, or
+/// `None` if the failure was transient and nothing was recorded.
+pub fn record_failure(url: &str, stderr: &str) -> Result<Option<QuarantineEntry>> {
+    let problem = classify_remote_error(stderr);
+    if !matches!(problem, RemoteProblem::Auth | RemoteProblem::NotFound) {
+        return Ok(None);
+    }
+    crate::read_only::check("write the quarantine list")?;
+    meta_core::data_dir::ensure_meta_dir()?;
+    let (data_path, lock_path) = store_paths();
+    let key = normalize_git_url(url);
+    let now = Utc::now();
+    let mut updated = None;
+
+    meta_core::store::update::<QuarantineStoreData, _>(&data_path, &lock_path, |store| {
+        let failures = store.repos.get(&key).map_or(0, |e| e.failures) + 1;
+        let entry = QuarantineEntry {
+            url: url.to_string(),
+            failures,
+            problem,
+            last_error: stderr.trim().to_string(),
+            last_failed_at: now,
+            until: backoff_window(failures).map(|window| now + window),
+        };
+        updated = Some(entry.clone());
+        store.repos.insert(key, entry);
+    })?;
+    Ok(updated)
+}
+
+/// Forget the failure history of `url` after a successful operation.
+pub fn record_success(url: &str) -> Result<()> {
+    let (data_path, lock_path) = store_paths();
+    if !data_path.exists() {
+        return Ok(());
+    }
+    let key = normalize_git_url(url);
+    if !read_store()?.repos.contains_key(&key) {
+        return Ok(());
+    }
+    crate::read_only::check("write the quarantine list")?;
+    meta_core::store::update::<QuarantineStoreData, _>(&data_path, &lock_path, |store| {
+        store.repos.remove(&key);
+    })
+}
+
+/// Clear the entry for `url`, or every entry when `url` is `None`.
+///
+/// Returns the number of entries removed.
+pub fn clear(url: Option<&str>) -> Result<usize> {
+    crate::read_only::check("clear the quarantine list")?;
+    let (data_path, lock_path) = store_paths();
+    if !data_path.exists() {
+        return Ok(0);
+    }
+    let mut removed = 0;
+    meta_core::store::update::<QuarantineStoreData, _>(
+        &data_path,
+        &lock_path,
+        |store| match url {
+            Some(url) => {
+                removed = usize::from(store.repos.remove(&normalize_git_url(url)).is_some())
+            }
+            None => {
+                removed = store.repos.len();
+                store.repos.clear();
+            }
+        },
+    )?;
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOT_FOUND: &str = "ERROR: Repository not found.";
+
+    #[test]
+    fn backoff_doubles_and_caps() {
+        assert_eq!(backoff_window(1), None);
+        assert_eq!(backoff_window(2), Some(Duration::hours(1)));
+        assert_eq!(backoff_window(3), Some(Duration::hours(2)));
+        assert_eq!(backoff_window(10), Some(Duration::hours(24)));
+        assert_eq!(backoff_window(u32::MAX), Some(Duration::hours(24)));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn repeated_failures_quarantine_until_success() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp.path());
+        let url = "git@github.com:org/gone.git";
+
+        assert!(record_failure(url, "Connection timed out")
+            .unwrap()
+            .is_none());
+        let first = record_failure(url, NOT_FOUND).unwrap().unwrap();
+        assert_eq!(first.failures, 1);
+        assert!(is_quarantined(url).unwrap().is_none());
+
+        let second = record_failure(url, NOT_FOUND).unwrap().unwrap();
+        assert_eq!(second.problem, RemoteProblem::NotFound);
+        // Matched in normalized form
+        assert!(is_quarantined("ssh://git@github.com/org/gone")
+            .unwrap()
+            .is_some());
+
+        record_success(url).unwrap();
+        assert!(is_quarantined(url).unwrap().is_none());
+        assert!(list().unwrap().is_empty());
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn clear_removes_entries() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp.path());
+        for url in ["git@h:a.git", "git@h:b.git"] {
+            record_failure(url, "Permission denied (publickey).").unwrap();
+        }
+        assert_eq!(clear(Some("git@h:a")).unwrap(), 1);
+        assert_eq!(list().unwrap().len(), 1);
+        assert_eq!(clear(None).unwrap(), 1);
+        assert!(list().unwrap().is_empty());
+        std::env::remove_var("META_DATA_DIR");
+    }
+}
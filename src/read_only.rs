@@ -0,0 +1,151 @@
+//! Global read-only mode.
+//!
+//! When enabled, every mutating entry point (clone, update, worktree
+//! create/destroy, snapshot and store writes, notes, `.meta`-adjacent config
+//! edits) fails with a typed [`ReadOnlyViolation`] before touching anything,
+//! so dashboards and agents can inspect a workspace with zero risk of
+//! modifying it.
+//!
+//! Read-only mode is on when any of these hold:
+//! - [`READ_ONLY_ENV`] is set to a truthy value (`1`, `true`, `yes`, `on`)
+//! - the process enabled it via [`ReadOnly::enable`] or [`set_read_only`]
+//! - [`enable_from_config`] found `"read_only": true` in the `.meta` file
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Environment variable that turns read-only mode on.
+pub const READ_ONLY_ENV: &str = "META_READ_ONLY";
+
+static FORCED: AtomicBool = AtomicBool::new(false);
+
+/// A mutating operation was attempted while read-only mode was on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadOnlyViolation {
+    pub operation: String,
+}
+
+impl std::fmt::Display for ReadOnlyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Refusing to {}: read-only mode is enabled (unset {} to allow changes)",
+            self.operation, READ_ONLY_ENV
+        )
+    }
+}
+
+impl std::error::Error for ReadOnlyViolation {}
+
+/// Scope guard enabling read-only mode until dropped.
+///
+/// Restores the previous process-level setting on drop, so guards nest.
+#[must_use = "read-only mode ends when the guard is dropped"]
+pub struct ReadOnly {
+    previous: bool,
+}
+
+impl ReadOnly {
+    pub fn enable() -> Self {
+        ReadOnly {
+            previous: FORCED.swap(true, Ordering::SeqCst),
+        }
+    }
+}
+
+impl Drop for ReadOnly {
+    fn drop(&mut self) {
+        FORCED.store(self.previous, Ordering::SeqCst);
+    }
+}
+
+fn is_truthy(value: &str) -> bool {
+    matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "1" | "true" | "yes" | "on"
+    )
+}
+
+/// Turn process-level read-only mode on or off.
+///
+/// Turning it off does not override [`READ_ONLY_ENV`].
+pub fn set_read_only(enabled: bool) {
+    FORCED.store(enabled, Ordering::SeqCst);
+}
+
+/// Whether read-only mode is currently on.
+pub fn is_read_only() -> bool {
+    FORCED.load(Ordering::SeqCst)
+        || std::env::var(READ_ONLY_ENV)
+            .map(|v| is_truthy(&v))
+            .unwrap_or(false)
+}
+
+/// Whether the `.meta` file in `meta_dir` sets `"read_only": true`.
+pub fn config_read_only(meta_dir: &Path) -> bool {
+    crate::worktree::helpers::read_meta_config_value(meta_dir)
+        .and_then(|v| v.get("read_only").and_then(|v| v.as_bool()))
+        .unwrap_or(false)
+}
+
+/// Enable read-only mode if the workspace config asks for it.
+///
+/// Returns whether read-only mode is now on.
+pub fn enable_from_config(meta_dir: &Path) -> bool {
+    if config_read_only(meta_dir) {
+        set_read_only(true);
+    }
+    is_read_only()
+}
+
+/// Fail with [`ReadOnlyViolation`] if read-only mode is on.
+///
+/// Call at the top of every mutating entry point, before any side effects.
+pub fn check(operation: &str) -> Result<(), ReadOnlyViolation> {
+    if is_read_only() {
+        return Err(ReadOnlyViolation {
+            operation: operation.to_string(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truthy_values() {
+        for v in ["1", "true", "TRUE", " yes ", "on"] {
+            assert!(is_truthy(v), "{v}");
+        }
+        for v in ["", "0", "false", "off", "nope"] {
+            assert!(!is_truthy(v), "{v}");
+        }
+    }
+
+    #[test]
+    fn violation_is_downcastable_from_anyhow() {
+        let err: anyhow::Error = ReadOnlyViolation {
+            operation: "remove worktree".into(),
+        }
+        .into();
+        let violation = err.downcast_ref::<ReadOnlyViolation>().unwrap();
+        assert_eq!(violation.operation, "remove worktree");
+        assert!(err.to_string().contains("read-only mode is enabled"));
+    }
+
+    #[test]
+    fn config_flag() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tmp.path().join(".meta"),
+            r#"{"projects": {}, "read_only": true}"#,
+        )
+        .unwrap();
+        assert!(config_read_only(tmp.path()));
+
+        std::fs::write(tmp.path().join(".meta"), r#"{"projects": {}}"#).unwrap();
+        assert!(!config_read_only(tmp.path()));
+    }
+}
@@ -0,0 +1,134 @@
+//! Local object sources for `git clone --reference`.
+//!
+//! Cloning a repo that already exists locally — as a checkout in another
+//! workspace, the source of a worktree, or a bare mirror in a cache
+//! directory — need not download its objects again. A [`ReferenceStore`]
+//! indexes such repos by remote URL; the clone then runs with
+//! `--reference <path> --dissociate`, copying what it can from the local repo
+//! and fetching only the rest. `--dissociate` keeps the new clone independent
+//! of the reference, so the cache can be pruned later.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::ssh_multiplexing::{get_remote_url, normalize_git_url};
+
+/// Local repos that clones may borrow objects from, keyed by remote URL.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReferenceStore {
+    /// (normalized remote URL, repo path), in discovery order
+    repos: Vec<(String, PathBuf)>,
+}
+
+impl ReferenceStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index every repo at or directly under each of `roots`.
+    ///
+    /// A root can be a repo itself (a checkout or bare mirror) or a directory
+    /// of them, such as a mirror cache or a meta workspace. Missing roots are
+    /// ignored.
+    pub fn scan(roots: &[PathBuf]) -> Self {
+        let mut store = Self::new();
+        for root in roots {
+            if store.add_repo(root) {
+                continue;
+            }
+            let Ok(entries) = std::fs::read_dir(root) else {
+                continue;
+            };
+            let mut children: Vec<PathBuf> = entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.is_dir())
+                .collect();
+            children.sort();
+            for child in children {
+                store.add_repo(&child);
+            }
+        }
+        store
+    }
+
+    /// Index the repo at `path` by its `origin` URL. Returns whether it was
+    /// added (false if `path` is not a repo or has no origin).
+    pub fn add_repo(&mut self, path: &Path) -> bool {
+        if !is_repo_root(path) {
+            return false;
+        }
+        let Some(url) = get_remote_url(path) else {
+            return false;
+        };
+        self.repos
+            .push((normalize_git_url(&url), path.to_path_buf()));
+        true
+    }
+
+    /// A local repo with the same remote as `url`, if any.
+    pub fn find(&self, url: &str) -> Option<&Path> {
+        let key = normalize_git_url(url);
+        self.repos
+            .iter()
+            .find(|(repo_url, _)| *repo_url == key)
+            .map(|(_, path)| path.as_path())
+    }
+
+    pub fn len(&self) -> usize {
+        self.repos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.repos.is_empty()
+    }
+}
+
+/// Whether `path` is the top of a checkout or a bare repo (not merely inside one).
+fn is_repo_root(path: &Path) -> bool {
+    path.join(".git").exists() || (path.join("HEAD").is_file() && path.join("objects").is_dir())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::git;
+
+    #[test]
+    fn indexes_checkouts_and_bare_mirrors() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cache = tmp.path().join("cache");
+        let checkout = cache.join("api");
+        std::fs::create_dir_all(&checkout).unwrap();
+        git(&checkout, &["init", "-q"]);
+        git(
+            &checkout,
+            &["remote", "add", "origin", "git@github.com:org/api.git"],
+        );
+        git(&cache, &["init", "-q", "--bare", "web.git"]);
+        git(
+            &cache.join("web.git"),
+            &["remote", "add", "origin", "https://github.com/org/web"],
+        );
+        // Not a repo of its own: must not pick up a parent's origin
+        std::fs::create_dir_all(checkout.join("src")).unwrap();
+
+        let store = ReferenceStore::scan(&[cache.clone(), tmp.path().join("missing")]);
+        assert_eq!(store.len(), 2);
+        assert_eq!(
+            store.find("ssh://git@github.com/org/api"),
+            Some(checkout.as_path())
+        );
+        assert_eq!(
+            store.find("https://github.com/org/web.git"),
+            Some(cache.join("web.git").as_path())
+        );
+        assert_eq!(store.find("git@github.com:org/other.git"), None);
+
+        // A root that is itself a repo is indexed directly
+        assert_eq!(
+            ReferenceStore::scan(std::slice::from_ref(&checkout)).len(),
+            1
+        );
+    }
+}
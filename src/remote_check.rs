@@ -0,0 +1,270 @@
+//! Remote reachability pre-check before large clone runs.
+//!
+//! [`precheck`] runs `git ls-remote` once per unique remote, a few at a time,
+//! and classifies failures (missing repo, auth, DNS, network) so a batch run
+//! can fail fast with a per-URL diagnosis instead of discovering a bad remote
+//! one worker at a time.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::credentials::{classify_failure, suppress_prompts, FailureKind, HttpsTokens};
+use crate::ssh_multiplexing::normalize_git_url;
+
+/// Default number of concurrent `ls-remote` checks.
+pub const DEFAULT_PRECHECK_CONCURRENCY: usize = 4;
+
+/// Why a remote could not be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteProblem {
+    /// The repository does not exist (or is hidden from us)
+    NotFound,
+    /// Credentials are missing or were rejected
+    Auth,
+    /// The host name does not resolve
+    Dns,
+    /// The host could not be reached
+    Unreachable,
+    Other,
+}
+
+impl std::fmt::Display for RemoteProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            RemoteProblem::NotFound => "repository not found",
+            RemoteProblem::Auth => "authentication failed",
+            RemoteProblem::Dns => "host name does not resolve",
+            RemoteProblem::Unreachable => "host unreachable",
+            RemoteProblem::Other => "remote check failed",
+        })
+    }
+}
+
+/// Result of checking one remote.
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoteCheck {
+    pub url: String,
+    /// `None` when the remote answered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub problem: Option<RemoteProblem>,
+    /// git's error output, when the check failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl RemoteCheck {
+    pub fn is_ok(&self) -> bool {
+        self.problem.is_none()
+    }
+}
+
+const NOT_FOUND_PATTERNS: &[&str] = &[
+    "Repository not found",
+    "repository not found",
+    "does not appear to be a git repository",
+    "returned error: 404",
+    "The project you were looking for could not be found",
+    // `fatal: repository '<url>' not found` (https) or `... does not exist` (local)
+    "fatal: repository '",
+];
+
+const DNS_PATTERNS: &[&str] = &[
+    "Could not resolve hostname",
+    "Could not resolve host",
+    "Name or service not known",
+    "nodename nor servname provided",
+];
+
+const UNREACHABLE_PATTERNS: &[&str] = &[
+    "Network is unreachable",
+    "No route to host",
+    "Connection timed out",
+    "Operation timed out",
+    "Failed to connect to",
+];
+
+/// Classify `git ls-remote` error output.
+pub fn classify_remote_error(stderr: &str) -> RemoteProblem {
+    let matches = |patterns: &[&str]| patterns.iter().any(|p| stderr.contains(p));
+    if matches(DNS_PATTERNS) {
+        RemoteProblem::Dns
+    } else if matches(NOT_FOUND_PATTERNS) {
+        RemoteProblem::NotFound
+    } else if classify_failure(stderr) == FailureKind::Auth {
+        RemoteProblem::Auth
+    } else if matches(UNREACHABLE_PATTERNS)
+        || matches!(
+            classify_failure(stderr),
+            FailureKind::RateLimit | FailureKind::Network
+        )
+    {
+        RemoteProblem::Unreachable
+    } else {
+        RemoteProblem::Other
+    }
+}
+
+/// Check a single remote with `git ls-remote`, authenticating like the clone
+/// would: with `ssh_command` (see
+/// [`ssh_command_for_url`](crate::ssh_multiplexing::ssh_command_for_url)),
+/// or for HTTPS remotes with `tokens`.
+pub fn check_remote(url: &str, ssh_command: Option<&str>, tokens: &HttpsTokens) -> RemoteCheck {
+    let mut cmd = Command::new("git");
+    suppress_prompts(&mut cmd);
+    if let Some(ssh_command) = ssh_command {
+        cmd.env("GIT_SSH_COMMAND", ssh_command);
+    }
+    tokens.apply(&mut cmd, url);
+    let output = crate::git_runner::output(
+        cmd.args(["ls-remote", "--quiet", "--", url, "HEAD"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped()),
+    );
+    match output {
+        Ok(out) if out.status.success() => RemoteCheck {
+            url: url.to_string(),
+            problem: None,
+            detail: None,
+        },
+        Ok(out) => {
+            let stderr = String::from_utf8_lossy(&out.stderr).trim().to_string();
+            RemoteCheck {
+                url: url.to_string(),
+                problem: Some(classify_remote_error(&stderr)),
+                detail: Some(stderr),
+            }
+        }
+        Err(e) => RemoteCheck {
+            url: url.to_string(),
+            problem: Some(RemoteProblem::Other),
+            detail: Some(format!("Failed to run git: {e}")),
+        },
+    }
+}
+
+/// Check every unique remote in `remotes`, `(url, ssh command)` pairs as for
+/// [`check_remote`], with at most `concurrency` checks at once.
+///
+/// URLs are deduplicated with [`normalize_git_url`]; results keep the first
+/// spelling of each remote, in input order.
+pub fn precheck(
+    remotes: &[(String, Option<String>)],
+    concurrency: usize,
+    tokens: &HttpsTokens,
+) -> Vec<RemoteCheck> {
+    let mut seen = HashSet::new();
+    let unique: Vec<&(String, Option<String>)> = remotes
+        .iter()
+        .filter(|(url, _)| seen.insert(normalize_git_url(url)))
+        .collect();
+
+    let workers = concurrency.max(1).min(unique.len().max(1));
+    let next = AtomicUsize::new(0);
+    let slots: Vec<Mutex<Option<RemoteCheck>>> = unique.iter().map(|_| Mutex::new(None)).collect();
+    let runner = crate::git_runner::installed();
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                crate::git_runner::with_installed(runner.clone(), || loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    let Some((url, ssh_command)) = unique.get(i) else {
+                        break;
+                    };
+                    let check = check_remote(url, ssh_command.as_deref(), tokens);
+                    *slots[i].lock().unwrap_or_else(|e| e.into_inner()) = Some(check);
+                })
+            });
+        }
+    });
+    slots
+        .into_iter()
+        .filter_map(|slot| slot.into_inner().unwrap_or_else(|e| e.into_inner()))
+        .collect()
+}
+
+/// One line per failed remote, e.g. for an error message. Empty if all passed.
+pub fn failure_report(checks: &[RemoteCheck]) -> String {
+    checks
+        .iter()
+        .filter_map(|c| {
+            let problem = c.problem?;
+            let detail = c
+                .detail
+                .as_deref()
+                .and_then(|d| d.lines().find(|l| !l.trim().is_empty()))
+                .unwrap_or("");
+            Some(format!("  {}: {} ({})", c.url, problem, detail.trim()))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::repo;
+
+    #[test]
+    fn classifies_common_errors() {
+        assert_eq!(
+            classify_remote_error(
+                "ERROR: Repository not found.\nfatal: Could not read from remote repository."
+            ),
+            RemoteProblem::NotFound
+        );
+        assert_eq!(
+            classify_remote_error(
+                "ssh: Could not resolve hostname gitub.com: Name or service not known"
+            ),
+            RemoteProblem::Dns
+        );
+        assert_eq!(
+            classify_remote_error("git@github.com: Permission denied (publickey)."),
+            RemoteProblem::Auth
+        );
+        assert_eq!(
+            classify_remote_error("ssh: connect to host example.com port 22: Connection timed out"),
+            RemoteProblem::Unreachable
+        );
+        assert_eq!(classify_remote_error("weird"), RemoteProblem::Other);
+    }
+
+    #[test]
+    fn prechecks_local_remotes_and_dedupes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let good = tmp.path().join("good");
+        repo(&good, &[]);
+        let good_url = good.to_string_lossy().into_owned();
+        let missing_url = tmp.path().join("missing").to_string_lossy().into_owned();
+
+        let checks = precheck(
+            &[
+                (good_url.clone(), None),
+                (missing_url.clone(), None),
+                (format!("{good_url}/"), None),
+            ],
+            2,
+            &HttpsTokens::default(),
+        );
+        assert_eq!(checks.len(), 2);
+        assert!(checks[0].is_ok(), "{:?}", checks[0]);
+        assert_eq!(checks[1].url, missing_url);
+        assert_eq!(checks[1].problem, Some(RemoteProblem::NotFound));
+
+        let report = failure_report(&checks);
+        assert!(report.contains(&missing_url));
+        assert!(!report.contains(&format!("{good_url}:")));
+
+        // A URL that looks like an option is a remote, not an option
+        let marker = tmp.path().join("ran");
+        let url = format!("--upload-pack=touch {}", marker.display());
+        let check = check_remote(&url, None, &HttpsTokens::default());
+        assert!(!check.is_ok());
+        assert!(!marker.exists());
+    }
+}
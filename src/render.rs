@@ -0,0 +1,787 @@
+//! Human-readable rendering of structured command outputs.
+//!
+//! Every command builds one output struct and either serializes it (`--json`,
+//! through [`crate::schema::json_output`] for the worktree outputs) or passes
+//! it to [`Render::render`], so pretty output is always generated from the
+//! same data as JSON output.
+
+use console::{pad_str, style, Alignment, StyledObject};
+
+use crate::clone_queue::{ClonePlan, CloneReport, PlanAction, PlanNode, RepoCloneStatus};
+use crate::preflight::PreflightReport;
+use crate::verify::BackupReport;
+use crate::worktree::types::{
+    DiffOutput, ListOutput, PruneEntry, PruneOutput, StatusOutput, UnpushedOutput,
+};
+
+/// Repos listed in the clone summary, slowest first.
+const CLONE_SUMMARY_REPOS: usize = 5;
+
+/// Color theme for rendered output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub color: bool,
+}
+
+impl Theme {
+    /// Plain text, no ANSI escapes.
+    pub fn plain() -> Self {
+        Theme { color: false }
+    }
+
+    /// Colored output.
+    pub fn colored() -> Self {
+        Theme { color: true }
+    }
+
+    /// Colored when stdout is a color-capable terminal.
+    pub fn detect() -> Self {
+        Theme {
+            color: console::colors_enabled(),
+        }
+    }
+
+    fn paint(&self, text: &str, f: impl Fn(StyledObject<&str>) -> StyledObject<&str>) -> String {
+        if self.color {
+            f(style(text)).force_styling(true).to_string()
+        } else {
+            text.to_string()
+        }
+    }
+
+    pub fn header(&self, text: &str) -> String {
+        self.paint(text, |s| s.bold())
+    }
+
+    pub fn ok(&self, text: &str) -> String {
+        self.paint(text, |s| s.green())
+    }
+
+    pub fn warn(&self, text: &str) -> String {
+        self.paint(text, |s| s.yellow())
+    }
+
+    pub fn error(&self, text: &str) -> String {
+        self.paint(text, |s| s.red())
+    }
+
+    pub fn dim(&self, text: &str) -> String {
+        self.paint(text, |s| s.dim())
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::detect()
+    }
+}
+
+/// Conversion of a structured output into human-readable text.
+pub trait Render {
+    fn render(&self, theme: &Theme) -> String;
+}
+
+/// Lay out rows as a left-aligned table with a bold header row.
+///
+/// Cells may already contain ANSI styling; widths are measured without it.
+pub fn table(theme: &Theme, headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            let w = console::measure_text_width(cell);
+            if i < widths.len() {
+                widths[i] = widths[i].max(w);
+            }
+        }
+    }
+
+    let format_row = |cells: Vec<String>| -> String {
+        let last = cells.len().saturating_sub(1);
+        cells
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                if i == last {
+                    cell.clone()
+                } else {
+                    pad_str(cell, widths[i], Alignment::Left, None).into_owned()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    };
+
+    let mut out = String::new();
+    let header_cells = headers.iter().map(|h| theme.header(h)).collect();
+    out.push_str(format_row(header_cells).trim_end());
+    out.push('\n');
+    for row in rows {
+        out.push_str(format_row(row.clone()).trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// Format a byte count with a binary unit (e.g. "1.5 MiB").
+pub fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// Format an age as its largest whole unit (e.g. "3d", "5h").
+pub fn human_age(secs: i64) -> String {
+    let secs = secs.max(0);
+    match secs {
+        s if s >= 604800 => format!("{}w", s / 604800),
+        s if s >= 86400 => format!("{}d", s / 86400),
+        s if s >= 3600 => format!("{}h", s / 3600),
+        s if s >= 60 => format!("{}m", s / 60),
+        s => format!("{s}s"),
+    }
+}
+
+impl Render for ListOutput {
+    fn render(&self, theme: &Theme) -> String {
+        if self.worktrees.is_empty() {
+            return format!("{}\n", theme.dim("No worktrees found."));
+        }
+        let rows: Vec<Vec<String>> = self
+            .worktrees
+            .iter()
+            .map(|wt| {
+                let dirty = wt.repos.iter().filter(|r| r.dirty).count();
+                let mut repos = if dirty > 0 {
+                    format!(
+                        "{} {}",
+                        wt.repos.len(),
+                        theme.warn(&format!("({dirty} dirty)"))
+                    )
+                } else {
+                    wt.repos.len().to_string()
+                };
+                let base_moved = wt.repos.iter().filter_map(|r| r.base_moved_by).max();
+                if let Some(commits) = base_moved {
+                    repos.push_str(&format!(" {}", theme.warn(&format!("(base +{commits})"))));
+                }
+                let prs = wt
+                    .pull_requests
+                    .iter()
+                    .map(|pr| format!("#{} {}", pr.number, pr.state.to_lowercase()))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                vec![
+                    wt.name.clone(),
+                    repos,
+                    wt.age_seconds.map(human_age).unwrap_or_default(),
+                    wt.disk_usage_bytes.map(human_bytes).unwrap_or_default(),
+                    prs,
+                ]
+            })
+            .collect();
+        table(theme, &["NAME", "REPOS", "AGE", "SIZE", "PRS"], &rows)
+    }
+}
+
+impl Render for StatusOutput {
+    fn render(&self, theme: &Theme) -> String {
+        let rows: Vec<Vec<String>> = self
+            .repos
+            .iter()
+            .map(|r| {
+                let state = if r.dirty {
+                    theme.warn(&format!(
+                        "{} modified, {} untracked",
+                        r.modified_count, r.untracked_count
+                    ))
+                } else {
+                    theme.ok("clean")
+                };
+                let sync = match (r.ahead, r.behind) {
+                    (0, 0) => String::new(),
+                    (a, 0) => format!("↑{a}"),
+                    (0, b) => format!("↓{b}"),
+                    (a, b) => format!("↑{a} ↓{b}"),
+                };
+                vec![r.alias.clone(), r.branch.clone(), state, sync]
+            })
+            .collect();
+        let mut out = format!(
+            "{}\n{}",
+            theme.header(&format!("Worktree: {}", self.name)),
+            table(theme, &["REPO", "BRANCH", "STATE", "SYNC"], &rows)
+        );
+        if !self.disabled.is_empty() {
+            out.push_str(&format!(
+                "\n{}",
+                theme.dim(&format!("Disabled: {}", self.disabled.join(", ")))
+            ));
+        }
+        out
+    }
+}
+
+impl Render for DiffOutput {
+    fn render(&self, theme: &Theme) -> String {
+        let rows: Vec<Vec<String>> = self
+            .repos
+            .iter()
+            .map(|r| {
+                vec![
+                    r.alias.clone(),
+                    r.files_changed.to_string(),
+                    theme.ok(&format!("+{}", r.insertions)),
+                    theme.error(&format!("-{}", r.deletions)),
+                ]
+            })
+            .collect();
+        format!(
+            "{}\n{}{}\n",
+            theme.header(&format!("Diff: {} vs {}", self.name, self.base)),
+            table(theme, &["REPO", "FILES", "ADDED", "REMOVED"], &rows),
+            theme.dim(&format!(
+                "{} repos, {} files changed, +{} -{}",
+                self.totals.repos_changed,
+                self.totals.files_changed,
+                self.totals.insertions,
+                self.totals.deletions
+            ))
+        )
+    }
+}
+
+impl Render for PruneOutput {
+    fn render(&self, theme: &Theme) -> String {
+        let rows = |entries: &[PruneEntry]| -> Vec<Vec<String>> {
+            entries
+                .iter()
+                .map(|e| {
+                    vec![
+                        e.name.clone(),
+                        e.reason.clone(),
+                        e.age_seconds
+                            .map(|s| human_age(s as i64))
+                            .unwrap_or_default(),
+                    ]
+                })
+                .collect()
+        };
+        let mut out = String::new();
+        if self.removed.is_empty() {
+            out.push_str(&format!("{}\n", theme.dim("Nothing to prune.")));
+        } else {
+            let title = if self.dry_run {
+                "Would remove:"
+            } else {
+                "Removed:"
+            };
+            out.push_str(&format!(
+                "{}\n{}",
+                theme.header(title),
+                table(theme, &["NAME", "REASON", "AGE"], &rows(&self.removed))
+            ));
+        }
+        if !self.refused.is_empty() {
+            out.push_str(&format!(
+                "{}\n{}",
+                theme.warn("Kept (use --force to remove):"),
+                table(theme, &["NAME", "REASON", "AGE"], &rows(&self.refused))
+            ));
+        }
+        out
+    }
+}
+
+impl Render for UnpushedOutput {
+    fn render(&self, theme: &Theme) -> String {
+        if self.is_empty() {
+            return format!(
+                "{}\n",
+                theme.ok(&format!("Nothing unpushed in {}.", self.name))
+            );
+        }
+        let mut out = String::new();
+        for repo in &self.repos {
+            out.push_str(&format!(
+                "{} {}\n",
+                theme.header(&repo.alias),
+                theme.warn(&format!("({} unpushed)", repo.commit_count()))
+            ));
+            for branch in &repo.branches {
+                let created = if branch.created {
+                    theme.dim(" (created)")
+                } else {
+                    String::new()
+                };
+                out.push_str(&format!("  {}{created}\n", branch.name));
+                for commit in &branch.commits {
+                    let short = commit.sha.get(..8).unwrap_or(&commit.sha);
+                    out.push_str(&format!("    {} {}\n", theme.dim(short), commit.subject));
+                }
+            }
+        }
+        out
+    }
+}
+
+impl Render for CloneReport {
+    fn render(&self, theme: &Theme) -> String {
+        let mut counts = vec![theme.ok(&format!("{} cloned", self.succeeded))];
+        if self.skipped_existing > 0 {
+            counts.push(format!("{} skipped", self.skipped_existing));
+        }
+        if self.failed > 0 {
+            counts.push(theme.error(&format!("{} failed", self.failed)));
+        }
+        let mut out = format!(
+            "{} in {}, {} ({} objects)\n",
+            counts.join(", "),
+            human_duration_ms(self.duration_ms),
+            human_bytes(self.bytes),
+            self.objects
+        );
+        let rows: Vec<Vec<String>> = self
+            .slowest(CLONE_SUMMARY_REPOS)
+            .into_iter()
+            .map(|repo| {
+                let (size, objects) = repo
+                    .stats
+                    .map(|s| (human_bytes(s.bytes), s.objects.to_string()))
+                    .unwrap_or_default();
+                let name = match repo.status {
+                    RepoCloneStatus::Failed { .. } => theme.error(&repo.name),
+                    RepoCloneStatus::Done(_) => repo.name.clone(),
+                };
+                vec![name, human_duration_ms(repo.duration_ms), size, objects]
+            })
+            .collect();
+        if !rows.is_empty() {
+            out.push_str(&table(theme, &["REPO", "TIME", "SIZE", "OBJECTS"], &rows));
+        }
+        if let Some(summary) = self.auth_failures.summary() {
+            out.push_str(&format!("{}\n", theme.warn(&summary)));
+        }
+        out
+    }
+}
+
+impl Render for ClonePlan {
+    fn render(&self, theme: &Theme) -> String {
+        fn render_nodes(out: &mut String, nodes: &[PlanNode], prefix: &str, theme: &Theme) {
+            for (i, node) in nodes.iter().enumerate() {
+                let last = i + 1 == nodes.len();
+                let action = match node.action {
+                    PlanAction::Clone => theme.ok("clone"),
+                    PlanAction::Present => theme.dim("present"),
+                    PlanAction::Disabled => theme.dim("disabled"),
+                    PlanAction::Filtered => theme.dim("filtered"),
+                    PlanAction::Quarantined => theme.warn("quarantined"),
+                    PlanAction::NoUrl => theme.warn("no url"),
+                };
+                let meta = if node.is_meta { " (meta)" } else { "" };
+                out.push_str(&format!(
+                    "{prefix}{}{}{meta}  {action}\n",
+                    if last { "└── " } else { "├── " },
+                    node.name
+                ));
+                let child_prefix = format!("{prefix}{}", if last { "    " } else { "│   " });
+                render_nodes(out, &node.children, &child_prefix, theme);
+            }
+        }
+
+        let mut out = format!("{}\n", theme.header(&self.root.display().to_string()));
+        render_nodes(&mut out, &self.projects, "", theme);
+        out.push_str(&format!("{} to clone\n", self.clone_count()));
+        out
+    }
+}
+
+impl Render for BackupReport {
+    fn render(&self, theme: &Theme) -> String {
+        if self.is_safe() {
+            return format!(
+                "{}\n",
+                theme.ok(&format!(
+                    "All work in {} repos is pushed; safe to wipe.",
+                    self.scanned
+                ))
+            );
+        }
+        let rows: Vec<Vec<String>> = self
+            .at_risk
+            .iter()
+            .map(|risk| {
+                let mut issues = Vec::new();
+                if let Some(error) = &risk.error {
+                    issues.push(format!("not checked: {error}"));
+                }
+                if risk.no_remote {
+                    issues.push("no remote".to_string());
+                }
+                for (branch, commits) in &risk.unpushed {
+                    issues.push(format!("{branch}: {commits} unpushed"));
+                }
+                if !risk.modified_files.is_empty() {
+                    issues.push(format!("{} modified", risk.modified_files.len()));
+                }
+                if risk.untracked > 0 {
+                    issues.push(format!("{} untracked", risk.untracked));
+                }
+                if risk.stashes > 0 {
+                    issues.push(format!("{} stashed", risk.stashes));
+                }
+                vec![
+                    risk.name.clone(),
+                    risk.worktree.clone().unwrap_or_default(),
+                    issues.join(", "),
+                ]
+            })
+            .collect();
+        format!(
+            "{}\n{}",
+            theme.warn(&format!(
+                "{} of {} repos have work that isn't pushed:",
+                self.at_risk.len(),
+                self.scanned
+            )),
+            table(theme, &["REPO", "WORKTREE", "AT RISK"], &rows)
+        )
+    }
+}
+
+impl Render for PreflightReport {
+    fn render(&self, theme: &Theme) -> String {
+        let mut out = String::new();
+        if !self.space.is_empty() {
+            let rows: Vec<Vec<String>> = self
+                .space
+                .iter()
+                .map(|fs| {
+                    vec![
+                        fs.mount.display().to_string(),
+                        fs.repos.to_string(),
+                        human_bytes(fs.needed),
+                        human_bytes(fs.available),
+                    ]
+                })
+                .collect();
+            out.push_str(&table(
+                theme,
+                &["FILESYSTEM", "REPOS", "NEEDED", "AVAILABLE"],
+                &rows,
+            ));
+        }
+        if self.is_ok() {
+            out.push_str(&format!("{}\n", theme.ok("Preflight checks passed.")));
+        } else {
+            out.push_str(&format!(
+                "{}\n",
+                theme.error(&format!("{} preflight problems:", self.problems.len()))
+            ));
+            for problem in &self.problems {
+                out.push_str(&format!("  {problem}\n"));
+            }
+        }
+        out
+    }
+}
+
+/// Format milliseconds as seconds with one decimal (e.g. "12.3s").
+fn human_duration_ms(ms: u64) -> String {
+    format!("{:.1}s", ms as f64 / 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worktree::types::{
+        DiffRepoEntry, DiffTotals, ListEntry, ListRepoEntry, PruneEntry, StatusRepoEntry,
+    };
+
+    #[test]
+    fn table_aligns_columns() {
+        let out = table(
+            &Theme::plain(),
+            &["A", "B"],
+            &[
+                vec!["long-name".into(), "x".into()],
+                vec!["s".into(), "y".into()],
+            ],
+        );
+        assert_eq!(out, "A          B\nlong-name  x\ns          y\n");
+    }
+
+    #[test]
+    fn table_ignores_ansi_in_widths() {
+        let theme = Theme::colored();
+        let out = table(
+            &theme,
+            &["A", "B"],
+            &[
+                vec![theme.ok("ok"), "x".into()],
+                vec!["abc".into(), "y".into()],
+            ],
+        );
+        let plain = console::strip_ansi_codes(&out);
+        assert_eq!(plain, "A    B\nok   x\nabc  y\n");
+    }
+
+    #[test]
+    fn human_units() {
+        assert_eq!(human_bytes(512), "512 B");
+        assert_eq!(human_bytes(1536), "1.5 KiB");
+        assert_eq!(human_age(7201), "2h");
+        assert_eq!(human_age(3 * 86400 + 5), "3d");
+    }
+
+    #[test]
+    fn list_renders_details_and_dirty_counts() {
+        let output = ListOutput {
+            worktrees: vec![ListEntry {
+                name: "feature".into(),
+                repos: vec![ListRepoEntry {
+                    alias: "api".into(),
+                    branch: "feature".into(),
+                    dirty: true,
+                    base_moved_by: Some(3),
+                }],
+                age_seconds: Some(86400),
+                disk_usage_bytes: Some(2048),
+                ..Default::default()
+            }],
+        };
+        let out = output.render(&Theme::plain());
+        assert!(out.contains("feature  1 (1 dirty) (base +3)  1d   2.0 KiB"));
+    }
+
+    #[test]
+    fn empty_outputs_have_messages() {
+        let theme = Theme::plain();
+        assert!(ListOutput { worktrees: vec![] }
+            .render(&theme)
+            .contains("No worktrees"));
+        let prune = PruneOutput {
+            removed: vec![],
+            dry_run: true,
+            refused: vec![],
+        };
+        assert!(prune.render(&theme).contains("Nothing to prune"));
+    }
+
+    #[test]
+    fn status_shows_sync_and_state() {
+        let output = StatusOutput {
+            name: "wt".into(),
+            repos: vec![StatusRepoEntry {
+                alias: "api".into(),
+                path: "/wt/api".into(),
+                branch: "main".into(),
+                dirty: false,
+                modified_count: 0,
+                untracked_count: 0,
+                ahead: 2,
+                behind: 1,
+                modified_files: vec![],
+                renames: vec![],
+                untracked_files: vec![],
+                last_fetched: None,
+            }],
+            disabled: vec!["legacy".into()],
+        };
+        let out = output.render(&Theme::plain());
+        assert!(out.starts_with("Worktree: wt\n"));
+        assert!(out.contains("api   main    clean  ↑2 ↓1"));
+        assert!(out.ends_with("Disabled: legacy"));
+    }
+
+    #[test]
+    fn diff_includes_totals() {
+        let output = DiffOutput {
+            name: "wt".into(),
+            base: "main".into(),
+            repos: vec![DiffRepoEntry {
+                alias: "api".into(),
+                base_ref: "main".into(),
+                files_changed: 3,
+                insertions: 10,
+                deletions: 4,
+                files: vec![],
+                renames: vec![],
+            }],
+            totals: DiffTotals {
+                repos_changed: 1,
+                files_changed: 3,
+                insertions: 10,
+                deletions: 4,
+            },
+        };
+        let out = output.render(&Theme::plain());
+        assert!(out.contains("api   3      +10    -4"));
+        assert!(out.ends_with("1 repos, 3 files changed, +10 -4\n"));
+    }
+
+    #[test]
+    fn prune_dry_run_title() {
+        let output = PruneOutput {
+            removed: vec![PruneEntry {
+                name: "old".into(),
+                path: "/wt/old".into(),
+                reason: "ttl expired".into(),
+                age_seconds: Some(3600),
+            }],
+            dry_run: true,
+            refused: vec![PruneEntry {
+                name: "wip".into(),
+                path: "/wt/wip".into(),
+                reason: "uncommitted changes in api".into(),
+                age_seconds: None,
+            }],
+        };
+        let out = output.render(&Theme::plain());
+        assert!(out.starts_with("Would remove:\n"));
+        assert!(out.contains("old   ttl expired  1h"));
+        assert!(out.contains("Kept (use --force to remove):\nNAME"));
+        assert!(out.contains("wip   uncommitted changes in api"));
+    }
+
+    #[test]
+    fn clone_report_lists_slowest_repos() {
+        use crate::clone_queue::{CloneStats, RepoCloneResult};
+        use crate::collision::{CloneAction, CloneResult, TargetState};
+        use crate::outcome::OperationOutcome;
+
+        let repo = |name: &str, duration_ms: u64, bytes: u64| RepoCloneResult {
+            name: name.into(),
+            url: format!("git@github.com:org/{name}.git"),
+            target_path: format!("/ws/{name}").into(),
+            depth_level: 0,
+            status: RepoCloneStatus::Done(CloneResult {
+                found: TargetState::Missing,
+                action: CloneAction::Cloned,
+                transport: None,
+            }),
+            duration_ms,
+            stats: Some(CloneStats { bytes, objects: 10 }),
+            setup: Vec::new(),
+        };
+        let report = CloneReport::new(
+            vec![repo("api", 1200, 2048), repo("web", 9500, 3 * 1024 * 1024)],
+            OperationOutcome::default(),
+            std::time::Duration::from_millis(10_000),
+        );
+        let out = report.render(&Theme::plain());
+        assert!(out.starts_with("2 cloned in 10.0s, 3.0 MiB (20 objects)\nREPO"));
+        assert!(out.contains("web   9.5s  3.0 MiB  10\napi   1.2s  2.0 KiB  10\n"));
+        assert!(report.auth_failures.is_empty());
+
+        let mut denied = repo("docs", 300, 0);
+        denied.stats = None;
+        denied.status = RepoCloneStatus::Failed {
+            category: crate::outcome::FailureCategory::Auth,
+            message: "Failed to clone git@github.com:org/docs.git into /ws/docs (auth): \
+                      git@github.com: Permission denied (publickey)."
+                .into(),
+        };
+        let report = CloneReport::new(
+            vec![denied],
+            OperationOutcome::default(),
+            std::time::Duration::from_millis(300),
+        );
+        assert_eq!(report.failed, 1);
+        assert_eq!(
+            serde_json::to_value(&report).unwrap()["auth_failures"],
+            serde_json::json!({"github.com": ["docs"]})
+        );
+        let out = report.render(&Theme::plain());
+        assert!(out.contains("1 repo(s) failed authentication:\n  github.com: docs\n"));
+    }
+
+    #[test]
+    fn unpushed_lists_commits_per_branch() {
+        use crate::worktree::types::{UnpushedBranch, UnpushedCommit, UnpushedRepoEntry};
+
+        let mut output = UnpushedOutput {
+            name: "feat".into(),
+            root: "/wt/feat".into(),
+            repos: Vec::new(),
+        };
+        assert_eq!(
+            output.render(&Theme::plain()),
+            "Nothing unpushed in feat.\n"
+        );
+        output.repos.push(UnpushedRepoEntry {
+            alias: "api".into(),
+            branches: vec![UnpushedBranch {
+                name: "feat".into(),
+                created: true,
+                commits: vec![UnpushedCommit {
+                    sha: "0123456789abcdef".into(),
+                    subject: "Add retries".into(),
+                }],
+            }],
+        });
+        assert_eq!(
+            output.render(&Theme::plain()),
+            "api (1 unpushed)\n  feat (created)\n    01234567 Add retries\n"
+        );
+    }
+
+    #[test]
+    fn clone_plan_renders_as_tree() {
+        let node = |name: &str, action, children| PlanNode {
+            name: name.into(),
+            url: Some(format!("git@github.com:org/{name}.git")),
+            target_path: format!("/ws/{name}").into(),
+            depth_level: 0,
+            is_meta: name == "platform",
+            action,
+            children,
+        };
+        let plan = ClonePlan {
+            root: "/ws".into(),
+            projects: vec![
+                node(
+                    "platform",
+                    PlanAction::Present,
+                    vec![node("lib", PlanAction::Clone, vec![])],
+                ),
+                node("web", PlanAction::Clone, vec![]),
+            ],
+        };
+        assert_eq!(
+            plan.render(&Theme::plain()),
+            "/ws\n├── platform (meta)  present\n│   └── lib  clone\n└── web  clone\n2 to clone\n"
+        );
+    }
+
+    #[test]
+    fn backup_report_lists_work_at_risk() {
+        use crate::verify::RepoRisk;
+
+        let mut report = BackupReport {
+            scanned: 3,
+            at_risk: Vec::new(),
+        };
+        assert!(report.render(&Theme::plain()).contains("safe to wipe"));
+        report.at_risk.push(RepoRisk {
+            name: "api".into(),
+            worktree: Some("feat".into()),
+            path: "/wt/feat/api".into(),
+            unpushed: vec![("feat".into(), 2)],
+            untracked: 1,
+            ..Default::default()
+        });
+        let out = report.render(&Theme::plain());
+        assert!(out.starts_with("1 of 3 repos have work that isn't pushed:\n"));
+        assert!(out.contains("api   feat      feat: 2 unpushed, 1 untracked\n"));
+    }
+}
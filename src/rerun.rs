@@ -0,0 +1,229 @@
+//! Idempotent re-run detection for batch operations.
+//!
+//! After a successful run, a fingerprint of the operation plan (operation
+//! name, parameters, `.meta` config, and each repo's HEAD and working-tree
+//! state) is recorded in `~/.meta/rerun.json`. A later invocation with an
+//! identical fingerprint can short-circuit with a "nothing to do" result
+//! instead of re-fetching everything — useful for frequent CI runs.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::worktree::helpers::read_meta_config_value;
+
+/// Recorded fingerprints keyed by `<operation>@<meta_dir>`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RerunStoreData {
+    pub runs: HashMap<String, RerunEntry>,
+}
+
+/// Fingerprint of the last successful run of an operation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RerunEntry {
+    pub fingerprint: String,
+    pub recorded_at: String,
+}
+
+/// The inputs that determine what a batch operation will do.
+#[derive(Debug, Clone)]
+pub struct OperationPlan {
+    operation: String,
+    meta_dir: PathBuf,
+    params: Vec<String>,
+    repos: Vec<PathBuf>,
+}
+
+impl OperationPlan {
+    pub fn new(operation: &str, meta_dir: &Path) -> Self {
+        OperationPlan {
+            operation: operation.to_string(),
+            meta_dir: meta_dir.to_path_buf(),
+            params: Vec::new(),
+            repos: Vec::new(),
+        }
+    }
+
+    /// Add a parameter that changes the outcome (flags, target refs, ...).
+    pub fn param(mut self, value: impl Into<String>) -> Self {
+        self.params.push(value.into());
+        self
+    }
+
+    /// Add a repo whose state is part of the plan.
+    pub fn repo(mut self, path: impl Into<PathBuf>) -> Self {
+        self.repos.push(path.into());
+        self
+    }
+
+    fn key(&self) -> String {
+        format!("{}@{}", self.operation, self.meta_dir.display())
+    }
+
+    /// Compute the plan fingerprint from the current on-disk state.
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = Fnv64::new();
+        hasher.write_str(&self.operation);
+        for param in &self.params {
+            hasher.write_str(param);
+        }
+        if let Some(config) = read_meta_config_value(&self.meta_dir) {
+            hasher.write_str(&config.to_string());
+        }
+
+        let mut repos = self.repos.clone();
+        repos.sort();
+        for repo in &repos {
+            hasher.write_str(&repo.to_string_lossy());
+            hasher.write_str(&git_output(repo, &["rev-parse", "HEAD"]));
+            hasher.write_str(&git_output(repo, &["status", "--porcelain"]));
+        }
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+/// Run a git command, returning stdout (empty on failure).
+fn git_output(repo_path: &Path, args: &[&str]) -> String {
+    crate::git_runner::output(
+        Command::new("git")
+            .args(args)
+            .current_dir(repo_path)
+            .stderr(Stdio::null()),
+    )
+    .ok()
+    .filter(|o| o.status.success())
+    .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+    .unwrap_or_default()
+}
+
+/// 64-bit FNV-1a hash. Stable across Rust versions, unlike `DefaultHasher`.
+pub(crate) struct Fnv64(u64);
+
+impl Fnv64 {
+    pub(crate) fn new() -> Self {
+        Fnv64(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(crate) fn write_str(&mut self, s: &str) {
+        for byte in s.bytes().chain(std::iter::once(0)) {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+fn store_paths() -> (PathBuf, PathBuf) {
+    let data_path = meta_core::data_dir::data_file("rerun");
+    let lock_path = data_path.with_extension("lock");
+    (data_path, lock_path)
+}
+
+/// Check whether `plan` matches the last successful run exactly.
+pub fn is_unchanged(plan: &OperationPlan) -> Result<bool> {
+    let (data_path, _) = store_paths();
+    let data: RerunStoreData = meta_core::store::read(&data_path)?;
+    Ok(data
+        .runs
+        .get(&plan.key())
+        .is_some_and(|entry| entry.fingerprint == plan.fingerprint()))
+}
+
+/// Record `plan` as successfully completed.
+///
+/// Call after the operation finishes, so the fingerprint reflects the
+/// post-run repo state.
+pub fn record_success(plan: &OperationPlan) -> Result<()> {
+    crate::read_only::check("write the rerun cache")?;
+    meta_core::data_dir::ensure_meta_dir()?;
+    let (data_path, lock_path) = store_paths();
+    let entry = RerunEntry {
+        fingerprint: plan.fingerprint(),
+        recorded_at: chrono::Utc::now().to_rfc3339(),
+    };
+    let key = plan.key();
+
+    meta_core::store::update::<RerunStoreData, _>(&data_path, &lock_path, |store| {
+        store.runs.insert(key, entry);
+    })
+}
+
+/// Forget the recorded run for `plan`, forcing the next run to do full work.
+pub fn invalidate(plan: &OperationPlan) -> Result<()> {
+    crate::read_only::check("write the rerun cache")?;
+    let (data_path, lock_path) = store_paths();
+    if !data_path.exists() {
+        return Ok(());
+    }
+    let key = plan.key();
+
+    meta_core::store::update::<RerunStoreData, _>(&data_path, &lock_path, |store| {
+        store.runs.remove(&key);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fingerprint_is_stable_for_same_inputs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let a = OperationPlan::new("update", tmp.path()).param("--rebase");
+        let b = OperationPlan::new("update", tmp.path()).param("--rebase");
+        assert_eq!(a.fingerprint(), b.fingerprint());
+    }
+
+    #[test]
+    fn fingerprint_changes_with_params_and_config() {
+        let tmp = tempfile::tempdir().unwrap();
+        let base = OperationPlan::new("update", tmp.path()).fingerprint();
+        let with_param = OperationPlan::new("update", tmp.path())
+            .param("--rebase")
+            .fingerprint();
+        assert_ne!(base, with_param);
+
+        std::fs::write(tmp.path().join(".meta"), r#"{"projects": {}}"#).unwrap();
+        assert_ne!(base, OperationPlan::new("update", tmp.path()).fingerprint());
+    }
+
+    #[test]
+    fn fingerprint_ignores_repo_order() {
+        let tmp = tempfile::tempdir().unwrap();
+        let a = OperationPlan::new("update", tmp.path())
+            .repo(tmp.path().join("a"))
+            .repo(tmp.path().join("b"));
+        let b = OperationPlan::new("update", tmp.path())
+            .repo(tmp.path().join("b"))
+            .repo(tmp.path().join("a"));
+        assert_eq!(a.fingerprint(), b.fingerprint());
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn record_then_detect_unchanged() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store_dir = tmp.path().join("meta-store");
+        std::fs::create_dir_all(&store_dir).unwrap();
+        std::env::set_var("META_DATA_DIR", &store_dir);
+
+        let plan = OperationPlan::new("update", tmp.path());
+        assert!(!is_unchanged(&plan).unwrap());
+
+        record_success(&plan).unwrap();
+        assert!(is_unchanged(&plan).unwrap());
+
+        // A different plan for the same operation is not a re-run
+        assert!(!is_unchanged(&plan.clone().param("--force")).unwrap());
+
+        invalidate(&plan).unwrap();
+        assert!(!is_unchanged(&plan).unwrap());
+
+        std::env::remove_var("META_DATA_DIR");
+    }
+}
@@ -0,0 +1,262 @@
+//! Path confinement for filesystem writes.
+//!
+//! A [`Sandbox`] lists the directories the library may create or delete
+//! things under: the workspace itself, the worktrees root, and the meta data
+//! dir. Paths are resolved through symlinks and `..` before the prefix check,
+//! so a malicious `.meta` (e.g. `worktrees_dir: /` or a snapshot name of
+//! `../../x`) can't steer a write outside the workspace.
+//!
+//! Only trusted sources contribute roots: the caller, `META_WORKTREES`, and
+//! `META_DATA_DIR`. Values read from `.meta` never widen the sandbox; project
+//! paths from `.meta` are checked with [`validate_project_path`].
+//!
+//! Worktree creation, removal, rename and rebalancing, snapshots, migrate
+//! import, and the bulk edits check their targets. [`move_to`] is the one
+//! exception: its destination comes from the caller, who may pick any
+//! directory. Setup commands and hooks are run with a checked repo as their
+//! working directory, but what they write is up to them.
+//!
+//! [`move_to`]: crate::worktree::placement::move_to
+
+use std::path::{Component, Path, PathBuf};
+
+/// A write was attempted outside every sandbox root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SandboxViolation {
+    pub operation: String,
+    pub path: PathBuf,
+}
+
+impl std::fmt::Display for SandboxViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Refusing to {}: {} is outside the workspace sandbox",
+            self.operation,
+            self.path.display()
+        )
+    }
+}
+
+impl std::error::Error for SandboxViolation {}
+
+/// Set of directories writes are confined to.
+#[derive(Debug, Clone, Default)]
+pub struct Sandbox {
+    roots: Vec<PathBuf>,
+}
+
+impl Sandbox {
+    pub fn new<I, P>(roots: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        roots.into_iter().fold(Sandbox::default(), Sandbox::allow)
+    }
+
+    /// Sandbox for a workspace rooted at `meta_dir`.
+    ///
+    /// Allows the workspace, every root in `META_WORKTREES` if set, and the
+    /// meta data dir.
+    pub fn for_workspace(meta_dir: &Path) -> Self {
+        let mut sandbox = Sandbox::new([meta_dir]).allow(meta_core::data_dir::meta_dir());
+        if let Some(env_roots) = std::env::var_os("META_WORKTREES") {
+            for root in std::env::split_paths(&env_roots).filter(|p| !p.as_os_str().is_empty()) {
+                sandbox = sandbox.allow(root);
+            }
+        }
+        sandbox
+    }
+
+    /// Add another permitted root.
+    pub fn allow(mut self, root: impl AsRef<Path>) -> Self {
+        self.roots.push(resolve(root.as_ref()));
+        self
+    }
+
+    /// The resolved roots.
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// Whether `path` resolves to somewhere under a root.
+    pub fn contains(&self, path: &Path) -> bool {
+        let resolved = resolve(path);
+        self.roots.iter().any(|root| resolved.starts_with(root))
+    }
+
+    /// Check that `operation` may write to `path`, returning the resolved path.
+    pub fn check(&self, path: &Path, operation: &str) -> Result<PathBuf, SandboxViolation> {
+        let resolved = resolve(path);
+        if self.roots.iter().any(|root| resolved.starts_with(root)) {
+            Ok(resolved)
+        } else {
+            Err(SandboxViolation {
+                operation: operation.to_string(),
+                path: resolved,
+            })
+        }
+    }
+}
+
+/// Validate a project `path` from the `.meta` in `base_dir` and return the
+/// directory it refers to.
+///
+/// Rejects absolute paths, any `..` component, and paths that resolve
+/// outside `base_dir` through a symlink.
+pub fn validate_project_path(base_dir: &Path, name: &str, path: &str) -> anyhow::Result<PathBuf> {
+    let relative = Path::new(path);
+    let reason = if path.is_empty() {
+        Some("path is empty")
+    } else if relative.has_root() || relative.is_absolute() {
+        Some("absolute paths are not allowed")
+    } else if relative
+        .components()
+        .any(|c| matches!(c, Component::ParentDir | Component::Prefix(_)))
+    {
+        Some("'..' components are not allowed")
+    } else if !Sandbox::new([base_dir]).contains(&base_dir.join(relative)) {
+        Some("resolves outside the workspace through a symlink")
+    } else {
+        None
+    };
+    match reason {
+        Some(reason) => anyhow::bail!(
+            "Invalid path '{}' for project '{}' in {}: {}",
+            path,
+            name,
+            base_dir.display(),
+            reason
+        ),
+        None => Ok(base_dir.join(relative)),
+    }
+}
+
+/// Resolve `path` to an absolute path without `.`/`..` components.
+///
+/// The deepest existing ancestor is canonicalized (following symlinks); the
+/// not-yet-existing remainder is normalized lexically.
+pub fn resolve(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+    let normalized = normalize(&absolute);
+
+    let mut existing = normalized.as_path();
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return rest
+                .iter()
+                .rev()
+                .fold(canonical, |acc: PathBuf, part| acc.join(part));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return normalized,
+        }
+    }
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_paths_under_roots() {
+        let tmp = tempfile::tempdir().unwrap();
+        let sandbox = Sandbox::new([tmp.path()]);
+        assert!(sandbox.contains(&tmp.path().join(".worktrees/feature")));
+        assert!(sandbox.contains(tmp.path()));
+        let resolved = sandbox
+            .check(&tmp.path().join("a/./b"), "create worktree")
+            .unwrap();
+        assert_eq!(resolved, tmp.path().canonicalize().unwrap().join("a/b"));
+    }
+
+    #[test]
+    fn rejects_parent_dir_escapes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ws = tmp.path().join("ws");
+        std::fs::create_dir_all(&ws).unwrap();
+        let sandbox = Sandbox::new([&ws]);
+        assert!(!sandbox.contains(&ws.join("../outside")));
+        assert!(!sandbox.contains(&ws.join("x/../../../..")));
+        assert!(!sandbox.contains(Path::new("/")));
+
+        let err = sandbox
+            .check(&ws.join("../outside"), "create worktree")
+            .unwrap_err();
+        assert_eq!(err.path, tmp.path().canonicalize().unwrap().join("outside"));
+        assert!(err.to_string().contains("outside the workspace sandbox"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlink_escapes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ws = tmp.path().join("ws");
+        let outside = tmp.path().join("outside");
+        std::fs::create_dir_all(&ws).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, ws.join("link")).unwrap();
+
+        let sandbox = Sandbox::new([&ws]);
+        assert!(!sandbox.contains(&ws.join("link/new-dir")));
+    }
+
+    #[test]
+    fn project_paths() {
+        let tmp = tempfile::tempdir().unwrap();
+        let base = tmp.path();
+        assert_eq!(
+            validate_project_path(base, "api", "libs/api").unwrap(),
+            base.join("libs/api")
+        );
+        assert!(validate_project_path(base, "root", ".").is_ok());
+        for bad in ["", "/etc", "../sibling", "libs/../../x"] {
+            assert!(validate_project_path(base, "evil", bad).is_err(), "{bad}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn project_path_through_symlink_is_rejected() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ws = tmp.path().join("ws");
+        std::fs::create_dir_all(&ws).unwrap();
+        std::os::unix::fs::symlink(tmp.path(), ws.join("up")).unwrap();
+        let err = validate_project_path(&ws, "evil", "up/target").unwrap_err();
+        assert!(err.to_string().contains("symlink"), "{err}");
+    }
+
+    #[test]
+    fn sibling_with_common_prefix_is_outside() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(tmp.path().join("ws")).unwrap();
+        let sandbox = Sandbox::new([tmp.path().join("ws")]);
+        assert!(!sandbox.contains(&tmp.path().join("ws-evil")));
+    }
+}
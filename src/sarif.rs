@@ -0,0 +1,302 @@
+//! SARIF 2.1.0 output for scanner findings.
+//!
+//! Scanners (secret scanning, large-object detection, policy checks) report
+//! [`Finding`]s; [`to_sarif`] turns them into a log that GitHub code scanning
+//! and other SARIF consumers accept. Each repo becomes its own `uriBaseId`
+//! so locations stay relative to the repo they were found in; findings about
+//! a whole repo point at the repo's root. Results carry a fingerprint that
+//! includes the repo, so the same finding in two repos isn't merged.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+pub const SARIF_VERSION: &str = "2.1.0";
+pub const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Severity of a finding, mapped to SARIF `level`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Note,
+    Warning,
+    Error,
+}
+
+/// A single issue reported by a scanner.
+#[derive(Debug, Clone)]
+pub struct Finding {
+    /// Stable rule identifier, e.g. `secret/aws-access-key`.
+    pub rule_id: String,
+    pub level: Level,
+    pub message: String,
+    /// Repo alias from the `.meta` config.
+    pub repo: String,
+    /// Path relative to the repo root, if the finding is file-specific.
+    pub file: Option<String>,
+    pub line: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SarifLog {
+    #[serde(rename = "$schema")]
+    pub schema: &'static str,
+    pub version: &'static str,
+    pub runs: Vec<SarifRun>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifRun {
+    pub tool: SarifTool,
+    pub results: Vec<SarifResult>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub original_uri_base_ids: BTreeMap<String, SarifArtifactLocation>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SarifTool {
+    pub driver: SarifDriver,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifDriver {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    pub rules: Vec<SarifRule>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SarifRule {
+    pub id: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifResult {
+    pub rule_id: String,
+    pub level: Level,
+    pub message: SarifMessage,
+    pub locations: Vec<SarifLocation>,
+    pub partial_fingerprints: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SarifMessage {
+    pub text: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifLocation {
+    pub physical_location: SarifPhysicalLocation,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifPhysicalLocation {
+    pub artifact_location: SarifArtifactLocation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<SarifRegion>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifArtifactLocation {
+    pub uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uri_base_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifRegion {
+    pub start_line: u32,
+}
+
+/// Key of the fingerprint in `partialFingerprints`.
+pub const FINGERPRINT_KEY: &str = "metaFinding/v1";
+
+/// `uriBaseId` for each repo alias in `findings`: the alias uppercased with
+/// `/` as `_` (SARIF ids may not contain `/`), numbered where two aliases
+/// would get the same id.
+fn base_ids(findings: &[Finding]) -> BTreeMap<&str, String> {
+    let mut repos: Vec<&str> = findings.iter().map(|f| f.repo.as_str()).collect();
+    repos.sort_unstable();
+    repos.dedup();
+    let mut taken = std::collections::BTreeSet::new();
+    let mut ids = BTreeMap::new();
+    for repo in repos {
+        let base = if repo == "." {
+            "ROOT".to_string()
+        } else {
+            repo.replace('/', "_").to_uppercase()
+        };
+        let mut id = base.clone();
+        let mut n = 1;
+        while !taken.insert(id.clone()) {
+            n += 1;
+            id = format!("{base}_{n}");
+        }
+        ids.insert(repo, id);
+    }
+    ids
+}
+
+/// Build a single-run SARIF log for `tool` from `findings`.
+///
+/// Repo aliases are mapped to base ids whose URIs are the alias paths
+/// relative to the workspace root, so uploads resolve against the meta repo.
+pub fn to_sarif(tool: &str, version: Option<&str>, findings: &[Finding]) -> SarifLog {
+    let mut rule_ids: Vec<String> = findings.iter().map(|f| f.rule_id.clone()).collect();
+    rule_ids.sort();
+    rule_ids.dedup();
+
+    let ids = base_ids(findings);
+    let mut bases = BTreeMap::new();
+    let results = findings
+        .iter()
+        .map(|f| {
+            let id = ids[f.repo.as_str()].clone();
+            let uri = if f.repo == "." {
+                String::new()
+            } else {
+                format!("{}/", f.repo.trim_end_matches('/'))
+            };
+            bases.entry(id.clone()).or_insert(SarifArtifactLocation {
+                uri,
+                uri_base_id: Some("%SRCROOT%".to_string()),
+            });
+            let location = SarifLocation {
+                physical_location: SarifPhysicalLocation {
+                    artifact_location: SarifArtifactLocation {
+                        // The repo's root for findings about the whole repo
+                        uri: f.file.clone().unwrap_or_else(|| ".".to_string()),
+                        uri_base_id: Some(id),
+                    },
+                    region: f.line.map(|start_line| SarifRegion { start_line }),
+                },
+            };
+            let message = if f.file.is_none() {
+                format!("{}: {}", f.repo, f.message)
+            } else {
+                f.message.clone()
+            };
+            let fingerprint = format!(
+                "{}:{}:{}:{}",
+                f.repo,
+                f.rule_id,
+                f.file.as_deref().unwrap_or(""),
+                f.line.map(|l| l.to_string()).unwrap_or_default()
+            );
+            SarifResult {
+                rule_id: f.rule_id.clone(),
+                level: f.level,
+                message: SarifMessage { text: message },
+                locations: vec![location],
+                partial_fingerprints: BTreeMap::from([(FINGERPRINT_KEY.to_string(), fingerprint)]),
+            }
+        })
+        .collect();
+
+    SarifLog {
+        schema: SARIF_SCHEMA,
+        version: SARIF_VERSION,
+        runs: vec![SarifRun {
+            tool: SarifTool {
+                driver: SarifDriver {
+                    name: tool.to_string(),
+                    version: version.map(str::to_string),
+                    rules: rule_ids.into_iter().map(|id| SarifRule { id }).collect(),
+                },
+            },
+            results,
+            original_uri_base_ids: bases,
+        }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finding(repo: &str, file: Option<&str>) -> Finding {
+        Finding {
+            rule_id: "secret/aws-access-key".to_string(),
+            level: Level::Error,
+            message: "AWS access key committed".to_string(),
+            repo: repo.to_string(),
+            file: file.map(str::to_string),
+            line: file.map(|_| 12),
+        }
+    }
+
+    #[test]
+    fn log_has_schema_and_dedup_rules() {
+        let log = to_sarif(
+            "meta-secrets",
+            Some("1.0.0"),
+            &[finding("api", Some("a.env")), finding("web", Some("b.env"))],
+        );
+        let json = serde_json::to_value(&log).unwrap();
+        assert_eq!(json["version"], "2.1.0");
+        assert_eq!(json["$schema"], SARIF_SCHEMA);
+        let rules = json["runs"][0]["tool"]["driver"]["rules"]
+            .as_array()
+            .unwrap();
+        assert_eq!(rules.len(), 1);
+    }
+
+    #[test]
+    fn locations_are_relative_to_repo_base() {
+        let log = to_sarif("scan", None, &[finding("libs/core", Some("src/key.rs"))]);
+        let json = serde_json::to_value(&log).unwrap();
+        let run = &json["runs"][0];
+        let loc = &run["results"][0]["locations"][0]["physicalLocation"];
+        assert_eq!(loc["artifactLocation"]["uri"], "src/key.rs");
+        assert_eq!(loc["artifactLocation"]["uriBaseId"], "LIBS_CORE");
+        assert_eq!(loc["region"]["startLine"], 12);
+        assert_eq!(run["originalUriBaseIds"]["LIBS_CORE"]["uri"], "libs/core/");
+        assert_eq!(run["results"][0]["level"], "error");
+    }
+
+    #[test]
+    fn repo_level_findings_point_at_the_repo_root() {
+        let log = to_sarif("policy", None, &[finding("api", None)]);
+        let json = serde_json::to_value(&log).unwrap();
+        let result = &json["runs"][0]["results"][0];
+        let artifact = &result["locations"][0]["physicalLocation"]["artifactLocation"];
+        assert_eq!(artifact["uri"], ".");
+        assert_eq!(artifact["uriBaseId"], "API");
+        assert_eq!(result["message"]["text"], "api: AWS access key committed");
+    }
+
+    #[test]
+    fn colliding_aliases_get_distinct_bases_and_fingerprints() {
+        let log = to_sarif(
+            "scan",
+            None,
+            &[
+                finding("libs/core", Some("a.env")),
+                finding("libs_core", Some("a.env")),
+            ],
+        );
+        let json = serde_json::to_value(&log).unwrap();
+        let run = &json["runs"][0];
+        let base = |i: usize| {
+            run["results"][i]["locations"][0]["physicalLocation"]["artifactLocation"]["uriBaseId"]
+                .clone()
+        };
+        assert_eq!(base(0), "LIBS_CORE");
+        assert_eq!(base(1), "LIBS_CORE_2");
+        assert_eq!(
+            run["originalUriBaseIds"]["LIBS_CORE_2"]["uri"],
+            "libs_core/"
+        );
+        let fingerprint =
+            |i: usize| run["results"][i]["partialFingerprints"][FINGERPRINT_KEY].clone();
+        assert_eq!(fingerprint(0), "libs/core:secret/aws-access-key:a.env:12");
+        assert_ne!(fingerprint(0), fingerprint(1));
+    }
+}
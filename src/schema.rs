@@ -0,0 +1,709 @@
+//! JSON Schema for the `--json` output types.
+//!
+//! [`export`] describes every worktree output struct (`CreateOutput`,
+//! `ListOutput`, ...) as a JSON Schema (draft 2020-12) document, with one
+//! entry per type under `$defs`, so downstream tools can validate the CLI's
+//! output or generate typed clients from it. The schemas are written out by
+//! hand; the tests check them against serialized values so they can't drift
+//! from the structs.
+//!
+//! Top-level outputs are printed with [`json_output`], which adds a
+//! `schema_version`: the major version of this schema. Objects don't forbid
+//! unknown fields, so adding an optional field doesn't bump it; renaming or
+//! removing one does, together with a downgrade step so scripts can keep
+//! asking for the version they were written against via
+//! [`SCHEMA_VERSION_ENV`]. The `api` module versions its responses
+//! separately.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+/// JSON Schema dialect of [`export`].
+pub const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Major version of the output schema.
+///
+/// Version 1 had no `schema_version` field; version 2 added it, which
+/// validators of version 1's closed schema saw as breaking.
+pub const OUTPUT_SCHEMA_VERSION: u32 = 2;
+
+/// Oldest schema version [`json_output`] can still produce.
+pub const OLDEST_OUTPUT_SCHEMA_VERSION: u32 = 1;
+
+/// Set to a schema version to get `--json` output in that version.
+pub const SCHEMA_VERSION_ENV: &str = "META_GIT_SCHEMA_VERSION";
+
+/// Steps from each version to the one before it, indexed from version 2.
+const DOWNGRADES: &[fn(&mut Map<String, Value>)] = &[
+    // 2 -> 1: no version field
+    |output| {
+        output.remove("schema_version");
+    },
+];
+
+/// An output with its schema version, as serialized for `--json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Versioned<T> {
+    schema_version: u32,
+    #[serde(flatten)]
+    output: T,
+}
+
+impl<T> Versioned<T> {
+    fn new(output: T) -> Self {
+        Versioned {
+            schema_version: OUTPUT_SCHEMA_VERSION,
+            output,
+        }
+    }
+}
+
+/// `output` (a top-level output struct) as `--json` prints it: pretty JSON
+/// in the schema version requested via [`SCHEMA_VERSION_ENV`], the current
+/// one by default.
+pub fn json_output<T: Serialize>(output: &T) -> Result<String> {
+    let value = versioned_output(output, requested_version()?)?;
+    Ok(serde_json::to_string_pretty(&value)?)
+}
+
+/// Schema version requested via [`SCHEMA_VERSION_ENV`], if any.
+fn requested_version() -> Result<Option<u32>> {
+    match std::env::var(SCHEMA_VERSION_ENV) {
+        Ok(v) if !v.trim().is_empty() => match v.trim().parse() {
+            Ok(version) => Ok(Some(version)),
+            Err(_) => anyhow::bail!("Invalid {SCHEMA_VERSION_ENV} '{v}': expected a number"),
+        },
+        _ => Ok(None),
+    }
+}
+
+/// `output` as JSON in schema `version`, the current one if `None`.
+///
+/// Errors on versions newer than [`OUTPUT_SCHEMA_VERSION`] or older than
+/// [`OLDEST_OUTPUT_SCHEMA_VERSION`].
+fn versioned_output<T: Serialize>(output: &T, version: Option<u32>) -> Result<Value> {
+    let version = version.unwrap_or(OUTPUT_SCHEMA_VERSION);
+    if !(OLDEST_OUTPUT_SCHEMA_VERSION..=OUTPUT_SCHEMA_VERSION).contains(&version) {
+        anyhow::bail!(
+            "Unsupported output schema version {version}; supported: {OLDEST_OUTPUT_SCHEMA_VERSION} to {OUTPUT_SCHEMA_VERSION}"
+        );
+    }
+    let mut value = serde_json::to_value(Versioned::new(output))?;
+    let Some(object) = value.as_object_mut() else {
+        anyhow::bail!("Output is not a JSON object");
+    };
+    for from in (version + 1..=OUTPUT_SCHEMA_VERSION).rev() {
+        DOWNGRADES[(from - 2) as usize](object);
+    }
+    Ok(value)
+}
+
+/// Top-level output types, each a key of `$defs`.
+pub const OUTPUT_TYPES: &[&str] = &[
+    "AddOutput",
+    "CreateOutput",
+    "DestroyOutput",
+    "DiffOutput",
+    "ListOutput",
+    "PruneOutput",
+    "StatusOutput",
+    "UnpushedOutput",
+];
+
+/// Schema document for all output types, in the current schema version.
+pub fn export() -> Value {
+    let mut defs: Map<String, Value> = definitions()
+        .into_iter()
+        .map(|(name, schema)| (name.to_string(), schema))
+        .collect();
+    for name in OUTPUT_TYPES {
+        let def = &mut defs[*name];
+        def["properties"]["schema_version"] =
+            json!({ "type": "integer", "const": OUTPUT_SCHEMA_VERSION });
+        if let Some(required) = def["required"].as_array_mut() {
+            required.insert(0, json!("schema_version"));
+        }
+    }
+    json!({
+        "$schema": SCHEMA_DIALECT,
+        "title": "meta git JSON output",
+        "version": OUTPUT_SCHEMA_VERSION,
+        "anyOf": OUTPUT_TYPES.iter().map(|name| reference(name)).collect::<Vec<_>>(),
+        "$defs": defs,
+    })
+}
+
+fn definitions() -> Vec<(&'static str, Value)> {
+    vec![
+        (
+            "CreateOutput",
+            object(
+                &[
+                    ("name", string()),
+                    ("root", string()),
+                    ("repos", array(reference("CreateRepoEntry"))),
+                ],
+                &[
+                    ("ephemeral", boolean()),
+                    ("ttl_seconds", unsigned()),
+                    ("custom", string_map()),
+                    ("change_group", string()),
+                    ("disabled", array(string())),
+                    ("expansions", array(reference("SpecExpansion"))),
+                ],
+            ),
+        ),
+        (
+            "CreateRepoEntry",
+            object(
+                &[
+                    ("alias", string()),
+                    ("path", string()),
+                    ("branch", string()),
+                    ("created_branch", boolean()),
+                ],
+                &[("setup", array(reference("SetupStepResult")))],
+            ),
+        ),
+        (
+            "SetupStepResult",
+            object(
+                &[
+                    ("command", string()),
+                    ("success", boolean()),
+                    ("output", string()),
+                    ("duration_ms", unsigned()),
+                ],
+                &[("exit_code", integer()), ("timed_out", boolean())],
+            ),
+        ),
+        (
+            "SpecExpansion",
+            object(&[("spec", string()), ("repos", array(string()))], &[]),
+        ),
+        (
+            "AddOutput",
+            object(
+                &[
+                    ("name", string()),
+                    ("repos", array(reference("CreateRepoEntry"))),
+                ],
+                &[],
+            ),
+        ),
+        (
+            "DestroyOutput",
+            object(
+                &[
+                    ("name", string()),
+                    ("path", string()),
+                    ("repos_removed", unsigned()),
+                ],
+                &[],
+            ),
+        ),
+        (
+            "ListOutput",
+            object(&[("worktrees", array(reference("ListEntry")))], &[]),
+        ),
+        (
+            "ListEntry",
+            object(
+                &[
+                    ("name", string()),
+                    ("root", string()),
+                    ("has_meta_root", boolean()),
+                    ("repos", array(reference("ListRepoEntry"))),
+                ],
+                &[
+                    ("ephemeral", boolean()),
+                    ("ttl_remaining_seconds", integer()),
+                    ("protected", boolean()),
+                    ("custom", string_map()),
+                    ("disk_usage_bytes", unsigned()),
+                    ("age_seconds", integer()),
+                    ("last_activity", string()),
+                    ("pull_requests", array(reference("ListPrEntry"))),
+                ],
+            ),
+        ),
+        (
+            "ListRepoEntry",
+            object(
+                &[
+                    ("alias", string()),
+                    ("branch", string()),
+                    ("dirty", boolean()),
+                ],
+                &[("base_moved_by", unsigned())],
+            ),
+        ),
+        (
+            "ListPrEntry",
+            object(
+                &[
+                    ("alias", string()),
+                    ("number", unsigned()),
+                    ("state", string()),
+                    ("title", string()),
+                    ("url", string()),
+                ],
+                &[],
+            ),
+        ),
+        (
+            "StatusOutput",
+            object(
+                &[
+                    ("name", string()),
+                    ("repos", array(reference("StatusRepoEntry"))),
+                ],
+                &[("disabled", array(string()))],
+            ),
+        ),
+        (
+            "StatusRepoEntry",
+            object(
+                &[
+                    ("alias", string()),
+                    ("path", string()),
+                    ("branch", string()),
+                    ("dirty", boolean()),
+                    ("modified_count", unsigned()),
+                    ("untracked_count", unsigned()),
+                    ("ahead", unsigned()),
+                    ("behind", unsigned()),
+                ],
+                &[
+                    ("modified_files", array(string())),
+                    ("renames", array(reference("RenameEntry"))),
+                    ("untracked_files", array(string())),
+                    ("last_fetched", string()),
+                ],
+            ),
+        ),
+        (
+            "DiffOutput",
+            object(
+                &[
+                    ("name", string()),
+                    ("base", string()),
+                    ("repos", array(reference("DiffRepoEntry"))),
+                    ("totals", reference("DiffTotals")),
+                ],
+                &[],
+            ),
+        ),
+        (
+            "DiffRepoEntry",
+            object(
+                &[
+                    ("alias", string()),
+                    ("base_ref", string()),
+                    ("files_changed", unsigned()),
+                    ("insertions", unsigned()),
+                    ("deletions", unsigned()),
+                ],
+                &[
+                    ("files", array(string())),
+                    ("renames", array(reference("RenameEntry"))),
+                ],
+            ),
+        ),
+        (
+            "RenameEntry",
+            object(
+                &[
+                    ("from", string()),
+                    ("to", string()),
+                    ("similarity", unsigned()),
+                ],
+                &[("copy", boolean())],
+            ),
+        ),
+        (
+            "DiffTotals",
+            object(
+                &[
+                    ("repos_changed", unsigned()),
+                    ("files_changed", unsigned()),
+                    ("insertions", unsigned()),
+                    ("deletions", unsigned()),
+                ],
+                &[],
+            ),
+        ),
+        (
+            "PruneOutput",
+            object(
+                &[
+                    ("removed", array(reference("PruneEntry"))),
+                    ("dry_run", boolean()),
+                ],
+                &[("refused", array(reference("PruneEntry")))],
+            ),
+        ),
+        (
+            "PruneEntry",
+            object(
+                &[("name", string()), ("path", string()), ("reason", string())],
+                &[("age_seconds", unsigned())],
+            ),
+        ),
+        (
+            "UnpushedOutput",
+            object(
+                &[
+                    ("name", string()),
+                    ("root", string()),
+                    ("repos", array(reference("UnpushedRepoEntry"))),
+                ],
+                &[],
+            ),
+        ),
+        (
+            "UnpushedRepoEntry",
+            object(
+                &[
+                    ("alias", string()),
+                    ("branches", array(reference("UnpushedBranch"))),
+                ],
+                &[],
+            ),
+        ),
+        (
+            "UnpushedBranch",
+            object(
+                &[
+                    ("name", string()),
+                    ("commits", array(reference("UnpushedCommit"))),
+                ],
+                &[("created", boolean())],
+            ),
+        ),
+        (
+            "UnpushedCommit",
+            object(&[("sha", string()), ("subject", string())], &[]),
+        ),
+    ]
+}
+
+/// An object with `required` fields (always serialized) and `optional` ones
+/// (left out when empty or unset). Other fields are allowed, so adding one
+/// stays compatible.
+fn object(required: &[(&str, Value)], optional: &[(&str, Value)]) -> Value {
+    let properties: Map<String, Value> = required
+        .iter()
+        .chain(optional)
+        .map(|(name, schema)| (name.to_string(), schema.clone()))
+        .collect();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+    })
+}
+
+fn reference(name: &str) -> Value {
+    json!({ "$ref": format!("#/$defs/{name}") })
+}
+
+fn array(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+fn string_map() -> Value {
+    json!({ "type": "object", "additionalProperties": string() })
+}
+
+fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+fn boolean() -> Value {
+    json!({ "type": "boolean" })
+}
+
+fn integer() -> Value {
+    json!({ "type": "integer" })
+}
+
+fn unsigned() -> Value {
+    json!({ "type": "integer", "minimum": 0 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worktree::types::*;
+    use std::collections::HashMap;
+
+    /// Check `value` against the subset of JSON Schema that [`export`] uses.
+    fn check(value: &Value, schema: &Value, defs: &Value, path: &str) {
+        if let Some(target) = schema.get("$ref").and_then(Value::as_str) {
+            let name = target.trim_start_matches("#/$defs/");
+            return check(value, &defs[name], defs, path);
+        }
+        match schema["type"].as_str().unwrap() {
+            "string" => assert!(value.is_string(), "{path}: expected string"),
+            "boolean" => assert!(value.is_boolean(), "{path}: expected boolean"),
+            "integer" if schema.get("minimum").is_some() => {
+                assert!(value.is_u64(), "{path}: expected unsigned integer")
+            }
+            "integer" => assert!(value.is_i64(), "{path}: expected integer"),
+            "array" => {
+                for (i, item) in value.as_array().unwrap().iter().enumerate() {
+                    check(item, &schema["items"], defs, &format!("{path}[{i}]"));
+                }
+            }
+            "object" => {
+                let object = value.as_object().unwrap();
+                for name in schema["required"].as_array().into_iter().flatten() {
+                    let name = name.as_str().unwrap();
+                    assert!(object.contains_key(name), "{path}: missing {name}");
+                }
+                for (key, field) in object {
+                    // Stricter than the schema: every field the structs
+                    // serialize must be declared, so the two can't drift
+                    let field_schema = match schema.get("properties") {
+                        Some(properties) => properties
+                            .get(key)
+                            .unwrap_or_else(|| panic!("{path}: undeclared field {key}")),
+                        None => &schema["additionalProperties"],
+                    };
+                    check(field, field_schema, defs, &format!("{path}.{key}"));
+                }
+            }
+            other => panic!("{path}: unexpected type {other}"),
+        }
+    }
+
+    /// Check that `output` matches its schema and survives a round trip.
+    fn assert_matches<T>(name: &str, output: &T)
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        let schema = export();
+        let value = versioned_output(output, None).unwrap();
+        check(&value, &schema["$defs"][name], &schema["$defs"], name);
+        let parsed: Versioned<T> = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(parsed.schema_version, OUTPUT_SCHEMA_VERSION);
+        assert_eq!(serde_json::to_value(parsed).unwrap(), value);
+    }
+
+    fn create_entry() -> CreateRepoEntry {
+        CreateRepoEntry {
+            alias: "api".to_string(),
+            path: "/wt/x/api".to_string(),
+            branch: "x".to_string(),
+            created_branch: true,
+            setup: vec![crate::setup::SetupStepResult {
+                command: "npm ci".to_string(),
+                success: false,
+                exit_code: Some(1),
+                timed_out: true,
+                output: "boom".to_string(),
+                duration_ms: 5,
+            }],
+        }
+    }
+
+    #[test]
+    fn every_output_type_matches_its_schema() {
+        let schema = export();
+        for name in OUTPUT_TYPES {
+            assert!(schema["$defs"].get(name).is_some(), "{name} has no schema");
+        }
+
+        assert_matches(
+            "CreateOutput",
+            &CreateOutput {
+                name: "x".to_string(),
+                root: "/wt/x".to_string(),
+                repos: vec![create_entry()],
+                ephemeral: true,
+                ttl_seconds: Some(60),
+                custom: HashMap::from([("k".to_string(), "v".to_string())]),
+                change_group: Some("cg-1".to_string()),
+                disabled: vec!["docs".to_string()],
+                expansions: vec![SpecExpansion {
+                    spec: "@backend".to_string(),
+                    repos: vec!["api".to_string()],
+                }],
+            },
+        );
+        assert_matches(
+            "AddOutput",
+            &AddOutput {
+                name: "x".to_string(),
+                repos: vec![create_entry()],
+            },
+        );
+        assert_matches(
+            "DestroyOutput",
+            &DestroyOutput {
+                name: "x".to_string(),
+                path: "/wt/x".to_string(),
+                repos_removed: 2,
+            },
+        );
+        assert_matches(
+            "ListOutput",
+            &ListOutput {
+                worktrees: vec![ListEntry {
+                    name: "x".to_string(),
+                    root: "/wt/x".to_string(),
+                    has_meta_root: true,
+                    repos: vec![ListRepoEntry {
+                        alias: "api".to_string(),
+                        branch: "x".to_string(),
+                        dirty: false,
+                        base_moved_by: Some(3),
+                    }],
+                    ephemeral: Some(true),
+                    ttl_remaining_seconds: Some(-5),
+                    protected: Some(true),
+                    custom: Some(HashMap::new()),
+                    disk_usage_bytes: Some(1024),
+                    age_seconds: Some(10),
+                    last_activity: Some("2025-01-01T00:00:00Z".to_string()),
+                    pull_requests: vec![ListPrEntry {
+                        alias: "api".to_string(),
+                        number: 42,
+                        state: "OPEN".to_string(),
+                        title: "Fix".to_string(),
+                        url: "https://github.com/org/api/pull/42".to_string(),
+                    }],
+                }],
+            },
+        );
+        assert_matches(
+            "StatusOutput",
+            &StatusOutput {
+                name: "x".to_string(),
+                repos: vec![StatusRepoEntry {
+                    alias: "api".to_string(),
+                    path: "/wt/x/api".to_string(),
+                    branch: "x".to_string(),
+                    dirty: true,
+                    modified_count: 1,
+                    untracked_count: 0,
+                    ahead: 1,
+                    behind: 0,
+                    modified_files: vec!["src/lib.rs".to_string()],
+                    renames: vec![],
+                    untracked_files: vec!["notes.txt".to_string()],
+                    last_fetched: Some("2025-01-01T00:00:00Z".to_string()),
+                }],
+                disabled: vec!["docs".to_string()],
+            },
+        );
+        assert_matches(
+            "DiffOutput",
+            &DiffOutput {
+                name: "x".to_string(),
+                base: "main".to_string(),
+                repos: vec![DiffRepoEntry {
+                    alias: "api".to_string(),
+                    base_ref: "origin/main".to_string(),
+                    files_changed: 1,
+                    insertions: 2,
+                    deletions: 3,
+                    files: vec!["src/lib.rs".to_string()],
+                    renames: vec![RenameEntry {
+                        from: "src/old.rs".to_string(),
+                        to: "src/lib.rs".to_string(),
+                        similarity: 92,
+                        copy: true,
+                    }],
+                }],
+                totals: DiffTotals {
+                    repos_changed: 1,
+                    files_changed: 1,
+                    insertions: 2,
+                    deletions: 3,
+                },
+            },
+        );
+        assert_matches(
+            "PruneOutput",
+            &PruneOutput {
+                removed: vec![PruneEntry {
+                    name: "x".to_string(),
+                    path: "/wt/x".to_string(),
+                    reason: "ttl expired".to_string(),
+                    age_seconds: Some(7200),
+                }],
+                dry_run: true,
+                refused: vec![PruneEntry {
+                    name: "y".to_string(),
+                    path: "/wt/y".to_string(),
+                    reason: "uncommitted changes in api".to_string(),
+                    age_seconds: None,
+                }],
+            },
+        );
+        assert_matches(
+            "UnpushedOutput",
+            &UnpushedOutput {
+                name: "x".to_string(),
+                root: "/wt/x".to_string(),
+                repos: vec![UnpushedRepoEntry {
+                    alias: "api".to_string(),
+                    branches: vec![UnpushedBranch {
+                        name: "x".to_string(),
+                        created: true,
+                        commits: vec![UnpushedCommit {
+                            sha: "0123abcd".to_string(),
+                            subject: "wip".to_string(),
+                        }],
+                    }],
+                }],
+            },
+        );
+    }
+
+    #[test]
+    fn older_schema_versions_on_request() {
+        let output = DestroyOutput {
+            name: "x".to_string(),
+            path: "/wt/x".to_string(),
+            repos_removed: 2,
+        };
+        let current = versioned_output(&output, None).unwrap();
+        assert_eq!(current["schema_version"], OUTPUT_SCHEMA_VERSION);
+
+        let v1 = versioned_output(&output, Some(1)).unwrap();
+        assert_eq!(
+            v1,
+            json!({"name": "x", "path": "/wt/x", "repos_removed": 2})
+        );
+        assert!(versioned_output(&output, Some(OUTPUT_SCHEMA_VERSION + 1)).is_err());
+        assert!(versioned_output(&output, Some(0)).is_err());
+        assert_eq!(
+            DOWNGRADES.len() as u32,
+            OUTPUT_SCHEMA_VERSION - OLDEST_OUTPUT_SCHEMA_VERSION
+        );
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn json_output_honors_the_requested_version() {
+        let output = DestroyOutput {
+            name: "x".to_string(),
+            path: "/wt/x".to_string(),
+            repos_removed: 2,
+        };
+        std::env::remove_var(SCHEMA_VERSION_ENV);
+        let current: Value = serde_json::from_str(&json_output(&output).unwrap()).unwrap();
+        assert_eq!(current["schema_version"], OUTPUT_SCHEMA_VERSION);
+
+        std::env::set_var(SCHEMA_VERSION_ENV, "1");
+        let v1: Value = serde_json::from_str(&json_output(&output).unwrap()).unwrap();
+        assert!(v1.get("schema_version").is_none());
+        std::env::set_var(SCHEMA_VERSION_ENV, "latest");
+        assert!(json_output(&output).is_err());
+        std::env::remove_var(SCHEMA_VERSION_ENV);
+    }
+}
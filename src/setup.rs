@@ -0,0 +1,162 @@
+//! Per-project setup commands.
+//!
+//! A project can list bootstrap commands in `.meta` (e.g. `npm ci`, `make
+//! deps`) that the clone pipeline runs after a fresh clone, and worktree
+//! creation too when `setup_in_worktrees` is set:
+//!
+//! ```json
+//! { "projects": { "web": { "repo": "...", "setup": ["npm ci", ["make", "assets"]], "setup_timeout": 600 } } }
+//! ```
+//!
+//! Commands take the same forms as hooks (see [`HookCommand`]) and run in
+//! order with the repo as the working directory, stopping at the first
+//! failure. Their output is captured for the run report rather than printed.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use crate::project_options::ProjectOptions;
+use crate::worktree::hooks::HookCommand;
+
+/// Time limit per setup command when the project doesn't set `setup_timeout`.
+pub const DEFAULT_SETUP_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// Captured output beyond this many bytes is cut from the front.
+const MAX_OUTPUT_BYTES: usize = 16 * 1024;
+
+/// Result of one setup command.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SetupStepResult {
+    pub command: String,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub timed_out: bool,
+    /// Stdout followed by stderr, keeping the end if long
+    pub output: String,
+    pub duration_ms: u64,
+}
+
+/// Run the project's setup commands in `repo_path`.
+///
+/// Returns one result per command run; after a failure the remaining
+/// commands are skipped. Empty when the project has no setup commands.
+pub fn run_project_setup(repo_path: &Path, options: &ProjectOptions) -> Vec<SetupStepResult> {
+    let timeout = options
+        .setup_timeout
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_SETUP_TIMEOUT);
+    run_setup(repo_path, &options.setup, timeout)
+}
+
+/// Run `commands` in order in `repo_path`, each limited to `timeout`.
+pub fn run_setup(
+    repo_path: &Path,
+    commands: &[HookCommand],
+    timeout: Duration,
+) -> Vec<SetupStepResult> {
+    let mut results = Vec::new();
+    for command in commands {
+        let result = run_step(repo_path, command, timeout);
+        let success = result.success;
+        if !success {
+            log::warn!(
+                "Setup command '{}' failed in {}",
+                result.command,
+                repo_path.display()
+            );
+        }
+        results.push(result);
+        if !success {
+            break;
+        }
+    }
+    results
+}
+
+/// Whether every setup command that ran succeeded.
+pub fn setup_succeeded(results: &[SetupStepResult]) -> bool {
+    results.iter().all(|r| r.success)
+}
+
+fn run_step(repo_path: &Path, command: &HookCommand, timeout: Duration) -> SetupStepResult {
+    let started = Instant::now();
+    let mut cmd = command.command();
+    cmd.current_dir(repo_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let result = crate::process_timeout::output_with_timeout(&mut cmd, Some(timeout));
+    let (success, exit_code, timed_out, output) = match result {
+        Ok(out) => {
+            let mut text = String::from_utf8_lossy(&out.stdout).into_owned();
+            text.push_str(&String::from_utf8_lossy(&out.stderr));
+            (out.status.success(), out.status.code(), false, text)
+        }
+        Err(e) => (
+            false,
+            None,
+            e.kind() == std::io::ErrorKind::TimedOut,
+            e.to_string(),
+        ),
+    };
+    SetupStepResult {
+        command: command.to_string(),
+        success,
+        exit_code,
+        timed_out,
+        output: keep_tail(output.trim_end()),
+        duration_ms: crate::hooks::millis(started.elapsed()),
+    }
+}
+
+fn keep_tail(text: &str) -> String {
+    if text.len() <= MAX_OUTPUT_BYTES {
+        return text.to_string();
+    }
+    let mut start = text.len() - MAX_OUTPUT_BYTES;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("...{}", &text[start..])
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_in_order_and_stops_at_first_failure() {
+        let tmp = tempfile::tempdir().unwrap();
+        let options: ProjectOptions = serde_json::from_value(serde_json::json!({
+            "setup": ["echo one > step1", ["sh", "-c", "echo oops >&2; exit 3"], "touch never"],
+        }))
+        .unwrap();
+
+        let results = run_project_setup(tmp.path(), &options);
+        assert_eq!(results.len(), 2);
+        assert!(tmp.path().join("step1").exists());
+        assert!(!tmp.path().join("never").exists());
+        assert!(results[0].success);
+        assert_eq!(results[1].exit_code, Some(3));
+        assert_eq!(results[1].output, "oops");
+        assert_eq!(results[1].command, "sh -c echo oops >&2; exit 3");
+        assert!(!setup_succeeded(&results));
+    }
+
+    #[test]
+    fn slow_commands_time_out() {
+        let tmp = tempfile::tempdir().unwrap();
+        let commands = [HookCommand::Shell("sleep 30".into())];
+        let results = run_setup(tmp.path(), &commands, Duration::from_millis(200));
+        assert!(results[0].timed_out && !results[0].success);
+    }
+
+    #[test]
+    fn invalid_setup_entries_are_rejected() {
+        let parsed = serde_json::from_value::<ProjectOptions>(serde_json::json!({"setup": [[]]}));
+        assert!(parsed.is_err());
+    }
+}
@@ -12,6 +12,8 @@ use std::fs;
 use std::path::Path;
 use std::process::{Command, Stdio};
 
+use crate::sandbox::Sandbox;
+
 const SNAPSHOTS_DIR: &str = ".meta-snapshots";
 
 /// State of a single repository at snapshot time
@@ -60,13 +62,14 @@ pub struct RestoreResult {
 /// Capture the current git state of a repository
 pub fn capture_repo_state(repo_path: &Path) -> Result<RepoState> {
     // Get current SHA
-    let sha_output = Command::new("git")
-        .args(["rev-parse", "HEAD"])
-        .current_dir(repo_path)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .context("Failed to run git rev-parse HEAD")?;
+    let sha_output = crate::git_runner::output(
+        Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .current_dir(repo_path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped()),
+    )
+    .context("Failed to run git rev-parse HEAD")?;
 
     if !sha_output.status.success() {
         anyhow::bail!(
@@ -97,11 +100,24 @@ pub fn restore_repo_state(
     state: &RepoState,
     force: bool,
 ) -> Result<RestoreResult> {
+    crate::read_only::check("restore snapshot")?;
     let repo_name = repo_path
         .file_name()
         .map(|n| n.to_string_lossy().to_string())
         .unwrap_or_else(|| ".".to_string());
 
+    // Checking out a SHA would fight jj for ownership of HEAD
+    if let Some(reason) = crate::vcs::jj_colocated_skip_reason(repo_path, "snapshot restore") {
+        return Ok(RestoreResult {
+            repo: repo_name,
+            success: false,
+            stashed: false,
+            message: reason,
+        });
+    }
+
+    let _lock = crate::lock::RepoLock::acquire(repo_path, "snapshot restore")?;
+
     // Check if repo is dirty and needs stashing
     let is_dirty = git_utils::is_dirty(repo_path).unwrap_or(false);
 
@@ -113,13 +129,14 @@ pub fn restore_repo_state(
             // In non-force mode, we've already confirmed with user
         }
 
-        let stash_output = Command::new("git")
-            .args(["stash", "push", "-m", "meta-snapshot-auto-stash"])
-            .current_dir(repo_path)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
-            .context("Failed to stash changes")?;
+        let stash_output = crate::git_runner::output(
+            Command::new("git")
+                .args(["stash", "push", "-m", "meta-snapshot-auto-stash"])
+                .current_dir(repo_path)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped()),
+        )
+        .context("Failed to stash changes")?;
 
         if !stash_output.status.success() {
             return Ok(RestoreResult {
@@ -136,13 +153,14 @@ pub fn restore_repo_state(
     }
 
     // Checkout to the snapshot SHA
-    let checkout_output = Command::new("git")
-        .args(["checkout", &state.sha])
-        .current_dir(repo_path)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .context("Failed to checkout SHA")?;
+    let checkout_output = crate::git_runner::output(
+        Command::new("git")
+            .args(["checkout", &state.sha])
+            .current_dir(repo_path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped()),
+    )
+    .context("Failed to checkout SHA")?;
 
     if !checkout_output.status.success() {
         let stderr = String::from_utf8_lossy(&checkout_output.stderr);
@@ -168,13 +186,14 @@ pub fn restore_repo_state(
 
     // If was on a branch, restore branch pointer
     if let Some(ref branch) = state.branch {
-        let branch_output = Command::new("git")
-            .args(["checkout", "-B", branch, &state.sha])
-            .current_dir(repo_path)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
-            .context("Failed to restore branch")?;
+        let branch_output = crate::git_runner::output(
+            Command::new("git")
+                .args(["checkout", "-B", branch, &state.sha])
+                .current_dir(repo_path)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped()),
+        )
+        .context("Failed to restore branch")?;
 
         if !branch_output.status.success() {
             // Non-fatal: we're at the right SHA, just not on the branch
@@ -208,10 +227,12 @@ pub fn restore_repo_state(
 
 /// Save a snapshot to disk
 pub fn save_snapshot(meta_root: &Path, snapshot: &Snapshot) -> Result<()> {
+    crate::read_only::check("save snapshot")?;
     let snapshots_dir = meta_root.join(SNAPSHOTS_DIR);
     fs::create_dir_all(&snapshots_dir).context("Failed to create snapshots directory")?;
 
     let snapshot_path = snapshots_dir.join(format!("{}.json", snapshot.name));
+    Sandbox::new([&snapshots_dir]).check(&snapshot_path, "save snapshot")?;
     let json = serde_json::to_string_pretty(snapshot).context("Failed to serialize snapshot")?;
 
     fs::write(&snapshot_path, json).context("Failed to write snapshot file")?;
@@ -270,7 +291,10 @@ pub fn list_snapshots(meta_root: &Path) -> Result<Vec<SnapshotInfo>> {
 
 /// Delete a snapshot
 pub fn delete_snapshot(meta_root: &Path, name: &str) -> Result<()> {
-    let snapshot_path = meta_root.join(SNAPSHOTS_DIR).join(format!("{name}.json"));
+    crate::read_only::check("delete snapshot")?;
+    let snapshots_dir = meta_root.join(SNAPSHOTS_DIR);
+    let snapshot_path = snapshots_dir.join(format!("{name}.json"));
+    Sandbox::new([&snapshots_dir]).check(&snapshot_path, "delete snapshot")?;
 
     if !snapshot_path.exists() {
         anyhow::bail!("Snapshot '{name}' not found");
@@ -403,6 +427,19 @@ mod tests {
         assert!(load_snapshot(temp.path(), "to-delete").is_err());
     }
 
+    #[test]
+    fn test_restore_skips_jj_colocated_repo() {
+        let temp = TempDir::new().unwrap();
+        create_test_repo(temp.path()).unwrap();
+        let state = capture_repo_state(temp.path()).unwrap();
+        fs::create_dir(temp.path().join(".jj")).unwrap();
+
+        let result = restore_repo_state(temp.path(), &state, false).unwrap();
+        assert!(!result.success);
+        assert!(!result.stashed);
+        assert!(result.message.contains("colocated jj repo"));
+    }
+
     #[test]
     fn test_is_git_repo() {
         let temp = TempDir::new().unwrap();
@@ -3,10 +3,20 @@
 //! When running multiple git commands in parallel (e.g., `meta git update`),
 //! SSH connections to the same host can be rate-limited. SSH multiplexing
 //! allows multiple sessions to share a single TCP connection, avoiding this issue.
+//!
+//! Before fanning out to a host for the first time, [`ensure_known_host`]
+//! pre-seeds `known_hosts` so parallel sessions don't all stop at the same
+//! host key prompt. Unattended runs (fresh CI machines) can opt into trusting
+//! new hosts without a prompt with [`NewHostKeys`].
 
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::git_url::{is_valid_hostname, GitUrl};
 
 /// Patterns that indicate SSH rate-limiting or connection issues
 const SSH_ERROR_PATTERNS: &[&str] = &[
@@ -24,45 +34,22 @@ pub fn is_ssh_rate_limit_error(error_output: &str) -> bool {
         .any(|pattern| error_output.contains(pattern))
 }
 
-/// Validate that a hostname contains only valid characters.
-///
-/// Valid hostnames contain:
-/// - Alphanumeric characters (a-z, A-Z, 0-9)
-/// - Hyphens (but not at start/end of labels)
-/// - Dots (as label separators)
-/// - Underscores (technically invalid per RFC but common in internal hostnames)
-///
-/// Also accepts:
-/// - IPv4 addresses (e.g., 192.168.1.1)
-/// - IPv6 addresses in brackets (e.g., [::1], [2001:db8::1])
-fn is_valid_hostname(host: &str) -> bool {
-    if host.is_empty() {
-        return false;
-    }
-
-    // Handle bracketed IPv6 addresses
-    if host.starts_with('[') && host.ends_with(']') {
-        let inner = &host[1..host.len() - 1];
-        // Basic IPv6 validation: hex digits, colons, and optional dots for mapped IPv4
-        return !inner.is_empty()
-            && inner
-                .chars()
-                .all(|c| c.is_ascii_hexdigit() || c == ':' || c == '.');
-    }
-
-    // Reject hosts that are just dots or start/end with dots
-    if host == "." || host == ".." || host.starts_with('.') || host.ends_with('.') {
-        return false;
-    }
-
-    // Reject hosts with consecutive dots
-    if host.contains("..") {
-        return false;
-    }
+/// Patterns (lower-case) that say outright that the server is throttling us
+const THROTTLING_PATTERNS: &[&str] = &[
+    "rate limit",
+    "rate-limit",
+    "too many requests",
+    "error: 429",
+    "too many connections",
+];
 
-    // All characters must be alphanumeric, hyphen, underscore, or dot
-    host.chars()
-        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+/// Check if an error message says the server throttled the request, as
+/// opposed to a dropped connection that may or may not be throttling.
+pub fn is_throttling_error(error_output: &str) -> bool {
+    let lower = error_output.to_ascii_lowercase();
+    THROTTLING_PATTERNS
+        .iter()
+        .any(|pattern| lower.contains(pattern))
 }
 
 /// Extract the SSH hostname from a git remote URL.
@@ -76,109 +63,34 @@ fn is_valid_hostname(host: &str) -> bool {
 /// - Malformed URLs with invalid hostnames
 /// - URLs with embedded credentials (user:password@host)
 pub fn extract_ssh_host(url: &str) -> Option<String> {
-    let url = url.trim();
-
-    if let Some(rest) = url.strip_prefix("ssh://") {
-        // ssh://[user[:password]@]host[:port]/path
-        let host_part = rest.split('/').next()?;
-
-        // Check for embedded password (user:password@host) - reject these
-        if let Some(at_pos) = host_part.rfind('@') {
-            let user_part = &host_part[..at_pos];
-            if user_part.contains(':') {
-                // Embedded password detected - reject for security
-                return None;
-            }
-        }
-
-        // Strip optional port (but be careful with IPv6 brackets)
-        let host_no_port = if host_part.contains('[') {
-            // IPv6 address: [::1]:port or [::1]
-            let bracket_end = host_part.find(']')?;
-            &host_part[..=bracket_end]
-        } else {
-            // Regular host:port
-            host_part.split(':').next()?
-        };
-
-        let host = host_no_port.split('@').next_back()?;
-        if !is_valid_hostname(host) {
-            return None;
-        }
-        Some(host.to_string())
-    } else if url.contains('@') && url.contains(':') && !url.contains("://") {
-        // git@host:path (SCP-like syntax)
-        // Must have exactly one @ for valid SCP syntax
-        let parts: Vec<&str> = url.splitn(2, '@').collect();
-        if parts.len() != 2 {
-            return None;
-        }
-
-        let after_at = parts[1];
-
-        // Check for embedded password in user part (user:password@host:path)
-        if parts[0].contains(':') {
-            return None;
-        }
-
-        let host = after_at.split(':').next()?;
-        if !is_valid_hostname(host) {
-            return None;
-        }
-        Some(host.to_string())
-    } else {
-        None
+    let url = GitUrl::parse(url).ok()?;
+    if !url.is_ssh() || url.password.is_some() {
+        // Embedded passwords are rejected for security
+        return None;
     }
+    url.host
 }
 
 /// Normalize a git remote URL for comparison purposes.
 ///
 /// Strips trailing `.git` suffix, trailing slashes, and converts
 /// `ssh://git@host/path` to SCP-like `git@host:path` form so that
-/// equivalent URLs compare equal.
+/// equivalent URLs compare equal (see [`GitUrl::normalized`]). Text that
+/// isn't a valid URL is only trimmed.
 pub fn normalize_git_url(url: &str) -> String {
-    let mut s = url.trim().to_string();
-
-    // Strip trailing .git
-    if s.ends_with(".git") {
-        s.truncate(s.len() - 4);
-    }
-    // Strip trailing slashes
-    while s.ends_with('/') {
-        s.pop();
-    }
-
-    // Normalize ssh:// URLs to SCP-like form for consistent comparison
-    if let Some(rest) = s.strip_prefix("ssh://") {
-        // ssh://[user@]host[:port]/path -> user@host:path (drop port)
-        if let Some(slash_pos) = rest.find('/') {
-            let host_part = &rest[..slash_pos];
-            let path = &rest[slash_pos + 1..];
-            // Strip optional port
-            let host_no_port = if let Some(colon_pos) = host_part.rfind(':') {
-                // Only strip if after @ (it's a port, not user separator)
-                if host_part[colon_pos + 1..]
-                    .chars()
-                    .all(|c| c.is_ascii_digit())
-                {
-                    &host_part[..colon_pos]
-                } else {
-                    host_part
-                }
-            } else {
-                host_part
-            };
-            // Ensure user@ prefix (default to git@)
-            let with_user = if host_no_port.contains('@') {
-                host_no_port.to_string()
-            } else {
-                format!("git@{host_no_port}")
-            };
-            return format!("{with_user}:{path}");
-        }
+    match GitUrl::parse(url) {
+        Ok(parsed) => parsed.normalized().to_string(),
+        Err(_) => url.trim().to_string(),
     }
+}
 
-    s
+/// HTTPS equivalent of an SSH remote URL, e.g. `git@github.com:org/repo.git`
+/// becomes `https://github.com/org/repo.git`.
+///
+/// Returns `None` for URLs that aren't SSH. The user and port are dropped.
+pub fn ssh_to_https_url(url: &str) -> Option<String> {
+    let url = GitUrl::parse(url).ok().filter(GitUrl::is_ssh)?;
+    Some(url.to_https()?.to_string())
 }
 
 /// Get the origin remote URL of a git repository.
@@ -186,11 +98,12 @@ pub fn normalize_git_url(url: &str) -> String {
 /// Returns `None` if the directory doesn't exist, isn't a git repo,
 /// or has no `origin` remote.
 pub fn get_remote_url(repo_path: &std::path::Path) -> Option<String> {
-    let output = std::process::Command::new("git")
-        .args(["remote", "get-url", "origin"])
-        .current_dir(repo_path)
-        .output()
-        .ok()?;
+    let output = crate::git_runner::output(
+        std::process::Command::new("git")
+            .args(["remote", "get-url", "origin"])
+            .current_dir(repo_path),
+    )
+    .ok()?;
 
     if output.status.success() {
         let url = String::from_utf8_lossy(&output.stdout).trim().to_string();
@@ -239,6 +152,870 @@ pub fn ensure_ssh_sockets_dir() -> io::Result<Option<PathBuf>> {
     Ok(Some(sockets_dir))
 }
 
+/// Per-host SSH options from the `hosts` map of the `.meta` config.
+///
+/// ```json
+/// "hosts": {
+///   "gitlab.corp.com": {"identity_file": "~/.ssh/id_corp", "fingerprints": ["SHA256:..."]}
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct HostOptions {
+    /// Private key to use for this host
+    pub identity_file: Option<String>,
+    /// Pinned host key fingerprints (see [`ensure_known_host`])
+    pub fingerprints: Vec<String>,
+}
+
+/// Load per-host options from the `.meta` config in `meta_dir`.
+///
+/// Hosts are keyed in lowercase. Entries that fail to parse are logged and skipped.
+pub fn load_host_options(meta_dir: &Path) -> HashMap<String, HostOptions> {
+    let Some(config) = crate::worktree::helpers::read_meta_config_value(meta_dir) else {
+        return HashMap::new();
+    };
+    let Some(hosts) = config.get("hosts").and_then(|h| h.as_object()) else {
+        return HashMap::new();
+    };
+    hosts
+        .iter()
+        .filter_map(
+            |(host, value)| match serde_json::from_value(value.clone()) {
+                Ok(options) => Some((host.to_ascii_lowercase(), options)),
+                Err(e) => {
+                    log::warn!("Ignoring invalid options for host '{host}' in .meta: {e}");
+                    None
+                }
+            },
+        )
+        .collect()
+}
+
+/// Expand a leading `~/` to the home directory.
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest).to_string_lossy().into_owned(),
+        _ => path.to_string(),
+    }
+}
+
+/// Quote `s` for the POSIX shell git runs `GIT_SSH_COMMAND` through.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Extend the ssh command `base` with the host's identity, if configured.
+pub fn host_ssh_command(base: &str, options: &HostOptions) -> String {
+    match &options.identity_file {
+        Some(identity) => format!(
+            "{base} -i {} -o IdentitiesOnly=yes",
+            shell_quote(&expand_home(identity))
+        ),
+        None => base.to_string(),
+    }
+}
+
+/// `GIT_SSH_COMMAND` to use for `url`, or `None` if its host has no options.
+///
+/// Builds on the command's prompt-suppression settings
+/// ([`crate::credentials::suppress_prompts`]) so both apply.
+pub fn ssh_command_for_url(url: &str, hosts: &HashMap<String, HostOptions>) -> Option<String> {
+    let host = extract_ssh_host(url)?.to_ascii_lowercase();
+    let options = hosts.get(&host)?;
+    options.identity_file.as_ref()?;
+    let base = if crate::credentials::prompts_suppressed() {
+        crate::credentials::batch_mode_ssh_command()
+    } else {
+        std::env::var("GIT_SSH_COMMAND").unwrap_or_else(|_| "ssh".to_string())
+    };
+    Some(host_ssh_command(&base, options))
+}
+
+/// Marker comment opening a config block written by this tool.
+pub const CONFIG_BLOCK_BEGIN: &str = "# >>> meta ssh multiplexing";
+/// Marker comment closing a config block written by this tool.
+pub const CONFIG_BLOCK_END: &str = "# <<< meta ssh multiplexing";
+
+/// Default `ControlPersist`, in seconds.
+pub const DEFAULT_CONTROL_PERSIST_SECS: u64 = 600;
+
+/// Multiplexing settings, from `ssh.multiplexing` in `.meta`.
+///
+/// ```json
+/// "ssh": {"multiplexing": {
+///   "persist_seconds": 1800,
+///   "socket_dir": "/run/user/1000/ssh-sockets",
+///   "extra_options": {"ServerAliveInterval": "30"}
+/// }}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct MultiplexingConfig {
+    /// How long an idle master connection stays open (`ControlPersist`)
+    pub persist_seconds: u64,
+    /// Where control sockets live; defaults to `~/.ssh/sockets`
+    pub socket_dir: Option<PathBuf>,
+    /// Additional options written into each host block
+    pub extra_options: BTreeMap<String, String>,
+}
+
+impl Default for MultiplexingConfig {
+    fn default() -> Self {
+        MultiplexingConfig {
+            persist_seconds: DEFAULT_CONTROL_PERSIST_SECS,
+            socket_dir: None,
+            extra_options: BTreeMap::new(),
+        }
+    }
+}
+
+impl MultiplexingConfig {
+    /// Load from the `.meta` config in `meta_dir`, falling back to defaults.
+    pub fn from_meta(meta_dir: &Path) -> Self {
+        let Some(value) = crate::worktree::helpers::read_meta_config_value(meta_dir)
+            .and_then(|v| v.get("ssh")?.get("multiplexing").cloned())
+        else {
+            return Self::default();
+        };
+        serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!("Ignoring invalid ssh.multiplexing settings in .meta: {e}");
+            Self::default()
+        })
+    }
+
+    /// Settings of the nearest workspace containing `path`, falling back to
+    /// defaults outside one.
+    pub fn for_path(path: &Path) -> Self {
+        meta_core::config::find_meta_config(path, None)
+            .and_then(|(config, _)| Some(Self::from_meta(config.parent()?)))
+            .unwrap_or_default()
+    }
+
+    /// Directory holding the control sockets.
+    pub fn sockets_dir(&self) -> Option<PathBuf> {
+        match &self.socket_dir {
+            Some(dir) => Some(PathBuf::from(expand_home(&dir.to_string_lossy()))),
+            None => ssh_sockets_dir(),
+        }
+    }
+
+    /// `ControlPath` pattern for ssh config and `-o` options.
+    pub fn control_path(&self) -> Option<String> {
+        self.sockets_dir()
+            .map(|dir| dir.join("%r@%h-%p").to_string_lossy().into_owned())
+    }
+
+    /// Create the sockets directory if needed.
+    ///
+    /// The default `~/.ssh/sockets` gets the same permission fixes as
+    /// [`ensure_ssh_sockets_dir`]; a custom directory is only restricted to
+    /// `0o700` when this call creates it.
+    pub fn ensure_sockets_dir(&self) -> io::Result<Option<PathBuf>> {
+        if self.socket_dir.is_none() {
+            return ensure_ssh_sockets_dir();
+        }
+        let Some(dir) = self.sockets_dir() else {
+            return Ok(None);
+        };
+        if !dir.exists() {
+            fs::create_dir_all(&dir)?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+            }
+        }
+        Ok(Some(dir))
+    }
+
+    /// `~/.ssh/config` block enabling multiplexing for `host`.
+    ///
+    /// Includes `IdentityFile` when the host has one configured in `.meta`.
+    /// Everything here comes from `.meta`, so the host, identity file, socket
+    /// dir and extra options are validated ([`check_config_value`],
+    /// [`check_extra_option`]) before anything is written.
+    pub fn config_block(
+        &self,
+        host: &str,
+        options: Option<&HostOptions>,
+    ) -> anyhow::Result<String> {
+        if !is_valid_hostname(host) {
+            anyhow::bail!("Refusing to write ssh config for invalid host '{host}'");
+        }
+        let control_path = self
+            .control_path()
+            .unwrap_or_else(|| "~/.ssh/sockets/%r@%h-%p".to_string());
+        check_config_value("ControlPath", &control_path)?;
+        let mut lines = vec![
+            format!("{CONFIG_BLOCK_BEGIN} {host}"),
+            format!("Host {host}"),
+            "    ControlMaster auto".to_string(),
+            format!("    ControlPath {control_path}"),
+            format!("    ControlPersist {}", self.persist_seconds),
+        ];
+        for (key, value) in &self.extra_options {
+            check_extra_option(key, value)?;
+            lines.push(format!("    {key} {value}"));
+        }
+        if let Some(identity) = options.and_then(|o| o.identity_file.as_deref()) {
+            check_config_value("IdentityFile", identity)?;
+            lines.push(format!("    IdentityFile {identity}"));
+            lines.push("    IdentitiesOnly yes".to_string());
+        }
+        lines.push(format!("{CONFIG_BLOCK_END} {host}"));
+        Ok(lines.join("\n") + "\n")
+    }
+}
+
+/// ssh options `extra_options` may set. Anything else, in particular
+/// [`FORBIDDEN_OPTIONS`], is refused.
+pub const ALLOWED_EXTRA_OPTIONS: &[&str] = &[
+    "AddressFamily",
+    "Compression",
+    "ConnectTimeout",
+    "ConnectionAttempts",
+    "IPQoS",
+    "ServerAliveCountMax",
+    "ServerAliveInterval",
+    "TCPKeepAlive",
+];
+
+/// ssh options that run commands or pull in other config, and so are never
+/// written from `.meta`.
+pub const FORBIDDEN_OPTIONS: &[&str] = &["ProxyCommand", "LocalCommand", "Include", "Match"];
+
+/// Reject a config value that could end its line or open a quoted string,
+/// which would let `.meta` inject options or `Host` blocks.
+pub fn check_config_value(key: &str, value: &str) -> anyhow::Result<()> {
+    if value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_control() || c == '"' || c == '\'')
+    {
+        anyhow::bail!("Refusing to write ssh option {key} with value {value:?}");
+    }
+    Ok(())
+}
+
+/// Check an `extra_options` entry against [`ALLOWED_EXTRA_OPTIONS`] and
+/// [`check_config_value`]. Keys are matched case-insensitively, like ssh does.
+pub fn check_extra_option(key: &str, value: &str) -> anyhow::Result<()> {
+    if FORBIDDEN_OPTIONS
+        .iter()
+        .any(|k| k.eq_ignore_ascii_case(key))
+    {
+        anyhow::bail!("Refusing to write ssh option {key} from .meta");
+    }
+    if !ALLOWED_EXTRA_OPTIONS
+        .iter()
+        .any(|k| k.eq_ignore_ascii_case(key))
+    {
+        anyhow::bail!(
+            "Unsupported ssh option {key:?} in ssh.multiplexing.extra_options (allowed: {})",
+            ALLOWED_EXTRA_OPTIONS.join(", ")
+        );
+    }
+    check_config_value(key, value)
+}
+
+/// `~/.ssh/config` block enabling multiplexing for `host` with default settings.
+pub fn multiplexing_config_block(
+    host: &str,
+    options: Option<&HostOptions>,
+) -> anyhow::Result<String> {
+    MultiplexingConfig::default().config_block(host, options)
+}
+
+/// Path of the user's `~/.ssh/config`.
+pub fn ssh_config_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|h| h.join(".ssh").join("config"))
+}
+
+/// Byte range of the block this tool wrote for `host` in `config_text`,
+/// including the trailing newline of the end marker.
+fn find_config_block(config_text: &str, host: &str) -> Option<std::ops::Range<usize>> {
+    let begin = format!("{CONFIG_BLOCK_BEGIN} {host}\n");
+    let end = format!("{CONFIG_BLOCK_END} {host}");
+    let start = config_text
+        .match_indices(&begin)
+        .map(|(i, _)| i)
+        .find(|&i| i == 0 || config_text[..i].ends_with('\n'))?;
+    let end_at = start + config_text[start..].find(&end)? + end.len();
+    let end_at = if config_text[end_at..].starts_with('\n') {
+        end_at + 1
+    } else {
+        end_at
+    };
+    Some(start..end_at)
+}
+
+/// Whether `config_text` has a block written by this tool for `host`.
+pub fn has_config_block(config_text: &str, host: &str) -> bool {
+    find_config_block(config_text, host).is_some()
+}
+
+/// Whether the block for `host` in `config_text` matches what `config`
+/// would write now. Outdated blocks are rewritten by [`setup_in`].
+pub fn config_block_is_current(
+    config_text: &str,
+    host: &str,
+    config: &MultiplexingConfig,
+    options: Option<&HostOptions>,
+) -> bool {
+    let Ok(block) = config.config_block(host, options) else {
+        return false;
+    };
+    find_config_block(config_text, host).is_some_and(|range| config_text[range] == block)
+}
+
+/// Add or refresh multiplexing blocks for `hosts` in the ssh config at
+/// `config_path`. Returns the hosts whose blocks were written.
+///
+/// Every block is validated first (see [`MultiplexingConfig::config_block`]);
+/// if any host or option is rejected, the config file is left untouched.
+pub fn setup_in(
+    config_path: &Path,
+    hosts: &[String],
+    config: &MultiplexingConfig,
+    host_options: &HashMap<String, HostOptions>,
+) -> anyhow::Result<Vec<String>> {
+    let blocks = hosts
+        .iter()
+        .map(|host| {
+            let options = host_options.get(&host.to_ascii_lowercase());
+            Ok((host, config.config_block(host, options)?))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut text = fs::read_to_string(config_path).unwrap_or_default();
+    let mut written = Vec::new();
+    for (host, block) in blocks {
+        match find_config_block(&text, host) {
+            Some(range) if text[range.clone()] == block => continue,
+            Some(range) => text.replace_range(range, &block),
+            None => {
+                // Host blocks are first-match-wins, so ours go before user entries
+                text = if text.is_empty() {
+                    block
+                } else {
+                    format!("{block}\n{text}")
+                };
+            }
+        }
+        written.push(host.clone());
+    }
+    if written.is_empty() {
+        return Ok(written);
+    }
+
+    crate::read_only::check("edit ssh config")?;
+    config.ensure_sockets_dir()?;
+    if let Some(dir) = config_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(config_path, text)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(config_path, fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(written)
+}
+
+/// [`setup_in`] for `~/.ssh/config`.
+pub fn setup(
+    hosts: &[String],
+    config: &MultiplexingConfig,
+    host_options: &HashMap<String, HostOptions>,
+) -> anyhow::Result<Vec<String>> {
+    let path =
+        ssh_config_path().ok_or_else(|| anyhow::anyhow!("Cannot determine home directory"))?;
+    setup_in(&path, hosts, config, host_options)
+}
+
+/// Hosts that have a block written by this tool in `config_text`.
+pub fn configured_hosts(config_text: &str) -> Vec<String> {
+    config_text
+        .lines()
+        .filter_map(|l| l.strip_prefix(CONFIG_BLOCK_BEGIN))
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .collect()
+}
+
+/// Remove the blocks this tool wrote for `hosts` from `config_text`.
+///
+/// Returns the new text and the hosts whose blocks were removed. A blank
+/// separator line left behind by [`setup_in`] is removed along with the block.
+pub fn remove_config_blocks(config_text: &str, hosts: &[String]) -> (String, Vec<String>) {
+    let mut text = config_text.to_string();
+    let mut removed = Vec::new();
+    for host in hosts {
+        if let Some(mut range) = find_config_block(&text, host) {
+            if text[range.end..].starts_with('\n') {
+                range.end += 1;
+            }
+            text.replace_range(range, "");
+            removed.push(host.clone());
+        }
+    }
+    (text, removed)
+}
+
+/// What [`teardown`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TeardownReport {
+    /// Control sockets whose master connection was told to exit
+    pub closed: Vec<PathBuf>,
+    /// Socket files removed because no master was listening
+    pub removed_sockets: Vec<PathBuf>,
+    /// Hosts whose config blocks were removed
+    pub removed_blocks: Vec<String>,
+}
+
+/// Whether a master connection is listening on `socket`.
+fn socket_is_live(socket: &Path) -> bool {
+    Command::new("ssh")
+        .args(["-O", "check", "-S"])
+        .arg(socket)
+        .arg("meta-socket-check")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// Control sockets in `dir`, optionally only those for `host`.
+///
+/// Sockets are named `%r@%h-%p`, so a host's sockets match `*@<host>-*`.
+fn control_sockets(dir: &Path, host: Option<&str>) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::FileTypeExt;
+                e.file_type().map(|t| t.is_socket()).unwrap_or(false)
+            }
+            #[cfg(not(unix))]
+            {
+                e.file_type().map(|t| t.is_file()).unwrap_or(false)
+            }
+        })
+        .filter(|e| {
+            let name = e.file_name().to_string_lossy().into_owned();
+            host.is_none_or(|h| {
+                name.split_once('@').is_some_and(|(_, rest)| {
+                    rest.strip_prefix(h).is_some_and(|p| p.starts_with('-'))
+                })
+            })
+        })
+        .map(|e| e.path())
+        .collect()
+}
+
+/// Undo multiplexing for `hosts`, with the config at `config_path`.
+///
+/// Closes live master connections (`ssh -O exit`), removes every stale
+/// socket in the sockets dir, and with `remove_config` also deletes the
+/// config blocks this tool added for those hosts.
+pub fn teardown_in(
+    config_path: &Path,
+    hosts: &[String],
+    config: &MultiplexingConfig,
+    remove_config: bool,
+) -> anyhow::Result<TeardownReport> {
+    let mut report = TeardownReport::default();
+
+    if let Some(dir) = config.sockets_dir() {
+        for host in hosts {
+            for socket in control_sockets(&dir, Some(host)) {
+                let exited = Command::new("ssh")
+                    .args(["-O", "exit", "-S"])
+                    .arg(&socket)
+                    .arg(host)
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()
+                    .map(|s| s.success())
+                    .unwrap_or(false);
+                if exited {
+                    report.closed.push(socket);
+                }
+            }
+        }
+        for socket in control_sockets(&dir, None) {
+            if !socket_is_live(&socket) && fs::remove_file(&socket).is_ok() {
+                report.removed_sockets.push(socket);
+            }
+        }
+    }
+
+    if remove_config {
+        if let Ok(text) = fs::read_to_string(config_path) {
+            let (new_text, removed) = remove_config_blocks(&text, hosts);
+            if !removed.is_empty() {
+                crate::read_only::check("edit ssh config")?;
+                fs::write(config_path, new_text)?;
+            }
+            report.removed_blocks = removed;
+        }
+    }
+    Ok(report)
+}
+
+/// [`teardown_in`] for `~/.ssh/config`.
+pub fn teardown(
+    hosts: &[String],
+    config: &MultiplexingConfig,
+    remove_config: bool,
+) -> anyhow::Result<TeardownReport> {
+    let path =
+        ssh_config_path().ok_or_else(|| anyhow::anyhow!("Cannot determine home directory"))?;
+    teardown_in(&path, hosts, config, remove_config)
+}
+
+/// Patterns that indicate a broken or stale multiplexing control socket
+const MUX_ERROR_PATTERNS: &[&str] = &[
+    "mux_client_request_session",
+    "mux_client_hello_exchange",
+    "mux_client_forward",
+    "Control socket connect(",
+    "master hello exchange failed",
+    "disabling multiplexing",
+];
+
+/// Check if an error message indicates a broken multiplexing master.
+pub fn is_broken_mux_error(error_output: &str) -> bool {
+    MUX_ERROR_PATTERNS
+        .iter()
+        .any(|pattern| error_output.contains(pattern))
+}
+
+/// Extract the offending control socket path from an ssh error, if named.
+///
+/// Handles `Control socket connect(<path>): ...` and
+/// `ControlSocket <path> already exists, disabling multiplexing`.
+pub fn broken_mux_socket(error_output: &str) -> Option<PathBuf> {
+    for line in error_output.lines() {
+        if let Some(rest) = line.split("Control socket connect(").nth(1) {
+            if let Some((path, _)) = rest.split_once("):") {
+                return Some(PathBuf::from(path));
+            }
+        }
+        if let Some(rest) = line.split("ControlSocket ").nth(1) {
+            if let Some((path, _)) = rest.split_once(" already exists") {
+                return Some(PathBuf::from(path));
+            }
+        }
+    }
+    None
+}
+
+/// Clean up after a broken-mux failure described by `error_output`.
+///
+/// Removes the socket named in the error, or failing that every stale
+/// socket in the configured sockets dir. Returns whether anything was removed.
+pub fn recover_broken_mux(error_output: &str, config: &MultiplexingConfig) -> bool {
+    if let Some(socket) = broken_mux_socket(error_output) {
+        if socket.exists() && !socket_is_live(&socket) && fs::remove_file(&socket).is_ok() {
+            log::debug!("Removed broken control socket {}", socket.display());
+            return true;
+        }
+    }
+    let Some(dir) = config.sockets_dir() else {
+        return false;
+    };
+    let mut removed = false;
+    for socket in control_sockets(&dir, None) {
+        if !socket_is_live(&socket) && fs::remove_file(&socket).is_ok() {
+            log::debug!("Removed stale control socket {}", socket.display());
+            removed = true;
+        }
+    }
+    removed
+}
+
+/// Run `cmd`, and if it fails with a broken-mux error, clean the offending
+/// control socket (looking in `config`'s sockets dir) and run it once more.
+pub fn output_with_mux_recovery(
+    cmd: &mut Command,
+    config: &MultiplexingConfig,
+) -> io::Result<std::process::Output> {
+    run_with_mux_recovery(cmd, config, crate::git_runner::output)
+}
+
+/// Like [`output_with_mux_recovery`], running the command with `run`.
+pub fn run_with_mux_recovery<F>(
+    cmd: &mut Command,
+    config: &MultiplexingConfig,
+    mut run: F,
+) -> io::Result<std::process::Output>
+where
+    F: FnMut(&mut Command) -> io::Result<std::process::Output>,
+{
+    let output = run(cmd)?;
+    if output.status.success() {
+        return Ok(output);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !is_broken_mux_error(&stderr) {
+        return Ok(output);
+    }
+    log::warn!("SSH multiplexing socket looks broken; cleaning it up and retrying once");
+    recover_broken_mux(&stderr, config);
+    run(cmd)
+}
+
+/// Outcome of [`ensure_known_host`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KnownHostStatus {
+    /// The host already had a known_hosts entry
+    AlreadyKnown,
+    /// Keys with these fingerprints were added
+    Added(Vec<String>),
+    /// The user declined the scanned fingerprints
+    Declined,
+}
+
+/// Path of the user's `~/.ssh/known_hosts`.
+pub fn known_hosts_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|h| h.join(".ssh").join("known_hosts"))
+}
+
+/// Check whether `known_hosts` has an entry for `host` (hashed or not).
+pub fn is_known_host_in(known_hosts: &Path, host: &str) -> bool {
+    if !known_hosts.exists() {
+        return false;
+    }
+    Command::new("ssh-keygen")
+        .arg("-F")
+        .arg(host)
+        .arg("-f")
+        .arg(known_hosts)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// SHA256 fingerprint (`SHA256:...`) of a single known_hosts key line.
+pub fn key_line_fingerprint(line: &str) -> Option<String> {
+    let mut child = Command::new("ssh-keygen")
+        .args(["-l", "-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        use std::io::Write;
+        let _ = writeln!(stdin, "{line}");
+    }
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .find(|w| w.starts_with("SHA256:"))
+        .map(str::to_string)
+}
+
+/// Pinned host key fingerprints from `hosts.<host>.fingerprints` in `.meta`.
+pub fn pinned_fingerprints(meta_dir: &Path, host: &str) -> Vec<String> {
+    load_host_options(meta_dir)
+        .remove(host)
+        .map(|o| o.fingerprints)
+        .unwrap_or_default()
+}
+
+/// Pick the scanned key lines to trust.
+///
+/// With pins, only keys whose fingerprint is pinned are kept, and it is an
+/// error if none match (the host may be impersonated). Without pins, all
+/// scanned keys are returned for confirmation.
+fn select_keys(
+    host: &str,
+    scanned: Vec<(String, String)>,
+    pinned: &[String],
+) -> anyhow::Result<Vec<(String, String)>> {
+    if pinned.is_empty() {
+        return Ok(scanned);
+    }
+    let matching: Vec<_> = scanned
+        .into_iter()
+        .filter(|(_, fp)| pinned.iter().any(|p| p == fp))
+        .collect();
+    if matching.is_empty() {
+        anyhow::bail!(
+            "None of the host keys offered by {host} match the fingerprints pinned in .meta; \
+             refusing to trust it"
+        );
+    }
+    Ok(matching)
+}
+
+/// Make sure `host` has a known_hosts entry before mass operations hit it.
+///
+/// If the host is missing, its keys are fetched with `ssh-keyscan`. Keys
+/// matching `pinned` fingerprints are trusted without asking; with no pins,
+/// `confirm` is shown the scanned fingerprints and decides. Accepted keys
+/// are appended to `~/.ssh/known_hosts`, so parallel clones don't all stall
+/// on the same interactive host key prompt.
+pub fn ensure_known_host(
+    host: &str,
+    pinned: &[String],
+    confirm: impl FnOnce(&str, &[String]) -> bool,
+) -> anyhow::Result<KnownHostStatus> {
+    let known_hosts =
+        known_hosts_path().ok_or_else(|| anyhow::anyhow!("Cannot determine home directory"))?;
+    ensure_known_host_in(&known_hosts, host, pinned, confirm)
+}
+
+/// [`ensure_known_host`] against an explicit known_hosts file.
+pub fn ensure_known_host_in(
+    known_hosts: &Path,
+    host: &str,
+    pinned: &[String],
+    confirm: impl FnOnce(&str, &[String]) -> bool,
+) -> anyhow::Result<KnownHostStatus> {
+    if !is_valid_hostname(host) {
+        anyhow::bail!("Invalid SSH host '{host}'");
+    }
+    if is_known_host_in(known_hosts, host) {
+        return Ok(KnownHostStatus::AlreadyKnown);
+    }
+    crate::read_only::check("add known_hosts entries")?;
+
+    let output = Command::new("ssh-keyscan")
+        .args(["-T", "10", host])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|e| anyhow::anyhow!("Failed to run ssh-keyscan. Is OpenSSH installed? {e}"))?;
+    let scanned: Vec<(String, String)> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|l| !l.trim().is_empty() && !l.starts_with('#'))
+        .filter_map(|l| key_line_fingerprint(l).map(|fp| (l.to_string(), fp)))
+        .collect();
+    if scanned.is_empty() {
+        anyhow::bail!("ssh-keyscan returned no host keys for {host}");
+    }
+
+    let keys = select_keys(host, scanned, pinned)?;
+    let fingerprints: Vec<String> = keys.iter().map(|(_, fp)| fp.clone()).collect();
+    if pinned.is_empty() && !confirm(host, &fingerprints) {
+        return Ok(KnownHostStatus::Declined);
+    }
+    append_known_hosts(known_hosts, keys.iter().map(|(line, _)| line.as_str()))?;
+    Ok(KnownHostStatus::Added(fingerprints))
+}
+
+/// How clones treat SSH hosts without a known_hosts entry, from
+/// `ssh.new_host_keys` in `.meta`.
+///
+/// Anything but the default trusts a host's key on first use, so it has to
+/// be chosen explicitly:
+///
+/// ```json
+/// "ssh": {"new_host_keys": "accept-new"}
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NewHostKeys {
+    /// Leave it to ssh: prompt, or fail when prompts are suppressed
+    #[default]
+    Ask,
+    /// Let ssh add the key offered on first connection
+    /// (`StrictHostKeyChecking=accept-new`); changed keys are still rejected
+    AcceptNew,
+    /// Add the keys of each new host with `ssh-keyscan` before its first
+    /// clone (see [`scan_new_host`]); fingerprints pinned in `.meta` are
+    /// still enforced
+    Scan,
+}
+
+impl NewHostKeys {
+    /// Load from the `.meta` config in `meta_dir`, falling back to [`Ask`](Self::Ask).
+    pub fn from_meta(meta_dir: &Path) -> Self {
+        let Some(value) = crate::worktree::helpers::read_meta_config_value(meta_dir)
+            .and_then(|v| v.get("ssh")?.get("new_host_keys").cloned())
+        else {
+            return Self::default();
+        };
+        serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!("Ignoring invalid ssh.new_host_keys setting in .meta: {e}");
+            Self::default()
+        })
+    }
+}
+
+/// The ssh command `base` with `StrictHostKeyChecking=accept-new`, unless it
+/// already sets host key checking.
+pub fn accept_new_ssh_command(base: &str) -> String {
+    if base.contains("StrictHostKeyChecking") {
+        base.to_string()
+    } else {
+        format!("{base} -o StrictHostKeyChecking=accept-new")
+    }
+}
+
+/// Make ssh run by the git command `cmd` accept new host keys, on top of
+/// the `GIT_SSH_COMMAND` it would otherwise use.
+pub fn accept_new_host_keys(cmd: &mut Command) -> &mut Command {
+    let base = cmd
+        .get_envs()
+        .find(|(key, _)| *key == "GIT_SSH_COMMAND")
+        .and_then(|(_, value)| value)
+        .map(|value| value.to_string_lossy().into_owned())
+        .or_else(|| std::env::var("GIT_SSH_COMMAND").ok())
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(|| "ssh".to_string());
+    cmd.env("GIT_SSH_COMMAND", accept_new_ssh_command(&base))
+}
+
+/// For [`NewHostKeys::Scan`]: add the keys of `host` to `~/.ssh/known_hosts`
+/// if it has no entry yet, trusting the scanned keys unless `meta_dir` pins
+/// fingerprints for it.
+pub fn scan_new_host(host: &str, meta_dir: Option<&Path>) -> anyhow::Result<KnownHostStatus> {
+    let pinned = meta_dir
+        .map(|dir| pinned_fingerprints(dir, &host.to_ascii_lowercase()))
+        .unwrap_or_default();
+    ensure_known_host(host, &pinned, |_, _| true)
+}
+
+fn append_known_hosts<'a>(
+    known_hosts: &Path,
+    lines: impl Iterator<Item = &'a str>,
+) -> io::Result<()> {
+    use std::io::Write;
+    if let Some(dir) = known_hosts.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(known_hosts)?;
+    for line in lines {
+        writeln!(file, "{line}")?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -411,6 +1188,20 @@ mod tests {
 
     // ============ URL Normalization Tests ============
 
+    #[test]
+    fn test_ssh_to_https_url() {
+        assert_eq!(
+            ssh_to_https_url("git@github.com:org/repo.git").as_deref(),
+            Some("https://github.com/org/repo.git")
+        );
+        assert_eq!(
+            ssh_to_https_url("ssh://git@gitlab.example.com:2222/group/sub/repo").as_deref(),
+            Some("https://gitlab.example.com/group/sub/repo.git")
+        );
+        assert_eq!(ssh_to_https_url("https://github.com/org/repo.git"), None);
+        assert_eq!(ssh_to_https_url("/srv/git/repo"), None);
+    }
+
     #[test]
     fn test_normalize_strips_trailing_dot_git() {
         assert_eq!(
@@ -691,4 +1482,434 @@ mod tests {
             Some("10.0.0.1".to_string())
         );
     }
+
+    // ── known_hosts ─────────────────────────────────────────
+
+    /// Generate a throwaway ed25519 key and return (known_hosts line, fingerprint).
+    fn fake_host_key(dir: &Path, host: &str) -> (String, String) {
+        let key = dir.join("hostkey");
+        let status = Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-f"])
+            .arg(&key)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let public = fs::read_to_string(key.with_extension("pub")).unwrap();
+        let mut parts = public.split_whitespace();
+        let line = format!(
+            "{} {} {}",
+            host,
+            parts.next().unwrap(),
+            parts.next().unwrap()
+        );
+        let out = Command::new("ssh-keygen")
+            .arg("-lf")
+            .arg(key.with_extension("pub"))
+            .output()
+            .unwrap();
+        let fp = String::from_utf8_lossy(&out.stdout)
+            .split_whitespace()
+            .nth(1)
+            .unwrap()
+            .to_string();
+        (line, fp)
+    }
+
+    #[test]
+    fn test_key_line_fingerprint_and_known_host_lookup() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (line, fp) = fake_host_key(tmp.path(), "git.example.test");
+        assert_eq!(key_line_fingerprint(&line), Some(fp));
+
+        let known_hosts = tmp.path().join("known_hosts");
+        assert!(!is_known_host_in(&known_hosts, "git.example.test"));
+        append_known_hosts(&known_hosts, std::iter::once(line.as_str())).unwrap();
+        assert!(is_known_host_in(&known_hosts, "git.example.test"));
+        assert!(!is_known_host_in(&known_hosts, "other.example.test"));
+        assert_eq!(
+            ensure_known_host_in(&known_hosts, "git.example.test", &[], |_, _| false).unwrap(),
+            KnownHostStatus::AlreadyKnown
+        );
+    }
+
+    #[test]
+    fn test_select_keys_honors_pins() {
+        let scanned = vec![
+            ("h ssh-ed25519 AAA".to_string(), "SHA256:good".to_string()),
+            ("h ssh-rsa BBB".to_string(), "SHA256:other".to_string()),
+        ];
+        assert_eq!(select_keys("h", scanned.clone(), &[]).unwrap().len(), 2);
+        let kept = select_keys("h", scanned.clone(), &["SHA256:good".to_string()]).unwrap();
+        assert_eq!(kept, vec![scanned[0].clone()]);
+        assert!(select_keys("h", scanned, &["SHA256:evil".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_pinned_fingerprints_from_meta() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(
+            tmp.path().join(".meta"),
+            r#"{"projects": {}, "hosts": {"github.com": {"fingerprints": ["SHA256:abc"]}}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            pinned_fingerprints(tmp.path(), "github.com"),
+            vec!["SHA256:abc".to_string()]
+        );
+        assert!(pinned_fingerprints(tmp.path(), "gitlab.com").is_empty());
+    }
+
+    #[test]
+    fn test_ensure_known_host_rejects_invalid_host() {
+        let tmp = tempfile::tempdir().unwrap();
+        let known_hosts = tmp.path().join("known_hosts");
+        assert!(ensure_known_host_in(&known_hosts, "-oProxyCommand=x", &[], |_, _| true).is_err());
+    }
+
+    // ── per-host options ────────────────────────────────────
+
+    #[test]
+    fn test_load_host_options_and_ssh_command() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(
+            tmp.path().join(".meta"),
+            r#"{"projects": {}, "hosts": {
+                "GitLab.corp.com": {"identity_file": "/keys/it's corp"},
+                "github.com": {"fingerprints": ["SHA256:abc"]},
+                "bad.example": {"identity_file": 7}
+            }}"#,
+        )
+        .unwrap();
+        let hosts = load_host_options(tmp.path());
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(
+            hosts["gitlab.corp.com"].identity_file.as_deref(),
+            Some("/keys/it's corp")
+        );
+
+        let cmd = ssh_command_for_url("git@gitlab.corp.com:team/web.git", &hosts).unwrap();
+        assert!(
+            cmd.ends_with(r"-i '/keys/it'\''s corp' -o IdentitiesOnly=yes"),
+            "{cmd}"
+        );
+        assert!(ssh_command_for_url("git@github.com:org/api.git", &hosts).is_none());
+        assert!(ssh_command_for_url("https://gitlab.corp.com/team/web.git", &hosts).is_none());
+    }
+
+    #[test]
+    fn test_multiplexing_config_block_includes_identity() {
+        let options = HostOptions {
+            identity_file: Some("~/.ssh/id_corp".into()),
+            ..Default::default()
+        };
+        let block = multiplexing_config_block("gitlab.corp.com", Some(&options)).unwrap();
+        assert!(block.starts_with(CONFIG_BLOCK_BEGIN));
+        assert!(block.contains("Host gitlab.corp.com\n    ControlMaster auto\n"));
+        assert!(block.contains("    IdentityFile ~/.ssh/id_corp\n    IdentitiesOnly yes\n"));
+        assert!(block
+            .trim_end()
+            .ends_with("# <<< meta ssh multiplexing gitlab.corp.com"));
+
+        let plain = multiplexing_config_block("github.com", None).unwrap();
+        assert!(!plain.contains("IdentityFile"));
+    }
+
+    #[test]
+    fn test_config_block_rejects_injected_values() {
+        let injected = HostOptions {
+            identity_file: Some("~/.ssh/id\nHost *\n    ProxyCommand sh -c id".into()),
+            ..Default::default()
+        };
+        assert!(multiplexing_config_block("gitlab.corp.com", Some(&injected)).is_err());
+        let quoted = HostOptions {
+            identity_file: Some("\"~/.ssh/id\"".into()),
+            ..Default::default()
+        };
+        assert!(multiplexing_config_block("gitlab.corp.com", Some(&quoted)).is_err());
+        assert!(multiplexing_config_block("github.com\nHost *", None).is_err());
+        assert!(multiplexing_config_block("github.com *", None).is_err());
+
+        for key in ["ProxyCommand", "localcommand", "Include", "Match", "User"] {
+            let config = MultiplexingConfig {
+                extra_options: BTreeMap::from([(key.to_string(), "x".to_string())]),
+                ..Default::default()
+            };
+            assert!(config.config_block("github.com", None).is_err(), "{key}");
+        }
+        let config = MultiplexingConfig {
+            extra_options: BTreeMap::from([(
+                "ServerAliveInterval".to_string(),
+                "30\nHost *".to_string(),
+            )]),
+            ..Default::default()
+        };
+        assert!(config.config_block("github.com", None).is_err());
+    }
+
+    // ── multiplexing config ─────────────────────────────────
+
+    #[test]
+    fn test_multiplexing_config_from_meta() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert_eq!(
+            MultiplexingConfig::from_meta(tmp.path()),
+            MultiplexingConfig::default()
+        );
+        fs::write(
+            tmp.path().join(".meta"),
+            r#"{"projects": {}, "ssh": {"multiplexing": {
+                "persist_seconds": 1800,
+                "socket_dir": "/run/ssh-sockets",
+                "extra_options": {"ServerAliveInterval": "30"}
+            }}}"#,
+        )
+        .unwrap();
+        let config = MultiplexingConfig::from_meta(tmp.path());
+        assert_eq!(config.persist_seconds, 1800);
+        assert_eq!(
+            config.control_path().as_deref(),
+            Some("/run/ssh-sockets/%r@%h-%p")
+        );
+        let block = config.config_block("github.com", None).unwrap();
+        assert!(block.contains("    ControlPersist 1800\n"));
+        assert!(block.contains("    ControlPath /run/ssh-sockets/%r@%h-%p\n"));
+        assert!(block.contains("    ServerAliveInterval 30\n"));
+    }
+
+    #[test]
+    fn test_setup_writes_and_refreshes_blocks() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config_path = tmp.path().join("config");
+        fs::write(&config_path, "Host *\n    ServerAliveInterval 60\n").unwrap();
+        let mut config = MultiplexingConfig {
+            socket_dir: Some(tmp.path().join("sockets")),
+            ..Default::default()
+        };
+        let hosts = vec!["github.com".to_string()];
+
+        let written = setup_in(&config_path, &hosts, &config, &HashMap::new()).unwrap();
+        assert_eq!(written, hosts);
+        assert!(tmp.path().join("sockets").is_dir());
+        let text = fs::read_to_string(&config_path).unwrap();
+        assert!(text.starts_with(CONFIG_BLOCK_BEGIN));
+        assert!(text.ends_with("Host *\n    ServerAliveInterval 60\n"));
+        assert!(config_block_is_current(&text, "github.com", &config, None));
+
+        // Unchanged settings are a no-op
+        assert!(setup_in(&config_path, &hosts, &config, &HashMap::new())
+            .unwrap()
+            .is_empty());
+
+        // Changed settings replace the block in place
+        config.persist_seconds = 60;
+        setup_in(&config_path, &hosts, &config, &HashMap::new()).unwrap();
+        let text = fs::read_to_string(&config_path).unwrap();
+        assert_eq!(text.matches(CONFIG_BLOCK_BEGIN).count(), 1);
+        assert!(text.contains("ControlPersist 60\n"));
+        assert!(text.contains("Host *"));
+    }
+
+    #[test]
+    fn test_setup_rejects_injected_options() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config_path = tmp.path().join("config");
+        fs::write(&config_path, "Host *\n    User me\n").unwrap();
+        let hosts = vec!["github.com".to_string()];
+
+        let proxy = MultiplexingConfig {
+            socket_dir: Some(tmp.path().join("sockets")),
+            extra_options: BTreeMap::from([(
+                "ProxyCommand".to_string(),
+                "sh -c 'touch /tmp/pwned'".to_string(),
+            )]),
+            ..Default::default()
+        };
+        assert!(setup_in(&config_path, &hosts, &proxy, &HashMap::new()).is_err());
+
+        let newline = MultiplexingConfig {
+            socket_dir: Some(tmp.path().join("sockets")),
+            extra_options: BTreeMap::from([(
+                "ServerAliveInterval".to_string(),
+                "30\nHost *\n    LocalCommand id".to_string(),
+            )]),
+            ..Default::default()
+        };
+        assert!(setup_in(&config_path, &hosts, &newline, &HashMap::new()).is_err());
+
+        // One bad host means nothing is written, not even the valid blocks
+        let config = MultiplexingConfig {
+            socket_dir: Some(tmp.path().join("sockets")),
+            ..Default::default()
+        };
+        let hosts = vec!["github.com".to_string(), "evil\nHost *".to_string()];
+        assert!(setup_in(&config_path, &hosts, &config, &HashMap::new()).is_err());
+        assert_eq!(
+            fs::read_to_string(&config_path).unwrap(),
+            "Host *\n    User me\n"
+        );
+        assert!(!tmp.path().join("sockets").exists());
+    }
+
+    // ── teardown ────────────────────────────────────────────
+
+    #[test]
+    fn test_remove_config_blocks() {
+        let config = MultiplexingConfig::default();
+        let text = format!(
+            "{}\n{}\nHost *\n    User me\n",
+            config.config_block("github.com", None).unwrap(),
+            config.config_block("gitlab.com", None).unwrap()
+        );
+        assert_eq!(configured_hosts(&text), vec!["github.com", "gitlab.com"]);
+
+        let (text, removed) = remove_config_blocks(&text, &["github.com".to_string()]);
+        assert_eq!(removed, vec!["github.com"]);
+        assert!(text.starts_with(CONFIG_BLOCK_BEGIN));
+        assert_eq!(configured_hosts(&text), vec!["gitlab.com"]);
+
+        let (text, _) = remove_config_blocks(&text, &["gitlab.com".to_string()]);
+        assert_eq!(text, "Host *\n    User me\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_teardown_removes_stale_sockets_and_blocks() {
+        let tmp = tempfile::tempdir().unwrap();
+        let sockets = tmp.path().join("sockets");
+        fs::create_dir_all(&sockets).unwrap();
+        // A bound-then-dropped listener leaves a socket file with no master behind it
+        drop(std::os::unix::net::UnixListener::bind(sockets.join("git@github.com-22")).unwrap());
+        fs::write(sockets.join("not-a-socket"), "").unwrap();
+
+        let config = MultiplexingConfig {
+            socket_dir: Some(sockets.clone()),
+            ..Default::default()
+        };
+        let config_path = tmp.path().join("config");
+        let hosts = vec!["github.com".to_string()];
+        setup_in(&config_path, &hosts, &config, &HashMap::new()).unwrap();
+
+        let report = teardown_in(&config_path, &hosts, &config, true).unwrap();
+        assert!(report.closed.is_empty());
+        assert_eq!(
+            report.removed_sockets,
+            vec![sockets.join("git@github.com-22")]
+        );
+        assert_eq!(report.removed_blocks, hosts);
+        assert!(sockets.join("not-a-socket").exists());
+        assert!(configured_hosts(&fs::read_to_string(&config_path).unwrap()).is_empty());
+    }
+
+    // ── broken mux recovery ─────────────────────────────────
+
+    #[test]
+    fn test_broken_mux_detection() {
+        let err = "mux_client_request_session: read from master failed: Broken pipe\n\
+                   fatal: Could not read from remote repository.";
+        assert!(is_broken_mux_error(err));
+        assert!(!is_broken_mux_error("Connection reset by peer"));
+        assert_eq!(
+            broken_mux_socket(
+                "Control socket connect(/home/u/.ssh/sockets/git@github.com-22): Connection refused"
+            ),
+            Some(PathBuf::from("/home/u/.ssh/sockets/git@github.com-22"))
+        );
+        assert_eq!(
+            broken_mux_socket(
+                "ControlSocket /tmp/s/git@h-22 already exists, disabling multiplexing"
+            ),
+            Some(PathBuf::from("/tmp/s/git@h-22"))
+        );
+        assert_eq!(broken_mux_socket(err), None);
+    }
+
+    #[test]
+    fn test_new_host_keys_setting() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert_eq!(NewHostKeys::from_meta(tmp.path()), NewHostKeys::Ask);
+        std::fs::write(
+            tmp.path().join(".meta"),
+            r#"{"projects": {}, "ssh": {"new_host_keys": "accept-new"}}"#,
+        )
+        .unwrap();
+        assert_eq!(NewHostKeys::from_meta(tmp.path()), NewHostKeys::AcceptNew);
+
+        assert_eq!(
+            accept_new_ssh_command("ssh -o BatchMode=yes"),
+            "ssh -o BatchMode=yes -o StrictHostKeyChecking=accept-new"
+        );
+        assert_eq!(
+            accept_new_ssh_command("ssh -o StrictHostKeyChecking=no"),
+            "ssh -o StrictHostKeyChecking=no"
+        );
+        let mut cmd = Command::new("git");
+        cmd.env("GIT_SSH_COMMAND", "ssh -i key");
+        accept_new_host_keys(&mut cmd);
+        let ssh = cmd
+            .get_envs()
+            .find(|(k, _)| *k == "GIT_SSH_COMMAND")
+            .unwrap()
+            .1;
+        assert_eq!(
+            ssh.unwrap(),
+            "ssh -i key -o StrictHostKeyChecking=accept-new"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_recover_broken_mux_removes_named_socket() {
+        let tmp = tempfile::tempdir().unwrap();
+        let socket = tmp.path().join("git@github.com-22");
+        drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+        let err = format!(
+            "Control socket connect({}): Connection refused",
+            socket.display()
+        );
+        assert!(recover_broken_mux(&err, &MultiplexingConfig::default()));
+        assert!(!socket.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_with_mux_recovery_retries_once() {
+        let tmp = tempfile::tempdir().unwrap();
+        let marker = tmp.path().join("ran-once");
+        let script = format!(
+            "if [ -e '{0}' ]; then echo retried; else touch '{0}'; \
+             echo 'mux_client_request_session: session request failed' >&2; exit 255; fi",
+            marker.display()
+        );
+        let output = output_with_mux_recovery(
+            Command::new("sh").args(["-c", &script]),
+            &MultiplexingConfig::default(),
+        )
+        .unwrap();
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "retried");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_mux_recovery_cleans_the_configured_sockets_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let sockets = tmp.path().join("sockets");
+        fs::create_dir_all(&sockets).unwrap();
+        let stale = sockets.join("git@example.com-22");
+        drop(std::os::unix::net::UnixListener::bind(&stale).unwrap());
+        let config = MultiplexingConfig {
+            socket_dir: Some(sockets),
+            ..Default::default()
+        };
+        let output = output_with_mux_recovery(
+            Command::new("sh").args([
+                "-c",
+                "echo 'mux_client_request_session: session request failed' >&2; exit 255",
+            ]),
+            &config,
+        )
+        .unwrap();
+        assert!(!output.status.success());
+        assert!(!stale.exists());
+    }
 }
@@ -0,0 +1,422 @@
+//! Dirty-repo index for incremental workspace status.
+//!
+//! Inspecting 200 repos with `git status` and `rev-list` on every status
+//! call adds up, though between two calls usually only a few repos changed.
+//! The index in `~/.meta/status-index.json` records each repo's last-known
+//! status with a [`Fingerprint`] of the files git updates when it changes
+//! (`HEAD` and the ref it points to, the index, `FETCH_HEAD`) and of the
+//! checkout itself: the modification time and size of every tracked file
+//! and of every directory holding one, which catches edits nothing has
+//! `git add`ed yet and new files anywhere in the tree. A repo whose
+//! fingerprint still matches is reported from the index without running
+//! git.
+//!
+//! Reading the fingerprint never runs git: it reads git's index file and
+//! `stat`s the checkout much like `git status` does, without the process
+//! and `rev-list` costs. Entries for checkouts that are gone or weren't
+//! inspected for [`MAX_AGE_DAYS`] are dropped when the index is saved.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::rerun::Fnv64;
+use crate::worktree::types::StatusRepoEntry;
+
+/// Indexed statuses not recorded for this long are dropped.
+pub const MAX_AGE_DAYS: i64 = 30;
+
+/// Modification time (nanoseconds since the epoch) and size of a file;
+/// `None` if it doesn't exist.
+pub type FileStamp = Option<(u128, u64)>;
+
+/// What a repo's status depends on, read without running git.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// Contents of `HEAD`: a ref name or a detached SHA
+    pub head: String,
+    /// The loose ref `HEAD` points to
+    pub head_ref: FileStamp,
+    pub packed_refs: FileStamp,
+    pub index: FileStamp,
+    pub fetch_head: FileStamp,
+    /// Hash of the [`FileStamp`]s of the tracked files and the directories
+    /// holding them, as found in the checkout; `None` if git's index can't
+    /// be parsed
+    #[serde(default)]
+    pub worktree: Option<u64>,
+}
+
+impl Fingerprint {
+    /// Fingerprint of the checkout at `repo_path`. `None` if it isn't a git
+    /// repo.
+    pub fn read(repo_path: &Path) -> Option<Self> {
+        let git_dir = git_dir(repo_path)?;
+        let common_dir = std::fs::read_to_string(git_dir.join("commondir"))
+            .map(|dir| git_dir.join(dir.trim()))
+            .unwrap_or_else(|_| git_dir.clone());
+        let head = std::fs::read_to_string(git_dir.join("HEAD"))
+            .ok()?
+            .trim()
+            .to_string();
+        let head_ref = head
+            .strip_prefix("ref: ")
+            .and_then(|name| stamp(&common_dir.join(name)));
+        Some(Fingerprint {
+            head_ref,
+            packed_refs: stamp(&common_dir.join("packed-refs")),
+            index: stamp(&git_dir.join("index")),
+            fetch_head: stamp(&common_dir.join("FETCH_HEAD")),
+            worktree: worktree_digest(repo_path, &git_dir.join("index"), &common_dir),
+            head,
+        })
+    }
+}
+
+/// Last-known status of one repo.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedStatus {
+    pub fingerprint: Fingerprint,
+    pub status: StatusRepoEntry,
+    pub recorded: DateTime<Utc>,
+}
+
+/// Indexed repos, keyed by canonical path.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct StatusIndexData {
+    pub repos: HashMap<String, IndexedStatus>,
+}
+
+/// The dirty-repo index, loaded once per status run.
+#[derive(Debug, Default)]
+pub struct StatusIndex {
+    data: StatusIndexData,
+    changed: bool,
+}
+
+impl StatusIndex {
+    /// Load the index; an unreadable index is treated as empty.
+    pub fn load() -> Self {
+        let data = meta_core::store::read(&store_paths().0).unwrap_or_else(|e| {
+            log::debug!("Ignoring unreadable status index: {e:#}");
+            StatusIndexData::default()
+        });
+        StatusIndex {
+            data,
+            changed: false,
+        }
+    }
+
+    /// The recorded status of `repo_path`, if nothing changed since.
+    pub fn lookup(&self, repo_path: &Path) -> Option<&StatusRepoEntry> {
+        let indexed = self.data.repos.get(&key(repo_path))?;
+        (Fingerprint::read(repo_path).as_ref() == Some(&indexed.fingerprint))
+            .then_some(&indexed.status)
+    }
+
+    /// Record `status`, just computed for `repo_path`.
+    ///
+    /// Call this after inspecting the repo: `git status` may refresh the
+    /// index, which changes the fingerprint.
+    pub fn record(&mut self, repo_path: &Path, status: &StatusRepoEntry) {
+        let Some(fingerprint) = Fingerprint::read(repo_path) else {
+            return;
+        };
+        self.data.repos.insert(
+            key(repo_path),
+            IndexedStatus {
+                fingerprint,
+                status: status.clone(),
+                recorded: Utc::now(),
+            },
+        );
+        self.changed = true;
+    }
+
+    /// Write the index back if anything was recorded, dropping entries for
+    /// checkouts that are gone or older than [`MAX_AGE_DAYS`].
+    pub fn save(self) -> Result<()> {
+        if !self.changed || crate::read_only::is_read_only() {
+            return Ok(());
+        }
+        let (data_path, lock_path) = store_paths();
+        let recorded = self.data.repos;
+        let cutoff = Utc::now() - chrono::Duration::days(MAX_AGE_DAYS);
+        meta_core::store::update(&data_path, &lock_path, |data: &mut StatusIndexData| {
+            data.repos.extend(recorded);
+            data.repos.retain(|path, indexed| {
+                indexed.recorded > cutoff && git_dir(Path::new(path)).is_some()
+            });
+        })
+    }
+}
+
+/// Forget every indexed status, forcing the next status to inspect every
+/// repo.
+pub fn clear() -> Result<()> {
+    let (data_path, _) = store_paths();
+    match std::fs::remove_file(&data_path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+fn store_paths() -> (PathBuf, PathBuf) {
+    let data_path = meta_core::data_dir::data_file("status-index");
+    let lock_path = data_path.with_extension("lock");
+    (data_path, lock_path)
+}
+
+fn key(repo_path: &Path) -> String {
+    repo_path
+        .canonicalize()
+        .unwrap_or_else(|_| repo_path.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// The git dir of the checkout at `repo_path`: `.git`, or where a `.git`
+/// file (of a linked worktree or submodule) points.
+fn git_dir(repo_path: &Path) -> Option<PathBuf> {
+    let dot_git = repo_path.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let contents = std::fs::read_to_string(&dot_git).ok()?;
+    let dir = contents.trim().strip_prefix("gitdir: ")?;
+    Some(repo_path.join(dir))
+}
+
+/// Hash of the [`stamp`]s of the files listed in the git index at `index`
+/// and of the directories holding them (the top-level one included), as
+/// found in the checkout at `repo_path`. `None` if the index can't be
+/// parsed; a missing index lists no files.
+fn worktree_digest(repo_path: &Path, index: &Path, common_dir: &Path) -> Option<u64> {
+    let paths = match std::fs::read(index) {
+        Ok(data) => index_paths(&data, oid_len(common_dir))?,
+        Err(_) => Vec::new(),
+    };
+    let mut hash = Fnv64::new();
+    let mut dirs = BTreeSet::from([String::new()]);
+    for path in &paths {
+        hash.write_str(path);
+        hash.write_str(&format!("{:?}", stamp(&repo_path.join(path))));
+        let mut dir = path.as_str();
+        // Once a directory is known, so are its parents
+        while let Some((parent, _)) = dir.rsplit_once('/') {
+            if !dirs.insert(parent.to_string()) {
+                break;
+            }
+            dir = parent;
+        }
+    }
+    for dir in &dirs {
+        hash.write_str(dir);
+        hash.write_str(&format!("{:?}", stamp(&repo_path.join(dir))));
+    }
+    Some(hash.finish())
+}
+
+/// Paths of the entries of a git index file (versions 2 to 4) whose object
+/// ids are `oid_len` bytes long.
+fn index_paths(data: &[u8], oid_len: usize) -> Option<Vec<String>> {
+    let be32 = |at: usize| Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?));
+    if data.get(..4)? != b"DIRC" {
+        return None;
+    }
+    let version = be32(4)?;
+    if !(2..=4).contains(&version) {
+        return None;
+    }
+    let mut paths = Vec::new();
+    let mut at = 12;
+    let mut previous: Vec<u8> = Vec::new();
+    for _ in 0..be32(8)? {
+        let start = at;
+        // Ten 32-bit stat fields and the object id come before the flags
+        let flags_at = start + 40 + oid_len;
+        let flags = u16::from_be_bytes(data.get(flags_at..flags_at + 2)?.try_into().ok()?);
+        at = flags_at + 2;
+        if version >= 3 && flags & 0x4000 != 0 {
+            // Extended flags
+            at += 2;
+        }
+        let path = if version == 4 {
+            // Prefix-compressed: drop that many bytes of the previous path,
+            // then append the rest
+            let (strip, len) = varint(data.get(at..)?)?;
+            at += len;
+            let end = at + data.get(at..)?.iter().position(|&b| b == 0)?;
+            previous.truncate(previous.len().checked_sub(strip)?);
+            previous.extend_from_slice(&data[at..end]);
+            at = end + 1;
+            previous.clone()
+        } else {
+            let end = at + data.get(at..)?.iter().position(|&b| b == 0)?;
+            let path = data[at..end].to_vec();
+            // NUL-padded to a multiple of 8 bytes
+            at = start + ((end - start) / 8 + 1) * 8;
+            path
+        };
+        paths.push(String::from_utf8_lossy(&path).into_owned());
+    }
+    Some(paths)
+}
+
+/// A variable-length integer of index version 4 at the start of `bytes`:
+/// its value and length.
+fn varint(bytes: &[u8]) -> Option<(usize, usize)> {
+    let mut value = 0usize;
+    for (i, &byte) in bytes.iter().enumerate().take(9) {
+        if i > 0 {
+            value = (value + 1) << 7;
+        }
+        value |= usize::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// Length of the repo's object ids: 32 bytes for SHA-256 repos, else 20.
+fn oid_len(common_dir: &Path) -> usize {
+    let config = std::fs::read_to_string(common_dir.join("config")).unwrap_or_default();
+    let sha256 = config.lines().any(|line| {
+        let line: String = line.split_whitespace().collect();
+        line.eq_ignore_ascii_case("objectformat=sha256")
+    });
+    if sha256 {
+        32
+    } else {
+        20
+    }
+}
+
+fn stamp(path: &Path) -> FileStamp {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_nanos();
+    Some((modified, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{git, repo as init_repo};
+
+    #[test]
+    fn fingerprint_changes_with_head_index_and_new_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = tmp.path();
+        init_repo(repo, &[("src/lib.rs", "a")]);
+        let clean = Fingerprint::read(repo).unwrap();
+        assert_eq!(clean.head, "ref: refs/heads/main");
+        assert_eq!(Fingerprint::read(repo), Some(clean.clone()));
+
+        // Directory mtimes can be coarse; make sure the change is visible
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        std::fs::write(repo.join("new.txt"), "x").unwrap();
+        let untracked = Fingerprint::read(repo).unwrap();
+        assert_ne!(untracked, clean);
+
+        git(repo, &["add", "new.txt"]);
+        let staged = Fingerprint::read(repo).unwrap();
+        assert_ne!(staged.index, untracked.index);
+
+        git(repo, &["checkout", "-q", "-b", "feature"]);
+        assert_eq!(
+            Fingerprint::read(repo).unwrap().head,
+            "ref: refs/heads/feature"
+        );
+        assert_eq!(Fingerprint::read(&tmp.path().join("missing")), None);
+
+        // Unstaged edits and new files below the top level
+        let before = Fingerprint::read(repo).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        std::fs::write(repo.join("src/lib.rs"), "b").unwrap();
+        let edited = Fingerprint::read(repo).unwrap();
+        assert_eq!(edited.index, before.index);
+        assert_ne!(edited.worktree, before.worktree);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        std::fs::write(repo.join("src/new.rs"), "").unwrap();
+        assert_ne!(Fingerprint::read(repo).unwrap().worktree, edited.worktree);
+    }
+
+    #[test]
+    fn reads_index_paths_in_every_version() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = tmp.path();
+        init_repo(
+            repo,
+            &[("a.txt", ""), ("src/lib.rs", ""), ("src/long-name.rs", "")],
+        );
+        let expected = ["a.txt", "src/lib.rs", "src/long-name.rs"];
+        for version in ["2", "3", "4"] {
+            git(repo, &["update-index", "--index-version", version]);
+            let data = std::fs::read(repo.join(".git/index")).unwrap();
+            assert_eq!(index_paths(&data, 20).unwrap(), expected, "v{version}");
+        }
+        // An intent-to-add entry has extended flags in version 3
+        std::fs::write(repo.join("later.txt"), "").unwrap();
+        git(repo, &["update-index", "--index-version", "3"]);
+        git(repo, &["add", "-N", "later.txt"]);
+        let data = std::fs::read(repo.join(".git/index")).unwrap();
+        assert_eq!(index_paths(&data, 20).unwrap()[1], "later.txt");
+        assert_eq!(index_paths(b"nope", 20), None);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn save_drops_missing_and_old_entries() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp.path().join("store"));
+        let (kept, gone) = (tmp.path().join("kept"), tmp.path().join("gone"));
+        init_repo(&kept, &[]);
+        init_repo(&gone, &[]);
+        let status = StatusRepoEntry {
+            alias: "x".into(),
+            path: String::new(),
+            branch: "main".into(),
+            dirty: false,
+            modified_count: 0,
+            untracked_count: 0,
+            ahead: 0,
+            behind: 0,
+            modified_files: Vec::new(),
+            renames: Vec::new(),
+            untracked_files: Vec::new(),
+            last_fetched: None,
+        };
+
+        let mut index = StatusIndex::load();
+        index.record(&kept, &status);
+        index.record(&gone, &status);
+        index.save().unwrap();
+        assert_eq!(StatusIndex::load().data.repos.len(), 2);
+
+        std::fs::remove_dir_all(&gone).unwrap();
+        let mut index = StatusIndex::load();
+        index.record(&kept, &status);
+        index.save().unwrap();
+        let index = StatusIndex::load();
+        assert_eq!(index.data.repos.len(), 1);
+        assert!(index.lookup(&kept).is_some());
+
+        let mut index = StatusIndex::load();
+        index.record(&kept, &status);
+        for indexed in index.data.repos.values_mut() {
+            indexed.recorded = Utc::now() - chrono::Duration::days(MAX_AGE_DAYS + 1);
+        }
+        index.save().unwrap();
+        assert!(StatusIndex::load().data.repos.is_empty());
+        std::env::remove_var("META_DATA_DIR");
+    }
+}
@@ -0,0 +1,147 @@
+//! Synthetic workspaces and queues for benchmarks.
+//!
+//! Built with the `bench` feature for the harness in `benches/`, so that
+//! performance work on the clone queue, workspace status, and the worktree
+//! store has a baseline to measure against. [`generate`] creates a
+//! workspace of git repos on disk; [`clone_tasks`] and [`store_entries`]
+//! build in-memory inputs of any size without touching the network.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::clone_queue::CloneTask;
+use crate::worktree::types::{StoreRepoEntry, WorktreeStoreEntry};
+
+/// A workspace made by [`generate`].
+#[derive(Debug, Clone)]
+pub struct SyntheticWorkspace {
+    pub root: PathBuf,
+    /// Project names, in `.meta` order
+    pub repos: Vec<String>,
+}
+
+impl SyntheticWorkspace {
+    /// Leave an uncommitted change in the `index`th repo.
+    pub fn make_dirty(&self, index: usize) -> Result<()> {
+        let repo = self.root.join(&self.repos[index]);
+        std::fs::write(repo.join("file-0.txt"), "changed\n")?;
+        Ok(())
+    }
+}
+
+/// Create a workspace at `root` whose `.meta` lists `repos` git repos, each
+/// with `files` committed files.
+pub fn generate(root: &Path, repos: usize, files: usize) -> Result<SyntheticWorkspace> {
+    let names: Vec<String> = (0..repos).map(|i| format!("repo-{i:04}")).collect();
+    for name in &names {
+        let repo = root.join(name);
+        std::fs::create_dir_all(&repo)?;
+        for i in 0..files {
+            std::fs::write(repo.join(format!("file-{i}.txt")), format!("{name} {i}\n"))?;
+        }
+        git(&repo, &["init", "-q", "-b", "main"])?;
+        git(&repo, &["add", "-A"])?;
+        git(&repo, &["commit", "-q", "--allow-empty", "-m", "init"])?;
+    }
+    let projects: serde_json::Map<String, serde_json::Value> = names
+        .iter()
+        .map(|name| {
+            (
+                name.clone(),
+                format!("git@example.com:bench/{name}.git").into(),
+            )
+        })
+        .collect();
+    std::fs::write(
+        root.join(".meta"),
+        serde_json::json!({ "projects": projects }).to_string(),
+    )?;
+    Ok(SyntheticWorkspace {
+        root: root.to_path_buf(),
+        repos: names,
+    })
+}
+
+/// `count` clone tasks under `base`, spread over three nesting levels, with
+/// every tenth task tagged `critical`.
+pub fn clone_tasks(base: &Path, count: usize) -> Vec<CloneTask> {
+    (0..count)
+        .map(|i| {
+            let name = format!("repo-{i:05}");
+            CloneTask {
+                url: format!("git@example.com:bench/{name}.git"),
+                target_path: base.join(&name),
+                depth_level: i % 3,
+                is_meta: false,
+                options: Default::default(),
+                ssh_command: None,
+                attempts: 0,
+                tags: if i % 10 == 0 {
+                    vec!["critical".to_string()]
+                } else {
+                    Vec::new()
+                },
+                name,
+            }
+        })
+        .collect()
+}
+
+/// `count` worktree store entries of the workspace `project`, each with
+/// `repos` repos, keyed by worktree path under `base`.
+pub fn store_entries(
+    base: &Path,
+    project: &str,
+    count: usize,
+    repos: usize,
+) -> Vec<(PathBuf, WorktreeStoreEntry)> {
+    (0..count)
+        .map(|i| {
+            let name = format!("wt-{i:05}");
+            let entry = WorktreeStoreEntry {
+                name: name.clone(),
+                project: project.to_string(),
+                created_at: chrono::Utc::now().to_rfc3339(),
+                ephemeral: i % 2 == 0,
+                ttl_seconds: (i % 2 == 0).then_some(3600),
+                repos: (0..repos)
+                    .map(|r| StoreRepoEntry {
+                        alias: format!("repo-{r:04}"),
+                        branch: name.clone(),
+                        created_branch: true,
+                        base_moved: None,
+                    })
+                    .collect(),
+                custom: Default::default(),
+                change_group: None,
+                protected: false,
+                expansions: Vec::new(),
+            };
+            (base.join(&name), entry)
+        })
+        .collect()
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<()> {
+    let output = Command::new("git")
+        .args([
+            "-c",
+            "user.email=bench@example.com",
+            "-c",
+            "user.name=Bench",
+        ])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .with_context(|| format!("Failed to run git {}", args[0]))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed in {}: {}",
+            args[0],
+            dir.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
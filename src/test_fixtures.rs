@@ -0,0 +1,106 @@
+//! Fixtures shared by the crate's unit tests: real git repos in temp dirs
+//! and worktree store entries.
+
+use std::path::Path;
+use std::process::Command;
+
+use crate::worktree::types::{StoreRepoEntry, WorktreeStoreEntry};
+
+/// Run git in `dir` with a test identity, failing the test if it fails.
+/// Returns stdout, trimmed.
+pub(crate) fn git(dir: &Path, args: &[&str]) -> String {
+    let out = Command::new("git")
+        .args(["-c", "user.email=t@t.com", "-c", "user.name=T"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "git {args:?}: {out:?}");
+    String::from_utf8_lossy(&out.stdout).trim().to_string()
+}
+
+/// A repo at `dir` on branch `main` with one commit holding `files`
+/// (`(path, content)`; none makes an empty commit).
+pub(crate) fn repo(dir: &Path, files: &[(&str, &str)]) {
+    std::fs::create_dir_all(dir).unwrap();
+    git(dir, &["init", "-q", "-b", "main"]);
+    git(dir, &["config", "user.email", "t@t.com"]);
+    git(dir, &["config", "user.name", "T"]);
+    for (name, content) in files {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+    git(dir, &["add", "."]);
+    git(dir, &["commit", "-q", "--allow-empty", "-m", "init"]);
+}
+
+/// A worktree of `repo` at `path` on a new branch `branch`.
+pub(crate) fn worktree(repo: &Path, path: &Path, branch: &str) {
+    git(
+        repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            branch,
+            path.to_str().unwrap(),
+        ],
+    );
+}
+
+/// Builds a [`WorktreeStoreEntry`]: non-ephemeral, created now, with no
+/// repos until [`repo`](Self::repo) adds them on a branch named like the
+/// worktree.
+pub(crate) struct StoreEntryBuilder(WorktreeStoreEntry);
+
+/// A store entry for worktree `name` of the workspace at `project`.
+pub(crate) fn store_entry(name: &str, project: &Path) -> StoreEntryBuilder {
+    StoreEntryBuilder(WorktreeStoreEntry {
+        name: name.to_string(),
+        project: project.to_string_lossy().into_owned(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        ephemeral: false,
+        ttl_seconds: None,
+        repos: Vec::new(),
+        custom: Default::default(),
+        change_group: None,
+        protected: false,
+        expansions: Vec::new(),
+    })
+}
+
+impl StoreEntryBuilder {
+    pub(crate) fn created_at(mut self, created_at: &str) -> Self {
+        self.0.created_at = created_at.to_string();
+        self
+    }
+
+    /// Ephemeral, expiring `ttl_seconds` after creation.
+    pub(crate) fn ephemeral(mut self, ttl_seconds: u64) -> Self {
+        self.0.ephemeral = true;
+        self.0.ttl_seconds = Some(ttl_seconds);
+        self
+    }
+
+    pub(crate) fn protected(mut self, protected: bool) -> Self {
+        self.0.protected = protected;
+        self
+    }
+
+    pub(crate) fn repo(mut self, alias: &str) -> Self {
+        let branch = self.0.name.clone();
+        self.0.repos.push(StoreRepoEntry {
+            alias: alias.to_string(),
+            branch,
+            created_branch: true,
+            base_moved: None,
+        });
+        self
+    }
+
+    pub(crate) fn build(self) -> WorktreeStoreEntry {
+        self.0
+    }
+}
@@ -0,0 +1,653 @@
+//! Fakes and fixtures for tests of code built on this crate.
+//!
+//! Built with the `test-util` feature. [`FakeGit`] is an in-memory git: it
+//! records every call, answers clone, status, branch, and update requests
+//! from per-repo state the test sets up, and fails where the test says so,
+//! all without spawning git. It implements both [`GitRunner`], so the
+//! crate's own subsystems (clone queue, worktrees, API) run against it once
+//! it is installed with [`git_runner::with_runner`] or
+//! [`CloneQueue::with_git_runner`](crate::clone_queue::CloneQueue::with_git_runner),
+//! and [`Vcs`], for code written against that trait.
+//!
+//! [`TestWorkspace`] lays out a workspace (a `.meta` listing N projects) in
+//! a temp dir, with stub checkouts for use with [`FakeGit`] or real repos
+//! where git itself is under test.
+//!
+//! ```no_run
+//! use meta_git_lib::git_runner;
+//! use meta_git_lib::test_util::TestWorkspace;
+//! use meta_git_lib::worktree::git_ops::git_status_summary;
+//! use std::sync::Arc;
+//!
+//! let ws = TestWorkspace::with_repos(3).unwrap();
+//! let git = Arc::new(ws.fake_git());
+//! git.set_dirty(&ws.repo_path(1), &["src/lib.rs"]);
+//! let status = git_runner::with_runner(git.clone(), || git_status_summary(&ws.repo_path(1)));
+//! assert!(status.unwrap().dirty);
+//! assert_eq!(git.calls().len(), 1);
+//! ```
+
+use anyhow::Result;
+use indicatif::ProgressBar;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::sync::Mutex;
+
+use crate::git_runner::{self, GitInvocation, GitRunner};
+use crate::vcs::{Vcs, VcsKind};
+use crate::worktree::types::GitStatusSummary;
+
+/// A call made to a [`FakeGit`], in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FakeCall {
+    Clone {
+        url: String,
+        target: PathBuf,
+    },
+    Status(PathBuf),
+    CurrentBranch(PathBuf),
+    Update(PathBuf),
+    /// Any other git command run through the [`GitRunner`], with the
+    /// arguments after global options such as `-c`
+    Git {
+        dir: PathBuf,
+        args: Vec<String>,
+    },
+}
+
+impl FakeCall {
+    fn operation(&self) -> FakeOperation {
+        match self {
+            FakeCall::Clone { .. } => FakeOperation::Clone,
+            FakeCall::Status(_) => FakeOperation::Status,
+            FakeCall::CurrentBranch(_) => FakeOperation::CurrentBranch,
+            FakeCall::Update(_) => FakeOperation::Update,
+            FakeCall::Git { .. } => FakeOperation::Git,
+        }
+    }
+
+    fn path(&self) -> &Path {
+        match self {
+            FakeCall::Clone { target, .. } => target,
+            FakeCall::Status(path) | FakeCall::CurrentBranch(path) | FakeCall::Update(path) => path,
+            FakeCall::Git { dir, .. } => dir,
+        }
+    }
+}
+
+/// Operations a [`FakeGit`] can be told to fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FakeOperation {
+    Clone,
+    Status,
+    CurrentBranch,
+    Update,
+    /// Every other git command
+    Git,
+}
+
+/// State of one fake checkout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FakeRepo {
+    /// `None` for a detached HEAD
+    pub branch: Option<String>,
+    pub status: GitStatusSummary,
+    /// URL it was cloned from, if cloned through the fake
+    pub url: Option<String>,
+    /// Times it was updated
+    pub updates: usize,
+}
+
+impl Default for FakeRepo {
+    fn default() -> Self {
+        FakeRepo {
+            branch: Some("main".to_string()),
+            status: GitStatusSummary::default(),
+            url: None,
+            updates: 0,
+        }
+    }
+}
+
+/// In-memory [`GitRunner`] and [`Vcs`] for deterministic tests.
+///
+/// Repos it doesn't know are treated as missing: status and update fail,
+/// and there is no current branch. Clones create the target directory (so
+/// code checking for it sees the clone) and register a clean repo on `main`;
+/// clones into a `<target>.partial-<pid>` staging directory register the
+/// final target. Other git commands succeed with no output unless the test
+/// scripted them with [`respond`](Self::respond).
+#[derive(Debug, Default)]
+pub struct FakeGit {
+    repos: Mutex<HashMap<PathBuf, FakeRepo>>,
+    failures: Mutex<HashSet<(FakeOperation, PathBuf)>>,
+    responses: Mutex<Vec<(Vec<String>, Output)>>,
+    calls: Mutex<Vec<FakeCall>>,
+}
+
+impl FakeGit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register (or replace) the checkout at `path`.
+    pub fn add_repo(&self, path: &Path, repo: FakeRepo) {
+        lock(&self.repos).insert(path.to_path_buf(), repo);
+    }
+
+    /// The state of the checkout at `path`, if known.
+    pub fn repo(&self, path: &Path) -> Option<FakeRepo> {
+        lock(&self.repos).get(path).cloned()
+    }
+
+    /// Put the checkout at `path` on `branch`.
+    pub fn set_branch(&self, path: &Path, branch: &str) {
+        self.edit(path, |repo| repo.branch = Some(branch.to_string()));
+    }
+
+    /// Give the checkout at `path` these modified files.
+    pub fn set_dirty(&self, path: &Path, modified_files: &[&str]) {
+        self.edit(path, |repo| {
+            repo.status.modified_files = modified_files.iter().map(|f| f.to_string()).collect();
+            repo.status.dirty = !modified_files.is_empty() || repo.status.untracked_count > 0;
+        });
+    }
+
+    /// Make `operation` on `path` fail until [`succeed`](Self::succeed).
+    pub fn fail(&self, operation: FakeOperation, path: &Path) {
+        lock(&self.failures).insert((operation, path.to_path_buf()));
+    }
+
+    pub fn succeed(&self, operation: FakeOperation, path: &Path) {
+        lock(&self.failures).remove(&(operation, path.to_path_buf()));
+    }
+
+    /// Answer git commands whose arguments start with `args` with `output`,
+    /// e.g. [`FakeGit::output`]`(0, "abc123\n", "")` for `["rev-parse", "HEAD"]`.
+    /// Later responses take precedence. Doesn't apply to the commands the
+    /// fake answers from repo state.
+    pub fn respond(&self, args: &[&str], output: Output) {
+        let args = args.iter().map(|a| a.to_string()).collect();
+        lock(&self.responses).insert(0, (args, output));
+    }
+
+    /// An [`Output`] exiting with `code`.
+    pub fn output(code: i32, stdout: &str, stderr: &str) -> Output {
+        Output {
+            status: git_runner::exit_status(code),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: stderr.as_bytes().to_vec(),
+        }
+    }
+
+    /// Every call so far, in order.
+    pub fn calls(&self) -> Vec<FakeCall> {
+        lock(&self.calls).clone()
+    }
+
+    /// Calls of `operation`, in order.
+    pub fn calls_of(&self, operation: FakeOperation) -> Vec<FakeCall> {
+        lock(&self.calls)
+            .iter()
+            .filter(|call| call.operation() == operation)
+            .cloned()
+            .collect()
+    }
+
+    fn edit(&self, path: &Path, f: impl FnOnce(&mut FakeRepo)) {
+        f(lock(&self.repos).entry(path.to_path_buf()).or_default());
+    }
+
+    /// Record `call`, failing if the test asked for it.
+    fn record(&self, call: FakeCall) -> Result<()> {
+        let key = (call.operation(), call.path().to_path_buf());
+        lock(&self.calls).push(call);
+        if lock(&self.failures).contains(&key) {
+            anyhow::bail!("fake {:?} failed in {}", key.0, key.1.display());
+        }
+        Ok(())
+    }
+
+    fn known(&self, path: &Path) -> Result<()> {
+        if !lock(&self.repos).contains_key(path) {
+            anyhow::bail!("{} is not a repository", path.display());
+        }
+        Ok(())
+    }
+}
+
+impl GitRunner for FakeGit {
+    fn run(&self, invocation: &GitInvocation) -> io::Result<Output> {
+        let cwd = match &invocation.dir {
+            Some(dir) => dir.clone(),
+            None => std::env::current_dir()?,
+        };
+        let (dir, args) = strip_global_options(cwd, invocation.args_lossy());
+        let has = |flag: &str| args.iter().any(|a| a == flag);
+        let fatal = |e: anyhow::Error| -> io::Result<Output> {
+            Ok(Self::output(128, "", &format!("fatal: {e}\n")))
+        };
+        let Some(command) = args.first().map(String::as_str) else {
+            return Ok(Self::output(1, "", "usage: git <command>\n"));
+        };
+        match command {
+            "clone" => {
+                let positional = clone_positionals(&args[1..]);
+                let [.., url, target] = positional.as_slice() else {
+                    return Ok(Self::output(129, "", "fatal: fake clone needs a target\n"));
+                };
+                let staging = dir.join(target);
+                let target = final_clone_target(&staging);
+                if let Err(e) = self.record(FakeCall::Clone {
+                    url: url.to_string(),
+                    target: target.clone(),
+                }) {
+                    return fatal(e);
+                }
+                let branch = args
+                    .windows(2)
+                    .find(|w| w[0] == "--branch" || w[0] == "-b")
+                    .map_or("main", |w| w[1].as_str());
+                std::fs::create_dir_all(staging.join(".git"))?;
+                std::fs::write(
+                    staging.join(".git").join("HEAD"),
+                    format!("ref: refs/heads/{branch}\n"),
+                )?;
+                self.add_repo(
+                    &target,
+                    FakeRepo {
+                        branch: Some(branch.to_string()),
+                        url: Some(url.to_string()),
+                        ..Default::default()
+                    },
+                );
+                Ok(Self::output(0, "", ""))
+            }
+            "status" => {
+                let status = match self.status(&dir) {
+                    Ok(status) => status,
+                    Err(e) => return fatal(e),
+                };
+                let v2 = args.iter().any(|a| a == "--porcelain=v2");
+                let end = if has("-z") { '\0' } else { '\n' };
+                let mut out = String::new();
+                for path in &status.modified_files {
+                    if v2 {
+                        out.push_str(&format!(
+                            "1 .M N... 100644 100644 100644 {ZERO} {ZERO} {path}"
+                        ));
+                    } else {
+                        out.push_str(&format!(" M {path}"));
+                    }
+                    out.push(end);
+                }
+                for path in &status.untracked_files {
+                    out.push_str(if v2 { "? " } else { "?? " });
+                    out.push_str(path);
+                    out.push(end);
+                }
+                Ok(Self::output(0, &out, ""))
+            }
+            "symbolic-ref" => match self.current_branch(&dir) {
+                Some(branch) if has("--short") => Ok(Self::output(0, &format!("{branch}\n"), "")),
+                Some(branch) => Ok(Self::output(0, &format!("refs/heads/{branch}\n"), "")),
+                None => Ok(Self::output(1, "", "")),
+            },
+            "branch" if has("--show-current") => {
+                let branch = self.current_branch(&dir).unwrap_or_default();
+                Ok(Self::output(0, &format!("{branch}\n"), ""))
+            }
+            "rev-parse" if has("--abbrev-ref") && has("HEAD") => {
+                let branch = self.current_branch(&dir);
+                let out = format!("{}\n", branch.as_deref().unwrap_or("HEAD"));
+                Ok(Self::output(0, &out, ""))
+            }
+            "pull" => match self.update(&dir) {
+                Ok(()) => Ok(Self::output(0, "", "")),
+                Err(e) => fatal(e),
+            },
+            _ => {
+                if let Err(e) = self.record(FakeCall::Git {
+                    dir: dir.clone(),
+                    args: args.clone(),
+                }) {
+                    return fatal(e);
+                }
+                let scripted = lock(&self.responses)
+                    .iter()
+                    .find(|(prefix, _)| args.starts_with(prefix))
+                    .map(|(_, output)| output.clone());
+                if let Some(output) = scripted {
+                    return Ok(output);
+                }
+                if args.starts_with(&["remote".into(), "get-url".into(), "origin".into()]) {
+                    return match self.repo(&dir).and_then(|r| r.url) {
+                        Some(url) => Ok(Self::output(0, &format!("{url}\n"), "")),
+                        None => Ok(Self::output(2, "", "error: No such remote 'origin'\n")),
+                    };
+                }
+                Ok(Self::output(0, "", ""))
+            }
+        }
+    }
+}
+
+const ZERO: &str = "0000000000000000000000000000000000000000";
+
+/// The directory git would run in and the arguments after the global
+/// options (`-c <config>`, `-C <dir>`, `--no-pager`, ...).
+fn strip_global_options(mut dir: PathBuf, args: Vec<String>) -> (PathBuf, Vec<String>) {
+    let mut rest = args.as_slice();
+    while let [flag, tail @ ..] = rest {
+        match (flag.as_str(), tail) {
+            ("-C", [path, tail @ ..]) => {
+                dir = dir.join(path);
+                rest = tail;
+            }
+            ("-c", [_, tail @ ..]) => rest = tail,
+            (flag, _) if flag.starts_with('-') => rest = tail,
+            _ => break,
+        }
+    }
+    (dir, rest.to_vec())
+}
+
+/// The non-option arguments of `git clone`: the source, then the target.
+fn clone_positionals(args: &[String]) -> Vec<&str> {
+    const WITH_VALUE: &[&str] = &[
+        "--depth",
+        "--reference",
+        "--reference-if-able",
+        "--branch",
+        "-b",
+        "--origin",
+        "-o",
+        "--config",
+        "-c",
+        "--jobs",
+        "-j",
+        "--template",
+        "--separate-git-dir",
+        "--upload-pack",
+        "-u",
+    ];
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            positional.extend(args.by_ref().map(String::as_str));
+        } else if WITH_VALUE.contains(&arg.as_str()) {
+            args.next();
+        } else if !arg.starts_with('-') {
+            positional.push(arg.as_str());
+        }
+    }
+    positional
+}
+
+/// `target` without the `.partial-<pid>` suffix of a staged clone.
+fn final_clone_target(target: &Path) -> PathBuf {
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    match name.rsplit_once(".partial-") {
+        Some((base, pid)) if pid.chars().all(|c| c.is_ascii_digit()) => target.with_file_name(base),
+        _ => target.to_path_buf(),
+    }
+}
+
+impl Vcs for FakeGit {
+    fn kind(&self) -> VcsKind {
+        VcsKind::Git
+    }
+
+    fn clone_repo(&self, url: &str, target_dir: &Path, _pb: Option<&ProgressBar>) -> Result<()> {
+        self.record(FakeCall::Clone {
+            url: url.to_string(),
+            target: target_dir.to_path_buf(),
+        })?;
+        std::fs::create_dir_all(target_dir)?;
+        self.add_repo(
+            target_dir,
+            FakeRepo {
+                url: Some(url.to_string()),
+                ..Default::default()
+            },
+        );
+        Ok(())
+    }
+
+    fn status(&self, repo_path: &Path) -> Result<GitStatusSummary> {
+        self.record(FakeCall::Status(repo_path.to_path_buf()))?;
+        self.known(repo_path)?;
+        Ok(lock(&self.repos)[repo_path].status.clone())
+    }
+
+    fn current_branch(&self, repo_path: &Path) -> Option<String> {
+        self.record(FakeCall::CurrentBranch(repo_path.to_path_buf()))
+            .ok()?;
+        lock(&self.repos).get(repo_path)?.branch.clone()
+    }
+
+    fn update(&self, repo_path: &Path) -> Result<()> {
+        self.record(FakeCall::Update(repo_path.to_path_buf()))?;
+        self.known(repo_path)?;
+        self.edit(repo_path, |repo| repo.updates += 1);
+        Ok(())
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A workspace in a temp dir, removed when dropped.
+pub struct TestWorkspace {
+    dir: tempfile::TempDir,
+    repos: Vec<String>,
+}
+
+impl TestWorkspace {
+    /// A workspace whose `.meta` lists `count` projects, `repo-0000` on,
+    /// each with a stub checkout: a `.git` directory with just a `HEAD` on
+    /// `main`, enough for [`VcsKind::detect`] and directory checks. Pair it
+    /// with [`fake_git`](Self::fake_git).
+    pub fn with_repos(count: usize) -> Result<Self> {
+        let ws = Self::empty(count)?;
+        for name in &ws.repos {
+            let git_dir = ws.root().join(name).join(".git");
+            std::fs::create_dir_all(&git_dir)?;
+            std::fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n")?;
+        }
+        Ok(ws)
+    }
+
+    /// Like [`with_repos`](Self::with_repos), with real git repos of `files`
+    /// committed files each, for tests of code that runs git itself.
+    pub fn with_git_repos(count: usize, files: usize) -> Result<Self> {
+        let dir = tempfile::tempdir()?;
+        let repos = crate::synthetic::generate(dir.path(), count, files)?.repos;
+        Ok(TestWorkspace { dir, repos })
+    }
+
+    /// A workspace whose `.meta` lists `count` projects that aren't cloned.
+    pub fn with_missing_repos(count: usize) -> Result<Self> {
+        Self::empty(count)
+    }
+
+    fn empty(count: usize) -> Result<Self> {
+        let dir = tempfile::tempdir()?;
+        let repos: Vec<String> = (0..count).map(|i| format!("repo-{i:04}")).collect();
+        let projects: serde_json::Map<String, serde_json::Value> = repos
+            .iter()
+            .map(|name| (name.clone(), Self::url_of(name).into()))
+            .collect();
+        std::fs::write(
+            dir.path().join(".meta"),
+            serde_json::json!({ "projects": projects }).to_string(),
+        )?;
+        Ok(TestWorkspace { dir, repos })
+    }
+
+    /// The URL the `.meta` lists for project `name`.
+    pub fn url_of(name: &str) -> String {
+        format!("git@example.com:test/{name}.git")
+    }
+
+    pub fn root(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Project names, in order.
+    pub fn repos(&self) -> &[String] {
+        &self.repos
+    }
+
+    /// Path of the `index`th project.
+    pub fn repo_path(&self, index: usize) -> PathBuf {
+        self.root().join(&self.repos[index])
+    }
+
+    /// A [`FakeGit`] knowing every checkout present in the workspace, each
+    /// clean and on `main`.
+    pub fn fake_git(&self) -> FakeGit {
+        let git = FakeGit::new();
+        for i in 0..self.repos.len() {
+            let path = self.repo_path(i);
+            if path.exists() {
+                git.add_repo(
+                    &path,
+                    FakeRepo {
+                        url: Some(Self::url_of(&self.repos[i])),
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+        git
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clone_queue::CloneQueue;
+    use crate::vcs::GitVcs;
+    use crate::worktree::git_ops::{git_check_ignore, git_status_summary};
+    use std::sync::Arc;
+
+    #[test]
+    fn fake_git_answers_from_state_and_records_calls() {
+        let ws = TestWorkspace::with_repos(3).unwrap();
+        assert_eq!(VcsKind::detect(&ws.repo_path(2)), Some(VcsKind::Git));
+        let git = ws.fake_git();
+        git.set_dirty(&ws.repo_path(1), &["src/lib.rs"]);
+        git.set_branch(&ws.repo_path(2), "feature");
+        git.fail(FakeOperation::Update, &ws.repo_path(0));
+
+        assert!(!git.status(&ws.repo_path(0)).unwrap().dirty);
+        assert_eq!(
+            git.status(&ws.repo_path(1)).unwrap().modified_files,
+            ["src/lib.rs"]
+        );
+        assert_eq!(
+            git.current_branch(&ws.repo_path(2)).as_deref(),
+            Some("feature")
+        );
+        assert!(git.update(&ws.repo_path(0)).is_err());
+        git.update(&ws.repo_path(1)).unwrap();
+        assert_eq!(git.repo(&ws.repo_path(1)).unwrap().updates, 1);
+        assert_eq!(git.calls_of(FakeOperation::Update).len(), 2);
+        assert_eq!(git.calls().len(), 5);
+    }
+
+    #[test]
+    fn fake_clones_register_repos() {
+        let ws = TestWorkspace::with_missing_repos(2).unwrap();
+        let git = ws.fake_git();
+        assert!(git.status(&ws.repo_path(0)).is_err());
+        let url = TestWorkspace::url_of(&ws.repos()[0]);
+        git.clone_repo(&url, &ws.repo_path(0), None).unwrap();
+        assert!(ws.repo_path(0).is_dir());
+        assert_eq!(git.repo(&ws.repo_path(0)).unwrap().url, Some(url));
+        assert!(meta_core::config::find_meta_config_in(ws.root()).is_some());
+    }
+
+    #[test]
+    fn subsystems_run_git_through_an_installed_fake() {
+        let ws = TestWorkspace::with_repos(2).unwrap();
+        let git = Arc::new(ws.fake_git());
+        git.set_dirty(&ws.repo_path(0), &["src/lib.rs"]);
+        git.set_branch(&ws.repo_path(1), "feature");
+        git.respond(&["rev-parse", "HEAD"], FakeGit::output(0, "abc123\n", ""));
+        git.respond(
+            &["check-ignore"],
+            FakeGit::output(0, ".gitignore\x001\x00target/\x00target/x\x00", ""),
+        );
+
+        git_runner::with_runner(git.clone(), || {
+            let ignored = git_check_ignore(&ws.repo_path(0), &["target/x".to_string()]).unwrap();
+            assert_eq!(ignored[0].0, "target/x");
+            assert!(ignored[0].1.is_some());
+            let status = git_status_summary(&ws.repo_path(0)).unwrap();
+            assert_eq!(status.modified_files, ["src/lib.rs"]);
+            assert_eq!(
+                GitVcs.current_branch(&ws.repo_path(1)).as_deref(),
+                Some("feature")
+            );
+            assert_eq!(
+                crate::get_remote_url(&ws.repo_path(0)),
+                Some(TestWorkspace::url_of(&ws.repos()[0]))
+            );
+            let head = git_runner::output(
+                std::process::Command::new("git")
+                    .args(["-c", "core.quotePath=false", "rev-parse", "HEAD"])
+                    .current_dir(ws.repo_path(0)),
+            )
+            .unwrap();
+            assert_eq!(head.stdout, b"abc123\n");
+        });
+        assert_eq!(git.calls_of(FakeOperation::Status).len(), 1);
+        assert_eq!(git.calls_of(FakeOperation::CurrentBranch).len(), 1);
+        assert_eq!(
+            git.calls_of(FakeOperation::Git).last(),
+            Some(&FakeCall::Git {
+                dir: ws.repo_path(0),
+                args: vec!["rev-parse".to_string(), "HEAD".to_string()],
+            })
+        );
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn clone_queue_clones_through_an_injected_fake() {
+        let data = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", data.path());
+        let ws = TestWorkspace::with_missing_repos(3).unwrap();
+        let git = Arc::new(FakeGit::new());
+        let queue = CloneQueue::new(None, None).with_git_runner(git.clone());
+        queue.push_from_meta(ws.root(), 0).unwrap();
+        let report = queue.run(2, &());
+
+        assert_eq!((report.succeeded, report.failed), (3, 0));
+        let mut cloned: Vec<FakeCall> = git.calls_of(FakeOperation::Clone);
+        cloned.sort_by(|a, b| a.path().cmp(b.path()));
+        for (i, call) in cloned.iter().enumerate() {
+            let url = TestWorkspace::url_of(&ws.repos()[i]);
+            assert_eq!(
+                call,
+                &FakeCall::Clone {
+                    url: url.clone(),
+                    target: ws.repo_path(i),
+                }
+            );
+            assert!(ws.repo_path(i).join(".git").is_dir());
+            assert_eq!(git.repo(&ws.repo_path(i)).unwrap().url, Some(url));
+        }
+        assert!(git_runner::installed().is_none());
+        std::env::remove_var("META_DATA_DIR");
+    }
+}
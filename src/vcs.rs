@@ -0,0 +1,764 @@
+//! Version control abstraction for mixed workspaces.
+//!
+//! Git is the primary backend and the only one with full feature support.
+//! Other systems get a minimal implementation of the [`Vcs`] trait so a
+//! workspace that includes them can still be cloned and status'd.
+//!
+//! Jujutsu repos colocated with git (both `.jj` and `.git` present) are
+//! treated as git repos, since jj keeps the git store in sync. Operations
+//! that would move git's HEAD behind jj's back check [`is_jj_colocated`]
+//! and skip with a clear reason instead; updates go through jj.
+
+use anyhow::{Context, Result};
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Instant;
+
+use crate::hooks::{fire_post_update, UpdateRepoResult};
+use crate::project_options::{load_project_options, ProjectOperation, ProjectOptions};
+use crate::worktree::git_ops::git_status_summary;
+use crate::worktree::helpers::load_projects_with_root;
+use crate::worktree::types::GitStatusSummary;
+
+/// Supported version control systems.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VcsKind {
+    #[default]
+    Git,
+    Hg,
+    Jj,
+}
+
+impl VcsKind {
+    /// Detect the version control system of an existing checkout.
+    pub fn detect(repo_path: &Path) -> Option<VcsKind> {
+        if repo_path.join(".git").exists() {
+            // Colocated jj repos are also valid git repos
+            Some(VcsKind::Git)
+        } else if repo_path.join(".jj").is_dir() {
+            Some(VcsKind::Jj)
+        } else if repo_path.join(".hg").is_dir() {
+            Some(VcsKind::Hg)
+        } else {
+            None
+        }
+    }
+
+    /// Name of the command-line tool for this system.
+    pub fn command(self) -> &'static str {
+        match self {
+            VcsKind::Git => "git",
+            VcsKind::Hg => "hg",
+            VcsKind::Jj => "jj",
+        }
+    }
+}
+
+impl std::fmt::Display for VcsKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.command())
+    }
+}
+
+/// Minimal set of operations every backend must support.
+pub trait Vcs: Send + Sync {
+    /// Which system this backend drives.
+    fn kind(&self) -> VcsKind;
+
+    /// Clone `url` into `target_dir`, reporting through `pb` if given.
+    fn clone_repo(&self, url: &str, target_dir: &Path, pb: Option<&ProgressBar>) -> Result<()>;
+
+    /// Summarize working-copy changes.
+    fn status(&self, repo_path: &Path) -> Result<GitStatusSummary>;
+
+    /// Current branch (or bookmark) name, if any.
+    fn current_branch(&self, repo_path: &Path) -> Option<String>;
+
+    /// Bring the checkout up to date with its default remote.
+    fn update(&self, repo_path: &Path) -> Result<()>;
+}
+
+/// Return the backend implementation for `kind`.
+pub fn for_kind(kind: VcsKind) -> Box<dyn Vcs> {
+    match kind {
+        VcsKind::Git => Box::new(GitVcs),
+        VcsKind::Hg => Box::new(HgVcs),
+        VcsKind::Jj => Box::new(JjVcs),
+    }
+}
+
+/// Detect the system used by `repo_path` and return its backend.
+pub fn detect(repo_path: &Path) -> Option<Box<dyn Vcs>> {
+    VcsKind::detect(repo_path).map(for_kind)
+}
+
+/// Put a git checkout on a configured `branch` or `git_ref`.
+///
+/// A ref (commit, tag, or other revision) is checked out detached, fetched
+/// from `origin` first if the repo doesn't have it; it takes precedence over
+/// `branch`. A branch is checked out, creating a local branch tracking
+/// `origin/<branch>` if there is none. Does nothing when neither is given.
+///
+/// Both come from `.meta`, so they are validated with
+/// [`check_configured_refs`] and passed after `--end-of-options`.
+pub fn checkout_configured(
+    repo_path: &Path,
+    branch: Option<&str>,
+    git_ref: Option<&str>,
+) -> Result<()> {
+    check_configured_refs(branch, git_ref)?;
+    let git = |args: &[&str]| run(VcsKind::Git, repo_path, args);
+    let verify = |rev: &str| git(&["rev-parse", "--verify", "--quiet", "--end-of-options", rev]);
+    if let Some(git_ref) = git_ref {
+        let commit = format!("{git_ref}^{{commit}}");
+        if verify(&commit).is_ok() {
+            git(&[
+                "checkout",
+                "--quiet",
+                "--detach",
+                "--end-of-options",
+                git_ref,
+            ])?;
+        } else {
+            git(&["fetch", "--quiet", "--end-of-options", "origin", git_ref])?;
+            git(&["checkout", "--quiet", "--detach", "FETCH_HEAD"])?;
+        }
+        return Ok(());
+    }
+    let Some(branch) = branch else {
+        return Ok(());
+    };
+    if GitVcs.current_branch(repo_path).as_deref() == Some(branch) {
+        return Ok(());
+    }
+    let local = format!("refs/heads/{branch}");
+    if verify(&local).is_ok() {
+        git(&["checkout", "--quiet", "--end-of-options", branch])?;
+        return Ok(());
+    }
+    let remote = format!("refs/remotes/origin/{branch}");
+    if verify(&remote).is_err() {
+        // Single-branch (e.g. shallow) clones only track the default branch
+        git(&["remote", "set-branches", "--add", "origin", branch])?;
+        let refspec = format!("+{local}:{remote}");
+        git(&["fetch", "--quiet", "--end-of-options", "origin", &refspec])?;
+    }
+    let upstream = format!("origin/{branch}");
+    git(&[
+        "checkout",
+        "--quiet",
+        "--track",
+        "-b",
+        branch,
+        "--end-of-options",
+        &upstream,
+    ])?;
+    Ok(())
+}
+
+/// Reject a configured `branch` or `git_ref` that isn't a well-formed ref
+/// name (`git check-ref-format`) or that could be read as an option.
+pub fn check_configured_refs(branch: Option<&str>, git_ref: Option<&str>) -> Result<()> {
+    let checks = [
+        (branch, &["check-ref-format", "--branch"][..]),
+        (git_ref, &["check-ref-format", "--allow-onelevel"][..]),
+    ];
+    for (name, args) in checks {
+        let Some(name) = name else { continue };
+        let valid = !name.starts_with('-')
+            && crate::git_runner::status(
+                Command::new("git")
+                    .args(args)
+                    .arg(name)
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null()),
+            )
+            .is_ok_and(|s| s.success());
+        if !valid {
+            anyhow::bail!("Invalid branch or ref '{name}' in .meta");
+        }
+    }
+    Ok(())
+}
+
+/// Update a project's checkout, honoring its configured `branch` or `ref`.
+///
+/// A project pinned to a ref is fetched and kept on it; one with a branch is
+/// switched back to that branch if needed and fast-forwarded. Other projects
+/// are updated with their backend's [`Vcs::update`].
+pub fn update_project(repo_path: &Path, options: &ProjectOptions) -> Result<()> {
+    if options.vcs != VcsKind::Git || (options.branch.is_none() && options.git_ref.is_none()) {
+        return for_kind(options.vcs).update(repo_path);
+    }
+    crate::read_only::check("update")?;
+    if let Some(reason) = jj_colocated_skip_reason(repo_path, "checking out the configured ref") {
+        anyhow::bail!(reason);
+    }
+    let _lock = crate::lock::RepoLock::acquire(repo_path, "update")?;
+    run(VcsKind::Git, repo_path, &["fetch", "--quiet", "origin"])?;
+    checkout_configured(
+        repo_path,
+        options.branch.as_deref(),
+        options.git_ref.as_deref(),
+    )?;
+    if options.git_ref.is_none() {
+        run(VcsKind::Git, repo_path, &["pull", "--ff-only"])?;
+    }
+    Ok(())
+}
+
+/// Update every project of the workspace at `meta_dir` that is checked out
+/// and not excluded from updates, with [`update_project`], then fire the
+/// `post-update` hook with the results.
+///
+/// A repo that fails doesn't stop the others.
+pub fn update_workspace(meta_dir: &Path) -> Result<Vec<UpdateRepoResult>> {
+    crate::read_only::check("update")?;
+    let started = Instant::now();
+    let options = load_project_options(meta_dir);
+    let mut results = Vec::new();
+    for project in load_projects_with_root(meta_dir, true)? {
+        let project_options = options.get(&project.name).cloned().unwrap_or_default();
+        if project_options.skips(ProjectOperation::Update) {
+            continue;
+        }
+        let repo_path = meta_dir.join(&project.path);
+        if !repo_path.is_dir() {
+            continue;
+        }
+        let repo_started = Instant::now();
+        let result = update_project(&repo_path, &project_options);
+        results.push(UpdateRepoResult::new(
+            &project.name,
+            &repo_path,
+            repo_started,
+            &result,
+        ));
+    }
+    fire_post_update(&results, started.elapsed(), meta_dir);
+    Ok(results)
+}
+
+/// Check whether `repo_path` is a jj repo colocated with a git repo.
+///
+/// In colocated repos git's HEAD is detached and owned by jj, so branch
+/// checkouts, worktree creation, and snapshot restores must not touch it.
+pub fn is_jj_colocated(repo_path: &Path) -> bool {
+    repo_path.join(".jj").is_dir() && repo_path.join(".git").exists()
+}
+
+/// Reason for skipping an operation that would rewrite git's HEAD in a
+/// colocated jj repo, or `None` if the repo is safe to operate on.
+pub fn jj_colocated_skip_reason(repo_path: &Path, operation: &str) -> Option<String> {
+    if !is_jj_colocated(repo_path) {
+        return None;
+    }
+    Some(format!(
+        "{} is a colocated jj repo; skipping {} because it would move git HEAD \
+         behind jj's back (use the jj equivalent instead)",
+        repo_path.display(),
+        operation
+    ))
+}
+
+/// Run a VCS command in `repo_path`, returning stdout or an error with stderr.
+fn run(kind: VcsKind, repo_path: &Path, args: &[&str]) -> Result<String> {
+    let output = crate::ssh_multiplexing::output_with_mux_recovery(
+        crate::credentials::suppress_prompts(&mut Command::new(kind.command()))
+            .args(args)
+            .current_dir(repo_path)
+            .stdin(Stdio::null()),
+        &crate::ssh_multiplexing::MultiplexingConfig::for_path(repo_path),
+    )
+    .with_context(|| format!("Failed to run {kind}. Is it installed?"))?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} {} failed in {}: {}",
+            kind,
+            args.join(" "),
+            repo_path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Git backend, delegating to the existing git helpers.
+pub struct GitVcs;
+
+impl Vcs for GitVcs {
+    fn kind(&self) -> VcsKind {
+        VcsKind::Git
+    }
+
+    fn clone_repo(&self, url: &str, target_dir: &Path, pb: Option<&ProgressBar>) -> Result<()> {
+        crate::clone_repo_with_progress(url, target_dir, pb)
+    }
+
+    fn status(&self, repo_path: &Path) -> Result<GitStatusSummary> {
+        git_status_summary(repo_path)
+    }
+
+    fn current_branch(&self, repo_path: &Path) -> Option<String> {
+        let branch = run(
+            VcsKind::Git,
+            repo_path,
+            &["symbolic-ref", "--quiet", "--short", "HEAD"],
+        )
+        .ok()?;
+        Some(branch.trim().to_string()).filter(|b| !b.is_empty())
+    }
+
+    fn update(&self, repo_path: &Path) -> Result<()> {
+        if is_jj_colocated(repo_path) {
+            // `git pull` would move HEAD behind jj's back; let jj fetch instead
+            return JjVcs.update(repo_path);
+        }
+        crate::read_only::check("update")?;
+        let _lock = crate::lock::RepoLock::acquire(repo_path, "update")?;
+        run(VcsKind::Git, repo_path, &["pull", "--ff-only"]).map(|_| ())
+    }
+}
+
+/// Mercurial backend.
+pub struct HgVcs;
+
+impl Vcs for HgVcs {
+    fn kind(&self) -> VcsKind {
+        VcsKind::Hg
+    }
+
+    fn clone_repo(&self, url: &str, target_dir: &Path, pb: Option<&ProgressBar>) -> Result<()> {
+        crate::read_only::check("clone repository")?;
+        if target_dir.exists() {
+            if let Some(pb) = pb {
+                pb.finish_with_message(format!(
+                    "{}: already exists, skipping",
+                    target_dir.display()
+                ));
+            }
+            return Ok(());
+        }
+        if let Some(pb) = pb {
+            pb.set_message(format!("Cloning {url} (hg)"));
+        }
+        let parent = target_dir.parent().unwrap_or(Path::new("."));
+        let result = run(
+            VcsKind::Hg,
+            parent,
+            &["clone", url, &target_dir.to_string_lossy()],
+        );
+        if let Some(pb) = pb {
+            match &result {
+                Ok(_) => pb.finish_with_message(format!("{} ✓", target_dir.display())),
+                Err(_) => pb.finish_with_message(format!(
+                    "Failed to clone {} into {}",
+                    url,
+                    target_dir.display()
+                )),
+            }
+        }
+        result.map(|_| ())
+    }
+
+    fn status(&self, repo_path: &Path) -> Result<GitStatusSummary> {
+        let text = run(VcsKind::Hg, repo_path, &["status"])?;
+        Ok(parse_hg_status(&text))
+    }
+
+    fn current_branch(&self, repo_path: &Path) -> Option<String> {
+        run(VcsKind::Hg, repo_path, &["branch"])
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    }
+
+    fn update(&self, repo_path: &Path) -> Result<()> {
+        crate::read_only::check("update")?;
+        run(VcsKind::Hg, repo_path, &["pull", "-u"]).map(|_| ())
+    }
+}
+
+/// Jujutsu backend for repos that are not colocated with git. Colocated
+/// repos use it only for [`Vcs::update`].
+pub struct JjVcs;
+
+impl Vcs for JjVcs {
+    fn kind(&self) -> VcsKind {
+        VcsKind::Jj
+    }
+
+    fn clone_repo(&self, url: &str, target_dir: &Path, pb: Option<&ProgressBar>) -> Result<()> {
+        crate::read_only::check("clone repository")?;
+        if target_dir.exists() {
+            if let Some(pb) = pb {
+                pb.finish_with_message(format!(
+                    "{}: already exists, skipping",
+                    target_dir.display()
+                ));
+            }
+            return Ok(());
+        }
+        if let Some(pb) = pb {
+            pb.set_message(format!("Cloning {url} (jj)"));
+        }
+        let parent = target_dir.parent().unwrap_or(Path::new("."));
+        let result = run(
+            VcsKind::Jj,
+            parent,
+            &["git", "clone", url, &target_dir.to_string_lossy()],
+        );
+        if let Some(pb) = pb {
+            match &result {
+                Ok(_) => pb.finish_with_message(format!("{} ✓", target_dir.display())),
+                Err(_) => pb.finish_with_message(format!(
+                    "Failed to clone {} into {}",
+                    url,
+                    target_dir.display()
+                )),
+            }
+        }
+        result.map(|_| ())
+    }
+
+    fn status(&self, repo_path: &Path) -> Result<GitStatusSummary> {
+        let text = run(VcsKind::Jj, repo_path, &["diff", "--summary"])?;
+        Ok(parse_jj_diff_summary(&text))
+    }
+
+    fn current_branch(&self, repo_path: &Path) -> Option<String> {
+        // jj has no current branch; report the bookmarks on the working-copy parent
+        run(
+            VcsKind::Jj,
+            repo_path,
+            &["log", "-r", "@-", "--no-graph", "-T", "bookmarks"],
+        )
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+    }
+
+    fn update(&self, repo_path: &Path) -> Result<()> {
+        crate::read_only::check("update")?;
+        run(VcsKind::Jj, repo_path, &["git", "fetch"]).map(|_| ())
+    }
+}
+
+/// Parse `jj diff --summary` output ("M file", "A file", ...).
+///
+/// jj snapshots new files automatically, so there is no untracked state.
+fn parse_jj_diff_summary(text: &str) -> GitStatusSummary {
+    let modified_files: Vec<String> = text
+        .lines()
+        .filter_map(|line| line.split_once(' ').map(|(_, file)| file.to_string()))
+        .collect();
+
+    GitStatusSummary {
+        dirty: !modified_files.is_empty(),
+        modified_files,
+        untracked_count: 0,
+        untracked_files: Vec::new(),
+        renames: Vec::new(),
+    }
+}
+
+/// Parse `hg status` output ("M file", "? file", ...) into a status summary.
+fn parse_hg_status(text: &str) -> GitStatusSummary {
+    let mut modified_files = Vec::new();
+    let mut untracked_files = Vec::new();
+
+    for line in text.lines() {
+        let Some((code, file)) = line.split_once(' ') else {
+            continue;
+        };
+        match code {
+            "?" => untracked_files.push(file.to_string()),
+            // Ignored files only appear with --ignored; never count them
+            "I" => {}
+            _ => modified_files.push(file.to_string()),
+        }
+    }
+
+    GitStatusSummary {
+        dirty: !modified_files.is_empty() || !untracked_files.is_empty(),
+        modified_files,
+        untracked_count: untracked_files.len(),
+        untracked_files,
+        renames: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{git, repo};
+
+    #[test]
+    fn detect_git_repo() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir(tmp.path().join(".git")).unwrap();
+        assert_eq!(VcsKind::detect(tmp.path()), Some(VcsKind::Git));
+    }
+
+    #[test]
+    fn detect_git_worktree_file() {
+        // Linked worktrees have a `.git` file instead of a directory
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join(".git"), "gitdir: /elsewhere").unwrap();
+        assert_eq!(VcsKind::detect(tmp.path()), Some(VcsKind::Git));
+    }
+
+    #[test]
+    fn detect_hg_repo() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir(tmp.path().join(".hg")).unwrap();
+        assert_eq!(VcsKind::detect(tmp.path()), Some(VcsKind::Hg));
+    }
+
+    #[test]
+    fn detect_colocated_jj_as_git() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir(tmp.path().join(".git")).unwrap();
+        std::fs::create_dir(tmp.path().join(".jj")).unwrap();
+        assert_eq!(VcsKind::detect(tmp.path()), Some(VcsKind::Git));
+        assert!(is_jj_colocated(tmp.path()));
+        assert!(jj_colocated_skip_reason(tmp.path(), "restore")
+            .unwrap()
+            .contains("colocated jj repo"));
+    }
+
+    #[test]
+    fn detect_standalone_jj() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir(tmp.path().join(".jj")).unwrap();
+        assert_eq!(VcsKind::detect(tmp.path()), Some(VcsKind::Jj));
+        assert!(!is_jj_colocated(tmp.path()));
+    }
+
+    #[test]
+    fn colocated_jj_updates_through_jj() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir(tmp.path().join(".git")).unwrap();
+        std::fs::create_dir(tmp.path().join(".jj")).unwrap();
+        // Not a real repo, so this fails either way; the error names the tool
+        let error = GitVcs.update(tmp.path()).unwrap_err();
+        let message = format!("{error:#}");
+        assert!(message.contains("jj"), "{message}");
+        assert!(!message.contains("pull"), "{message}");
+    }
+
+    #[test]
+    fn plain_git_repo_has_no_skip_reason() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir(tmp.path().join(".git")).unwrap();
+        assert!(jj_colocated_skip_reason(tmp.path(), "restore").is_none());
+    }
+
+    #[test]
+    fn parse_jj_diff_summary_has_no_untracked() {
+        let summary = parse_jj_diff_summary("M src/lib.rs\nA new.rs\n");
+        assert!(summary.dirty);
+        assert_eq!(summary.modified_files, vec!["src/lib.rs", "new.rs"]);
+        assert_eq!(summary.untracked_count, 0);
+    }
+
+    #[test]
+    fn detect_plain_dir_returns_none() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert!(VcsKind::detect(tmp.path()).is_none());
+        assert!(detect(tmp.path()).is_none());
+    }
+
+    #[test]
+    fn vcs_kind_deserializes_lowercase() {
+        let kind: VcsKind = serde_json::from_str("\"hg\"").unwrap();
+        assert_eq!(kind, VcsKind::Hg);
+        assert_eq!(kind.to_string(), "hg");
+    }
+
+    #[test]
+    fn parse_hg_status_counts_changes() {
+        let summary = parse_hg_status("M src/lib.rs\nA new.rs\n? scratch.txt\n");
+        assert!(summary.dirty);
+        assert_eq!(summary.modified_files, vec!["src/lib.rs", "new.rs"]);
+        assert_eq!(summary.untracked_count, 1);
+    }
+
+    #[test]
+    fn parse_hg_status_clean() {
+        let summary = parse_hg_status("");
+        assert!(!summary.dirty);
+    }
+
+    #[test]
+    fn clones_and_updates_honor_configured_branch_and_ref() {
+        let tmp = tempfile::tempdir().unwrap();
+        let origin = tmp.path().join("origin");
+        repo(&origin, &[]);
+        let pinned = git(&origin, &["rev-parse", "HEAD"]);
+        git(&origin, &["commit", "-q", "--allow-empty", "-m", "two"]);
+        git(&origin, &["branch", "release"]);
+        let url = origin.to_string_lossy().into_owned();
+
+        let on_branch = tmp.path().join("on-branch");
+        let options = crate::CloneOptions {
+            branch: Some("release".into()),
+            ..Default::default()
+        };
+        crate::clone_repo_with_options(&url, &on_branch, None, &options).unwrap();
+        assert_eq!(git(&on_branch, &["branch", "--show-current"]), "release");
+
+        // Adopting an existing checkout puts it on the configured branch too
+        git(&on_branch, &["checkout", "-q", "main"]);
+        let adopt = crate::CloneOptions {
+            collision: crate::collision::CollisionPolicy::Adopt,
+            ..options.clone()
+        };
+        let result = crate::clone_repo_with_options(&url, &on_branch, None, &adopt).unwrap();
+        assert_eq!(result.action, crate::collision::CloneAction::Adopted);
+        assert_eq!(git(&on_branch, &["branch", "--show-current"]), "release");
+
+        let pinned_vendor = tmp.path().join("vendor");
+        let options = crate::CloneOptions {
+            branch: Some("release".into()),
+            single_branch: true,
+            ..Default::default()
+        };
+        crate::clone_repo_with_options(&url, &pinned_vendor, None, &options).unwrap();
+        assert_eq!(
+            git(&pinned_vendor, &["branch", "--show-current"]),
+            "release"
+        );
+        assert_eq!(
+            git(
+                &pinned_vendor,
+                &["branch", "-r", "--format=%(refname:short)"]
+            ),
+            "origin/release"
+        );
+
+        let on_ref = tmp.path().join("on-ref");
+        let options = crate::CloneOptions {
+            git_ref: Some(pinned.clone()),
+            ..Default::default()
+        };
+        crate::clone_repo_with_options(&url, &on_ref, None, &options).unwrap();
+        assert_eq!(git(&on_ref, &["rev-parse", "HEAD"]), pinned);
+
+        // Update switches back to the configured branch and fast-forwards it
+        git(&on_branch, &["checkout", "-q", "main"]);
+        git(&origin, &["checkout", "-q", "release"]);
+        git(&origin, &["commit", "-q", "--allow-empty", "-m", "three"]);
+        let options = ProjectOptions {
+            branch: Some("release".into()),
+            ..Default::default()
+        };
+        update_project(&on_branch, &options).unwrap();
+        assert_eq!(git(&on_branch, &["branch", "--show-current"]), "release");
+        assert_eq!(
+            git(&on_branch, &["rev-parse", "HEAD"]),
+            git(&origin, &["rev-parse", "release"])
+        );
+
+        // A pinned project stays on its ref
+        let options = ProjectOptions {
+            git_ref: Some(pinned.clone()),
+            ..Default::default()
+        };
+        update_project(&on_ref, &options).unwrap();
+        assert_eq!(git(&on_ref, &["rev-parse", "HEAD"]), pinned);
+    }
+
+    #[test]
+    fn missing_remote_branch_is_fetched_and_tracked() {
+        let tmp = tempfile::tempdir().unwrap();
+        let origin = tmp.path().join("origin");
+        repo(&origin, &[]);
+        git(&origin, &["branch", "develop"]);
+        let clone = tmp.path().join("clone");
+        git(
+            tmp.path(),
+            &[
+                "clone",
+                "-q",
+                "--single-branch",
+                &format!("file://{}", origin.display()),
+                "clone",
+            ],
+        );
+
+        checkout_configured(&clone, Some("develop"), None).unwrap();
+        assert_eq!(git(&clone, &["branch", "--show-current"]), "develop");
+        assert_eq!(
+            git(&clone, &["rev-parse", "--abbrev-ref", "@{upstream}"]),
+            "origin/develop"
+        );
+    }
+
+    #[test]
+    fn option_like_refs_are_rejected() {
+        let tmp = tempfile::tempdir().unwrap();
+        repo(tmp.path(), &[]);
+        let marker = tmp.path().join("ran");
+        let upload_pack = format!("--upload-pack=touch {}", marker.display());
+
+        assert!(checkout_configured(tmp.path(), None, Some(&upload_pack)).is_err());
+        assert!(checkout_configured(tmp.path(), Some(&upload_pack), None).is_err());
+        assert!(checkout_configured(tmp.path(), Some("a..b"), None).is_err());
+        assert!(!marker.exists());
+        assert!(check_configured_refs(Some("feature/x"), Some("v1.0")).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn update_workspace_updates_projects_and_fires_post_update_in_root() {
+        let tmp = tempfile::tempdir().unwrap();
+        let origin = tmp.path().join("origin");
+        repo(&origin, &[]);
+        let ws = tmp.path().join("ws");
+        std::fs::create_dir_all(&ws).unwrap();
+        for name in ["api", "old"] {
+            git(&ws, &["clone", "-q", origin.to_str().unwrap(), name]);
+        }
+        let payload_file = tmp.path().join("payload.json");
+        let cwd_file = tmp.path().join("cwd");
+        std::fs::write(
+            ws.join(".meta"),
+            serde_json::json!({
+                "projects": {
+                    "api": origin.display().to_string(),
+                    "old": {"repo": origin.display().to_string(), "disabled": true},
+                },
+                "hooks": {"post-update": [
+                    "sh",
+                    "-c",
+                    format!("pwd > '{}'; cat > '{}'", cwd_file.display(), payload_file.display()),
+                ]},
+            })
+            .to_string(),
+        )
+        .unwrap();
+        git(&origin, &["commit", "-q", "--allow-empty", "-m", "two"]);
+
+        let results = update_workspace(&ws).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].success, "{results:?}");
+        let head = git(&origin, &["rev-parse", "HEAD"]);
+        assert_eq!(git(&ws.join("api"), &["rev-parse", "HEAD"]), head);
+        assert_ne!(git(&ws.join("old"), &["rev-parse", "HEAD"]), head);
+
+        let payload: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&payload_file).unwrap()).unwrap();
+        assert_eq!(payload["action"], "update");
+        assert_eq!(payload["repos"][0]["name"], "api");
+        let cwd = std::fs::read_to_string(&cwd_file).unwrap();
+        assert_eq!(
+            Path::new(cwd.trim()).canonicalize().unwrap(),
+            ws.canonicalize().unwrap()
+        );
+    }
+}
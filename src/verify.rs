@@ -0,0 +1,226 @@
+//! Checking that a workspace's work exists somewhere besides this machine.
+//!
+//! [`all_work_pushed`] answers "is it safe to wipe this machine?" for laptop
+//! migrations and off-boarding: it scans every cloned repo of the workspace
+//! (nested ones included) and every checkout of its worktrees for commits no
+//! remote-tracking ref contains, uncommitted or untracked files, and stashes.
+//! A repo that can't be inspected counts as at risk.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::workspace_model::WorkspaceModel;
+use crate::worktree::git_ops::git_status_summary;
+use crate::worktree::store::store_entries_for;
+use crate::worktree::unpushed::{all_branches_unpushed, repo_unpushed};
+
+/// Result of [`all_work_pushed`].
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BackupReport {
+    /// Repos and worktree checkouts scanned
+    pub scanned: usize,
+    /// Those with work that only exists here
+    pub at_risk: Vec<RepoRisk>,
+}
+
+impl BackupReport {
+    /// Whether wiping the machine would lose nothing.
+    pub fn is_safe(&self) -> bool {
+        self.at_risk.is_empty()
+    }
+}
+
+/// Work in one repo or worktree checkout that no remote has.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RepoRisk {
+    /// Project key from the workspace root (`.` for the meta repo itself),
+    /// or the repo alias within a worktree
+    pub name: String,
+    /// Worktree the checkout belongs to, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub worktree: Option<String>,
+    pub path: PathBuf,
+    /// Branches with commits no remote-tracking ref contains, as
+    /// (branch, commits); `HEAD` for a detached checkout
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unpushed: Vec<(String, usize)>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub modified_files: Vec<String>,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub untracked: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub stashes: usize,
+    /// The repo has no remote at all
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub no_remote: bool,
+    /// Why the repo couldn't be inspected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RepoRisk {
+    fn is_empty(&self) -> bool {
+        self.unpushed.is_empty()
+            && self.modified_files.is_empty()
+            && self.untracked == 0
+            && self.stashes == 0
+            && !self.no_remote
+            && self.error.is_none()
+    }
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+/// Scan the workspace at `meta_dir` and its worktrees for work that exists
+/// only on this machine.
+pub fn all_work_pushed(meta_dir: &Path) -> Result<BackupReport> {
+    let mut report = BackupReport::default();
+    let mut scan = |name: String, worktree: Option<String>, path: PathBuf| {
+        if !path.join(".git").exists() {
+            return;
+        }
+        report.scanned += 1;
+        let mut risk = RepoRisk {
+            name,
+            worktree,
+            path,
+            ..Default::default()
+        };
+        if let Err(e) = inspect(&mut risk) {
+            risk.error = Some(format!("{e:#}"));
+        }
+        if !risk.is_empty() {
+            report.at_risk.push(risk);
+        }
+    };
+
+    scan(".".to_string(), None, meta_dir.to_path_buf());
+    let model = WorkspaceModel::load(meta_dir)?;
+    for key in model.keys() {
+        if let Some((path, _)) = model.lookup(key) {
+            scan(key.to_string(), None, path.clone());
+        }
+    }
+
+    for (root, entry) in store_entries_for(meta_dir)? {
+        for repo in &entry.repos {
+            let path = if repo.alias == "." {
+                PathBuf::from(&root)
+            } else {
+                Path::new(&root).join(&repo.alias)
+            };
+            scan(repo.alias.clone(), Some(entry.name.clone()), path);
+        }
+    }
+    Ok(report)
+}
+
+fn inspect(risk: &mut RepoRisk) -> Result<()> {
+    let path = risk.path.clone();
+    let status = git_status_summary(&path)?;
+    risk.modified_files = status.modified_files;
+    risk.untracked = status.untracked_count;
+    risk.no_remote = git_lines(&path, &["remote"])?.is_empty();
+
+    if risk.worktree.is_some() {
+        // Branches and stashes are shared with the main checkout, which is
+        // scanned too; only the checked-out commit is specific to this one
+        risk.unpushed = repo_unpushed(&path, None, None)?
+            .into_iter()
+            .map(|b| (b.name, b.commits.len()))
+            .collect();
+        return Ok(());
+    }
+
+    risk.unpushed = unpushed_branches(&path)?;
+    risk.stashes = git_lines(&path, &["stash", "list"])?.len();
+    Ok(())
+}
+
+/// Local branches of the repo at `path` with commits no remote-tracking ref
+/// contains, as (branch, commits); `HEAD` too if it's detached.
+pub fn unpushed_branches(path: &Path) -> Result<Vec<(String, usize)>> {
+    Ok(all_branches_unpushed(path)?
+        .into_iter()
+        .map(|b| (b.name, b.commits.len()))
+        .collect())
+}
+
+/// Non-empty lines of `git <args>` in `path`.
+pub(crate) fn git_lines(path: &Path, args: &[&str]) -> Result<Vec<String>> {
+    let output = crate::git_runner::output(Command::new("git").args(args).current_dir(path))
+        .with_context(|| format!("Failed to run git {}", args[0]))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed in {}: {}",
+            args[0],
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{git, repo as init_repo};
+
+    #[test]
+    #[serial_test::serial]
+    fn reports_unpushed_dirty_and_stashed_work() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp.path().join("meta-store"));
+        let origin = tmp.path().join("origin");
+        init_repo(&origin, &[]);
+
+        let ws = tmp.path().join("ws");
+        std::fs::create_dir(&ws).unwrap();
+        std::fs::write(
+            ws.join(".meta"),
+            serde_json::json!({"projects": {
+                "api": origin.display().to_string(),
+                "web": origin.display().to_string(),
+            }})
+            .to_string(),
+        )
+        .unwrap();
+        git(&ws, &["clone", "-q", origin.to_str().unwrap(), "api"]);
+        git(&ws, &["clone", "-q", origin.to_str().unwrap(), "web"]);
+
+        let report = all_work_pushed(&ws).unwrap();
+        assert_eq!(report.scanned, 2);
+        assert!(report.is_safe(), "{report:?}");
+
+        let api = ws.join("api");
+        git(&api, &["checkout", "-q", "-b", "feature"]);
+        git(&api, &["commit", "-q", "--allow-empty", "-m", "local only"]);
+        std::fs::write(ws.join("web/notes.txt"), "wip").unwrap();
+        git(&ws.join("web"), &["stash", "-q", "--include-untracked"]);
+        std::fs::write(ws.join("web/scratch.txt"), "more").unwrap();
+
+        let report = all_work_pushed(&ws).unwrap();
+        assert!(!report.is_safe());
+        let summary: Vec<_> = report
+            .at_risk
+            .iter()
+            .map(|r| (r.name.as_str(), r.unpushed.clone(), r.untracked, r.stashes))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("api", vec![("feature".to_string(), 1)], 0, 0),
+                ("web", vec![], 1, 1),
+            ]
+        );
+        std::env::remove_var("META_DATA_DIR");
+    }
+}
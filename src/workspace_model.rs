@@ -0,0 +1,281 @@
+//! Cached model of a nested meta workspace.
+//!
+//! Walking the meta tree reads and parses every nested `.meta` file, which
+//! adds up on large workspaces when several operations (alias lookup, clone
+//! discovery, status, worktree creation) each walk it again. Parsed configs
+//! are cached per file, and [`WorkspaceModel::load`] returns the same model
+//! until a `.meta` file in the tree changes (by modification time and size)
+//! or one appears in or disappears from a project directory. Stale entries
+//! are dropped whenever a model is rebuilt.
+
+use anyhow::Result;
+use meta_core::config::{MetaTreeNode, ProjectInfo};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Identity of a `.meta` file's contents: (path, modified, size).
+type ConfigStamp = (PathBuf, Option<SystemTime>, u64);
+
+/// Parsed project lists, keyed by config path.
+type ConfigCache = HashMap<PathBuf, (ConfigStamp, Arc<Vec<ProjectInfo>>)>;
+
+static CONFIGS: Mutex<Option<ConfigCache>> = Mutex::new(None);
+/// Loaded models, keyed by workspace root.
+static MODELS: Mutex<Option<HashMap<PathBuf, Arc<WorkspaceModel>>>> = Mutex::new(None);
+
+/// Projects of every meta repo in a workspace, with their full paths.
+#[derive(Debug)]
+pub struct WorkspaceModel {
+    root: PathBuf,
+    tree: Vec<MetaTreeNode>,
+    /// Full project key (`vendor/lib`) -> (path, info)
+    projects: HashMap<String, (PathBuf, ProjectInfo)>,
+    /// Config found in each directory the walk looked at
+    stamps: Vec<(PathBuf, Option<ConfigStamp>)>,
+}
+
+impl WorkspaceModel {
+    /// The model of the workspace at `meta_dir`, reusing the cached one if
+    /// no `.meta` file in the tree changed since it was built.
+    pub fn load(meta_dir: &Path) -> Result<Arc<WorkspaceModel>> {
+        let key = meta_dir.to_path_buf();
+        {
+            let mut models = MODELS.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(model) = models.get_or_insert_with(HashMap::new).get(&key) {
+                if model.is_fresh() {
+                    return Ok(model.clone());
+                }
+            }
+        }
+        let model = Arc::new(Self::build(meta_dir)?);
+        {
+            let mut models = MODELS.lock().unwrap_or_else(|e| e.into_inner());
+            let models = models.get_or_insert_with(HashMap::new);
+            models.retain(|_, m| m.is_fresh());
+            models.insert(key, model.clone());
+        }
+        evict_stale_configs();
+        Ok(model)
+    }
+
+    fn build(meta_dir: &Path) -> Result<Self> {
+        let mut stamps = Vec::new();
+        let tree = walk(meta_dir, &mut stamps, &mut HashSet::new())?;
+        let projects = meta_core::config::build_project_map(&tree, meta_dir, "");
+        Ok(WorkspaceModel {
+            root: meta_dir.to_path_buf(),
+            tree,
+            projects,
+            stamps,
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Projects of the root `.meta`, with their nested projects as children.
+    pub fn tree(&self) -> &[MetaTreeNode] {
+        &self.tree
+    }
+
+    /// The project at `key`, a path from the root such as `vendor/lib`.
+    pub fn lookup(&self, key: &str) -> Option<&(PathBuf, ProjectInfo)> {
+        self.projects.get(key)
+    }
+
+    /// Every project key in the workspace, sorted.
+    pub fn keys(&self) -> Vec<&str> {
+        let mut keys: Vec<&str> = self.projects.keys().map(String::as_str).collect();
+        keys.sort();
+        keys
+    }
+
+    /// Keys and paths of the nested meta repos, sorted by key.
+    pub fn nested_meta_dirs(&self) -> Vec<(&str, &Path)> {
+        let mut dirs: Vec<(&str, &Path)> = self
+            .stamps
+            .iter()
+            .filter(|(dir, stamp)| stamp.is_some() && *dir != self.root)
+            .filter_map(|(dir, _)| {
+                self.projects
+                    .iter()
+                    .find(|(_, (path, _))| path == dir)
+                    .map(|(key, (path, _))| (key.as_str(), path.as_path()))
+            })
+            .collect();
+        dirs.sort();
+        dirs
+    }
+
+    /// Whether every `.meta` file the model was built from is unchanged and
+    /// no project directory gained one.
+    pub fn is_fresh(&self) -> bool {
+        self.stamps
+            .iter()
+            .all(|(dir, stamp)| config_stamp(dir) == *stamp)
+    }
+}
+
+/// The projects of the `.meta` config in `dir`, parsed once per version of
+/// the file. `None` if `dir` has no config.
+pub fn projects_in(dir: &Path) -> Result<Option<Arc<Vec<ProjectInfo>>>> {
+    match config_stamp(dir) {
+        Some(stamp) => parse_cached(stamp).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// The projects of the config file at `config_path`, parsed once per
+/// version of the file.
+pub fn parse_config(config_path: &Path) -> Result<Arc<Vec<ProjectInfo>>> {
+    parse_cached(file_stamp(config_path))
+}
+
+fn parse_cached(stamp: ConfigStamp) -> Result<Arc<Vec<ProjectInfo>>> {
+    {
+        let mut configs = CONFIGS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((cached, projects)) = configs.get_or_insert_with(HashMap::new).get(&stamp.0) {
+            if *cached == stamp {
+                return Ok(projects.clone());
+            }
+        }
+    }
+    let (projects, _) = meta_core::config::parse_meta_config(&stamp.0)?;
+    let projects = Arc::new(projects);
+    CONFIGS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(HashMap::new)
+        .insert(stamp.0.clone(), (stamp, projects.clone()));
+    Ok(projects)
+}
+
+/// Drop cached configs whose file changed or is gone.
+fn evict_stale_configs() {
+    let mut configs = CONFIGS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(configs) = configs.as_mut() {
+        configs.retain(|path, (stamp, _)| file_stamp(path) == *stamp);
+    }
+}
+
+/// Walk the meta tree below `dir`. Project paths are validated, and a
+/// directory already walked (a `.` project, or a symlink back up the tree)
+/// is not descended into again.
+fn walk(
+    dir: &Path,
+    stamps: &mut Vec<(PathBuf, Option<ConfigStamp>)>,
+    visited: &mut HashSet<PathBuf>,
+) -> Result<Vec<MetaTreeNode>> {
+    if !visited.insert(dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf())) {
+        return Ok(Vec::new());
+    }
+    let stamp = config_stamp(dir);
+    stamps.push((dir.to_path_buf(), stamp.clone()));
+    let Some(stamp) = stamp else {
+        return Ok(Vec::new());
+    };
+    let mut nodes = Vec::new();
+    for info in parse_cached(stamp)?.iter() {
+        let child = crate::sandbox::validate_project_path(dir, &info.name, &info.path)?;
+        let children = walk(&child, stamps, visited)?;
+        let is_meta = stamps.iter().any(|(d, s)| *d == child && s.is_some());
+        nodes.push(MetaTreeNode {
+            info: info.clone(),
+            is_meta,
+            children,
+        });
+    }
+    Ok(nodes)
+}
+
+fn config_stamp(dir: &Path) -> Option<ConfigStamp> {
+    let (path, _) = meta_core::config::find_meta_config_in(dir)?;
+    Some(file_stamp(&path))
+}
+
+fn file_stamp(path: &Path) -> ConfigStamp {
+    let metadata = std::fs::metadata(path).ok();
+    (
+        path.to_path_buf(),
+        metadata.as_ref().and_then(|m| m.modified().ok()),
+        metadata.map(|m| m.len()).unwrap_or(0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn model_is_reused_until_a_config_changes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ws = tmp.path();
+        std::fs::create_dir_all(ws.join("vendor")).unwrap();
+        std::fs::write(
+            ws.join(".meta"),
+            r#"{"projects": {"vendor": "git@github.com:org/vendor.git"}}"#,
+        )
+        .unwrap();
+
+        let first = WorkspaceModel::load(ws).unwrap();
+        assert_eq!(first.keys(), ["vendor"]);
+        assert!(Arc::ptr_eq(&first, &WorkspaceModel::load(ws).unwrap()));
+
+        // A nested .meta appearing (e.g. after a clone) invalidates the model
+        std::fs::write(
+            ws.join("vendor/.meta"),
+            r#"{"projects": {"lib": "git@github.com:org/lib.git"}}"#,
+        )
+        .unwrap();
+        assert!(!first.is_fresh());
+        let second = WorkspaceModel::load(ws).unwrap();
+        assert_eq!(second.keys(), ["vendor", "vendor/lib"]);
+        assert_eq!(
+            second.nested_meta_dirs(),
+            [("vendor", ws.join("vendor").as_path())]
+        );
+        assert!(second.tree()[0].is_meta);
+
+        // So does an edit to one
+        std::fs::write(
+            ws.join("vendor/.meta"),
+            r#"{"projects": {"lib": "git@github.com:org/lib.git", "util": "git@github.com:org/util.git"}}"#,
+        )
+        .unwrap();
+        let third = WorkspaceModel::load(ws).unwrap();
+        assert_eq!(
+            third.lookup("vendor/util").unwrap().0,
+            ws.join("vendor/util")
+        );
+    }
+
+    #[test]
+    fn walk_stops_at_cycles_and_rejects_escaping_paths() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ws = tmp.path();
+        std::fs::create_dir_all(ws.join("vendor")).unwrap();
+        std::fs::write(
+            ws.join(".meta"),
+            r#"{"projects": {"root": ".", "vendor": "git@github.com:org/vendor.git"}}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            ws.join("vendor/.meta"),
+            r#"{"projects": {"loop": "git@github.com:org/loop.git"}}"#,
+        )
+        .unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(ws.join("vendor"), ws.join("vendor/loop")).unwrap();
+
+        let model = WorkspaceModel::load(ws).unwrap();
+        assert!(model.lookup("vendor").is_some());
+        assert!(model.lookup("vendor/loop").is_some());
+        assert!(model.lookup("vendor/loop/loop").is_none());
+
+        std::fs::write(ws.join("vendor/.meta"), r#"{"projects": {"up": "../.."}}"#).unwrap();
+        assert!(WorkspaceModel::load(ws).is_err());
+    }
+}
@@ -0,0 +1,248 @@
+//! Base-branch synchronization for worktrees.
+//!
+//! When the main workspace fetches its default branches, worktree branches
+//! created from them fall behind. [`fetch_default_branches`] can mark every
+//! affected worktree repo as "base moved" in the store, with the number of
+//! new base commits, so list/status can badge worktrees that need a rebase
+//! and a rebase-all pass can find them with [`base_moved_worktrees`].
+
+use anyhow::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use super::git_ops::git_fetch_branch;
+use super::helpers::load_projects_with_root;
+use super::store::{store_list, store_set_base_moved};
+use super::types::{BaseMoved, WorktreeStoreEntry};
+
+/// A worktree repo whose base branch has commits it doesn't have.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BaseMovedEvent {
+    pub worktree: String,
+    /// Worktree root path
+    pub root: String,
+    pub alias: String,
+    pub base: String,
+    pub commits: u32,
+}
+
+/// Result of [`fetch_default_branches`].
+#[derive(Debug, Default, Serialize)]
+pub struct BaseSyncReport {
+    /// Projects whose default branch was fetched
+    pub fetched: Vec<String>,
+    /// Projects whose fetch failed, with the error
+    pub failed: Vec<(String, String)>,
+    /// Worktree repos now behind their base (empty unless marking was requested)
+    pub base_moved: Vec<BaseMovedEvent>,
+}
+
+fn git_stdout(repo_path: &Path, args: &[&str]) -> Option<String> {
+    let output = crate::git_runner::output(
+        Command::new("git")
+            .args(args)
+            .current_dir(repo_path)
+            .stderr(Stdio::null()),
+    )
+    .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// The remote-tracking ref of the repo's default branch, e.g. `origin/main`.
+///
+/// Uses `origin/HEAD` when set, falling back to `origin/main` or `origin/master`.
+pub fn default_base_ref(repo_path: &Path) -> Option<String> {
+    git_stdout(
+        repo_path,
+        &["symbolic-ref", "--short", "refs/remotes/origin/HEAD"],
+    )
+    .or_else(|| {
+        ["origin/main", "origin/master"]
+            .into_iter()
+            .find(|candidate| {
+                git_stdout(repo_path, &["rev-parse", "--verify", "--quiet", candidate]).is_some()
+            })
+            .map(str::to_string)
+    })
+}
+
+/// Commits reachable from `base` but not from HEAD.
+fn commits_behind(repo_path: &Path, base: &str) -> Option<u32> {
+    git_stdout(
+        repo_path,
+        &["rev-list", "--count", &format!("HEAD..{base}")],
+    )?
+    .parse()
+    .ok()
+}
+
+fn belongs_to(entry: &WorktreeStoreEntry, meta_dir: &Path) -> bool {
+    let project = Path::new(&entry.project);
+    project == meta_dir
+        || project
+            .canonicalize()
+            .is_ok_and(|p| meta_dir.canonicalize().is_ok_and(|m| p == m))
+}
+
+fn repo_path(root: &str, alias: &str) -> PathBuf {
+    if alias == "." {
+        PathBuf::from(root)
+    } else {
+        Path::new(root).join(alias)
+    }
+}
+
+/// Recompute the base-moved marker of every worktree repo of `meta_dir`.
+///
+/// Markers are set for repos behind their base and cleared for the rest.
+/// Returns the repos that are behind.
+pub fn mark_base_moved(meta_dir: &Path) -> Result<Vec<BaseMovedEvent>> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut updates = Vec::new();
+    let mut events = Vec::new();
+    for (root, entry) in store_list()?.worktrees {
+        if !belongs_to(&entry, meta_dir) {
+            continue;
+        }
+        for repo in &entry.repos {
+            let path = repo_path(&root, &repo.alias);
+            if !path.exists() {
+                continue;
+            }
+            let marker = default_base_ref(&path).and_then(|base| {
+                let commits = commits_behind(&path, &base).filter(|&n| n > 0)?;
+                Some(BaseMoved {
+                    base,
+                    commits,
+                    detected_at: now.clone(),
+                })
+            });
+            if let Some(marker) = &marker {
+                events.push(BaseMovedEvent {
+                    worktree: entry.name.clone(),
+                    root: root.clone(),
+                    alias: repo.alias.clone(),
+                    base: marker.base.clone(),
+                    commits: marker.commits,
+                });
+            }
+            if marker != repo.base_moved {
+                updates.push((PathBuf::from(&root), repo.alias.clone(), marker));
+            }
+        }
+    }
+    store_set_base_moved(updates)?;
+    events.sort_by(|a, b| (&a.worktree, &a.alias).cmp(&(&b.worktree, &b.alias)));
+    Ok(events)
+}
+
+/// Fetch the default branch of every repo in the workspace.
+///
+/// With `mark_worktrees`, then runs [`mark_base_moved`] and reports the
+/// affected worktree repos. Fetch failures are reported, not fatal.
+pub fn fetch_default_branches(meta_dir: &Path, mark_worktrees: bool) -> Result<BaseSyncReport> {
+    crate::read_only::check("fetch")?;
+    let mut report = BaseSyncReport::default();
+    for project in load_projects_with_root(meta_dir, true)? {
+        let path = meta_dir.join(&project.path);
+        let Some(base) = default_base_ref(&path) else {
+            continue;
+        };
+        let branch = base.strip_prefix("origin/").unwrap_or(&base);
+        match git_fetch_branch(&path, branch) {
+            Ok(()) => report.fetched.push(project.name),
+            Err(e) => report.failed.push((project.name, e.to_string())),
+        }
+    }
+    if mark_worktrees {
+        report.base_moved = mark_base_moved(meta_dir)?;
+    }
+    Ok(report)
+}
+
+/// Worktree repos of `meta_dir` currently marked as base moved, for rebase-all.
+pub fn base_moved_worktrees(meta_dir: &Path) -> Result<Vec<BaseMovedEvent>> {
+    let mut events: Vec<_> = store_list()?
+        .worktrees
+        .into_iter()
+        .filter(|(_, entry)| belongs_to(entry, meta_dir))
+        .flat_map(|(root, entry)| {
+            entry
+                .repos
+                .into_iter()
+                .filter_map(|repo| {
+                    let moved = repo.base_moved?;
+                    Some(BaseMovedEvent {
+                        worktree: entry.name.clone(),
+                        root: root.clone(),
+                        alias: repo.alias,
+                        base: moved.base,
+                        commits: moved.commits,
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    events.sort_by(|a, b| (&a.worktree, &a.alias).cmp(&(&b.worktree, &b.alias)));
+    Ok(events)
+}
+
+/// Clear the marker after `alias` in the worktree at `worktree_path` was rebased.
+pub fn clear_base_moved(worktree_path: &Path, alias: &str) -> Result<()> {
+    store_set_base_moved(vec![(worktree_path.to_path_buf(), alias.to_string(), None)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{git, repo, store_entry, worktree};
+    use crate::worktree::store::store_add;
+
+    fn commit(dir: &Path, msg: &str) {
+        git(dir, &["commit", "-q", "--allow-empty", "-m", msg]);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn fetch_marks_worktrees_behind_base() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp.path().join("store"));
+
+        let origin = tmp.path().join("origin");
+        repo(&origin, &[]);
+
+        let ws = tmp.path().join("ws");
+        std::fs::create_dir_all(&ws).unwrap();
+        git(&ws, &["clone", "-q", origin.to_str().unwrap(), "api"]);
+        std::fs::write(
+            ws.join(".meta"),
+            format!(r#"{{"projects": {{"api": "{}"}}}}"#, origin.display()),
+        )
+        .unwrap();
+
+        let wt_root = ws.join(".worktrees/feat");
+        worktree(&ws.join("api"), &wt_root.join("api"), "feat");
+        store_add(&wt_root, store_entry("feat", &ws).repo("api").build()).unwrap();
+
+        let report = fetch_default_branches(&ws, true).unwrap();
+        assert_eq!(report.fetched, ["api"]);
+        assert!(report.base_moved.is_empty());
+
+        commit(&origin, "one");
+        commit(&origin, "two");
+        let report = fetch_default_branches(&ws, true).unwrap();
+        assert_eq!(report.base_moved.len(), 1, "{report:?}");
+        assert_eq!(report.base_moved[0].base, "origin/main");
+        assert_eq!(report.base_moved[0].commits, 2);
+        assert_eq!(base_moved_worktrees(&ws).unwrap(), report.base_moved);
+
+        clear_base_moved(&wt_root, "api").unwrap();
+        assert!(base_moved_worktrees(&ws).unwrap().is_empty());
+        std::env::remove_var("META_DATA_DIR");
+    }
+}
@@ -0,0 +1,194 @@
+//! Extended `worktree list` details.
+//!
+//! Disk usage, age, last activity, and linked PRs for each worktree, so the
+//! list view is enough to decide what to prune. Entries are filled in
+//! parallel; `fast` mode skips everything except the store-derived age.
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use super::git_ops::git_last_commit_time;
+use super::types::{ListEntry, ListPrEntry, WorktreeStoreData};
+
+/// Fill the extended fields of `entries` in place, one thread per worktree.
+pub fn fill_list_details(entries: &mut [ListEntry], store: &WorktreeStoreData, fast: bool) {
+    let now = chrono::Utc::now();
+    let runner = crate::git_runner::installed();
+    std::thread::scope(|scope| {
+        for entry in entries.iter_mut() {
+            let runner = runner.clone();
+            scope.spawn(move || {
+                crate::git_runner::with_installed(runner, || {
+                    let root = Path::new(&entry.root).to_path_buf();
+                    entry.age_seconds = worktree_age(&root, store, now);
+                    if fast {
+                        return;
+                    }
+                    entry.disk_usage_bytes = Some(dir_disk_usage(&root));
+                    entry.last_activity = entry
+                        .repos
+                        .iter()
+                        .filter_map(|r| git_last_commit_time(&repo_dir(&root, &r.alias)))
+                        .max()
+                        .map(|t| t.to_rfc3339());
+                    entry.pull_requests = entry
+                        .repos
+                        .iter()
+                        .filter_map(|r| {
+                            gh_pr_for_branch(&repo_dir(&root, &r.alias), &r.branch).map(|mut pr| {
+                                pr.alias = r.alias.clone();
+                                pr
+                            })
+                        })
+                        .collect();
+                })
+            });
+        }
+    });
+}
+
+fn repo_dir(root: &Path, alias: &str) -> std::path::PathBuf {
+    if alias == "." {
+        root.to_path_buf()
+    } else {
+        root.join(alias)
+    }
+}
+
+/// Seconds since creation, from the store entry or the directory's metadata.
+fn worktree_age(
+    root: &Path,
+    store: &WorktreeStoreData,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Option<i64> {
+    let key = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let from_store = store
+        .worktrees
+        .get(&*key.to_string_lossy())
+        .and_then(|e| chrono::DateTime::parse_from_rfc3339(&e.created_at).ok())
+        .map(|dt| dt.with_timezone(&chrono::Utc));
+    let created = from_store.or_else(|| {
+        let meta = std::fs::metadata(root).ok()?;
+        meta.created()
+            .or_else(|_| meta.modified())
+            .ok()
+            .map(chrono::DateTime::<chrono::Utc>::from)
+    })?;
+    Some((now - created).num_seconds().max(0))
+}
+
+/// Total size in bytes of regular files under `path`. Symlinks are not followed.
+pub fn dir_disk_usage(path: &Path) -> u64 {
+    let Ok(read_dir) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    read_dir
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(ft) if ft.is_dir() => dir_disk_usage(&entry.path()),
+            Ok(ft) if ft.is_file() => entry.metadata().map(|m| m.len()).unwrap_or(0),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Most recent PR opened from `branch`, via the `gh` CLI. `None` if `gh` is
+/// unavailable or no PR exists.
+pub(crate) fn gh_pr_for_branch(repo_path: &Path, branch: &str) -> Option<ListPrEntry> {
+    let output = Command::new("gh")
+        .args([
+            "pr",
+            "list",
+            "--head",
+            branch,
+            "--state",
+            "all",
+            "--limit",
+            "1",
+            "--json",
+            "number,state,title,url",
+        ])
+        .current_dir(repo_path)
+        .env("GH_PROMPT_DISABLED", "1")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_gh_pr_list(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_gh_pr_list(json: &str) -> Option<ListPrEntry> {
+    #[derive(serde::Deserialize)]
+    struct GhPr {
+        number: u64,
+        state: String,
+        title: String,
+        url: String,
+    }
+    let prs: Vec<GhPr> = serde_json::from_str(json).ok()?;
+    prs.into_iter().next().map(|pr| ListPrEntry {
+        alias: String::new(),
+        number: pr.number,
+        state: pr.state,
+        title: pr.title,
+        url: pr.url,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disk_usage_sums_nested_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("a"), [0u8; 100]).unwrap();
+        std::fs::create_dir(tmp.path().join("sub")).unwrap();
+        std::fs::write(tmp.path().join("sub").join("b"), [0u8; 50]).unwrap();
+        assert_eq!(dir_disk_usage(tmp.path()), 150);
+    }
+
+    #[test]
+    fn disk_usage_of_missing_dir_is_zero() {
+        assert_eq!(dir_disk_usage(Path::new("/nonexistent/worktree")), 0);
+    }
+
+    #[test]
+    fn parse_gh_pr_list_takes_first() {
+        let json = r#"[{"number": 42, "state": "OPEN", "title": "Add x", "url": "https://github.com/o/r/pull/42"}]"#;
+        let pr = parse_gh_pr_list(json).unwrap();
+        assert_eq!(pr.number, 42);
+        assert_eq!(pr.state, "OPEN");
+        assert!(parse_gh_pr_list("[]").is_none());
+    }
+
+    #[test]
+    fn fast_mode_only_sets_age() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut entries = vec![ListEntry {
+            name: "wt".to_string(),
+            root: tmp.path().to_string_lossy().into_owned(),
+            ..Default::default()
+        }];
+        fill_list_details(&mut entries, &WorktreeStoreData::default(), true);
+        assert!(entries[0].age_seconds.is_some());
+        assert!(entries[0].disk_usage_bytes.is_none());
+        assert!(entries[0].last_activity.is_none());
+    }
+
+    #[test]
+    fn full_mode_computes_disk_usage() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("file"), [0u8; 10]).unwrap();
+        let mut entries = vec![ListEntry {
+            name: "wt".to_string(),
+            root: tmp.path().to_string_lossy().into_owned(),
+            ..Default::default()
+        }];
+        fill_list_details(&mut entries, &WorktreeStoreData::default(), false);
+        assert_eq!(entries[0].disk_usage_bytes, Some(10));
+    }
+}
@@ -0,0 +1,593 @@
+//! One-shot ephemeral worktrees.
+//!
+//! [`with_ephemeral`] and [`run_ephemeral`] create a uniquely named worktree
+//! with a TTL, run a closure or command inside it, and remove it afterwards.
+//! Cleanup happens on return and on panic (via `Drop`). Signal handling is
+//! left to the application: its Ctrl-C handler should call
+//! [`cleanup_active`] to tear down every live ephemeral worktree before
+//! exiting. If the process is killed outright, the TTL recorded in the store
+//! lets `worktree prune` reclaim it.
+//!
+//! The `pre-create` and `pre-destroy` guard hooks apply as for any other
+//! worktree: a refused creation fails, and a refused removal leaves the
+//! worktree for `worktree prune`.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::change_group::ChangeGroupId;
+use crate::project_options::{skipped_projects, ProjectOperation};
+use crate::sandbox::Sandbox;
+
+use super::git_ops::{git_worktree_add, git_worktree_remove};
+use super::helpers::{lookup_nested_project, resolve_start_ref, resolve_worktree_root};
+use super::hooks::{fire_pre_create, fire_pre_destroy};
+use super::selection::expand_repo_specs;
+use super::store::{store_add, store_remove};
+use super::types::{PlannedRepoEntry, RepoSpec, StoreRepoEntry, WorktreeStoreEntry};
+
+/// Default lifetime recorded for ephemeral worktrees (1 hour).
+pub const DEFAULT_EPHEMERAL_TTL_SECS: u64 = 3600;
+
+/// What to materialize in an ephemeral worktree.
+#[derive(Debug, Clone)]
+pub struct EphemeralSpec {
+    pub meta_dir: PathBuf,
+    /// Repos to include; `alias:ref` (or `alias@ref`, `alias#PR`) starts that
+    /// repo from `ref`. Globs and
+    /// tag selectors are expanded (see [`super::selection`]).
+    pub repos: Vec<RepoSpec>,
+    /// Default starting ref for repos without one (HEAD if unset).
+    pub from_ref: Option<String>,
+    /// Name prefix; a unique suffix is appended.
+    pub prefix: String,
+    pub ttl_seconds: u64,
+}
+
+impl EphemeralSpec {
+    pub fn new(meta_dir: impl Into<PathBuf>, repos: Vec<RepoSpec>) -> Self {
+        EphemeralSpec {
+            meta_dir: meta_dir.into(),
+            repos,
+            from_ref: None,
+            prefix: "ephemeral".to_string(),
+            ttl_seconds: DEFAULT_EPHEMERAL_TTL_SECS,
+        }
+    }
+
+    pub fn from_ref(mut self, from_ref: impl Into<String>) -> Self {
+        self.from_ref = Some(from_ref.into());
+        self
+    }
+
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    pub fn ttl_seconds(mut self, ttl_seconds: u64) -> Self {
+        self.ttl_seconds = ttl_seconds;
+        self
+    }
+}
+
+/// A repo checked out inside an ephemeral worktree.
+#[derive(Debug, Clone)]
+struct EphemeralRepo {
+    alias: String,
+    source: PathBuf,
+    dest: PathBuf,
+    branch: String,
+    created_branch: bool,
+}
+
+/// A live ephemeral worktree; removed when dropped.
+#[derive(Debug)]
+pub struct EphemeralWorktree {
+    name: String,
+    meta_dir: PathBuf,
+    root: PathBuf,
+    change_group: ChangeGroupId,
+    repos: Vec<EphemeralRepo>,
+    /// Requested repos left out because they are disabled for worktrees
+    disabled: Vec<String>,
+    cleaned: bool,
+}
+
+/// What it takes to remove a live worktree, by root.
+#[derive(Debug, Clone)]
+struct ActiveWorktree {
+    name: String,
+    meta_dir: PathBuf,
+    repos: Vec<EphemeralRepo>,
+}
+
+/// Worktrees that must be torn down if the process is interrupted.
+static ACTIVE: Mutex<Option<HashMap<PathBuf, ActiveWorktree>>> = Mutex::new(None);
+static NAME_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn register(wt: &EphemeralWorktree) {
+    let mut active = ACTIVE.lock().unwrap_or_else(|e| e.into_inner());
+    active.get_or_insert_with(HashMap::new).insert(
+        wt.root.clone(),
+        ActiveWorktree {
+            name: wt.name.clone(),
+            meta_dir: wt.meta_dir.clone(),
+            repos: wt.repos.clone(),
+        },
+    );
+}
+
+/// Forget `root`; `false` if [`cleanup_active`] already took it.
+fn unregister(root: &Path) -> bool {
+    let mut active = ACTIVE.lock().unwrap_or_else(|e| e.into_inner());
+    active
+        .as_mut()
+        .is_some_and(|map| map.remove(root).is_some())
+}
+
+/// Keep the live ephemeral worktree at `root`, if any: neither its `Drop`
+/// nor [`cleanup_active`] removes it anymore. For
+/// [`make_persistent`](super::store::make_persistent).
+pub(super) fn disarm(root: &Path) {
+    let canonical = root.canonicalize().ok();
+    let mut active = ACTIVE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(map) = active.as_mut() {
+        map.retain(|r, _| r != root && (canonical.is_none() || r.canonicalize().ok() != canonical));
+    }
+}
+
+/// Remove every ephemeral worktree still alive in this process.
+///
+/// Meant for the application's Ctrl-C handler, to call before exiting, since
+/// an interrupted process never runs the worktrees' `Drop`.
+pub fn cleanup_active() {
+    let drained: Vec<(PathBuf, ActiveWorktree)> = {
+        let mut active = ACTIVE.lock().unwrap_or_else(|e| e.into_inner());
+        active
+            .as_mut()
+            .map(|m| m.drain().collect())
+            .unwrap_or_default()
+    };
+    for (root, wt) in drained {
+        remove(&wt.name, &wt.meta_dir, &root, &wt.repos);
+    }
+}
+
+fn unique_name(prefix: &str) -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let seq = NAME_COUNTER.fetch_add(1, Ordering::SeqCst);
+    format!(
+        "{prefix}-{:x}-{:x}{seq}",
+        nanos & 0xffff_ffff_ffff,
+        std::process::id()
+    )
+}
+
+/// Fire `pre-destroy` for worktree `name` at `root` and, unless the hook
+/// refuses, [`teardown`] it. A refused worktree is kept, with a warning,
+/// until `worktree prune` finds its TTL expired.
+fn remove(name: &str, meta_dir: &Path, root: &Path, repos: &[EphemeralRepo]) {
+    if let Err(e) = fire_pre_destroy(name, root, false, Some(meta_dir)) {
+        log::warn!("Keeping ephemeral worktree {}: {e:#}", root.display());
+        return;
+    }
+    teardown(root, repos);
+}
+
+/// Remove repos (children first, then `.`), their created branches, the
+/// directory, and the store entry. Best-effort: failures are logged.
+fn teardown(root: &Path, repos: &[EphemeralRepo]) {
+    let ordered = repos
+        .iter()
+        .filter(|r| r.alias != ".")
+        .chain(repos.iter().filter(|r| r.alias == "."));
+    for repo in ordered {
+        if let Err(e) = git_worktree_remove(&repo.source, &repo.dest, true) {
+            log::warn!(
+                "Failed to remove ephemeral worktree for '{}': {e}",
+                repo.alias
+            );
+        }
+        if repo.created_branch {
+            let _ = crate::git_runner::status(
+                Command::new("git")
+                    .args(["branch", "-D", &repo.branch])
+                    .current_dir(&repo.source)
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null()),
+            );
+        }
+    }
+    // Before deleting the directory, while the store key still canonicalizes
+    if let Err(e) = store_remove(root) {
+        log::warn!("Failed to remove store entry for {}: {e}", root.display());
+    }
+    if root.exists() {
+        if let Err(e) = std::fs::remove_dir_all(root) {
+            log::warn!("Failed to remove {}: {e}", root.display());
+        }
+    }
+}
+
+impl EphemeralWorktree {
+    /// Create the worktree described by `spec`, unless its `pre-create`
+    /// hook refuses. Repos disabled for worktrees are left out (see
+    /// [`disabled`](Self::disabled)).
+    pub fn create(spec: &EphemeralSpec) -> Result<Self> {
+        crate::read_only::check("create worktree")?;
+        if spec.repos.is_empty() {
+            anyhow::bail!("Ephemeral worktree needs at least one repo");
+        }
+        let (repos, expansions) = expand_repo_specs(&spec.meta_dir, spec.repos.clone())?;
+        let skipped = skipped_projects(&spec.meta_dir, ProjectOperation::Worktree);
+        let (repos, disabled): (Vec<RepoSpec>, Vec<RepoSpec>) =
+            repos.into_iter().partition(|r| !skipped.contains(&r.alias));
+        if repos.is_empty() {
+            anyhow::bail!("All requested repos are disabled for worktrees");
+        }
+
+        let name = unique_name(&spec.prefix);
+        let root = resolve_worktree_root(Some(&spec.meta_dir))?.join(&name);
+        Sandbox::for_workspace(&spec.meta_dir).check(&root, "create worktree")?;
+        let planned: Vec<PlannedRepoEntry> = repos
+            .iter()
+            .map(|r| PlannedRepoEntry {
+                alias: r.alias.clone(),
+                path: if r.alias == "." {
+                    root.to_string_lossy().into_owned()
+                } else {
+                    root.join(&r.alias).to_string_lossy().into_owned()
+                },
+                branch: name.clone(),
+            })
+            .collect();
+        fire_pre_create(&name, &root, &planned, false, Some(&spec.meta_dir))?;
+        std::fs::create_dir_all(&root)
+            .with_context(|| format!("Failed to create {}", root.display()))?;
+
+        let mut wt = EphemeralWorktree {
+            name: name.clone(),
+            meta_dir: spec.meta_dir.clone(),
+            root: root.clone(),
+            change_group: ChangeGroupId::generate(),
+            repos: Vec::new(),
+            disabled: disabled.into_iter().map(|r| r.alias).collect(),
+            cleaned: false,
+        };
+        register(&wt);
+
+        // "." first so child repos nest inside the meta repo worktree
+        let mut specs: Vec<&RepoSpec> = repos.iter().collect();
+        specs.sort_by_key(|s| s.alias != ".");
+        for repo_spec in specs {
+            let (source, dest) = if repo_spec.alias == "." {
+                (spec.meta_dir.clone(), root.clone())
+            } else {
+                let (path, _) = lookup_nested_project(&spec.meta_dir, &repo_spec.alias)?;
+                (path, root.join(&repo_spec.alias))
+            };
+            if repo_spec.alias == "." {
+                // git worktree add needs a non-existent or empty destination
+                std::fs::remove_dir(&root).ok();
+            }
+            let default_ref = repo_spec.branch.as_deref().or(spec.from_ref.as_deref());
+            let from_ref = resolve_start_ref(&source, repo_spec, default_ref)?;
+            let created_branch = git_worktree_add(&source, &dest, &name, from_ref.as_deref())?;
+            wt.repos.push(EphemeralRepo {
+                alias: repo_spec.alias.clone(),
+                source,
+                dest,
+                branch: name.clone(),
+                created_branch,
+            });
+            register(&wt);
+        }
+
+        store_add(
+            &root,
+            WorktreeStoreEntry {
+                name: name.clone(),
+                project: spec.meta_dir.to_string_lossy().into_owned(),
+                created_at: chrono::Utc::now().to_rfc3339(),
+                ephemeral: true,
+                ttl_seconds: Some(spec.ttl_seconds),
+                repos: wt
+                    .repos
+                    .iter()
+                    .map(|r| StoreRepoEntry {
+                        alias: r.alias.clone(),
+                        branch: r.branch.clone(),
+                        created_branch: r.created_branch,
+                        base_moved: None,
+                    })
+                    .collect(),
+                custom: HashMap::new(),
+                change_group: Some(wt.change_group.to_string()),
+                protected: false,
+                expansions,
+            },
+        )?;
+
+        Ok(wt)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    /// Change group to tag commits made in the worktree with.
+    pub fn change_group(&self) -> &ChangeGroupId {
+        &self.change_group
+    }
+
+    /// Requested repos that were left out because they are disabled for
+    /// worktrees.
+    pub fn disabled(&self) -> &[String] {
+        &self.disabled
+    }
+
+    /// Path of a repo inside the worktree.
+    pub fn repo_path(&self, alias: &str) -> Option<&Path> {
+        self.repos
+            .iter()
+            .find(|r| r.alias == alias)
+            .map(|r| r.dest.as_path())
+    }
+
+    /// Remove the worktree now.
+    pub fn cleanup(mut self) {
+        self.cleanup_inner();
+    }
+
+    fn cleanup_inner(&mut self) {
+        if self.cleaned {
+            return;
+        }
+        self.cleaned = true;
+        if unregister(&self.root) {
+            remove(&self.name, &self.meta_dir, &self.root, &self.repos);
+        }
+    }
+}
+
+impl Drop for EphemeralWorktree {
+    fn drop(&mut self) {
+        self.cleanup_inner();
+    }
+}
+
+/// Create an ephemeral worktree, run `f` in it, and remove it afterwards.
+pub fn with_ephemeral<T>(
+    spec: &EphemeralSpec,
+    f: impl FnOnce(&EphemeralWorktree) -> Result<T>,
+) -> Result<T> {
+    let wt = EphemeralWorktree::create(spec)?;
+    let result = f(&wt);
+    wt.cleanup();
+    result
+}
+
+/// Run `cmd` (program and arguments) in an ephemeral worktree's root.
+pub fn run_ephemeral(spec: &EphemeralSpec, cmd: &[String]) -> Result<ExitStatus> {
+    let (program, args) = cmd
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("No command given"))?;
+    with_ephemeral(spec, |wt| {
+        Command::new(program)
+            .args(args)
+            .current_dir(wt.path())
+            .env("META_WORKTREE", wt.name())
+            .status()
+            .with_context(|| format!("Failed to run '{program}'"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{git, repo};
+
+    fn setup() -> tempfile::TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        let store_dir = tmp.path().join("meta-store");
+        std::fs::create_dir_all(&store_dir).unwrap();
+        std::env::set_var("META_DATA_DIR", &store_dir);
+        std::env::remove_var("META_WORKTREES");
+
+        let ws = tmp.path().join("ws");
+        repo(&ws.join("api"), &[]);
+        std::fs::write(
+            ws.join(".meta"),
+            r#"{"projects": {"api": "git@github.com:org/api.git"}}"#,
+        )
+        .unwrap();
+        tmp
+    }
+
+    fn branches(repo: &Path) -> String {
+        git(repo, &["branch", "--format=%(refname:short)"])
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn with_ephemeral_creates_and_removes() {
+        let tmp = setup();
+        let ws = tmp.path().join("ws");
+        let spec = EphemeralSpec::new(&ws, vec!["api".parse().unwrap()]);
+
+        let root = with_ephemeral(&spec, |wt| {
+            assert!(wt.repo_path("api").unwrap().join(".git").exists());
+            let store = crate::worktree::store::store_list()?;
+            assert_eq!(store.worktrees.len(), 1);
+            Ok(wt.path().to_path_buf())
+        })
+        .unwrap();
+
+        assert!(!root.exists());
+        assert_eq!(branches(&ws.join("api")), "main");
+        let store = crate::worktree::store::store_list().unwrap();
+        assert!(store.worktrees.is_empty());
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn disabled_repos_are_left_out() {
+        let tmp = setup();
+        let ws = tmp.path().join("ws");
+        repo(&ws.join("legacy"), &[]);
+        std::fs::write(
+            ws.join(".meta"),
+            serde_json::json!({"projects": {
+                "api": "git@github.com:org/api.git",
+                "legacy": {"repo": "git@github.com:org/legacy.git", "skip": ["worktree"]},
+            }})
+            .to_string(),
+        )
+        .unwrap();
+
+        let spec = EphemeralSpec::new(&ws, vec!["api".parse().unwrap(), "legacy".parse().unwrap()]);
+        with_ephemeral(&spec, |wt| {
+            assert!(wt.repo_path("api").is_some());
+            assert!(wt.repo_path("legacy").is_none());
+            assert_eq!(wt.disabled(), ["legacy"]);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(branches(&ws.join("legacy")), "main");
+
+        let spec = EphemeralSpec::new(&ws, vec!["legacy".parse().unwrap()]);
+        let err = EphemeralWorktree::create(&spec).unwrap_err();
+        assert!(err.to_string().contains("disabled"), "{err}");
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn persistent_worktrees_survive_drop() {
+        let tmp = setup();
+        let ws = tmp.path().join("ws");
+        let spec = EphemeralSpec::new(&ws, vec!["api".parse().unwrap()]);
+
+        let root = with_ephemeral(&spec, |wt| {
+            crate::worktree::store::make_persistent(wt.path())?;
+            Ok(wt.path().to_path_buf())
+        })
+        .unwrap();
+
+        assert!(root.join("api").join(".git").exists());
+        let store = crate::worktree::store::store_list().unwrap();
+        assert_eq!(store.worktrees.len(), 1);
+        assert!(!store.worktrees.values().next().unwrap().ephemeral);
+        cleanup_active();
+        assert!(root.exists());
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn cleanup_runs_on_panic() {
+        let tmp = setup();
+        let ws = tmp.path().join("ws");
+        let spec = EphemeralSpec::new(&ws, vec!["api".parse().unwrap()]);
+
+        let root = std::sync::Mutex::new(None);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            with_ephemeral(&spec, |wt| -> Result<()> {
+                *root.lock().unwrap() = Some(wt.path().to_path_buf());
+                panic!("boom");
+            })
+        }));
+        assert!(result.is_err());
+        assert!(!root.lock().unwrap().as_ref().unwrap().exists());
+        assert_eq!(branches(&ws.join("api")), "main");
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn run_ephemeral_reports_exit_status() {
+        let tmp = setup();
+        let ws = tmp.path().join("ws");
+        let spec = EphemeralSpec::new(&ws, vec!["api".parse().unwrap()]);
+
+        let ok = run_ephemeral(&spec, &["test".into(), "-d".into(), "api".into()]).unwrap();
+        assert!(ok.success());
+        let fail = run_ephemeral(&spec, &["false".into()]).unwrap();
+        assert!(!fail.success());
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn cleanup_active_removes_live_worktrees() {
+        let tmp = setup();
+        let ws = tmp.path().join("ws");
+        let spec = EphemeralSpec::new(&ws, vec!["api".parse().unwrap()]);
+
+        let wt = EphemeralWorktree::create(&spec).unwrap();
+        let root = wt.path().to_path_buf();
+        cleanup_active();
+        assert!(!root.exists());
+        // Drop after the signal-path cleanup is harmless
+        drop(wt);
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[serial_test::serial]
+    fn guard_hooks_can_refuse_creation_and_removal() {
+        let tmp = setup();
+        let ws = tmp.path().join("ws");
+        let write_hooks = |hooks: serde_json::Value| {
+            std::fs::write(
+                ws.join(".meta"),
+                serde_json::json!({
+                    "projects": {"api": "git@github.com:org/api.git"},
+                    "worktree": {"hooks": hooks},
+                })
+                .to_string(),
+            )
+            .unwrap();
+        };
+        let spec = EphemeralSpec::new(&ws, vec!["api".parse().unwrap()]);
+
+        write_hooks(serde_json::json!({"pre-create": "echo no ephemerals >&2; exit 1"}));
+        let err = EphemeralWorktree::create(&spec).unwrap_err();
+        assert!(err.to_string().contains("no ephemerals"), "{err:#}");
+        assert!(!ws.join(".worktrees").exists());
+
+        write_hooks(serde_json::json!({"pre-destroy": "exit 1"}));
+        let wt = EphemeralWorktree::create(&spec).unwrap();
+        let root = wt.path().to_path_buf();
+        drop(wt);
+        // Kept for prune to reclaim
+        assert!(root.join("api").exists());
+        assert_eq!(
+            crate::worktree::store::store_list()
+                .unwrap()
+                .worktrees
+                .len(),
+            1
+        );
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    fn unique_names_differ() {
+        assert_ne!(unique_name("x"), unique_name("x"));
+    }
+}
@@ -1,10 +1,16 @@
 //! Git operations for worktree management.
 
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use std::path::Path;
 use std::process::{Command, Stdio};
 
-use super::types::GitStatusSummary;
+use super::types::{GitDiffSummary, GitStatusSummary, RenameEntry};
+use crate::git_output::{
+    parse_check_ignore, parse_name_status_renames, parse_numstat, parse_status_v2, IgnoreRule,
+    RenameSource, StatusEntry,
+};
+use crate::ssh_multiplexing::MultiplexingConfig;
 
 pub fn git_worktree_add(
     repo_path: &Path,
@@ -12,15 +18,24 @@ pub fn git_worktree_add(
     branch: &str,
     from_ref: Option<&str>,
 ) -> Result<bool> {
+    crate::read_only::check("create worktree")?;
+    // jj does not track git worktrees; creating one would desync its view of HEAD
+    if let Some(reason) = crate::vcs::jj_colocated_skip_reason(repo_path, "git worktree add") {
+        anyhow::bail!(reason);
+    }
+
+    let _lock = crate::lock::RepoLock::acquire(repo_path, "git worktree add")?;
+
     // If from_ref is specified, verify it exists in this repo
     if let Some(ref_name) = from_ref {
-        let ref_exists = Command::new("git")
-            .args(["rev-parse", "--verify", ref_name])
-            .current_dir(repo_path)
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .status()?
-            .success();
+        let ref_exists = crate::git_runner::status(
+            Command::new("git")
+                .args(["rev-parse", "--verify", ref_name])
+                .current_dir(repo_path)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null()),
+        )?
+        .success();
 
         if !ref_exists {
             anyhow::bail!(
@@ -31,17 +46,18 @@ pub fn git_worktree_add(
         }
 
         // Create branch from the specified ref
-        let output = Command::new("git")
-            .args([
-                "worktree",
-                "add",
-                "-b",
-                branch,
-                &worktree_dest.to_string_lossy(),
-                ref_name,
-            ])
-            .current_dir(repo_path)
-            .output()?;
+        let output = crate::git_runner::output(
+            Command::new("git")
+                .args([
+                    "worktree",
+                    "add",
+                    "-b",
+                    branch,
+                    &worktree_dest.to_string_lossy(),
+                    ref_name,
+                ])
+                .current_dir(repo_path),
+        )?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -57,27 +73,29 @@ pub fn git_worktree_add(
     }
 
     // Check if branch exists locally
-    let branch_exists = Command::new("git")
-        .args(["rev-parse", "--verify", &format!("refs/heads/{branch}")])
-        .current_dir(repo_path)
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status()?
-        .success();
-
-    // Also check if branch exists on remote
-    let remote_branch_exists = if !branch_exists {
+    let branch_exists = crate::git_runner::status(
         Command::new("git")
-            .args([
-                "rev-parse",
-                "--verify",
-                &format!("refs/remotes/origin/{branch}"),
-            ])
+            .args(["rev-parse", "--verify", &format!("refs/heads/{branch}")])
             .current_dir(repo_path)
             .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .status()?
-            .success()
+            .stderr(Stdio::null()),
+    )?
+    .success();
+
+    // Also check if branch exists on remote
+    let remote_branch_exists = if !branch_exists {
+        crate::git_runner::status(
+            Command::new("git")
+                .args([
+                    "rev-parse",
+                    "--verify",
+                    &format!("refs/remotes/origin/{branch}"),
+                ])
+                .current_dir(repo_path)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null()),
+        )?
+        .success()
     } else {
         false
     };
@@ -104,10 +122,8 @@ pub fn git_worktree_add(
         vec!["worktree", "add", "-b", branch, &dest_str]
     };
 
-    let output = Command::new("git")
-        .args(&wt_args)
-        .current_dir(repo_path)
-        .output()?;
+    let output =
+        crate::git_runner::output(Command::new("git").args(&wt_args).current_dir(repo_path))?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -125,6 +141,8 @@ pub fn git_worktree_add(
 }
 
 pub fn git_worktree_remove(repo_path: &Path, worktree_path: &Path, force: bool) -> Result<()> {
+    crate::read_only::check("remove worktree")?;
+    let _lock = crate::lock::RepoLock::acquire(repo_path, "git worktree remove")?;
     let mut args = vec!["worktree", "remove"];
     if force {
         args.push("--force");
@@ -132,10 +150,7 @@ pub fn git_worktree_remove(repo_path: &Path, worktree_path: &Path, force: bool)
     let wt_str = worktree_path.to_string_lossy();
     args.push(&wt_str);
 
-    let output = Command::new("git")
-        .args(&args)
-        .current_dir(repo_path)
-        .output()?;
+    let output = crate::git_runner::output(Command::new("git").args(&args).current_dir(repo_path))?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -145,49 +160,117 @@ pub fn git_worktree_remove(repo_path: &Path, worktree_path: &Path, force: bool)
 }
 
 pub fn git_status_summary(repo_path: &Path) -> Result<GitStatusSummary> {
-    let output = Command::new("git")
-        .args(["status", "--porcelain"])
-        .current_dir(repo_path)
-        .output()?;
+    git_status_summary_in(repo_path, &[])
+}
+
+/// [`git_status_summary`] limited to paths matching `pathspecs` (e.g.
+/// `src/**`); all paths when empty.
+pub fn git_status_summary_in(repo_path: &Path, pathspecs: &[String]) -> Result<GitStatusSummary> {
+    let output = crate::git_runner::output(
+        Command::new("git")
+            .args(["status", "--porcelain=v2", "-z", "--renames", "--"])
+            .args(pathspecs)
+            .current_dir(repo_path),
+    )?;
 
     let mut modified_files = Vec::new();
-    let mut untracked_count = 0;
-
-    // git status --porcelain format: "XY filename"
-    // Positions 0-1: index (X) and work-tree (Y) status codes
-    // Position 2: space separator
-    // Position 3+: filename
-    for line in String::from_utf8_lossy(&output.stdout).lines() {
-        if line.len() < 3 {
-            continue;
-        }
-        let status = &line[..2];
-        let file = &line[3..];
-
-        if status == "??" {
-            untracked_count += 1;
-        } else if !file.is_empty() {
-            // Tracked file with modifications (staged, unstaged, or both).
-            // For renames ("R  old -> new"), extract the new name.
-            let name = file.split(" -> ").last().unwrap_or(file);
-            modified_files.push(name.to_string());
+    let mut untracked_files = Vec::new();
+    let mut renames = Vec::new();
+
+    for entry in parse_status_v2(&output.stdout) {
+        match entry {
+            StatusEntry::Untracked { path } => untracked_files.push(path),
+            StatusEntry::Ignored { .. } => {}
+            // Tracked file with modifications (staged, unstaged, or both);
+            // renames are listed under their new name
+            StatusEntry::Changed {
+                path, renamed_from, ..
+            } => {
+                renames.extend(renamed_from.map(|source| rename_entry(source, &path)));
+                modified_files.push(path)
+            }
+            StatusEntry::Unmerged { path } => modified_files.push(path),
         }
     }
 
-    let dirty = !modified_files.is_empty() || untracked_count > 0;
+    let dirty = !modified_files.is_empty() || !untracked_files.is_empty();
     Ok(GitStatusSummary {
         dirty,
         modified_files,
-        untracked_count,
+        untracked_count: untracked_files.len(),
+        untracked_files,
+        renames,
     })
 }
 
+/// Bytes of paths passed to one `git check-ignore`, well under the command
+/// line limits of every platform (32 KiB on Windows).
+const CHECK_IGNORE_BATCH_BYTES: usize = 16 * 1024;
+
+/// Whether each of `paths` (relative to `repo_path`) is ignored, and the
+/// rule that decides it, from `git check-ignore -v`. Tracked paths are
+/// never ignored.
+pub fn git_check_ignore(
+    repo_path: &Path,
+    paths: &[String],
+) -> Result<Vec<(String, Option<IgnoreRule>)>> {
+    let mut checks = Vec::with_capacity(paths.len());
+    let mut rest = paths;
+    while !rest.is_empty() {
+        let mut bytes = 0;
+        let len = rest
+            .iter()
+            .take_while(|path| {
+                bytes += path.len() + 1;
+                bytes <= CHECK_IGNORE_BATCH_BYTES
+            })
+            .count()
+            .max(1);
+        let (batch, remaining) = rest.split_at(len);
+        checks.extend(check_ignore_batch(repo_path, batch)?);
+        rest = remaining;
+    }
+    Ok(checks)
+}
+
+fn check_ignore_batch(
+    repo_path: &Path,
+    paths: &[String],
+) -> Result<Vec<(String, Option<IgnoreRule>)>> {
+    // -z separates the fields of the output with NULs
+    let output = crate::git_runner::output(
+        Command::new("git")
+            .args(["check-ignore", "-v", "-n", "-z", "--"])
+            .args(paths)
+            .current_dir(repo_path),
+    )?;
+    // Exit status 1 only means nothing was ignored
+    if !matches!(output.status.code(), Some(0 | 1)) {
+        anyhow::bail!(
+            "git check-ignore failed in {}: {}",
+            repo_path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(parse_check_ignore(&output.stdout))
+}
+
+fn rename_entry(source: RenameSource, to: &str) -> RenameEntry {
+    RenameEntry {
+        from: source.from,
+        to: to.to_string(),
+        similarity: source.similarity,
+        copy: source.copy,
+    }
+}
+
 pub fn git_ahead_behind(repo_path: &Path) -> Result<(u32, u32)> {
-    let output = Command::new("git")
-        .args(["rev-list", "--left-right", "--count", "HEAD...@{upstream}"])
-        .current_dir(repo_path)
-        .stderr(Stdio::null())
-        .output()?;
+    let output = crate::git_runner::output(
+        Command::new("git")
+            .args(["rev-list", "--left-right", "--count", "HEAD...@{upstream}"])
+            .current_dir(repo_path)
+            .stderr(Stdio::null()),
+    )?;
 
     if !output.status.success() {
         // No upstream configured
@@ -205,52 +288,168 @@ pub fn git_ahead_behind(repo_path: &Path) -> Result<(u32, u32)> {
     }
 }
 
+/// When the repo last fetched from a remote.
+///
+/// Uses the newer of `FETCH_HEAD`'s mtime and the autofetch log entry.
+/// Returns `None` if the repo has never been fetched.
+pub fn git_last_fetched(repo_path: &Path) -> Option<DateTime<Utc>> {
+    let from_fetch_head = git_common_dir(repo_path)
+        .and_then(|dir| std::fs::metadata(dir.join("FETCH_HEAD")).ok())
+        .and_then(|m| m.modified().ok())
+        .map(DateTime::<Utc>::from);
+
+    let key = repo_path
+        .canonicalize()
+        .unwrap_or_else(|_| repo_path.to_path_buf());
+    let from_log = crate::autofetch::read_log()
+        .ok()
+        .and_then(|log| log.repos.get(&*key.to_string_lossy()).cloned())
+        .filter(|r| r.success)
+        .and_then(|r| DateTime::parse_from_rfc3339(&r.fetched_at).ok())
+        .map(|dt| dt.with_timezone(&Utc));
+
+    from_fetch_head.max(from_log)
+}
+
+/// Committer time of HEAD, or `None` for a repo without commits.
+pub fn git_last_commit_time(repo_path: &Path) -> Option<DateTime<Utc>> {
+    let output = crate::git_runner::output(
+        Command::new("git")
+            .args(["log", "-1", "--format=%cI"])
+            .current_dir(repo_path)
+            .stderr(Stdio::null()),
+    )
+    .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    DateTime::parse_from_rfc3339(String::from_utf8_lossy(&output.stdout).trim())
+        .ok()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// Resolve the common git directory (shared by all linked worktrees).
+pub(crate) fn git_common_dir(repo_path: &Path) -> Option<std::path::PathBuf> {
+    let output = crate::git_runner::output(
+        crate::credentials::suppress_prompts(&mut Command::new("git"))
+            .args(["rev-parse", "--git-common-dir"])
+            .current_dir(repo_path)
+            .stderr(Stdio::null()),
+    )
+    .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let dir = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Some(repo_path.join(dir))
+}
+
+/// Fetch the repo if its remote data is older than `max_age_secs`.
+///
+/// Returns whether a fetch was performed. A failed fetch is logged and the
+/// stale data is used as-is.
+pub fn git_fetch_if_stale(repo_path: &Path, max_age_secs: u64) -> Result<bool> {
+    // Read-only callers get the stale data, same as after a failed fetch
+    if crate::read_only::is_read_only() {
+        return Ok(false);
+    }
+    let fresh = git_last_fetched(repo_path)
+        .is_some_and(|t| (Utc::now() - t).num_seconds() < max_age_secs as i64);
+    if fresh {
+        return Ok(false);
+    }
+
+    let _lock = crate::lock::RepoLock::acquire(repo_path, "git fetch")?;
+    let output = crate::ssh_multiplexing::output_with_mux_recovery(
+        crate::credentials::suppress_prompts(&mut Command::new("git"))
+            .args(["fetch", "--quiet"])
+            .current_dir(repo_path)
+            .stderr(Stdio::piped()),
+        &MultiplexingConfig::for_path(repo_path),
+    )?;
+    if !output.status.success() {
+        log::warn!(
+            "Failed to refresh {}: {}",
+            repo_path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        return Ok(false);
+    }
+    Ok(true)
+}
+
 pub fn git_diff_stat(
     worktree_path: &Path,
     base_ref: &str,
 ) -> Result<(usize, usize, usize, Vec<String>)> {
-    // Try three-dot diff first (changes since divergence)
-    let numstat_output = Command::new("git")
-        .args(["diff", "--numstat", &format!("{base_ref}...HEAD")])
-        .current_dir(worktree_path)
-        .stderr(Stdio::null())
-        .output()?;
+    let summary = git_diff_summary(worktree_path, base_ref, &[])?;
+    Ok((
+        summary.files_changed,
+        summary.insertions,
+        summary.deletions,
+        summary.files,
+    ))
+}
 
-    let numstat_text = if numstat_output.status.success() {
-        String::from_utf8_lossy(&numstat_output.stdout).to_string()
-    } else {
-        // Fallback to two-dot diff
-        let fallback = Command::new("git")
-            .args(["diff", "--numstat", &format!("{base_ref}..HEAD")])
-            .current_dir(worktree_path)
-            .stderr(Stdio::null())
-            .output()?;
-        if fallback.status.success() {
-            String::from_utf8_lossy(&fallback.stdout).to_string()
-        } else {
-            String::new()
+/// Files changed between `base_ref` and HEAD, limited to paths matching
+/// `pathspecs` (all paths when empty), with renames and copies detected.
+pub fn git_diff_summary(
+    worktree_path: &Path,
+    base_ref: &str,
+    pathspecs: &[String],
+) -> Result<GitDiffSummary> {
+    // Try three-dot diff first (changes since divergence), then two-dot
+    let mut range = None;
+    let mut numstat = Vec::new();
+    for candidate in [format!("{base_ref}...HEAD"), format!("{base_ref}..HEAD")] {
+        let output = crate::git_runner::output(&mut diff_command(
+            worktree_path,
+            "--numstat",
+            &candidate,
+            pathspecs,
+        ))?;
+        if output.status.success() {
+            numstat = output.stdout;
+            range = Some(candidate);
+            break;
         }
-    };
+    }
 
-    let mut files_changed = 0;
-    let mut insertions = 0;
-    let mut deletions = 0;
-    let mut files = Vec::new();
+    let mut summary = GitDiffSummary::default();
+    for entry in parse_numstat(&numstat) {
+        summary.files_changed += 1;
+        // Binary files count as changed without line counts
+        summary.insertions += entry.insertions.unwrap_or(0);
+        summary.deletions += entry.deletions.unwrap_or(0);
+        summary.files.push(entry.path);
+    }
 
-    for line in numstat_text.lines() {
-        if line.is_empty() {
-            continue;
-        }
-        let parts: Vec<&str> = line.split('\t').collect();
-        if parts.len() >= 3 {
-            files_changed += 1;
-            insertions += parts[0].parse::<usize>().unwrap_or(0);
-            deletions += parts[1].parse::<usize>().unwrap_or(0);
-            files.push(parts[2].to_string());
+    // --numstat names the old path of a rename but not its similarity
+    if let Some(range) = range.filter(|_| summary.files_changed > 0) {
+        let output = crate::git_runner::output(&mut diff_command(
+            worktree_path,
+            "--name-status",
+            &range,
+            pathspecs,
+        ))?;
+        if output.status.success() {
+            summary.renames = parse_name_status_renames(&output.stdout)
+                .into_iter()
+                .map(|(to, source)| rename_entry(source, &to))
+                .collect();
         }
     }
 
-    Ok((files_changed, insertions, deletions, files))
+    Ok(summary)
+}
+
+fn diff_command(worktree_path: &Path, format: &str, range: &str, pathspecs: &[String]) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.args(["diff", format, "-z", "-M", range, "--"])
+        .args(pathspecs)
+        .current_dir(worktree_path)
+        .stderr(Stdio::null());
+    cmd
 }
 
 /// Remove all worktree repos in correct order (children first, "." last).
@@ -262,6 +461,7 @@ pub fn remove_worktree_repos(
     force: bool,
     verbose: bool,
 ) -> Result<usize> {
+    crate::read_only::check("remove worktree")?;
     let mut failures = 0;
 
     // Remove child repos first
@@ -303,11 +503,15 @@ pub fn remove_worktree_repos(
 
 /// Fetch a branch from origin if not locally available.
 pub fn git_fetch_branch(repo_path: &Path, branch: &str) -> Result<()> {
-    let output = Command::new("git")
-        .args(["fetch", "origin", branch])
-        .current_dir(repo_path)
-        .stderr(Stdio::piped())
-        .output()?;
+    crate::read_only::check("fetch")?;
+    let _lock = crate::lock::RepoLock::acquire(repo_path, "git fetch")?;
+    let output = crate::ssh_multiplexing::output_with_mux_recovery(
+        crate::credentials::suppress_prompts(&mut Command::new("git"))
+            .args(["fetch", "origin", branch])
+            .current_dir(repo_path)
+            .stderr(Stdio::piped()),
+        &MultiplexingConfig::for_path(repo_path),
+    )?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -316,6 +520,32 @@ pub fn git_fetch_branch(repo_path: &Path, branch: &str) -> Result<()> {
     Ok(())
 }
 
+/// Fetch the head of pull request `pr` from origin (GitHub's
+/// `refs/pull/<pr>/head`) and return the ref it was stored as,
+/// `origin/pr/<pr>`.
+pub fn git_fetch_pr(repo_path: &Path, pr: u32) -> Result<String> {
+    crate::read_only::check("fetch")?;
+    let _lock = crate::lock::RepoLock::acquire(repo_path, "git fetch")?;
+    let refspec = format!("+refs/pull/{pr}/head:refs/remotes/origin/pr/{pr}");
+    let mut cmd = Command::new("git");
+    crate::credentials::suppress_prompts(&mut cmd);
+    if let Some(url) = crate::get_remote_url(repo_path) {
+        crate::credentials::HttpsTokens::from_env().apply(&mut cmd, &url);
+    }
+    let output = crate::ssh_multiplexing::output_with_mux_recovery(
+        cmd.args(["fetch", "origin", &refspec])
+            .current_dir(repo_path)
+            .stderr(Stdio::piped()),
+        &MultiplexingConfig::for_path(repo_path),
+    )?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("Failed to fetch PR #{}: {}", pr, stderr.trim());
+    }
+    Ok(format!("origin/pr/{pr}"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -433,6 +663,50 @@ mod tests {
         assert!(summary.modified_files.contains(&"README.md".to_string()));
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn status_summary_and_diff_stat_keep_unusual_names() {
+        let tmp = init_git_repo();
+        make_initial_commit(tmp.path());
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .args(args)
+                .current_dir(tmp.path())
+                .stdout(Stdio::null())
+                .status()
+                .unwrap();
+        };
+        let names = ["with space.txt", "naïve.txt", "line\nbreak.txt"];
+        for name in names {
+            std::fs::write(tmp.path().join(name), "one\n").unwrap();
+        }
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "add"]);
+
+        let (files_changed, insertions, _, files) = git_diff_stat(tmp.path(), "HEAD~1").unwrap();
+        assert_eq!((files_changed, insertions), (3, 3));
+        for name in names {
+            assert!(files.contains(&name.to_string()), "{name}: {files:?}");
+        }
+
+        for name in names {
+            std::fs::write(tmp.path().join(name), "two\n").unwrap();
+        }
+        git(&["mv", "README.md", "read me.md"]);
+        let summary = git_status_summary(tmp.path()).unwrap();
+        let mut modified = summary.modified_files.clone();
+        modified.sort();
+        assert_eq!(
+            modified,
+            [
+                "line\nbreak.txt",
+                "naïve.txt",
+                "read me.md",
+                "with space.txt"
+            ]
+        );
+    }
+
     // ── git_ahead_behind ────────────────────────────────────
 
     #[test]
@@ -445,6 +719,66 @@ mod tests {
         assert_eq!(behind, 0);
     }
 
+    // ── git_check_ignore ────────────────────────────────────
+
+    #[test]
+    fn check_ignore_handles_more_paths_than_fit_in_one_batch() {
+        let tmp = init_git_repo();
+        std::fs::write(tmp.path().join(".gitignore"), "*.log\n").unwrap();
+        let paths: Vec<String> = (0..4000)
+            .map(|i| format!("some/fairly/deep/dir/file-{i}.{}", ["log", "rs"][i % 2]))
+            .collect();
+
+        let checks = git_check_ignore(tmp.path(), &paths).unwrap();
+        assert_eq!(checks.len(), paths.len());
+        for (i, (path, rule)) in checks.iter().enumerate() {
+            assert_eq!(path, &paths[i]);
+            assert_eq!(rule.is_some(), i % 2 == 0, "{path}");
+        }
+        assert!(git_check_ignore(tmp.path(), &[]).unwrap().is_empty());
+    }
+
+    // ── git_last_fetched / git_fetch_if_stale ───────────────
+
+    #[test]
+    fn last_fetched_none_for_never_fetched_repo() {
+        let tmp = init_git_repo();
+        make_initial_commit(tmp.path());
+        assert!(git_last_fetched(tmp.path()).is_none());
+    }
+
+    #[test]
+    fn last_fetched_uses_fetch_head_mtime() {
+        let tmp = init_git_repo();
+        make_initial_commit(tmp.path());
+        std::fs::write(tmp.path().join(".git/FETCH_HEAD"), "").unwrap();
+
+        let fetched = git_last_fetched(tmp.path()).unwrap();
+        assert!((Utc::now() - fetched).num_seconds() < 60);
+    }
+
+    #[test]
+    fn fetch_if_stale_skips_fresh_repo() {
+        let tmp = init_git_repo();
+        make_initial_commit(tmp.path());
+        std::fs::write(tmp.path().join(".git/FETCH_HEAD"), "").unwrap();
+
+        assert!(!git_fetch_if_stale(tmp.path(), 3600).unwrap());
+    }
+
+    // ── git_worktree_add ────────────────────────────────────
+
+    #[test]
+    fn worktree_add_refuses_jj_colocated_repo() {
+        let tmp = init_git_repo();
+        make_initial_commit(tmp.path());
+        std::fs::create_dir(tmp.path().join(".jj")).unwrap();
+
+        let dest = tempfile::tempdir().unwrap();
+        let err = git_worktree_add(tmp.path(), &dest.path().join("wt"), "feat", None).unwrap_err();
+        assert!(err.to_string().contains("colocated jj repo"));
+    }
+
     // ── remove_worktree_repos ordering ──────────────────────
     // These tests verify the ordering logic without actual git operations.
     // We construct WorktreeRepoInfo values and check that "." is processed last.
@@ -510,4 +844,78 @@ mod tests {
             .collect();
         assert_eq!(children, vec!["lib"]);
     }
+
+    // ── git_fetch_pr ────────────────────────────────────────
+
+    #[test]
+    fn fetch_pr_stores_pull_request_head() {
+        let origin = init_git_repo();
+        make_initial_commit(origin.path());
+        Command::new("git")
+            .args(["update-ref", "refs/pull/42/head", "HEAD"])
+            .current_dir(origin.path())
+            .status()
+            .unwrap();
+        let tmp = tempfile::tempdir().unwrap();
+        let clone = tmp.path().join("clone");
+        Command::new("git")
+            .args(["clone", "-q"])
+            .arg(origin.path())
+            .arg(&clone)
+            .status()
+            .unwrap();
+
+        assert_eq!(git_fetch_pr(&clone, 42).unwrap(), "origin/pr/42");
+        let verified = Command::new("git")
+            .args(["rev-parse", "--verify", "origin/pr/42"])
+            .current_dir(&clone)
+            .output()
+            .unwrap();
+        assert!(verified.status.success());
+        assert!(git_fetch_pr(&clone, 7).is_err());
+    }
+
+    #[test]
+    fn renames_are_detected_and_pathspecs_filter() {
+        let tmp = init_git_repo();
+        make_initial_commit(tmp.path());
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .args(args)
+                .current_dir(tmp.path())
+                .stdout(Stdio::null())
+                .status()
+                .unwrap();
+        };
+        std::fs::create_dir(tmp.path().join("src")).unwrap();
+        let body = "fn main() {}\n".repeat(20);
+        std::fs::write(tmp.path().join("src/old.rs"), &body).unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "add"]);
+        git(&["mv", "src/old.rs", "src/new.rs"]);
+        std::fs::write(tmp.path().join("README.md"), "changed\n").unwrap();
+
+        let summary = git_status_summary(tmp.path()).unwrap();
+        assert_eq!(summary.modified_files.len(), 2);
+        assert_eq!(
+            summary.renames,
+            [RenameEntry {
+                from: "src/old.rs".to_string(),
+                to: "src/new.rs".to_string(),
+                similarity: 100,
+                copy: false,
+            }]
+        );
+        let filtered = git_status_summary_in(tmp.path(), &["src/**".to_string()]).unwrap();
+        assert_eq!(filtered.modified_files, ["src/new.rs"]);
+
+        git(&["commit", "-q", "-am", "rename"]);
+        let diff = git_diff_summary(tmp.path(), "HEAD~1", &[]).unwrap();
+        assert_eq!(diff.files_changed, 2);
+        assert_eq!(diff.renames.len(), 1);
+        assert_eq!(diff.renames[0].to, "src/new.rs");
+        let diff = git_diff_summary(tmp.path(), "HEAD~1", &["README.md".to_string()]).unwrap();
+        assert_eq!(diff.files, ["README.md"]);
+        assert!(diff.renames.is_empty());
+    }
 }
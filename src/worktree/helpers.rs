@@ -25,9 +25,11 @@ pub fn validate_worktree_name(name: &str) -> Result<()> {
 }
 
 pub fn resolve_worktree_root(meta_dir: Option<&Path>) -> Result<PathBuf> {
-    // 1. Check META_WORKTREES env var
-    if let Ok(env_path) = std::env::var("META_WORKTREES") {
-        return Ok(PathBuf::from(env_path));
+    // 1. Check META_WORKTREES env var (the first root, if it lists several)
+    if let Some(env_paths) = std::env::var_os("META_WORKTREES") {
+        if let Some(first) = std::env::split_paths(&env_paths).find(|p| !p.as_os_str().is_empty()) {
+            return Ok(first);
+        }
     }
     // 2. Check worktrees_dir in .meta config
     if let Some(dir) = meta_dir {
@@ -76,12 +78,97 @@ pub fn read_worktrees_dir_from_config(meta_dir: &Path) -> Option<String> {
         .map(|s| s.to_string())
 }
 
+/// Find the meta dir for the current directory.
+///
+/// Walks up from cwd; inside a worktree whose tree has no `.meta` of its
+/// own, falls back to the workspace the worktree was created from (see
+/// [`resolve_meta_dir_from`]).
 pub fn find_meta_dir() -> Option<PathBuf> {
     let cwd = std::env::current_dir().ok()?;
-    meta_core::config::find_meta_config(&cwd, None)
+    walk_up_for_meta_dir(&cwd).or_else(|| resolve_meta_dir_from(&cwd).map(|r| r.meta_dir))
+}
+
+fn walk_up_for_meta_dir(start: &Path) -> Option<PathBuf> {
+    meta_core::config::find_meta_config(start, None)
         .map(|(path, _)| path.parent().unwrap_or(Path::new(".")).to_path_buf())
 }
 
+/// Where a directory sits relative to its meta workspace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetaDirResolution {
+    /// The originating workspace (the source checkout for worktrees)
+    pub meta_dir: PathBuf,
+    /// Set when the directory is inside a worktree
+    pub worktree: Option<WorktreeContext>,
+}
+
+/// Resolve the meta workspace for `start`, recognizing worktrees.
+///
+/// Checks, in order:
+/// 1. the worktree store: `start` is inside a registered worktree
+/// 2. git's worktree metadata: `start` is inside a linked worktree whose
+///    main checkout belongs to a meta workspace
+/// 3. a plain walk up from `start` for a `.meta` file
+pub fn resolve_meta_dir_from(start: &Path) -> Option<MetaDirResolution> {
+    let start = start.canonicalize().unwrap_or_else(|_| start.to_path_buf());
+    resolve_from_store(&start)
+        .or_else(|| resolve_from_git_worktree(&start))
+        .or_else(|| {
+            walk_up_for_meta_dir(&start).map(|meta_dir| MetaDirResolution {
+                meta_dir,
+                worktree: None,
+            })
+        })
+}
+
+/// [`resolve_meta_dir_from`] for the current directory.
+pub fn resolve_meta_dir() -> Option<MetaDirResolution> {
+    resolve_meta_dir_from(&std::env::current_dir().ok()?)
+}
+
+fn worktree_resolution(meta_dir: PathBuf, wt_dir: PathBuf) -> MetaDirResolution {
+    MetaDirResolution {
+        worktree: Some(WorktreeContext {
+            meta_dir: Some(meta_dir.clone()),
+            worktree_root: wt_dir.parent().unwrap_or(&wt_dir).to_path_buf(),
+            wt_dir,
+        }),
+        meta_dir,
+    }
+}
+
+fn resolve_from_store(start: &Path) -> Option<MetaDirResolution> {
+    let store = super::store::store_list().ok()?;
+    store
+        .worktrees
+        .into_iter()
+        .filter(|(root, _)| start.starts_with(root))
+        // Innermost registered worktree wins
+        .max_by_key(|(root, _)| root.len())
+        .map(|(root, entry)| worktree_resolution(PathBuf::from(entry.project), PathBuf::from(root)))
+}
+
+fn resolve_from_git_worktree(start: &Path) -> Option<MetaDirResolution> {
+    // Linked worktrees have a `.git` file instead of a directory
+    let checkout = start
+        .ancestors()
+        .find(|dir| dir.join(".git").is_file())?
+        .to_path_buf();
+    let common_dir = super::git_ops::git_common_dir(&checkout)?
+        .canonicalize()
+        .ok()?;
+    let main_checkout = common_dir.parent()?;
+    let meta_dir = walk_up_for_meta_dir(main_checkout)?.canonicalize().ok()?;
+
+    // The repo's alias path inside the workspace is its path inside the worktree
+    let alias = main_checkout.strip_prefix(&meta_dir).ok()?;
+    let mut wt_dir = checkout.as_path();
+    for _ in alias.components() {
+        wt_dir = wt_dir.parent()?;
+    }
+    Some(worktree_resolution(meta_dir, wt_dir.to_path_buf()))
+}
+
 /// Like `find_meta_dir()` but returns an error if not found.
 pub fn require_meta_dir() -> Result<PathBuf> {
     find_meta_dir().ok_or_else(|| anyhow::anyhow!("Not inside a meta project (no .meta found)"))
@@ -89,10 +176,16 @@ pub fn require_meta_dir() -> Result<PathBuf> {
 
 /// Resolve worktree context for a named worktree.
 /// Returns meta_dir (for hooks), worktree_root, and the specific worktree directory.
+///
+/// With several worktree roots, an existing worktree is found in whichever
+/// root holds it; otherwise the primary root is used.
 pub fn resolve_worktree_context(name: &str) -> Result<WorktreeContext> {
     let meta_dir = find_meta_dir();
-    let worktree_root = resolve_worktree_root(meta_dir.as_deref())?;
-    let wt_dir = worktree_root.join(name);
+    let wt_dir = match super::placement::find_worktree(meta_dir.as_deref(), name)? {
+        Some(existing) => existing,
+        None => resolve_worktree_root(meta_dir.as_deref())?.join(name),
+    };
+    let worktree_root = wt_dir.parent().unwrap_or(&wt_dir).to_path_buf();
     Ok(WorktreeContext {
         meta_dir,
         worktree_root,
@@ -124,11 +217,16 @@ pub fn discover_and_validate_worktree(
 }
 
 /// Load and parse the .meta config, returning the project list.
+///
+/// Fails if any project path escapes `meta_dir`.
 pub fn load_projects(meta_dir: &Path) -> Result<Vec<meta_core::config::ProjectInfo>> {
     let (config_path, _) = meta_core::config::find_meta_config(meta_dir, None)
         .ok_or_else(|| anyhow::anyhow!("No .meta config found in {}", meta_dir.display()))?;
-    let (projects, _) = meta_core::config::parse_meta_config(&config_path)?;
-    Ok(projects)
+    let projects = crate::workspace_model::parse_config(&config_path)?;
+    for project in projects.iter() {
+        crate::sandbox::validate_project_path(meta_dir, &project.name, &project.path)?;
+    }
+    Ok(projects.to_vec())
 }
 
 /// Load projects and optionally include the root repo as ".".
@@ -184,7 +282,8 @@ pub fn lookup_project<'a>(
 /// Look up a project by alias, supporting nested paths like "vendor/tree-sitter-markdown".
 ///
 /// For simple aliases (no `/`), uses flat lookup from the current .meta.
-/// For nested paths, walks the meta tree recursively to find the project.
+/// For nested paths, looks the project up in the (cached)
+/// [`WorkspaceModel`](crate::workspace_model::WorkspaceModel).
 ///
 /// Returns the resolved path and project info.
 pub fn lookup_nested_project(
@@ -193,23 +292,12 @@ pub fn lookup_nested_project(
 ) -> Result<(PathBuf, meta_core::config::ProjectInfo)> {
     // If alias contains '/', use recursive lookup
     if alias.contains('/') {
-        let tree = meta_core::config::walk_meta_tree(meta_dir, None)?;
-
-        // Build a map of full path -> ProjectInfo
-        let project_map = meta_core::config::build_project_map(&tree, meta_dir, "");
-
-        project_map.get(alias).cloned().ok_or_else(|| {
-            // Use keys from the map we already built (avoids re-walking the tree)
-            let mut valid_paths: Vec<_> = project_map.keys().collect();
-            valid_paths.sort();
+        let model = crate::workspace_model::WorkspaceModel::load(meta_dir)?;
+        model.lookup(alias).cloned().ok_or_else(|| {
             anyhow::anyhow!(
                 "Unknown nested repo: '{}'. Valid nested paths:\n  {}",
                 alias,
-                valid_paths
-                    .into_iter()
-                    .cloned()
-                    .collect::<Vec<_>>()
-                    .join("\n  ")
+                model.keys().join("\n  ")
             )
         })
     } else {
@@ -220,6 +308,19 @@ pub fn lookup_nested_project(
     }
 }
 
+/// Ref a new worktree of `spec` starts from: the head of its PR (fetched
+/// into `source`), its base, or else `default_ref`.
+pub fn resolve_start_ref(
+    source: &Path,
+    spec: &super::types::RepoSpec,
+    default_ref: Option<&str>,
+) -> Result<Option<String>> {
+    if let Some(pr) = spec.pr {
+        return super::git_ops::git_fetch_pr(source, pr).map(Some);
+    }
+    Ok(spec.base.as_deref().or(default_ref).map(str::to_string))
+}
+
 pub fn resolve_branch(
     task_name: &str,
     branch_flag: Option<&str>,
@@ -345,10 +446,11 @@ pub fn resolve_from_pr(from_pr: &str) -> Result<(String, u32, String)> {
 pub fn repo_matches_spec(repo_path: &Path, spec: &str) -> bool {
     use std::process::Command;
 
-    let output = Command::new("git")
-        .args(["remote", "get-url", "origin"])
-        .current_dir(repo_path)
-        .output();
+    let output = crate::git_runner::output(
+        Command::new("git")
+            .args(["remote", "get-url", "origin"])
+            .current_dir(repo_path),
+    );
 
     match output {
         Ok(o) if o.status.success() => {
@@ -365,6 +467,7 @@ pub fn ensure_worktrees_in_gitignore(
     worktrees_dirname: &str,
     quiet: bool,
 ) -> Result<()> {
+    crate::read_only::check("edit .gitignore")?;
     let gitignore_path = meta_dir.join(".gitignore");
     let pattern = format!("{worktrees_dirname}/");
 
@@ -394,6 +497,7 @@ pub fn ensure_worktrees_in_gitignore(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_fixtures::{repo, store_entry, worktree};
 
     // ── validate_worktree_name ──────────────────────────────
 
@@ -783,4 +887,66 @@ mod tests {
         assert_eq!(info.name, "deep-lib");
         assert_eq!(path, tmp.path().join("vendor/sub-vendor/deep-lib"));
     }
+
+    // ── resolve_meta_dir_from ───────────────────────────────
+
+    /// Workspace at `<tmp>/ws` with repo `api` and a worktree of it at
+    /// `<tmp>/elsewhere/feat/api`, outside the workspace tree.
+    fn workspace_with_outside_worktree(tmp: &Path) -> (PathBuf, PathBuf) {
+        let ws = tmp.join("ws");
+        let api = ws.join("api");
+        repo(&api, &[]);
+        std::fs::write(
+            ws.join(".meta"),
+            r#"{"projects": {"api": "git@x:api.git"}}"#,
+        )
+        .unwrap();
+        let wt_dir = tmp.join("elsewhere").join("feat");
+        worktree(&api, &wt_dir.join("api"), "feat");
+        (ws.canonicalize().unwrap(), wt_dir.canonicalize().unwrap())
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn resolves_worktree_outside_workspace_via_git_metadata() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp.path().join("store"));
+        let (ws, wt_dir) = workspace_with_outside_worktree(tmp.path());
+        let inside = wt_dir.join("api").join("src");
+        std::fs::create_dir_all(&inside).unwrap();
+
+        let resolved = resolve_meta_dir_from(&inside).unwrap();
+        assert_eq!(resolved.meta_dir, ws);
+        let ctx = resolved.worktree.unwrap();
+        assert_eq!(ctx.wt_dir, wt_dir);
+        assert_eq!(ctx.worktree_root, wt_dir.parent().unwrap());
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn resolves_registered_worktree_via_store() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp.path().join("store"));
+        let (ws, wt_dir) = workspace_with_outside_worktree(tmp.path());
+        super::super::store::store_add(&wt_dir, store_entry("feat", &ws).build()).unwrap();
+
+        // The worktree root itself has no `.git`, so only the store knows it
+        let resolved = resolve_meta_dir_from(&wt_dir).unwrap();
+        assert_eq!(resolved.meta_dir, ws);
+        assert_eq!(resolved.worktree.unwrap().wt_dir, wt_dir);
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn plain_workspace_has_no_worktree_context() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp.path().join("store"));
+        let (ws, _) = workspace_with_outside_worktree(tmp.path());
+        let resolved = resolve_meta_dir_from(&ws.join("api")).unwrap();
+        assert_eq!(resolved.meta_dir, ws);
+        assert!(resolved.worktree.is_none());
+        std::env::remove_var("META_DATA_DIR");
+    }
 }
@@ -1,63 +1,487 @@
 //! Worktree lifecycle hooks.
+//!
+//! `post-*` hooks are notifications: a failing one only logs a warning.
+//! `pre-create` and `pre-destroy` run before anything is touched and abort
+//! the operation when they exit non-zero (see [`HookAborted`]), unless it
+//! is forced, so teams can enforce naming policies or refuse to destroy a
+//! worktree with an open PR.
+//!
+//! Besides inline commands in the config, hooks can be executable files in
+//! a `.meta/hooks/` directory next to a `.meta.json` or `.meta.yaml` config,
+//! named after the hook (`.meta/hooks/pre-destroy`); see [`hook_script`].
+//! An inline command takes precedence over a script of the same name.
+//!
+//! Besides the JSON payload, simple scripts can use `$META_HOOK_ACTION`,
+//! `$META_WT_NAME`, `$META_WT_PATH`, and `$META_WT_REPOS` (space-separated
+//! aliases): `sh -c 'cp .env $META_WT_PATH'`.
+//!
+//! `post-add-repo` and `pre-remove-repo` fire once per repo, with
+//! `$META_REPO_ALIAS`, `$META_REPO_BRANCH`, `$META_REPO_SOURCE` (the
+//! project's checkout), and `$META_REPO_PATH` (its worktree), so per-repo
+//! setup is one line: `test -f $META_REPO_PATH/package.json && cd
+//! $META_REPO_PATH && npm ci`. A failing `pre-remove-repo` aborts the
+//! removal like `pre-destroy`.
+//!
+//! Command strings run through `sh -c`, or `cmd /C` on Windows; set
+//! `hook_shell` (top level in `.meta`) to `sh`, `cmd`, `powershell`, or
+//! `pwsh` to pick another (see [`HookShell`]). Argv hooks are executed
+//! directly either way.
+//!
+//! A hook still running after `hook_timeout_seconds` (top level in `.meta`,
+//! default 60; 0 for no limit) is killed and counts as failed. What it
+//! wrote to stderr goes into the warning (or [`HookAborted`] message);
+//! stdout is logged at debug level.
 
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::Path;
 use std::process::{Command, Stdio};
+use std::time::Duration;
 
 use super::helpers::read_meta_config_value;
-use super::types::{CreateRepoEntry, PruneEntry};
+use super::types::{CreateRepoEntry, PlannedRepoEntry, PruneEntry};
+use crate::process_timeout::{self, Watchdog};
+use meta_cli::worktree::WorktreeRepoInfo;
 
-/// Fire a worktree lifecycle hook if configured in `.meta`.
+/// Environment variable pointing hooks at a temp file holding the payload.
+pub const HOOK_PAYLOAD_FILE_ENV: &str = "META_HOOK_PAYLOAD_FILE";
+/// Environment variable carrying the payload inline, when small enough.
+pub const HOOK_PAYLOAD_ENV: &str = "META_HOOK_PAYLOAD";
+/// Directory of executable hook scripts, relative to the meta dir.
+pub const HOOKS_DIR: &str = ".meta/hooks";
+/// Time limit for a hook unless `hook_timeout_seconds` is set.
+pub const DEFAULT_HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+/// Environment variable with the payload's `action`.
+pub const HOOK_ACTION_ENV: &str = "META_HOOK_ACTION";
+/// Environment variable with the worktree's name, for worktree hooks.
+pub const WT_NAME_ENV: &str = "META_WT_NAME";
+/// Environment variable with the worktree's path, for worktree hooks.
+pub const WT_PATH_ENV: &str = "META_WT_PATH";
+/// Environment variable with the worktree's repo aliases, space-separated.
+pub const WT_REPOS_ENV: &str = "META_WT_REPOS";
+/// Environment variable with the repo's alias, for per-repo hooks.
+pub const REPO_ALIAS_ENV: &str = "META_REPO_ALIAS";
+/// Environment variable with the repo's branch, for per-repo hooks.
+pub const REPO_BRANCH_ENV: &str = "META_REPO_BRANCH";
+/// Environment variable with the repo's source checkout, for per-repo hooks.
+pub const REPO_SOURCE_ENV: &str = "META_REPO_SOURCE";
+/// Environment variable with the repo's worktree path, for per-repo hooks.
+pub const REPO_PATH_ENV: &str = "META_REPO_PATH";
+/// Top-level `.meta` key naming the [`HookShell`], next to
+/// `hook_timeout_seconds`.
+pub const HOOK_SHELL_KEY: &str = "hook_shell";
+/// Payloads larger than this are only passed via stdin and the temp file.
+const MAX_ENV_PAYLOAD_BYTES: usize = 32 * 1024;
+
+/// A configured hook command.
 ///
-/// Reads the `.meta` config for `worktree.hooks.<hook_name>`.
-/// If configured, spawns the command and pipes `payload` JSON to stdin.
-/// Hook failure prints a warning but doesn't block the operation.
-pub fn fire_worktree_hook(hook_name: &str, payload: &serde_json::Value, meta_dir: Option<&Path>) {
-    let dir = match meta_dir {
-        Some(d) => d,
-        None => return,
-    };
+/// Also used for per-project setup commands (see [`crate::setup`]), so it
+/// (de)serializes as either form.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged, try_from = "serde_json::Value")]
+pub enum HookCommand {
+    /// `"hook": "cmd --flag"`, run through the [`HookShell`]
+    Shell(String),
+    /// `"hook": ["cmd", "--flag"]`, executed directly without a shell
+    Argv(Vec<String>),
+}
 
-    let config = match read_meta_config_value(dir) {
-        Some(c) => c,
-        None => return,
-    };
+impl HookCommand {
+    /// Parse a hook value from `.meta`. Returns `None` for anything that is
+    /// neither a string nor a non-empty array of strings.
+    pub fn from_value(value: &serde_json::Value) -> Option<Self> {
+        match value {
+            serde_json::Value::String(s) => Some(HookCommand::Shell(s.clone())),
+            serde_json::Value::Array(items) => {
+                let argv = items
+                    .iter()
+                    .map(|v| v.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()?;
+                if argv.is_empty() {
+                    None
+                } else {
+                    Some(HookCommand::Argv(argv))
+                }
+            }
+            _ => None,
+        }
+    }
 
-    let hook_cmd = config
-        .get("worktree")
-        .and_then(|wt| wt.get("hooks"))
-        .and_then(|hooks| hooks.get(hook_name))
-        .and_then(|v| v.as_str());
+    /// The command to run, with the platform's default shell.
+    pub(crate) fn command(&self) -> Command {
+        self.command_in(HookShell::default())
+    }
 
-    let cmd_str = match hook_cmd {
-        Some(c) => c,
-        None => return,
-    };
+    /// The command to run, with command strings run through `shell`.
+    pub(crate) fn command_in(&self, shell: HookShell) -> Command {
+        match self {
+            HookCommand::Shell(cmd) => shell.command(cmd),
+            HookCommand::Argv(argv) => {
+                let mut command = Command::new(&argv[0]);
+                command.args(&argv[1..]);
+                command
+            }
+        }
+    }
+}
 
-    let payload_json = match serde_json::to_string(payload) {
-        Ok(j) => j,
-        Err(_) => return,
-    };
+impl TryFrom<serde_json::Value> for HookCommand {
+    type Error = String;
 
-    let result = Command::new("sh")
-        .args(["-c", cmd_str])
-        .stdin(Stdio::piped())
-        .stdout(Stdio::null())
-        .stderr(Stdio::piped())
-        .spawn()
-        .and_then(|mut child| {
-            // Write payload then drop stdin to signal EOF before waiting
-            if let Some(mut stdin) = child.stdin.take() {
-                use std::io::Write;
-                let _ = stdin.write_all(payload_json.as_bytes());
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        HookCommand::from_value(&value)
+            .ok_or_else(|| format!("expected a command string or argv array, got {value}"))
+    }
+}
+
+impl std::fmt::Display for HookCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HookCommand::Shell(cmd) => f.write_str(cmd),
+            HookCommand::Argv(argv) => f.write_str(&argv.join(" ")),
+        }
+    }
+}
+
+/// The shell that runs [`HookCommand::Shell`] hooks, configured as
+/// `hook_shell`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HookShell {
+    /// `sh -c`
+    Sh,
+    /// `cmd /C`
+    Cmd,
+    /// Windows PowerShell, `powershell -Command`
+    PowerShell,
+    /// PowerShell 7+, `pwsh -Command`
+    Pwsh,
+}
+
+impl Default for HookShell {
+    /// `cmd` on Windows, `sh` everywhere else.
+    fn default() -> Self {
+        if cfg!(windows) {
+            HookShell::Cmd
+        } else {
+            HookShell::Sh
+        }
+    }
+}
+
+impl HookShell {
+    /// A command running `script` in this shell.
+    pub fn command(self, script: &str) -> Command {
+        match self {
+            HookShell::Sh => {
+                let mut command = Command::new("sh");
+                command.args(["-c", script]);
+                command
+            }
+            HookShell::Cmd => {
+                let mut command = Command::new("cmd");
+                command.args(["/D", "/S", "/C"]);
+                cmd_script_arg(&mut command, script);
+                command
             }
-            // stdin is now dropped — child sees EOF
-            child.wait()
+            HookShell::PowerShell | HookShell::Pwsh => {
+                let program = if self == HookShell::Pwsh {
+                    "pwsh"
+                } else {
+                    "powershell"
+                };
+                let mut command = Command::new(program);
+                command.args(["-NoProfile", "-NonInteractive", "-Command", script]);
+                command
+            }
+        }
+    }
+}
+
+/// cmd.exe doesn't understand the `\"` escaping Rust applies to arguments,
+/// so the script is passed verbatim; with `/S` cmd strips just the outer
+/// quotes and runs the rest as typed.
+#[cfg(windows)]
+fn cmd_script_arg(command: &mut Command, script: &str) {
+    use std::os::windows::process::CommandExt;
+    command.raw_arg(format!("\"{script}\""));
+}
+
+#[cfg(not(windows))]
+fn cmd_script_arg(command: &mut Command, script: &str) {
+    command.arg(script);
+}
+
+/// The [`HookShell`] for the workspace at `meta_dir`: its `hook_shell`, or
+/// the platform default. An unknown shell is reported and ignored.
+pub fn hook_shell(meta_dir: Option<&Path>) -> HookShell {
+    let configured = meta_dir
+        .and_then(read_meta_config_value)
+        .and_then(|config| config.get(HOOK_SHELL_KEY).cloned());
+    match configured.map(serde_json::from_value::<HookShell>) {
+        Some(Ok(shell)) => shell,
+        Some(Err(e)) => {
+            log::warn!("Ignoring {HOOK_SHELL_KEY}: {e}");
+            HookShell::default()
+        }
+        None => HookShell::default(),
+    }
+}
+
+/// Write `payload` to a fresh temp file for the hook to read. The file is
+/// created exclusively, readable only by us (0600 on Unix), and deleted
+/// when dropped.
+fn write_payload_file(hook_name: &str, payload: &str) -> Option<tempfile::NamedTempFile> {
+    use std::io::Write;
+    let written = tempfile::Builder::new()
+        .prefix(&format!("meta-hook-{hook_name}-"))
+        .suffix(".json")
+        .tempfile()
+        .and_then(|mut file| {
+            file.write_all(payload.as_bytes())?;
+            file.flush()?;
+            Ok(file)
         });
+    match written {
+        Ok(file) => Some(file),
+        Err(e) => {
+            log::debug!("Could not write hook payload file: {e}");
+            None
+        }
+    }
+}
+
+/// A `pre-*` hook exited non-zero (or couldn't run), so the operation it
+/// guards was not performed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookAborted {
+    pub hook: String,
+    /// Exit code, `None` if the hook couldn't be run or was killed
+    pub status: Option<i32>,
+    /// What the hook wrote to stderr, or why it couldn't be run
+    pub message: String,
+}
+
+impl std::fmt::Display for HookAborted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Hook '{}' aborted the operation", self.hook)?;
+        if let Some(code) = self.status {
+            write!(f, " (exit status {code})")?;
+        }
+        if !self.message.is_empty() {
+            write!(f, ": {}", self.message)?;
+        }
+        write!(f, "; use force to override")
+    }
+}
+
+impl std::error::Error for HookAborted {}
 
+/// The command configured as `worktree.hooks.<hook_name>` in the `.meta`
+/// file in `meta_dir`, either a shell string or an argv array (see
+/// [`HookCommand`]), or else the [`hook_script`] of that name.
+fn configured_hook(hook_name: &str, meta_dir: Option<&Path>) -> Option<HookCommand> {
+    let meta_dir = meta_dir?;
+    read_meta_config_value(meta_dir)
+        .and_then(|config| {
+            config
+                .get("worktree")
+                .and_then(|wt| wt.get("hooks"))
+                .and_then(|hooks| hooks.get(hook_name))
+                .and_then(HookCommand::from_value)
+        })
+        .or_else(|| hook_script(meta_dir, hook_name))
+}
+
+/// The executable `<meta_dir>/.meta/hooks/<hook_name>`, as a command.
+///
+/// A file there that isn't executable is skipped with a warning, since a
+/// hook that silently never runs is worse than one that's reported.
+pub fn hook_script(meta_dir: &Path, hook_name: &str) -> Option<HookCommand> {
+    if hook_name.is_empty() || hook_name.contains(['/', '\\']) || hook_name.starts_with('.') {
+        return None;
+    }
+    let path = meta_dir.join(HOOKS_DIR).join(hook_name);
+    if !path.is_file() {
+        return None;
+    }
+    if !is_executable(&path) {
+        log::warn!(
+            "Hook script {} is not executable; skipping it",
+            path.display()
+        );
+        return None;
+    }
+    Some(HookCommand::Argv(vec![path.to_string_lossy().into_owned()]))
+}
+
+/// Names of the files in `<meta_dir>/.meta/hooks/`, sorted.
+pub fn hook_script_names(meta_dir: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(meta_dir.join(HOOKS_DIR)) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter(|e| e.path().is_file())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter(|name| !name.starts_with('.'))
+        .collect();
+    names.sort();
+    names
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).is_ok_and(|m| m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    true
+}
+
+/// The time limit for hooks of the workspace at `meta_dir`: its
+/// `hook_timeout_seconds`, or [`DEFAULT_HOOK_TIMEOUT`]. `None` for no limit.
+pub fn hook_timeout(meta_dir: Option<&Path>) -> Option<Duration> {
+    let configured = meta_dir
+        .and_then(read_meta_config_value)
+        .and_then(|config| config.get("hook_timeout_seconds")?.as_u64());
+    match configured {
+        Some(0) => None,
+        Some(seconds) => Some(Duration::from_secs(seconds)),
+        None => Some(DEFAULT_HOOK_TIMEOUT),
+    }
+}
+
+/// Fire a worktree lifecycle hook if configured in `.meta`, with
+/// [`run_hook`].
+pub fn fire_worktree_hook(hook_name: &str, payload: &serde_json::Value, meta_dir: Option<&Path>) {
+    if let Some(hook) = configured_hook(hook_name, meta_dir) {
+        let result = spawn_hook(
+            hook_name,
+            hook.command_in(hook_shell(meta_dir)),
+            payload,
+            hook_timeout(meta_dir),
+            &worktree_env(payload),
+            meta_dir,
+        );
+        warn_on_failure(hook_name, result);
+    }
+}
+
+/// [`WT_NAME_ENV`], [`WT_PATH_ENV`], [`WT_REPOS_ENV`], and the per-repo
+/// `REPO_*_ENV` variables for a worktree hook's `payload`, for those it has.
+fn worktree_env(payload: &serde_json::Value) -> Vec<(&'static str, String)> {
+    let mut env = Vec::new();
+    for (var, key) in [
+        (WT_NAME_ENV, "name"),
+        (WT_PATH_ENV, "path"),
+        (REPO_ALIAS_ENV, "alias"),
+        (REPO_BRANCH_ENV, "branch"),
+        (REPO_SOURCE_ENV, "source"),
+        (REPO_PATH_ENV, "repo_path"),
+    ] {
+        if let Some(value) = payload.get(key).and_then(|v| v.as_str()) {
+            env.push((var, value.to_string()));
+        }
+    }
+    if let Some(repos) = payload.get("repos").and_then(|v| v.as_array()) {
+        let aliases: Vec<&str> = repos
+            .iter()
+            .filter_map(|r| r.get("alias")?.as_str())
+            .collect();
+        env.push((WT_REPOS_ENV, aliases.join(" ")));
+    }
+    env
+}
+
+/// Fire a `pre-*` hook if configured in `.meta`, failing with
+/// [`HookAborted`] if it exits non-zero or can't be run.
+///
+/// With `force` the hook still runs, but its failure is only a warning.
+pub fn fire_guard_hook(
+    hook_name: &str,
+    payload: &serde_json::Value,
+    meta_dir: Option<&Path>,
+    force: bool,
+) -> anyhow::Result<()> {
+    let Some(hook) = configured_hook(hook_name, meta_dir) else {
+        return Ok(());
+    };
+    let result = spawn_hook(
+        hook_name,
+        hook.command_in(hook_shell(meta_dir)),
+        payload,
+        hook_timeout(meta_dir),
+        &worktree_env(payload),
+        meta_dir,
+    );
+    let aborted = match result {
+        Ok(out) if out.status.success() => return Ok(()),
+        Ok(out) => HookAborted {
+            hook: hook_name.to_string(),
+            status: out.status.code(),
+            message: String::from_utf8_lossy(&out.stderr).trim().to_string(),
+        },
+        Err(e) => HookAborted {
+            hook: hook_name.to_string(),
+            status: None,
+            message: format!("failed to execute: {e}"),
+        },
+    };
+    if force {
+        log::warn!("{aborted} (ignored, forced)");
+        return Ok(());
+    }
+    Err(aborted.into())
+}
+
+/// Run `hook` with `payload`.
+///
+/// The payload JSON is piped to stdin, written to a temp file named by
+/// [`HOOK_PAYLOAD_FILE_ENV`], and, when small, set inline in
+/// [`HOOK_PAYLOAD_ENV`]; its `action` is also set in [`HOOK_ACTION_ENV`].
+/// Command strings run through `shell`, in `dir` if given, and the hook is
+/// killed after `timeout`. Hook failure prints a warning, with the hook's
+/// stderr, but doesn't block the operation.
+pub fn run_hook(
+    hook_name: &str,
+    hook: &HookCommand,
+    payload: &serde_json::Value,
+    timeout: Option<Duration>,
+    shell: HookShell,
+    dir: Option<&Path>,
+) {
+    warn_on_failure(
+        hook_name,
+        spawn_hook(
+            hook_name,
+            hook.command_in(shell),
+            payload,
+            timeout,
+            &[],
+            dir,
+        ),
+    );
+}
+
+fn warn_on_failure(hook_name: &str, result: std::io::Result<std::process::Output>) {
     match result {
-        Ok(status) if !status.success() => {
-            log::warn!("Hook '{hook_name}' exited with status {status}");
+        Ok(out) if !out.status.success() => {
+            let stderr = String::from_utf8_lossy(&out.stderr);
+            let stderr = stderr.trim();
+            if stderr.is_empty() {
+                log::warn!("Hook '{hook_name}' exited with status {}", out.status);
+            } else {
+                log::warn!(
+                    "Hook '{hook_name}' exited with status {}: {stderr}",
+                    out.status
+                );
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+            log::warn!("Hook '{hook_name}' timed out and was {e}");
         }
         Err(e) => {
             log::warn!("Hook '{hook_name}' failed to execute: {e}");
@@ -66,6 +490,143 @@ pub fn fire_worktree_hook(hook_name: &str, payload: &serde_json::Value, meta_dir
     }
 }
 
+/// Run `command` in `dir` with `payload` and `env` as described for
+/// [`run_hook`] and wait for it, capturing its output. Stdout is logged at
+/// debug level.
+fn spawn_hook(
+    hook_name: &str,
+    mut command: Command,
+    payload: &serde_json::Value,
+    timeout: Option<Duration>,
+    env: &[(&str, String)],
+    dir: Option<&Path>,
+) -> std::io::Result<std::process::Output> {
+    let payload_json = serde_json::to_string(payload)?;
+
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    command.env("META_HOOK_NAME", hook_name);
+    if let Some(action) = payload.get("action").and_then(|v| v.as_str()) {
+        command.env(HOOK_ACTION_ENV, action);
+    }
+    command.envs(env.iter().map(|(k, v)| (k, v)));
+    let payload_file = write_payload_file(hook_name, &payload_json);
+    if let Some(file) = &payload_file {
+        command.env(HOOK_PAYLOAD_FILE_ENV, file.path());
+    }
+    if payload_json.len() <= MAX_ENV_PAYLOAD_BYTES {
+        command.env(HOOK_PAYLOAD_ENV, &payload_json);
+    }
+
+    command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // The watchdog also covers a hook that never reads its stdin
+    let result = process_timeout::spawn(&mut command, timeout).and_then(|(mut child, watchdog)| {
+        // Write payload then drop stdin to signal EOF before waiting
+        if let Some(mut stdin) = child.stdin.take() {
+            use std::io::Write;
+            let _ = stdin.write_all(payload_json.as_bytes());
+        }
+        // stdin is now dropped — child sees EOF
+        let output = child.wait_with_output();
+        match (watchdog.map(Watchdog::stop), timeout) {
+            (Some(true), Some(timeout)) => Err(process_timeout::timed_out_error(timeout)),
+            _ => output,
+        }
+    });
+
+    drop(payload_file);
+    if let Ok(out) = &result {
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        if !stdout.trim().is_empty() {
+            log::debug!("Hook '{hook_name}' output: {}", stdout.trim());
+        }
+    }
+    result
+}
+
+/// Fire pre-create hook with the would-be worktree; an error aborts the
+/// creation unless `force`.
+pub fn fire_pre_create(
+    name: &str,
+    path: &Path,
+    repos: &[PlannedRepoEntry],
+    force: bool,
+    meta_dir: Option<&Path>,
+) -> anyhow::Result<()> {
+    let payload = serde_json::json!({
+        "action": "create",
+        "name": name,
+        "path": path.display().to_string(),
+        "repos": repos,
+        "force": force,
+    });
+    fire_guard_hook("pre-create", &payload, meta_dir, force)
+}
+
+/// Fire pre-destroy hook for the worktree about to be removed; an error
+/// aborts the removal unless `force`.
+pub fn fire_pre_destroy(
+    name: &str,
+    path: &Path,
+    force: bool,
+    meta_dir: Option<&Path>,
+) -> anyhow::Result<()> {
+    let payload = serde_json::json!({
+        "action": "destroy",
+        "name": name,
+        "path": path.display().to_string(),
+        "force": force,
+    });
+    fire_guard_hook("pre-destroy", &payload, meta_dir, force)
+}
+
+/// Fire post-add-repo hook for a repo just added to worktree `name` at
+/// `path`.
+pub fn fire_post_add_repo(
+    name: &str,
+    path: &Path,
+    repo: &WorktreeRepoInfo,
+    meta_dir: Option<&Path>,
+) {
+    let payload = serde_json::json!({
+        "action": "add-repo",
+        "name": name,
+        "path": path.display().to_string(),
+        "alias": repo.alias,
+        "branch": repo.branch,
+        "source": repo.source_path.display().to_string(),
+        "repo_path": repo.path.display().to_string(),
+        "created_branch": repo.created_branch.unwrap_or(false),
+    });
+    fire_worktree_hook("post-add-repo", &payload, meta_dir);
+}
+
+/// Fire pre-remove-repo hook for a repo about to be removed from worktree
+/// `name`; an error aborts the removal unless `force`.
+pub fn fire_pre_remove_repo(
+    name: &str,
+    path: &Path,
+    repo: &WorktreeRepoInfo,
+    force: bool,
+    meta_dir: Option<&Path>,
+) -> anyhow::Result<()> {
+    let payload = serde_json::json!({
+        "action": "remove-repo",
+        "name": name,
+        "path": path.display().to_string(),
+        "alias": repo.alias,
+        "branch": repo.branch,
+        "source": repo.source_path.display().to_string(),
+        "repo_path": repo.path.display().to_string(),
+        "force": force,
+    });
+    fire_guard_hook("pre-remove-repo", &payload, meta_dir, force)
+}
+
 /// Fire post-create hook with structured payload.
 pub fn fire_post_create(
     name: &str,
@@ -99,6 +660,25 @@ pub fn fire_post_destroy(name: &str, path: &Path, force: bool, meta_dir: Option<
     fire_worktree_hook("post-destroy", &payload, meta_dir);
 }
 
+/// Fire post-rename hook after worktree `previous_name` was renamed to
+/// `name`.
+pub fn fire_post_rename(
+    name: &str,
+    path: &Path,
+    previous_name: &str,
+    previous_path: &Path,
+    meta_dir: Option<&Path>,
+) {
+    let payload = serde_json::json!({
+        "action": "rename",
+        "name": name,
+        "path": path.display().to_string(),
+        "previous_name": previous_name,
+        "previous_path": previous_path.display().to_string(),
+    });
+    fire_worktree_hook("post-rename", &payload, meta_dir);
+}
+
 /// Fire post-prune hook with structured payload.
 pub fn fire_post_prune(removed: &[PruneEntry], meta_dir: Option<&Path>) {
     let payload = serde_json::json!({
@@ -107,3 +687,307 @@ pub fn fire_post_prune(removed: &[PruneEntry], meta_dir: Option<&Path>) {
     });
     fire_worktree_hook("post-prune", &payload, meta_dir);
 }
+
+/// Fire metadata-changed hook when a custom metadata key is set, changed,
+/// or removed. `value` is `None` on removal, `previous` when newly set.
+pub fn fire_metadata_changed(
+    name: &str,
+    path: &Path,
+    key: &str,
+    previous: Option<&str>,
+    value: Option<&str>,
+    meta_dir: Option<&Path>,
+) {
+    let payload = serde_json::json!({
+        "action": "metadata-changed",
+        "name": name,
+        "path": path.display().to_string(),
+        "key": key,
+        "previous": previous,
+        "value": value,
+    });
+    fire_worktree_hook("metadata-changed", &payload, meta_dir);
+}
+
+/// Fire ttl-changed hook when a worktree's TTL is extended or it's converted
+/// between ephemeral and persistent.
+pub fn fire_ttl_changed(
+    action: &str,
+    name: &str,
+    path: &Path,
+    ephemeral: bool,
+    previous_ttl_seconds: Option<u64>,
+    ttl_seconds: Option<u64>,
+    meta_dir: Option<&Path>,
+) {
+    let payload = serde_json::json!({
+        "action": action,
+        "name": name,
+        "path": path.display().to_string(),
+        "ephemeral": ephemeral,
+        "previous_ttl_seconds": previous_ttl_seconds,
+        "ttl_seconds": ttl_seconds,
+    });
+    fire_worktree_hook("ttl-changed", &payload, meta_dir);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_shell_and_argv_forms() {
+        assert_eq!(
+            HookCommand::from_value(&json!("echo hi")),
+            Some(HookCommand::Shell("echo hi".into()))
+        );
+        assert_eq!(
+            HookCommand::from_value(&json!(["script", "--flag"])),
+            Some(HookCommand::Argv(vec!["script".into(), "--flag".into()]))
+        );
+        assert_eq!(HookCommand::from_value(&json!([])), None);
+        assert_eq!(HookCommand::from_value(&json!(["ok", 1])), None);
+        assert_eq!(HookCommand::from_value(&json!(true)), None);
+    }
+
+    #[test]
+    fn shells_get_the_script_as_one_argument() {
+        let script = r#"echo "a b" 'c' $X %Y% & echo d"#;
+        let args = |command: &Command| -> (String, Vec<String>) {
+            (
+                command.get_program().to_string_lossy().into_owned(),
+                command
+                    .get_args()
+                    .map(|a| a.to_string_lossy().into_owned())
+                    .collect(),
+            )
+        };
+        assert_eq!(
+            args(&HookShell::Sh.command(script)),
+            ("sh".to_string(), vec!["-c".to_string(), script.to_string()])
+        );
+        let (program, argv) = args(&HookShell::Pwsh.command(script));
+        assert_eq!(program, "pwsh");
+        assert_eq!(argv.last().map(String::as_str), Some(script));
+        assert_eq!(args(&HookShell::PowerShell.command(script)).0, "powershell");
+        let (program, argv) = args(&HookShell::Cmd.command(script));
+        assert_eq!(program, "cmd");
+        assert_eq!(argv[..3], ["/D", "/S", "/C"]);
+        #[cfg(not(windows))]
+        assert_eq!(argv[3], script);
+
+        let argv = HookCommand::Argv(vec!["tool".into(), "a b".into()]);
+        assert_eq!(
+            args(&argv.command_in(HookShell::Cmd)),
+            ("tool".to_string(), vec!["a b".to_string()])
+        );
+    }
+
+    #[test]
+    fn hook_shell_comes_from_config() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert_eq!(hook_shell(Some(tmp.path())), HookShell::default());
+        for (value, expected) in [
+            ("pwsh", HookShell::Pwsh),
+            ("powershell", HookShell::PowerShell),
+            ("cmd", HookShell::Cmd),
+            ("bash", HookShell::default()),
+        ] {
+            std::fs::write(
+                tmp.path().join(".meta"),
+                json!({"projects": {}, "hook_shell": value}).to_string(),
+            )
+            .unwrap();
+            assert_eq!(hook_shell(Some(tmp.path())), expected, "{value}");
+        }
+
+        // `shell` under `worktree.hooks` is a hook like any other
+        std::fs::write(
+            tmp.path().join(".meta"),
+            json!({"projects": {}, "worktree": {"hooks": {"shell": "true"}}}).to_string(),
+        )
+        .unwrap();
+        assert_eq!(hook_shell(Some(tmp.path())), HookShell::default());
+        assert!(configured_hook("shell", Some(tmp.path())).is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn shell_hooks_keep_their_quoting() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out");
+        let script = format!(
+            r#"printf '%s|%s|%s' "a  b" 'it"s' $0 > '{}'"#,
+            out.display()
+        );
+        std::fs::write(
+            tmp.path().join(".meta"),
+            json!({"projects": {}, "hook_shell": "sh", "worktree": {"hooks": {"post-prune": script}}})
+                .to_string(),
+        )
+        .unwrap();
+        fire_post_prune(&[], Some(tmp.path()));
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), r#"a  b|it"s|sh"#);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn argv_hook_receives_payload_without_shell_quoting() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("it's out.json");
+        std::fs::write(
+            tmp.path().join(".meta"),
+            json!({
+                "projects": {},
+                "worktree": {"hooks": {"post-destroy": [
+                    "cp", "/dev/stdin", out.to_str().unwrap()
+                ]}}
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        fire_post_destroy("feat", Path::new("/tmp/feat"), true, Some(tmp.path()));
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&out).unwrap()).unwrap();
+        assert_eq!(written["action"], "destroy");
+        assert_eq!(written["name"], "feat");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn payload_file_and_env_are_provided() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out");
+        let script = format!(
+            "cat \"${HOOK_PAYLOAD_FILE_ENV}\" > '{0}.file'; printf '%s' \"${HOOK_PAYLOAD_ENV}\" > '{0}.env'",
+            out.display()
+        );
+        std::fs::write(
+            tmp.path().join(".meta"),
+            json!({"projects": {}, "worktree": {"hooks": {"post-prune": script}}}).to_string(),
+        )
+        .unwrap();
+
+        fire_post_prune(&[], Some(tmp.path()));
+        for ext in ["file", "env"] {
+            let text = std::fs::read_to_string(out.with_extension(ext)).unwrap();
+            let v: serde_json::Value = serde_json::from_str(&text).unwrap();
+            assert_eq!(v["action"], "prune", "{ext}");
+        }
+
+        use std::os::unix::fs::PermissionsExt;
+        let file = write_payload_file("post-prune", "{}").unwrap();
+        let mode = file.as_file().metadata().unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let path = file.path().to_path_buf();
+        drop(file);
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scripts_in_hooks_dir_run_unless_shadowed() {
+        use std::os::unix::fs::PermissionsExt;
+        let tmp = tempfile::tempdir().unwrap();
+        let hooks_dir = tmp.path().join(HOOKS_DIR);
+        std::fs::create_dir_all(&hooks_dir).unwrap();
+        std::fs::write(tmp.path().join(".meta.json"), r#"{"projects": {}}"#).unwrap();
+        let script = |name: &str, body: &str, mode: u32| {
+            let path = hooks_dir.join(name);
+            std::fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        };
+        script(
+            "pre-destroy",
+            "cat > /dev/null; echo 'PR #12 is open' >&2; exit 1",
+            0o755,
+        );
+        script("pre-create", "exit 1", 0o644);
+
+        let err =
+            fire_pre_destroy("feat", Path::new("/tmp/feat"), false, Some(tmp.path())).unwrap_err();
+        let aborted = err.downcast_ref::<HookAborted>().unwrap();
+        assert_eq!(aborted.status, Some(1));
+        assert_eq!(aborted.message, "PR #12 is open");
+        fire_pre_destroy("feat", Path::new("/tmp/feat"), true, Some(tmp.path())).unwrap();
+
+        // Not executable, so not a hook
+        fire_pre_create("feat", Path::new("/tmp/feat"), &[], false, Some(tmp.path())).unwrap();
+
+        // An inline command wins over the script
+        std::fs::write(
+            tmp.path().join(".meta.json"),
+            json!({"projects": {}, "worktree": {"hooks": {"pre-destroy": "true"}}}).to_string(),
+        )
+        .unwrap();
+        fire_pre_destroy("feat", Path::new("/tmp/feat"), false, Some(tmp.path())).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hung_hook_is_killed_after_timeout() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tmp.path().join(".meta"),
+            json!({
+                "projects": {},
+                "hook_timeout_seconds": 1,
+                "worktree": {"hooks": {"pre-create": "echo starting >&2; sleep 30"}}
+            })
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(hook_timeout(Some(tmp.path())), Some(Duration::from_secs(1)));
+
+        let started = std::time::Instant::now();
+        let err = fire_pre_create("feat", Path::new("/tmp/feat"), &[], false, Some(tmp.path()))
+            .unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(20));
+        let aborted = err.downcast_ref::<HookAborted>().unwrap();
+        assert_eq!(aborted.status, None);
+        assert!(
+            aborted.message.contains(process_timeout::TIMEOUT_MESSAGE),
+            "{}",
+            aborted.message
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn worktree_fields_are_exported_as_env_vars() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("env");
+        let script = format!(
+            "printf '%s|%s|%s|%s' \"${HOOK_ACTION_ENV}\" \"${WT_NAME_ENV}\" \"${WT_PATH_ENV}\" \"${WT_REPOS_ENV}\" > '{}'",
+            out.display()
+        );
+        std::fs::write(
+            tmp.path().join(".meta"),
+            json!({"projects": {}, "worktree": {"hooks": {"post-create": script}}}).to_string(),
+        )
+        .unwrap();
+
+        let repos = ["api", "web"].map(|alias| CreateRepoEntry {
+            alias: alias.to_string(),
+            path: format!("/wt/feat/{alias}"),
+            branch: "feat".to_string(),
+            created_branch: true,
+            setup: Vec::new(),
+        });
+        fire_post_create(
+            "feat",
+            Path::new("/wt/feat"),
+            &repos,
+            false,
+            None,
+            &HashMap::new(),
+            Some(tmp.path()),
+        );
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "create|feat|/wt/feat|api web"
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! Matrix runs across parallel ephemeral worktrees.
+//!
+//! [`run`] materializes one ephemeral worktree per spec (e.g. one per PR or
+//! branch combination), runs the same command in each with bounded
+//! concurrency, and aggregates the outcomes into a [`MatrixReport`].
+
+use anyhow::Result;
+use serde::Serialize;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use super::ephemeral::{EphemeralSpec, EphemeralWorktree};
+
+/// Outcome of the command in one matrix cell.
+#[derive(Debug, Clone, Serialize)]
+pub struct MatrixResult {
+    /// Human label, e.g. `api:pr-12 web:pr-7`
+    pub label: String,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+    /// Combined stdout and stderr
+    pub output: String,
+    /// Set when the worktree could not be created or the command not started
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Repos of the spec left out because they are disabled for worktrees
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub disabled: Vec<String>,
+}
+
+/// Aggregated results, in spec order.
+#[derive(Debug, Clone, Serialize)]
+pub struct MatrixReport {
+    pub results: Vec<MatrixResult>,
+    pub passed: usize,
+    pub failed: usize,
+}
+
+impl MatrixReport {
+    pub fn all_passed(&self) -> bool {
+        self.failed == 0
+    }
+}
+
+fn label(spec: &EphemeralSpec) -> String {
+    spec.repos
+        .iter()
+        .map(|r| r.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Run `cmd` in a fresh ephemeral worktree per spec, at most `concurrency` at once.
+///
+/// Worktree creation and removal are serialized (they take per-repo locks on
+/// shared source repos); only the commands run in parallel.
+pub fn run(specs: &[EphemeralSpec], cmd: &[String], concurrency: usize) -> Result<MatrixReport> {
+    let (program, args) = cmd
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("No command given"))?;
+    let workers = concurrency.max(1).min(specs.len().max(1));
+    let next = AtomicUsize::new(0);
+    let setup = Mutex::new(());
+    let slots: Vec<Mutex<Option<MatrixResult>>> = specs.iter().map(|_| Mutex::new(None)).collect();
+
+    let runner = crate::git_runner::installed();
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                crate::git_runner::with_installed(runner.clone(), || loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    let Some(spec) = specs.get(i) else {
+                        break;
+                    };
+                    let started = Instant::now();
+                    let mut result = MatrixResult {
+                        label: label(spec),
+                        success: false,
+                        exit_code: None,
+                        duration_ms: 0,
+                        output: String::new(),
+                        error: None,
+                        disabled: Vec::new(),
+                    };
+
+                    let created = {
+                        let _guard = setup.lock().unwrap_or_else(|e| e.into_inner());
+                        EphemeralWorktree::create(spec)
+                    };
+                    match created {
+                        Ok(wt) => {
+                            result.disabled = wt.disabled().to_vec();
+                            match Command::new(program)
+                                .args(args)
+                                .current_dir(wt.path())
+                                .env("META_WORKTREE", wt.name())
+                                .output()
+                            {
+                                Ok(out) => {
+                                    result.success = out.status.success();
+                                    result.exit_code = out.status.code();
+                                    result.output = format!(
+                                        "{}{}",
+                                        String::from_utf8_lossy(&out.stdout),
+                                        String::from_utf8_lossy(&out.stderr)
+                                    );
+                                }
+                                Err(e) => {
+                                    result.error = Some(format!("Failed to run '{program}': {e}"))
+                                }
+                            }
+                            let _guard = setup.lock().unwrap_or_else(|e| e.into_inner());
+                            wt.cleanup();
+                        }
+                        Err(e) => result.error = Some(e.to_string()),
+                    }
+
+                    result.duration_ms = started.elapsed().as_millis() as u64;
+                    *slots[i].lock().unwrap_or_else(|e| e.into_inner()) = Some(result);
+                })
+            });
+        }
+    });
+
+    let results: Vec<MatrixResult> = slots
+        .into_iter()
+        .filter_map(|slot| slot.into_inner().unwrap_or_else(|e| e.into_inner()))
+        .collect();
+    let passed = results.iter().filter(|r| r.success).count();
+    Ok(MatrixReport {
+        failed: results.len() - passed,
+        passed,
+        results,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{git, repo};
+
+    #[test]
+    #[serial_test::serial]
+    fn runs_each_spec_and_aggregates() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store_dir = tmp.path().join("meta-store");
+        std::fs::create_dir_all(&store_dir).unwrap();
+        std::env::set_var("META_DATA_DIR", &store_dir);
+        std::env::remove_var("META_WORKTREES");
+
+        let ws = tmp.path().join("ws");
+        repo(&ws.join("api"), &[]);
+        git(&ws.join("api"), &["branch", "feature"]);
+        repo(&ws.join("legacy"), &[]);
+        std::fs::write(
+            ws.join(".meta"),
+            r#"{"projects": {"api": "git@github.com:org/api.git",
+                "legacy": {"repo": "git@github.com:org/legacy.git", "disabled": true}}}"#,
+        )
+        .unwrap();
+
+        let specs = vec![
+            EphemeralSpec::new(
+                &ws,
+                vec!["api:main".parse().unwrap(), "legacy".parse().unwrap()],
+            ),
+            EphemeralSpec::new(&ws, vec!["api:feature".parse().unwrap()]),
+            EphemeralSpec::new(&ws, vec!["api:missing-ref".parse().unwrap()]),
+        ];
+        let cmd = vec![
+            "git".to_string(),
+            "-C".into(),
+            "api".into(),
+            "status".into(),
+        ];
+        let report = run(&specs, &cmd, 2).unwrap();
+
+        assert_eq!(report.results.len(), 3);
+        assert_eq!(report.results[0].label, "api:main legacy");
+        assert!(report.results[0].success);
+        assert_eq!(report.results[0].disabled, ["legacy"]);
+        assert!(report.results[1].disabled.is_empty());
+        assert!(report.results[1].success);
+        assert!(report.results[2].error.is_some());
+        assert_eq!((report.passed, report.failed), (2, 1));
+        assert!(!report.all_passed());
+
+        let store = crate::worktree::store::store_list().unwrap();
+        assert!(store.worktrees.is_empty());
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    fn empty_command_is_rejected() {
+        assert!(run(&[], &[], 1).is_err());
+    }
+}
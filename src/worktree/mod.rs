@@ -3,11 +3,21 @@
 //! Provides types, store operations, git operations, helpers, and hooks
 //! for worktree management. Command handlers live in `meta_git_cli::commands::worktree`.
 
+pub mod base_sync;
+pub mod details;
+pub mod ephemeral;
 pub mod git_ops;
 pub mod helpers;
 pub mod hooks;
+pub mod matrix;
+pub mod placement;
+pub mod prune;
+pub mod rename;
+pub mod selection;
 pub mod store;
 pub mod types;
+pub mod unpushed;
 
 // Re-export commonly-used types
-pub use types::RepoSpec;
+pub use rename::rename;
+pub use types::{RepoSpec, RepoSpecError};
@@ -0,0 +1,698 @@
+//! Worktree placement across several roots.
+//!
+//! `META_WORKTREES` may list more than one directory (separated like `PATH`),
+//! e.g. one per disk. New worktrees go to the root picked by the
+//! [`PlacementPolicy`] set as `worktree_placement` in `.meta`, and
+//! [`rebalance`] moves existing worktrees between roots with
+//! `git worktree move` to even out free space. [`move_to`] moves a single
+//! worktree anywhere, e.g. to another disk when `.worktrees/` fills up;
+//! [`find_worktree`] still finds it by name through its store entry.
+//!
+//! Only `META_WORKTREES` can name extra roots: like the sandbox, values from
+//! `.meta` never send writes outside the workspace.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::details::dir_disk_usage;
+use super::helpers::{
+    lookup_nested_project, read_meta_config_value, resolve_worktree_root, validate_worktree_name,
+};
+use super::store::{store_get, store_list, store_list_cached, store_rekey};
+use super::types::WorktreeStoreEntry;
+use crate::operations::OperationHandle;
+use crate::sandbox::Sandbox;
+
+/// How to choose the root for a new worktree.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlacementPolicy {
+    /// Always the first root
+    #[default]
+    First,
+    /// The root with the most available space
+    MostAvailable,
+    /// The first root with room for the estimated checkout size, falling back
+    /// to the one with the most available space
+    FitSize,
+}
+
+impl PlacementPolicy {
+    /// Policy from `worktree_placement` in the `.meta` file in `meta_dir`.
+    pub fn from_meta(meta_dir: &Path) -> Self {
+        read_meta_config_value(meta_dir)
+            .and_then(|v| v.get("worktree_placement").cloned())
+            .and_then(|v| {
+                serde_json::from_value(v)
+                    .map_err(|e| log::warn!("Invalid worktree_placement in .meta: {e}"))
+                    .ok()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// All worktree roots for `meta_dir`, primary first.
+pub fn worktree_roots(meta_dir: Option<&Path>) -> Result<Vec<PathBuf>> {
+    if let Some(env_paths) = std::env::var_os("META_WORKTREES") {
+        let roots: Vec<PathBuf> = std::env::split_paths(&env_paths)
+            .filter(|p| !p.as_os_str().is_empty())
+            .collect();
+        if !roots.is_empty() {
+            return Ok(roots);
+        }
+    }
+    Ok(vec![resolve_worktree_root(meta_dir)?])
+}
+
+/// The existing worktree `name` in any root, if there is one, or else
+/// wherever [`move_to`] put `meta_dir`'s worktree of that name.
+pub fn find_worktree(meta_dir: Option<&Path>, name: &str) -> Result<Option<PathBuf>> {
+    let in_root = worktree_roots(meta_dir)?
+        .into_iter()
+        .map(|root| root.join(name))
+        .find(|dir| dir.exists());
+    if in_root.is_some() {
+        return Ok(in_root);
+    }
+    let Some(meta_dir) = meta_dir else {
+        return Ok(None);
+    };
+    let store = match store_list_cached() {
+        Ok(store) => store,
+        Err(e) => {
+            log::warn!("Could not read the worktree store: {e:#}");
+            return Ok(None);
+        }
+    };
+    let moved = store
+        .worktrees
+        .iter()
+        .filter(|(_, entry)| entry.name == name && belongs_to(entry, meta_dir))
+        .map(|(key, _)| PathBuf::from(key))
+        .find(|dir| dir.exists());
+    Ok(moved)
+}
+
+/// Available bytes on the filesystem holding `path` (or its nearest existing
+/// ancestor), from `df`. `None` if it can't be determined.
+pub fn available_space(path: &Path) -> Option<u64> {
+    filesystem_space(path).map(|(_, available)| available)
+}
+
+/// Mount point and available bytes of the filesystem holding `path` (or its
+/// nearest existing ancestor), from `df`.
+pub fn filesystem_space(path: &Path) -> Option<(PathBuf, u64)> {
+    let existing = path.ancestors().find(|p| p.exists())?;
+    let output = Command::new("df").arg("-Pk").arg(existing).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    // POSIX format: header, then `fs blocks used available capacity mount`
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut fields = stdout.lines().nth(1)?.split_whitespace();
+    let kib: u64 = fields.nth(3)?.parse().ok()?;
+    let mount = fields.skip(1).collect::<Vec<_>>().join(" ");
+    Some((PathBuf::from(mount), kib * 1024))
+}
+
+/// Estimated size in bytes of a checkout of `repo`'s HEAD (sum of blob sizes).
+pub fn estimate_checkout_size(repo: &Path) -> u64 {
+    let Ok(output) = crate::git_runner::output(
+        Command::new("git")
+            .args(["ls-tree", "-r", "-l", "-z", "HEAD"])
+            .current_dir(repo),
+    ) else {
+        return 0;
+    };
+    crate::git_output::ls_tree_sizes(&output.stdout).sum()
+}
+
+/// Pick a root for a worktree of `estimate` bytes, given each root's
+/// available space from `available`. Roots with unknown space count as empty.
+pub fn choose_root_with(
+    roots: &[PathBuf],
+    policy: PlacementPolicy,
+    estimate: u64,
+    available: impl Fn(&Path) -> Option<u64>,
+) -> Option<PathBuf> {
+    let spaces: Vec<(&PathBuf, u64)> = roots
+        .iter()
+        .map(|root| (root, available(root).unwrap_or(0)))
+        .collect();
+    let most_available = || {
+        spaces
+            .iter()
+            // Earlier roots win ties
+            .rev()
+            .max_by_key(|(_, space)| *space)
+            .map(|(root, _)| (*root).clone())
+    };
+    match policy {
+        PlacementPolicy::First => roots.first().cloned(),
+        PlacementPolicy::MostAvailable => most_available(),
+        PlacementPolicy::FitSize => spaces
+            .iter()
+            .find(|(_, space)| *space > estimate)
+            .map(|(root, _)| (*root).clone())
+            .or_else(most_available),
+    }
+}
+
+/// Where to create worktree `name` of `meta_dir`, whose repos are checked out
+/// from `sources`.
+///
+/// Uses the policy from `.meta`; with a single root this is always
+/// `<root>/<name>`.
+pub fn place_worktree(meta_dir: &Path, name: &str, sources: &[PathBuf]) -> Result<PathBuf> {
+    let roots = worktree_roots(Some(meta_dir))?;
+    let policy = PlacementPolicy::from_meta(meta_dir);
+    if roots.len() == 1 || policy == PlacementPolicy::First {
+        return Ok(roots[0].join(name));
+    }
+    let estimate = sources.iter().map(|s| estimate_checkout_size(s)).sum();
+    let root = choose_root_with(&roots, policy, estimate, available_space)
+        .unwrap_or_else(|| roots[0].clone());
+    Ok(root.join(name))
+}
+
+/// A worktree relocation planned or done by [`rebalance`] or [`move_to`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WorktreeMove {
+    pub name: String,
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub bytes: u64,
+}
+
+/// Plan moves that even out free space: largest worktrees first, each moves
+/// to the root with the most space if that root would still have more free
+/// space than its current one had.
+///
+/// `roots` holds the available bytes of each root; `worktrees` holds
+/// `(name, root index, size)`. Returns `(worktree index, target root index)`.
+pub fn plan_rebalance(roots: &[u64], worktrees: &[(String, usize, u64)]) -> Vec<(usize, usize)> {
+    let mut space: Vec<u64> = roots.to_vec();
+    let mut order: Vec<usize> = (0..worktrees.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(worktrees[i].2));
+
+    let mut moves = Vec::new();
+    for i in order {
+        let (_, from, size) = worktrees[i];
+        let Some(to) = (0..space.len()).rev().max_by_key(|&r| space[r]) else {
+            break;
+        };
+        if to != from && space[to].saturating_sub(size) > space[from] {
+            space[to] -= size;
+            space[from] += size;
+            moves.push((i, to));
+        }
+    }
+    moves
+}
+
+fn belongs_to(entry: &WorktreeStoreEntry, meta_dir: &Path) -> bool {
+    let project = Path::new(&entry.project);
+    project == meta_dir
+        || project
+            .canonicalize()
+            .is_ok_and(|p| meta_dir.canonicalize().is_ok_and(|m| p == m))
+}
+
+/// Move `meta_dir`'s worktrees between roots to even out free space.
+///
+/// With `dry_run`, only returns the planned moves.
+pub fn rebalance(meta_dir: &Path, dry_run: bool) -> Result<Vec<WorktreeMove>> {
+    let roots: Vec<PathBuf> = worktree_roots(Some(meta_dir))?
+        .into_iter()
+        .map(|r| r.canonicalize().unwrap_or(r))
+        .collect();
+    if roots.len() < 2 {
+        return Ok(Vec::new());
+    }
+    let space: Vec<u64> = roots
+        .iter()
+        .map(|r| available_space(r).unwrap_or(0))
+        .collect();
+
+    let mut entries = Vec::new();
+    let mut worktrees = Vec::new();
+    for (key, entry) in store_list()?.worktrees {
+        let path = PathBuf::from(&key);
+        let Some(root) = path
+            .parent()
+            .and_then(|parent| roots.iter().position(|r| r == parent))
+        else {
+            continue;
+        };
+        if !belongs_to(&entry, meta_dir) || !path.exists() {
+            continue;
+        }
+        worktrees.push((entry.name.clone(), root, dir_disk_usage(&path)));
+        entries.push((key, entry));
+    }
+
+    let sandbox = Sandbox::for_workspace(meta_dir);
+    let planned = plan_rebalance(&space, &worktrees);
+    let mut journal = if dry_run {
+        None
+    } else {
+        Some(OperationHandle::start(
+            "worktree rebalance",
+            meta_dir,
+            serde_json::Value::Null,
+            planned.iter().map(|&(i, _)| entries[i].0.clone()).collect(),
+        )?)
+    };
+    let mut moves = Vec::new();
+    for (i, to) in planned {
+        let (key, entry) = &entries[i];
+        let from = PathBuf::from(key);
+        let target = roots[to].join(&entry.name);
+        if target.exists() {
+            log::warn!(
+                "Not moving worktree '{}': {} already exists",
+                entry.name,
+                target.display()
+            );
+            if let Some(journal) = &mut journal {
+                journal.fail_item(key)?;
+            }
+            continue;
+        }
+        if let Some(journal) = &mut journal {
+            sandbox.check(&target, "move worktree")?;
+            relocate(meta_dir, entry, key, &from, &target)?;
+            journal.complete_item(key)?;
+        }
+        moves.push(WorktreeMove {
+            name: entry.name.clone(),
+            from,
+            to: target,
+            bytes: worktrees[i].2,
+        });
+    }
+    if let Some(journal) = journal {
+        journal.finish()?;
+    }
+    Ok(moves)
+}
+
+/// Move worktree `name` of the workspace at `meta_dir` to `dest`, which
+/// becomes the worktree's directory, as with `git worktree move`.
+///
+/// `dest` may be on another filesystem and outside every worktree root; it
+/// comes from the caller, so it isn't confined to the sandbox, and a later
+/// [`rename`](super::rename::rename) may use its directory too. The store
+/// entry is re-keyed to the new path. If a repo can't be moved, the others
+/// are moved back.
+pub fn move_to(meta_dir: &Path, name: &str, dest: &Path) -> Result<WorktreeMove> {
+    validate_worktree_name(name)?;
+    let from = find_worktree(Some(meta_dir), name)?
+        .with_context(|| format!("Worktree '{name}' not found"))?;
+    let from = from.canonicalize().unwrap_or(from);
+    let entry = store_get(&from)?
+        .with_context(|| format!("Worktree '{name}' is not in the worktree store"))?;
+    let to = std::path::absolute(dest)?;
+    if to.exists() {
+        anyhow::bail!("{} already exists", to.display());
+    }
+    if to.starts_with(&from) {
+        anyhow::bail!("Cannot move worktree '{name}' into itself");
+    }
+
+    let bytes = dir_disk_usage(&from);
+    relocate(meta_dir, &entry, &from.to_string_lossy(), &from, &to)?;
+    Ok(WorktreeMove {
+        name: entry.name,
+        from,
+        to,
+        bytes,
+    })
+}
+
+/// Move the worktree stored under `key` from `from` to `to` and re-key its
+/// store entry, moving it back if the store can't be updated.
+fn relocate(
+    meta_dir: &Path,
+    entry: &WorktreeStoreEntry,
+    key: &str,
+    from: &Path,
+    to: &Path,
+) -> Result<()> {
+    move_worktree(meta_dir, entry, from, to)?;
+    if let Err(e) = store_rekey(key, to) {
+        if let Err(undo) = move_worktree(meta_dir, entry, to, from) {
+            log::warn!(
+                "Could not move {} back to {}: {undo:#}",
+                to.display(),
+                from.display()
+            );
+        }
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Move every repo of a worktree from `from` to `to`.
+///
+/// A meta repo worktree (`.`) carries its nested repos along; their links
+/// are fixed with `git worktree repair`. If a repo fails to move, the ones
+/// already moved are moved back.
+fn move_worktree(
+    meta_dir: &Path,
+    entry: &WorktreeStoreEntry,
+    from: &Path,
+    to: &Path,
+) -> Result<()> {
+    crate::read_only::check("move worktree")?;
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // (source repo, old path, new path) of each repo moved so far
+    let mut moved: Vec<(PathBuf, PathBuf, PathBuf)> = Vec::new();
+    if let Err(e) = move_repos(meta_dir, entry, from, to, &mut moved) {
+        undo_moves(meta_dir, entry, from, &moved);
+        return Err(e);
+    }
+    if from.exists() {
+        remove_empty_dirs(from);
+    }
+    Ok(())
+}
+
+fn move_repos(
+    meta_dir: &Path,
+    entry: &WorktreeStoreEntry,
+    from: &Path,
+    to: &Path,
+    moved: &mut Vec<(PathBuf, PathBuf, PathBuf)>,
+) -> Result<()> {
+    let has_meta_root = entry.repos.iter().any(|r| r.alias == ".");
+    if has_meta_root {
+        git_worktree_move(meta_dir, from, to)?;
+        moved.push((meta_dir.to_path_buf(), from.to_path_buf(), to.to_path_buf()));
+    }
+    for repo in entry.repos.iter().filter(|r| r.alias != ".") {
+        let source = lookup_nested_project(meta_dir, &repo.alias)?.0;
+        let dest = to.join(&repo.alias);
+        if has_meta_root {
+            git_worktree_repair(&source, &dest)?;
+        } else {
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let old = from.join(&repo.alias);
+            git_worktree_move(&source, &old, &dest)
+                .with_context(|| format!("Failed to move '{}'", repo.alias))?;
+            moved.push((source, old, dest));
+        }
+    }
+    Ok(())
+}
+
+/// Move the repos in `moved` back where they were, newest first. Failures
+/// are logged: the caller is already reporting the error that caused this.
+fn undo_moves(
+    meta_dir: &Path,
+    entry: &WorktreeStoreEntry,
+    from: &Path,
+    moved: &[(PathBuf, PathBuf, PathBuf)],
+) {
+    for (source, old, new) in moved.iter().rev() {
+        let undone = git_worktree_move(source, new, old).and_then(|()| {
+            if old != from {
+                return Ok(());
+            }
+            // A meta root took its nested repos along; point them back
+            entry
+                .repos
+                .iter()
+                .filter(|r| r.alias != ".")
+                .try_for_each(|r| {
+                    let nested = lookup_nested_project(meta_dir, &r.alias)?.0;
+                    git_worktree_repair(&nested, &from.join(&r.alias))
+                })
+        });
+        if let Err(e) = undone {
+            log::warn!(
+                "Could not move {} back to {}: {e:#}",
+                new.display(),
+                old.display()
+            );
+        }
+    }
+}
+
+/// `git worktree move`, falling back to copy + `git worktree repair` when
+/// the target is on another filesystem (git only renames).
+fn git_worktree_move(source: &Path, from: &Path, to: &Path) -> Result<()> {
+    let _lock = crate::lock::RepoLock::acquire(source, "git worktree move")?;
+    let output = crate::git_runner::output(
+        Command::new("git")
+            // Untranslated messages, for the cross-device check below
+            .env("LC_ALL", "C")
+            .arg("worktree")
+            .arg("move")
+            .arg(from)
+            .arg(to)
+            .current_dir(source),
+    )?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stderr.contains("cross-device") {
+        anyhow::bail!("git worktree move failed: {}", stderr.trim());
+    }
+    copy_dir(from, to)?;
+    std::fs::remove_dir_all(from)?;
+    git_worktree_repair(source, to)
+}
+
+pub(super) fn git_worktree_repair(source: &Path, worktree: &Path) -> Result<()> {
+    let output = crate::git_runner::output(
+        Command::new("git")
+            .arg("worktree")
+            .arg("repair")
+            .arg(worktree)
+            .current_dir(source),
+    )?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git worktree repair failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Recursive copy that keeps symlinks as symlinks.
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let (src, dest) = (entry.path(), to.join(entry.file_name()));
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            copy_dir(&src, &dest)?;
+        } else if file_type.is_symlink() {
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(std::fs::read_link(&src)?, &dest)?;
+            #[cfg(not(unix))]
+            std::fs::copy(&src, &dest)?;
+        } else {
+            std::fs::copy(&src, &dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// Remove `dir` and empty directories under it, leaving anything with files.
+fn remove_empty_dirs(dir: &Path) {
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                remove_empty_dirs(&entry.path());
+            }
+        }
+    }
+    let _ = std::fs::remove_dir(dir);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{git, repo, store_entry, worktree};
+    use crate::worktree::store::store_add;
+
+    fn roots() -> Vec<PathBuf> {
+        vec![PathBuf::from("/ssd"), PathBuf::from("/hdd")]
+    }
+
+    fn space(path: &Path) -> Option<u64> {
+        match path.to_str()? {
+            "/ssd" => Some(100),
+            "/hdd" => Some(1_000),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn chooses_root_by_policy() {
+        let roots = roots();
+        assert_eq!(
+            choose_root_with(&roots, PlacementPolicy::First, 500, space),
+            Some(PathBuf::from("/ssd"))
+        );
+        assert_eq!(
+            choose_root_with(&roots, PlacementPolicy::MostAvailable, 10, space),
+            Some(PathBuf::from("/hdd"))
+        );
+        // Fits on the first root
+        assert_eq!(
+            choose_root_with(&roots, PlacementPolicy::FitSize, 50, space),
+            Some(PathBuf::from("/ssd"))
+        );
+        // Too big for the first root
+        assert_eq!(
+            choose_root_with(&roots, PlacementPolicy::FitSize, 500, space),
+            Some(PathBuf::from("/hdd"))
+        );
+        // Fits nowhere: most space wins
+        assert_eq!(
+            choose_root_with(&roots, PlacementPolicy::FitSize, 5_000, space),
+            Some(PathBuf::from("/hdd"))
+        );
+    }
+
+    #[test]
+    fn rebalance_moves_largest_worktrees_to_free_space() {
+        let worktrees = vec![
+            ("small".to_string(), 0, 10),
+            ("big".to_string(), 0, 300),
+            ("other".to_string(), 1, 50),
+        ];
+        // Root 0 is nearly full, root 1 has plenty of space
+        assert_eq!(plan_rebalance(&[100, 1_000], &worktrees), [(1, 1), (0, 1)]);
+        // Already balanced: nothing to do
+        assert!(plan_rebalance(&[500, 500], &worktrees).is_empty());
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn roots_from_env_path_list() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (a, b) = (tmp.path().join("a"), tmp.path().join("b"));
+        std::env::set_var("META_WORKTREES", std::env::join_paths([&a, &b]).unwrap());
+        assert_eq!(worktree_roots(None).unwrap(), [a.clone(), b.clone()]);
+        std::fs::create_dir_all(b.join("feat")).unwrap();
+        assert_eq!(find_worktree(None, "feat").unwrap(), Some(b.join("feat")));
+        assert_eq!(find_worktree(None, "other").unwrap(), None);
+        std::env::remove_var("META_WORKTREES");
+
+        std::fs::write(
+            tmp.path().join(".meta"),
+            r#"{"worktree_placement": "fit_size"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            PlacementPolicy::from_meta(tmp.path()),
+            PlacementPolicy::FitSize
+        );
+        assert_eq!(
+            worktree_roots(Some(tmp.path())).unwrap(),
+            [tmp.path().join(".worktrees")]
+        );
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn moves_worktree_repos_between_roots() {
+        let tmp = tempfile::tempdir().unwrap();
+        let tmp_path = tmp.path().canonicalize().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp_path.join("data"));
+        let ws = tmp_path.join("ws");
+        let api = ws.join("api");
+        repo(&api, &[]);
+        std::fs::write(
+            ws.join(".meta"),
+            r#"{"projects": {"api": "git@x:api.git"}}"#,
+        )
+        .unwrap();
+        let from = tmp_path.join("root1").join("feat");
+        let to = tmp_path.join("root2").join("feat");
+        worktree(&api, &from.join("api"), "feat");
+        store_add(&from, store_entry("feat", &ws).repo("api").build()).unwrap();
+
+        let (key, entry) = store_list().unwrap().worktrees.into_iter().next().unwrap();
+        move_worktree(&ws, &entry, &from, &to).unwrap();
+        store_rekey(&key, &to).unwrap();
+
+        assert!(!from.exists());
+        assert!(to.join("api/.git").is_file());
+        let list = git(&api, &["worktree", "list", "--porcelain"]);
+        assert!(list.contains(&*to.join("api").to_string_lossy()));
+        let store = store_list().unwrap();
+        assert!(store.worktrees.contains_key(&*to.to_string_lossy()));
+        assert_eq!(store.worktrees.len(), 1);
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn moves_one_worktree_out_of_the_roots() {
+        let tmp = tempfile::tempdir().unwrap();
+        let tmp_path = tmp.path().canonicalize().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp_path.join("data"));
+        std::env::remove_var("META_WORKTREES");
+        let ws = tmp_path.join("ws");
+        let api = ws.join("api");
+        repo(&api, &[]);
+        std::fs::write(
+            ws.join(".meta"),
+            r#"{"projects": {"api": "git@x:api.git"}}"#,
+        )
+        .unwrap();
+        let from = ws.join(".worktrees").join("feat");
+        worktree(&api, &from.join("api"), "feat");
+        let entry = store_entry("feat", &ws).repo("api");
+
+        // A repo that can't be moved sends the ones already moved back
+        store_add(
+            &from,
+            store_entry("feat", &ws).repo("api").repo("gone").build(),
+        )
+        .unwrap();
+        let failed_dest = tmp_path.join("big-disk").join("failed");
+        assert!(move_to(&ws, "feat", &failed_dest).is_err());
+        assert!(from.join("api/.git").is_file());
+        assert!(!failed_dest.join("api").exists());
+        git(&from.join("api"), &["status", "--short"]);
+        assert!(store_get(&from).unwrap().is_some());
+
+        store_add(&from, entry.build()).unwrap();
+        assert!(move_to(&ws, "nope", &tmp_path.join("x")).is_err());
+        assert!(move_to(&ws, "feat", &from.join("inner")).is_err());
+        let dest = tmp_path.join("big-disk").join("feat-wt");
+        let moved = move_to(&ws, "feat", &dest).unwrap();
+        assert_eq!((moved.from, moved.to.clone()), (from.clone(), dest.clone()));
+
+        assert!(!from.exists());
+        git(&dest.join("api"), &["status", "--short"]);
+        let store = store_list().unwrap();
+        assert!(store.worktrees.contains_key(&*dest.to_string_lossy()));
+        assert_eq!(store.worktrees.len(), 1);
+        // Still found by name outside the roots
+        assert_eq!(
+            find_worktree(Some(&ws), "feat").unwrap(),
+            Some(dest.clone())
+        );
+        assert_eq!(find_worktree(Some(&ws), "other").unwrap(), None);
+        // And renamed next to where it was put
+        let renamed = super::super::rename::rename(&ws, "feat", "feat2").unwrap();
+        assert_eq!(renamed, dest.with_file_name("feat2"));
+        std::env::remove_var("META_DATA_DIR");
+    }
+}
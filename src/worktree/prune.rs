@@ -0,0 +1,345 @@
+//! Deciding which worktrees prune removes.
+//!
+//! An expired TTL makes a worktree a prune candidate, but protected entries
+//! (see [`store::set_protected`](super::store::set_protected)) are always
+//! kept, and expired worktrees with uncommitted changes or unpushed commits
+//! are only removed with `force`. [`prune`] removes what [`plan`] allows.
+
+use anyhow::Result;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::git_ops::{git_status_summary, remove_worktree_repos};
+use super::hooks::{fire_post_prune, fire_pre_destroy};
+use super::store::{entry_ttl_remaining, store_entries_for, store_remove_batch};
+use super::types::{
+    PruneEntry, PruneOutput, UnpushedRepoEntry, WorktreeStoreData, WorktreeStoreEntry,
+};
+use super::unpushed::{branches_unpushed, current_branch};
+
+/// Remove the expired worktrees of the workspace at `meta_dir` that [`plan`]
+/// allows, firing `pre-destroy` for each and `post-prune` once. With
+/// `dry_run`, only reports what would be removed.
+///
+/// Worktrees whose `pre-destroy` hook refuses, or whose removal fails, are
+/// reported as refused and left in the store.
+pub fn prune(meta_dir: &Path, dry_run: bool, force: bool) -> Result<PruneOutput> {
+    if !dry_run {
+        crate::read_only::check("prune worktrees")?;
+    }
+    let store = WorktreeStoreData {
+        worktrees: store_entries_for(meta_dir)?.into_iter().collect(),
+    };
+    let (candidates, mut refused) = plan(&store, chrono::Utc::now().timestamp(), force);
+    if dry_run {
+        return Ok(PruneOutput {
+            removed: candidates,
+            dry_run,
+            refused,
+        });
+    }
+
+    let mut removed = Vec::new();
+    for mut entry in candidates {
+        let root = PathBuf::from(&entry.path);
+        let outcome = fire_pre_destroy(&entry.name, &root, force, Some(meta_dir))
+            .and_then(|()| remove_expired(&root, &entry.path));
+        match outcome {
+            Ok(()) => removed.push(entry),
+            Err(e) => {
+                entry.reason = format!("{e:#}");
+                refused.push(entry);
+            }
+        }
+    }
+    refused.sort_by(|a, b| a.name.cmp(&b.name));
+    fire_post_prune(&removed, Some(meta_dir));
+    Ok(PruneOutput {
+        removed,
+        dry_run,
+        refused,
+    })
+}
+
+/// Remove the worktree at `root` (store key `key`): its repos, its store
+/// entry, and the directory.
+fn remove_expired(root: &Path, key: &str) -> Result<()> {
+    if root.exists() {
+        let repos = meta_cli::worktree::discover_worktree_repos(root)?;
+        remove_worktree_repos(&repos, true, false)?;
+    }
+    store_remove_batch(&[key.to_string()])?;
+    if root.exists() {
+        std::fs::remove_dir_all(root)?;
+    }
+    Ok(())
+}
+
+/// What prune does with one store entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PruneDecision {
+    /// Not expired, or protected
+    Keep,
+    /// Expired and safe to remove (or forced)
+    Remove { reason: String },
+    /// Expired, but removing it would lose work
+    Refuse { reason: String },
+}
+
+/// Decide what prune does with the worktree at `root`.
+pub fn decide(
+    root: &Path,
+    entry: &WorktreeStoreEntry,
+    now_epoch: i64,
+    force: bool,
+) -> PruneDecision {
+    if entry.protected {
+        return PruneDecision::Keep;
+    }
+    if entry_ttl_remaining(entry, now_epoch).is_none_or(|remaining| remaining > 0) {
+        return PruneDecision::Keep;
+    }
+    if !force {
+        if let Some(reason) = unsaved_work(root, entry) {
+            return PruneDecision::Refuse { reason };
+        }
+    }
+    PruneDecision::Remove {
+        reason: "ttl expired".to_string(),
+    }
+}
+
+/// Expired worktrees of `store` to remove and to keep because of unsaved
+/// work, as `(removed, refused)` for a [`PruneOutput`](super::types::PruneOutput).
+pub fn plan(
+    store: &WorktreeStoreData,
+    now_epoch: i64,
+    force: bool,
+) -> (Vec<PruneEntry>, Vec<PruneEntry>) {
+    let mut removed = Vec::new();
+    let mut refused = Vec::new();
+    for (root, entry) in &store.worktrees {
+        let (list, reason) = match decide(Path::new(root), entry, now_epoch, force) {
+            PruneDecision::Keep => continue,
+            PruneDecision::Remove { reason } => (&mut removed, reason),
+            PruneDecision::Refuse { reason } => (&mut refused, reason),
+        };
+        list.push(PruneEntry {
+            name: entry.name.clone(),
+            path: root.clone(),
+            reason,
+            age_seconds: chrono::DateTime::parse_from_rfc3339(&entry.created_at)
+                .ok()
+                .and_then(|created| u64::try_from(now_epoch - created.timestamp()).ok()),
+        });
+    }
+    removed.sort_by(|a, b| a.name.cmp(&b.name));
+    refused.sort_by(|a, b| a.name.cmp(&b.name));
+    (removed, refused)
+}
+
+/// Why removing the worktree would lose work, if it would: the first repo
+/// with uncommitted changes, or with commits no remote has on any branch
+/// the checkout has been on (see [`worktree_branches`]), not just the one
+/// checked out now.
+fn unsaved_work(root: &Path, entry: &WorktreeStoreEntry) -> Option<String> {
+    for repo in &entry.repos {
+        let path: PathBuf = if repo.alias == "." {
+            root.to_path_buf()
+        } else {
+            root.join(&repo.alias)
+        };
+        if !path.exists() {
+            continue;
+        }
+        if git_status_summary(&path).is_ok_and(|s| s.dirty) {
+            return Some(format!("uncommitted changes in {}", repo.alias));
+        }
+        let created = repo.created_branch.then_some(repo.branch.as_str());
+        let unpushed = branches_unpushed(&path, worktree_branches(&path, &repo.branch), created);
+        match unpushed {
+            Ok(branches) if branches.is_empty() => {}
+            Ok(branches) => {
+                let n = UnpushedRepoEntry {
+                    alias: repo.alias.clone(),
+                    branches,
+                }
+                .commit_count();
+                return Some(format!("{n} unpushed commit(s) in {}", repo.alias));
+            }
+            Err(e) => log::debug!("Could not list unpushed commits in {}: {e}", path.display()),
+        }
+    }
+    None
+}
+
+/// Branches the worktree checkout at `path` has been on: the one checked
+/// out (or `HEAD` if detached), `recorded`, and every branch its `HEAD`
+/// reflog moved between. Branches now checked out in another worktree are
+/// left to that worktree's own checks.
+fn worktree_branches(path: &Path, recorded: &str) -> Vec<String> {
+    let mut names = vec![current_branch(path).unwrap_or_else(|| "HEAD".to_string())];
+    names.push(recorded.to_string());
+    let reflog = crate::git_runner::output(
+        Command::new("git")
+            .args(["reflog", "show", "--format=%gs", "HEAD", "--"])
+            .current_dir(path),
+    );
+    if let Ok(reflog) = reflog {
+        for line in String::from_utf8_lossy(&reflog.stdout).lines() {
+            if let Some((from, to)) = line
+                .strip_prefix("checkout: moving from ")
+                .and_then(|moves| moves.split_once(" to "))
+            {
+                names.extend([from.to_string(), to.to_string()]);
+            }
+        }
+    }
+    let elsewhere = checked_out_elsewhere(path);
+    let mut seen = HashSet::new();
+    names.retain(|name| !elsewhere.contains(name) && seen.insert(name.clone()));
+    names
+}
+
+/// Branches checked out in the other worktrees of the repo at `path`.
+fn checked_out_elsewhere(path: &Path) -> Vec<String> {
+    let Ok(output) = crate::git_runner::output(
+        Command::new("git")
+            .args(["worktree", "list", "--porcelain"])
+            .current_dir(path),
+    ) else {
+        return Vec::new();
+    };
+    let here = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let mut branches = Vec::new();
+    let mut elsewhere = false;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if let Some(worktree) = line.strip_prefix("worktree ") {
+            let worktree = Path::new(worktree);
+            elsewhere = worktree
+                .canonicalize()
+                .unwrap_or_else(|_| worktree.to_path_buf())
+                != here;
+        } else if let Some(branch) = line.strip_prefix("branch refs/heads/") {
+            if elsewhere {
+                branches.push(branch.to_string());
+            }
+        }
+    }
+    branches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{git, repo as init_repo, store_entry, worktree};
+
+    fn entry(created_at: &str, protected: bool) -> WorktreeStoreEntry {
+        store_entry("wt", Path::new("/tmp/project"))
+            .created_at(created_at)
+            .ephemeral(60)
+            .protected(protected)
+            .repo("api")
+            .build()
+    }
+
+    #[test]
+    fn expired_worktrees_with_unsaved_work_need_force() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let repo = root.join("api");
+        init_repo(&repo, &[]);
+
+        let now = chrono::Utc::now().timestamp();
+        let expired = entry("2025-01-01T00:00:00Z", false);
+        let fresh = entry(&chrono::Utc::now().to_rfc3339(), false);
+        assert_eq!(decide(root, &fresh, now, true), PruneDecision::Keep);
+        assert_eq!(
+            decide(root, &entry("2025-01-01T00:00:00Z", true), now, true),
+            PruneDecision::Keep
+        );
+
+        // The commit was never pushed
+        assert_eq!(
+            decide(root, &expired, now, false),
+            PruneDecision::Refuse {
+                reason: "1 unpushed commit(s) in api".to_string()
+            }
+        );
+        std::fs::write(repo.join("notes.txt"), "wip").unwrap();
+        assert_eq!(
+            decide(root, &expired, now, false),
+            PruneDecision::Refuse {
+                reason: "uncommitted changes in api".to_string()
+            }
+        );
+        assert_eq!(
+            decide(root, &expired, now, true),
+            PruneDecision::Remove {
+                reason: "ttl expired".to_string()
+            }
+        );
+
+        let mut store = WorktreeStoreData::default();
+        store
+            .worktrees
+            .insert(root.to_string_lossy().into_owned(), expired);
+        let (removed, refused) = plan(&store, now, false);
+        assert!(removed.is_empty());
+        assert_eq!(refused[0].reason, "uncommitted changes in api");
+        assert!(refused[0].age_seconds.is_some());
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn prune_removes_expired_worktrees_without_unsaved_work() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp.path().join("meta-store"));
+        let origin = tmp.path().join("origin");
+        init_repo(&origin, &[]);
+        let ws = tmp.path().join("ws");
+        std::fs::create_dir(&ws).unwrap();
+        std::fs::write(ws.join(".meta"), r#"{"projects": {"api": "x"}}"#).unwrap();
+        git(&ws, &["clone", "-q", origin.to_str().unwrap(), "api"]);
+
+        let expired = |name: &str| {
+            let root = tmp.path().join(name);
+            worktree(&ws.join("api"), &root.join("api"), name);
+            let entry = store_entry(name, &ws)
+                .created_at("2025-01-01T00:00:00Z")
+                .ephemeral(60)
+                .repo("api")
+                .build();
+            crate::worktree::store::store_add(&root, entry).unwrap();
+            root
+        };
+        let old = expired("old");
+        let busy = expired("busy");
+        // A branch made in the worktree and switched away from
+        git(&busy.join("api"), &["checkout", "-q", "-b", "side"]);
+        git(
+            &busy.join("api"),
+            &["commit", "-q", "--allow-empty", "-m", "wip"],
+        );
+        git(&busy.join("api"), &["checkout", "-q", "busy"]);
+
+        let names = |entries: &[PruneEntry]| -> Vec<String> {
+            entries.iter().map(|e| e.name.clone()).collect()
+        };
+        let dry = prune(&ws, true, false).unwrap();
+        assert_eq!(names(&dry.removed), ["old"]);
+        assert_eq!(names(&dry.refused), ["busy"]);
+        assert!(old.exists());
+
+        let output = prune(&ws, false, false).unwrap();
+        assert_eq!(names(&output.removed), ["old"]);
+        assert_eq!(output.refused[0].reason, "1 unpushed commit(s) in api");
+        assert!(!old.exists());
+        assert!(busy.exists());
+        let left = store_entries_for(&ws).unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].1.name, "busy");
+        std::env::remove_var("META_DATA_DIR");
+    }
+}
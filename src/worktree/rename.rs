@@ -0,0 +1,147 @@
+//! Renaming a worktree.
+//!
+//! The worktree directory is renamed in place (same root), every repo's
+//! gitdir links are fixed with `git worktree repair`, and the store entry is
+//! re-keyed under the new path and name. Branches keep their names; a
+//! `post-rename` hook can rename them if a team wants that.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use super::helpers::validate_worktree_name;
+use super::hooks::fire_post_rename;
+use super::placement::{find_worktree, git_worktree_repair};
+use super::store::store_rename;
+use crate::sandbox::Sandbox;
+
+/// Rename worktree `old` of the workspace at `meta_dir` to `new`, returning
+/// its new path.
+///
+/// If a repo's links can't be repaired or the store entry can't be updated,
+/// the directory is moved back.
+pub fn rename(meta_dir: &Path, old: &str, new: &str) -> Result<PathBuf> {
+    crate::read_only::check("rename worktree")?;
+    validate_worktree_name(old)?;
+    validate_worktree_name(new)?;
+    let from = find_worktree(Some(meta_dir), old)?
+        .with_context(|| format!("Worktree '{old}' not found"))?;
+    if let Some(existing) = find_worktree(Some(meta_dir), new)? {
+        anyhow::bail!(
+            "Worktree '{}' already exists at {}",
+            new,
+            existing.display()
+        );
+    }
+    let to = from.with_file_name(new);
+    let mut sandbox = Sandbox::for_workspace(meta_dir);
+    if !sandbox.contains(&from) {
+        // Put outside the roots by `move_to`; the rename stays next to it
+        if let Some(parent) = from.parent() {
+            sandbox = sandbox.allow(parent);
+        }
+    }
+    sandbox.check(&to, "rename worktree")?;
+    if to.exists() {
+        anyhow::bail!("{} already exists", to.display());
+    }
+
+    let repos = meta_cli::worktree::discover_worktree_repos(&from)?;
+    let old_key = from
+        .canonicalize()
+        .unwrap_or_else(|_| from.clone())
+        .to_string_lossy()
+        .into_owned();
+    std::fs::rename(&from, &to)
+        .with_context(|| format!("Failed to move {} to {}", from.display(), to.display()))?;
+
+    let repair = |root: &Path| -> Result<()> {
+        for repo in &repos {
+            let path = match repo.path.strip_prefix(&from) {
+                Ok(relative) => root.join(relative),
+                Err(_) => root.join(&repo.alias),
+            };
+            git_worktree_repair(&repo.source_path, &path)
+                .with_context(|| format!("Failed to repair '{}'", repo.alias))?;
+        }
+        Ok(())
+    };
+    if let Err(e) = repair(&to).and_then(|()| store_rename(&old_key, &to, new)) {
+        if let Err(undo) = std::fs::rename(&to, &from) {
+            log::warn!("Could not move {} back: {undo}", to.display());
+        } else if let Err(undo) = repair(&from) {
+            log::warn!("Could not restore links of {}: {undo:#}", from.display());
+        }
+        return Err(e);
+    }
+
+    fire_post_rename(new, &to, old, &from, Some(meta_dir));
+    Ok(to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{git, repo, store_entry, worktree};
+    use crate::worktree::store::{store_add, store_list};
+
+    #[test]
+    #[serial_test::serial]
+    fn renames_directory_links_and_store_entry() {
+        let tmp = tempfile::tempdir().unwrap();
+        let tmp_path = tmp.path().canonicalize().unwrap();
+        std::env::set_var("META_DATA_DIR", tmp_path.join("data"));
+        std::env::remove_var("META_WORKTREES");
+        let ws = tmp_path.join("ws");
+        let api = ws.join("api");
+        repo(&api, &[]);
+        let log = tmp_path.join("renamed");
+        std::fs::write(
+            ws.join(".meta"),
+            serde_json::json!({
+                "projects": {"api": "git@x:api.git"},
+                "worktree": {"hooks": {"post-rename": format!(
+                    "echo \"$META_WT_NAME $(basename $META_WT_PATH)\" > '{}'",
+                    log.display()
+                )}}
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let from = ws.join(".worktrees").join("feat");
+        worktree(&api, &from.join("api"), "feat");
+
+        // Without a store entry to re-key, the directory is moved back
+        let err = rename(&ws, "feat", "feat-auth").unwrap_err().to_string();
+        assert!(err.contains("No worktree store entry"), "{err}");
+        assert!(from.join("api/.git").is_file());
+        assert!(!ws.join(".worktrees/feat-auth").exists());
+        git(&from.join("api"), &["status", "--short"]);
+
+        store_add(&from, store_entry("feat", &ws).repo("api").build()).unwrap();
+
+        assert!(rename(&ws, "feat", "../x").is_err());
+        assert!(rename(&ws, "nope", "other").is_err());
+        let to = rename(&ws, "feat", "feat-auth").unwrap();
+        assert_eq!(to, ws.join(".worktrees").join("feat-auth"));
+        assert!(!from.exists());
+
+        // The repo still works from its new location
+        git(&to.join("api"), &["status", "--short"]);
+        let list = git(&api, &["worktree", "list", "--porcelain"]);
+        assert!(list.contains(&*to.join("api").to_string_lossy()));
+
+        let store = store_list().unwrap();
+        let entry = &store.worktrees[&*to.to_string_lossy()];
+        assert_eq!(entry.name, "feat-auth");
+        assert_eq!(store.worktrees.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&log).unwrap(),
+            "feat-auth feat-auth\n"
+        );
+
+        std::fs::create_dir_all(ws.join(".worktrees").join("taken")).unwrap();
+        let err = rename(&ws, "feat-auth", "taken").unwrap_err().to_string();
+        assert!(err.contains("already exists"), "{err}");
+        std::env::remove_var("META_DATA_DIR");
+    }
+}
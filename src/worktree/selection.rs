@@ -0,0 +1,176 @@
+//! Selecting several repos with one argument.
+//!
+//! Besides plain aliases, a [`RepoSpec`] can name a glob over project keys
+//! or a tag selector, which [`expand_repo_specs`] expands against the
+//! workspace model:
+//!
+//! - `meta_*` matches top-level repos; `*` and `?` don't cross `/`
+//! - `vendor/**` matches every repo nested under `vendor`
+//! - `@backend` matches every repo tagged `backend`
+//!
+//! A branch or base applies to every matched repo (`@backend:feature-x`). The
+//! expansions are returned so callers can record them in the worktree store.
+
+use anyhow::Result;
+use std::path::Path;
+
+use super::types::{RepoSpec, SpecExpansion};
+use crate::workspace_model::WorkspaceModel;
+
+/// Whether `alias` is a glob or tag selector rather than a single alias.
+pub fn is_selector(alias: &str) -> bool {
+    alias.starts_with('@') || alias.contains(['*', '?'])
+}
+
+/// Replace globs and tag selectors in `specs` with the repos they match,
+/// in key order. Plain aliases are kept as given; a repo selected twice is
+/// kept once, with the branch of its first spec.
+///
+/// Errors if a selector matches nothing.
+pub fn expand_repo_specs(
+    meta_dir: &Path,
+    specs: Vec<RepoSpec>,
+) -> Result<(Vec<RepoSpec>, Vec<SpecExpansion>)> {
+    if !specs.iter().any(|s| is_selector(&s.alias)) {
+        return Ok((specs, Vec::new()));
+    }
+    let model = WorkspaceModel::load(meta_dir)?;
+    let mut expanded: Vec<RepoSpec> = Vec::new();
+    let mut expansions = Vec::new();
+    for spec in specs {
+        if !is_selector(&spec.alias) {
+            if !expanded.iter().any(|s| s.alias == spec.alias) {
+                expanded.push(spec);
+            }
+            continue;
+        }
+        let repos: Vec<String> = model
+            .keys()
+            .into_iter()
+            .filter(|key| match spec.alias.strip_prefix('@') {
+                Some(tag) => model
+                    .lookup(key)
+                    .is_some_and(|(_, info)| info.tags.iter().any(|t| t == tag)),
+                None => glob_match(&spec.alias, key),
+            })
+            .map(str::to_string)
+            .collect();
+        if repos.is_empty() {
+            anyhow::bail!("'{}' matches no repos", spec.alias);
+        }
+        for alias in &repos {
+            if !expanded.iter().any(|s| &s.alias == alias) {
+                expanded.push(RepoSpec {
+                    alias: alias.clone(),
+                    ..spec.clone()
+                });
+            }
+        }
+        expansions.push(SpecExpansion {
+            spec: spec.to_string(),
+            repos,
+        });
+    }
+    Ok((expanded, expansions))
+}
+
+/// Match `text` against `pattern`, where `**` matches anything, `*` any run
+/// of characters other than `/`, and `?` one character other than `/`.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    matches(&pattern, &text)
+}
+
+fn matches(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => {
+            // `a/**/b` also matches `a/b`
+            let rest_after_slash = rest.strip_prefix(&['/']).unwrap_or(rest);
+            (0..=text.len()).any(|i| matches(rest, &text[i..]))
+                || (rest_after_slash.len() < rest.len() && matches(rest_after_slash, text))
+        }
+        ['*', rest @ ..] => {
+            let run = text.iter().take_while(|c| **c != '/').count();
+            (0..=run).any(|i| matches(rest, &text[i..]))
+        }
+        ['?', rest @ ..] => text.first().is_some_and(|c| *c != '/') && matches(rest, &text[1..]),
+        [c, rest @ ..] => text.first() == Some(c) && matches(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_respect_path_separators() {
+        assert!(glob_match("meta_*", "meta_cli"));
+        assert!(!glob_match("meta_*", "meta_cli/plugins"));
+        assert!(glob_match("vendor/**", "vendor/lib"));
+        assert!(glob_match("vendor/**", "vendor/lib/core"));
+        assert!(!glob_match("vendor/**", "vendored"));
+        assert!(glob_match("vendor/**/core", "vendor/core"));
+        assert!(glob_match("api-v?", "api-v2"));
+        assert!(!is_selector("meta_cli") && is_selector("@backend"));
+    }
+
+    #[test]
+    fn expands_globs_and_tags_against_the_workspace() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ws = tmp.path();
+        std::fs::create_dir_all(ws.join("vendor")).unwrap();
+        std::fs::write(
+            ws.join(".meta"),
+            serde_json::json!({"projects": {
+                "meta_cli": {"repo": "git@github.com:org/meta_cli.git", "tags": ["backend"]},
+                "meta_core": "git@github.com:org/meta_core.git",
+                "web": {"repo": "git@github.com:org/web.git", "tags": ["frontend"]},
+                "vendor": "git@github.com:org/vendor.git",
+            }})
+            .to_string(),
+        )
+        .unwrap();
+        std::fs::write(
+            ws.join("vendor/.meta"),
+            serde_json::json!({"projects": {
+                "lib": {"repo": "git@github.com:org/lib.git", "tags": ["backend"]},
+            }})
+            .to_string(),
+        )
+        .unwrap();
+
+        let specs = ["web", "@backend:feature-x", "meta_*"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let (expanded, expansions) = expand_repo_specs(ws, specs).unwrap();
+        let expanded: Vec<String> = expanded.iter().map(RepoSpec::to_string).collect();
+        assert_eq!(
+            expanded,
+            [
+                "web",
+                "meta_cli:feature-x",
+                "vendor/lib:feature-x",
+                "meta_core"
+            ]
+        );
+        assert_eq!(
+            expansions,
+            [
+                SpecExpansion {
+                    spec: "@backend:feature-x".to_string(),
+                    repos: vec!["meta_cli".to_string(), "vendor/lib".to_string()],
+                },
+                SpecExpansion {
+                    spec: "meta_*".to_string(),
+                    repos: vec!["meta_cli".to_string(), "meta_core".to_string()],
+                },
+            ]
+        );
+
+        let unmatched = vec!["@mobile".parse().unwrap()];
+        assert!(expand_repo_specs(ws, unmatched).is_err());
+    }
+}
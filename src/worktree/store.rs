@@ -3,9 +3,12 @@
 //! Manages `~/.meta/worktree.json` — the persistent record of all worktrees.
 
 use anyhow::Result;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 
-use super::types::{StoreRepoEntry, WorktreeStoreData, WorktreeStoreEntry};
+use super::types::{BaseMoved, StoreRepoEntry, WorktreeStoreData, WorktreeStoreEntry};
 
 /// Derive the store key from a worktree path.
 ///
@@ -46,6 +49,7 @@ fn store_paths() -> (PathBuf, PathBuf) {
 
 /// Add a worktree entry to the centralized store.
 pub fn store_add(worktree_path: &Path, entry: WorktreeStoreEntry) -> Result<()> {
+    crate::read_only::check("write the worktree store")?;
     meta_core::data_dir::ensure_meta_dir()?;
     let (data_path, lock_path) = store_paths();
     let key = store_key(worktree_path);
@@ -57,6 +61,7 @@ pub fn store_add(worktree_path: &Path, entry: WorktreeStoreEntry) -> Result<()>
 
 /// Remove a worktree entry from the centralized store.
 pub fn store_remove(worktree_path: &Path) -> Result<()> {
+    crate::read_only::check("write the worktree store")?;
     let (data_path, lock_path) = store_paths();
     if !data_path.exists() {
         return Ok(());
@@ -73,8 +78,58 @@ pub fn store_list() -> Result<WorktreeStoreData> {
     meta_core::store::read(&store_path())
 }
 
+/// Identity of the store file's contents: (path, modified, size).
+type StoreStamp = (PathBuf, Option<SystemTime>, u64);
+
+static STORE_CACHE: Mutex<Option<(StoreStamp, Arc<WorktreeStoreData>)>> = Mutex::new(None);
+
+/// Like [`store_list`], but read again only when the store file changed (by
+/// modification time and size), for lookups made on every command.
+pub(super) fn store_list_cached() -> Result<Arc<WorktreeStoreData>> {
+    let path = store_path();
+    let metadata = std::fs::metadata(&path).ok();
+    let stamp = (
+        path,
+        metadata.as_ref().and_then(|m| m.modified().ok()),
+        metadata.map(|m| m.len()).unwrap_or(0),
+    );
+    let mut cache = STORE_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((cached, store)) = cache.as_ref() {
+        if *cached == stamp {
+            return Ok(store.clone());
+        }
+    }
+    let store = Arc::new(store_list()?);
+    *cache = Some((stamp, store.clone()));
+    Ok(store)
+}
+
+/// Get the store entry of one worktree, if it has one.
+pub fn store_get(worktree_path: &Path) -> Result<Option<WorktreeStoreEntry>> {
+    Ok(store_list()?.worktrees.remove(&store_key(worktree_path)))
+}
+
+/// Entries of the worktrees created from the workspace at `meta_dir`, by
+/// store key, sorted by name.
+pub fn store_entries_for(meta_dir: &Path) -> Result<Vec<(String, WorktreeStoreEntry)>> {
+    let project = meta_dir
+        .canonicalize()
+        .unwrap_or_else(|_| meta_dir.to_path_buf());
+    let mut entries: Vec<_> = store_list()?
+        .worktrees
+        .into_iter()
+        .filter(|(_, e)| {
+            let p = Path::new(&e.project);
+            p == meta_dir || p.canonicalize().is_ok_and(|c| c == project)
+        })
+        .collect();
+    entries.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+    Ok(entries)
+}
+
 /// Add repos to an existing worktree entry in the store.
 pub fn store_extend_repos(worktree_path: &Path, repos: Vec<StoreRepoEntry>) -> Result<()> {
+    crate::read_only::check("write the worktree store")?;
     let (data_path, lock_path) = store_paths();
     let key = store_key(worktree_path);
 
@@ -85,8 +140,78 @@ pub fn store_extend_repos(worktree_path: &Path, repos: Vec<StoreRepoEntry>) -> R
     })
 }
 
+/// Set or clear the base-moved marker on worktree repos in a single lock cycle.
+///
+/// Each update is `(worktree_path, repo_alias, marker)`; unknown worktrees
+/// and repos are ignored.
+pub fn store_set_base_moved(updates: Vec<(PathBuf, String, Option<BaseMoved>)>) -> Result<()> {
+    crate::read_only::check("write the worktree store")?;
+    let (data_path, lock_path) = store_paths();
+    if !data_path.exists() || updates.is_empty() {
+        return Ok(());
+    }
+    let updates: Vec<_> = updates
+        .into_iter()
+        .map(|(path, alias, marker)| (store_key(&path), alias, marker))
+        .collect();
+
+    meta_core::store::update::<WorktreeStoreData, _>(&data_path, &lock_path, move |store| {
+        for (key, alias, marker) in updates {
+            if let Some(repo) = store
+                .worktrees
+                .get_mut(&key)
+                .and_then(|e| e.repos.iter_mut().find(|r| r.alias == alias))
+            {
+                repo.base_moved = marker;
+            }
+        }
+    })
+}
+
+/// Re-register the entry stored under `old_key` for a worktree moved to
+/// `new_path`. Fails if there is no entry under `old_key`.
+pub fn store_rekey(old_key: &str, new_path: &Path) -> Result<()> {
+    crate::read_only::check("write the worktree store")?;
+    let (data_path, lock_path) = store_paths();
+    let new_key = store_key(new_path);
+
+    let mut found = false;
+    meta_core::store::update::<WorktreeStoreData, _>(&data_path, &lock_path, |store| {
+        if let Some(entry) = store.worktrees.remove(old_key) {
+            store.worktrees.insert(new_key, entry);
+            found = true;
+        }
+    })?;
+    if !found {
+        anyhow::bail!("No worktree store entry for {old_key}");
+    }
+    Ok(())
+}
+
+/// Re-register the entry stored under `old_key` as worktree `new_name` at
+/// `new_path`. Fails if there is no entry under `old_key`.
+pub fn store_rename(old_key: &str, new_path: &Path, new_name: &str) -> Result<()> {
+    crate::read_only::check("write the worktree store")?;
+    let (data_path, lock_path) = store_paths();
+    let new_key = store_key(new_path);
+
+    let mut found = false;
+    meta_core::store::update::<WorktreeStoreData, _>(&data_path, &lock_path, |store| {
+        if let Some(mut entry) = store.worktrees.remove(old_key) {
+            entry.name = new_name.to_string();
+            store.worktrees.insert(new_key, entry);
+            found = true;
+        }
+    })?;
+    if !found {
+        anyhow::bail!("No worktree store entry for {old_key}");
+    }
+    Ok(())
+}
+
 /// Remove multiple worktree entries from the store in a single lock cycle.
 pub fn store_remove_batch(keys: &[String]) -> Result<()> {
+    crate::read_only::check("write the worktree store")?;
     let (data_path, lock_path) = store_paths();
     if !data_path.exists() {
         return Ok(());
@@ -99,6 +224,183 @@ pub fn store_remove_batch(keys: &[String]) -> Result<()> {
     })
 }
 
+/// Set custom metadata `key` on a stored worktree, returning the previous value.
+///
+/// Fires the `metadata-changed` hook when the value changes. Errors if the
+/// worktree isn't in the store.
+pub fn set_custom(worktree_path: &Path, key: &str, value: &str) -> Result<Option<String>> {
+    if key.is_empty() {
+        anyhow::bail!("Custom metadata key must not be empty");
+    }
+    change_custom(worktree_path, key, Some(value))
+}
+
+/// Remove custom metadata `key` from a stored worktree, returning the
+/// removed value.
+///
+/// Fires the `metadata-changed` hook if the key was set. Errors if the
+/// worktree isn't in the store.
+pub fn unset_custom(worktree_path: &Path, key: &str) -> Result<Option<String>> {
+    change_custom(worktree_path, key, None)
+}
+
+/// Custom metadata `key` of a stored worktree. Errors if the worktree isn't
+/// in the store.
+pub fn get_custom(worktree_path: &Path, key: &str) -> Result<Option<String>> {
+    let store = store_list()?;
+    match store.worktrees.get(&store_key(worktree_path)) {
+        Some(entry) => Ok(entry.custom.get(key).cloned()),
+        None => Err(not_in_store(worktree_path)),
+    }
+}
+
+/// Whether `entry` has every key of `filter` set to the given value.
+pub fn custom_matches(entry: &WorktreeStoreEntry, filter: &HashMap<String, String>) -> bool {
+    filter
+        .iter()
+        .all(|(key, value)| entry.custom.get(key) == Some(value))
+}
+
+/// Extend the TTL of an ephemeral worktree by `duration`, returning the new
+/// TTL in seconds (counted from creation, like `ttl_seconds`).
+///
+/// Fires the `ttl-changed` hook. Errors if the worktree isn't in the store
+/// or isn't ephemeral.
+pub fn extend_ttl(worktree_path: &Path, duration: Duration) -> Result<u64> {
+    let (key, entry, previous) = update_entry(worktree_path, |entry| {
+        let previous = entry.ttl_seconds;
+        if let (true, Some(ttl)) = (entry.ephemeral, previous) {
+            entry.ttl_seconds = Some(ttl.saturating_add(duration.as_secs()));
+        }
+        previous
+    })?;
+    let Some(ttl) = entry.ttl_seconds.filter(|_| entry.ephemeral) else {
+        anyhow::bail!("Worktree '{}' is not ephemeral", entry.name);
+    };
+    fire_ttl_changed("extend-ttl", &key, &entry, previous);
+    Ok(ttl)
+}
+
+/// Keep an ephemeral worktree: clear its TTL so prune never removes it,
+/// and if it is a live [`EphemeralWorktree`](super::ephemeral::EphemeralWorktree)
+/// of this process, so dropping that doesn't either.
+///
+/// Fires the `ttl-changed` hook if the worktree was ephemeral. Errors if
+/// the worktree isn't in the store.
+pub fn make_persistent(worktree_path: &Path) -> Result<()> {
+    let (key, entry, previous) = update_entry(worktree_path, |entry| {
+        let previous = (entry.ephemeral, entry.ttl_seconds);
+        entry.ephemeral = false;
+        entry.ttl_seconds = None;
+        previous
+    })?;
+    super::ephemeral::disarm(worktree_path);
+    if previous.0 {
+        fire_ttl_changed("make-persistent", &key, &entry, previous.1);
+    }
+    Ok(())
+}
+
+/// Make a worktree ephemeral, expiring `ttl` from now. Returns the new TTL
+/// in seconds (counted from creation, like `ttl_seconds`).
+///
+/// Fires the `ttl-changed` hook. Errors if the worktree isn't in the store
+/// or its creation time can't be parsed.
+pub fn make_ephemeral(worktree_path: &Path, ttl: Duration) -> Result<u64> {
+    let now = chrono::Utc::now().timestamp();
+    let mut age = None;
+    let (key, entry, previous) = update_entry(worktree_path, |entry| {
+        let previous = entry.ttl_seconds;
+        if let Ok(created) = chrono::DateTime::parse_from_rfc3339(&entry.created_at) {
+            let elapsed = u64::try_from(now - created.timestamp()).unwrap_or(0);
+            entry.ephemeral = true;
+            entry.ttl_seconds = Some(elapsed.saturating_add(ttl.as_secs()));
+            age = Some(elapsed);
+        }
+        previous
+    })?;
+    if age.is_none() {
+        anyhow::bail!(
+            "Worktree '{}' has a malformed created_at '{}'",
+            entry.name,
+            entry.created_at
+        );
+    }
+    fire_ttl_changed("make-ephemeral", &key, &entry, previous);
+    Ok(entry.ttl_seconds.unwrap_or_default())
+}
+
+/// Mark a stored worktree protected from prune, or clear the mark. Returns
+/// the previous setting.
+///
+/// Errors if the worktree isn't in the store.
+pub fn set_protected(worktree_path: &Path, protected: bool) -> Result<bool> {
+    let (_, _, previous) = update_entry(worktree_path, |entry| {
+        std::mem::replace(&mut entry.protected, protected)
+    })?;
+    Ok(previous)
+}
+
+fn fire_ttl_changed(action: &str, key: &str, entry: &WorktreeStoreEntry, previous: Option<u64>) {
+    super::hooks::fire_ttl_changed(
+        action,
+        &entry.name,
+        Path::new(key),
+        entry.ephemeral,
+        previous,
+        entry.ttl_seconds,
+        Some(Path::new(&entry.project)),
+    );
+}
+
+fn change_custom(worktree_path: &Path, key: &str, value: Option<&str>) -> Result<Option<String>> {
+    let (store_key, entry, previous) = update_entry(worktree_path, |entry| match value {
+        Some(value) => entry.custom.insert(key.to_string(), value.to_string()),
+        None => entry.custom.remove(key),
+    })?;
+    if previous.as_deref() != value {
+        super::hooks::fire_metadata_changed(
+            &entry.name,
+            Path::new(&store_key),
+            key,
+            previous.as_deref(),
+            value,
+            Some(Path::new(&entry.project)),
+        );
+    }
+    Ok(previous)
+}
+
+/// Apply `f` to the stored entry of `worktree_path` in a single lock cycle.
+///
+/// Returns the store key, the updated entry, and what `f` returned. Errors
+/// if the worktree isn't in the store.
+fn update_entry<R>(
+    worktree_path: &Path,
+    f: impl FnOnce(&mut WorktreeStoreEntry) -> R,
+) -> Result<(String, WorktreeStoreEntry, R)> {
+    crate::read_only::check("write the worktree store")?;
+    let (data_path, lock_path) = store_paths();
+    if !data_path.exists() {
+        return Err(not_in_store(worktree_path));
+    }
+    let key = store_key(worktree_path);
+
+    let mut updated = None;
+    meta_core::store::update::<WorktreeStoreData, _>(&data_path, &lock_path, |store| {
+        if let Some(entry) = store.worktrees.get_mut(&key) {
+            let result = f(entry);
+            updated = Some((entry.clone(), result));
+        }
+    })?;
+    let (entry, result) = updated.ok_or_else(|| not_in_store(worktree_path))?;
+    Ok((key, entry, result))
+}
+
+fn not_in_store(worktree_path: &Path) -> anyhow::Error {
+    anyhow::anyhow!("Worktree {} is not in the store", worktree_path.display())
+}
+
 /// Compute TTL remaining seconds for a store entry.
 /// Returns `None` if no TTL is set. Negative means expired.
 /// On malformed `created_at`, warns and treats as not expired.
@@ -123,18 +425,16 @@ pub fn entry_ttl_remaining(entry: &WorktreeStoreEntry, now_epoch: i64) -> Option
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_fixtures::store_entry;
     use std::collections::HashMap;
 
     fn make_entry(created_at: &str, ttl_seconds: Option<u64>) -> WorktreeStoreEntry {
-        WorktreeStoreEntry {
-            name: "test-wt".to_string(),
-            project: "/tmp/project".to_string(),
-            created_at: created_at.to_string(),
-            ephemeral: ttl_seconds.is_some(),
-            ttl_seconds,
-            repos: vec![],
-            custom: HashMap::new(),
+        let entry = store_entry("test-wt", Path::new("/tmp/project")).created_at(created_at);
+        match ttl_seconds {
+            Some(ttl) => entry.ephemeral(ttl),
+            None => entry,
         }
+        .build()
     }
 
     // ── store_key ───────────────────────────────────────────
@@ -350,6 +650,7 @@ mod tests {
             alias: "repo1".to_string(),
             branch: "main".to_string(),
             created_branch: false,
+            base_moved: None,
         }];
         store_add(&wt_path, entry).unwrap();
 
@@ -358,6 +659,7 @@ mod tests {
             alias: "repo2".to_string(),
             branch: "main".to_string(),
             created_branch: false,
+            base_moved: None,
         }];
         store_extend_repos(&wt_path, new_repos).unwrap();
 
@@ -546,4 +848,128 @@ mod tests {
         std::env::remove_var("META_DATA_DIR");
         // temp_dir cleanup is automatic on drop
     }
+
+    #[test]
+    #[serial_test::serial]
+    #[cfg(unix)]
+    fn custom_metadata_crud_fires_metadata_changed() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store_dir = temp_dir.path().join("meta-store");
+        std::fs::create_dir_all(&store_dir).unwrap();
+        std::env::set_var("META_DATA_DIR", &store_dir);
+
+        let project = temp_dir.path().join("project");
+        std::fs::create_dir(&project).unwrap();
+        let out = temp_dir.path().join("changes.jsonl");
+        std::fs::write(
+            project.join(".meta"),
+            serde_json::json!({
+                "projects": {},
+                "worktree": {"hooks": {"metadata-changed": format!("cat >> {}; echo >> {}", out.display(), out.display())}},
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let wt_path = temp_dir.path().join("meta-wt");
+        std::fs::create_dir(&wt_path).unwrap();
+        let mut entry = make_entry("2025-01-01T00:00:00Z", None);
+        entry.project = project.to_string_lossy().into_owned();
+        store_add(&wt_path, entry).unwrap();
+
+        assert_eq!(set_custom(&wt_path, "ticket", "ENG-1").unwrap(), None);
+        assert_eq!(
+            set_custom(&wt_path, "ticket", "ENG-2").unwrap().as_deref(),
+            Some("ENG-1")
+        );
+        // Unchanged values don't fire the hook
+        set_custom(&wt_path, "ticket", "ENG-2").unwrap();
+        assert_eq!(
+            get_custom(&wt_path, "ticket").unwrap().as_deref(),
+            Some("ENG-2")
+        );
+
+        let stored = store_list().unwrap().worktrees[&store_key(&wt_path)].clone();
+        let filter = HashMap::from([("ticket".to_string(), "ENG-2".to_string())]);
+        assert!(custom_matches(&stored, &filter));
+        assert!(custom_matches(&stored, &HashMap::new()));
+        assert!(!custom_matches(
+            &stored,
+            &HashMap::from([("ticket".to_string(), "ENG-1".to_string())])
+        ));
+
+        assert_eq!(
+            unset_custom(&wt_path, "ticket").unwrap().as_deref(),
+            Some("ENG-2")
+        );
+        assert_eq!(unset_custom(&wt_path, "ticket").unwrap(), None);
+        assert_eq!(get_custom(&wt_path, "ticket").unwrap(), None);
+
+        let changes: Vec<serde_json::Value> = std::fs::read_to_string(&out)
+            .unwrap()
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        let summary: Vec<_> = changes
+            .iter()
+            .map(|c| (c["previous"].clone(), c["value"].clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (serde_json::Value::Null, "ENG-1".into()),
+                ("ENG-1".into(), "ENG-2".into()),
+                ("ENG-2".into(), serde_json::Value::Null),
+            ]
+        );
+        assert_eq!(changes[0]["action"], "metadata-changed");
+        assert_eq!(changes[0]["key"], "ticket");
+        assert_eq!(changes[0]["name"], "test-wt");
+
+        let unknown = temp_dir.path().join("unknown-wt");
+        assert!(set_custom(&unknown, "ticket", "x").is_err());
+        assert!(get_custom(&unknown, "ticket").is_err());
+
+        store_remove(&wt_path).unwrap();
+        std::env::remove_var("META_DATA_DIR");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn ttl_extension_and_conversion() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store_dir = temp_dir.path().join("meta-store");
+        std::fs::create_dir_all(&store_dir).unwrap();
+        std::env::set_var("META_DATA_DIR", &store_dir);
+
+        let wt_path = temp_dir.path().join("ttl-wt");
+        std::fs::create_dir(&wt_path).unwrap();
+        let created = chrono::Utc::now() - chrono::Duration::seconds(100);
+        store_add(&wt_path, make_entry(&created.to_rfc3339(), Some(60))).unwrap();
+        let stored = || store_list().unwrap().worktrees[&store_key(&wt_path)].clone();
+
+        assert_eq!(
+            extend_ttl(&wt_path, Duration::from_secs(3600)).unwrap(),
+            3660
+        );
+
+        make_persistent(&wt_path).unwrap();
+        let entry = stored();
+        assert!(!entry.ephemeral);
+        assert_eq!(entry.ttl_seconds, None);
+        assert!(extend_ttl(&wt_path, Duration::from_secs(60)).is_err());
+
+        // The new TTL counts from now, not from creation
+        let ttl = make_ephemeral(&wt_path, Duration::from_secs(3600)).unwrap();
+        assert!((3700..3710).contains(&ttl), "{ttl}");
+        let entry = stored();
+        assert!(entry.ephemeral);
+        let remaining = entry_ttl_remaining(&entry, chrono::Utc::now().timestamp()).unwrap();
+        assert!((3590..=3600).contains(&remaining), "{remaining}");
+
+        assert!(make_persistent(&temp_dir.path().join("unknown-wt")).is_err());
+
+        store_remove(&wt_path).unwrap();
+        std::env::remove_var("META_DATA_DIR");
+    }
 }
@@ -6,36 +6,117 @@ use std::path::PathBuf;
 
 // ==================== Domain Types ====================
 
-/// A repo specifier: `alias` or `alias:branch`
-#[derive(Debug, Clone)]
+/// A repo specifier: `alias[:branch][@base]` or `alias#PR`
+///
+/// `base` is the ref a new branch starts from (e.g. `origin/release-2.3`);
+/// `pr` starts it from the head of that pull request instead. A leading `@`
+/// is part of the alias (a tag selector, see `worktree::selection`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct RepoSpec {
     pub alias: String,
     pub branch: Option<String>,
+    pub base: Option<String>,
+    pub pr: Option<u32>,
 }
 
 impl std::fmt::Display for RepoSpec {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &self.branch {
-            Some(branch) => write!(f, "{}:{}", self.alias, branch),
-            None => write!(f, "{}", self.alias),
+        write!(f, "{}", self.alias)?;
+        if let Some(branch) = &self.branch {
+            write!(f, ":{branch}")?;
         }
+        if let Some(base) = &self.base {
+            write!(f, "@{base}")?;
+        }
+        if let Some(pr) = self.pr {
+            write!(f, "#{pr}")?;
+        }
+        Ok(())
     }
 }
 
+/// A repo specifier that doesn't follow `alias[:branch][@base]` or
+/// `alias#PR`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepoSpecError {
+    /// `alias:` or `alias@` with nothing after the separator
+    EmptyPart { spec: String, part: &'static str },
+    /// `alias#x` where `x` isn't a pull request number
+    InvalidPr { spec: String },
+    /// `alias:branch#PR` or `alias@base#PR`
+    PrWithBranch { spec: String },
+}
+
+impl std::fmt::Display for RepoSpecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepoSpecError::EmptyPart { spec, part } => {
+                write!(f, "Invalid repo spec '{spec}': empty {part}")
+            }
+            RepoSpecError::InvalidPr { spec } => {
+                write!(
+                    f,
+                    "Invalid repo spec '{spec}': expected a PR number after '#'"
+                )
+            }
+            RepoSpecError::PrWithBranch { spec } => write!(
+                f,
+                "Invalid repo spec '{spec}': a PR spec can't also set a branch or base"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RepoSpecError {}
+
 impl std::str::FromStr for RepoSpec {
-    type Err = std::convert::Infallible;
+    type Err = RepoSpecError;
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        match s.split_once(':') {
-            Some((alias, branch)) => Ok(RepoSpec {
-                alias: alias.to_string(),
-                branch: Some(branch.to_string()),
-            }),
-            None => Ok(RepoSpec {
-                alias: s.to_string(),
-                branch: None,
-            }),
+        // Keep a tag selector's leading '@' out of the base split
+        let tag_prefix = if s.starts_with('@') { 1 } else { 0 };
+        let (prefix, body) = s.split_at(tag_prefix);
+        let non_empty = |value: &str, part| {
+            if value.is_empty() {
+                Err(RepoSpecError::EmptyPart {
+                    spec: s.to_string(),
+                    part,
+                })
+            } else {
+                Ok(value.to_string())
+            }
+        };
+
+        if let Some((alias, pr)) = body.split_once('#') {
+            if alias.contains([':', '@']) {
+                return Err(RepoSpecError::PrWithBranch {
+                    spec: s.to_string(),
+                });
+            }
+            let pr = pr.parse().map_err(|_| RepoSpecError::InvalidPr {
+                spec: s.to_string(),
+            })?;
+            return Ok(RepoSpec {
+                alias: format!("{prefix}{alias}"),
+                pr: Some(pr),
+                ..Default::default()
+            });
         }
+
+        let (head, base) = match body.split_once('@') {
+            Some((head, base)) => (head, Some(non_empty(base, "base")?)),
+            None => (body, None),
+        };
+        let (alias, branch) = match head.split_once(':') {
+            Some((alias, branch)) => (alias, Some(non_empty(branch, "branch")?)),
+            None => (head, None),
+        };
+        Ok(RepoSpec {
+            alias: format!("{prefix}{alias}"),
+            branch,
+            base,
+            pr: None,
+        })
     }
 }
 
@@ -45,6 +126,7 @@ impl From<&CreateRepoEntry> for StoreRepoEntry {
             alias: r.alias.clone(),
             branch: r.branch.clone(),
             created_branch: r.created_branch,
+            base_moved: None,
         }
     }
 }
@@ -52,6 +134,7 @@ impl From<&CreateRepoEntry> for StoreRepoEntry {
 // ==================== Internal Types ====================
 
 /// Resolved worktree context for operations that need meta_dir, worktree_root, and worktree path.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct WorktreeContext {
     pub meta_dir: Option<PathBuf>,
     pub worktree_root: PathBuf,
@@ -78,6 +161,24 @@ pub struct WorktreeStoreEntry {
     pub repos: Vec<StoreRepoEntry>,
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub custom: HashMap<String, String>,
+    /// Cross-repo change this worktree belongs to (see `change_group`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub change_group: Option<String>,
+    /// Never removed by prune, whatever its TTL
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub protected: bool,
+    /// Globs and tag selectors the repos were chosen with
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expansions: Vec<SpecExpansion>,
+}
+
+/// A glob or tag selector and the repos it matched (see
+/// `worktree::selection`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpecExpansion {
+    /// The selector as given, e.g. `vendor/**` or `@backend:feature`
+    pub spec: String,
+    pub repos: Vec<String>,
 }
 
 /// Repo entry within a store entry.
@@ -86,77 +187,141 @@ pub struct StoreRepoEntry {
     pub alias: String,
     pub branch: String,
     pub created_branch: bool,
+    /// Set when the repo's base branch gained commits since the worktree
+    /// branched off (see `worktree::base_sync`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_moved: Option<BaseMoved>,
+}
+
+/// The base branch moved ahead of a worktree repo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BaseMoved {
+    /// Base ref, e.g. `origin/main`
+    pub base: String,
+    /// Commits on the base that the worktree branch doesn't have
+    pub commits: u32,
+    pub detected_at: String,
 }
 
 // ==================== JSON Output Structures ====================
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CreateOutput {
     pub name: String,
     pub root: String,
     pub repos: Vec<CreateRepoEntry>,
-    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub ephemeral: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub ttl_seconds: Option<u64>,
-    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub custom: HashMap<String, String>,
+    /// Change group id for commits made in the worktree (see `change_group`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub change_group: Option<String>,
+    /// Requested repos left out because they are disabled for worktrees
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disabled: Vec<String>,
+    /// Globs and tag selectors and the repos they matched
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expansions: Vec<SpecExpansion>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CreateRepoEntry {
     pub alias: String,
     pub path: String,
     pub branch: String,
     pub created_branch: bool,
+    /// Setup commands run in the new worktree (with `setup_in_worktrees`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub setup: Vec<crate::setup::SetupStepResult>,
+}
+
+/// A repo a worktree is about to be created with, for the `pre-create`
+/// payload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedRepoEntry {
+    pub alias: String,
+    pub path: String,
+    pub branch: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ListOutput {
     pub worktrees: Vec<ListEntry>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct AddOutput {
     pub name: String,
     pub repos: Vec<CreateRepoEntry>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DestroyOutput {
     pub name: String,
     pub path: String,
     pub repos_removed: usize,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct ListEntry {
     pub name: String,
     pub root: String,
     pub has_meta_root: bool,
     pub repos: Vec<ListRepoEntry>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub ephemeral: Option<bool>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub ttl_remaining_seconds: Option<i64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protected: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub custom: Option<HashMap<String, String>>,
+    /// Total size of the worktree directory in bytes (skipped with `--fast`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_usage_bytes: Option<u64>,
+    /// Seconds since the worktree was created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub age_seconds: Option<i64>,
+    /// Most recent commit across the worktree's repos (RFC 3339, skipped with `--fast`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_activity: Option<String>,
+    /// Pull requests opened from the worktree's branches (skipped with `--fast`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pull_requests: Vec<ListPrEntry>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListPrEntry {
+    pub alias: String,
+    pub number: u64,
+    pub state: String,
+    pub title: String,
+    pub url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ListRepoEntry {
     pub alias: String,
     pub branch: String,
     pub dirty: bool,
+    /// Commits the base branch moved ahead by, when a rebase is due
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_moved_by: Option<u32>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct StatusOutput {
     pub name: String,
     pub repos: Vec<StatusRepoEntry>,
+    /// Projects left out because they are disabled for status
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disabled: Vec<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StatusRepoEntry {
     pub alias: String,
     pub path: String,
@@ -166,11 +331,33 @@ pub struct StatusRepoEntry {
     pub untracked_count: usize,
     pub ahead: u32,
     pub behind: u32,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub modified_files: Vec<String>,
+    /// Renamed or copied files among `modified_files`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub renames: Vec<RenameEntry>,
+    /// Untracked paths, when asked for; untracked directories are listed
+    /// once, with a trailing `/`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub untracked_files: Vec<String>,
+    /// When remote data was last fetched (RFC 3339); ahead/behind is only as fresh as this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_fetched: Option<String>,
+}
+
+/// A file git detected as renamed or copied.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenameEntry {
+    pub from: String,
+    pub to: String,
+    /// Similarity of the two files, 0 to 100
+    pub similarity: u8,
+    /// A copy rather than a rename; `from` still exists
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub copy: bool,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DiffOutput {
     pub name: String,
     pub base: String,
@@ -178,18 +365,21 @@ pub struct DiffOutput {
     pub totals: DiffTotals,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DiffRepoEntry {
     pub alias: String,
     pub base_ref: String,
     pub files_changed: usize,
     pub insertions: usize,
     pub deletions: usize,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub files: Vec<String>,
+    /// Renamed or copied files among `files`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub renames: Vec<RenameEntry>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DiffTotals {
     pub repos_changed: usize,
     pub files_changed: usize,
@@ -197,28 +387,112 @@ pub struct DiffTotals {
     pub deletions: usize,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct PruneOutput {
     pub removed: Vec<PruneEntry>,
     pub dry_run: bool,
+    /// Expired worktrees kept because of uncommitted or unpushed work
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub refused: Vec<PruneEntry>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PruneEntry {
     pub name: String,
     pub path: String,
     pub reason: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub age_seconds: Option<u64>,
 }
 
+/// Work in a worktree that no remote has (see `worktree::unpushed`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UnpushedOutput {
+    pub name: String,
+    pub root: String,
+    /// Repos with unpushed commits; repos with none are left out
+    pub repos: Vec<UnpushedRepoEntry>,
+}
+
+impl UnpushedOutput {
+    /// Whether nothing would be lost by deleting the worktree's branches.
+    pub fn is_empty(&self) -> bool {
+        self.repos.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnpushedRepoEntry {
+    pub alias: String,
+    pub branches: Vec<UnpushedBranch>,
+}
+
+impl UnpushedRepoEntry {
+    /// Unpushed commits across the repo's branches, counting shared ones once.
+    pub fn commit_count(&self) -> usize {
+        let mut shas: Vec<&str> = self
+            .branches
+            .iter()
+            .flat_map(|b| b.commits.iter().map(|c| c.sha.as_str()))
+            .collect();
+        shas.sort_unstable();
+        shas.dedup();
+        shas.len()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnpushedBranch {
+    /// Branch name, or `HEAD` for a detached checkout
+    pub name: String,
+    /// Created with the worktree, per the store
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub created: bool,
+    /// Newest first
+    pub commits: Vec<UnpushedCommit>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnpushedCommit {
+    pub sha: String,
+    pub subject: String,
+}
+
 // ==================== Git Status ====================
 
-/// Combined git status summary from a single `git status --porcelain` call.
+/// Combined git status summary from a single `git status --porcelain=v2` call.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct GitStatusSummary {
     pub dirty: bool,
     pub modified_files: Vec<String>,
     pub untracked_count: usize,
+    pub untracked_files: Vec<String>,
+    pub renames: Vec<RenameEntry>,
+}
+
+/// Whether a path in a repo is ignored, and by which rule.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IgnoreCheck {
+    /// The repo the path is in
+    pub alias: String,
+    /// The path relative to that repo
+    pub path: String,
+    pub ignored: bool,
+    /// The last matching rule; a `!pattern` when it un-ignores the path.
+    /// `None` when no rule matches (or the path is tracked, which no
+    /// ignore rule affects)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<crate::git_output::IgnoreRule>,
+}
+
+/// Combined `git diff --numstat` and rename detection against a base ref.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GitDiffSummary {
+    pub files_changed: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+    pub files: Vec<String>,
+    pub renames: Vec<RenameEntry>,
 }
 
 #[cfg(test)]
@@ -262,7 +536,7 @@ mod tests {
     fn repo_spec_display_alias_only() {
         let spec = RepoSpec {
             alias: "meta_cli".to_string(),
-            branch: None,
+            ..Default::default()
         };
         assert_eq!(spec.to_string(), "meta_cli");
     }
@@ -272,6 +546,7 @@ mod tests {
         let spec = RepoSpec {
             alias: "meta_cli".to_string(),
             branch: Some("feature-x".to_string()),
+            ..Default::default()
         };
         assert_eq!(spec.to_string(), "meta_cli:feature-x");
     }
@@ -292,6 +567,50 @@ mod tests {
         assert_eq!(spec.to_string(), input);
     }
 
+    // ── RepoSpec base refs and PRs ──────────────────────────
+
+    #[test]
+    fn repo_spec_parse_base_and_pr() {
+        let spec: RepoSpec = "api:featY@origin/release-2.3".parse().unwrap();
+        assert_eq!(spec.alias, "api");
+        assert_eq!(spec.branch.as_deref(), Some("featY"));
+        assert_eq!(spec.base.as_deref(), Some("origin/release-2.3"));
+
+        let spec: RepoSpec = "api@v1.2.0".parse().unwrap();
+        assert!(spec.branch.is_none());
+        assert_eq!(spec.base.as_deref(), Some("v1.2.0"));
+
+        let spec: RepoSpec = "web#42".parse().unwrap();
+        assert_eq!((spec.alias.as_str(), spec.pr), ("web", Some(42)));
+
+        let spec: RepoSpec = "@backend:feat@main".parse().unwrap();
+        assert_eq!(spec.alias, "@backend");
+        assert_eq!(spec.base.as_deref(), Some("main"));
+
+        for input in [
+            "api:featY@origin/release-2.3",
+            "web#42",
+            "@backend:feat@main",
+        ] {
+            assert_eq!(input.parse::<RepoSpec>().unwrap().to_string(), input);
+        }
+    }
+
+    #[test]
+    fn repo_spec_rejects_malformed_specs() {
+        assert!(matches!(
+            "web#abc".parse::<RepoSpec>(),
+            Err(RepoSpecError::InvalidPr { .. })
+        ));
+        assert!(matches!(
+            "web:feat#42".parse::<RepoSpec>(),
+            Err(RepoSpecError::PrWithBranch { .. })
+        ));
+        let err = "api:".parse::<RepoSpec>().unwrap_err();
+        assert_eq!(err.to_string(), "Invalid repo spec 'api:': empty branch");
+        assert!("api@".parse::<RepoSpec>().is_err());
+    }
+
     // ── StoreRepoEntry From<CreateRepoEntry> ────────────────
 
     #[test]
@@ -301,6 +620,7 @@ mod tests {
             path: "/tmp/lib".to_string(),
             branch: "main".to_string(),
             created_branch: true,
+            setup: Vec::new(),
         };
         let store: StoreRepoEntry = StoreRepoEntry::from(&create);
         assert_eq!(store.alias, "lib");
@@ -315,6 +635,7 @@ mod tests {
             path: "/tmp/app".to_string(),
             branch: "develop".to_string(),
             created_branch: false,
+            setup: Vec::new(),
         };
         let store: StoreRepoEntry = StoreRepoEntry::from(&create);
         assert!(!store.created_branch);
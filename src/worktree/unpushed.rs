@@ -0,0 +1,241 @@
+//! Finding work in a worktree that exists nowhere else.
+//!
+//! Deleting a worktree (and the branches it created) loses every commit no
+//! remote-tracking branch contains. [`unpushed`] reports those commits per
+//! repo and branch: the checked-out branch, plus the branch the store
+//! recorded for the repo, which may differ after a switch;
+//! [`all_branches_unpushed`] checks every local branch. They back the
+//! destroy and prune safety checks, [`crate::verify`], and a standalone
+//! report.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::process::Command;
+
+use super::placement::find_worktree;
+use super::store::store_get;
+use super::types::{UnpushedBranch, UnpushedCommit, UnpushedOutput, UnpushedRepoEntry};
+
+/// Unpushed commits in worktree `name`.
+pub fn unpushed(meta_dir: Option<&Path>, name: &str) -> Result<UnpushedOutput> {
+    let root =
+        find_worktree(meta_dir, name)?.with_context(|| format!("Worktree '{name}' not found"))?;
+    unpushed_at(&root, name)
+}
+
+/// Unpushed commits in the worktree at `root`.
+pub fn unpushed_at(root: &Path, name: &str) -> Result<UnpushedOutput> {
+    // (alias, branch recorded in the store, whether the worktree created it)
+    let repos: Vec<(String, Option<String>, bool)> = match store_get(root)? {
+        Some(entry) => entry
+            .repos
+            .into_iter()
+            .map(|r| (r.alias, Some(r.branch), r.created_branch))
+            .collect(),
+        None => meta_cli::worktree::discover_worktree_repos(root)?
+            .into_iter()
+            .map(|r| (r.alias, None, false))
+            .collect(),
+    };
+
+    let mut output = UnpushedOutput {
+        name: name.to_string(),
+        root: root.to_string_lossy().into_owned(),
+        repos: Vec::new(),
+    };
+    for (alias, branch, created) in repos {
+        let path = if alias == "." {
+            root.to_path_buf()
+        } else {
+            root.join(&alias)
+        };
+        if !path.exists() {
+            continue;
+        }
+        let created = created.then_some(branch.as_deref()).flatten();
+        let branches = repo_unpushed(&path, branch.as_deref(), created)?;
+        if !branches.is_empty() {
+            output.repos.push(UnpushedRepoEntry { alias, branches });
+        }
+    }
+    Ok(output)
+}
+
+/// Unpushed commits on the checked-out branch of the repo at `path` and on
+/// `branch`, if given and different. Branches with none are left out;
+/// `created` marks the branch the worktree created.
+pub fn repo_unpushed(
+    path: &Path,
+    branch: Option<&str>,
+    created: Option<&str>,
+) -> Result<Vec<UnpushedBranch>> {
+    let mut names = vec![current_branch(path).unwrap_or_else(|| "HEAD".to_string())];
+    if let Some(branch) = branch.filter(|b| !names.iter().any(|n| n == b)) {
+        names.push(branch.to_string());
+    }
+    branches_unpushed(path, names, created)
+}
+
+/// Unpushed commits on every local branch of the repo at `path`, and on
+/// `HEAD` if it's detached. Branches with none are left out.
+pub fn all_branches_unpushed(path: &Path) -> Result<Vec<UnpushedBranch>> {
+    let output = crate::git_runner::output(
+        Command::new("git")
+            .args(["for-each-ref", "--format=%(refname:short)", "refs/heads"])
+            .current_dir(path),
+    )
+    .context("Failed to run git for-each-ref")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git for-each-ref failed in {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let mut names: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect();
+    if current_branch(path).is_none() {
+        names.push("HEAD".to_string());
+    }
+    branches_unpushed(path, names, None)
+}
+
+/// Unpushed commits on each of the local branches `names` (or `HEAD`) of
+/// the repo at `path`. Branches with none, or that don't exist, are left
+/// out; `created` marks the branch the worktree created.
+pub fn branches_unpushed(
+    path: &Path,
+    names: Vec<String>,
+    created: Option<&str>,
+) -> Result<Vec<UnpushedBranch>> {
+    let mut branches = Vec::new();
+    for name in names {
+        let Some(commits) = commits_on_no_remote(path, &name)? else {
+            continue;
+        };
+        if !commits.is_empty() {
+            branches.push(UnpushedBranch {
+                created: created == Some(name.as_str()),
+                name,
+                commits,
+            });
+        }
+    }
+    Ok(branches)
+}
+
+/// The branch checked out in the repo at `path`; `None` if `HEAD` is
+/// detached.
+pub(super) fn current_branch(path: &Path) -> Option<String> {
+    let output = crate::git_runner::output(
+        Command::new("git")
+            .args(["symbolic-ref", "--quiet", "--short", "HEAD"])
+            .current_dir(path),
+    )
+    .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Commits reachable from local branch `branch` (or `HEAD`) but from no
+/// remote-tracking branch. `None` if it doesn't exist (e.g. a deleted
+/// branch).
+fn commits_on_no_remote(path: &Path, branch: &str) -> Result<Option<Vec<UnpushedCommit>>> {
+    // Qualified, so a branch can't be taken for a tag, a revision range or
+    // an option
+    let rev = if branch == "HEAD" {
+        branch.to_string()
+    } else {
+        format!("refs/heads/{branch}")
+    };
+    let exists = crate::git_runner::output(
+        Command::new("git")
+            .args(["rev-parse", "--verify", "--quiet", "--end-of-options"])
+            .arg(format!("{rev}^{{commit}}"))
+            .current_dir(path),
+    )
+    .context("Failed to run git rev-parse")?;
+    if !exists.status.success() {
+        return Ok(None);
+    }
+    let output = crate::git_runner::output(
+        Command::new("git")
+            .args(["log", "--format=%H%x09%s", "--end-of-options", &rev])
+            .args(["--not", "--remotes", "--"])
+            .current_dir(path),
+    )
+    .context("Failed to run git log")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git log failed in {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let commits = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (sha, subject) = line.split_once('\t')?;
+            Some(UnpushedCommit {
+                sha: sha.to_string(),
+                subject: subject.to_string(),
+            })
+        })
+        .collect();
+    Ok(Some(commits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{git, repo as init_repo};
+
+    #[test]
+    fn reports_commits_no_remote_has_per_branch() {
+        let tmp = tempfile::tempdir().unwrap();
+        let origin = tmp.path().join("origin");
+        init_repo(&origin, &[]);
+        let repo = tmp.path().join("api");
+        git(
+            tmp.path(),
+            &["clone", "-q", origin.to_str().unwrap(), "api"],
+        );
+
+        assert!(repo_unpushed(&repo, None, None).unwrap().is_empty());
+
+        git(&repo, &["checkout", "-q", "-b", "feature"]);
+        git(&repo, &["commit", "-q", "--allow-empty", "-m", "first"]);
+        git(&repo, &["commit", "-q", "--allow-empty", "-m", "second"]);
+        git(&repo, &["checkout", "-q", "-b", "spike"]);
+        git(&repo, &["commit", "-q", "--allow-empty", "-m", "third"]);
+
+        let branches = repo_unpushed(&repo, Some("feature"), Some("feature")).unwrap();
+        let summary: Vec<_> = branches
+            .iter()
+            .map(|b| {
+                let subjects: Vec<&str> = b.commits.iter().map(|c| c.subject.as_str()).collect();
+                (b.name.as_str(), b.created, subjects)
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("spike", false, vec!["third", "second", "first"]),
+                ("feature", true, vec!["second", "first"]),
+            ]
+        );
+        let entry = UnpushedRepoEntry {
+            alias: "api".to_string(),
+            branches,
+        };
+        assert_eq!(entry.commit_count(), 3);
+
+        // Branches that no longer exist are skipped
+        assert_eq!(repo_unpushed(&repo, Some("gone"), None).unwrap().len(), 1);
+    }
+}